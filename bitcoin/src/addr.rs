@@ -1,10 +1,24 @@
 use crate::{
-    secp256k1::SecretKey, Address, ConversionError, Error, Hash, Network, Payload, PubkeyHash, Script, ScriptHash,
-    WPubkeyHash, WScriptHash,
+    secp256k1::{self, constants::PUBLIC_KEY_SIZE, Secp256k1, SecretKey},
+    Address, ConversionError, Error, Hash, Network, Payload, PubkeyHash, PublicKey, Script, ScriptHash, WPubkeyHash,
+    WScriptHash,
 };
+use lazy_static::lazy_static;
+use prometheus::{IntCounterVec, Opts};
 use sp_core::H160;
 use std::str::FromStr;
 
+lazy_static! {
+    pub static ref REJECTED_PAYMENTS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "rejected_payments",
+            "Number of payments rejected before broadcast because of the destination address"
+        ),
+        &["reason"]
+    )
+    .expect("Failed to create metric");
+}
+
 pub trait PartialAddress: Sized + Eq + PartialOrd {
     /// Decode the `PartialAddress` from the `Payload` type.
     ///
@@ -23,6 +37,9 @@ pub trait PartialAddress: Sized + Eq + PartialOrd {
     /// # Arguments
     /// * `network` - network to prefix
     fn encode_str(&self, network: Network) -> Result<String, ConversionError>;
+
+    /// Get the output script paying to the `PartialAddress`.
+    fn to_script(&self) -> Result<Script, ConversionError>;
 }
 
 #[cfg(feature = "interbtc")]
@@ -47,16 +64,19 @@ impl PartialAddress for interbtc_bitcoin::Address {
     }
 
     fn encode_str(&self, network: Network) -> Result<String, ConversionError> {
-        let script = match self {
+        let script = self.to_script()?;
+        let payload = Payload::from_script(&script).ok_or(ConversionError::InvalidPayload)?;
+        let address = Address { payload, network };
+        Ok(address.to_string())
+    }
+
+    fn to_script(&self) -> Result<Script, ConversionError> {
+        Ok(match self {
             Self::P2PKH(hash) => Script::new_p2pkh(&PubkeyHash::from_slice(hash.as_bytes())?),
             Self::P2SH(hash) => Script::new_p2sh(&ScriptHash::from_slice(hash.as_bytes())?),
             Self::P2WPKHv0(hash) => Script::new_v0_wpkh(&WPubkeyHash::from_slice(hash.as_bytes())?),
             Self::P2WSHv0(hash) => Script::new_v0_wsh(&WScriptHash::from_slice(hash.as_bytes())?),
-        };
-
-        let payload = Payload::from_script(&script).ok_or(ConversionError::InvalidPayload)?;
-        let address = Address { payload, network };
-        Ok(address.to_string())
+        })
     }
 }
 
@@ -77,6 +97,72 @@ impl PartialAddress for Payload {
         };
         Ok(address.to_string())
     }
+
+    fn to_script(&self) -> Result<Script, ConversionError> {
+        Ok(self.script_pubkey())
+    }
+}
+
+/// Networks whose addresses share the encoding, e.g. testnet addresses are valid on regtest
+/// except for the bech32 prefix.
+fn is_compatible_network(address: &Address, network: Network) -> bool {
+    match (address.network, network) {
+        (Network::Bitcoin, Network::Bitcoin) => true,
+        (Network::Bitcoin, _) | (_, Network::Bitcoin) => false,
+        // base58 addresses are decoded as testnet, also on regtest
+        (Network::Testnet, _) => true,
+        (decoded, network) => decoded == network,
+    }
+}
+
+/// Label under which a payment rejected with the error is counted.
+fn rejection_reason(err: &Error) -> &'static str {
+    match err {
+        Error::AddressNetworkMismatch { .. } => "network_mismatch",
+        Error::UnsupportedAddressType(_) => "unsupported_type",
+        _ => "invalid_address",
+    }
+}
+
+fn reject(destination: &str, err: Error) -> Error {
+    REJECTED_PAYMENTS.with_label_values(&[rejection_reason(&err)]).inc();
+    log::warn!("Rejected payment to {}: {}", destination, err);
+    err
+}
+
+fn is_supported_script(script: &Script) -> bool {
+    script.is_p2pkh() || script.is_p2sh() || script.is_v0_p2wpkh() || script.is_v0_p2wsh()
+}
+
+fn check_address(address: &str, network: Network) -> Result<Address, Error> {
+    let parsed = Address::from_str(address).map_err(ConversionError::from)?;
+    if !is_compatible_network(&parsed, network) {
+        return Err(Error::AddressNetworkMismatch {
+            address: address.to_string(),
+            expected: network,
+            found: parsed.network,
+        });
+    }
+    if !is_supported_script(&parsed.script_pubkey()) {
+        return Err(Error::UnsupportedAddressType(address.to_string()));
+    }
+    Ok(parsed)
+}
+
+/// Check that the address can be paid to on the given network, so that a mismatch is reported
+/// as such instead of as an error of bitcoind. Rejections are counted by reason.
+pub fn validate_address(address: &str, network: Network) -> Result<Address, Error> {
+    check_address(address, network).map_err(|err| reject(address, err))
+}
+
+/// Check that the script paid to is of a supported type, e.g. that of an address given by a
+/// redeemer, which carries no network. Rejections are counted by reason.
+pub fn validate_script(script: &Script) -> Result<(), Error> {
+    if is_supported_script(script) {
+        return Ok(());
+    }
+    let script = format!("{:x}", script);
+    Err(reject(&script, Error::UnsupportedAddressType(script.clone())))
 }
 
 pub fn calculate_deposit_secret_key(vault_key: SecretKey, issue_key: SecretKey) -> Result<SecretKey, Error> {
@@ -85,6 +171,28 @@ pub fn calculate_deposit_secret_key(vault_key: SecretKey, issue_key: SecretKey)
     Ok(deposit_key)
 }
 
+/// Derive the deposit address for the master public key and public secret independently of
+/// the wallet, as the parachain does: the P2WPKH address of the master public key
+/// multiplied by the secret. The deposit key derived by the wallet belongs to this address.
+///
+/// # Arguments
+/// * `public_key` - master public key of the vault
+/// * `secret_key` - public secret of the deposit (derived from the request id)
+pub fn calculate_deposit_address<P: Into<[u8; PUBLIC_KEY_SIZE]>>(
+    public_key: P,
+    secret_key: &[u8],
+) -> Result<Payload, Error> {
+    let mut deposit_key = secp256k1::PublicKey::from_slice(&public_key.into())?;
+    deposit_key.mul_assign(&Secp256k1::verification_only(), secret_key)?;
+    let deposit_key = PublicKey {
+        compressed: true,
+        key: deposit_key,
+    };
+    // the network only affects the encoding of the address
+    let address = Address::p2wpkh(&deposit_key, Network::Bitcoin).map_err(ConversionError::from)?;
+    Ok(address.payload)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,6 +209,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate_address() {
+        let regtest = "bcrt1q6v2c7q7uv8vu6xle2k9ryfj3y3fuuy4rqnl50f";
+        let testnet = "2N8hwP1WmJrFF5QWABn38y63uYLhnJYJYTF";
+        let mainnet = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let reason = |address, network| check_address(address, network).map_err(|err| rejection_reason(&err));
+
+        assert!(reason(regtest, Network::Regtest).is_ok());
+        assert!(reason(testnet, Network::Regtest).is_ok());
+        assert!(reason(mainnet, Network::Bitcoin).is_ok());
+        assert_eq!(reason(regtest, Network::Testnet).unwrap_err(), "network_mismatch");
+        assert_eq!(reason(testnet, Network::Bitcoin).unwrap_err(), "network_mismatch");
+        assert_eq!(reason(mainnet, Network::Regtest).unwrap_err(), "network_mismatch");
+        assert_eq!(
+            reason("bcrt1qinvalid", Network::Regtest).unwrap_err(),
+            "invalid_address"
+        );
+
+        // witness version 1 is not supported yet
+        let taproot = Payload::WitnessProgram {
+            version: bitcoincore_rpc::bitcoin::bech32::u5::try_from_u8(1).unwrap(),
+            program: vec![0; 32],
+        };
+        assert_eq!(
+            reason(&taproot.encode_str(Network::Regtest).unwrap(), Network::Regtest).unwrap_err(),
+            "unsupported_type"
+        );
+    }
+
+    #[test]
+    fn test_validate_script() {
+        assert!(validate_script(&Script::new_v0_wpkh(&WPubkeyHash::hash(&[1; 33]))).is_ok());
+        assert!(validate_script(&Script::new_p2sh(&ScriptHash::hash(&[1; 20]))).is_ok());
+
+        // a witness program of unsupported length
+        let payload = Payload::WitnessProgram {
+            version: bitcoincore_rpc::bitcoin::bech32::u5::try_from_u8(0).unwrap(),
+            program: vec![0; 25],
+        };
+        let address = Address {
+            payload,
+            network: Network::Regtest,
+        };
+        assert!(matches!(
+            validate_script(&address.script_pubkey()),
+            Err(Error::UnsupportedAddressType(_))
+        ));
+    }
+
     #[test]
     fn test_calculate_deposit_secret_key() {
         let secp = Secp256k1::new();
@@ -126,5 +283,17 @@ mod tests {
             deposit_public_key,
             PublicKey::from_secret_key(&secp, &deposit_secret_key)
         );
+
+        // the address derived from the public keys belongs to the derived secret key
+        let deposit_address = calculate_deposit_address(vault_public_key.serialize(), &secret_key[..]).unwrap();
+        let expected = Address::p2wpkh(
+            &crate::PublicKey {
+                compressed: true,
+                key: PublicKey::from_secret_key(&secp, &deposit_secret_key),
+            },
+            Network::Regtest,
+        )
+        .unwrap();
+        assert_eq!(deposit_address, expected.payload);
     }
 }
@@ -0,0 +1,275 @@
+use crate::{
+    json, Address, Amount, Auth, BitcoinError, Block, BlockHash, BlockHeader, JsonRpcError, OutPoint, PrivateKey,
+    RpcError, Transaction, Txid,
+};
+use bitcoincore_rpc::bitcoin::{consensus::encode::deserialize, hashes::hex::FromHex};
+use hyper::Error as HyperError;
+use reqwest::header::CONTENT_TYPE;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Maximum number of idle connections kept open to bitcoind.
+const POOL_MAX_IDLE: usize = 32;
+
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// Timeout of the requests if the connection profile sets none, so that a hung bitcoind
+/// does not stall the hot paths forever. Calls that may take longer (e.g. rescans) are
+/// made with the blocking client.
+pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Result<T> = std::result::Result<T, BitcoinError>;
+
+#[derive(Deserialize)]
+struct Response {
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+/// Async JSON-RPC client for the subset of the bitcoind API on the hot paths (blocks,
+/// transactions, proofs and the mempool). Unlike the blocking `bitcoincore_rpc::Client`,
+/// requests do not occupy a thread while waiting for bitcoind, so many can be in flight.
+/// Errors are mapped to the same `bitcoincore_rpc::Error` variants as those of the blocking
+/// client, so that they are classified in the same way.
+pub(crate) struct AsyncClient {
+    url: String,
+    user: Option<String>,
+    pass: Option<String>,
+    http: reqwest::Client,
+    next_id: AtomicU64,
+}
+
+/// Map a transport error to the error the blocking client returns in the same situation.
+fn transport_error(err: reqwest::Error) -> BitcoinError {
+    let kind = if err.is_connect() {
+        IoErrorKind::ConnectionRefused
+    } else if err.is_timeout() {
+        IoErrorKind::TimedOut
+    } else {
+        IoErrorKind::Other
+    };
+    BitcoinError::JsonRpc(JsonRpcError::Hyper(HyperError::Io(IoError::new(kind, err))))
+}
+
+fn from_hex<T: bitcoincore_rpc::bitcoin::consensus::Decodable>(hex: &str) -> Result<T> {
+    Ok(deserialize(&Vec::<u8>::from_hex(hex)?)?)
+}
+
+impl AsyncClient {
+    pub(crate) fn new(url: String, auth: Auth, request_timeout: Option<Duration>) -> Result<Self> {
+        let (user, pass) = auth.get_user_pass()?;
+        let http = reqwest::Client::builder()
+            .pool_max_idle_per_host(POOL_MAX_IDLE)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .timeout(request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT))
+            .build()
+            .map_err(transport_error)?;
+        Ok(Self {
+            url,
+            user,
+            pass,
+            http,
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) async fn call<T: DeserializeOwned>(&self, method: &str, params: &[Value]) -> Result<T> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request_body = serde_json::to_vec(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+        let mut request = self
+            .http
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(request_body);
+        if let Some(ref user) = self.user {
+            request = request.basic_auth(user, self.pass.as_ref());
+        }
+        let body = request
+            .send()
+            .await
+            .map_err(transport_error)?
+            .bytes()
+            .await
+            .map_err(transport_error)?;
+        // bitcoind responds with an error status and a JSON body to failed calls, and with an
+        // empty body to unauthorized requests, which fails to decode like for the blocking client
+        let response: Response =
+            serde_json::from_slice(&body).map_err(|err| BitcoinError::JsonRpc(JsonRpcError::Json(err)))?;
+        match (response.error, response.result) {
+            (Some(err), _) => Err(BitcoinError::JsonRpc(JsonRpcError::Rpc(err))),
+            (None, result) => serde_json::from_value(result.unwrap_or(Value::Null))
+                .map_err(|err| BitcoinError::JsonRpc(JsonRpcError::Json(err))),
+        }
+    }
+
+    pub(crate) async fn get_block_count(&self) -> Result<u64> {
+        self.call("getblockcount", &[]).await
+    }
+
+    pub(crate) async fn get_best_block_hash(&self) -> Result<BlockHash> {
+        self.call("getbestblockhash", &[]).await
+    }
+
+    pub(crate) async fn get_block_hash(&self, height: u64) -> Result<BlockHash> {
+        self.call("getblockhash", &[height.into()]).await
+    }
+
+    pub(crate) async fn get_block(&self, hash: &BlockHash) -> Result<Block> {
+        let hex: String = self.call("getblock", &[serde_json::to_value(hash)?, 0.into()]).await?;
+        from_hex(&hex)
+    }
+
+    pub(crate) async fn get_block_info(&self, hash: &BlockHash) -> Result<json::GetBlockResult> {
+        self.call("getblock", &[serde_json::to_value(hash)?, 1.into()]).await
+    }
+
+    pub(crate) async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader> {
+        let hex: String = self
+            .call("getblockheader", &[serde_json::to_value(hash)?, false.into()])
+            .await?;
+        from_hex(&hex)
+    }
+
+    pub(crate) async fn get_raw_transaction(&self, txid: &Txid, block_hash: Option<&BlockHash>) -> Result<Transaction> {
+        let mut params = vec![serde_json::to_value(txid)?, false.into()];
+        if let Some(block_hash) = block_hash {
+            params.push(serde_json::to_value(block_hash)?);
+        }
+        let hex: String = self.call("getrawtransaction", &params).await?;
+        from_hex(&hex)
+    }
+
+    pub(crate) async fn get_tx_out_proof(&self, txids: &[Txid], block_hash: Option<&BlockHash>) -> Result<Vec<u8>> {
+        let mut params = vec![serde_json::to_value(txids)?];
+        if let Some(block_hash) = block_hash {
+            params.push(serde_json::to_value(block_hash)?);
+        }
+        let hex: String = self.call("gettxoutproof", &params).await?;
+        Ok(Vec::<u8>::from_hex(&hex)?)
+    }
+
+    pub(crate) async fn send_raw_transaction(&self, transaction: &Transaction) -> Result<Txid> {
+        let hex = bitcoincore_rpc::bitcoin::consensus::encode::serialize_hex(transaction);
+        self.call("sendrawtransaction", &[hex.into()]).await
+    }
+
+    pub(crate) async fn get_blockchain_info(&self) -> Result<json::GetBlockchainInfoResult> {
+        self.call("getblockchaininfo", &[]).await
+    }
+
+    pub(crate) async fn get_raw_mempool(&self) -> Result<Vec<Txid>> {
+        self.call("getrawmempool", &[]).await
+    }
+
+    pub(crate) async fn get_transaction(&self, txid: &Txid) -> Result<json::GetTransactionResult> {
+        self.call("gettransaction", &[serde_json::to_value(txid)?]).await
+    }
+
+    pub(crate) async fn sign_raw_transaction_with_wallet(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<json::SignRawTransactionResult> {
+        let hex = bitcoincore_rpc::bitcoin::consensus::encode::serialize_hex(transaction);
+        self.call("signrawtransactionwithwallet", &[hex.into()]).await
+    }
+
+    pub(crate) async fn get_new_address(&self, address_type: json::AddressType) -> Result<Address> {
+        self.call("getnewaddress", &["".into(), serde_json::to_value(address_type)?])
+            .await
+    }
+
+    pub(crate) async fn get_address_info(&self, address: &Address) -> Result<json::GetAddressInfoResult> {
+        self.call("getaddressinfo", &[address.to_string().into()]).await
+    }
+
+    pub(crate) async fn dump_private_key(&self, address: &Address) -> Result<PrivateKey> {
+        self.call("dumpprivkey", &[address.to_string().into()]).await
+    }
+
+    /// Import the key without rescanning the chain, which could exceed the request timeout.
+    pub(crate) async fn import_private_key(&self, private_key: &PrivateKey) -> Result<()> {
+        self.call(
+            "importprivkey",
+            &[private_key.to_string().into(), "".into(), false.into()],
+        )
+        .await
+    }
+
+    pub(crate) async fn fund_raw_transaction(
+        &self,
+        hex: &str,
+        options: &json::FundRawTransactionOptions,
+    ) -> Result<json::FundRawTransactionResult> {
+        self.call("fundrawtransaction", &[hex.into(), serde_json::to_value(options)?])
+            .await
+    }
+
+    pub(crate) async fn decode_raw_transaction(&self, hex: &str) -> Result<json::DecodeRawTransactionResult> {
+        self.call("decoderawtransaction", &[hex.into()]).await
+    }
+
+    pub(crate) async fn unlock_unspent(&self, outpoints: &[OutPoint]) -> Result<bool> {
+        let outpoints = outpoints
+            .iter()
+            .map(|outpoint| json!({ "txid": outpoint.txid, "vout": outpoint.vout }))
+            .collect::<Vec<_>>();
+        self.call("lockunspent", &[true.into(), outpoints.into()]).await
+    }
+
+    pub(crate) async fn get_received_by_address(&self, address: &Address, min_conf: Option<u32>) -> Result<Amount> {
+        let mut params = vec![address.to_string().into()];
+        if let Some(min_conf) = min_conf {
+            params.push(min_conf.into());
+        }
+        let btc: f64 = self.call("getreceivedbyaddress", &params).await?;
+        Ok(Amount::from_btc(btc)?)
+    }
+
+    pub(crate) async fn list_wallets(&self) -> Result<Vec<String>> {
+        self.call("listwallets", &[]).await
+    }
+
+    pub(crate) async fn list_transactions(
+        &self,
+        count: usize,
+        skip: usize,
+    ) -> Result<Vec<json::ListTransactionResult>> {
+        self.call("listtransactions", &["*".into(), count.into(), skip.into()])
+            .await
+    }
+
+    pub(crate) async fn generate_to_address(&self, blocks: u64, address: &Address) -> Result<Vec<BlockHash>> {
+        self.call("generatetoaddress", &[blocks.into(), address.to_string().into()])
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_response() {
+        let response: Response = serde_json::from_str(
+            r#"{"result":null,"error":{"code":-5,"message":"No such mempool transaction"},"id":1}"#,
+        )
+        .unwrap();
+        assert_eq!(response.error.unwrap().code, -5);
+
+        let response: Response = serde_json::from_str(r#"{"result":700000,"error":null,"id":2}"#).unwrap();
+        assert_eq!(
+            serde_json::from_value::<u64>(response.result.unwrap()).unwrap(),
+            700_000
+        );
+    }
+}
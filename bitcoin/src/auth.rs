@@ -0,0 +1,160 @@
+use crate::{async_rpc::AsyncClient, Auth, Client, Error};
+use bitcoincore_rpc::jsonrpc::client::Client as JsonRpcClient;
+use hyper::Client as HyperClient;
+use log::{info, warn};
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+/// RPC clients that are rebuilt when the credentials change. Bitcoind writes a new
+/// `.cookie` file on every restart, so with cookie authentication the file is
+/// re-read whenever it is modified.
+pub(crate) struct ReloadingClient {
+    url: String,
+    state: RwLock<ClientState>,
+}
+
+struct ClientState {
+    auth: Auth,
+    client: Arc<Client>,
+    async_client: Arc<AsyncClient>,
+    /// Timeout of the requests of both clients. If not set, the async client uses
+    /// `DEFAULT_REQUEST_TIMEOUT` and the blocking client none.
+    request_timeout: Option<Duration>,
+    /// Modification time of the cookie file when the client was built.
+    cookie_modified: Option<SystemTime>,
+}
+
+impl ClientState {
+    fn new(url: &str, auth: Auth, request_timeout: Option<Duration>) -> Result<Self, Error> {
+        Ok(Self {
+            cookie_modified: cookie_modified(&auth),
+            client: Arc::new(blocking_client(url, auth.clone(), request_timeout)?),
+            async_client: Arc::new(AsyncClient::new(url.to_string(), auth.clone(), request_timeout)?),
+            request_timeout,
+            auth,
+        })
+    }
+}
+
+/// Build the blocking client, with the timeout on reading and writing its connections if set.
+fn blocking_client(url: &str, auth: Auth, request_timeout: Option<Duration>) -> Result<Client, Error> {
+    let request_timeout = match request_timeout {
+        Some(request_timeout) => request_timeout,
+        None => return Ok(Client::new(url.to_string(), auth)?),
+    };
+    let (user, pass) = auth.get_user_pass()?;
+    let mut http = HyperClient::new();
+    http.set_read_timeout(Some(request_timeout));
+    http.set_write_timeout(Some(request_timeout));
+    Ok(Client::from_jsonrpc(JsonRpcClient::with_client(
+        url.to_string(),
+        user,
+        pass,
+        http,
+    )))
+}
+
+fn cookie_modified(auth: &Auth) -> Option<SystemTime> {
+    match auth {
+        Auth::CookieFile(path) => modified(path),
+        _ => None,
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl ReloadingClient {
+    pub(crate) fn new(url: String, auth: Auth) -> Result<Self, Error> {
+        let state = ClientState::new(&url, auth, None)?;
+        Ok(Self {
+            url,
+            state: RwLock::new(state),
+        })
+    }
+
+    /// Get the current blocking client, reloading the cookie first if bitcoind has rotated it.
+    pub(crate) fn get(&self) -> Arc<Client> {
+        self.current(|state| state.client.clone())
+    }
+
+    /// Get the current async client, reloading the cookie first if bitcoind has rotated it.
+    pub(crate) fn get_async(&self) -> Arc<AsyncClient> {
+        self.current(|state| state.async_client.clone())
+    }
+
+    fn current<T, F: Fn(&ClientState) -> T>(&self, get: F) -> T {
+        let (client, rotated) = {
+            let state = self.state.read().expect("poisoned");
+            let current = cookie_modified(&state.auth);
+            // the cookie is deleted on shutdown, keep using the old one until it is recreated
+            let rotated = current.is_some() && current != state.cookie_modified;
+            (get(&state), rotated)
+        };
+        if !rotated {
+            return client;
+        }
+        info!("Bitcoin RPC cookie changed, reloading credentials");
+        match self.reload() {
+            Ok(()) => get(&self.state.read().expect("poisoned")),
+            Err(err) => {
+                warn!("Failed to reload bitcoin RPC credentials: {}", err);
+                client
+            }
+        }
+    }
+
+    /// Rebuild the client from the current credentials, re-reading the cookie file if used.
+    pub(crate) fn reload(&self) -> Result<(), Error> {
+        let auth = self.state.read().expect("poisoned").auth.clone();
+        self.set_auth(auth)
+    }
+
+    /// Replace the credentials used for all subsequent requests.
+    pub(crate) fn set_auth(&self, auth: Auth) -> Result<(), Error> {
+        let request_timeout = self.state.read().expect("poisoned").request_timeout;
+        let state = ClientState::new(&self.url, auth, request_timeout)?;
+        *self.state.write().expect("poisoned") = state;
+        Ok(())
+    }
+
+    /// Rebuild the clients with a timeout on their requests.
+    pub(crate) fn set_request_timeout(&self, request_timeout: Option<Duration>) -> Result<(), Error> {
+        let auth = self.state.read().expect("poisoned").auth.clone();
+        let state = ClientState::new(&self.url, auth, request_timeout)?;
+        *self.state.write().expect("poisoned") = state;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, thread, time::Duration};
+
+    #[test]
+    fn test_reload_on_cookie_rotation() {
+        let path = env::temp_dir().join(format!("bitcoin-auth-test-{}.cookie", std::process::id()));
+        fs::write(&path, "__cookie__:first").unwrap();
+
+        let client =
+            ReloadingClient::new("http://localhost:18443".to_string(), Auth::CookieFile(path.clone())).unwrap();
+        let first = client.get();
+        assert!(Arc::ptr_eq(&first, &client.get()));
+
+        // ensure the modification time differs on filesystems with coarse timestamps
+        thread::sleep(Duration::from_millis(1100));
+        fs::write(&path, "__cookie__:second").unwrap();
+        assert!(!Arc::ptr_eq(&first, &client.get()));
+
+        // keep the last client while bitcoind is restarting
+        fs::remove_file(&path).unwrap();
+        let second = client.get();
+        assert!(Arc::ptr_eq(&second, &client.get()));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{btc_to_sat, Error};
+use serde::{Deserialize, Serialize};
+
+/// Balances of the wallet by confirmation status, in satoshis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct WalletBalances {
+    /// Confirmed outputs and unconfirmed outputs created by the wallet itself.
+    pub trusted: u64,
+    /// Unconfirmed outputs received from others.
+    pub untrusted_pending: u64,
+    /// Coinbase outputs that have not matured yet.
+    pub immature: u64,
+}
+
+impl WalletBalances {
+    /// Balance including unconfirmed and immature outputs.
+    pub fn total(&self) -> u64 {
+        self.trusted
+            .saturating_add(self.untrusted_pending)
+            .saturating_add(self.immature)
+    }
+}
+
+/// Response of `getbalances`, watch-only balances are not included.
+#[derive(Deserialize)]
+pub(crate) struct GetBalancesResult {
+    mine: GetBalancesResultEntry,
+}
+
+#[derive(Deserialize)]
+struct GetBalancesResultEntry {
+    trusted: f64,
+    untrusted_pending: f64,
+    immature: f64,
+}
+
+impl GetBalancesResult {
+    pub(crate) fn into_balances(self) -> Result<WalletBalances, Error> {
+        Ok(WalletBalances {
+            trusted: btc_to_sat(self.mine.trusted)?,
+            untrusted_pending: btc_to_sat(self.mine.untrusted_pending)?,
+            immature: btc_to_sat(self.mine.immature)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_balances() {
+        let json = serde_json::json!({
+            "mine": {
+                "trusted": 1.5,
+                "untrusted_pending": 0.001,
+                "immature": 0.0
+            }
+        });
+        let balances = serde_json::from_value::<GetBalancesResult>(json)
+            .unwrap()
+            .into_balances()
+            .unwrap();
+        assert_eq!(
+            balances,
+            WalletBalances {
+                trusted: 150_000_000,
+                untrusted_pending: 100_000,
+                immature: 0,
+            }
+        );
+        assert_eq!(balances.total(), 150_100_000);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{format_btc, ConversionError, Network, PartialAddress};
+
+/// Percent-encode all but the unreserved characters of RFC 3986.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Build a BIP21 payment URI, e.g. to be shown as QR code to the user paying an issue request.
+///
+/// # Arguments
+/// * `address` - address to pay to
+/// * `network` - network to encode the address for
+/// * `amount` - exact amount to pay in satoshis
+/// * `label` - label for the payment, e.g. the issue id
+pub fn payment_uri<A: PartialAddress>(
+    address: &A,
+    network: Network,
+    amount: u64,
+    label: Option<&str>,
+) -> Result<String, ConversionError> {
+    let mut uri = format!("bitcoin:{}?amount={}", address.encode_str(network)?, format_btc(amount));
+    if let Some(label) = label {
+        uri.push_str("&label=");
+        uri.push_str(&percent_encode(label));
+    }
+    Ok(uri)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Payload;
+
+    #[test]
+    fn test_payment_uri() {
+        let address = Payload::decode_str("bcrt1q6v2c7q7uv8vu6xle2k9ryfj3y3fuuy4rqnl50f").unwrap();
+        assert_eq!(
+            payment_uri(&address, Network::Regtest, 150_000_000, Some("issue #1")).unwrap(),
+            "bitcoin:bcrt1q6v2c7q7uv8vu6xle2k9ryfj3y3fuuy4rqnl50f?amount=1.5&label=issue%20%231"
+        );
+        assert_eq!(
+            payment_uri(&address, Network::Regtest, 1, None).unwrap(),
+            "bitcoin:bcrt1q6v2c7q7uv8vu6xle2k9ryfj3y3fuuy4rqnl50f?amount=0.00000001"
+        );
+        assert_eq!(format_btc(200_000_000), "2");
+    }
+}
@@ -1,7 +1,10 @@
-use crate::{BitcoinCore, Error};
+use crate::{
+    BitcoinCore, BroadcastChannel, ConnectionProfile, Error, FeeEstimateMode, FeeEstimation, HeaderStore,
+    LockTimePolicy, MaxFeeRate, TransactionLimits, TransactionPolicy,
+};
 use bitcoincore_rpc::{bitcoin::Network, Auth};
 use clap::Clap;
-use std::{str::FromStr, time::Duration};
+use std::{path::PathBuf, str::FromStr, time::Duration};
 
 #[derive(Debug, Copy, Clone)]
 pub struct BitcoinNetwork(pub Network);
@@ -23,33 +26,161 @@ pub struct BitcoinOpts {
     #[clap(long, env = "BITCOIN_RPC_URL")]
     pub bitcoin_rpc_url: String,
 
-    #[clap(long, env = "BITCOIN_RPC_USER")]
-    pub bitcoin_rpc_user: String,
+    #[clap(long, env = "BITCOIN_RPC_USER", required_unless_present = "bitcoin-rpc-cookie")]
+    pub bitcoin_rpc_user: Option<String>,
 
-    #[clap(long, env = "BITCOIN_RPC_PASS")]
-    pub bitcoin_rpc_pass: String,
+    #[clap(long, env = "BITCOIN_RPC_PASS", required_unless_present = "bitcoin-rpc-cookie")]
+    pub bitcoin_rpc_pass: Option<String>,
+
+    /// Path to the `.cookie` file of bitcoind, used instead of user and password.
+    /// The file is re-read whenever bitcoind rotates it, and on SIGHUP.
+    #[clap(long, env = "BITCOIN_RPC_COOKIE", conflicts_with_all = &["bitcoin-rpc-user", "bitcoin-rpc-pass"])]
+    pub bitcoin_rpc_cookie: Option<PathBuf>,
 
     /// Timeout in milliseconds to wait for connection to bitcoin-core.
     #[clap(long, default_value = "60000")]
     pub bitcoin_connection_timeout_ms: u64,
 
-    /// Bitcoin network type for address encoding.
-    #[clap(long, default_value = "regtest")]
-    pub network: BitcoinNetwork,
+    /// Use the longer timeouts and retry delays suited to a connection over Tor, e.g. to
+    /// bitcoind behind a hidden service. Implied if the url is a .onion address.
+    #[clap(long)]
+    pub bitcoin_tor: bool,
+
+    /// Bitcoin network type for address encoding (mainnet, testnet or regtest), defaults to
+    /// that of the network profile or regtest.
+    #[clap(long)]
+    pub bitcoin_network: Option<BitcoinNetwork>,
+
+    /// Locktime of created transactions, either `current-height` (anti-fee-sniping) or `zero`.
+    #[clap(long, default_value = "current-height")]
+    pub bitcoin_lock_time_policy: LockTimePolicy,
+
+    /// Signal replace-by-fee (BIP125) on created transactions.
+    #[clap(long)]
+    pub bitcoin_replaceable: bool,
+
+    /// Maximum fee rate (in sat/vbyte) of broadcast transactions, overriding bitcoind's
+    /// default. Enforced locally as well, must not exceed 100000 sat/vbyte.
+    #[clap(long)]
+    pub bitcoin_max_fee_rate: Option<u64>,
+
+    /// Fee estimation mode of created transactions, either `economical` or `conservative`.
+    /// Sweeps are always estimated economically. If unset, bitcoind's default is used.
+    #[clap(long)]
+    pub bitcoin_fee_estimate_mode: Option<FeeEstimateMode>,
+
+    /// Fee estimation mode of payments with a deadline, e.g. for redeem requests, which risk
+    /// missing it if underpaid. If unset, `--bitcoin-fee-estimate-mode` applies.
+    #[clap(long)]
+    pub bitcoin_deadline_fee_estimate_mode: Option<FeeEstimateMode>,
+
+    /// Number of blocks within which created transactions should confirm, used to estimate
+    /// their fee. If unset, bitcoind's `-txconfirmtarget` is used.
+    #[clap(long)]
+    pub bitcoin_conf_target: Option<u16>,
+
+    /// Maximum virtual size of created transactions, must not exceed the standard
+    /// limit of 100000 vbytes.
+    #[clap(long, default_value = "100000")]
+    pub bitcoin_max_tx_vsize: u64,
+
+    /// Maximum number of inputs of created transactions, to bound the size of the
+    /// transaction proofs submitted to the parachain.
+    #[clap(long, default_value = "250")]
+    pub bitcoin_max_tx_inputs: usize,
+
+    /// Channel to which transactions are broadcast in addition to bitcoind, either
+    /// `bitcoind:<url>`, `esplora:<url>` or `relay:<url>`. Can be specified multiple times.
+    #[clap(long)]
+    pub bitcoin_broadcast_channel: Vec<BroadcastChannel>,
+
+    /// Number of block headers and final block hashes kept in memory, so that they are
+    /// only fetched from bitcoind once. Zero disables the cache.
+    #[clap(long, default_value = "2016")]
+    pub bitcoin_header_cache_size: usize,
+
+    /// File in which the headers of blocks deeper than the maximum reorg depth are kept
+    /// across restarts. If unset, headers are only cached in memory.
+    #[clap(long)]
+    pub bitcoin_header_store: Option<PathBuf>,
+
+    /// File in which the transactions sent by the vault are kept across restarts, so that
+    /// they are not mistaken for spends by others. If unset, they are only kept in memory.
+    #[clap(long)]
+    pub bitcoin_sent_transactions_file: Option<PathBuf>,
+
+    /// File to which the fee rate and the mempool conditions of every broadcast are appended
+    /// as JSON lines, to review the fee bumps of payments. If unset, they are not recorded.
+    #[clap(long)]
+    pub bitcoin_fee_history_file: Option<PathBuf>,
 }
 
 impl BitcoinOpts {
+    /// The configured bitcoin network, regtest if unset.
+    pub fn network(&self) -> Network {
+        self.bitcoin_network.map_or(Network::Regtest, |network| network.0)
+    }
+
     fn new_auth(&self) -> Auth {
-        Auth::UserPass(self.bitcoin_rpc_user.clone(), self.bitcoin_rpc_pass.clone())
+        match self.bitcoin_rpc_cookie {
+            Some(ref path) => Auth::CookieFile(path.clone()),
+            None => Auth::UserPass(
+                self.bitcoin_rpc_user.clone().unwrap_or_default(),
+                self.bitcoin_rpc_pass.clone().unwrap_or_default(),
+            ),
+        }
+    }
+
+    fn connection_profile(&self) -> ConnectionProfile {
+        if self.bitcoin_tor {
+            ConnectionProfile::Tor
+        } else {
+            ConnectionProfile::for_url(&self.bitcoin_rpc_url)
+        }
+    }
+
+    fn header_store(&self) -> Result<HeaderStore, Error> {
+        let header_store = HeaderStore::new(self.bitcoin_header_cache_size);
+        match &self.bitcoin_header_store {
+            Some(path) => header_store.with_file(path, self.network()),
+            None => Ok(header_store),
+        }
     }
 
     pub fn new_client(&self, wallet_name: Option<String>) -> Result<BitcoinCore, Error> {
+        let max_fee_rate = self.bitcoin_max_fee_rate.map(MaxFeeRate::new).transpose()?;
+        let fee_estimation = FeeEstimation::new(self.bitcoin_fee_estimate_mode, self.bitcoin_conf_target)?;
+        let deadline_fee_estimation = FeeEstimation::new(self.bitcoin_deadline_fee_estimate_mode, None)?;
+        let transaction_limits = TransactionLimits::new(self.bitcoin_max_tx_vsize, self.bitcoin_max_tx_inputs)?;
+        let header_store = self.header_store()?;
         BitcoinCore::new(
             self.bitcoin_rpc_url.clone(),
             self.new_auth(),
             wallet_name,
-            self.network.0,
+            self.network(),
             Duration::from_millis(self.bitcoin_connection_timeout_ms),
         )
+        .and_then(|bitcoin_core| bitcoin_core.with_connection_profile(self.connection_profile()))
+        .map(|bitcoin_core| {
+            bitcoin_core
+                .with_transaction_policy(TransactionPolicy {
+                    lock_time: self.bitcoin_lock_time_policy,
+                    replaceable: self.bitcoin_replaceable,
+                })
+                .with_max_fee_rate(max_fee_rate)
+                .with_fee_estimation(fee_estimation)
+                .with_deadline_fee_estimation(deadline_fee_estimation)
+                .with_transaction_limits(transaction_limits)
+                .with_broadcast_channels(self.bitcoin_broadcast_channel.clone())
+                .with_header_store(header_store)
+        })
+        .map(|bitcoin_core| match &self.bitcoin_fee_history_file {
+            Some(path) => bitcoin_core.with_fee_history_file(path.clone()),
+            None => bitcoin_core,
+        })
+        .and_then(|bitcoin_core| match &self.bitcoin_sent_transactions_file {
+            Some(path) => bitcoin_core.with_sent_transactions_file(path.clone()),
+            None => Ok(bitcoin_core),
+        })
     }
 }
@@ -0,0 +1,245 @@
+use crate::{
+    auth::ReloadingClient, malleability::normalized_txid, BlockHash, GetTransactionResult, RpcApi, Txid, WalletTxInfo,
+};
+use futures::channel::oneshot;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::delay_for;
+
+/// Interval at which the tip is polled while transactions are watched.
+const TIP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Txid under which a transaction was confirmed, which differs from the watched one if
+/// the transaction was malleated, and the height and hash of the block containing it.
+pub(crate) type Inclusion = (Txid, u32, BlockHash);
+
+struct Waiter {
+    num_confirmations: u32,
+    sender: oneshot::Sender<Inclusion>,
+}
+
+#[derive(Default)]
+struct State {
+    waiters: HashMap<Txid, Vec<Waiter>>,
+    /// Set while the polling task runs, it stops once nothing is watched.
+    running: bool,
+    /// Set when a transaction is added, so that it is checked without waiting for a block.
+    added: bool,
+}
+
+impl State {
+    /// Notify the waiters whose target is reached by the given number of confirmations,
+    /// and drop those that are no longer awaited.
+    fn confirm(&mut self, txid: &Txid, confirmations: u32, inclusion: Inclusion) {
+        let waiters = match self.waiters.remove(txid) {
+            Some(waiters) => waiters,
+            None => return,
+        };
+        let remaining = waiters
+            .into_iter()
+            .filter_map(|waiter| {
+                if waiter.sender.is_canceled() {
+                    None
+                } else if confirmations >= waiter.num_confirmations {
+                    let _ = waiter.sender.send(inclusion);
+                    None
+                } else {
+                    Some(waiter)
+                }
+            })
+            .collect::<Vec<_>>();
+        if !remaining.is_empty() {
+            self.waiters.insert(*txid, remaining);
+        }
+    }
+
+    fn prune_canceled(&mut self) {
+        for waiters in self.waiters.values_mut() {
+            waiters.retain(|waiter| !waiter.sender.is_canceled());
+        }
+        self.waiters.retain(|_, waiters| !waiters.is_empty());
+    }
+}
+
+/// Tracks the confirmations of many wallet transactions with a single polling task. The
+/// confirmations are only fetched when the tip changes (or a transaction is added), so the
+/// load on bitcoind does not depend on the time the payments take to confirm.
+#[derive(Clone)]
+pub(crate) struct ConfirmationWatcher {
+    client: Arc<ReloadingClient>,
+    state: Arc<Mutex<State>>,
+}
+
+impl ConfirmationWatcher {
+    pub(crate) fn new(client: Arc<ReloadingClient>) -> Self {
+        Self {
+            client,
+            state: Default::default(),
+        }
+    }
+
+    /// Wait until the transaction has the given number of confirmations.
+    pub(crate) async fn wait(&self, txid: Txid, num_confirmations: u32) -> Inclusion {
+        let (sender, receiver) = oneshot::channel();
+        let start = {
+            let mut state = self.state.lock().expect("poisoned");
+            state.waiters.entry(txid).or_default().push(Waiter {
+                num_confirmations,
+                sender,
+            });
+            state.added = true;
+            !std::mem::replace(&mut state.running, true)
+        };
+        if start {
+            tokio::spawn(self.clone().poll());
+        }
+        // the sender is only dropped once the target is reached
+        receiver.await.expect("sender dropped")
+    }
+
+    /// Find a confirmed wallet transaction conflicting with the given one that is a malleated
+    /// version of it, i.e. spends the same outputs to the same outputs under another txid.
+    fn find_malleated(&self, result: &GetTransactionResult) -> Option<(u32, Inclusion)> {
+        let normalized = normalized_txid(&result.transaction().ok()?);
+        result.info.wallet_conflicts.iter().find_map(|conflict| {
+            let conflict = self.client.get().get_transaction(conflict, None).ok()?;
+            let is_malleated = normalized_txid(&conflict.transaction().ok()?) == normalized;
+            match conflict.info {
+                WalletTxInfo {
+                    confirmations,
+                    blockhash: Some(hash),
+                    blockheight: Some(height),
+                    txid,
+                    ..
+                } if confirmations > 0 && is_malleated => Some((confirmations as u32, (txid, height, hash))),
+                _ => None,
+            }
+        })
+    }
+
+    /// Fetch the tip and, if it changed or a transaction was added, the confirmations of the
+    /// watched transactions. Returns the tip to compare against in the next round. The rpc
+    /// client is blocking, so this is run off the runtime.
+    fn check(&self, mut tip: Option<BlockHash>, added: bool) -> Option<BlockHash> {
+        let txids = match self.client.get().get_best_block_hash() {
+            Ok(hash) if tip == Some(hash) && !added => Vec::new(),
+            Ok(hash) => {
+                tip = Some(hash);
+                let state = self.state.lock().expect("poisoned");
+                state.waiters.keys().copied().collect()
+            }
+            Err(err) => {
+                log::warn!("Failed to get the tip while watching confirmations: {}", err);
+                // check all transactions once bitcoind is reachable again
+                tip = None;
+                Vec::new()
+            }
+        };
+
+        for txid in txids {
+            match self.client.get().get_transaction(&txid, None) {
+                Ok(GetTransactionResult {
+                    info:
+                        WalletTxInfo {
+                            confirmations,
+                            blockhash: Some(hash),
+                            blockheight: Some(height),
+                            ..
+                        },
+                    ..
+                }) if confirmations > 0 => {
+                    self.state
+                        .lock()
+                        .expect("poisoned")
+                        .confirm(&txid, confirmations as u32, (txid, height, hash));
+                }
+                // conflicted, possibly by a malleated version of the transaction
+                Ok(result) if result.info.confirmations < 0 => {
+                    if let Some((confirmations, inclusion)) = self.find_malleated(&result) {
+                        self.state
+                            .lock()
+                            .expect("poisoned")
+                            .confirm(&txid, confirmations, inclusion);
+                    }
+                }
+                // unconfirmed
+                Ok(_) => {}
+                Err(err) => log::warn!("Failed to get the confirmations of {}: {}", txid, err),
+            }
+        }
+        tip
+    }
+
+    async fn poll(self) {
+        let mut tip = None;
+        loop {
+            let added = {
+                let mut state = self.state.lock().expect("poisoned");
+                state.prune_canceled();
+                // checked under the lock, so that transactions added later restart the task
+                if state.waiters.is_empty() {
+                    state.running = false;
+                    return;
+                }
+                std::mem::replace(&mut state.added, false)
+            };
+
+            let watcher = self.clone();
+            tip = match tokio::task::spawn_blocking(move || watcher.check(tip, added)).await {
+                Ok(tip) => tip,
+                Err(err) => {
+                    log::warn!("Failed to check the confirmations of the watched transactions: {}", err);
+                    None
+                }
+            };
+
+            delay_for(TIP_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Hash;
+
+    #[test]
+    fn test_waiters_are_notified_at_their_target() {
+        let txid = Txid::from_slice(&[1; 32]).unwrap();
+        let inclusion = (txid, 100, BlockHash::from_slice(&[2; 32]).unwrap());
+        let mut state = State::default();
+        let (one_sender, mut one_receiver) = oneshot::channel();
+        let (six_sender, mut six_receiver) = oneshot::channel();
+        let (canceled_sender, canceled_receiver) = oneshot::channel();
+        state.waiters.insert(
+            txid,
+            vec![
+                Waiter {
+                    num_confirmations: 1,
+                    sender: one_sender,
+                },
+                Waiter {
+                    num_confirmations: 6,
+                    sender: six_sender,
+                },
+                Waiter {
+                    num_confirmations: 1,
+                    sender: canceled_sender,
+                },
+            ],
+        );
+        drop(canceled_receiver);
+
+        state.confirm(&txid, 3, inclusion);
+        assert_eq!(one_receiver.try_recv().unwrap(), Some(inclusion));
+        assert_eq!(six_receiver.try_recv().unwrap(), None);
+        assert_eq!(state.waiters[&txid].len(), 1);
+
+        state.confirm(&txid, 6, inclusion);
+        assert_eq!(six_receiver.try_recv().unwrap(), Some(inclusion));
+        assert!(state.waiters.is_empty());
+    }
+}
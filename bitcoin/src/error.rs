@@ -5,6 +5,7 @@ use bitcoincore_rpc::{
         hashes::Error as HashesError,
         secp256k1::Error as Secp256k1Error,
         util::{address::Error as AddressError, key::Error as KeyError},
+        Network,
     },
     jsonrpc::{error::RpcError, Error as JsonRpcError},
 };
@@ -13,7 +14,7 @@ use hyper::Error as HyperError;
 use serde_json::Error as SerdeJsonError;
 use std::io::ErrorKind as IoErrorKind;
 use thiserror::Error;
-use tokio::time::Elapsed;
+use tokio::{task::JoinError, time::Elapsed};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -33,6 +34,12 @@ pub enum Error {
     KeyError(#[from] KeyError),
     #[error("Timeout: {0}")]
     TimeElapsed(#[from] Elapsed),
+    #[error("JoinError: {0}")]
+    JoinError(#[from] JoinError),
+    #[error("ReqwestError: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
 
     #[error("Could not confirm transaction")]
     ConfirmationError,
@@ -50,9 +57,60 @@ pub enum Error {
     WalletNotFound,
     #[error("Invalid Bitcoin network")]
     InvalidBitcoinNetwork,
+    #[error("Previous output not found")]
+    PrevoutNotFound,
+    #[error("Spending from the wallet has been halted")]
+    SpendsHalted,
+    #[error("Spending is fenced, another instance is the leader")]
+    SpendsFenced,
+    #[error("Invalid merkle proof: {0}")]
+    InvalidProof(&'static str),
+    #[error("Invalid locktime policy")]
+    InvalidLockTimePolicy,
+    #[error("Invalid fee estimate mode, expected economical or conservative")]
+    InvalidFeeEstimateMode,
+    #[error("Invalid confirmation target {0}, must be between 1 and 1008 blocks")]
+    InvalidConfTarget(u16),
+    #[error("Invalid maximum fee rate {0} sat/vbyte")]
+    InvalidMaxFeeRate(u64),
+    #[error("Fee of {fee} sat for {vsize} vbytes exceeds the maximum fee rate of {max_fee_rate} sat/vbyte")]
+    FeeRateTooHigh { fee: u64, vsize: u64, max_fee_rate: u64 },
+    #[error("Transaction would exceed the mempool package limits: {0}")]
+    MempoolChainTooLong(&'static str),
+    #[error("Invalid broadcast channel {0}, expected bitcoind:<url>, esplora:<url> or relay:<url>")]
+    InvalidBroadcastChannel(String),
+    #[error("Broadcast rejected by {0}")]
+    BroadcastRejected(String),
+    #[error("Invalid block header: {0}")]
+    InvalidBlockHeader(&'static str),
+    #[error("Address {address} is for {found} but connected to {expected}")]
+    AddressNetworkMismatch {
+        address: String,
+        expected: Network,
+        found: Network,
+    },
+    #[error("Scan of the UTXO set was aborted")]
+    UtxoScanAborted,
+    #[error("Unsupported address type: {0}")]
+    UnsupportedAddressType(String),
+    #[error("Invalid transaction limits of {max_vsize} vbytes and {max_inputs} inputs")]
+    InvalidTransactionLimits { max_vsize: u64, max_inputs: usize },
+    #[error("Transaction of {vsize} vbytes exceeds the maximum of {max_vsize} vbytes")]
+    TransactionTooLarge { vsize: u64, max_vsize: u64 },
+    #[error("Transaction with {inputs} inputs exceeds the maximum of {max_inputs} inputs")]
+    TooManyInputs { inputs: usize, max_inputs: usize },
+    #[error("Arithmetic overflow or underflow")]
+    ArithmeticError,
+    #[error("Failed to import watch-only address: {0}")]
+    WatchOnlyImportFailed(String),
 }
 
 impl Error {
+    /// True if the transaction exceeds the transaction limits, so a smaller one may succeed.
+    pub fn is_transaction_too_large(&self) -> bool {
+        matches!(self, Error::TransactionTooLarge { .. } | Error::TooManyInputs { .. })
+    }
+
     pub fn is_connection_refused(&self) -> bool {
         matches!(self,
             Error::BitcoinError(BitcoinError::JsonRpc(JsonRpcError::Hyper(HyperError::Io(err))))
@@ -78,6 +136,14 @@ impl Error {
         )
     }
 
+    /// True for generic wallet errors, e.g. a legacy-only call made on a descriptor wallet.
+    pub fn is_wallet_error(&self) -> bool {
+        matches!(self,
+            Error::BitcoinError(BitcoinError::JsonRpc(JsonRpcError::Rpc(err)))
+                if BitcoinRpcError::from(err.clone()) == BitcoinRpcError::RpcWalletError
+        )
+    }
+
     pub fn is_invalid_parameter(&self) -> bool {
         matches!(self,
             Error::BitcoinError(BitcoinError::JsonRpc(JsonRpcError::Rpc(err)))
@@ -0,0 +1,104 @@
+use crate::{deserialize, shared_http_client, BlockHash, BlockHeader, ConversionError, Error};
+use reqwest::StatusCode;
+use std::str::FromStr;
+
+/// Read-only client of an Esplora API (e.g. electrs), used to keep following the chain
+/// when bitcoind is unavailable. Responses are not trusted: headers are checked against
+/// the requested hash and their proof of work before they are returned.
+#[derive(Clone)]
+pub struct EsploraClient {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl EsploraClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            http: shared_http_client(),
+        }
+    }
+
+    /// Use the given HTTP client instead of the shared one, e.g. with different pool limits.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Get the body of `<url><path>`, `None` if the resource does not exist.
+    async fn get(&self, path: &str) -> Result<Option<String>, Error> {
+        let response = self.http.get(&format!("{}{}", self.url, path)).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.text().await?))
+    }
+
+    pub async fn get_block_count(&self) -> Result<u64, Error> {
+        let height = self
+            .get("/blocks/tip/height")
+            .await?
+            .ok_or(Error::InvalidBitcoinHeight)?;
+        height
+            .trim()
+            .parse()
+            .map_err(|_| Error::InvalidBlockHeader("invalid tip height"))
+    }
+
+    pub async fn get_block_hash(&self, height: u32) -> Result<BlockHash, Error> {
+        let hash = self
+            .get(&format!("/block-height/{}", height))
+            .await?
+            .ok_or(Error::InvalidBitcoinHeight)?;
+        Ok(BlockHash::from_str(hash.trim()).map_err(ConversionError::from)?)
+    }
+
+    pub async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader, Error> {
+        let header = self
+            .get(&format!("/block/{}/header", hash))
+            .await?
+            .ok_or(Error::InvalidBlockHeader("unknown block"))?;
+        let raw_header = hex::decode(header.trim()).map_err(ConversionError::from)?;
+        validate_header(hash, &raw_header)
+    }
+}
+
+/// Decode a header and check that it has the expected hash and satisfies its own target.
+/// Whether the target is the one required at its height is left to the relay.
+pub fn validate_header(hash: &BlockHash, raw_header: &[u8]) -> Result<BlockHeader, Error> {
+    let header: BlockHeader = deserialize(raw_header)?;
+    if header.block_hash() != *hash {
+        return Err(Error::InvalidBlockHeader("hash mismatch"));
+    }
+    header
+        .validate_pow(&header.target())
+        .map_err(|_| Error::InvalidBlockHeader("insufficient proof of work"))?;
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+    #[test]
+    fn test_validate_header() {
+        let hash = BlockHash::from_str(GENESIS_HASH).unwrap();
+        let mut raw_header = hex::decode(GENESIS_HEADER).unwrap();
+        assert!(validate_header(&hash, &raw_header).is_ok());
+
+        // changing the nonce changes the hash and breaks the proof of work
+        raw_header[76] ^= 1;
+        assert!(matches!(
+            validate_header(&hash, &raw_header),
+            Err(Error::InvalidBlockHeader("hash mismatch"))
+        ));
+        let hash = deserialize::<BlockHeader>(&raw_header).unwrap().block_hash();
+        assert!(matches!(
+            validate_header(&hash, &raw_header),
+            Err(Error::InvalidBlockHeader("insufficient proof of work"))
+        ));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::Error;
+use bitcoincore_rpc::json::{EstimateMode, FundRawTransactionOptions};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Confirmation target of bitcoind if not configured with `-txconfirmtarget`.
+pub(crate) const DEFAULT_CONF_TARGET: u16 = 6;
+
+/// Largest confirmation target for which bitcoind estimates fees.
+pub const MAX_CONF_TARGET: u16 = 1008;
+
+/// How bitcoind estimates the fee rate needed for a confirmation target. Serialized as in
+/// the command line options, `economical` or `conservative`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeeEstimateMode {
+    /// Responds quickly to falling fee rates, at the risk of underpaying when they rise.
+    Economical,
+    /// Considers a longer history of blocks, so that the fee rate is less likely to be
+    /// too low for the target. Suited to payments with a deadline.
+    Conservative,
+}
+
+impl FromStr for FeeEstimateMode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "economical" => Ok(FeeEstimateMode::Economical),
+            "conservative" => Ok(FeeEstimateMode::Conservative),
+            _ => Err(Error::InvalidFeeEstimateMode),
+        }
+    }
+}
+
+impl From<FeeEstimateMode> for EstimateMode {
+    fn from(mode: FeeEstimateMode) -> Self {
+        match mode {
+            FeeEstimateMode::Economical => EstimateMode::Economical,
+            FeeEstimateMode::Conservative => EstimateMode::Conservative,
+        }
+    }
+}
+
+/// Parameters of the fee estimation of created transactions. Unset parameters are left to
+/// the configured defaults, and to bitcoind if those are unset as well (`-txconfirmtarget`,
+/// and the conservative mode for replaceable transactions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FeeEstimation {
+    pub mode: Option<FeeEstimateMode>,
+    /// Number of blocks within which the transaction should confirm.
+    pub conf_target: Option<u16>,
+}
+
+impl FeeEstimation {
+    pub fn new(mode: Option<FeeEstimateMode>, conf_target: Option<u16>) -> Result<Self, Error> {
+        match conf_target {
+            Some(conf_target) if conf_target == 0 || conf_target > MAX_CONF_TARGET => {
+                Err(Error::InvalidConfTarget(conf_target))
+            }
+            _ => Ok(Self { mode, conf_target }),
+        }
+    }
+
+    /// Economical estimation, e.g. for sweeps that can wait for falling fee rates.
+    pub fn economical() -> Self {
+        Self {
+            mode: Some(FeeEstimateMode::Economical),
+            conf_target: None,
+        }
+    }
+
+    pub fn conservative() -> Self {
+        Self {
+            mode: Some(FeeEstimateMode::Conservative),
+            conf_target: None,
+        }
+    }
+
+    /// These parameters, with the unset ones taken from `defaults`.
+    pub fn or(self, defaults: FeeEstimation) -> Self {
+        Self {
+            mode: self.mode.or(defaults.mode),
+            conf_target: self.conf_target.or(defaults.conf_target),
+        }
+    }
+
+    pub(crate) fn apply(&self, options: &mut FundRawTransactionOptions) {
+        options.estimate_mode = self.mode.map(Into::into);
+        options.conf_target = self.conf_target.map(Into::into);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_estimation_overrides() {
+        let defaults = FeeEstimation::new(Some(FeeEstimateMode::Economical), Some(6)).unwrap();
+        assert_eq!(FeeEstimation::default().or(defaults), defaults);
+        assert_eq!(FeeEstimation::economical().or(defaults), defaults);
+        assert_eq!(
+            FeeEstimation::conservative().or(defaults),
+            FeeEstimation {
+                mode: Some(FeeEstimateMode::Conservative),
+                conf_target: Some(6),
+            }
+        );
+
+        let mut options = FundRawTransactionOptions::default();
+        FeeEstimation::conservative().apply(&mut options);
+        assert!(matches!(options.estimate_mode, Some(EstimateMode::Conservative)));
+        assert_eq!(options.conf_target, None);
+    }
+
+    #[test]
+    fn test_fee_estimation_parameters() {
+        assert_eq!(
+            "conservative".parse::<FeeEstimateMode>().unwrap(),
+            FeeEstimateMode::Conservative
+        );
+        assert!("unset".parse::<FeeEstimateMode>().is_err());
+        assert!(FeeEstimation::new(None, Some(0)).is_err());
+        assert!(FeeEstimation::new(None, Some(MAX_CONF_TARGET + 1)).is_err());
+        assert!(FeeEstimation::new(None, Some(MAX_CONF_TARGET)).is_ok());
+    }
+}
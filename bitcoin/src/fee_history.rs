@@ -0,0 +1,119 @@
+use crate::{Error, OutPoint, Transaction, Txid, BLOCK_MAX_VSIZE};
+use serde::{Deserialize, Serialize};
+use std::{fs::OpenOptions, io::Write, path::Path, time::SystemTime};
+
+/// State of the mempool at the time of a broadcast.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MempoolConditions {
+    /// Fee rate (sat/vbyte) needed for inclusion in the next block.
+    pub next_block_fee_rate: u64,
+    /// Number of blocks needed to clear the mempool, see `FeeHistogram::congestion`.
+    pub congestion: f64,
+}
+
+impl MempoolConditions {
+    /// Conditions given the fee rate estimated for the next block and the total vsize of the
+    /// mempool, as reported by `getmempoolinfo`.
+    pub fn new(next_block_fee_rate: u64, mempool_vsize: u64) -> Self {
+        Self {
+            next_block_fee_rate,
+            congestion: mempool_vsize as f64 / BLOCK_MAX_VSIZE as f64,
+        }
+    }
+}
+
+/// A single broadcast of a transaction. In JSON, `time` is given as
+/// `{"secs_since_epoch", "nanos_since_epoch"}` and `inputs` as `"<txid>:<vout>"` strings.
+/// Replacements spend at least one input of the transaction they replace, so the bump history
+/// of a payment consists of all recorded broadcasts sharing an input with it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BroadcastRecord {
+    pub txid: Txid,
+    /// Fee rate (sat/vbyte) paid by the transaction, `None` if its fee is not known.
+    pub fee_rate: Option<u64>,
+    /// `None` if the mempool could not be queried at the time of the broadcast.
+    pub mempool: Option<MempoolConditions>,
+    pub time: SystemTime,
+    inputs: Vec<OutPoint>,
+}
+
+impl BroadcastRecord {
+    pub fn new(
+        transaction: &Transaction,
+        fee_rate: Option<u64>,
+        mempool: Option<MempoolConditions>,
+        time: SystemTime,
+    ) -> Self {
+        Self {
+            txid: transaction.txid(),
+            fee_rate,
+            mempool,
+            time,
+            inputs: transaction.input.iter().map(|input| input.previous_output).collect(),
+        }
+    }
+}
+
+/// Append the record to the fee history file, one JSON object per line.
+pub(crate) fn record_broadcast(path: &Path, record: &BroadcastRecord) -> Result<(), Error> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(file.sync_data()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash, Script, TxIn, TxOut};
+
+    fn transaction(inputs: &[u8], value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: inputs
+                .iter()
+                .map(|input| TxIn {
+                    previous_output: OutPoint::new(Txid::from_slice(&[*input; 32]).unwrap(), 0),
+                    script_sig: Script::new(),
+                    sequence: 0xFFFFFFFD,
+                    witness: vec![],
+                })
+                .collect(),
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_fee_history_is_appended() {
+        let path = std::env::temp_dir().join(format!("fee-history-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let original = transaction(&[1], 1000);
+        let bump = transaction(&[1, 2], 900);
+        let records = vec![
+            BroadcastRecord::new(&original, Some(2), None, SystemTime::UNIX_EPOCH),
+            BroadcastRecord::new(
+                &bump,
+                Some(10),
+                Some(MempoolConditions::new(12, 2_500_000)),
+                SystemTime::UNIX_EPOCH,
+            ),
+        ];
+        for record in &records {
+            record_broadcast(&path, record).unwrap();
+        }
+
+        let recorded = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<BroadcastRecord>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(recorded, records);
+        assert_eq!(recorded[1].mempool.unwrap().congestion, 2.5);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{fee_for_vsize, sat_to_btc, vsize, Amount, Error, Transaction};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// Upper bound on the configurable maximum fee rate (in sat/vbyte). This is ten times
+/// bitcoind's default of 0.10 BTC/kvB, anything above it is almost certainly a
+/// misconfiguration (e.g. BTC/kvB entered as sat/vbyte).
+pub const MAX_FEE_RATE_CAP: u64 = 100_000;
+
+/// Maximum fee rate of transactions that we broadcast, also passed as `maxfeerate` to
+/// `sendrawtransaction` so that bitcoind's (lower) default does not reject legitimate
+/// high-fee payments during fee spikes. Serialized as `{"sat_per_vbyte": <u64>}`, the
+/// rate is validated when deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedMaxFeeRate")]
+pub struct MaxFeeRate {
+    sat_per_vbyte: u64,
+}
+
+#[derive(Deserialize)]
+struct UncheckedMaxFeeRate {
+    sat_per_vbyte: u64,
+}
+
+impl TryFrom<UncheckedMaxFeeRate> for MaxFeeRate {
+    type Error = Error;
+
+    fn try_from(unchecked: UncheckedMaxFeeRate) -> Result<Self, Self::Error> {
+        MaxFeeRate::new(unchecked.sat_per_vbyte)
+    }
+}
+
+impl MaxFeeRate {
+    pub fn new(sat_per_vbyte: u64) -> Result<Self, Error> {
+        if sat_per_vbyte == 0 || sat_per_vbyte > MAX_FEE_RATE_CAP {
+            return Err(Error::InvalidMaxFeeRate(sat_per_vbyte));
+        }
+        Ok(Self { sat_per_vbyte })
+    }
+
+    pub fn sat_per_vbyte(&self) -> u64 {
+        self.sat_per_vbyte
+    }
+
+    /// The fee rate in BTC/kvB, as expected by `sendrawtransaction`.
+    pub fn btc_per_kvbyte(&self) -> f64 {
+        sat_to_btc(self.sat_per_vbyte * 1000)
+    }
+
+    /// Ensure that paying `fee` for `transaction` does not exceed this fee rate.
+    pub fn check(&self, transaction: &Transaction, fee: Amount) -> Result<(), Error> {
+        let vsize = vsize(transaction);
+        // compare fee / vsize > max without rounding
+        if fee > fee_for_vsize(self.sat_per_vbyte, vsize)? {
+            return Err(Error::FeeRateTooHigh {
+                fee: fee.as_sat(),
+                vsize,
+                max_fee_rate: self.sat_per_vbyte,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash, OutPoint, Script, TxIn, TxOut, Txid};
+
+    fn transaction() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 0),
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 1000,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_max_fee_rate() {
+        assert!(MaxFeeRate::new(0).is_err());
+        assert!(MaxFeeRate::new(MAX_FEE_RATE_CAP + 1).is_err());
+
+        let max_fee_rate = MaxFeeRate::new(100).unwrap();
+        assert_eq!(max_fee_rate.btc_per_kvbyte(), 0.001);
+
+        let transaction = transaction();
+        let vsize = vsize(&transaction);
+        assert!(max_fee_rate.check(&transaction, Amount::from_sat(100 * vsize)).is_ok());
+        assert!(max_fee_rate
+            .check(&transaction, Amount::from_sat(100 * vsize + 1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_fee_rate_serde() {
+        let max_fee_rate = MaxFeeRate::new(100).unwrap();
+        let json = serde_json::to_string(&max_fee_rate).unwrap();
+        assert_eq!(json, r#"{"sat_per_vbyte":100}"#);
+        assert_eq!(serde_json::from_str::<MaxFeeRate>(&json).unwrap(), max_fee_rate);
+        assert!(serde_json::from_str::<MaxFeeRate>(r#"{"sat_per_vbyte":0}"#).is_err());
+    }
+}
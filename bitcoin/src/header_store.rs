@@ -0,0 +1,421 @@
+use crate::{deserialize, serialize, BlockHash, BlockHeader, Error, Network, MAX_REORG_DEPTH};
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use lazy_static::lazy_static;
+use log::warn;
+use prometheus::{IntCounterVec, Opts};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// Number of headers kept in memory if not configured otherwise, one difficulty period.
+pub const DEFAULT_HEADER_CACHE_SIZE: usize = 2016;
+
+/// Size of a record in the header file: the height (little endian) and the serialized header.
+const RECORD_SIZE: usize = 4 + 80;
+
+/// Size of the hash of the genesis block at the start of the header file.
+const GENESIS_SIZE: usize = 32;
+
+lazy_static! {
+    pub static ref HEADER_STORE_LOOKUPS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "bitcoin_header_store_lookups",
+            "Lookups of block headers and hashes by where they were found"
+        ),
+        &["lookup", "source"]
+    )
+    .expect("Failed to create metric");
+}
+
+/// Headers in memory, the least recently used one is evicted first.
+#[derive(Default)]
+struct Cache {
+    capacity: usize,
+    tick: u64,
+    headers: HashMap<BlockHash, (BlockHeader, u64)>,
+    last_used: BTreeMap<u64, BlockHash>,
+    /// Hashes of the main chain at heights below the reorg depth, the lowest are evicted first.
+    heights: BTreeMap<u32, BlockHash>,
+    heights_by_hash: HashMap<BlockHash, u32>,
+    tip: Option<u32>,
+}
+
+impl Cache {
+    fn header(&mut self, hash: &BlockHash) -> Option<BlockHeader> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (header, last_used) = self.headers.get_mut(hash)?;
+        self.last_used.remove(last_used);
+        self.last_used.insert(tick, *hash);
+        *last_used = tick;
+        Some(*header)
+    }
+
+    fn insert_header(&mut self, header: BlockHeader) {
+        let hash = header.block_hash();
+        if self.header(&hash).is_some() || self.capacity == 0 {
+            return;
+        }
+        self.headers.insert(hash, (header, self.tick));
+        self.last_used.insert(self.tick, hash);
+        while self.headers.len() > self.capacity {
+            let oldest = *self.last_used.keys().next().expect("not empty");
+            if let Some(hash) = self.last_used.remove(&oldest) {
+                self.headers.remove(&hash);
+            }
+        }
+    }
+
+    /// Whether the block at the height is deep enough not to be reorganized.
+    fn is_final(&self, height: u32) -> bool {
+        self.tip
+            .map_or(false, |tip| height.saturating_add(MAX_REORG_DEPTH as u32) <= tip)
+    }
+
+    fn insert_height(&mut self, height: u32, hash: BlockHash) {
+        if !self.is_final(height) || self.capacity == 0 {
+            return;
+        }
+        self.heights.insert(height, hash);
+        self.heights_by_hash.insert(hash, height);
+        while self.heights.len() > self.capacity {
+            let lowest = *self.heights.keys().next().expect("not empty");
+            if let Some(hash) = self.heights.remove(&lowest) {
+                self.heights_by_hash.remove(&hash);
+            }
+        }
+    }
+}
+
+/// Append-only file of the headers at final heights, indexed in memory when opened. It starts
+/// with the hash of the genesis block, so that the headers of another chain are not served.
+struct HeaderFile {
+    file: File,
+    /// End of the last complete record, where the next one is written.
+    len: u64,
+    by_height: BTreeMap<u32, u64>,
+    by_hash: HashMap<BlockHash, u64>,
+}
+
+impl HeaderFile {
+    fn open(path: &Path, genesis_hash: BlockHash) -> Result<Self, Error> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let genesis_hash = serialize(&genesis_hash);
+        if contents.get(..GENESIS_SIZE) != Some(&genesis_hash[..]) {
+            if !contents.is_empty() {
+                warn!("{} holds the headers of another chain, starting anew", path.display());
+            }
+            file.set_len(0)?;
+            file.write_all(&genesis_hash)?;
+            contents = genesis_hash;
+        }
+        let mut header_file = Self {
+            file,
+            len: GENESIS_SIZE as u64,
+            by_height: BTreeMap::new(),
+            by_hash: HashMap::new(),
+        };
+        // a record cut off by a crash is ignored and overwritten, the header is fetched again
+        for record in contents[GENESIS_SIZE..].chunks_exact(RECORD_SIZE) {
+            match decode_record(record) {
+                Ok((height, header)) => {
+                    header_file.index(height, header.block_hash(), header_file.len);
+                    header_file.len += RECORD_SIZE as u64;
+                }
+                Err(err) => {
+                    warn!(
+                        "Ignoring the headers from offset {} of {}: {}",
+                        header_file.len,
+                        path.display(),
+                        err
+                    );
+                    break;
+                }
+            }
+        }
+        Ok(header_file)
+    }
+
+    fn index(&mut self, height: u32, hash: BlockHash, offset: u64) {
+        self.by_height.insert(height, offset);
+        self.by_hash.insert(hash, offset);
+    }
+
+    fn read(&mut self, offset: u64) -> Result<BlockHeader, Error> {
+        let mut record = [0; RECORD_SIZE];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut record)?;
+        Ok(decode_record(&record)?.1)
+    }
+
+    fn append(&mut self, height: u32, header: &BlockHeader) -> Result<(), Error> {
+        let hash = header.block_hash();
+        if self.by_hash.contains_key(&hash) {
+            return Ok(());
+        }
+        // drop anything after the last complete record, it would misalign the records after it
+        self.file.set_len(self.len)?;
+        let mut record = height.to_le_bytes().to_vec();
+        record.extend(serialize(header));
+        self.file.write_all(&record)?;
+        self.index(height, hash, self.len);
+        self.len += RECORD_SIZE as u64;
+        Ok(())
+    }
+}
+
+fn decode_record(record: &[u8]) -> Result<(u32, BlockHeader), Error> {
+    let mut height = [0; 4];
+    height.copy_from_slice(&record[..4]);
+    Ok((u32::from_le_bytes(height), deserialize(&record[4..])?))
+}
+
+/// Block headers by hash and the hashes of the main chain by height, so that the headers
+/// needed repeatedly (e.g. to construct proofs, analyze reorgs or relay blocks) are only
+/// fetched from bitcoind once. Headers are kept in a bounded in-memory cache and, if a
+/// file is configured, the headers of blocks deeper than `MAX_REORG_DEPTH` are persisted.
+/// Heights are only indexed for such final blocks, since the hash at a height near the
+/// tip may still change. The file is only accessed on the blocking thread pool.
+#[derive(Clone)]
+pub struct HeaderStore {
+    cache: Arc<Mutex<Cache>>,
+    file: Option<Arc<Mutex<HeaderFile>>>,
+}
+
+impl Default for HeaderStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_HEADER_CACHE_SIZE)
+    }
+}
+
+impl HeaderStore {
+    /// Keep up to `capacity` headers and final heights in memory, zero disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cache: Arc::new(Mutex::new(Cache {
+                capacity,
+                ..Default::default()
+            })),
+            file: None,
+        }
+    }
+
+    /// Persist the headers of final blocks of the network to the file, and look them up there.
+    pub fn with_file(mut self, path: &Path, network: Network) -> Result<Self, Error> {
+        let genesis_hash = genesis_block(network).block_hash();
+        self.file = Some(Arc::new(Mutex::new(HeaderFile::open(path, genesis_hash)?)));
+        Ok(self)
+    }
+
+    /// Height of the best block, headers below it by more than the reorg depth are final.
+    pub fn set_tip(&self, height: u32) {
+        self.cache.lock().expect("poisoned").tip = Some(height);
+    }
+
+    pub async fn header(&self, hash: &BlockHash) -> Option<BlockHeader> {
+        if let Some(header) = self.cache.lock().expect("poisoned").header(hash) {
+            HEADER_STORE_LOOKUPS.with_label_values(&["header", "memory"]).inc();
+            return Some(header);
+        }
+        let hash = *hash;
+        let header = self.read_file(move |file| file.by_hash.get(&hash).copied()).await;
+        if let Some(header) = header {
+            HEADER_STORE_LOOKUPS.with_label_values(&["header", "disk"]).inc();
+            self.cache.lock().expect("poisoned").insert_header(header);
+        } else {
+            HEADER_STORE_LOOKUPS.with_label_values(&["header", "miss"]).inc();
+        }
+        header
+    }
+
+    /// Hash of the main chain block at the height, if the height is final and known.
+    pub async fn hash_at(&self, height: u32) -> Option<BlockHash> {
+        if let Some(hash) = self.cache.lock().expect("poisoned").heights.get(&height) {
+            HEADER_STORE_LOOKUPS.with_label_values(&["hash", "memory"]).inc();
+            return Some(*hash);
+        }
+        let header = self.read_file(move |file| file.by_height.get(&height).copied()).await;
+        if let Some(header) = header {
+            HEADER_STORE_LOOKUPS.with_label_values(&["hash", "disk"]).inc();
+            let mut cache = self.cache.lock().expect("poisoned");
+            cache.insert_header(header);
+            cache.insert_height(height, header.block_hash());
+            Some(header.block_hash())
+        } else {
+            HEADER_STORE_LOOKUPS.with_label_values(&["hash", "miss"]).inc();
+            None
+        }
+    }
+
+    pub async fn insert_header(&self, header: BlockHeader) {
+        let height = {
+            let mut cache = self.cache.lock().expect("poisoned");
+            cache.insert_header(header);
+            cache.heights_by_hash.get(&header.block_hash()).copied()
+        };
+        if let Some(height) = height {
+            self.persist(height, header).await;
+        }
+    }
+
+    /// Record the hash of the main chain block at the height, ignored unless the height is final.
+    pub async fn insert_hash(&self, height: u32, hash: BlockHash) {
+        let header = {
+            let mut cache = self.cache.lock().expect("poisoned");
+            cache.insert_height(height, hash);
+            if !cache.heights.contains_key(&height) {
+                return;
+            }
+            cache.headers.get(&hash).map(|(header, _)| *header)
+        };
+        if let Some(header) = header {
+            self.persist(height, header).await;
+        }
+    }
+
+    /// Read the header at the offset given by the index of the file, if any.
+    async fn read_file<F>(&self, offset: F) -> Option<BlockHeader>
+    where
+        F: FnOnce(&HeaderFile) -> Option<u64> + Send + 'static,
+    {
+        let file = self.file.clone()?;
+        let result = tokio::task::spawn_blocking(move || {
+            let mut file = file.lock().expect("poisoned");
+            match offset(&file) {
+                Some(offset) => file.read(offset).map(Some),
+                None => Ok(None),
+            }
+        })
+        .await
+        .map_err(Error::from)
+        .and_then(|result| result);
+        match result {
+            Ok(header) => header,
+            Err(err) => {
+                warn!("Failed to read block header from file: {}", err);
+                None
+            }
+        }
+    }
+
+    async fn persist(&self, height: u32, header: BlockHeader) {
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => return,
+        };
+        let result = tokio::task::spawn_blocking(move || file.lock().expect("poisoned").append(height, &header))
+            .await
+            .map_err(Error::from)
+            .and_then(|result| result);
+        if let Err(err) = result {
+            warn!("Failed to write block header to file: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash, TxMerkleNode};
+
+    fn header(nonce: u32) -> BlockHeader {
+        BlockHeader {
+            version: 2,
+            prev_blockhash: BlockHash::from_slice(&[1; 32]).unwrap(),
+            merkle_root: TxMerkleNode::from_slice(&[2; 32]).unwrap(),
+            time: 1_600_000_000,
+            bits: 0x1d00ffff,
+            nonce,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lru_eviction() {
+        let store = HeaderStore::new(2);
+        let (a, b, c) = (header(1), header(2), header(3));
+        store.insert_header(a).await;
+        store.insert_header(b).await;
+        // `a` is used more recently than `b`
+        assert_eq!(store.header(&a.block_hash()).await, Some(a));
+        store.insert_header(c).await;
+        assert_eq!(store.header(&b.block_hash()).await, None);
+        assert_eq!(store.header(&a.block_hash()).await, Some(a));
+        assert_eq!(store.header(&c.block_hash()).await, Some(c));
+    }
+
+    #[tokio::test]
+    async fn test_only_final_heights_are_indexed() {
+        let store = HeaderStore::new(10);
+        let hash = header(1).block_hash();
+        store.insert_hash(100, hash).await;
+        assert_eq!(store.hash_at(100).await, None);
+
+        store.set_tip(100 + MAX_REORG_DEPTH as u32 - 1);
+        store.insert_hash(100, hash).await;
+        assert_eq!(store.hash_at(100).await, None);
+
+        store.set_tip(100 + MAX_REORG_DEPTH as u32);
+        store.insert_hash(100, hash).await;
+        assert_eq!(store.hash_at(100).await, Some(hash));
+    }
+
+    #[tokio::test]
+    async fn test_file_store() {
+        let path = std::env::temp_dir().join(format!("headers-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let final_header = header(1);
+        {
+            let store = HeaderStore::new(10).with_file(&path, Network::Regtest).unwrap();
+            store.set_tip(500);
+            store.insert_header(final_header).await;
+            store.insert_hash(100, final_header.block_hash()).await;
+            // not final, kept in memory only
+            store.insert_header(header(2)).await;
+            store.insert_hash(499, header(2).block_hash()).await;
+        }
+
+        let store = HeaderStore::new(10).with_file(&path, Network::Regtest).unwrap();
+        assert_eq!(store.header(&final_header.block_hash()).await, Some(final_header));
+        assert_eq!(store.hash_at(100).await, Some(final_header.block_hash()));
+        assert_eq!(store.header(&header(2).block_hash()).await, None);
+        assert_eq!(store.hash_at(499).await, None);
+
+        // the headers of regtest are not served on testnet
+        let store = HeaderStore::new(10).with_file(&path, Network::Testnet).unwrap();
+        assert_eq!(store.hash_at(100).await, None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cut_off_record_is_ignored() {
+        let path = std::env::temp_dir().join(format!("headers-cut-off-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let (a, b) = (header(1), header(2));
+        {
+            let store = HeaderStore::new(10).with_file(&path, Network::Regtest).unwrap();
+            store.set_tip(500);
+            store.insert_header(a).await;
+            store.insert_hash(100, a.block_hash()).await;
+        }
+        // crashed while writing the next record
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0; RECORD_SIZE / 2]).unwrap();
+
+        let store = HeaderStore::new(10).with_file(&path, Network::Regtest).unwrap();
+        assert_eq!(store.hash_at(100).await, Some(a.block_hash()));
+        store.set_tip(500);
+        store.insert_header(b).await;
+        store.insert_hash(101, b.block_hash()).await;
+
+        let store = HeaderStore::new(10).with_file(&path, Network::Regtest).unwrap();
+        assert_eq!(store.hash_at(100).await, Some(a.block_hash()));
+        assert_eq!(store.hash_at(101).await, Some(b.block_hash()));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
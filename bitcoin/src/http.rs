@@ -0,0 +1,34 @@
+use lazy_static::lazy_static;
+use std::time::Duration;
+
+/// Maximum number of idle connections kept open per host.
+const POOL_MAX_IDLE_PER_HOST: usize = 8;
+
+/// Time after which idle connections are closed.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Interval of the TCP keep-alive probes on open connections.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref SHARED_HTTP_CLIENT: reqwest::Client = reqwest::Client::builder()
+        .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE)
+        .tcp_nodelay(true)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("Failed to create http client");
+}
+
+/// HTTP client shared by the Esplora clients and the broadcast channels. Clones share the
+/// connection pool, so that e.g. the many requests of an address rescan reuse warm
+/// connections. Host names are resolved by the caching trust-dns resolver.
+pub fn shared_http_client() -> reqwest::Client {
+    SHARED_HTTP_CLIENT.clone()
+}
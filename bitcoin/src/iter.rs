@@ -213,11 +213,12 @@ mod tests {
                 txid: Txid,
                 num_confirmations: u32,
             ) -> Result<TransactionMetadata, Error>;
-            async fn create_transaction<A: PartialAddress + Send + Sync + 'static>(
+            async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
                 &self,
                 address: A,
                 sat: u64,
                 request_id: Option<H256>,
+                fee_estimation: FeeEstimation,
             ) -> Result<LockedTransaction, Error>;
             async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, Error>;
             async fn create_and_send_transaction<A: PartialAddress + Send + 'static>(
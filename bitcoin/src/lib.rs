@@ -1,41 +1,126 @@
 #[cfg(feature = "cli")]
 pub mod cli;
+#[cfg(any(test, feature = "test_vectors"))]
+pub mod test_vectors;
 
 mod addr;
+mod async_rpc;
+mod auth;
+mod balance;
+mod bip21;
+mod broadcast;
+mod confirmations;
 mod error;
+mod esplora;
+mod fee_estimation;
+mod fee_history;
+mod fee_rate;
+mod header_store;
+mod http;
 mod iter;
-
-pub use addr::PartialAddress;
+mod lock_time;
+mod malleability;
+mod mempool;
+mod money;
+mod outputs;
+mod prevout;
+mod proof;
+mod raw_block;
+mod reservation;
+mod scan;
+mod serde_hex;
+mod spending;
+mod tor;
+mod tx_size;
+mod utxo_set;
+mod watch_only;
+mod watcher;
+
+pub use addr::{calculate_deposit_address, validate_address, validate_script, PartialAddress, REJECTED_PAYMENTS};
+use async_rpc::AsyncClient;
 use async_trait::async_trait;
-use backoff::{backoff::Backoff, future::FutureOperation as _, ExponentialBackoff};
+use auth::ReloadingClient;
+use backoff::{backoff::Backoff, future::FutureOperation as _};
+use balance::GetBalancesResult;
+pub use balance::WalletBalances;
+pub use bip21::payment_uri;
 pub use bitcoincore_rpc::{
     bitcoin::{
         blockdata::{opcodes::all as opcodes, script::Builder},
         consensus::encode::{deserialize, serialize},
-        hash_types::BlockHash,
+        hash_types::{BlockHash, Wtxid},
         hashes::{hex::ToHex, Hash},
         secp256k1,
         secp256k1::{constants::PUBLIC_KEY_SIZE, SecretKey},
         util::{address::Payload, key, merkleblock::PartialMerkleTree, psbt::serialize::Serialize, uint::Uint256},
         Address, Amount, Block, BlockHeader, Network, OutPoint, PrivateKey, PubkeyHash, PublicKey, Script, ScriptHash,
-        Transaction, TxIn, TxMerkleNode, TxOut, Txid, WPubkeyHash, WScriptHash,
+        SignedAmount, Transaction, TxIn, TxMerkleNode, TxOut, Txid, WPubkeyHash, WScriptHash,
     },
     bitcoincore_rpc_json::{CreateRawTransactionInput, GetTransactionResult, WalletTxInfo},
     json::{self, AddressType, GetBlockResult},
     jsonrpc::{error::RpcError, Error as JsonRpcError},
     Auth, Client, Error as BitcoinError, RpcApi,
 };
+pub use broadcast::{BroadcastChannel, Broadcaster};
+use confirmations::ConfirmationWatcher;
 pub use error::{BitcoinRpcError, ConversionError, Error};
+pub use esplora::{validate_header, EsploraClient};
+use fee_estimation::DEFAULT_CONF_TARGET;
+pub use fee_estimation::{FeeEstimateMode, FeeEstimation, MAX_CONF_TARGET};
+use fee_history::record_broadcast;
+pub use fee_history::{BroadcastRecord, MempoolConditions};
+pub use fee_rate::{MaxFeeRate, MAX_FEE_RATE_CAP};
+pub use header_store::{HeaderStore, DEFAULT_HEADER_CACHE_SIZE, HEADER_STORE_LOOKUPS};
+pub use http::shared_http_client;
 use hyper::Error as HyperError;
 pub use iter::{reverse_stream_transactions, stream_blocks, stream_in_chain_transactions};
+pub use lock_time::{LockTimePolicy, TransactionPolicy};
 use log::{info, trace};
+pub use malleability::{normalized_txid, Malleation, MALLEATED_TRANSACTIONS};
+use malleability::{record_sent, SentTransactions};
+pub use mempool::{FeeHistogram, MempoolEntry, MempoolLimits, BLOCK_MAX_VSIZE};
+use mempool::{GetMempoolEntryResult, GetMempoolInfoResult, VerboseMempoolEntry};
+pub use money::{
+    btc_to_sat, fee_for_vsize, fee_rate, format_btc, sat_to_btc, signed_difference, vsize, AmountExt,
+    SATOSHI_PER_BITCOIN,
+};
+pub use outputs::{OutputInfo, OutputMetadata};
+use prevout::VerboseTransaction;
+pub use prevout::{ScriptType, TransactionWithPrevouts};
+pub use proof::verify_proof;
+pub use raw_block::{RawBlock, RawTransaction};
+pub use reservation::UtxoReservation;
+use reservation::UtxoReservations;
+use scan::GetWalletInfoScanning;
+pub use scan::{ScanProgress, SCAN_PROGRESS_INTERVAL};
+use serde::{Deserialize, Serialize};
 use serde_json::error::Category as SerdeJsonCategory;
 use sp_core::H256;
-use std::{future::Future, io::ErrorKind as IoErrorKind, sync::Arc, time::Duration};
+use spending::{ListSinceBlockResult, Prevout, SpendingPrevoutResult};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    future::Future,
+    io::ErrorKind as IoErrorKind,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::{
-    sync::{Mutex, OwnedMutexGuard},
+    sync::{watch, Mutex},
     time::{delay_for, timeout},
 };
+pub use tor::{is_onion_url, ConnectionProfile, TOR_CONNECTION_TIMEOUT};
+pub use tx_size::{TransactionLimits, DEFAULT_MAX_INPUTS, MAX_STANDARD_TX_VSIZE};
+use utxo_set::ScanTxOutSetResult;
+pub use utxo_set::{address_descriptor, Utxo};
+use watch_only::{check_import_results, DescriptorInfo, ImportDescriptorRequest, ImportMultiRequest, ImportResult};
+pub use watcher::{AddressWatcher, Delta, Deposit, Spend, WatchEvent, MAX_REORG_DEPTH};
 
 #[macro_use]
 extern crate num_derive;
@@ -43,16 +128,41 @@ extern crate num_derive;
 pub const BLOCK_INTERVAL: Duration = Duration::from_secs(600); // 10 minutes
 
 const NOT_IN_MEMPOOL_ERROR_CODE: i32 = BitcoinRpcError::RpcInvalidAddressOrKey as i32;
+const METHOD_NOT_FOUND_ERROR_CODE: i32 = BitcoinRpcError::RpcMethodNotFound as i32;
 
 const RETRY_DURATION: Duration = Duration::from_millis(1000);
 
-#[derive(Debug, Clone)]
+/// Time after which `wait_for_transaction_metadata` gives up on the confirmations.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Delay before funding a transaction again if it would exceed the mempool package limits.
+const MEMPOOL_CHAIN_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Number of times a transaction is funded again before the mempool package limits error is
+/// returned, roughly three blocks.
+const MEMPOOL_CHAIN_MAX_RETRIES: u32 = 30;
+
+/// Maximum number of transactions a split payment is spread over.
+const MAX_PAYMENT_PARTS: usize = 16;
+
+/// Number of wallet transactions listed per request, bitcoind rejects counts above `i32::MAX`.
+const LIST_TRANSACTIONS_PAGE_SIZE: usize = 1000;
+
+/// A transaction included in a block, with the data needed to prove its inclusion. In
+/// JSON, `txid` and `block_hash` are given in their usual (reversed) hex form, `proof` and
+/// `raw_tx` as hex strings of their serialization.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TransactionMetadata {
     pub txid: Txid,
+    #[serde(with = "serde_hex")]
     pub proof: Vec<u8>,
+    #[serde(with = "serde_hex")]
     pub raw_tx: Vec<u8>,
     pub block_height: u32,
     pub block_hash: BlockHash,
+    /// The outputs of `raw_tx`, empty in metadata recorded before they were included.
+    #[serde(default)]
+    pub outputs: OutputMetadata,
 }
 
 #[async_trait]
@@ -83,6 +193,25 @@ pub trait BitcoinCoreApi {
 
     async fn get_block(&self, hash: &BlockHash) -> Result<Block, Error>;
 
+    /// Get only those transactions of a block that pay to one of the given scripts.
+    async fn get_block_transactions_paying_to(
+        &self,
+        hash: &BlockHash,
+        scripts: &[Script],
+    ) -> Result<Vec<Transaction>, Error> {
+        let block = self.get_block(hash).await?;
+        Ok(block
+            .txdata
+            .into_iter()
+            .filter(|transaction| {
+                transaction
+                    .output
+                    .iter()
+                    .any(|output| scripts.contains(&output.script_pubkey))
+            })
+            .collect())
+    }
+
     async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader, Error>;
 
     async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, Error>;
@@ -97,13 +226,48 @@ pub trait BitcoinCoreApi {
         num_confirmations: u32,
     ) -> Result<TransactionMetadata, Error>;
 
+    /// Whether the transaction is in the mempool or confirmed, as opposed to dropped or
+    /// conflicted. Implementations that can not tell assume it is, so that nothing relying on
+    /// this is paid twice.
+    async fn is_transaction_live(&self, txid: Txid) -> Result<bool, Error> {
+        let _ = txid;
+        Ok(true)
+    }
+
+    /// Creates and return a transaction; it is not submitted to the mempool. While the returned value
+    /// is alive, the outputs it spends are reserved and not used to fund other transactions. This
+    /// prevents accidental double spending, while allowing payments to be funded in parallel.
+    ///
+    /// # Arguments
+    /// * `address` - Bitcoin address to fund
+    /// * `sat` - number of Satoshis to transfer
+    /// * `request_id` - the issue/redeem/replace id for which this transfer is being made
     async fn create_transaction<A: PartialAddress + Send + Sync + 'static>(
         &self,
         address: A,
         sat: u64,
         request_id: Option<H256>,
+    ) -> Result<LockedTransaction, Error> {
+        self.create_transaction_with_fee_estimation(address, sat, request_id, FeeEstimation::default())
+            .await
+    }
+
+    /// Like `create_transaction`, with the fee estimation overridden for this payment, e.g.
+    /// with `deadline_fee_estimation` for payments with a deadline.
+    async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
+        &self,
+        address: A,
+        sat: u64,
+        request_id: Option<H256>,
+        fee_estimation: FeeEstimation,
     ) -> Result<LockedTransaction, Error>;
 
+    /// Fee estimation of payments with a deadline, e.g. for redeem requests. Implementations
+    /// without a separate configuration use the fee estimation of all other payments.
+    fn deadline_fee_estimation(&self) -> FeeEstimation {
+        FeeEstimation::default()
+    }
+
     async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, Error>;
 
     async fn create_and_send_transaction<A: PartialAddress + Send + Sync + 'static>(
@@ -123,6 +287,36 @@ pub trait BitcoinCoreApi {
 
     async fn create_or_load_wallet(&self) -> Result<(), Error>;
 
+    /// Create or load the watch-only wallet, without private keys so that descriptor wallets
+    /// accept watch-only imports.
+    async fn create_or_load_watch_only_wallet(&self, wallet_name: String) -> Result<(), Error> {
+        if self.async_rpc().list_wallets().await?.contains(&wallet_name) {
+            return Ok(());
+        }
+        let client = self.rpc();
+        tokio::task::spawn_blocking(move || -> Result<(), Error> {
+            if client.load_wallet(&wallet_name).is_ok() {
+                return Ok(());
+            }
+            // wallet_name, disable_private_keys, blank, passphrase, avoid_reuse, descriptors
+            let args = [
+                wallet_name.clone().into(),
+                true.into(),
+                true.into(),
+                "".into(),
+                false.into(),
+                true.into(),
+            ];
+            if let Err(err) = client.call::<serde_json::Value>("createwallet", &args) {
+                // nodes before 0.21 do not know the `descriptors` argument, create a legacy wallet
+                log::warn!("Failed to create descriptor wallet {}: {}", wallet_name, err);
+                client.create_wallet(&wallet_name, Some(true), Some(true), None, None)?;
+            }
+            Ok(())
+        })
+        .await?
+    }
+
     async fn wallet_has_public_key<P>(&self, public_key: P) -> Result<bool, Error>
     where
         P: Into<[u8; PUBLIC_KEY_SIZE]> + From<[u8; PUBLIC_KEY_SIZE]> + Clone + PartialEq + Send + Sync + 'static;
@@ -132,41 +326,79 @@ pub trait BitcoinCoreApi {
     async fn rescan_blockchain(&self, start_height: usize) -> Result<(), Error>;
 }
 
+/// A created transaction together with the reservation of the outputs that it spends.
 pub struct LockedTransaction {
     pub transaction: Transaction,
     pub recipient: String,
-    _lock: Option<OwnedMutexGuard<()>>,
+    reservation: Option<UtxoReservation>,
+    /// Fee paid by the transaction, if known.
+    fee: Option<Amount>,
 }
 
 impl LockedTransaction {
-    pub fn new(transaction: Transaction, recipient: String, lock: Option<OwnedMutexGuard<()>>) -> Self {
+    pub fn new(transaction: Transaction, recipient: String, reservation: Option<UtxoReservation>) -> Self {
         LockedTransaction {
             transaction,
             recipient,
-            _lock: lock,
+            reservation,
+            fee: None,
         }
     }
-}
 
-fn get_exponential_backoff() -> ExponentialBackoff {
-    ExponentialBackoff {
-        max_elapsed_time: Some(Duration::from_secs(24 * 60 * 60)),
-        max_interval: Duration::from_secs(5 * 60), // wait at most 5 minutes before retrying
-        initial_interval: Duration::from_secs(1),
-        current_interval: Duration::from_secs(1),
-        multiplier: 2.0,            // delay doubles every time
-        randomization_factor: 0.25, // random value between 25% below and 25% above the ideal delay
-        ..Default::default()
+    pub fn with_fee(mut self, fee: Amount) -> Self {
+        self.fee = Some(fee);
+        self
+    }
+
+    pub fn fee(&self) -> Option<Amount> {
+        self.fee
     }
 }
 
 #[derive(Clone)]
 pub struct BitcoinCore {
-    rpc: Arc<Client>,
+    client: Arc<ReloadingClient>,
     wallet_name: Option<String>,
+    /// Client of the watch-only wallet `<wallet_name>-watch-only`, into which the addresses of
+    /// other vaults are imported, since descriptor wallets cannot mix them with private keys.
+    watch_only_client: Option<Arc<ReloadingClient>>,
     network: Network,
-    transaction_creation_lock: Arc<Mutex<()>>,
+    /// Outputs spent by created transactions that have not been broadcast yet.
+    utxo_reservations: UtxoReservations,
+    /// Unused change addresses that are known to the parachain, each address is used at most once.
+    change_addresses: Arc<Mutex<VecDeque<Address>>>,
+    /// If set, no transactions are created or broadcast, e.g. after the vault was flagged for theft.
+    spends_halted: Arc<AtomicBool>,
+    /// If set, spends are held back because another instance is the active payer.
+    spends_fenced: Arc<AtomicBool>,
+    transaction_policy: TransactionPolicy,
+    /// If set, overrides bitcoind's default maximum fee rate when broadcasting.
+    max_fee_rate: Option<MaxFeeRate>,
+    /// Fee estimation of created transactions, unless overridden for a payment.
+    fee_estimation: FeeEstimation,
+    /// Overrides of `fee_estimation` for payments with a deadline.
+    deadline_fee_estimation: FeeEstimation,
+    mempool_limits: MempoolLimits,
+    transaction_limits: TransactionLimits,
+    /// Secondary channels to which transactions are broadcast as well.
+    broadcaster: Broadcaster,
+    /// Transactions broadcast by this client, to tell them apart from external spends.
+    sent_transactions: Arc<Mutex<SentTransactions>>,
+    /// File in which the sent transactions are kept across restarts.
+    sent_transactions_file: Option<PathBuf>,
+    /// File to which the fee rate and mempool conditions of every broadcast are appended.
+    fee_history_file: Option<PathBuf>,
+    /// Set once the node turns out not to support `gettxspendingprevout`.
+    spending_prevout_unsupported: Arc<AtomicBool>,
+    /// Progress of the running wallet rescan, `None` if no rescan is running.
+    scan_progress_tx: Arc<watch::Sender<Option<ScanProgress>>>,
+    scan_progress_rx: watch::Receiver<Option<ScanProgress>>,
+    /// Confirmations of the transactions awaited by `wait_for_transaction_metadata`.
+    confirmations: ConfirmationWatcher,
     connection_timeout: Duration,
+    connection_profile: ConnectionProfile,
+    /// Block headers and final block hashes already fetched from bitcoind.
+    header_store: HeaderStore,
 }
 
 impl BitcoinCore {
@@ -177,30 +409,211 @@ impl BitcoinCore {
         network: Network,
         connection_timeout: Duration,
     ) -> Result<Self, Error> {
+        let watch_only_client = match wallet_name {
+            Some(ref x) => Some(Arc::new(ReloadingClient::new(
+                format!("{}/wallet/{}", url, watch_only_wallet_name(x)),
+                auth.clone(),
+            )?)),
+            None => None,
+        };
         let url = match wallet_name {
             Some(ref x) => format!("{}/wallet/{}", url, x),
             None => url,
         };
+        let client = Arc::new(ReloadingClient::new(url, auth)?);
+        let (scan_progress_tx, scan_progress_rx) = watch::channel(None);
         Ok(Self {
-            rpc: Arc::new(Client::new(url, auth)?),
+            utxo_reservations: UtxoReservations::new(client.clone()),
+            confirmations: ConfirmationWatcher::new(client.clone()),
+            client,
             wallet_name,
+            watch_only_client,
             network,
-            transaction_creation_lock: Arc::new(Mutex::new(())),
+            change_addresses: Arc::new(Mutex::new(VecDeque::new())),
+            spends_halted: Arc::new(AtomicBool::new(false)),
+            spends_fenced: Arc::new(AtomicBool::new(false)),
+            transaction_policy: TransactionPolicy::default(),
+            max_fee_rate: None,
+            fee_estimation: FeeEstimation::default(),
+            deadline_fee_estimation: FeeEstimation::default(),
+            mempool_limits: MempoolLimits::default(),
+            transaction_limits: TransactionLimits::default(),
+            broadcaster: Broadcaster::default(),
+            sent_transactions: Default::default(),
+            sent_transactions_file: None,
+            fee_history_file: None,
+            spending_prevout_unsupported: Arc::new(AtomicBool::new(false)),
+            scan_progress_tx: Arc::new(scan_progress_tx),
+            scan_progress_rx,
             connection_timeout,
+            connection_profile: ConnectionProfile::default(),
+            header_store: HeaderStore::default(),
         })
     }
 
+    /// Set the timeouts and retry delays of the connection, e.g. for bitcoind behind a
+    /// Tor hidden service.
+    pub fn with_connection_profile(mut self, connection_profile: ConnectionProfile) -> Result<Self, Error> {
+        self.client.set_request_timeout(connection_profile.request_timeout())?;
+        if let Some(ref watch_only_client) = self.watch_only_client {
+            watch_only_client.set_request_timeout(connection_profile.request_timeout())?;
+        }
+        self.connection_profile = connection_profile;
+        Ok(self)
+    }
+
+    /// Set the locktime and sequence policy of created transactions.
+    pub fn with_transaction_policy(mut self, transaction_policy: TransactionPolicy) -> Self {
+        self.transaction_policy = transaction_policy;
+        self
+    }
+
+    /// Set the package limits of the mempool, if bitcoind is not using the defaults.
+    pub fn with_mempool_limits(mut self, mempool_limits: MempoolLimits) -> Self {
+        self.mempool_limits = mempool_limits;
+        self
+    }
+
+    /// Set the maximum size and number of inputs of created transactions.
+    pub fn with_transaction_limits(mut self, transaction_limits: TransactionLimits) -> Self {
+        self.transaction_limits = transaction_limits;
+        self
+    }
+
+    /// Set the maximum fee rate of broadcast transactions, enforced both locally and by bitcoind.
+    pub fn with_max_fee_rate(mut self, max_fee_rate: Option<MaxFeeRate>) -> Self {
+        self.max_fee_rate = max_fee_rate;
+        self
+    }
+
+    /// Set the fee estimation of created transactions, unless overridden for a payment.
+    pub fn with_fee_estimation(mut self, fee_estimation: FeeEstimation) -> Self {
+        self.fee_estimation = fee_estimation;
+        self
+    }
+
+    /// Set the fee estimation of payments with a deadline, parameters that are not set are
+    /// taken from the fee estimation of all payments.
+    pub fn with_deadline_fee_estimation(mut self, deadline_fee_estimation: FeeEstimation) -> Self {
+        self.deadline_fee_estimation = deadline_fee_estimation;
+        self
+    }
+
+    /// Broadcast transactions to the given channels in addition to bitcoind, to improve
+    /// their propagation.
+    pub fn with_broadcast_channels(mut self, channels: Vec<BroadcastChannel>) -> Self {
+        self.broadcaster = Broadcaster::new(channels);
+        self
+    }
+
+    /// Set the store of the block headers fetched from bitcoind, e.g. to persist them.
+    pub fn with_header_store(mut self, header_store: HeaderStore) -> Self {
+        self.header_store = header_store;
+        self
+    }
+
+    /// Keep the transactions broadcast by this client in the file, so that they are not
+    /// mistaken for external spends after a restart.
+    pub fn with_sent_transactions_file(mut self, path: PathBuf) -> Result<Self, Error> {
+        self.sent_transactions = Arc::new(Mutex::new(SentTransactions::load(&path)?));
+        self.sent_transactions_file = Some(path);
+        Ok(self)
+    }
+
+    /// Append the fee rate and the mempool conditions of every broadcast to the file, to
+    /// review the fee bumps of payments.
+    pub fn with_fee_history_file(mut self, path: PathBuf) -> Self {
+        self.fee_history_file = Some(path);
+        self
+    }
+
+    fn rpc(&self) -> Arc<Client> {
+        self.client.get()
+    }
+
+    /// Client for the calls on the hot paths, which do not block a thread while waiting.
+    fn async_rpc(&self) -> Arc<AsyncClient> {
+        self.client.get_async()
+    }
+
+    /// Re-read the credentials, e.g. the cookie file after bitcoind has been restarted.
+    pub fn reload_auth(&self) -> Result<(), Error> {
+        if let Some(ref watch_only_client) = self.watch_only_client {
+            watch_only_client.reload()?;
+        }
+        self.client.reload()
+    }
+
+    /// Re-read the credentials whenever the process receives SIGHUP, e.g. when the cookie
+    /// file was replaced without changing its modification time.
+    #[cfg(unix)]
+    pub async fn reload_auth_on_hangup(&self) -> Result<(), Error> {
+        let mut hangups = signal(SignalKind::hangup())?;
+        while hangups.recv().await.is_some() {
+            info!("Received SIGHUP, reloading bitcoin RPC credentials");
+            if let Err(err) = self.reload_auth() {
+                log::warn!("Failed to reload bitcoin RPC credentials: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the credentials used for all subsequent requests without reconnecting.
+    pub fn set_auth(&self, auth: Auth) -> Result<(), Error> {
+        if let Some(ref watch_only_client) = self.watch_only_client {
+            watch_only_client.set_auth(auth.clone())?;
+        }
+        self.client.set_auth(auth)
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Refuse to create or broadcast any further transactions from this wallet.
+    pub fn halt_spends(&self) {
+        self.spends_halted.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume_spends(&self) {
+        self.spends_halted.store(false, Ordering::SeqCst);
+    }
+
+    pub fn are_spends_halted(&self) -> bool {
+        self.spends_halted.load(Ordering::SeqCst)
+    }
+
+    /// Hold back spends while this instance is not the leader, independent of `halt_spends`.
+    pub fn fence_spends(&self) {
+        self.spends_fenced.store(true, Ordering::SeqCst);
+    }
+
+    pub fn unfence_spends(&self) {
+        self.spends_fenced.store(false, Ordering::SeqCst);
+    }
+
+    fn ensure_spends_allowed(&self) -> Result<(), Error> {
+        if self.are_spends_halted() {
+            Err(Error::SpendsHalted)
+        } else if self.spends_fenced.load(Ordering::SeqCst) {
+            Err(Error::SpendsFenced)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Connect to a bitcoin-core full node or timeout.
     pub async fn connect(&self) -> Result<(), Error> {
         info!("Connecting to bitcoin-core...");
-        timeout(self.connection_timeout, async move {
+        let connection_timeout = self.connection_profile.connection_timeout(self.connection_timeout);
+        timeout(connection_timeout, async move {
             loop {
-                match self.rpc.get_blockchain_info() {
+                match self.async_rpc().get_blockchain_info().await {
                     Err(BitcoinError::JsonRpc(JsonRpcError::Hyper(HyperError::Io(err))))
-                        if err.kind() == IoErrorKind::ConnectionRefused =>
+                        if self.connection_profile.is_retryable(err.kind()) =>
                     {
-                        trace!("could not connect to bitcoin-core");
-                        delay_for(RETRY_DURATION).await;
+                        trace!("could not connect to bitcoin-core: {}", err);
+                        delay_for(self.connection_profile.retry_duration()).await;
                         continue;
                     }
                     Err(BitcoinError::JsonRpc(JsonRpcError::Rpc(err)))
@@ -208,7 +621,15 @@ impl BitcoinCore {
                     {
                         // may be loading block index or verifying wallet
                         trace!("bitcoin-core still in warm up");
-                        delay_for(RETRY_DURATION).await;
+                        delay_for(self.connection_profile.retry_duration()).await;
+                        continue;
+                    }
+                    Err(BitcoinError::JsonRpc(JsonRpcError::Json(err))) if err.classify() == SerdeJsonCategory::Eof => {
+                        // bitcoind responds to unauthorized requests with an empty body,
+                        // the cookie may have been rotated since we last read it
+                        trace!("bitcoin-core rejected credentials, reloading");
+                        self.reload_auth()?;
+                        delay_for(self.connection_profile.retry_duration()).await;
                         continue;
                     }
                     Err(BitcoinError::JsonRpc(JsonRpcError::Json(err)))
@@ -216,7 +637,7 @@ impl BitcoinCore {
                     {
                         // invalid response, can happen if server is in shutdown
                         trace!("bitcoin-core gave an invalid response: {}", err);
-                        delay_for(RETRY_DURATION).await;
+                        delay_for(self.connection_profile.retry_duration()).await;
                         continue;
                     }
                     Ok(_) => {
@@ -234,7 +655,7 @@ impl BitcoinCore {
     pub async fn sync(&self) -> Result<(), Error> {
         info!("Waiting for bitcoin-core to sync...");
         loop {
-            let info = self.rpc.get_blockchain_info()?;
+            let info = self.async_rpc().get_blockchain_info().await?;
             // NOTE: initial_block_download is always true on regtest
             if !info.initial_block_download || info.verification_progress.eq(&1.0) {
                 info!("Synced!");
@@ -245,8 +666,9 @@ impl BitcoinCore {
         }
     }
 
-    /// Wrapper of rust_bitcoincore_rpc::create_raw_transaction_hex that accepts an optional op_return
-    fn create_raw_transaction_hex(
+    /// Wrapper of rust_bitcoincore_rpc::create_raw_transaction_hex that accepts an optional op_return,
+    /// the locktime and sequence numbers are set according to the transaction policy
+    async fn create_raw_transaction_hex(
         &self,
         address: String,
         amount: Amount,
@@ -261,17 +683,766 @@ impl BitcoinCore {
             outputs.insert("data".to_string(), serde_json::Value::from(request_id.to_hex()));
         }
 
+        let lock_time = match self.transaction_policy.lock_time {
+            LockTimePolicy::Zero => 0,
+            LockTimePolicy::CurrentHeight => self
+                .transaction_policy
+                .lock_time(self.async_rpc().get_block_count().await?),
+        };
+
         let args = [
             serde_json::to_value::<&[json::CreateRawTransactionInput]>(&[])?,
             serde_json::to_value(outputs)?,
+            serde_json::to_value(lock_time)?,
+            serde_json::to_value(self.transaction_policy.replaceable)?,
+        ];
+        Ok(self.async_rpc().call("createrawtransaction", &args).await?)
+    }
+
+    /// Derive the private key for the master public key and public secret, the
+    /// master key must be owned by the wallet.
+    ///
+    /// # Arguments
+    /// * `public_key` - master public key of the vault
+    /// * `secret_key` - public secret of the deposit (derived from the request id)
+    pub async fn derive_deposit_key<P: Into<[u8; PUBLIC_KEY_SIZE]>>(
+        &self,
+        public_key: P,
+        secret_key: Vec<u8>,
+    ) -> Result<PrivateKey, Error> {
+        let address = Address::p2wpkh(&PublicKey::from_slice(&public_key.into())?, self.network)
+            .map_err(ConversionError::from)?;
+        let private_key = self.async_rpc().dump_private_key(&address).await?;
+        let deposit_secret_key =
+            addr::calculate_deposit_secret_key(private_key.key, SecretKey::from_slice(&secret_key)?)?;
+        Ok(PrivateKey {
+            compressed: private_key.compressed,
+            network: self.network,
+            key: deposit_secret_key,
+        })
+    }
+
+    /// Import multiple private keys, rescanning the chain only once afterwards.
+    ///
+    /// # Arguments
+    /// * `private_keys` - keys to import
+    /// * `rescan_start_height` - height from which to rescan for transactions of these keys
+    pub async fn import_private_keys(
+        &self,
+        private_keys: &[PrivateKey],
+        rescan_start_height: usize,
+    ) -> Result<(), Error> {
+        for private_key in private_keys {
+            self.with_wallet(|| async { Ok(self.async_rpc().import_private_key(private_key).await?) })
+                .await?;
+        }
+        self.rescan_blockchain(rescan_start_height).await
+    }
+
+    /// Import the addresses as watch-only under the given label into the watch-only wallet
+    /// `<wallet_name>-watch-only`, without rescanning the chain, so that bitcoind filters
+    /// their transactions from now on (e.g. those of other vaults for theft monitoring).
+    /// Legacy wallets import them with `importmulti`, descriptor wallets with
+    /// `importdescriptors`.
+    ///
+    /// # Arguments
+    /// * `addresses` - addresses to watch
+    /// * `label` - label of the imported addresses in the wallet
+    pub async fn import_watch_only_addresses<A: PartialAddress>(
+        &self,
+        addresses: Vec<A>,
+        label: &str,
+    ) -> Result<(), Error> {
+        if addresses.is_empty() {
+            return Ok(());
+        }
+        let watch_only_client = match (&self.wallet_name, &self.watch_only_client) {
+            (Some(wallet_name), Some(watch_only_client)) => {
+                self.create_or_load_watch_only_wallet(watch_only_wallet_name(wallet_name))
+                    .await?;
+                watch_only_client.get_async()
+            }
+            _ => return Err(Error::WalletNotFound),
+        };
+        let addresses = addresses
+            .iter()
+            .map(|address| address.encode_str(self.network))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let requests: Vec<_> = addresses
+            .iter()
+            .map(|address| ImportMultiRequest::new(address, label))
+            .collect();
+        let result: Result<Vec<ImportResult>, Error> = watch_only_client
+            .call(
+                "importmulti",
+                &[serde_json::to_value(&requests)?, serde_json::json!({ "rescan": false })],
+            )
+            .await
+            .map_err(Error::from);
+        match result {
+            Ok(results) => return check_import_results(results),
+            // descriptor wallets only support `importdescriptors`
+            Err(err) if err.is_wallet_error() => {}
+            Err(err) => return Err(err),
+        }
+
+        let mut requests = Vec::with_capacity(addresses.len());
+        for address in &addresses {
+            // the descriptor must include its checksum
+            let info: DescriptorInfo = self
+                .async_rpc()
+                .call("getdescriptorinfo", &[format!("addr({})", address).into()])
+                .await?;
+            requests.push(ImportDescriptorRequest::new(info.descriptor, label));
+        }
+        let results: Vec<ImportResult> = watch_only_client
+            .call("importdescriptors", &[serde_json::to_value(&requests)?])
+            .await?;
+        check_import_results(results)
+    }
+
+    /// Get the progress of the wallet rescan, `None` if no rescan is running.
+    pub async fn get_scan_progress(&self) -> Result<Option<ScanProgress>, Error> {
+        let info: GetWalletInfoScanning = self
+            .with_wallet(|| async { Ok(self.async_rpc().call("getwalletinfo", &[]).await?) })
+            .await?;
+        Ok(info.progress())
+    }
+
+    /// Stream of the progress of wallet rescans made by this client, updated every
+    /// `SCAN_PROGRESS_INTERVAL` and reset to `None` when a rescan ends.
+    pub fn subscribe_scan_progress(&self) -> watch::Receiver<Option<ScanProgress>> {
+        self.scan_progress_rx.clone()
+    }
+
+    /// Rescan the chain from the given height for transactions of the wallet. The rescan
+    /// can take hours, so `on_progress` is called with its progress while it is running.
+    pub async fn rescan_blockchain_with_progress<F: FnMut(ScanProgress)>(
+        &self,
+        start_height: usize,
+        mut on_progress: F,
+    ) -> Result<(), Error> {
+        let client = self.rpc();
+        // the request only returns once the rescan is complete
+        let mut rescan = tokio::task::spawn_blocking(move || client.rescan_blockchain(Some(start_height), None));
+        let result = loop {
+            match timeout(SCAN_PROGRESS_INTERVAL, &mut rescan).await {
+                Ok(result) => break result,
+                Err(_) => match self.get_scan_progress().await {
+                    Ok(Some(progress)) => {
+                        let _ = self.scan_progress_tx.broadcast(Some(progress));
+                        on_progress(progress);
+                    }
+                    Ok(None) => {}
+                    Err(err) => trace!("Failed to get rescan progress: {}", err),
+                },
+            }
+        };
+        let _ = self.scan_progress_tx.broadcast(None);
+        result??;
+        Ok(())
+    }
+
+    /// List the entries of all transactions of the wallet, one page at a time.
+    async fn list_all_transactions(&self) -> Result<Vec<json::ListTransactionResult>, Error> {
+        let mut entries = Vec::new();
+        loop {
+            let page = self
+                .async_rpc()
+                .list_transactions(LIST_TRANSACTIONS_PAGE_SIZE, entries.len())
+                .await?;
+            let last_page = page.len() < LIST_TRANSACTIONS_PAGE_SIZE;
+            entries.extend(page);
+            if last_page {
+                return Ok(entries);
+            }
+        }
+    }
+
+    /// Get the transactions sent by the wallet which include an OP_RETURN,
+    /// i.e. the payments made for redeem, replace and refund requests.
+    pub async fn get_outgoing_payments(&self) -> Result<Vec<(Txid, H256)>, Error> {
+        // a transaction is listed once per output, so remove duplicates
+        let mut seen = HashSet::new();
+        let txids = self
+            .list_all_transactions()
+            .await?
+            .into_iter()
+            .filter(|entry| entry.detail.category == json::GetTransactionResultDetailCategory::Send)
+            .map(|entry| entry.info.txid)
+            .filter(|txid| seen.insert(*txid))
+            .collect::<Vec<_>>();
+
+        let mut payments = Vec::new();
+        for txid in txids {
+            let transaction = self.async_rpc().get_transaction(&txid).await?.transaction()?;
+            if let Some(request_id) = transaction.get_op_return() {
+                payments.push((txid, request_id));
+            }
+        }
+        Ok(payments)
+    }
+
+    /// All transactions sent or received by the wallet, e.g. the payments of redeem requests
+    /// and the deposits of issue requests.
+    pub async fn get_wallet_transactions(&self) -> Result<Vec<Transaction>, Error> {
+        // a transaction is listed once per output, so remove duplicates
+        let mut seen = HashSet::new();
+        let txids = self
+            .list_all_transactions()
+            .await?
+            .into_iter()
+            .map(|entry| entry.info.txid)
+            .filter(|txid| seen.insert(*txid))
+            .collect::<Vec<_>>();
+
+        let mut transactions = Vec::with_capacity(txids.len());
+        for txid in txids {
+            transactions.push(self.async_rpc().get_transaction(&txid).await?.transaction()?);
+        }
+        Ok(transactions)
+    }
+
+    /// Add an address to the pool of change addresses used when funding transactions. The
+    /// caller is responsible for making sure the address is registered on the parachain,
+    /// otherwise the change output would be reported as theft.
+    ///
+    /// # Arguments
+    /// * `address` - unused address owned by the wallet
+    pub async fn add_change_address<A: PartialAddress + Send + 'static>(&self, address: A) -> Result<(), Error> {
+        let address = Address::from_str(&address.encode_str(self.network)?).map_err(ConversionError::from)?;
+        self.change_addresses.lock().await.push_back(address);
+        Ok(())
+    }
+
+    /// Add those of the addresses to the pool of change addresses that belong to the wallet and
+    /// have never received any funds, e.g. the registered change addresses that were still in the
+    /// pool when the vault was restarted. Returns the number of added addresses.
+    ///
+    /// # Arguments
+    /// * `addresses` - addresses registered on the parachain
+    pub async fn restore_change_addresses<A: PartialAddress + Send + 'static>(
+        &self,
+        addresses: Vec<A>,
+    ) -> Result<usize, Error> {
+        let mut unused = Vec::new();
+        for address in addresses {
+            let address = Address::from_str(&address.encode_str(self.network)?).map_err(ConversionError::from)?;
+            if !self.is_mine(&address.script_pubkey()).await {
+                continue;
+            }
+            let received = self.async_rpc().get_received_by_address(&address, Some(0)).await?;
+            if received.as_sat() == 0 {
+                unused.push(address);
+            }
+        }
+
+        let mut change_addresses = self.change_addresses.lock().await;
+        unused.retain(|address| !change_addresses.contains(address));
+        let restored = unused.len();
+        change_addresses.extend(unused);
+        Ok(restored)
+    }
+
+    /// Number of change addresses that have not been used yet.
+    pub async fn change_address_pool_size(&self) -> usize {
+        self.change_addresses.lock().await.len()
+    }
+
+    /// Get the serialized block, to be parsed with `RawBlock`.
+    pub async fn get_raw_block(&self, hash: &BlockHash) -> Result<Vec<u8>, Error> {
+        let hex: String = self
+            .async_rpc()
+            .call("getblock", &[serde_json::to_value(hash)?, 0.into()])
+            .await?;
+        Ok(hex::decode(hex).map_err(ConversionError::from)?)
+    }
+
+    /// Get the package details of an unconfirmed transaction, `None` if it is not in the mempool.
+    pub async fn get_mempool_entry(&self, txid: &Txid) -> Result<Option<MempoolEntry>, Error> {
+        match self
+            .async_rpc()
+            .call::<GetMempoolEntryResult>("getmempoolentry", &[serde_json::to_value(txid)?])
+            .await
+        {
+            Ok(entry) => Ok(Some(entry.into_entry()?)),
+            Err(err) if err_not_in_mempool(&err) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Ensure that the transaction does not exceed the mempool package limits through
+    /// the unconfirmed transactions whose outputs it spends (e.g. our own change).
+    async fn check_mempool_limits(&self, transaction: &Transaction) -> Result<(), Error> {
+        let parent_txids: HashSet<_> = transaction
+            .input
+            .iter()
+            .map(|input| input.previous_output.txid)
+            .collect();
+        let mut parents = Vec::new();
+        for txid in parent_txids {
+            if let Some(entry) = self.get_mempool_entry(&txid).await? {
+                parents.push(entry);
+            }
+        }
+        let vsize = vsize(transaction);
+        self.mempool_limits.check(&parents, vsize)
+    }
+
+    /// Get the mempool transaction spending the given output of the wallet, `None` if it is
+    /// unspent or only spent in a block. Uses `gettxspendingprevout` on nodes that support it
+    /// (Bitcoin Core 24+), otherwise the unconfirmed transactions of the wallet are checked.
+    pub async fn get_spending_tx(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        Ok(self.get_spending_txs(&[*outpoint]).await?.remove(outpoint))
+    }
+
+    /// Get the mempool transactions spending any of the given outputs of the wallet, see
+    /// `get_spending_tx`.
+    pub async fn get_spending_txs(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, Txid>, Error> {
+        if outpoints.is_empty() {
+            return Ok(HashMap::new());
+        }
+        if !self.spending_prevout_unsupported.load(Ordering::SeqCst) {
+            let prevouts: Vec<_> = outpoints.iter().copied().map(Prevout::from).collect();
+            match self
+                .async_rpc()
+                .call::<Vec<SpendingPrevoutResult>>("gettxspendingprevout", &[serde_json::to_value(prevouts)?])
+                .await
+            {
+                Ok(result) => {
+                    return Ok(result
+                        .into_iter()
+                        .filter_map(|entry| Some((OutPoint::new(entry.txid, entry.vout), entry.spendingtxid?)))
+                        .collect())
+                }
+                Err(err) if err_method_not_found(&err) => {
+                    info!("gettxspendingprevout is not supported, checking the wallet transactions instead");
+                    self.spending_prevout_unsupported.store(true, Ordering::SeqCst);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        // the wallet knows every transaction spending its outputs, so only its unconfirmed
+        // transactions need to be checked instead of the whole mempool
+        let tip = self.async_rpc().get_best_block_hash().await?;
+        let unconfirmed: ListSinceBlockResult = self
+            .with_wallet(|| async {
+                Ok(self
+                    .async_rpc()
+                    .call("listsinceblock", &[serde_json::to_value(tip)?])
+                    .await?)
+            })
+            .await?;
+        let outpoints: HashSet<_> = outpoints.iter().collect();
+        let mut spending_txs = HashMap::new();
+        for txid in unconfirmed.txids() {
+            let transaction = self.async_rpc().get_transaction(&txid).await?.transaction()?;
+            for input in &transaction.input {
+                if outpoints.contains(&input.previous_output) {
+                    spending_txs.insert(input.previous_output, transaction.txid());
+                }
+            }
+        }
+        Ok(spending_txs)
+    }
+
+    /// Get the outputs of the wallet that are unspent, including unconfirmed ones.
+    pub async fn list_unspent_outpoints(&self) -> Result<Vec<OutPoint>, Error> {
+        let unspent: Vec<Prevout> = self
+            .with_wallet(|| async { Ok(self.async_rpc().call("listunspent", &[0.into()]).await?) })
+            .await?;
+        Ok(unspent.into_iter().map(Into::into).collect())
+    }
+
+    /// True if the transaction was broadcast by this client, also if it was malleated since.
+    /// Transactions not broadcast under this txid are looked up in the wallet to compare their
+    /// contents.
+    pub async fn is_own_transaction(&self, txid: &Txid) -> bool {
+        if self.sent_transactions.lock().await.contains(txid) {
+            return true;
+        }
+        // spends of wallet outputs are wallet transactions, so this does not require `-txindex`
+        let transaction = match self.async_rpc().get_transaction(txid).await {
+            Ok(result) => result.transaction().ok(),
+            Err(_) => None,
+        };
+        let transaction = match transaction {
+            Some(transaction) => transaction,
+            None => return false,
+        };
+        self.sent_transactions
+            .lock()
+            .await
+            .sent_version_of(&transaction)
+            .is_some()
+    }
+
+    /// Get the balances of the wallet by confirmation status.
+    pub async fn get_balances(&self) -> Result<WalletBalances, Error> {
+        let result: GetBalancesResult = self
+            .with_wallet(|| async { Ok(self.async_rpc().call("getbalances", &[]).await?) })
+            .await?;
+        result.into_balances()
+    }
+
+    /// Get the unspent outputs matching the descriptors (e.g. `addr(<address>)`, see
+    /// `address_descriptor`) from the UTXO set. This does not depend on the wallet, so it
+    /// can be used to audit the funds of any vault. The scan takes a while and bitcoind runs
+    /// only one at a time.
+    pub async fn scan_utxo_set(&self, descriptors: &[String]) -> Result<Vec<Utxo>, Error> {
+        let client = self.rpc();
+        let args = ["start".into(), serde_json::to_value(descriptors)?];
+        // the request only returns once the scan is complete
+        let result: ScanTxOutSetResult =
+            tokio::task::spawn_blocking(move || client.call("scantxoutset", &args)).await??;
+        result.into_utxos()
+    }
+
+    /// Creates a transaction like `create_transaction_with_fee_estimation`. If `subtract_fee`
+    /// is set, the fee is deducted from the paid amount, which must then be the first output.
+    async fn fund_transaction<A: PartialAddress + Send + Sync + 'static>(
+        &self,
+        address: A,
+        sat: u64,
+        request_id: Option<H256>,
+        fee_estimation: FeeEstimation,
+        subtract_fee: bool,
+    ) -> Result<LockedTransaction, Error> {
+        self.ensure_spends_allowed()?;
+        // take the next change address from the pool so that it is never reused, if the
+        // pool is empty bitcoind picks a new change address which the caller needs to register
+        let change_address = self.change_addresses.lock().await.pop_front();
+        let mut fund_options = json::FundRawTransactionOptions {
+            change_address: change_address.clone(),
+            replaceable: Some(self.transaction_policy.replaceable),
+            // lock the selected outputs so that concurrently funded transactions use other outputs
+            lock_unspents: Some(true),
+            subtract_fee_from_outputs: if subtract_fee { Some(vec![0]) } else { None },
+            ..Default::default()
+        };
+        fee_estimation.or(self.fee_estimation).apply(&mut fund_options);
+        self.release_settled_reservations().await;
+
+        let result = self
+            .with_wallet(|| async {
+                let address_string = address.encode_str(self.network)?;
+                // the address is encoded for our network, only the script chosen by the redeemer can be invalid
+                let destination = Address::from_str(&address_string).map_err(ConversionError::from)?;
+                validate_script(&destination.script_pubkey())?;
+
+                // create raw transaction that includes the op_return (if any). If we were to add the op_return
+                // after funding, the fees might be insufficient. An alternative to our own version of
+                // this function would be to call create_raw_transaction (without the _hex suffix), and
+                // to add the op_return afterwards. However, this function fails if no inputs are
+                // specified, as is the case for us prior to calling fund_raw_transaction.
+                let raw_tx = self
+                    .create_raw_transaction_hex(address_string.clone(), Amount::from_sat(sat), request_id)
+                    .await?;
+
+                let mut retries = 0;
+                loop {
+                    // fund the transaction: adds required inputs, and possibly a return-to-self output
+                    let funded_raw_tx = self
+                        .async_rpc()
+                        .fund_raw_transaction(raw_tx.as_str(), &fund_options)
+                        .await?;
+
+                    // the inputs are locked until the transaction is broadcast, or unlocked when
+                    // the reservation is dropped (e.g. if any of the following steps fail)
+                    let reservation = self.utxo_reservations.reserve(
+                        self.decode_funded_transaction(&funded_raw_tx)
+                            .await?
+                            .input
+                            .iter()
+                            .map(|input| input.previous_output)
+                            .collect(),
+                    );
+
+                    // sign the transaction
+                    let signed_funded_raw_tx = self
+                        .async_rpc()
+                        .sign_raw_transaction_with_wallet(&funded_raw_tx.transaction()?)
+                        .await?;
+
+                    // Make sure signing is successful
+                    if signed_funded_raw_tx.errors.is_some() {
+                        return Err(Error::TransactionSigningError);
+                    }
+
+                    let transaction = signed_funded_raw_tx.transaction()?;
+
+                    // check the fee rate of the signed transaction, the witness counts towards the vsize
+                    if let Some(max_fee_rate) = self.max_fee_rate {
+                        max_fee_rate.check(&transaction, funded_raw_tx.fee)?;
+                    }
+
+                    // a transaction above the limits would not be relayed, or not be provable on the parachain
+                    self.transaction_limits.check(&transaction)?;
+
+                    // bitcoind may pick our unconfirmed change, make sure the result is accepted
+                    match self.check_mempool_limits(&transaction).await {
+                        Ok(()) => {
+                            return Ok(LockedTransaction::new(transaction, address_string, Some(reservation))
+                                .with_fee(funded_raw_tx.fee))
+                        }
+                        Err(Error::MempoolChainTooLong(reason)) if retries < MEMPOOL_CHAIN_MAX_RETRIES => {
+                            retries += 1;
+                            drop(reservation);
+                            log::warn!(
+                                "Not sending to {}, {} - waiting for confirmations",
+                                address_string,
+                                reason
+                            );
+                            delay_for(MEMPOOL_CHAIN_RETRY_DELAY).await;
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+            })
+            .await;
+
+        // the change address is returned to the pool if funding failed or the transaction has no change
+        if let Some(change_address) = change_address {
+            let script_pubkey = change_address.script_pubkey();
+            let has_change = matches!(&result, Ok(locked) if locked
+                .transaction
+                .output
+                .iter()
+                .any(|output| output.script_pubkey == script_pubkey));
+            if !has_change {
+                self.change_addresses.lock().await.push_front(change_address);
+            }
+        }
+        result
+    }
+
+    /// Decode the funded transaction. Its inputs were locked by `fundrawtransaction` but are
+    /// not reserved yet, so they are unlocked if decoding fails.
+    async fn decode_funded_transaction(
+        &self,
+        funded_raw_tx: &json::FundRawTransactionResult,
+    ) -> Result<Transaction, Error> {
+        let err = match funded_raw_tx.transaction() {
+            Ok(transaction) => return Ok(transaction),
+            Err(err) => err,
+        };
+        let hex = funded_raw_tx.hex.to_hex();
+        match self.async_rpc().decode_raw_transaction(&hex).await {
+            Ok(decoded) => {
+                let outpoints: Vec<_> = decoded
+                    .vin
+                    .iter()
+                    .filter_map(|vin| Some(OutPoint::new(vin.txid?, vin.vout?)))
+                    .collect();
+                if let Err(err) = self.async_rpc().unlock_unspent(&outpoints).await {
+                    log::warn!("Failed to unlock the inputs of the funded transaction: {}", err);
+                }
+            }
+            Err(err) => log::warn!("Failed to decode the funded transaction to unlock its inputs: {}", err),
+        }
+        Err(err.into())
+    }
+
+    /// Release the reservations of the broadcast transactions that have left the mempool,
+    /// i.e. were confirmed or evicted.
+    async fn release_settled_reservations(&self) {
+        for txid in self.utxo_reservations.broadcast_txids() {
+            match self.get_mempool_entry(&txid).await {
+                Ok(None) => self.utxo_reservations.release_broadcast(&txid),
+                Ok(Some(_)) => {}
+                Err(err) => log::warn!("Failed to check whether {} is still in the mempool: {}", txid, err),
+            }
+        }
+    }
+
+    /// Whether the script belongs to the wallet, i.e. an output to it returns funds to the
+    /// wallet. Failing to ask the wallet is logged and treated as not belonging to it.
+    async fn is_mine(&self, script_pubkey: &Script) -> bool {
+        let address = match Address::from_script(script_pubkey, self.network) {
+            Some(address) => address,
+            None => return false,
+        };
+        match self
+            .async_rpc()
+            .call::<json::GetAddressInfoResult>("getaddressinfo", &[address.to_string().into()])
+            .await
+        {
+            Ok(info) => info.is_mine.unwrap_or_default(),
+            Err(err) => {
+                log::warn!("Failed to check whether {} belongs to the wallet: {}", address, err);
+                false
+            }
+        }
+    }
+
+    /// Send an amount that is not bound to a request (e.g. a sweep), split over several
+    /// transactions if a single one would exceed the transaction limits. Without a deadline,
+    /// the fee is estimated economically. The fee of each
+    /// transaction is deducted from its part, so that exactly `sat` leaves the wallet. Payments
+    /// for a request can not be split, since the parachain expects a single transaction.
+    pub async fn send_split<A: PartialAddress + Clone + Send + Sync + 'static>(
+        &self,
+        address: A,
+        sat: u64,
+    ) -> Result<Vec<Txid>, Error> {
+        let mut pending = vec![sat];
+        let mut txids = Vec::new();
+        while let Some(amount) = pending.pop() {
+            let transaction = self
+                .fund_transaction(address.clone(), amount, None, FeeEstimation::economical(), true)
+                .await;
+            match transaction {
+                Ok(transaction) => txids.push(self.send_transaction(transaction).await?),
+                Err(err)
+                    if err.is_transaction_too_large()
+                        && amount > 1
+                        && txids.len() + pending.len() + 2 <= MAX_PAYMENT_PARTS =>
+                {
+                    log::info!("Splitting payment of {} sat: {}", amount, err);
+                    pending.push(amount / 2);
+                    pending.push(amount - amount / 2);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(txids)
+    }
+
+    /// Append the fee rate and the mempool conditions of a broadcast transaction to the fee
+    /// history file, if any. Failing to query the mempool does not fail the broadcast, the
+    /// conditions are left empty instead.
+    async fn record_broadcast(&self, transaction: &Transaction, fee: Option<Amount>) {
+        let path = match &self.fee_history_file {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let txid = transaction.txid();
+        let fee = match fee {
+            Some(fee) => Some(fee),
+            // not funded by us, e.g. a replacement, but bitcoind knows its fee
+            None => match self.get_mempool_entry(&txid).await {
+                Ok(entry) => entry.map(|entry| Amount::from_sat(entry.fee)),
+                Err(err) => {
+                    log::warn!("Failed to get the fee of {}: {}", txid, err);
+                    None
+                }
+            },
+        };
+        let fee_rate = fee.map(|fee| fee_rate(fee, vsize(transaction)));
+        let mempool = match self.mempool_conditions().await {
+            Ok(mempool) => Some(mempool),
+            Err(err) => {
+                log::warn!("Failed to record the mempool conditions of {}: {}", txid, err);
+                None
+            }
+        };
+        let record = BroadcastRecord::new(transaction, fee_rate, mempool, SystemTime::now());
+        let result = tokio::task::spawn_blocking(move || record_broadcast(&path, &record))
+            .await
+            .map_err(Error::from)
+            .and_then(|result| result);
+        if let Err(err) = result {
+            log::warn!("Failed to record the broadcast of {}: {}", txid, err);
+        }
+    }
+
+    /// Get the fee rate needed for inclusion in the next block and the size of the mempool,
+    /// without fetching the whole mempool as `mempool_fee_histogram` does.
+    async fn mempool_conditions(&self) -> Result<MempoolConditions, Error> {
+        let info: GetMempoolInfoResult = self.async_rpc().call("getmempoolinfo", &[]).await?;
+        let next_block = FeeEstimation {
+            mode: None,
+            conf_target: Some(1),
+        };
+        let next_block_fee_rate = match self.estimate_fee_rate(next_block).await? {
+            Some(fee_rate) => fee_rate,
+            // no estimate yet, e.g. shortly after bitcoind started
+            None => btc_to_sat(info.mempoolminfee)?.saturating_add(999) / 1000,
+        };
+        Ok(MempoolConditions::new(next_block_fee_rate, info.bytes))
+    }
+
+    /// Get the distribution of the mempool by fee rate, from which the congestion
+    /// and the fee rate needed for timely inclusion can be derived.
+    pub async fn mempool_fee_histogram(&self) -> Result<FeeHistogram, Error> {
+        let entries: HashMap<String, VerboseMempoolEntry> =
+            self.async_rpc().call("getrawmempool", &[true.into()]).await?;
+        entries
+            .into_iter()
+            .map(|(_, entry)| Ok((btc_to_sat(entry.fees.base)?, entry.vsize)))
+            .collect::<Result<Vec<_>, Error>>()
+            .map(FeeHistogram::from_entries)
+    }
+
+    /// Estimate the fee rate (sat/vbyte) for the transaction to confirm within the target,
+    /// with the parameters not set taken from the configured fee estimation. Returns `None`
+    /// if bitcoind does not have enough data for an estimate, e.g. shortly after it started.
+    pub async fn estimate_fee_rate(&self, fee_estimation: FeeEstimation) -> Result<Option<u64>, Error> {
+        let fee_estimation = fee_estimation.or(self.fee_estimation);
+        let mut params = vec![fee_estimation.conf_target.unwrap_or(DEFAULT_CONF_TARGET).into()];
+        if let Some(mode) = fee_estimation.mode {
+            params.push(serde_json::to_value(mode)?);
+        }
+        let estimate: json::EstimateSmartFeeResult = self.async_rpc().call("estimatesmartfee", &params).await?;
+        // the estimate is given in BTC/kvbyte
+        Ok(estimate
+            .fee_rate
+            .map(|fee_rate| fee_rate.as_sat().saturating_add(999) / 1000))
+    }
+
+    /// Get a transaction together with the outputs spent by its inputs, e.g. to
+    /// compute its fee or classify its spends. Nodes supporting `getrawtransaction`
+    /// verbosity 2 return the prevouts directly, otherwise the previous transactions
+    /// are looked up individually (which requires `-txindex` for confirmed transactions).
+    ///
+    /// # Arguments
+    /// * `txid` - transaction ID
+    /// * `block_hash` - hash of the block tx is stored in, if known
+    pub async fn get_transaction_with_prevouts(
+        &self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> Result<TransactionWithPrevouts, Error> {
+        let args = [
+            serde_json::to_value(txid)?,
+            serde_json::to_value(2)?,
+            serde_json::to_value(block_hash)?,
         ];
-        Ok(self.rpc.call("createrawtransaction", &args)?)
+        let verbose_transaction: VerboseTransaction = self.async_rpc().call("getrawtransaction", &args).await?;
+        let (transaction, prevouts) = verbose_transaction.decode()?;
+        let prevouts = match prevouts {
+            Some(prevouts) => prevouts,
+            None => self.lookup_prevouts(&transaction).await?,
+        };
+        Ok(TransactionWithPrevouts { transaction, prevouts })
     }
 
-    #[cfg(feature = "regtest-manual-mining")]
-    pub fn mine_block(&self) -> Result<(), Error> {
-        self.rpc
-            .generate_to_address(1, &self.rpc.get_new_address(None, Some(AddressType::Bech32))?)?;
+    /// Fallback for nodes that do not report prevouts, fetches each distinct
+    /// previous transaction once.
+    async fn lookup_prevouts(&self, transaction: &Transaction) -> Result<Vec<Option<TxOut>>, Error> {
+        let mut previous_transactions = HashMap::<Txid, Transaction>::new();
+        let mut prevouts = Vec::with_capacity(transaction.input.len());
+        for input in &transaction.input {
+            let outpoint = input.previous_output;
+            let previous_transaction = match previous_transactions.entry(outpoint.txid) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.async_rpc().get_raw_transaction(&outpoint.txid, None).await?),
+            };
+            let prevout = previous_transaction
+                .output
+                .get(outpoint.vout as usize)
+                .cloned()
+                .ok_or(Error::PrevoutNotFound)?;
+            prevouts.push(Some(prevout));
+        }
+        Ok(prevouts)
+    }
+
+    #[cfg(any(feature = "regtest-manual-mining", feature = "regtest-mine-on-tx"))]
+    pub async fn mine_block(&self) -> Result<(), Error> {
+        let address = self.async_rpc().get_new_address(AddressType::Bech32).await?;
+        self.async_rpc().generate_to_address(1, &address).await?;
         Ok(())
     }
 
@@ -284,7 +1455,7 @@ impl BitcoinCore {
         F: Fn() -> R,
         R: Future<Output = Result<T, Error>>,
     {
-        let mut backoff = get_exponential_backoff();
+        let mut backoff = self.connection_profile.exponential_backoff();
         loop {
             let err = match call().await.map_err(Error::from) {
                 Err(inner) if inner.is_wallet_not_found() => {
@@ -306,6 +1477,22 @@ impl BitcoinCore {
     }
 }
 
+/// Name of the watch-only wallet kept alongside the wallet of the vault.
+fn watch_only_wallet_name(wallet_name: &str) -> String {
+    format!("{}-watch-only", wallet_name)
+}
+
+/// true if the node does not know the called method, e.g. because it is too old
+fn err_method_not_found(err: &bitcoincore_rpc::Error) -> bool {
+    matches!(
+        err,
+        &bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(RpcError {
+            code: METHOD_NOT_FOUND_ERROR_CODE,
+            ..
+        }))
+    )
+}
+
 /// true if the given indicates that the item was not found in the mempool
 fn err_not_in_mempool(err: &bitcoincore_rpc::Error) -> bool {
     matches!(
@@ -327,11 +1514,11 @@ impl BitcoinCoreApi for BitcoinCore {
     /// * `num_confirmations` - minimum for a block to be accepted
     async fn wait_for_block(&self, height: u32, num_confirmations: u32) -> Result<Block, Error> {
         loop {
-            match self.rpc.get_block_hash(height.into()) {
+            match self.async_rpc().get_block_hash(height.into()).await {
                 Ok(hash) => {
-                    let info = self.rpc.get_block_info(&hash)?;
+                    let info = self.async_rpc().get_block_info(&hash).await?;
                     if info.confirmations >= num_confirmations {
-                        return Ok(self.rpc.get_block(&hash)?);
+                        return Ok(self.async_rpc().get_block(&hash).await?);
                     } else {
                         delay_for(RETRY_DURATION).await;
                         continue;
@@ -351,7 +1538,9 @@ impl BitcoinCoreApi for BitcoinCore {
 
     /// Get the tip of the main chain as reported by Bitcoin core.
     async fn get_block_count(&self) -> Result<u64, Error> {
-        Ok(self.rpc.get_block_count()?)
+        let count = self.async_rpc().get_block_count().await?;
+        self.header_store.set_tip(count as u32);
+        Ok(count)
     }
 
     /// Get the raw transaction identified by `Txid` and stored
@@ -361,16 +1550,21 @@ impl BitcoinCoreApi for BitcoinCore {
     /// * `txid` - transaction ID
     /// * `block_hash` - hash of the block tx is stored in
     async fn get_raw_tx(&self, txid: &Txid, block_hash: &BlockHash) -> Result<Vec<u8>, Error> {
-        Ok(serialize(&self.rpc.get_raw_transaction(txid, Some(block_hash))?))
+        Ok(serialize(
+            &self.async_rpc().get_raw_transaction(txid, Some(block_hash)).await?,
+        ))
     }
 
-    /// Get the merkle proof which can be used to validate transaction inclusion.
+    /// Get the merkle proof which can be used to validate transaction inclusion. The proof
+    /// is verified locally, so that an invalid proof is never submitted to the parachain.
     ///
     /// # Arguments
     /// * `txid` - transaction ID
     /// * `block_hash` - hash of the block tx is stored in
     async fn get_proof(&self, txid: Txid, block_hash: &BlockHash) -> Result<Vec<u8>, Error> {
-        Ok(self.rpc.get_tx_out_proof(&[txid], Some(block_hash))?)
+        let proof = self.async_rpc().get_tx_out_proof(&[txid], Some(block_hash)).await?;
+        verify_proof(&proof, &txid, block_hash)?;
+        Ok(proof)
     }
 
     /// Get the block hash for a given height.
@@ -378,8 +1572,14 @@ impl BitcoinCoreApi for BitcoinCore {
     /// # Arguments
     /// * `height` - block height
     async fn get_block_hash(&self, height: u32) -> Result<BlockHash, Error> {
-        match self.rpc.get_block_hash(height.into()) {
-            Ok(block_hash) => Ok(block_hash),
+        if let Some(block_hash) = self.header_store.hash_at(height).await {
+            return Ok(block_hash);
+        }
+        match self.async_rpc().get_block_hash(height.into()).await {
+            Ok(block_hash) => {
+                self.header_store.insert_hash(height, block_hash).await;
+                Ok(block_hash)
+            }
             Err(BitcoinError::JsonRpc(JsonRpcError::Rpc(err)))
                 if BitcoinRpcError::from(err.clone()) == BitcoinRpcError::RpcInvalidParameter =>
             {
@@ -394,8 +1594,28 @@ impl BitcoinCoreApi for BitcoinCore {
     ///
     /// # Arguments
     /// * `block_hash` - hash of the block to verify
+    async fn is_transaction_live(&self, txid: Txid) -> Result<bool, Error> {
+        match self
+            .async_rpc()
+            .call::<json::GetTransactionResult>("gettransaction", &[serde_json::to_value(txid)?])
+            .await
+        {
+            Ok(result) if result.info.confirmations > 0 => Ok(true),
+            // conflicted, e.g. by a payment spending the same outputs
+            Ok(result) if result.info.confirmations < 0 => Ok(false),
+            Ok(_) => Ok(self.get_mempool_entry(&txid).await?.is_some()),
+            // not a wallet transaction
+            Err(err) if err_not_in_mempool(&err) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     async fn is_block_known(&self, block_hash: BlockHash) -> Result<bool, Error> {
-        match self.rpc.get_block(&block_hash) {
+        if self.header_store.header(&block_hash).await.is_some() {
+            return Ok(true);
+        }
+        // like `getblock`, this fails for blocks of which bitcoind only has the header
+        match self.async_rpc().get_block_info(&block_hash).await {
             Ok(_) => Ok(true),
             Err(BitcoinError::JsonRpc(JsonRpcError::Rpc(err)))
                 if BitcoinRpcError::from(err.clone()) == BitcoinRpcError::RpcInvalidAddressOrKey =>
@@ -408,14 +1628,14 @@ impl BitcoinCoreApi for BitcoinCore {
 
     /// Gets a new address from the wallet
     async fn get_new_address<A: PartialAddress + Send + 'static>(&self) -> Result<A, Error> {
-        let address = self.rpc.get_new_address(None, Some(AddressType::Bech32))?;
+        let address = self.async_rpc().get_new_address(AddressType::Bech32).await?;
         Ok(A::decode_str(&address.to_string())?)
     }
 
     /// Gets a new public key for an address in the wallet
     async fn get_new_public_key<P: From<[u8; PUBLIC_KEY_SIZE]> + 'static>(&self) -> Result<P, Error> {
-        let address = self.rpc.get_new_address(None, Some(AddressType::Bech32))?;
-        let address_info = self.rpc.get_address_info(&address)?;
+        let address = self.async_rpc().get_new_address(AddressType::Bech32).await?;
+        let address_info = self.async_rpc().get_address_info(&address).await?;
         let public_key = address_info.pubkey.ok_or(Error::MissingPublicKey)?;
         Ok(P::from(public_key.key.serialize()))
     }
@@ -426,38 +1646,50 @@ impl BitcoinCoreApi for BitcoinCore {
         public_key: P,
         secret_key: Vec<u8>,
     ) -> Result<(), Error> {
-        let address = Address::p2wpkh(&PublicKey::from_slice(&public_key.into())?, self.network)
-            .map_err(ConversionError::from)?;
-        let private_key = self.rpc.dump_private_key(&address)?;
-        let deposit_secret_key =
-            addr::calculate_deposit_secret_key(private_key.key, SecretKey::from_slice(&secret_key)?)?;
-        self.rpc.import_private_key(
-            &PrivateKey {
-                compressed: private_key.compressed,
-                network: self.network,
-                key: deposit_secret_key,
-            },
-            None,
-            // rescan true by default
-            Some(false),
-        )?;
-        Ok(())
+        let deposit_key = self.derive_deposit_key(public_key, secret_key).await?;
+        Ok(self.async_rpc().import_private_key(&deposit_key).await?)
     }
 
     async fn get_best_block_hash(&self) -> Result<BlockHash, Error> {
-        Ok(self.rpc.get_best_block_hash()?)
+        Ok(self.async_rpc().get_best_block_hash().await?)
     }
 
     async fn get_block(&self, hash: &BlockHash) -> Result<Block, Error> {
-        Ok(self.rpc.get_block(hash)?)
+        let block = self.async_rpc().get_block(hash).await?;
+        self.header_store.insert_header(block.header).await;
+        Ok(block)
+    }
+
+    /// Parses the block with `RawBlock`, so that only the matching transactions are deserialized.
+    async fn get_block_transactions_paying_to(
+        &self,
+        hash: &BlockHash,
+        scripts: &[Script],
+    ) -> Result<Vec<Transaction>, Error> {
+        let bytes = self.get_raw_block(hash).await?;
+        let raw_block = RawBlock::new(&bytes);
+        let scripts: Vec<&[u8]> = scripts.iter().map(|script| script.as_bytes()).collect();
+        let mut transactions = Vec::new();
+        for transaction in raw_block.transactions()? {
+            let transaction = transaction?;
+            if transaction.pays_to_any(&scripts) {
+                transactions.push(transaction.deserialize()?);
+            }
+        }
+        Ok(transactions)
     }
 
     async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader, Error> {
-        Ok(self.rpc.get_block_header(hash)?)
+        if let Some(header) = self.header_store.header(hash).await {
+            return Ok(header);
+        }
+        let header = self.async_rpc().get_block_header(hash).await?;
+        self.header_store.insert_header(header).await;
+        Ok(header)
     }
 
     async fn get_block_info(&self, hash: &BlockHash) -> Result<GetBlockResult, Error> {
-        Ok(self.rpc.get_block_info(hash)?)
+        Ok(self.async_rpc().get_block_info(hash).await?)
     }
 
     /// Get the transactions that are currently in the mempool. Since `impl trait` is not
@@ -466,20 +1698,21 @@ impl BitcoinCoreApi for BitcoinCore {
         &'a self,
     ) -> Result<Box<dyn Iterator<Item = Result<Transaction, Error>> + Send + 'a>, Error> {
         // get txids from the mempool
-        let txids = self.rpc.get_raw_mempool()?;
+        let txids = self.async_rpc().get_raw_mempool().await?;
         // map txid to the actual Transaction structs
-        let iterator = txids.into_iter().filter_map(move |txid| {
-            match self.rpc.get_raw_transaction_info(&txid, None) {
-                Ok(x) => Some(x.transaction().map_err(Into::into)),
-                Err(e) if err_not_in_mempool(&e) => None, // not in mempool anymore, so filter out
-                Err(e) => Some(Err(e.into())),            // unknown error, propagate to user
+        let mut transactions = Vec::with_capacity(txids.len());
+        for txid in txids {
+            match self.async_rpc().get_raw_transaction(&txid, None).await {
+                Ok(transaction) => transactions.push(Ok(transaction)),
+                Err(e) if err_not_in_mempool(&e) => {} // not in mempool anymore, so filter out
+                Err(e) => transactions.push(Err(e.into())), // unknown error, propagate to user
             }
-        });
-        Ok(Box::new(iterator))
+        }
+        Ok(Box::new(transactions.into_iter()))
     }
 
     /// Waits for the required number of confirmations, and collects data about the
-    /// transaction
+    /// transaction. If it was malleated, the data is that of the confirmed version.
     ///
     /// # Arguments
     /// * `txid` - transaction ID
@@ -489,89 +1722,77 @@ impl BitcoinCoreApi for BitcoinCore {
         txid: Txid,
         num_confirmations: u32,
     ) -> Result<TransactionMetadata, Error> {
-        let (block_height, block_hash) = (|| async {
-            Ok(match self.rpc.get_transaction(&txid, None) {
-                Ok(GetTransactionResult {
-                    info:
-                        WalletTxInfo {
-                            confirmations,
-                            blockhash: Some(hash),
-                            blockheight: Some(height),
-                            ..
-                        },
-                    ..
-                }) if confirmations >= 0 && confirmations as u32 >= num_confirmations => Ok((height, hash)),
-                Ok(_) => Err(Error::ConfirmationError),
-                Err(e) => Err(e.into()),
-            }?)
-        })
-        .retry(get_exponential_backoff())
-        .await?;
+        let sent_txid = txid;
+        // the txid differs from the sent one if the transaction was malleated
+        let (txid, block_height, block_hash) = timeout(
+            CONFIRMATION_TIMEOUT,
+            self.confirmations.wait(sent_txid, num_confirmations),
+        )
+        .await
+        .map_err(|_| Error::ConfirmationError)?;
 
         let proof = (|| async { Ok(self.get_proof(txid, &block_hash).await?) })
-            .retry(get_exponential_backoff())
+            .retry(self.connection_profile.exponential_backoff())
             .await?;
 
         let raw_tx = (|| async { Ok(self.get_raw_tx(&txid, &block_hash).await?) })
-            .retry(get_exponential_backoff())
+            .retry(self.connection_profile.exponential_backoff())
             .await?;
 
+        // the witness in the block may differ from the broadcast one, even if the txid does not
+        let confirmed = deserialize(&raw_tx)?;
+        let recipient = {
+            let mut sent_transactions = self.sent_transactions.lock().await;
+            if sent_transactions.contains(&sent_txid) {
+                sent_transactions.confirmed(&sent_txid, &confirmed);
+            }
+            sent_transactions.recipient(&sent_txid).cloned()
+        };
+
+        let outputs = match recipient {
+            // funding our payment only added change besides the recipient
+            Some(recipient) => OutputMetadata::new(&confirmed, |_, output| output.script_pubkey != recipient),
+            // not sent by this client, e.g. before a restart, so ask the wallet
+            None => {
+                let mut is_mine = Vec::with_capacity(confirmed.output.len());
+                for output in confirmed.output.iter() {
+                    is_mine.push(self.is_mine(&output.script_pubkey).await);
+                }
+                OutputMetadata::new(&confirmed, |index, _| is_mine[index])
+            }
+        };
+
         Ok(TransactionMetadata {
             txid,
             proof,
             raw_tx,
             block_height,
             block_hash,
+            outputs,
         })
     }
 
-    /// Creates and return a transaction; it is not submitted to the mempool. While the returned value
-    /// is alive, no other transactions can be created (this is guarded by a mutex). This prevents
-    /// accidental double spending.
+    /// Creates a transaction like `create_transaction`, estimating its fee with the given
+    /// parameters. Parameters that are not set are taken from the configured fee estimation.
     ///
     /// # Arguments
     /// * `address` - Bitcoin address to fund
     /// * `sat` - number of Satoshis to transfer
     /// * `request_id` - the issue/redeem/replace id for which this transfer is being made
-    async fn create_transaction<A: PartialAddress + Send + Sync + 'static>(
+    /// * `fee_estimation` - fee estimation parameters of this payment
+    async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
         &self,
         address: A,
         sat: u64,
         request_id: Option<H256>,
+        fee_estimation: FeeEstimation,
     ) -> Result<LockedTransaction, Error> {
-        self.with_wallet(|| async {
-            let address_string = address.encode_str(self.network)?;
-
-            // create raw transaction that includes the op_return (if any). If we were to add the op_return
-            // after funding, the fees might be insufficient. An alternative to our own version of
-            // this function would be to call create_raw_transaction (without the _hex suffix), and
-            // to add the op_return afterwards. However, this function fails if no inputs are
-            // specified, as is the case for us prior to calling fund_raw_transaction.
-            let raw_tx = self.create_raw_transaction_hex(address_string.clone(), Amount::from_sat(sat), request_id)?;
-
-            // ensure no other fund_raw_transaction calls are made until we submitted the
-            // transaction to the bitcoind. If we don't do this, the same uxto may be used
-            // as input twice (i.e. double spend)
-            let lock = self.transaction_creation_lock.clone().lock_owned().await;
-
-            // fund the transaction: adds required inputs, and possibly a return-to-self output
-            let funded_raw_tx = self.rpc.fund_raw_transaction(raw_tx, None, None)?;
-
-            // sign the transaction
-            let signed_funded_raw_tx =
-                self.rpc
-                    .sign_raw_transaction_with_wallet(&funded_raw_tx.transaction()?, None, None)?;
-
-            // Make sure signing is successful
-            if signed_funded_raw_tx.errors.is_some() {
-                return Err(Error::TransactionSigningError);
-            }
-
-            let transaction = signed_funded_raw_tx.transaction()?;
+        self.fund_transaction(address, sat, request_id, fee_estimation, false)
+            .await
+    }
 
-            Ok(LockedTransaction::new(transaction, address_string, Some(lock)))
-        })
-        .await
+    fn deadline_fee_estimation(&self) -> FeeEstimation {
+        self.deadline_fee_estimation
     }
 
     /// Submits a transaction to the mempool
@@ -579,10 +1800,56 @@ impl BitcoinCoreApi for BitcoinCore {
     /// # Arguments
     /// * `transaction` - The transaction created by create_transaction
     async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, Error> {
+        self.ensure_spends_allowed()?;
         // place the transaction into the mempool, this is fine to retry
-        let txid = self
-            .with_wallet(|| async { Ok(self.rpc.send_raw_transaction(&transaction.transaction)?) })
-            .await?;
+        let result = self
+            .with_wallet(|| async {
+                let txid = match self.max_fee_rate {
+                    Some(max_fee_rate) => {
+                        self.async_rpc()
+                            .call(
+                                "sendrawtransaction",
+                                &[
+                                    serialize(&transaction.transaction).to_hex().into(),
+                                    serde_json::to_value(max_fee_rate.btc_per_kvbyte())?,
+                                ],
+                            )
+                            .await?
+                    }
+                    None => self.async_rpc().send_raw_transaction(&transaction.transaction).await?,
+                };
+                Ok(txid)
+            })
+            .await;
+        let txid = if self.broadcaster.is_empty() {
+            result?
+        } else {
+            self.broadcaster.reconcile(&transaction.transaction, result).await?
+        };
+        if let Some(reservation) = transaction.reservation {
+            reservation.spent(txid);
+        }
+        let recipient = Address::from_str(&transaction.recipient)
+            .ok()
+            .map(|recipient| recipient.script_pubkey());
+        {
+            let mut sent_transactions = self.sent_transactions.lock().await;
+            sent_transactions.insert(&transaction.transaction);
+            if let Some(recipient) = &recipient {
+                sent_transactions.set_recipient(txid, recipient.clone());
+            }
+        }
+        if let Some(path) = self.sent_transactions_file.clone() {
+            let sent = transaction.transaction.clone();
+            let result = tokio::task::spawn_blocking(move || record_sent(&path, &sent, recipient.as_ref()))
+                .await
+                .map_err(Error::from)
+                .and_then(|result| result);
+            if let Err(err) = result {
+                log::warn!("Failed to record sent transaction {}: {}", txid, err);
+            }
+        }
+        self.record_broadcast(&transaction.transaction, transaction.fee).await;
         Ok(txid)
     }
 
@@ -623,8 +1890,7 @@ impl BitcoinCoreApi for BitcoinCore {
         let txid = self.create_and_send_transaction(address, sat, request_id).await?;
 
         #[cfg(feature = "regtest-mine-on-tx")]
-        self.rpc
-            .generate_to_address(1, &self.rpc.get_new_address(None, Some(AddressType::Bech32))?)?;
+        self.mine_block().await?;
 
         Ok(self.wait_for_transaction_metadata(txid, num_confirmations).await?)
     }
@@ -637,14 +1903,22 @@ impl BitcoinCoreApi for BitcoinCore {
             return Err(Error::WalletNotFound);
         };
 
-        // NOTE: bitcoincore-rpc does not expose listwalletdir
-        if self.rpc.list_wallets()?.contains(wallet_name) || self.rpc.load_wallet(wallet_name).is_ok() {
-            // wallet already loaded
+        if self.async_rpc().list_wallets().await?.contains(wallet_name) {
             return Ok(());
         }
-        // wallet does not exist, create
-        self.rpc.create_wallet(wallet_name, None, None, None, None)?;
-        Ok(())
+        // loading or creating a large wallet can take longer than the request timeout
+        let client = self.rpc();
+        let wallet_name = wallet_name.clone();
+        tokio::task::spawn_blocking(move || -> Result<(), Error> {
+            // NOTE: bitcoincore-rpc does not expose listwalletdir
+            if client.load_wallet(&wallet_name).is_ok() {
+                return Ok(());
+            }
+            // wallet does not exist, create
+            client.create_wallet(&wallet_name, None, None, None, None)?;
+            Ok(())
+        })
+        .await?
     }
 
     async fn wallet_has_public_key<P>(&self, public_key: P) -> Result<bool, Error>
@@ -654,7 +1928,7 @@ impl BitcoinCoreApi for BitcoinCore {
         self.with_wallet(|| async {
             let address = Address::p2wpkh(&PublicKey::from_slice(&public_key.clone().into())?, self.network)
                 .map_err(ConversionError::from)?;
-            let address_info = self.rpc.get_address_info(&address)?;
+            let address_info = self.async_rpc().get_address_info(&address).await?;
             let wallet_pubkey = address_info.pubkey.ok_or(Error::MissingPublicKey)?;
             Ok(P::from(wallet_pubkey.key.serialize()) == public_key)
         })
@@ -662,13 +1936,24 @@ impl BitcoinCoreApi for BitcoinCore {
     }
 
     async fn import_private_key(&self, privkey: PrivateKey) -> Result<(), Error> {
-        self.with_wallet(|| async { Ok(self.rpc.import_private_key(&privkey, None, None)?) })
-            .await
+        self.with_wallet(|| async {
+            // the import rescans the chain, which can take longer than the request timeout
+            let client = self.rpc();
+            Ok(tokio::task::spawn_blocking(move || client.import_private_key(&privkey, None, None)).await??)
+        })
+        .await
     }
 
     async fn rescan_blockchain(&self, start_height: usize) -> Result<(), Error> {
-        self.rpc.rescan_blockchain(Some(start_height), None)?;
-        Ok(())
+        self.rescan_blockchain_with_progress(start_height, |progress| {
+            info!(
+                "Rescanned {:.1}% of the chain in {}s, about {}s remaining",
+                progress.progress * 100.0,
+                progress.elapsed.as_secs(),
+                progress.remaining().unwrap_or_default().as_secs()
+            )
+        })
+        .await
     }
 }
 
@@ -892,4 +2177,36 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_transaction_metadata_serde() {
+        let metadata = TransactionMetadata {
+            txid: Txid::from_slice(&[1; 32]).unwrap(),
+            proof: vec![0xde, 0xad],
+            raw_tx: vec![0xbe, 0xef],
+            block_height: 100,
+            block_hash: BlockHash::from_slice(&[2; 32]).unwrap(),
+            outputs: OutputMetadata {
+                recipient: Some(OutputInfo { index: 0, value: 1000 }),
+                change: None,
+                op_return: None,
+            },
+        };
+        let json = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(json["proof"], "dead");
+        assert_eq!(json["raw_tx"], "beef");
+        assert_eq!(json["txid"], Txid::from_slice(&[1; 32]).unwrap().to_string());
+        assert_eq!(
+            serde_json::from_value::<TransactionMetadata>(json.clone()).unwrap(),
+            metadata
+        );
+
+        // recorded without outputs
+        let mut json = json;
+        json.as_object_mut().unwrap().remove("outputs");
+        assert_eq!(
+            serde_json::from_value::<TransactionMetadata>(json).unwrap().outputs,
+            OutputMetadata::default()
+        );
+    }
 }
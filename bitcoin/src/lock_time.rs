@@ -0,0 +1,87 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Locktimes below this threshold are interpreted as block heights.
+const LOCK_TIME_THRESHOLD: u64 = 500_000_000;
+
+/// How the `nLockTime` of created transactions is set. Serialized as in the command
+/// line options, `zero` or `current-height`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockTimePolicy {
+    /// Always use locktime 0.
+    Zero,
+    /// Use the current block height, so that the transaction cannot be included in
+    /// a reorg of the current tip. This discourages fee sniping, and makes the
+    /// transactions indistinguishable from those created by most wallets.
+    CurrentHeight,
+}
+
+impl Default for LockTimePolicy {
+    fn default() -> Self {
+        LockTimePolicy::CurrentHeight
+    }
+}
+
+impl FromStr for LockTimePolicy {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "zero" => Ok(LockTimePolicy::Zero),
+            "current-height" => Ok(LockTimePolicy::CurrentHeight),
+            _ => Err(Error::InvalidLockTimePolicy),
+        }
+    }
+}
+
+/// Locktime and sequence policy of created transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TransactionPolicy {
+    pub lock_time: LockTimePolicy,
+    /// Signal BIP125 replaceability on all inputs. Otherwise inputs use the highest
+    /// sequence number that still enforces the locktime.
+    pub replaceable: bool,
+}
+
+impl TransactionPolicy {
+    /// The locktime for a transaction created at the given block height.
+    pub fn lock_time(&self, height: u64) -> u32 {
+        match self.lock_time {
+            LockTimePolicy::Zero => 0,
+            // should never be reached, but a timestamp locktime would make the tx non-final
+            LockTimePolicy::CurrentHeight if height >= LOCK_TIME_THRESHOLD => 0,
+            LockTimePolicy::CurrentHeight => height as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_time_policy() {
+        let policy = TransactionPolicy::default();
+        assert_eq!(policy.lock_time(680_000), 680_000);
+        assert_eq!(policy.lock_time(LOCK_TIME_THRESHOLD), 0);
+
+        let policy = TransactionPolicy {
+            lock_time: "zero".parse().unwrap(),
+            replaceable: false,
+        };
+        assert_eq!(policy.lock_time(680_000), 0);
+        assert!("latest".parse::<LockTimePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_transaction_policy_serde() {
+        let policy = TransactionPolicy {
+            lock_time: LockTimePolicy::CurrentHeight,
+            replaceable: true,
+        };
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(json, r#"{"lock_time":"current-height","replaceable":true}"#);
+        assert_eq!(serde_json::from_str::<TransactionPolicy>(&json).unwrap(), policy);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{deserialize, Error, Script, Transaction, Txid, Wtxid};
+use bitcoincore_rpc::bitcoin::{
+    consensus::encode::serialize_hex,
+    hashes::hex::{FromHex, ToHex},
+};
+use lazy_static::lazy_static;
+use log::warn;
+use prometheus::{IntCounterVec, Opts};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+lazy_static! {
+    pub static ref MALLEATED_TRANSACTIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "bitcoin_malleated_transactions",
+            "Sent transactions that were confirmed under a different witness or txid than broadcast"
+        ),
+        &["kind"]
+    )
+    .expect("Failed to create metric");
+}
+
+/// Id of the transaction without its scriptSigs and witnesses, which is the same for all
+/// malleated versions of a transaction: they spend the same outputs to the same outputs.
+pub fn normalized_txid(transaction: &Transaction) -> Txid {
+    let mut normalized = transaction.clone();
+    for input in normalized.input.iter_mut() {
+        input.script_sig = Script::new();
+        input.witness.clear();
+    }
+    normalized.txid()
+}
+
+/// How the confirmed version of a sent transaction differs from the broadcast one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malleation {
+    None,
+    /// Same txid, but a different witness. The payment is unaffected, only the wtxid changed.
+    Witness,
+    /// Different txid, e.g. a changed scriptSig of a non-segwit input.
+    Txid,
+}
+
+impl Malleation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Malleation::None => "none",
+            Malleation::Witness => "witness",
+            Malleation::Txid => "txid",
+        }
+    }
+}
+
+/// Transactions broadcast by this client by txid and wtxid, so that they are recognized
+/// even if they are confirmed in a malleated form.
+#[derive(Default)]
+pub(crate) struct SentTransactions {
+    /// Current wtxid of each sent transaction, updated when confirmed under another witness.
+    wtxids: HashMap<Txid, Wtxid>,
+    /// Txid of each sent transaction by its normalized txid.
+    by_normalized: HashMap<Txid, Txid>,
+    /// Script paid by each sent transaction, all its other outputs are OP_RETURN or change.
+    recipients: HashMap<Txid, Script>,
+}
+
+impl SentTransactions {
+    /// Restore the transactions recorded with `record_sent`, if the file exists.
+    pub(crate) fn load(path: &Path) -> Result<Self, Error> {
+        let mut sent_transactions = Self::default();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(sent_transactions),
+            Err(err) => return Err(err.into()),
+        };
+        for line in contents.lines() {
+            let mut fields = line.split_whitespace();
+            // a line cut off by a crash is skipped
+            let transaction: Transaction = match fields
+                .next()
+                .and_then(|hex| Vec::<u8>::from_hex(hex).ok())
+                .and_then(|bytes| deserialize(&bytes).ok())
+            {
+                Some(transaction) => transaction,
+                None => continue,
+            };
+            sent_transactions.insert(&transaction);
+            if let Some(recipient) = fields.next().and_then(|hex| Vec::<u8>::from_hex(hex).ok()) {
+                sent_transactions.set_recipient(transaction.txid(), Script::from(recipient));
+            }
+        }
+        Ok(sent_transactions)
+    }
+
+    pub(crate) fn insert(&mut self, transaction: &Transaction) {
+        let txid = transaction.txid();
+        self.wtxids.insert(txid, transaction.wtxid());
+        self.by_normalized.insert(normalized_txid(transaction), txid);
+    }
+
+    /// Remember the script paid by the sent transaction, to tell its change apart once confirmed.
+    pub(crate) fn set_recipient(&mut self, txid: Txid, script_pubkey: Script) {
+        self.recipients.insert(txid, script_pubkey);
+    }
+
+    pub(crate) fn recipient(&self, txid: &Txid) -> Option<&Script> {
+        self.recipients.get(txid)
+    }
+
+    pub(crate) fn contains(&self, txid: &Txid) -> bool {
+        self.wtxids.contains_key(txid)
+    }
+
+    /// Txid of the sent transaction of which the transaction is a version, possibly malleated.
+    pub(crate) fn sent_version_of(&self, transaction: &Transaction) -> Option<Txid> {
+        let txid = transaction.txid();
+        if self.contains(&txid) {
+            return Some(txid);
+        }
+        self.by_normalized.get(&normalized_txid(transaction)).copied()
+    }
+
+    /// Record the version of the sent transaction that was confirmed, so that it is tracked
+    /// under its new txid and wtxid, and report how it was malleated.
+    pub(crate) fn confirmed(&mut self, sent_txid: &Txid, transaction: &Transaction) -> Malleation {
+        let (txid, wtxid) = (transaction.txid(), transaction.wtxid());
+        let malleation = if txid != *sent_txid {
+            Malleation::Txid
+        } else if self.wtxids.get(&txid).map_or(false, |sent_wtxid| *sent_wtxid != wtxid) {
+            Malleation::Witness
+        } else {
+            Malleation::None
+        };
+        if malleation != Malleation::None {
+            warn!(
+                "Transaction {} was confirmed with a different {} as {} (wtxid {})",
+                sent_txid,
+                malleation.as_str(),
+                txid,
+                wtxid
+            );
+            MALLEATED_TRANSACTIONS.with_label_values(&[malleation.as_str()]).inc();
+            self.insert(transaction);
+        }
+        malleation
+    }
+}
+
+/// Append the sent transaction and the script it pays to the file, one hex encoded
+/// transaction per line followed by the script, so that it is recognized after a restart.
+pub(crate) fn record_sent(path: &Path, transaction: &Transaction, recipient: Option<&Script>) -> Result<(), Error> {
+    let mut line = serialize_hex(transaction);
+    if let Some(recipient) = recipient {
+        line.push(' ');
+        line.push_str(&recipient.as_bytes().to_hex());
+    }
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(file.sync_data()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash, OutPoint, TxIn, TxOut};
+
+    fn transaction(script_sig: Vec<u8>, witness: Vec<Vec<u8>>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 0),
+                script_sig: Script::from(script_sig),
+                sequence: 0xFFFFFFFF,
+                witness,
+            }],
+            output: vec![TxOut {
+                value: 1000,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_witness_malleation() {
+        let sent = transaction(vec![], vec![vec![1; 71]]);
+        let malleated = transaction(vec![], vec![vec![2; 72]]);
+        assert_eq!(sent.txid(), malleated.txid());
+        assert_ne!(sent.wtxid(), malleated.wtxid());
+
+        let mut journal = SentTransactions::default();
+        journal.insert(&sent);
+        assert_eq!(journal.confirmed(&sent.txid(), &sent), Malleation::None);
+        assert_eq!(journal.confirmed(&sent.txid(), &malleated), Malleation::Witness);
+        // the journal follows the confirmed witness
+        assert_eq!(journal.confirmed(&sent.txid(), &malleated), Malleation::None);
+    }
+
+    #[test]
+    fn test_txid_malleation() {
+        let sent = transaction(vec![1; 72], vec![]);
+        let malleated = transaction(vec![2; 73], vec![]);
+        assert_ne!(sent.txid(), malleated.txid());
+        assert_eq!(normalized_txid(&sent), normalized_txid(&malleated));
+
+        let mut journal = SentTransactions::default();
+        journal.insert(&sent);
+        journal.set_recipient(sent.txid(), Script::from(vec![0x00, 0x14, 1]));
+        assert!(!journal.contains(&malleated.txid()));
+        assert_eq!(journal.sent_version_of(&malleated), Some(sent.txid()));
+        assert_eq!(journal.confirmed(&sent.txid(), &malleated), Malleation::Txid);
+        assert!(journal.contains(&malleated.txid()));
+        // the recipient is looked up by the txid that was sent
+        assert_eq!(
+            journal.recipient(&sent.txid()),
+            Some(&Script::from(vec![0x00, 0x14, 1]))
+        );
+
+        let other = Transaction {
+            lock_time: 1,
+            ..sent.clone()
+        };
+        assert_eq!(journal.sent_version_of(&other), None);
+    }
+
+    #[test]
+    fn test_sent_transactions_survive_restart() {
+        let path = std::env::temp_dir().join(format!("bitcoin-sent-transactions-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let sent = transaction(vec![1; 72], vec![]);
+        let recipient = Script::from(vec![0x00, 0x14, 1]);
+        record_sent(&path, &sent, Some(&recipient)).unwrap();
+        // a record cut off by a crash
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"0200")
+            .unwrap();
+
+        let journal = SentTransactions::load(&path).unwrap();
+        assert!(journal.contains(&sent.txid()));
+        assert_eq!(journal.recipient(&sent.txid()), Some(&recipient));
+        let malleated = transaction(vec![2; 73], vec![]);
+        assert_eq!(journal.sent_version_of(&malleated), Some(sent.txid()));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
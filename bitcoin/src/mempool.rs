@@ -0,0 +1,229 @@
+use crate::{btc_to_sat, Error};
+use serde::{Deserialize, Serialize};
+
+/// Maximum virtual size of a block.
+pub const BLOCK_MAX_VSIZE: u64 = 1_000_000;
+
+/// Lower bounds (sat/vbyte) of the histogram buckets.
+const BUCKETS: &[u64] = &[
+    0, 1, 2, 3, 4, 5, 6, 8, 10, 12, 15, 20, 30, 40, 50, 60, 70, 80, 90, 100, 125, 150, 175, 200, 250, 300, 350, 400,
+    500, 600, 700, 800, 900, 1000, 1200, 1400, 1700, 2000,
+];
+
+/// Distribution of the mempool by fee rate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeHistogram {
+    /// Pairs of (minimum fee rate in sat/vbyte, total vsize of the transactions in the
+    /// bucket), ordered from the highest to the lowest fee rate. Empty buckets are omitted.
+    pub buckets: Vec<(u64, u64)>,
+}
+
+impl FeeHistogram {
+    /// Build the histogram from the (fee in satoshis, vsize) of each mempool transaction.
+    pub fn from_entries<I: IntoIterator<Item = (u64, u64)>>(entries: I) -> Self {
+        let mut vsizes = vec![0u64; BUCKETS.len()];
+        for (fee, vsize) in entries {
+            if vsize == 0 {
+                continue;
+            }
+            let fee_rate = fee / vsize;
+            // the first bucket starts at zero, so this always finds one
+            let index = BUCKETS.iter().rposition(|bound| *bound <= fee_rate).unwrap_or_default();
+            vsizes[index] = vsizes[index].saturating_add(vsize);
+        }
+        FeeHistogram {
+            buckets: BUCKETS
+                .iter()
+                .copied()
+                .zip(vsizes)
+                .filter(|(_, vsize)| *vsize > 0)
+                .rev()
+                .collect(),
+        }
+    }
+
+    /// Total vsize of all transactions in the mempool.
+    pub fn total_vsize(&self) -> u64 {
+        self.buckets.iter().map(|(_, vsize)| vsize).sum()
+    }
+
+    /// Minimum fee rate (sat/vbyte) a transaction needs to pay to be included within
+    /// the given number of blocks, assuming no new transactions arrive.
+    pub fn fee_rate_for_blocks(&self, num_blocks: u64) -> u64 {
+        let capacity = num_blocks.saturating_mul(BLOCK_MAX_VSIZE);
+        let mut cumulative = 0u64;
+        for (fee_rate, vsize) in &self.buckets {
+            cumulative = cumulative.saturating_add(*vsize);
+            if cumulative >= capacity {
+                // a transaction paying more than this bucket gets ahead of it
+                return fee_rate.saturating_add(1);
+            }
+        }
+        1
+    }
+
+    /// Congestion score, the number of blocks needed to clear the current mempool.
+    /// Below 1 the next block has free space, above a few blocks payments with
+    /// a low fee rate may be delayed considerably.
+    pub fn congestion(&self) -> f64 {
+        self.total_vsize() as f64 / BLOCK_MAX_VSIZE as f64
+    }
+}
+
+/// Subset of an entry in the response of `getrawmempool true`.
+#[derive(Deserialize)]
+pub(crate) struct VerboseMempoolEntry {
+    pub vsize: u64,
+    pub fees: MempoolEntryFees,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MempoolEntryFees {
+    /// Fee in BTC.
+    pub base: f64,
+    /// Fees of the transaction and its in-mempool ancestors in BTC.
+    #[serde(default)]
+    pub ancestor: f64,
+    /// Fees of the transaction and its in-mempool descendants in BTC.
+    #[serde(default)]
+    pub descendant: f64,
+}
+
+/// Subset of the result of `getmempoolinfo`.
+#[derive(Deserialize)]
+pub(crate) struct GetMempoolInfoResult {
+    /// Total vsize of the transactions in the mempool.
+    pub bytes: u64,
+    /// Minimum fee rate in BTC/kvbyte for transactions to be accepted.
+    pub mempoolminfee: f64,
+}
+
+/// Result of `getmempoolentry`.
+#[derive(Deserialize)]
+pub(crate) struct GetMempoolEntryResult {
+    vsize: u64,
+    ancestorcount: u64,
+    ancestorsize: u64,
+    descendantcount: u64,
+    descendantsize: u64,
+    fees: MempoolEntryFees,
+}
+
+impl GetMempoolEntryResult {
+    pub(crate) fn into_entry(self) -> Result<MempoolEntry, Error> {
+        Ok(MempoolEntry {
+            vsize: self.vsize,
+            fee: btc_to_sat(self.fees.base)?,
+            ancestor_count: self.ancestorcount,
+            ancestor_size: self.ancestorsize,
+            ancestor_fees: btc_to_sat(self.fees.ancestor)?,
+            descendant_count: self.descendantcount,
+            descendant_size: self.descendantsize,
+            descendant_fees: btc_to_sat(self.fees.descendant)?,
+        })
+    }
+}
+
+/// Package details of an unconfirmed transaction. Counts and sizes (in vbytes)
+/// include the transaction itself, fees are in satoshis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MempoolEntry {
+    pub vsize: u64,
+    pub fee: u64,
+    pub ancestor_count: u64,
+    pub ancestor_size: u64,
+    pub ancestor_fees: u64,
+    pub descendant_count: u64,
+    pub descendant_size: u64,
+    pub descendant_fees: u64,
+}
+
+/// Package limits enforced by bitcoind's mempool policy, defaults to those of bitcoind
+/// (`-limitancestorcount`, `-limitancestorsize`, `-limitdescendantcount`, `-limitdescendantsize`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolLimits {
+    pub max_ancestor_count: u64,
+    pub max_ancestor_size: u64,
+    pub max_descendant_count: u64,
+    pub max_descendant_size: u64,
+}
+
+impl Default for MempoolLimits {
+    fn default() -> Self {
+        Self {
+            max_ancestor_count: 25,
+            max_ancestor_size: 101_000,
+            max_descendant_count: 25,
+            max_descendant_size: 101_000,
+        }
+    }
+}
+
+impl MempoolLimits {
+    /// Check that a transaction of the given vsize spending outputs of the given unconfirmed
+    /// parents would be accepted. Ancestors shared by multiple parents are counted multiple
+    /// times, so this errs on the side of rejecting.
+    pub fn check(&self, parents: &[MempoolEntry], vsize: u64) -> Result<(), Error> {
+        let ancestor_count = parents.iter().map(|parent| parent.ancestor_count).sum::<u64>() + 1;
+        let ancestor_size = parents.iter().map(|parent| parent.ancestor_size).sum::<u64>() + vsize;
+        if ancestor_count > self.max_ancestor_count || ancestor_size > self.max_ancestor_size {
+            return Err(Error::MempoolChainTooLong("too many unconfirmed ancestors"));
+        }
+        // every ancestor gains a descendant, but parents have the most descendants of the
+        // ancestors that we know of
+        if parents.iter().any(|parent| {
+            parent.descendant_count + 1 > self.max_descendant_count
+                || parent.descendant_size + vsize > self.max_descendant_size
+        }) {
+            return Err(Error::MempoolChainTooLong("too many unconfirmed descendants"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parent(ancestor_count: u64, descendant_count: u64) -> MempoolEntry {
+        MempoolEntry {
+            vsize: 200,
+            ancestor_count,
+            ancestor_size: ancestor_count * 200,
+            descendant_count,
+            descendant_size: descendant_count * 200,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mempool_limits() {
+        let limits = MempoolLimits::default();
+        assert!(limits.check(&[], 200).is_ok());
+        assert!(limits.check(&[parent(24, 1)], 200).is_ok());
+        assert!(limits.check(&[parent(25, 1)], 200).is_err());
+        assert!(limits.check(&[parent(12, 1), parent(13, 1)], 200).is_err());
+        assert!(limits.check(&[parent(1, 25)], 200).is_err());
+        assert!(limits.check(&[parent(1, 1)], 101_000).is_err());
+    }
+
+    #[test]
+    fn test_histogram_buckets() {
+        let histogram = FeeHistogram::from_entries(vec![(1_000, 100), (1_050, 100), (250, 250), (50, 100)]);
+        assert_eq!(histogram.buckets, vec![(10, 200), (1, 250), (0, 100)]);
+        assert_eq!(histogram.total_vsize(), 550);
+    }
+
+    #[test]
+    fn test_fee_rate_for_blocks() {
+        let histogram = FeeHistogram::from_entries(vec![
+            (50 * BLOCK_MAX_VSIZE / 2, BLOCK_MAX_VSIZE / 2),
+            (20 * BLOCK_MAX_VSIZE, BLOCK_MAX_VSIZE),
+            (2 * BLOCK_MAX_VSIZE, BLOCK_MAX_VSIZE),
+        ]);
+        assert_eq!(histogram.fee_rate_for_blocks(1), 21);
+        assert_eq!(histogram.fee_rate_for_blocks(2), 3);
+        assert_eq!(histogram.fee_rate_for_blocks(3), 1);
+        assert!((histogram.congestion() - 2.5).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{Amount, ConversionError, Error, SignedAmount, Transaction};
+use std::convert::TryFrom;
+
+pub const SATOSHI_PER_BITCOIN: u64 = 100_000_000;
+
+/// Checked arithmetic on amounts, failing with `Error::ArithmeticError` on overflow or
+/// underflow instead of returning `None`.
+pub trait AmountExt: Sized {
+    fn try_add(self, other: Self) -> Result<Self, Error>;
+
+    fn try_sub(self, other: Self) -> Result<Self, Error>;
+
+    /// Multiply by a rate, e.g. a fee rate in sat/vbyte by a size in vbytes.
+    fn try_mul(self, rate: u64) -> Result<Self, Error>;
+}
+
+impl AmountExt for Amount {
+    fn try_add(self, other: Self) -> Result<Self, Error> {
+        self.checked_add(other).ok_or(Error::ArithmeticError)
+    }
+
+    fn try_sub(self, other: Self) -> Result<Self, Error> {
+        self.checked_sub(other).ok_or(Error::ArithmeticError)
+    }
+
+    fn try_mul(self, rate: u64) -> Result<Self, Error> {
+        self.checked_mul(rate).ok_or(Error::ArithmeticError)
+    }
+}
+
+impl AmountExt for SignedAmount {
+    fn try_add(self, other: Self) -> Result<Self, Error> {
+        self.checked_add(other).ok_or(Error::ArithmeticError)
+    }
+
+    fn try_sub(self, other: Self) -> Result<Self, Error> {
+        self.checked_sub(other).ok_or(Error::ArithmeticError)
+    }
+
+    fn try_mul(self, rate: u64) -> Result<Self, Error> {
+        let rate = i64::try_from(rate).map_err(|_| Error::ArithmeticError)?;
+        self.checked_mul(rate).ok_or(Error::ArithmeticError)
+    }
+}
+
+/// Convert an amount in bitcoin, as reported by bitcoind, to satoshis.
+pub fn btc_to_sat(btc: f64) -> Result<u64, ConversionError> {
+    Ok(Amount::from_btc(btc)
+        .map_err(|_| ConversionError::InvalidFormat)?
+        .as_sat())
+}
+
+/// Convert an amount in satoshis to bitcoin, e.g. for the arguments of bitcoind calls.
+pub fn sat_to_btc(sat: u64) -> f64 {
+    Amount::from_sat(sat).as_btc()
+}
+
+/// The difference `a - b`, negative if `b` is larger, e.g. the surplus of a balance over a reserve.
+pub fn signed_difference(a: Amount, b: Amount) -> Result<SignedAmount, Error> {
+    let a = a.to_signed().map_err(|_| Error::ArithmeticError)?;
+    let b = b.to_signed().map_err(|_| Error::ArithmeticError)?;
+    a.try_sub(b)
+}
+
+/// Virtual size of the transaction, the weight divided by four rounded up.
+pub fn vsize(transaction: &Transaction) -> u64 {
+    (transaction.get_weight() as u64 + 3) / 4
+}
+
+/// Fee (in satoshis) of a transaction of `vsize` vbytes at `fee_rate` sat/vbyte.
+pub fn fee_for_vsize(fee_rate: u64, vsize: u64) -> Result<Amount, Error> {
+    Amount::from_sat(fee_rate).try_mul(vsize)
+}
+
+/// Fee rate (in sat/vbyte) paid by a fee for `vsize` vbytes, rounded down so that it is
+/// never overstated.
+pub fn fee_rate(fee: Amount, vsize: u64) -> u64 {
+    fee.as_sat() / vsize.max(1)
+}
+
+/// Format the amount in bitcoin without trailing zeros, e.g. `1.5` for 150000000 satoshis.
+pub fn format_btc(sat: u64) -> String {
+    let fraction = format!("{:08}", sat % SATOSHI_PER_BITCOIN);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        (sat / SATOSHI_PER_BITCOIN).to_string()
+    } else {
+        format!("{}.{}", sat / SATOSHI_PER_BITCOIN, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let max = Amount::from_sat(u64::MAX);
+        assert!(matches!(max.try_add(Amount::from_sat(1)), Err(Error::ArithmeticError)));
+        assert!(matches!(
+            Amount::from_sat(1).try_sub(Amount::from_sat(2)),
+            Err(Error::ArithmeticError)
+        ));
+        assert_eq!(fee_for_vsize(10, 250).unwrap(), Amount::from_sat(2500));
+        assert!(fee_for_vsize(u64::MAX, 2).is_err());
+        assert_eq!(fee_rate(Amount::from_sat(2599), 250), 10);
+        assert_eq!(fee_rate(Amount::from_sat(100), 0), 100);
+
+        assert_eq!(
+            signed_difference(Amount::from_sat(1000), Amount::from_sat(3000)).unwrap(),
+            SignedAmount::from_sat(-2000)
+        );
+        assert!(signed_difference(max, Amount::from_sat(0)).is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(btc_to_sat(0.5).unwrap(), 50_000_000);
+        assert!(btc_to_sat(-1.0).is_err());
+        assert_eq!(sat_to_btc(150_000_000), 1.5);
+        assert_eq!(format_btc(150_000_000), "1.5");
+        assert_eq!(format_btc(200_000_000), "2");
+        assert_eq!(format_btc(1), "0.00000001");
+    }
+}
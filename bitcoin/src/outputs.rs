@@ -0,0 +1,113 @@
+use crate::{Transaction, TransactionExt, TxOut};
+use serde::{Deserialize, Serialize};
+use sp_core::H256;
+
+/// An output of a transaction, by its index in the transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputInfo {
+    pub index: u32,
+    /// Value of the output in Satoshis.
+    pub value: u64,
+}
+
+/// The outputs of a payment made for a request, so that callers don't need to parse the
+/// raw transaction to find the paid amount, the change or the request id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputMetadata {
+    /// The first output that does not return funds to the wallet.
+    pub recipient: Option<OutputInfo>,
+    /// The return-to-self output, if the payment was not to the wallet itself.
+    pub change: Option<OutputInfo>,
+    /// Payload of the OP_RETURN output, i.e. the id of the request.
+    pub op_return: Option<H256>,
+}
+
+impl OutputMetadata {
+    /// Classify the outputs of the transaction. `is_change` tells whether the output at an
+    /// index returns funds to the wallet. If all outputs do, the first one is the recipient, as for
+    /// payments to the wallet itself.
+    pub fn new<F: Fn(usize, &TxOut) -> bool>(transaction: &Transaction, is_change: F) -> Self {
+        let op_return = transaction.get_op_return();
+        let (mut change, mut others): (Vec<_>, Vec<_>) = transaction
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| !output.script_pubkey.is_op_return())
+            .map(|(index, output)| {
+                (
+                    is_change(index, output),
+                    OutputInfo {
+                        index: index as u32,
+                        value: output.value,
+                    },
+                )
+            })
+            .partition(|(is_change, _)| *is_change);
+        if others.is_empty() && !change.is_empty() {
+            others.push(change.remove(0));
+        }
+        Self {
+            recipient: others.first().map(|(_, info)| *info),
+            change: change.first().map(|(_, info)| *info),
+            op_return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{opcodes, Builder, Script};
+
+    fn output(value: u64, script_pubkey: Script) -> TxOut {
+        TxOut { value, script_pubkey }
+    }
+
+    fn transaction(output: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output,
+        }
+    }
+
+    fn op_return(request_id: H256) -> Script {
+        Builder::new()
+            .push_opcode(opcodes::OP_RETURN)
+            .push_slice(request_id.as_bytes())
+            .into_script()
+    }
+
+    #[test]
+    fn test_output_metadata() {
+        let request_id = H256::repeat_byte(1);
+        let recipient = Script::from(vec![0x00, 0x14, 1]);
+        let change = Script::from(vec![0x00, 0x14, 2]);
+        let tx = transaction(vec![
+            output(1000, recipient.clone()),
+            output(0, op_return(request_id)),
+            output(500, change.clone()),
+        ]);
+
+        let metadata = OutputMetadata::new(&tx, |_, output| output.script_pubkey == change);
+        assert_eq!(
+            metadata,
+            OutputMetadata {
+                recipient: Some(OutputInfo { index: 0, value: 1000 }),
+                change: Some(OutputInfo { index: 2, value: 500 }),
+                op_return: Some(request_id),
+            }
+        );
+
+        // paid to the wallet itself, without change
+        let tx = transaction(vec![output(0, op_return(request_id)), output(1000, change.clone())]);
+        let metadata = OutputMetadata::new(&tx, |_, _| true);
+        assert_eq!(metadata.recipient, Some(OutputInfo { index: 1, value: 1000 }));
+        assert_eq!(metadata.change, None);
+
+        let metadata = OutputMetadata::new(&transaction(vec![output(1000, recipient)]), |_, _| false);
+        assert_eq!(metadata.change, None);
+        assert_eq!(metadata.op_return, None);
+    }
+}
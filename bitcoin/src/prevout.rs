@@ -0,0 +1,200 @@
+use crate::{btc_to_sat, deserialize, ConversionError, Error, Script, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+
+/// Classification of the locking script of a transaction output, serialized in snake
+/// case (e.g. `p2wpkh`, `op_return`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    OpReturn,
+    NonStandard,
+}
+
+impl From<&Script> for ScriptType {
+    fn from(script: &Script) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_v0_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_v0_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_op_return() {
+            ScriptType::OpReturn
+        } else {
+            ScriptType::NonStandard
+        }
+    }
+}
+
+/// A transaction together with the outputs spent by each of its inputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionWithPrevouts {
+    pub transaction: Transaction,
+    /// The spent outputs in input order, `None` for the input of a coinbase transaction.
+    pub prevouts: Vec<Option<TxOut>>,
+}
+
+impl TransactionWithPrevouts {
+    /// Total value of all outputs spent by this transaction.
+    pub fn input_value(&self) -> u64 {
+        self.prevouts.iter().flatten().map(|prevout| prevout.value).sum()
+    }
+
+    /// Total value of all outputs created by this transaction.
+    pub fn output_value(&self) -> u64 {
+        self.transaction.output.iter().map(|output| output.value).sum()
+    }
+
+    /// The fee paid by this transaction, `None` for coinbase transactions.
+    pub fn fee(&self) -> Option<u64> {
+        if self.transaction.is_coin_base() {
+            return None;
+        }
+        self.input_value().checked_sub(self.output_value())
+    }
+
+    /// The script types of the outputs spent by each input.
+    pub fn input_script_types(&self) -> Vec<Option<ScriptType>> {
+        self.prevouts
+            .iter()
+            .map(|prevout| prevout.as_ref().map(|prevout| ScriptType::from(&prevout.script_pubkey)))
+            .collect()
+    }
+}
+
+/// Response of `getrawtransaction` with verbosity 2. Nodes that predate
+/// this verbosity level treat it as `verbose=true` and omit the prevouts.
+#[derive(Deserialize)]
+pub(crate) struct VerboseTransaction {
+    hex: String,
+    vin: Vec<VerboseTxIn>,
+}
+
+#[derive(Deserialize)]
+struct VerboseTxIn {
+    #[serde(default)]
+    prevout: Option<VerbosePrevout>,
+}
+
+#[derive(Deserialize)]
+struct VerbosePrevout {
+    value: f64,
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: VerboseScriptPubKey,
+}
+
+#[derive(Deserialize)]
+struct VerboseScriptPubKey {
+    hex: String,
+}
+
+impl VerbosePrevout {
+    fn into_tx_out(self) -> Result<TxOut, Error> {
+        Ok(TxOut {
+            value: btc_to_sat(self.value)?,
+            script_pubkey: Script::from(hex::decode(self.script_pub_key.hex).map_err(ConversionError::from)?),
+        })
+    }
+}
+
+impl VerboseTransaction {
+    /// Decode the transaction and, if the node reported them for every input,
+    /// the outputs it spends.
+    pub(crate) fn decode(self) -> Result<(Transaction, Option<Vec<Option<TxOut>>>), Error> {
+        let transaction: Transaction = deserialize(&hex::decode(self.hex).map_err(ConversionError::from)?)?;
+        if transaction.is_coin_base() {
+            return Ok((transaction, Some(vec![None])));
+        }
+
+        let prevouts = self
+            .vin
+            .into_iter()
+            .map(|input| input.prevout.map(VerbosePrevout::into_tx_out).transpose())
+            .collect::<Result<Option<Vec<_>>, Error>>()?
+            .map(|prevouts| prevouts.into_iter().map(Some).collect());
+
+        Ok((transaction, prevouts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize, Hash, OutPoint, TxIn, Txid};
+
+    fn spending_transaction() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 0),
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 90_000,
+                script_pubkey: Script::from(hex::decode("0014e8df018c7e326cc253faac7e46cdc51e68542c42").unwrap()),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_decode_with_prevouts() {
+        let transaction = spending_transaction();
+        let json = serde_json::json!({
+            "hex": hex::encode(serialize(&transaction)),
+            "vin": [{
+                "txid": "0101010101010101010101010101010101010101010101010101010101010101",
+                "vout": 0,
+                "prevout": {
+                    "generated": false,
+                    "height": 100,
+                    "value": 0.001,
+                    "scriptPubKey": {
+                        "hex": "76a914e8df018c7e326cc253faac7e46cdc51e68542c4288ac",
+                        "type": "pubkeyhash"
+                    }
+                }
+            }]
+        });
+
+        let (decoded, prevouts) = serde_json::from_value::<VerboseTransaction>(json)
+            .unwrap()
+            .decode()
+            .unwrap();
+        let tx = TransactionWithPrevouts {
+            transaction: decoded,
+            prevouts: prevouts.unwrap(),
+        };
+
+        assert_eq!(tx.transaction, transaction);
+        assert_eq!(tx.input_value(), 100_000);
+        assert_eq!(tx.fee(), Some(10_000));
+        assert_eq!(tx.input_script_types(), vec![Some(ScriptType::P2pkh)]);
+    }
+
+    #[test]
+    fn test_decode_without_prevouts() {
+        let transaction = spending_transaction();
+        let json = serde_json::json!({
+            "hex": hex::encode(serialize(&transaction)),
+            "vin": [{
+                "txid": "0101010101010101010101010101010101010101010101010101010101010101",
+                "vout": 0
+            }]
+        });
+
+        let (_, prevouts) = serde_json::from_value::<VerboseTransaction>(json)
+            .unwrap()
+            .decode()
+            .unwrap();
+        assert_eq!(prevouts, None);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{deserialize, BlockHash, Error, Txid};
+use bitcoincore_rpc::bitcoin::util::merkleblock::MerkleBlock;
+
+/// Verify a merkle proof as returned by `gettxoutproof` before it is submitted to the
+/// parachain: the proof must be internally consistent, commit to the given block and
+/// prove the inclusion of the given transaction.
+pub fn verify_proof(proof: &[u8], txid: &Txid, block_hash: &BlockHash) -> Result<(), Error> {
+    let merkle_block: MerkleBlock = deserialize(proof)?;
+    if &merkle_block.header.block_hash() != block_hash {
+        return Err(Error::InvalidProof("proof is for a different block"));
+    }
+
+    let mut matches = Vec::new();
+    let mut indexes = Vec::new();
+    // also checks that the computed merkle root matches the header
+    merkle_block
+        .extract_matches(&mut matches, &mut indexes)
+        .map_err(|_| Error::InvalidProof("merkle root does not match the block header"))?;
+    if !matches.contains(txid) {
+        return Err(Error::InvalidProof("transaction is not included in the proof"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize, Block, BlockHeader, Hash, OutPoint, Script, Transaction, TxIn, TxOut};
+    use std::collections::HashSet;
+
+    fn transaction(value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 0),
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    fn block() -> Block {
+        let mut block = Block {
+            header: BlockHeader {
+                version: 4,
+                prev_blockhash: Default::default(),
+                merkle_root: Default::default(),
+                time: 1,
+                bits: 2,
+                nonce: 3,
+            },
+            txdata: vec![transaction(1), transaction(2), transaction(3)],
+        };
+        block.header.merkle_root = block.merkle_root();
+        block
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let block = block();
+        let txid = block.txdata[1].txid();
+        let mut match_txids = HashSet::new();
+        match_txids.insert(txid);
+        let proof = serialize(&MerkleBlock::from_block(&block, &match_txids));
+
+        assert!(verify_proof(&proof, &txid, &block.block_hash()).is_ok());
+        assert!(verify_proof(&proof, &block.txdata[0].txid(), &block.block_hash()).is_err());
+        assert!(verify_proof(&proof, &txid, &BlockHash::from_slice(&[1; 32]).unwrap()).is_err());
+        assert!(verify_proof(&proof[..proof.len() - 1], &txid, &block.block_hash()).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{deserialize, BlockHeader, Error, Hash, OutPoint, Transaction, Txid};
+use bitcoincore_rpc::bitcoin::hashes::HashEngine;
+use std::convert::TryInto;
+
+const HEADER_SIZE: usize = 80;
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).ok_or(Error::ParsingError)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(Error::ParsingError)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(
+            self.take(8)?.try_into().map_err(|_| Error::ParsingError)?,
+        ))
+    }
+
+    fn read_compact_size(&mut self) -> Result<usize, Error> {
+        let value = match self.take(1)?[0] {
+            0xfd => u16::from_le_bytes(self.take(2)?.try_into().map_err(|_| Error::ParsingError)?) as u64,
+            0xfe => u32::from_le_bytes(self.take(4)?.try_into().map_err(|_| Error::ParsingError)?) as u64,
+            0xff => self.read_u64()?,
+            value => value as u64,
+        };
+        // every item is at least one byte, so larger counts can never be valid
+        if value > self.bytes.len() as u64 {
+            return Err(Error::ParsingError);
+        }
+        Ok(value as usize)
+    }
+
+    fn skip_var_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_compact_size()?;
+        self.take(len)
+    }
+}
+
+/// A serialized block. Borrows from the raw bytes instead of materializing a full
+/// `Block`, so that the header and the few transactions of interest can be
+/// extracted cheaply, e.g. during catch-up.
+pub struct RawBlock<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> RawBlock<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn header(&self) -> Result<BlockHeader, Error> {
+        let header = self.bytes.get(..HEADER_SIZE).ok_or(Error::ParsingError)?;
+        Ok(deserialize(header)?)
+    }
+
+    /// Iterate over the transactions of the block without deserializing them.
+    pub fn transactions(&self) -> Result<RawTransactions<'a>, Error> {
+        let mut cursor = Cursor {
+            bytes: self.bytes,
+            pos: HEADER_SIZE,
+        };
+        let remaining = cursor.read_compact_size()?;
+        Ok(RawTransactions { cursor, remaining })
+    }
+}
+
+pub struct RawTransactions<'a> {
+    cursor: Cursor<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for RawTransactions<'a> {
+    type Item = Result<RawTransaction<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let result = RawTransaction::parse(&mut self.cursor);
+        if result.is_err() {
+            // the remaining bytes cannot be interpreted
+            self.remaining = 0;
+        }
+        Some(result)
+    }
+}
+
+/// A serialized transaction, borrowed from the block.
+pub struct RawTransaction<'a> {
+    bytes: &'a [u8],
+    version: &'a [u8],
+    inputs: &'a [u8],
+    outputs: &'a [u8],
+    lock_time: &'a [u8],
+    /// Serialized outpoint spent by each input.
+    prevouts: Vec<&'a [u8]>,
+    /// Value and script of each output.
+    output_scripts: Vec<(u64, &'a [u8])>,
+}
+
+impl<'a> RawTransaction<'a> {
+    fn parse(cursor: &mut Cursor<'a>) -> Result<Self, Error> {
+        let start = cursor.pos;
+        let version = cursor.take(4)?;
+
+        // segwit marker and flag
+        let is_segwit = cursor.peek(0) == Some(0x00) && cursor.peek(1) == Some(0x01);
+        if is_segwit {
+            cursor.take(2)?;
+        }
+
+        let inputs_start = cursor.pos;
+        let num_inputs = cursor.read_compact_size()?;
+        let mut prevouts = Vec::with_capacity(num_inputs);
+        for _ in 0..num_inputs {
+            // previous output, script sig, sequence
+            prevouts.push(cursor.take(36)?);
+            cursor.skip_var_bytes()?;
+            cursor.take(4)?;
+        }
+        let inputs = &cursor.bytes[inputs_start..cursor.pos];
+
+        let outputs_start = cursor.pos;
+        let num_outputs = cursor.read_compact_size()?;
+        let mut output_scripts = Vec::with_capacity(num_outputs);
+        for _ in 0..num_outputs {
+            let value = cursor.read_u64()?;
+            output_scripts.push((value, cursor.skip_var_bytes()?));
+        }
+        let outputs = &cursor.bytes[outputs_start..cursor.pos];
+
+        if is_segwit {
+            for _ in 0..num_inputs {
+                let num_items = cursor.read_compact_size()?;
+                for _ in 0..num_items {
+                    cursor.skip_var_bytes()?;
+                }
+            }
+        }
+
+        let lock_time = cursor.take(4)?;
+        Ok(RawTransaction {
+            bytes: &cursor.bytes[start..cursor.pos],
+            version,
+            inputs,
+            outputs,
+            lock_time,
+            prevouts,
+            output_scripts,
+        })
+    }
+
+    /// The transaction id, i.e. the hash of the serialization without witness data.
+    pub fn txid(&self) -> Txid {
+        let mut engine = Txid::engine();
+        engine.input(self.version);
+        engine.input(self.inputs);
+        engine.input(self.outputs);
+        engine.input(self.lock_time);
+        Txid::from_engine(engine)
+    }
+
+    /// Outpoints spent by the inputs, the null outpoint for coinbase inputs.
+    pub fn previous_outputs(&self) -> impl Iterator<Item = OutPoint> + '_ {
+        self.prevouts.iter().map(|prevout| {
+            let (txid, vout) = prevout.split_at(32);
+            OutPoint::new(
+                Txid::from_slice(txid).expect("slice has 32 bytes"),
+                u32::from_le_bytes(vout.try_into().expect("slice has 4 bytes")),
+            )
+        })
+    }
+
+    /// Value and raw locking script of each output.
+    pub fn outputs(&self) -> &[(u64, &'a [u8])] {
+        &self.output_scripts
+    }
+
+    /// Returns true if any output pays to one of the given scripts.
+    pub fn pays_to_any(&self, scripts: &[&[u8]]) -> bool {
+        self.output_scripts.iter().any(|(_, script)| scripts.contains(script))
+    }
+
+    pub fn deserialize(&self) -> Result<Transaction, Error> {
+        Ok(deserialize(self.bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize, Block, Script, TxIn, TxMerkleNode, TxOut};
+
+    fn transaction(value: u64, script: &[u8], witness: Vec<Vec<u8>>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 0),
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness,
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::from(script.to_vec()),
+            }],
+        }
+    }
+
+    fn header() -> BlockHeader {
+        BlockHeader {
+            version: 4,
+            prev_blockhash: Default::default(),
+            merkle_root: TxMerkleNode::from_slice(&[2; 32]).unwrap(),
+            time: 1,
+            bits: 2,
+            nonce: 3,
+        }
+    }
+
+    #[test]
+    fn test_parse_raw_block() {
+        let legacy = transaction(1000, &[0x51], vec![]);
+        let segwit = transaction(2000, &[0x00, 0x14, 0xaa], vec![vec![1, 2, 3], vec![4]]);
+        let block = Block {
+            header: header(),
+            txdata: vec![legacy.clone(), segwit.clone()],
+        };
+        let bytes = serialize(&block);
+        let raw_block = RawBlock::new(&bytes);
+
+        assert_eq!(raw_block.header().unwrap(), block.header);
+
+        let transactions = raw_block
+            .transactions()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].txid(), legacy.txid());
+        assert_eq!(transactions[1].txid(), segwit.txid());
+        assert_eq!(transactions[1].outputs(), &[(2000, &[0x00, 0x14, 0xaa][..])]);
+        assert!(transactions[1].pays_to_any(&[&[0x00, 0x14, 0xaa]]));
+        assert!(!transactions[0].pays_to_any(&[&[0x00, 0x14, 0xaa]]));
+        assert_eq!(transactions[1].deserialize().unwrap(), segwit);
+        assert_eq!(
+            transactions[1].previous_outputs().collect::<Vec<_>>(),
+            vec![segwit.input[0].previous_output]
+        );
+    }
+
+    #[test]
+    fn test_parse_truncated_block() {
+        let block = Block {
+            header: header(),
+            txdata: vec![transaction(1000, &[0x51], vec![])],
+        };
+        let bytes = serialize(&block);
+        let raw_block = RawBlock::new(&bytes[..bytes.len() - 2]);
+        let mut transactions = raw_block.transactions().unwrap();
+        assert!(transactions.next().unwrap().is_err());
+        assert!(transactions.next().is_none());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{auth::ReloadingClient, OutPoint, RpcApi, Txid};
+use log::trace;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Holder of reserved outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reserved {
+    /// Spent by a funded transaction that has not been broadcast yet.
+    Funded(u64),
+    /// Spent by the broadcast transaction, until it leaves the mempool.
+    Broadcast(Txid),
+}
+
+/// Keeps track of the wallet outputs that are spent by created transactions. The outputs
+/// are locked in bitcoind (`lockunspent`) when the transaction is funded, so that
+/// concurrently funded transactions use disjoint outputs. They stay reserved until the
+/// transaction is dropped without being broadcast, or until it has left the mempool.
+#[derive(Clone)]
+pub(crate) struct UtxoReservations {
+    client: Arc<ReloadingClient>,
+    reserved: Arc<Mutex<HashMap<OutPoint, Reserved>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl UtxoReservations {
+    pub(crate) fn new(client: Arc<ReloadingClient>) -> Self {
+        Self {
+            client,
+            reserved: Default::default(),
+            next_id: Default::default(),
+        }
+    }
+
+    /// Record the outputs that were locked when funding a transaction.
+    pub(crate) fn reserve(&self, outpoints: Vec<OutPoint>) -> UtxoReservation {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut reserved = self.reserved.lock().expect("poisoned");
+        for outpoint in &outpoints {
+            reserved.insert(*outpoint, Reserved::Funded(id));
+        }
+        UtxoReservation {
+            reservations: self.clone(),
+            id,
+            outpoints,
+        }
+    }
+
+    /// Broadcast transactions whose outputs are still reserved.
+    pub(crate) fn broadcast_txids(&self) -> HashSet<Txid> {
+        self.reserved
+            .lock()
+            .expect("poisoned")
+            .values()
+            .filter_map(|reserved| match reserved {
+                Reserved::Broadcast(txid) => Some(*txid),
+                Reserved::Funded(_) => None,
+            })
+            .collect()
+    }
+
+    /// Release the outputs of a broadcast transaction that has left the mempool. They are
+    /// unlocked in case the transaction was evicted rather than confirmed.
+    pub(crate) fn release_broadcast(&self, txid: &Txid) {
+        let released = self.take(Reserved::Broadcast(*txid), None);
+        if !released.is_empty() {
+            trace!(
+                "Releasing {} outputs of {}, which left the mempool",
+                released.len(),
+                txid
+            );
+            self.unlock(&released);
+        }
+    }
+
+    /// Remove the outputs held by `holder` (restricted to `outpoints` if given) and return them.
+    fn take(&self, holder: Reserved, outpoints: Option<&[OutPoint]>) -> Vec<OutPoint> {
+        let mut reserved = self.reserved.lock().expect("poisoned");
+        let owned: Vec<_> = reserved
+            .iter()
+            .filter(|(outpoint, owner)| {
+                **owner == holder && outpoints.map_or(true, |outpoints| outpoints.contains(outpoint))
+            })
+            .map(|(outpoint, _)| *outpoint)
+            .collect();
+        for outpoint in &owned {
+            reserved.remove(outpoint);
+        }
+        owned
+    }
+
+    /// Hand the outputs of the funded transaction over to the broadcast transaction.
+    fn broadcast(&self, id: u64, outpoints: &[OutPoint], txid: Txid) {
+        let mut reserved = self.reserved.lock().expect("poisoned");
+        for outpoint in outpoints {
+            if let Some(owner) = reserved.get_mut(outpoint) {
+                if *owner == Reserved::Funded(id) {
+                    *owner = Reserved::Broadcast(txid);
+                }
+            }
+        }
+    }
+
+    /// Unlock the outputs in the background if called on the runtime, e.g. when a
+    /// reservation is dropped, so that the caller is not blocked on bitcoind.
+    fn unlock(&self, outpoints: &[OutPoint]) {
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                if let Err(err) = self.client.get().unlock_unspent(outpoints) {
+                    trace!("Failed to unlock outputs: {}", err);
+                }
+                return;
+            }
+        };
+        let client = self.client.get_async();
+        let outpoints = outpoints.to_vec();
+        handle.spawn(async move {
+            if let Err(err) = client.unlock_unspent(&outpoints).await {
+                // the outputs may have been spent in the meantime, or bitcoind was restarted
+                trace!("Failed to unlock outputs: {}", err);
+            }
+        });
+    }
+}
+
+/// Outputs reserved for a single transaction. The outputs are unlocked when this is
+/// dropped without the transaction being broadcast.
+pub struct UtxoReservation {
+    reservations: UtxoReservations,
+    id: u64,
+    outpoints: Vec<OutPoint>,
+}
+
+impl UtxoReservation {
+    /// Keep the outputs reserved for the broadcast transaction until it leaves the mempool.
+    pub(crate) fn spent(mut self, txid: Txid) {
+        self.reservations.broadcast(self.id, &self.outpoints, txid);
+        self.outpoints.clear();
+    }
+}
+
+impl Drop for UtxoReservation {
+    fn drop(&mut self) {
+        if self.outpoints.is_empty() {
+            return;
+        }
+        let outpoints = self.reservations.take(Reserved::Funded(self.id), Some(&self.outpoints));
+        if !outpoints.is_empty() {
+            self.reservations.unlock(&outpoints);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Auth, Hash};
+
+    fn reservations() -> UtxoReservations {
+        // unlocking fails without a node, which is ignored
+        let client = ReloadingClient::new("http://127.0.0.1:1".to_string(), Auth::None).unwrap();
+        UtxoReservations::new(Arc::new(client))
+    }
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), vout)
+    }
+
+    fn num_reserved(reservations: &UtxoReservations) -> usize {
+        reservations.reserved.lock().unwrap().len()
+    }
+
+    #[test]
+    fn test_reservation_released_on_drop() {
+        let reservations = reservations();
+        let first = reservations.reserve(vec![outpoint(0), outpoint(1)]);
+        let _second = reservations.reserve(vec![outpoint(2)]);
+        assert_eq!(num_reserved(&reservations), 3);
+
+        drop(first);
+        assert_eq!(num_reserved(&reservations), 1);
+    }
+
+    #[test]
+    fn test_broadcast_reservation_released_after_mempool() {
+        let reservations = reservations();
+        let txid = Txid::from_slice(&[2; 32]).unwrap();
+        reservations.reserve(vec![outpoint(0), outpoint(1)]).spent(txid);
+        let _other = reservations.reserve(vec![outpoint(2)]);
+        assert_eq!(num_reserved(&reservations), 3);
+        assert_eq!(reservations.broadcast_txids(), vec![txid].into_iter().collect());
+
+        reservations.release_broadcast(&txid);
+        assert_eq!(num_reserved(&reservations), 1);
+        assert!(reservations.broadcast_txids().is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Interval at which the progress of a running rescan is polled.
+pub const SCAN_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Progress of a running wallet rescan.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScanProgress {
+    /// Time since the rescan started.
+    pub elapsed: Duration,
+    /// Fraction of the blocks that have been scanned, between 0 and 1.
+    pub progress: f64,
+}
+
+impl ScanProgress {
+    /// Estimated time until the rescan completes, assuming that blocks are scanned at
+    /// a constant rate. `None` until any progress has been made.
+    pub fn remaining(&self) -> Option<Duration> {
+        if self.progress <= 0.0 || self.progress > 1.0 {
+            return None;
+        }
+        Some(self.elapsed.mul_f64((1.0 - self.progress) / self.progress))
+    }
+}
+
+/// The `scanning` field of `getwalletinfo` is `false` if no rescan is running.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Scanning {
+    Idle(bool),
+    Running { duration: u64, progress: f64 },
+}
+
+/// Subset of the response of `getwalletinfo`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct GetWalletInfoScanning {
+    /// Not reported by nodes older than 0.19.
+    scanning: Option<Scanning>,
+}
+
+impl GetWalletInfoScanning {
+    pub(crate) fn progress(&self) -> Option<ScanProgress> {
+        match self.scanning {
+            Some(Scanning::Running { duration, progress }) => Some(ScanProgress {
+                elapsed: Duration::from_secs(duration),
+                progress,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_scan_progress() {
+        let info: GetWalletInfoScanning =
+            serde_json::from_str(r#"{"walletname": "", "scanning": {"duration": 120, "progress": 0.25}}"#).unwrap();
+        let progress = info.progress().unwrap();
+        assert_eq!(progress.elapsed, Duration::from_secs(120));
+        assert_eq!(progress.remaining(), Some(Duration::from_secs(360)));
+
+        let info: GetWalletInfoScanning = serde_json::from_str(r#"{"walletname": "", "scanning": false}"#).unwrap();
+        assert_eq!(info.progress(), None);
+        let info: GetWalletInfoScanning = serde_json::from_str(r#"{"walletname": ""}"#).unwrap();
+        assert_eq!(info.progress(), None);
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+
+/// Serialize bytes as a hex string, for use with `#[serde(with = "serde_hex")]`.
+pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+/// Deserialize bytes from a hex string.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let hex_str = String::deserialize(deserializer)?;
+    hex::decode(&hex_str).map_err(D::Error::custom)
+}
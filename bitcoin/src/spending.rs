@@ -0,0 +1,89 @@
+use crate::{OutPoint, Txid};
+use serde::{Deserialize, Serialize};
+
+/// Output as given to `gettxspendingprevout` and returned by `listunspent`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Prevout {
+    pub txid: Txid,
+    pub vout: u32,
+}
+
+impl From<OutPoint> for Prevout {
+    fn from(outpoint: OutPoint) -> Self {
+        Prevout {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+        }
+    }
+}
+
+impl From<Prevout> for OutPoint {
+    fn from(prevout: Prevout) -> Self {
+        OutPoint::new(prevout.txid, prevout.vout)
+    }
+}
+
+/// Entry of the response of `gettxspendingprevout`, available since Bitcoin Core 24.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct SpendingPrevoutResult {
+    pub txid: Txid,
+    pub vout: u32,
+    /// The mempool transaction spending the output, if any.
+    pub spendingtxid: Option<Txid>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct WalletTransaction {
+    txid: Txid,
+}
+
+/// Response of `listsinceblock` for the tip, i.e. the unconfirmed transactions of the wallet.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ListSinceBlockResult {
+    transactions: Vec<WalletTransaction>,
+}
+
+impl ListSinceBlockResult {
+    /// Txids of the transactions, which are listed once per wallet input or output.
+    pub(crate) fn txids(&self) -> Vec<Txid> {
+        let mut txids: Vec<_> = self.transactions.iter().map(|entry| entry.txid).collect();
+        txids.sort();
+        txids.dedup();
+        txids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Hash;
+
+    #[test]
+    fn test_decode_spending_prevout() {
+        let txid = "b1e9dbaa2e4bdbb4e76c6c2ad1dc5d0a8a95c4ef55f5e67d4d3f0e4bca5e3e0a";
+        let result: Vec<SpendingPrevoutResult> = serde_json::from_str(&format!(
+            r#"[{{"txid": "{0}", "vout": 0, "spendingtxid": "{0}"}}, {{"txid": "{0}", "vout": 1}}]"#,
+            txid
+        ))
+        .unwrap();
+        assert_eq!(result[0].spendingtxid, Some(txid.parse().unwrap()));
+        assert_eq!(result[1].spendingtxid, None);
+
+        let prevout = Prevout::from(OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 2));
+        assert_eq!(
+            serde_json::to_value(prevout).unwrap()["vout"],
+            serde_json::Value::from(2)
+        );
+    }
+
+    #[test]
+    fn test_decode_list_since_block() {
+        let txid = "b1e9dbaa2e4bdbb4e76c6c2ad1dc5d0a8a95c4ef55f5e67d4d3f0e4bca5e3e0a";
+        let result: ListSinceBlockResult = serde_json::from_str(&format!(
+            r#"{{"transactions": [{{"txid": "{0}", "category": "send", "vout": 0}}, {{"txid": "{0}", "category": "send", "vout": 1}}], "removed": [], "lastblock": "{0}"}}"#,
+            txid
+        ))
+        .unwrap();
+        assert_eq!(result.txids(), vec![txid.parse().unwrap()]);
+    }
+}
@@ -0,0 +1,348 @@
+//! Canonical transactions with the expected results of the OP_RETURN and address matching
+//! in `TransactionExt`, so that other implementations of the matching (e.g. in the
+//! parachain) can be checked against the same fixtures.
+
+use crate::{
+    opcodes, serialize, Builder, Hash, OutPoint, Payload, PubkeyHash, Script, Transaction, TransactionExt, TxIn, TxOut,
+    Txid, WPubkeyHash,
+};
+use sp_core::H256;
+
+/// Compressed public key of the vault, its P2WPKH address holds the vault's funds.
+pub const VAULT_PUBLIC_KEY: &str = "037dbedcebf19e92d3d2f10846f3470797d7ba74f3faf111ab2fa94f77fd7e58d7";
+
+/// Compressed public key of the user paying for an issue request.
+pub const USER_PUBLIC_KEY: &str = "02b309205f020e2c9643f12ce0eea9ec5b3e1e3be99df61f629fe22687d7d80238";
+
+/// Request id committed to by the OP_RETURN outputs.
+pub const REQUEST_ID: [u8; 32] = [0x42; 32];
+
+/// Amount of the payments, in satoshis.
+pub const PAYMENT_AMOUNT: u64 = 100_000;
+
+const CHANGE_AMOUNT: u64 = 50_000;
+
+/// Results of the matching expected for a test vector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expected {
+    /// Request id of the first OP_RETURN output among the first three outputs.
+    pub op_return: Option<H256>,
+    /// Amount paid to the recipient of the vector among the first three outputs.
+    pub payment_amount: Option<u64>,
+    pub input_addresses: Vec<Payload>,
+    /// Addresses of the outputs with a non-zero value.
+    pub output_addresses: Vec<Payload>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestVector {
+    pub name: &'static str,
+    pub transaction: Transaction,
+    /// Address of which the payment amount is matched.
+    pub recipient: Payload,
+    pub expected: Expected,
+}
+
+impl TestVector {
+    /// The serialized transaction, e.g. for fixtures in other languages.
+    pub fn raw_tx_hex(&self) -> String {
+        hex::encode(serialize(&self.transaction))
+    }
+}
+
+fn public_key(hex: &str) -> Vec<u8> {
+    hex::decode(hex).expect("valid hex")
+}
+
+/// P2WPKH address of the public key.
+pub fn wpkh_address(public_key_hex: &str) -> Payload {
+    let script = Script::new_v0_wpkh(&WPubkeyHash::hash(&public_key(public_key_hex)));
+    Payload::from_script(&script).expect("standard script")
+}
+
+/// P2PKH address with the given hash, e.g. that of a redeemer.
+pub fn pkh_address(hash: [u8; 20]) -> Payload {
+    let script = Script::new_p2pkh(&PubkeyHash::from_slice(&hash).expect("20 bytes"));
+    Payload::from_script(&script).expect("standard script")
+}
+
+/// Address to which the redeemed bitcoin are paid.
+pub fn redeemer_address() -> Payload {
+    pkh_address([0x11; 20])
+}
+
+/// Address not known to the vault, the destination of a theft.
+pub fn thief_address() -> Payload {
+    pkh_address([0x77; 20])
+}
+
+/// Input spending a P2WPKH output of the given key, the signature is not valid.
+fn wpkh_input(public_key_hex: &str, vout: u32) -> TxIn {
+    TxIn {
+        previous_output: OutPoint::new(Txid::from_slice(&[vout as u8 + 1; 32]).expect("32 bytes"), vout),
+        script_sig: Script::new(),
+        sequence: 0xFFFFFFFF,
+        witness: vec![vec![0x30; 71], public_key(public_key_hex)],
+    }
+}
+
+fn output(value: u64, address: &Payload) -> TxOut {
+    TxOut {
+        value,
+        script_pubkey: address.script_pubkey(),
+    }
+}
+
+fn op_return(data: &[u8]) -> TxOut {
+    TxOut {
+        value: 0,
+        script_pubkey: Builder::new()
+            .push_opcode(opcodes::OP_RETURN)
+            .push_slice(data)
+            .into_script(),
+    }
+}
+
+fn transaction(input: Vec<TxIn>, output: Vec<TxOut>) -> Transaction {
+    Transaction {
+        version: 2,
+        lock_time: 0,
+        input,
+        output,
+    }
+}
+
+/// All test vectors, each of which is expected to hold for every implementation of the matching.
+pub fn all() -> Vec<TestVector> {
+    let vault = wpkh_address(VAULT_PUBLIC_KEY);
+    let user = wpkh_address(USER_PUBLIC_KEY);
+    let redeemer = redeemer_address();
+    let thief = thief_address();
+    let request_id = H256::from(REQUEST_ID);
+
+    vec![
+        TestVector {
+            name: "issue_payment",
+            transaction: transaction(
+                vec![wpkh_input(USER_PUBLIC_KEY, 0)],
+                vec![output(PAYMENT_AMOUNT, &vault), output(CHANGE_AMOUNT, &user)],
+            ),
+            recipient: vault.clone(),
+            expected: Expected {
+                op_return: None,
+                payment_amount: Some(PAYMENT_AMOUNT),
+                input_addresses: vec![user.clone()],
+                output_addresses: vec![vault.clone(), user.clone()],
+            },
+        },
+        TestVector {
+            name: "redeem_payment",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0)],
+                vec![
+                    output(PAYMENT_AMOUNT, &redeemer),
+                    op_return(&REQUEST_ID),
+                    output(CHANGE_AMOUNT, &vault),
+                ],
+            ),
+            recipient: redeemer.clone(),
+            expected: Expected {
+                op_return: Some(request_id),
+                payment_amount: Some(PAYMENT_AMOUNT),
+                input_addresses: vec![vault.clone()],
+                output_addresses: vec![redeemer.clone(), vault.clone()],
+            },
+        },
+        TestVector {
+            name: "redeem_op_return_first",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0)],
+                vec![op_return(&REQUEST_ID), output(PAYMENT_AMOUNT, &redeemer)],
+            ),
+            recipient: redeemer.clone(),
+            expected: Expected {
+                op_return: Some(request_id),
+                payment_amount: Some(PAYMENT_AMOUNT),
+                input_addresses: vec![vault.clone()],
+                output_addresses: vec![redeemer.clone()],
+            },
+        },
+        TestVector {
+            // the parachain only checks the first three outputs
+            name: "op_return_after_third_output",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0)],
+                vec![
+                    output(CHANGE_AMOUNT, &vault),
+                    output(CHANGE_AMOUNT, &vault),
+                    output(CHANGE_AMOUNT, &vault),
+                    op_return(&REQUEST_ID),
+                    output(PAYMENT_AMOUNT, &redeemer),
+                ],
+            ),
+            recipient: redeemer.clone(),
+            expected: Expected {
+                op_return: None,
+                payment_amount: None,
+                input_addresses: vec![vault.clone()],
+                output_addresses: vec![vault.clone(), vault.clone(), vault.clone(), redeemer.clone()],
+            },
+        },
+        TestVector {
+            name: "op_return_wrong_length",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0)],
+                vec![output(PAYMENT_AMOUNT, &redeemer), op_return(&REQUEST_ID[..31])],
+            ),
+            recipient: redeemer.clone(),
+            expected: Expected {
+                op_return: None,
+                payment_amount: Some(PAYMENT_AMOUNT),
+                input_addresses: vec![vault.clone()],
+                output_addresses: vec![redeemer.clone()],
+            },
+        },
+        TestVector {
+            // spends the vault's funds without a request, to be reported as theft
+            name: "theft_spend",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0), wpkh_input(VAULT_PUBLIC_KEY, 1)],
+                vec![output(PAYMENT_AMOUNT, &thief)],
+            ),
+            recipient: thief.clone(),
+            expected: Expected {
+                op_return: None,
+                payment_amount: Some(PAYMENT_AMOUNT),
+                input_addresses: vec![vault.clone(), vault.clone()],
+                output_addresses: vec![thief.clone()],
+            },
+        },
+        TestVector {
+            // e.g. consolidating the vault's outputs, no funds leave the vault
+            name: "return_to_self",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0), wpkh_input(VAULT_PUBLIC_KEY, 1)],
+                vec![output(PAYMENT_AMOUNT, &vault)],
+            ),
+            recipient: vault.clone(),
+            expected: Expected {
+                op_return: None,
+                payment_amount: Some(PAYMENT_AMOUNT),
+                input_addresses: vec![vault.clone(), vault.clone()],
+                output_addresses: vec![vault.clone()],
+            },
+        },
+        TestVector {
+            // the payment and the change both go to the vault, e.g. a redeem by the vault itself
+            name: "return_to_self_with_op_return",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0)],
+                vec![
+                    output(PAYMENT_AMOUNT, &vault),
+                    op_return(&REQUEST_ID),
+                    output(CHANGE_AMOUNT, &vault),
+                ],
+            ),
+            recipient: vault.clone(),
+            expected: Expected {
+                op_return: Some(request_id),
+                payment_amount: Some(PAYMENT_AMOUNT),
+                input_addresses: vec![vault.clone()],
+                output_addresses: vec![vault.clone(), vault.clone()],
+            },
+        },
+        TestVector {
+            // outputs without value are not counted as output addresses
+            name: "zero_value_output",
+            transaction: transaction(
+                vec![wpkh_input(VAULT_PUBLIC_KEY, 0)],
+                vec![output(0, &thief), output(PAYMENT_AMOUNT, &vault)],
+            ),
+            recipient: thief.clone(),
+            expected: Expected {
+                op_return: None,
+                payment_amount: Some(0),
+                input_addresses: vec![vault.clone()],
+                output_addresses: vec![vault],
+            },
+        },
+    ]
+}
+
+/// Check an OP_RETURN matcher against all vectors, panicking with the name of the first
+/// vector that does not match.
+pub fn assert_op_return_matcher<F: Fn(&Transaction) -> Option<H256>>(matcher: F) {
+    for vector in all() {
+        assert_eq!(
+            matcher(&vector.transaction),
+            vector.expected.op_return,
+            "op_return of {}",
+            vector.name
+        );
+    }
+}
+
+/// Check a payment matcher against all vectors, panicking with the name of the first
+/// vector that does not match.
+pub fn assert_payment_matcher<F: Fn(&Transaction, &Payload) -> Option<u64>>(matcher: F) {
+    for vector in all() {
+        assert_eq!(
+            matcher(&vector.transaction, &vector.recipient),
+            vector.expected.payment_amount,
+            "payment amount of {}",
+            vector.name
+        );
+    }
+}
+
+/// Check all expectations of the vector against the matching of `TransactionExt`.
+pub fn assert_vector(vector: &TestVector) {
+    let transaction = &vector.transaction;
+    assert_eq!(
+        transaction.get_op_return(),
+        vector.expected.op_return,
+        "op_return of {}",
+        vector.name
+    );
+    assert_eq!(
+        transaction.get_payment_amount_to(vector.recipient.clone()),
+        vector.expected.payment_amount,
+        "payment amount of {}",
+        vector.name
+    );
+    assert_eq!(
+        transaction.extract_input_addresses::<Payload>(),
+        vector.expected.input_addresses,
+        "input addresses of {}",
+        vector.name
+    );
+    assert_eq!(
+        transaction.extract_output_addresses::<Payload>(),
+        vector.expected.output_addresses,
+        "output addresses of {}",
+        vector.name
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize;
+
+    #[test]
+    fn test_transaction_ext_matches_vectors() {
+        for vector in all() {
+            assert_vector(&vector);
+        }
+        assert_op_return_matcher(|transaction| transaction.get_op_return());
+        assert_payment_matcher(|transaction, recipient| transaction.get_payment_amount_to(recipient.clone()));
+    }
+
+    #[test]
+    fn test_raw_tx_roundtrip() {
+        for vector in all() {
+            let transaction: Transaction = deserialize(&hex::decode(vector.raw_tx_hex()).unwrap()).unwrap();
+            assert_eq!(transaction, vector.transaction, "{}", vector.name);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{vsize, Error, Transaction};
+
+/// Maximum virtual size of a standard transaction, larger transactions are not relayed.
+pub const MAX_STANDARD_TX_VSIZE: u64 = 100_000;
+
+/// Default maximum number of inputs. Each input adds to the size of the transaction that is
+/// submitted to the parachain as part of the inclusion proof.
+pub const DEFAULT_MAX_INPUTS: usize = 250;
+
+/// Upper bound on the virtual size and the number of inputs of created transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionLimits {
+    max_vsize: u64,
+    max_inputs: usize,
+}
+
+impl Default for TransactionLimits {
+    fn default() -> Self {
+        Self {
+            max_vsize: MAX_STANDARD_TX_VSIZE,
+            max_inputs: DEFAULT_MAX_INPUTS,
+        }
+    }
+}
+
+impl TransactionLimits {
+    /// The limits can only be lowered, a transaction above the standard size would not be relayed.
+    pub fn new(max_vsize: u64, max_inputs: usize) -> Result<Self, Error> {
+        if max_vsize == 0 || max_vsize > MAX_STANDARD_TX_VSIZE || max_inputs == 0 {
+            return Err(Error::InvalidTransactionLimits { max_vsize, max_inputs });
+        }
+        Ok(Self { max_vsize, max_inputs })
+    }
+
+    pub fn max_vsize(&self) -> u64 {
+        self.max_vsize
+    }
+
+    pub fn max_inputs(&self) -> usize {
+        self.max_inputs
+    }
+
+    /// Ensure that the signed transaction is within the limits.
+    pub fn check(&self, transaction: &Transaction) -> Result<(), Error> {
+        let inputs = transaction.input.len();
+        if inputs > self.max_inputs {
+            return Err(Error::TooManyInputs {
+                inputs,
+                max_inputs: self.max_inputs,
+            });
+        }
+        let vsize = vsize(transaction);
+        if vsize > self.max_vsize {
+            return Err(Error::TransactionTooLarge {
+                vsize,
+                max_vsize: self.max_vsize,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash, OutPoint, Script, TxIn, TxOut, Txid};
+
+    fn transaction(inputs: usize) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: (0..inputs)
+                .map(|vout| TxIn {
+                    previous_output: OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), vout as u32),
+                    script_sig: Script::new(),
+                    sequence: 0xFFFFFFFF,
+                    witness: vec![],
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: 1000,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_transaction_limits() {
+        assert!(TransactionLimits::new(0, 1).is_err());
+        assert!(TransactionLimits::new(MAX_STANDARD_TX_VSIZE + 1, 1).is_err());
+        assert!(TransactionLimits::new(1000, 0).is_err());
+
+        let limits = TransactionLimits::new(1000, 10).unwrap();
+        assert!(limits.check(&transaction(10)).is_ok());
+        assert!(matches!(
+            limits.check(&transaction(11)),
+            Err(Error::TooManyInputs { inputs: 11, .. })
+        ));
+
+        // each input without witness is 41 bytes
+        let limits = TransactionLimits::new(200, 10).unwrap();
+        assert!(matches!(
+            limits.check(&transaction(5)),
+            Err(Error::TransactionTooLarge { max_vsize: 200, .. })
+        ));
+    }
+}
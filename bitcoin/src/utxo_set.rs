@@ -0,0 +1,104 @@
+use crate::{btc_to_sat, ConversionError, Error, Network, OutPoint, PartialAddress, Script, Txid};
+use serde::Deserialize;
+
+/// Unspent output found in the UTXO set, independent of any wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub script_pubkey: Script,
+    /// Descriptor that matched the output.
+    pub descriptor: String,
+    /// Amount in satoshis.
+    pub amount: u64,
+    /// Height of the block that created the output.
+    pub height: u32,
+}
+
+/// Descriptor of the outputs paying to the address, as accepted by `scantxoutset`.
+pub fn address_descriptor<A: PartialAddress>(address: &A, network: Network) -> Result<String, ConversionError> {
+    Ok(format!("addr({})", address.encode_str(network)?))
+}
+
+/// Response of `scantxoutset start`.
+#[derive(Deserialize)]
+pub(crate) struct ScanTxOutSetResult {
+    /// Absent in versions before Bitcoin Core 0.21.
+    #[serde(default = "default_success")]
+    success: bool,
+    unspents: Vec<ScanTxOutSetUnspent>,
+}
+
+fn default_success() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanTxOutSetUnspent {
+    txid: Txid,
+    vout: u32,
+    script_pub_key: String,
+    desc: String,
+    amount: f64,
+    height: u32,
+}
+
+impl ScanTxOutSetResult {
+    pub(crate) fn into_utxos(self) -> Result<Vec<Utxo>, Error> {
+        if !self.success {
+            return Err(Error::UtxoScanAborted);
+        }
+        self.unspents
+            .into_iter()
+            .map(|unspent| {
+                Ok(Utxo {
+                    outpoint: OutPoint::new(unspent.txid, unspent.vout),
+                    script_pubkey: Script::from(hex::decode(&unspent.script_pub_key).map_err(ConversionError::from)?),
+                    descriptor: unspent.desc,
+                    amount: btc_to_sat(unspent.amount)?,
+                    height: unspent.height,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Payload;
+
+    #[test]
+    fn test_decode_scan_result() {
+        let txid = "b1e9dbaa2e4bdbb4e76c6c2ad1dc5d0a8a95c4ef55f5e67d4d3f0e4bca5e3e0a";
+        let json = serde_json::json!({
+            "success": true,
+            "txouts": 1000,
+            "height": 120,
+            "bestblock": "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            "unspents": [{
+                "txid": txid,
+                "vout": 1,
+                "scriptPubKey": "0014d3158f03dc61d9cd1bf9558a3226512453ce12a3",
+                "desc": "addr(bcrt1q6v2c7q7uv8vu6xle2k9ryfj3y3fuuy4rqnl50f)",
+                "amount": 0.5,
+                "height": 101
+            }],
+            "total_amount": 0.5
+        });
+        let utxos = serde_json::from_value::<ScanTxOutSetResult>(json)
+            .unwrap()
+            .into_utxos()
+            .unwrap();
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos[0].outpoint, OutPoint::new(txid.parse().unwrap(), 1));
+        assert_eq!(utxos[0].amount, 50_000_000);
+        assert_eq!(utxos[0].height, 101);
+
+        let payload = Payload::from_script(&utxos[0].script_pubkey).unwrap();
+        assert_eq!(
+            address_descriptor(&payload, Network::Regtest).unwrap(),
+            "addr(bcrt1q6v2c7q7uv8vu6xle2k9ryfj3y3fuuy4rqnl50f)"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+
+/// Only track transactions from the time of the import, the chain is not rescanned.
+const IMPORT_TIMESTAMP: &str = "now";
+
+#[derive(Debug, Serialize)]
+struct ScriptPubKey<'a> {
+    address: &'a str,
+}
+
+/// Request of `importmulti`, supported by legacy wallets.
+#[derive(Debug, Serialize)]
+pub(crate) struct ImportMultiRequest<'a> {
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: ScriptPubKey<'a>,
+    timestamp: &'static str,
+    watchonly: bool,
+    label: &'a str,
+}
+
+impl<'a> ImportMultiRequest<'a> {
+    pub(crate) fn new(address: &'a str, label: &'a str) -> Self {
+        Self {
+            script_pub_key: ScriptPubKey { address },
+            timestamp: IMPORT_TIMESTAMP,
+            watchonly: true,
+            label,
+        }
+    }
+}
+
+/// Request of `importdescriptors`, supported by descriptor wallets.
+#[derive(Debug, Serialize)]
+pub(crate) struct ImportDescriptorRequest<'a> {
+    /// Descriptor including its checksum, see `getdescriptorinfo`.
+    desc: String,
+    timestamp: &'static str,
+    label: &'a str,
+}
+
+impl<'a> ImportDescriptorRequest<'a> {
+    pub(crate) fn new(desc: String, label: &'a str) -> Self {
+        Self {
+            desc,
+            timestamp: IMPORT_TIMESTAMP,
+            label,
+        }
+    }
+}
+
+/// Response of `getdescriptorinfo`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct DescriptorInfo {
+    pub descriptor: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportError {
+    message: String,
+}
+
+/// Entry of the response of `importmulti` and `importdescriptors`, one per request.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ImportResult {
+    success: bool,
+    #[serde(default)]
+    error: Option<ImportError>,
+}
+
+/// Fail with the error of the first request that was not imported.
+pub(crate) fn check_import_results(results: Vec<ImportResult>) -> Result<(), Error> {
+    match results.into_iter().find(|result| !result.success) {
+        Some(result) => Err(Error::WatchOnlyImportFailed(
+            result.error.map(|error| error.message).unwrap_or_default(),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_import_requests() {
+        assert_eq!(
+            serde_json::to_value(ImportMultiRequest::new("bcrt1qaddress", "vaults")).unwrap(),
+            serde_json::json!({
+                "scriptPubKey": { "address": "bcrt1qaddress" },
+                "timestamp": "now",
+                "watchonly": true,
+                "label": "vaults",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(ImportDescriptorRequest::new(
+                "addr(bcrt1qaddress)#checksum".to_string(),
+                "vaults"
+            ))
+            .unwrap(),
+            serde_json::json!({
+                "desc": "addr(bcrt1qaddress)#checksum",
+                "timestamp": "now",
+                "label": "vaults",
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_import_results() {
+        let results: Vec<ImportResult> = serde_json::from_str(r#"[{"success":true},{"success":true}]"#).unwrap();
+        assert!(check_import_results(results).is_ok());
+
+        let results: Vec<ImportResult> = serde_json::from_str(
+            r#"[{"success":true},{"success":false,"error":{"code":-5,"message":"Invalid address"}}]"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            check_import_results(results),
+            Err(Error::WatchOnlyImportFailed(message)) if message == "Invalid address"
+        ));
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{BitcoinCore, BitcoinCoreApi, BlockHash, Error, OutPoint, RawBlock, Script, Transaction, TxOut, Txid};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Number of connected blocks kept to roll back their deltas on a reorg.
+pub const MAX_REORG_DEPTH: usize = 100;
+
+/// An output paying to one of the watched scripts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deposit {
+    pub outpoint: OutPoint,
+    pub output: TxOut,
+}
+
+/// The spend of an output paying to one of the watched scripts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Spend {
+    pub outpoint: OutPoint,
+    pub output: TxOut,
+    pub spending_txid: Txid,
+}
+
+/// Deposits to and spends from the watched scripts in one block, or in the mempool.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Delta {
+    pub deposits: Vec<Deposit>,
+    pub spends: Vec<Spend>,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.deposits.is_empty() && self.spends.is_empty()
+    }
+}
+
+/// Serialized with the variant in `type` (`connected`, `disconnected` or `mempool`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WatchEvent {
+    /// The block was connected to the main chain.
+    Connected { height: u32, hash: BlockHash, delta: Delta },
+    /// The block was disconnected by a reorg, its delta no longer applies. Disconnected
+    /// blocks are reported from the tip downwards, before the blocks of the new chain.
+    Disconnected { height: u32, hash: BlockHash, delta: Delta },
+    /// Deposits and spends of the transactions currently in the mempool, replacing the
+    /// previously reported mempool delta.
+    Mempool(Delta),
+}
+
+struct ConnectedBlock {
+    height: u32,
+    hash: BlockHash,
+    delta: Delta,
+}
+
+/// Scans every new block for deposits to and spends from a set of scripts, so that issue
+/// detection, theft monitoring and accounting share a single block scan. Blocks are
+/// parsed with `RawBlock` without deserializing their transactions.
+pub struct AddressWatcher {
+    scripts: HashSet<Vec<u8>>,
+    /// Unspent outputs paying to the watched scripts, to detect their spends.
+    outputs: HashMap<OutPoint, TxOut>,
+    connected: VecDeque<ConnectedBlock>,
+    next_height: u32,
+    include_mempool: bool,
+}
+
+impl AddressWatcher {
+    /// Watch the given scripts from the block at `start_height`.
+    pub fn new(scripts: impl IntoIterator<Item = Script>, start_height: u32) -> Self {
+        Self {
+            scripts: scripts.into_iter().map(Script::into_bytes).collect(),
+            outputs: HashMap::new(),
+            connected: VecDeque::new(),
+            next_height: start_height,
+            include_mempool: false,
+        }
+    }
+
+    /// Also report the deposits and spends of unconfirmed transactions.
+    pub fn with_mempool(mut self, include_mempool: bool) -> Self {
+        self.include_mempool = include_mempool;
+        self
+    }
+
+    pub fn watch_script(&mut self, script: Script) {
+        self.scripts.insert(script.into_bytes());
+    }
+
+    /// Track an output created before the start height, so that its spend is reported.
+    pub fn track_output(&mut self, outpoint: OutPoint, output: TxOut) {
+        self.outputs.insert(outpoint, output);
+    }
+
+    /// Scan the blocks connected since the last poll, rolling back the blocks that were
+    /// disconnected in the meantime.
+    pub async fn poll(&mut self, rpc: &BitcoinCore) -> Result<Vec<WatchEvent>, Error> {
+        let mut events = self.roll_back(rpc).await?;
+
+        let tip = rpc.get_block_count().await? as u32;
+        while self.next_height <= tip {
+            let hash = rpc.get_block_hash(self.next_height).await?;
+            let bytes = rpc.get_raw_block(&hash).await?;
+            let raw_block = RawBlock::new(&bytes);
+            if let Some(last) = self.connected.back() {
+                if raw_block.header()?.prev_blockhash != last.hash {
+                    // reorg during the scan, rolled back on the next poll
+                    break;
+                }
+            }
+
+            let delta = self.apply_block(&raw_block)?;
+            events.push(WatchEvent::Connected {
+                height: self.next_height,
+                hash,
+                delta: delta.clone(),
+            });
+            self.connected.push_back(ConnectedBlock {
+                height: self.next_height,
+                hash,
+                delta,
+            });
+            if self.connected.len() > MAX_REORG_DEPTH {
+                self.connected.pop_front();
+            }
+            self.next_height += 1;
+        }
+
+        if self.include_mempool {
+            let transactions = rpc.get_mempool_transactions().await?.collect::<Result<Vec<_>, _>>()?;
+            events.push(WatchEvent::Mempool(self.mempool_delta(&transactions)));
+        }
+        Ok(events)
+    }
+
+    /// Disconnect the blocks that are no longer in the main chain and revert their deltas.
+    async fn roll_back(&mut self, rpc: &BitcoinCore) -> Result<Vec<WatchEvent>, Error> {
+        let mut events = Vec::new();
+        while let Some(last) = self.connected.back() {
+            match rpc.get_block_hash(last.height).await {
+                Ok(hash) if hash == last.hash => break,
+                // the chain may have become shorter
+                Ok(_) | Err(Error::InvalidBitcoinHeight) => {}
+                Err(err) => return Err(err),
+            }
+            let block = self.connected.pop_back().expect("checked above");
+            self.revert(&block.delta);
+            self.next_height = block.height;
+            events.push(WatchEvent::Disconnected {
+                height: block.height,
+                hash: block.hash,
+                delta: block.delta,
+            });
+        }
+        Ok(events)
+    }
+
+    fn apply_block(&mut self, raw_block: &RawBlock) -> Result<Delta, Error> {
+        let mut delta = Delta::default();
+        for transaction in raw_block.transactions()? {
+            let transaction = transaction?;
+            let txid = transaction.txid();
+            for outpoint in transaction.previous_outputs() {
+                if let Some(output) = self.outputs.remove(&outpoint) {
+                    delta.spends.push(Spend {
+                        outpoint,
+                        output,
+                        spending_txid: txid,
+                    });
+                }
+            }
+            for (vout, (value, script)) in transaction.outputs().iter().enumerate() {
+                if self.scripts.contains(*script) {
+                    let deposit = Deposit {
+                        outpoint: OutPoint::new(txid, vout as u32),
+                        output: TxOut {
+                            value: *value,
+                            script_pubkey: Script::from(script.to_vec()),
+                        },
+                    };
+                    self.outputs.insert(deposit.outpoint, deposit.output.clone());
+                    delta.deposits.push(deposit);
+                }
+            }
+        }
+        Ok(delta)
+    }
+
+    /// Spends are reverted first, outputs created and spent within the block are dropped.
+    fn revert(&mut self, delta: &Delta) {
+        for spend in &delta.spends {
+            self.outputs.insert(spend.outpoint, spend.output.clone());
+        }
+        for deposit in &delta.deposits {
+            self.outputs.remove(&deposit.outpoint);
+        }
+    }
+
+    /// The mempool is not applied to the tracked outputs, it is reported as a whole on
+    /// every poll.
+    fn mempool_delta(&self, transactions: &[Transaction]) -> Delta {
+        let mut delta = Delta::default();
+        let mut unconfirmed = HashMap::new();
+        for transaction in transactions {
+            let txid = transaction.txid();
+            for (vout, output) in transaction.output.iter().enumerate() {
+                if self.scripts.contains(output.script_pubkey.as_bytes()) {
+                    let outpoint = OutPoint::new(txid, vout as u32);
+                    unconfirmed.insert(outpoint, output.clone());
+                    delta.deposits.push(Deposit {
+                        outpoint,
+                        output: output.clone(),
+                    });
+                }
+            }
+        }
+        for transaction in transactions {
+            for input in &transaction.input {
+                let outpoint = input.previous_output;
+                if let Some(output) = self.outputs.get(&outpoint).or_else(|| unconfirmed.get(&outpoint)) {
+                    delta.spends.push(Spend {
+                        outpoint,
+                        output: output.clone(),
+                        spending_txid: transaction.txid(),
+                    });
+                }
+            }
+        }
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialize, Block, BlockHeader, Hash, TxIn, TxMerkleNode};
+
+    fn transaction(previous_output: OutPoint, value: u64, script: &Script) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: Script::new(),
+                sequence: 0xFFFFFFFF,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: script.clone(),
+            }],
+        }
+    }
+
+    fn block(txdata: Vec<Transaction>) -> Vec<u8> {
+        serialize(&Block {
+            header: BlockHeader {
+                version: 4,
+                prev_blockhash: Default::default(),
+                merkle_root: TxMerkleNode::from_slice(&[2; 32]).unwrap(),
+                time: 1,
+                bits: 2,
+                nonce: 3,
+            },
+            txdata,
+        })
+    }
+
+    #[test]
+    fn test_apply_and_revert_block() {
+        let watched = Script::from(vec![0x51]);
+        let other = Script::from(vec![0x52]);
+        let mut watcher = AddressWatcher::new(vec![watched.clone()], 0);
+
+        let deposit = transaction(OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 0), 1000, &watched);
+        let deposit_outpoint = OutPoint::new(deposit.txid(), 0);
+        let spend = transaction(deposit_outpoint, 900, &other);
+
+        let bytes = block(vec![deposit.clone()]);
+        let delta = watcher.apply_block(&RawBlock::new(&bytes)).unwrap();
+        assert_eq!(delta.deposits.len(), 1);
+        assert_eq!(delta.deposits[0].outpoint, deposit_outpoint);
+        assert!(delta.spends.is_empty());
+
+        // unconfirmed spends are reported without being applied
+        let mempool = watcher.mempool_delta(&[spend.clone()]);
+        assert_eq!(mempool.spends[0].spending_txid, spend.txid());
+        assert!(watcher.outputs.contains_key(&deposit_outpoint));
+
+        let bytes = block(vec![spend.clone()]);
+        let spend_delta = watcher.apply_block(&RawBlock::new(&bytes)).unwrap();
+        assert_eq!(spend_delta.spends.len(), 1);
+        assert!(spend_delta.deposits.is_empty());
+        assert!(watcher.outputs.is_empty());
+
+        // rolling back the spend makes the output unspent again
+        watcher.revert(&spend_delta);
+        assert!(watcher.outputs.contains_key(&deposit_outpoint));
+        watcher.revert(&delta);
+        assert!(watcher.outputs.is_empty());
+    }
+
+    #[test]
+    fn test_watch_event_serde() {
+        let outpoint = OutPoint::new(Txid::from_slice(&[1; 32]).unwrap(), 0);
+        let output = TxOut {
+            value: 1000,
+            script_pubkey: Script::from(vec![0x51]),
+        };
+        let event = WatchEvent::Connected {
+            height: 1,
+            hash: BlockHash::from_slice(&[2; 32]).unwrap(),
+            delta: Delta {
+                deposits: vec![Deposit {
+                    outpoint,
+                    output: output.clone(),
+                }],
+                spends: vec![Spend {
+                    outpoint,
+                    output,
+                    spending_txid: Txid::from_slice(&[3; 32]).unwrap(),
+                }],
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "connected");
+        assert_eq!(serde_json::from_value::<WatchEvent>(json).unwrap(), event);
+
+        let event = WatchEvent::Mempool(Delta::default());
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "mempool");
+        assert_eq!(serde_json::from_value::<WatchEvent>(json).unwrap(), event);
+    }
+}
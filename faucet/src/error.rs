@@ -3,6 +3,7 @@ use jsonrpc_http_server::jsonrpc_core::Error as JsonRpcError;
 use kv::Error as KvError;
 use parity_scale_codec::Error as CodecError;
 use runtime::Error as RuntimeError;
+use service::Error as ServiceError;
 use std::net::AddrParseError;
 use thiserror::Error;
 
@@ -10,6 +11,8 @@ use thiserror::Error;
 pub enum Error {
     #[error("RuntimeError: {0}")]
     RuntimeError(#[from] RuntimeError),
+    #[error("ServiceError: {0}")]
+    ServiceError(#[from] ServiceError),
     #[error("CodecError: {0}")]
     CodecError(#[from] CodecError),
     #[error("JsonRpcError: {0}")]
@@ -8,7 +8,8 @@ use jsonrpc_http_server::{
 use kv::*;
 use parity_scale_codec::{Decode, Encode};
 use runtime::{
-    AccountId, CollateralBalancesPallet, Error as RuntimeError, InterBtcParachain, VaultRegistryPallet, PLANCK_PER_DOT,
+    AccountId, CollateralAmount, CollateralBalancesPallet, Dot, Error as RuntimeError, InterBtcParachain,
+    VaultRegistryPallet,
 };
 use serde::{Deserialize, Deserializer};
 use std::{collections::HashMap, net::SocketAddr, time::Duration};
@@ -201,11 +202,8 @@ async fn atomic_faucet_funding(
     )
     .await?;
 
-    let amount = allowances
-        .get(&account_type)
-        .ok_or(Error::NoFaucetAllowance)?
-        .checked_mul(PLANCK_PER_DOT)
-        .ok_or(Error::MathError)?;
+    let allowance = allowances.get(&account_type).ok_or(Error::NoFaucetAllowance)?;
+    let amount = CollateralAmount::<Dot>::from_units(*allowance).map_err(|_| Error::MathError)?;
 
     log::info!(
         "AccountId: {}, Type: {:?}, Amount: {}",
@@ -213,7 +211,7 @@ async fn atomic_faucet_funding(
         account_type,
         amount
     );
-    parachain_rpc.transfer_to(&account_id, amount).await?;
+    parachain_rpc.transfer_to(&account_id, amount.planck()).await?;
 
     // Replace the previous (expired) claim datetime with the datetime of the current claim, only update
     // this after successfully transferring funds to ensure that this can be called again on error
@@ -296,12 +294,11 @@ mod tests {
 
     use super::{
         fund_account, open_kv_store, CollateralBalancesPallet, FundAccountJsonRpcRequest, FundingRequestAccountType,
-        PLANCK_PER_DOT,
     };
     use kv::{Config, Store};
     use runtime::{
-        integration::*, AccountId, BtcPublicKey, ExchangeRateOraclePallet, FixedPointNumber, FixedU128,
-        VaultRegistryPallet,
+        integration::*, AccountId, BtcPublicKey, CollateralAmount, ExchangeRateOraclePallet, FixedPointNumber,
+        FixedU128, VaultRegistryPallet, PLANCK_PER_DOT,
     };
     use sp_keyring::AccountKeyring;
 
@@ -444,7 +441,10 @@ mod tests {
         .await
         .expect("Funding the account failed");
 
-        bob_provider.register_vault(100, dummy_public_key()).await.unwrap();
+        bob_provider
+            .register_vault(CollateralAmount::from_planck(100), dummy_public_key())
+            .await
+            .unwrap();
 
         let bob_funds_before = alice_provider
             .get_free_balance_for_id(bob_account_id.clone())
@@ -521,7 +521,10 @@ mod tests {
         let expected_amount_planck: u128 = dot_to_planck(vault_allowance_dot);
 
         let bob_provider = setup_provider(client.clone(), AccountKeyring::Bob).await;
-        bob_provider.register_vault(100, dummy_public_key()).await.unwrap();
+        bob_provider
+            .register_vault(CollateralAmount::from_planck(100), dummy_public_key())
+            .await
+            .unwrap();
 
         let alice_provider = setup_provider(client.clone(), AccountKeyring::Alice).await;
 
@@ -572,7 +575,10 @@ mod tests {
         let expected_amount_planck: u128 = dot_to_planck(vault_allowance_dot);
 
         let bob_provider = setup_provider(client.clone(), AccountKeyring::Bob).await;
-        bob_provider.register_vault(100, dummy_public_key()).await.unwrap();
+        bob_provider
+            .register_vault(CollateralAmount::from_planck(100), dummy_public_key())
+            .await
+            .unwrap();
 
         let alice_provider = setup_provider(client.clone(), AccountKeyring::Alice).await;
         // Drain the amount Bob was prefunded by, so he is eligible to receive Faucet funding
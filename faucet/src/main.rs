@@ -5,7 +5,7 @@ use clap::Clap;
 use error::Error;
 use git_version::git_version;
 use runtime::{substrate_subxt::PairSigner, InterBtcRuntime};
-use service::{on_shutdown, wait_or_shutdown};
+use service::{on_shutdown, route_through_proxy, wait_or_shutdown, Proxy};
 use std::net::SocketAddr;
 
 const VERSION: &str = git_version!(args = ["--tags"]);
@@ -35,6 +35,11 @@ pub struct FaucetConfig {
     #[clap(long, default_value = "[::0]:3033")]
     http_addr: SocketAddr,
 
+    /// SOCKS5 proxy (e.g. Tor) for the parachain connection, of the form
+    /// socks5://[user:password@]host:port.
+    #[clap(long)]
+    proxy: Option<Proxy>,
+
     /// Comma separated list of allowed origins.
     #[clap(long, default_value = "*")]
     rpc_cors_domain: String,
@@ -60,9 +65,13 @@ async fn main() -> Result<(), Error> {
 
     let (shutdown_tx, _) = tokio::sync::broadcast::channel(16);
 
-    let parachain_config = opts.parachain;
+    let mut parachain_config = opts.parachain;
     let faucet_config = opts.faucet;
 
+    // connect through the local end of the proxy tunnel, if any
+    parachain_config.btc_parachain_url =
+        Some(route_through_proxy(faucet_config.proxy.as_ref(), &parachain_config.parachain_url()).await?);
+
     loop {
         let btc_parachain = parachain_config.try_connect(signer.clone()).await?;
 
@@ -75,14 +84,10 @@ async fn main() -> Result<(), Error> {
         )
         .await;
 
-        // run block listener to restart faucet on disconnect
+        // run block listener to restart faucet on disconnect, and to stop it on an
+        // upgrade to an unsupported runtime
         let block_listener = wait_or_shutdown(shutdown_tx.clone(), async move {
-            btc_parachain
-                .on_block(move |header| async move {
-                    log::debug!("Got block {:?}", header);
-                    Ok(())
-                })
-                .await?;
+            btc_parachain.watch_runtime_version().await?;
             Ok(())
         });
 
@@ -0,0 +1,90 @@
+use crate::error::Error;
+use runtime::{cli::ProviderUserOpts, substrate_subxt::PairSigner, InterBtcRuntime, InterBtcSigner};
+use std::str::FromStr;
+
+/// Currency pair of the exchange rate submitted by the oracle.
+pub const BTC_DOT: &str = "btc/dot";
+
+/// Currency pairs (feeds) for which a separate account can be configured.
+pub(crate) const KNOWN_PAIRS: &[&str] = &[BTC_DOT];
+
+/// Account from the keyfile used to submit the feed of a currency pair.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairAccount {
+    pub pair: String,
+    pub keyname: String,
+}
+
+impl FromStr for PairAccount {
+    type Err = Error;
+
+    /// Parses an account of the form `PAIR=KEYNAME`, e.g. `btc/dot=oracle-btc-dot`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidPairAccount(KNOWN_PAIRS.join(", "));
+        let mut parts = s.splitn(2, '=');
+        let pair = parts
+            .next()
+            .map(|pair| pair.trim().to_lowercase())
+            .ok_or_else(invalid)?;
+        let keyname = parts.next().map(|keyname| keyname.trim()).ok_or_else(invalid)?;
+        if !KNOWN_PAIRS.contains(&pair.as_str()) || keyname.is_empty() {
+            return Err(invalid());
+        }
+        Ok(PairAccount {
+            pair,
+            keyname: keyname.to_string(),
+        })
+    }
+}
+
+/// Selects the account that submits each feed, so that a compromised or rate-limited
+/// key only affects a subset of the feeds. Pairs without a dedicated account use the
+/// default account. Keys are loaded for every submission, so that a key can be rotated
+/// by updating the keyfile without restarting the oracle.
+pub struct Accounts {
+    default: ProviderUserOpts,
+    pair_accounts: Vec<PairAccount>,
+}
+
+impl Accounts {
+    pub fn new(default: ProviderUserOpts, pair_accounts: Vec<PairAccount>) -> Result<Self, Error> {
+        for (i, account) in pair_accounts.iter().enumerate() {
+            if pair_accounts[..i].iter().any(|other| other.pair == account.pair) {
+                return Err(Error::DuplicatePairAccount(account.pair.clone()));
+            }
+        }
+        let accounts = Self { default, pair_accounts };
+        // fail early if any of the keys cannot be loaded
+        for pair in KNOWN_PAIRS {
+            accounts.signer(pair)?;
+        }
+        Ok(accounts)
+    }
+
+    pub fn signer(&self, pair: &str) -> Result<InterBtcSigner, Error> {
+        let key_pair = match self.pair_accounts.iter().find(|account| account.pair == pair) {
+            Some(account) => self.default.get_named_key_pair(&account.keyname)?,
+            None => self.default.get_key_pair()?.0,
+        };
+        Ok(PairSigner::<InterBtcRuntime, _>::new(key_pair))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pair_account() {
+        assert_eq!(
+            "BTC/DOT=oracle-1".parse::<PairAccount>().unwrap(),
+            PairAccount {
+                pair: BTC_DOT.to_string(),
+                keyname: "oracle-1".to_string(),
+            }
+        );
+        assert!("btc/dot".parse::<PairAccount>().is_err());
+        assert!("btc/dot=".parse::<PairAccount>().is_err());
+        assert!("btc/ksm=oracle-1".parse::<PairAccount>().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    cross_rate::{CrossRates, Prices},
+    error::Error,
+};
+use runtime::{FixedPointNumber, FixedU128};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// Source data of one submission round, persisted as a line of JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceRecord {
+    /// Unix timestamp of the round.
+    pub timestamp: i64,
+    pub prices: Prices,
+    /// Inner value of the exchange rate read from the chain after the submission of this
+    /// round, `None` if the submission failed.
+    pub on_chain: Option<u128>,
+}
+
+/// Append-only file of the source data of past submission rounds.
+pub struct PriceHistory {
+    path: PathBuf,
+}
+
+impl PriceHistory {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn append(&self, record: &PriceRecord) -> Result<(), Error> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Vec<PriceRecord>, Error> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Outcome of replaying one recorded round.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestResult {
+    pub timestamp: i64,
+    /// Exchange rate the current configuration would have submitted, `None` if it
+    /// would not have submitted a rate.
+    pub simulated: Option<FixedU128>,
+    pub on_chain: Option<FixedU128>,
+}
+
+impl BacktestResult {
+    /// Relative deviation of the simulated from the on-chain exchange rate.
+    pub fn deviation(&self) -> Option<f64> {
+        match (self.simulated, self.on_chain) {
+            (Some(simulated), Some(on_chain)) if on_chain.into_inner() > 0 => {
+                let simulated = simulated.into_inner() as f64;
+                let on_chain = on_chain.into_inner() as f64;
+                Some((simulated - on_chain).abs() / on_chain)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Replays the recorded source data through the aggregation, so that changes to its
+/// configuration can be evaluated before deploying them.
+pub fn backtest(
+    records: &[PriceRecord],
+    cross_rates: &CrossRates,
+    conversion_factor: FixedU128,
+) -> Vec<BacktestResult> {
+    records
+        .iter()
+        .map(|record| BacktestResult {
+            timestamp: record.timestamp,
+            simulated: crate::exchange_rate_from_prices(cross_rates, &record.prices, conversion_factor).ok(),
+            on_chain: record.on_chain.map(FixedU128::from_inner),
+        })
+        .collect()
+}
+
+/// Print the result of every round followed by a summary.
+pub fn report(results: &[BacktestResult]) {
+    let format_rate = |rate: Option<FixedU128>| rate.map_or_else(|| "-".to_string(), |rate| rate.to_string());
+    let deviations: Vec<f64> = results.iter().filter_map(BacktestResult::deviation).collect();
+    for result in results {
+        println!(
+            "{}\tsimulated={}\ton_chain={}\tdeviation={}",
+            result.timestamp,
+            format_rate(result.simulated),
+            format_rate(result.on_chain),
+            result
+                .deviation()
+                .map_or_else(|| "-".to_string(), |deviation| format!("{:.4}%", deviation * 100.0))
+        );
+    }
+    let max_deviation = deviations.iter().cloned().fold(0.0, f64::max);
+    let mean_deviation = if deviations.is_empty() {
+        0.0
+    } else {
+        deviations.iter().sum::<f64>() / deviations.len() as f64
+    };
+    println!(
+        "rounds={} skipped={} compared={} max_deviation={:.4}% mean_deviation={:.4}%",
+        results.len(),
+        results.iter().filter(|result| result.simulated.is_none()).count(),
+        deviations.len(),
+        max_deviation * 100.0,
+        mean_deviation * 100.0
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: i64, usd_prices: (f64, f64), on_chain: Option<u128>) -> PriceRecord {
+        let mut prices = Prices::new();
+        prices
+            .entry("bitcoin".to_string())
+            .or_default()
+            .insert("usd".to_string(), usd_prices.0);
+        prices
+            .entry("polkadot".to_string())
+            .or_default()
+            .insert("usd".to_string(), usd_prices.1);
+        PriceRecord {
+            timestamp,
+            prices,
+            on_chain,
+        }
+    }
+
+    #[test]
+    fn test_backtest() {
+        let cross_rates = CrossRates {
+            bridges: vec!["usd".to_string()],
+            quote_uncertainty: 0.005,
+            max_uncertainty: 0.01,
+        };
+        let on_chain = FixedU128::from_integer(2000).into_inner();
+        let records = vec![
+            record(1, (50000.0, 25.0), Some(on_chain)),
+            record(2, (50000.0, 20.0), Some(on_chain)),
+            record(3, (50000.0, 0.0), None),
+        ];
+        let results = backtest(&records, &cross_rates, FixedU128::one());
+
+        assert_eq!(results[0].simulated, Some(FixedU128::from_integer(2000)));
+        assert_eq!(results[0].deviation(), Some(0.0));
+        assert_eq!(results[1].deviation(), Some(0.25));
+        assert_eq!(results[2].simulated, None);
+        assert_eq!(results[2].deviation(), None);
+    }
+
+    #[test]
+    fn test_price_record_roundtrip() {
+        let record = record(1, (50000.0, 25.0), Some(1));
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(serde_json::from_str::<PriceRecord>(&line).unwrap(), record);
+    }
+}
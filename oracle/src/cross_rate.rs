@@ -0,0 +1,136 @@
+use crate::error::Error;
+use runtime::{FixedPointNumber, FixedU128};
+use std::collections::HashMap;
+
+/// Prices by coin id and currency, as returned by the CoinGecko `simple/price` endpoint.
+pub type Prices = HashMap<String, HashMap<String, f64>>;
+
+/// A price together with its relative uncertainty, e.g. 0.01 for ±1%.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub price: f64,
+    pub uncertainty: f64,
+}
+
+impl Quote {
+    fn new(price: f64, uncertainty: f64) -> Option<Self> {
+        // a missing or zero price means the source is unhealthy
+        if price.is_finite() && price > 0.0 {
+            Some(Self { price, uncertainty })
+        } else {
+            None
+        }
+    }
+
+    /// Divide two quotes in the same (bridging) currency, e.g. BTC/USD ÷ DOT/USD = BTC/DOT.
+    /// Relative uncertainties add up to first order.
+    fn cross(self, other: Quote) -> Self {
+        Self {
+            price: self.price / other.price,
+            uncertainty: self.uncertainty + other.uncertainty,
+        }
+    }
+
+    pub fn to_fixed(&self) -> Result<FixedU128, Error> {
+        let inner = self.price * FixedU128::accuracy() as f64;
+        if inner.is_finite() && inner < u128::MAX as f64 {
+            Ok(FixedU128::from_inner(inner as u128))
+        } else {
+            Err(Error::InvalidExchangeRate)
+        }
+    }
+}
+
+/// Computes the price of a coin in another currency, falling back to cross rates over
+/// bridging currencies when the direct pair is unavailable.
+pub struct CrossRates {
+    /// Currencies to bridge through, in order of preference.
+    pub bridges: Vec<String>,
+    /// Relative uncertainty assumed for each quote of the source.
+    pub quote_uncertainty: f64,
+    /// Cross rates with a higher combined uncertainty are rejected.
+    pub max_uncertainty: f64,
+}
+
+impl CrossRates {
+    fn quote(&self, prices: &Prices, coin: &str, currency: &str) -> Option<Quote> {
+        let price = *prices.get(coin)?.get(currency)?;
+        Quote::new(price, self.quote_uncertainty)
+    }
+
+    /// Price of `coin` in `currency`, where `currency_coin` is the coin id of `currency`.
+    pub fn price(&self, prices: &Prices, coin: &str, currency_coin: &str, currency: &str) -> Result<Quote, Error> {
+        if let Some(quote) = self.quote(prices, coin, currency) {
+            return Ok(quote);
+        }
+        for bridge in &self.bridges {
+            let quote = match (
+                self.quote(prices, coin, bridge),
+                self.quote(prices, currency_coin, bridge),
+            ) {
+                (Some(coin_quote), Some(currency_quote)) => coin_quote.cross(currency_quote),
+                _ => continue,
+            };
+            if quote.uncertainty > self.max_uncertainty {
+                log::warn!(
+                    "Cross rate {}/{} via {} exceeds the maximum uncertainty ({} > {})",
+                    coin,
+                    currency,
+                    bridge,
+                    quote.uncertainty,
+                    self.max_uncertainty
+                );
+                continue;
+            }
+            log::info!("Using cross rate {}/{} via {}", coin, currency, bridge);
+            return Ok(quote);
+        }
+        Err(Error::InvalidExchangeRate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(entries: &[(&str, &str, f64)]) -> Prices {
+        let mut prices = Prices::new();
+        for (coin, currency, price) in entries {
+            prices
+                .entry(coin.to_string())
+                .or_default()
+                .insert(currency.to_string(), *price);
+        }
+        prices
+    }
+
+    fn cross_rates(max_uncertainty: f64) -> CrossRates {
+        CrossRates {
+            bridges: vec!["eur".to_string(), "usd".to_string()],
+            quote_uncertainty: 0.005,
+            max_uncertainty,
+        }
+    }
+
+    #[test]
+    fn test_direct_price() {
+        let prices = prices(&[("bitcoin", "dot", 2308.0), ("bitcoin", "usd", 50000.0)]);
+        let quote = cross_rates(0.01).price(&prices, "bitcoin", "polkadot", "dot").unwrap();
+        assert_eq!(quote, Quote::new(2308.0, 0.005).unwrap());
+    }
+
+    #[test]
+    fn test_cross_rate_fallback() {
+        let prices = prices(&[
+            ("bitcoin", "dot", 0.0),
+            ("bitcoin", "usd", 50000.0),
+            ("polkadot", "usd", 25.0),
+        ]);
+        let quote = cross_rates(0.01).price(&prices, "bitcoin", "polkadot", "dot").unwrap();
+        assert_eq!(quote.price, 2000.0);
+        assert_eq!(quote.uncertainty, 0.01);
+        assert_eq!(quote.to_fixed().unwrap(), FixedU128::from_integer(2000));
+
+        assert!(cross_rates(0.009).price(&prices, "bitcoin", "polkadot", "dot").is_err());
+    }
+}
@@ -1,16 +1,39 @@
 use reqwest::Error as ReqwestError;
 use runtime::{substrate_subxt::Error as SubxtError, Error as RuntimeError};
+use service::Error as ServiceError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Invalid exchange rate")]
     InvalidExchangeRate,
+    #[error("Invalid maintenance window, expected HH:MM-HH:MM")]
+    InvalidMaintenanceWindow,
+    #[error("Invalid lease duration")]
+    InvalidLeaseDuration,
+    #[error("Invalid pair account, expected PAIR=KEYNAME with pair one of {0}")]
+    InvalidPairAccount(String),
+    #[error("Multiple accounts configured for pair {0}")]
+    DuplicatePairAccount(String),
+    #[error("Unknown pair {0}, expected one of {1}")]
+    UnknownPair(String, String),
+    #[error("Value deviates by {deviation} from the {reference} value, use --force to submit anyway")]
+    ManualRateDeviates { reference: &'static str, deviation: f64 },
+    #[error("Invalid source weight, expected SOURCE=WEIGHT with WEIGHT a number or `volume`")]
+    InvalidSourceWeight,
+    #[error("Submission aborted")]
+    SubmissionAborted,
 
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("SerdeJsonError: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
     #[error("ReqwestError: {0}")]
     ReqwestError(#[from] ReqwestError),
     #[error("RuntimeError: {0}")]
     RuntimeError(#[from] RuntimeError),
     #[error("SubxtError: {0}")]
     SubxtError(#[from] SubxtError),
+    #[error("ServiceError: {0}")]
+    ServiceError(#[from] ServiceError),
 }
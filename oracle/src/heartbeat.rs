@@ -0,0 +1,38 @@
+use crate::error::Error;
+use log::{debug, warn};
+use reqwest::Client;
+use std::time::Duration;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Dead-man switch (e.g. a healthchecks.io check) that is pinged after every submission
+/// round, so that an external service alerts if the oracle silently stops.
+pub struct Heartbeat {
+    client: Client,
+    success_url: Option<String>,
+    failure_url: Option<String>,
+}
+
+impl Heartbeat {
+    pub fn new(success_url: Option<String>, failure_url: Option<String>) -> Result<Self, Error> {
+        Ok(Self {
+            client: Client::builder().timeout(PING_TIMEOUT).build()?,
+            success_url,
+            failure_url,
+        })
+    }
+
+    /// Ping the success endpoint, or post the error to the failure endpoint. Failing to
+    /// reach the endpoint only logs a warning, the missing ping triggers the alert.
+    pub async fn report(&self, result: &Result<(), Error>) {
+        let request = match (result, &self.success_url, &self.failure_url) {
+            (Ok(()), Some(url), _) => self.client.get(url),
+            (Err(err), _, Some(url)) => self.client.post(url).body(err.to_string()),
+            _ => return,
+        };
+        match request.send().await.and_then(|response| response.error_for_status()) {
+            Ok(_) => debug!("Pinged heartbeat endpoint"),
+            Err(err) => warn!("Failed to ping heartbeat endpoint: {}", err),
+        }
+    }
+}
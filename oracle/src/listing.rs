@@ -0,0 +1,70 @@
+use crate::error::Error;
+use log::{info, warn};
+use runtime::CurrencyId;
+
+/// Tracks whether the currency pair submitted by the oracle is delisted on chain, i.e. its
+/// key was removed from the oracle keys, e.g. by a runtime upgrade. Submissions are skipped
+/// with a warning while the pair is delisted instead of failing the round, and resume once
+/// the pair is listed again.
+#[derive(Debug)]
+pub struct Listing {
+    pair: &'static str,
+    currency_id: CurrencyId,
+    delisted: bool,
+}
+
+impl Listing {
+    /// Track the pair, e.g. `btc/dot`, whose exchange rate is quoted in the second currency.
+    pub fn new(pair: &'static str) -> Result<Self, Error> {
+        let quote = pair.rsplit('/').next().unwrap_or_default();
+        Ok(Self {
+            pair,
+            currency_id: quote.parse()?,
+            delisted: false,
+        })
+    }
+
+    pub fn pair(&self) -> &'static str {
+        self.pair
+    }
+
+    /// Currency of the oracle key of the pair.
+    pub fn currency_id(&self) -> CurrencyId {
+        self.currency_id
+    }
+
+    /// Record whether the pair is listed, logging when this changes. Returns `listed`.
+    pub fn update(&mut self, listed: bool) -> bool {
+        if listed && self.delisted {
+            info!("{} is listed on chain again, resuming submissions", self.pair);
+        } else if !listed && !self.delisted {
+            warn!("{} is no longer listed on chain, pausing submissions", self.pair);
+        }
+        self.delisted = !listed;
+        listed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::BTC_DOT;
+
+    #[test]
+    fn test_listing_transitions() {
+        let mut listing = Listing::new(BTC_DOT).unwrap();
+        assert_eq!(listing.currency_id(), CurrencyId::DOT);
+        assert!(listing.update(true));
+        assert!(!listing.delisted);
+
+        assert!(!listing.update(false));
+        assert!(listing.delisted);
+        // still delisted in the next round
+        assert!(!listing.update(false));
+        assert!(listing.delisted);
+
+        // re-added
+        assert!(listing.update(true));
+        assert!(!listing.delisted);
+    }
+}
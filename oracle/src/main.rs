@@ -1,14 +1,31 @@
+mod accounts;
+mod backtest;
+mod cross_rate;
 mod error;
+mod heartbeat;
+mod listing;
+mod maintenance;
+mod manual;
+mod sources;
 
+use accounts::{Accounts, PairAccount, BTC_DOT};
+use backtest::{PriceHistory, PriceRecord};
 use clap::Clap;
+use cross_rate::{CrossRates, Prices, Quote};
 use error::Error;
 use git_version::git_version;
-use log::{error, info};
+use heartbeat::Heartbeat;
+use listing::Listing;
+use log::{error, info, warn};
+use maintenance::{default_instance_name, Lease, MaintenanceWindow};
+use manual::SubmitOpts;
 use runtime::{
-    substrate_subxt::PairSigner, ExchangeRateOraclePallet, FixedPointNumber, FixedPointTraits::CheckedMul, FixedU128,
-    InterBtcParachain, InterBtcRuntime,
+    ExchangeRateOraclePallet, FixedPointNumber, FixedPointTraits::CheckedMul, FixedU128, InterBtcParachain,
+    NetworkProfile, DEFAULT_PARACHAIN_URL,
 };
-use std::{collections::HashMap, time::Duration};
+use service::{route_through_proxy, Proxy};
+use sources::{Aggregation, SourceQuote, SourceWeight, COINGECKO, COINGECKO_API_URL};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 use tokio::time::delay_for;
 
 const VERSION: &str = git_version!(args = ["--tags"]);
@@ -18,35 +35,93 @@ const ABOUT: &str = env!("CARGO_PKG_DESCRIPTION");
 
 const ERR_RETRY_WAIT: Duration = Duration::from_secs(10);
 
-async fn get_exchange_rate_from_coingecko() -> Result<u128, Error> {
+/// How often to check whether a maintenance window has ended, or whether a
+/// standby instance can take over the lease.
+const STANDBY_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+async fn get_prices_from_coingecko(api_url: &str, cross_rates: &CrossRates) -> Result<Prices, Error> {
     // https://www.coingecko.com/api/documentations/v3
-    let resp = reqwest::get("https://api.coingecko.com/api/v3/simple/price?ids=bitcoin&vs_currencies=dot")
-        .await?
-        .json::<HashMap<String, HashMap<String, u128>>>()
-        .await?;
-
-    Ok(*resp
-        .get("bitcoin")
-        .ok_or(Error::InvalidExchangeRate)?
-        .get("dot")
-        .ok_or(Error::InvalidExchangeRate)?)
+    let mut vs_currencies = vec!["dot".to_string()];
+    vs_currencies.extend(cross_rates.bridges.iter().cloned());
+    let url = format!(
+        "{}/simple/price?ids=bitcoin,polkadot&vs_currencies={}",
+        api_url,
+        vs_currencies.join(",")
+    );
+    Ok(reqwest::get(&url).await?.json::<Prices>().await?)
+}
+
+/// Exchange rate to submit for the given source prices, in Planck per Satoshi.
+pub(crate) fn exchange_rate_from_prices(
+    cross_rates: &CrossRates,
+    prices: &Prices,
+    conversion_factor: FixedU128,
+) -> Result<FixedU128, Error> {
+    // exchange_rate given in BTC/DOT so there is no need to adjust
+    cross_rates
+        .price(prices, "bitcoin", "polkadot", "dot")?
+        .to_fixed()?
+        .checked_mul(&conversion_factor)
+        .ok_or(Error::InvalidExchangeRate)
+}
+
+/// Exchange rate to submit for the weighted median of the CoinGecko price and the prices
+/// of the exchange sources, in Planck per Satoshi. Exchanges that fail to report are left out.
+async fn exchange_rate_from_sources(
+    opts: &Opts,
+    api_url: &str,
+    cross_rates: &CrossRates,
+    prices: &Prices,
+    conversion_factor: FixedU128,
+) -> Result<FixedU128, Error> {
+    let coingecko = cross_rates.price(prices, "bitcoin", "polkadot", "dot")?;
+    let mut quotes = vec![SourceQuote {
+        source: COINGECKO.to_string(),
+        price: coingecko.price,
+        uncertainty: coingecko.uncertainty,
+        volume: None,
+    }];
+    // the exchange prices are converted to BTC by CoinGecko as well
+    quotes.extend(sources::get_exchange_quotes(api_url, &opts.exchange_source, cross_rates.quote_uncertainty).await?);
+    let aggregation = Aggregation {
+        weights: opts.source_weight.clone(),
+    };
+    let quote = aggregation.aggregate(&quotes)?;
+    info!("Aggregated price: {} (±{})", quote.price, quote.uncertainty);
+    quote
+        .to_fixed()?
+        .checked_mul(&conversion_factor)
+        .ok_or(Error::InvalidExchangeRate)
 }
 
 #[derive(Clap)]
 #[clap(name = NAME, version = VERSION, author = AUTHORS, about = ABOUT)]
 struct Opts {
-    /// Parachain URL, can be over WebSockets or HTTP.
-    #[clap(long, default_value = "ws://127.0.0.1:9944")]
-    btc_parachain_url: String,
+    /// Network profile (interlay, kintsugi, testnet or local) to take the default parachain
+    /// url and collateral decimals from. The oracle refuses to start against any other
+    /// parachain than that of the profile.
+    #[clap(long)]
+    network: Option<NetworkProfile>,
+
+    /// Parachain URL, can be over WebSockets or HTTP. Defaults to the url of the network
+    /// profile or ws://127.0.0.1:9944.
+    #[clap(long)]
+    btc_parachain_url: Option<String>,
+
+    /// SOCKS5 proxy (e.g. Tor) for all outbound connections, i.e. to the parachain, CoinGecko
+    /// and the heartbeat endpoints, of the form socks5://[user:password@]host:port.
+    #[clap(long)]
+    proxy: Option<Proxy>,
 
     /// Exchange rate from the collateral currency to
     /// the wrapped currency - i.e. 1 BTC = 2308 DOT.
     #[clap(long, default_value = "2308")]
     exchange_rate: u128,
 
-    /// Number of decimals for the collateral currency.
-    #[clap(long, default_value = "10")]
-    collateral_decimals: u32,
+    /// Number of decimals for the collateral currency, defaults to that of the network
+    /// profile or 10.
+    #[clap(long)]
+    collateral_decimals: Option<u32>,
 
     /// Number of decimals for the wrapped currency.
     #[clap(long, default_value = "8")]
@@ -60,13 +135,208 @@ struct Opts {
     #[clap(flatten)]
     account_info: runtime::cli::ProviderUserOpts,
 
+    /// Account from the keyfile used to submit the feed of a currency pair instead of
+    /// the default account, e.g. btc/dot=oracle-btc-dot. Can be specified multiple times.
+    #[clap(long, requires = "keyfile")]
+    pair_account: Vec<PairAccount>,
+
     /// Fetch the exchange rate from CoinGecko.
     #[clap(long, conflicts_with("exchange-rate"))]
     coingecko: bool,
 
+    /// CoinGecko id of an exchange whose BTC/DOT price is aggregated with the CoinGecko
+    /// price, e.g. kraken. Can be specified multiple times.
+    #[clap(long, requires = "coingecko")]
+    exchange_source: Vec<String>,
+
+    /// Weight of a price source in the aggregation, e.g. kraken=volume or coingecko=2.
+    /// Sources have weight 1 unless given otherwise. The sources weighted by `volume`
+    /// together weigh as much as the same number of sources of weight 1, split by their
+    /// trading volume of the last 24 hours. Can be specified multiple times.
+    #[clap(long, requires = "exchange-source")]
+    source_weight: Vec<SourceWeight>,
+
     /// Timeout in milliseconds to wait for connection to btc-parachain.
     #[clap(long, default_value = "60000")]
     connection_timeout_ms: u64,
+
+    /// Daily maintenance window in UTC, e.g. 22:00-23:30. A final exchange rate is
+    /// submitted when the window starts, after which the oracle pauses until it ends.
+    /// Can be specified multiple times.
+    #[clap(long)]
+    maintenance_window: Vec<MaintenanceWindow>,
+
+    /// Lease file shared with standby instances, only the instance holding the
+    /// lease submits exchange rates. The lease is released during maintenance.
+    /// Each epoch of the lease is kept in a file `<path>.<epoch>` next to it.
+    #[clap(long)]
+    lease_file: Option<PathBuf>,
+
+    /// Name identifying this instance in the lease file, defaults to the hostname and process id.
+    #[clap(long)]
+    instance_name: Option<String>,
+
+    /// Currencies to compute a cross rate through when CoinGecko has no direct
+    /// BTC/DOT price, in order of preference. Can be specified multiple times.
+    #[clap(long, default_value = "usd")]
+    bridge_currency: Vec<String>,
+
+    /// Relative uncertainty assumed for each price fetched from CoinGecko.
+    #[clap(long, default_value = "0.005")]
+    quote_uncertainty: f64,
+
+    /// Maximum combined relative uncertainty of a cross rate, e.g. 0.01 for ±1%.
+    #[clap(long, default_value = "0.01")]
+    max_cross_rate_uncertainty: f64,
+
+    /// Dead-man switch URL (e.g. of a healthchecks.io check) to ping after every
+    /// successful submission round.
+    #[clap(long)]
+    heartbeat_url: Option<String>,
+
+    /// URL to which the error is posted after every failed submission round,
+    /// e.g. the `/fail` endpoint of a healthchecks.io check.
+    #[clap(long)]
+    heartbeat_failure_url: Option<String>,
+
+    /// File to which the CoinGecko prices and the resulting on-chain exchange rate of
+    /// every submission round are appended, for use with the `backtest` subcommand.
+    #[clap(long)]
+    price_history: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    subcmd: Option<SubCommand>,
+}
+
+#[derive(Clap)]
+enum SubCommand {
+    /// Replay the recorded price history with the current cross rate settings and compare
+    /// the exchange rates that would have been submitted to those set on chain.
+    Backtest(BacktestOpts),
+    /// Submit an exchange rate once, e.g. to intervene manually in an emergency. The value
+    /// is compared against the CoinGecko price and the exchange rate set on chain first.
+    Submit(SubmitOpts),
+}
+
+#[derive(Clap)]
+struct BacktestOpts {
+    /// File written with `--price-history`.
+    #[clap(long)]
+    history: PathBuf,
+}
+
+impl Opts {
+    fn parachain_url(&self) -> String {
+        match (&self.btc_parachain_url, self.network) {
+            (Some(url), _) => url.clone(),
+            (None, Some(profile)) => profile.parachain_url.to_string(),
+            (None, None) => DEFAULT_PARACHAIN_URL.to_string(),
+        }
+    }
+
+    fn collateral_decimals(&self) -> u32 {
+        self.collateral_decimals
+            .or_else(|| self.network.map(|profile| profile.collateral_decimals))
+            .unwrap_or(10)
+    }
+
+    fn in_maintenance_window(&self) -> bool {
+        let now = chrono::Utc::now().time();
+        self.maintenance_window.iter().any(|window| window.contains(now))
+    }
+}
+
+async fn connect(opts: &Opts, accounts: &Accounts, pair: &str) -> Result<InterBtcParachain, Error> {
+    let parachain = InterBtcParachain::from_url_with_retry(
+        &opts.parachain_url(),
+        accounts.signer(pair)?,
+        Duration::from_millis(opts.connection_timeout_ms),
+    )
+    .await?;
+    Ok(match &opts.network {
+        Some(profile) => parachain.expect_profile(profile)?,
+        None => parachain,
+    })
+}
+
+/// Submit the exchange rate, unless the pair is no longer listed on chain. Returns the
+/// exchange rate stored on chain after the submission, `None` if nothing was submitted.
+async fn submit_exchange_rate(
+    opts: &Opts,
+    accounts: &Accounts,
+    listing: &mut Listing,
+    exchange_rate: FixedU128,
+) -> Result<Option<FixedU128>, Error> {
+    let parachain = connect(opts, accounts, listing.pair()).await?;
+    if !listing.update(parachain.is_exchange_rate_listed(listing.currency_id()).await?) {
+        return Ok(None);
+    }
+
+    info!(
+        "Setting exchange rate: {} ({})",
+        exchange_rate,
+        chrono::offset::Local::now()
+    );
+    parachain.set_exchange_rate_info(exchange_rate).await?;
+
+    // read the rate back, since the runtime may store another value than the one submitted
+    let (on_chain, _, _) = parachain.get_exchange_rate_info().await?;
+    if on_chain != exchange_rate {
+        warn!(
+            "Submitted exchange rate {}, but {} is stored on chain",
+            exchange_rate, on_chain
+        );
+    }
+    Ok(Some(on_chain))
+}
+
+/// Check the manually entered exchange rate against the sources and the chain, ask for
+/// confirmation and submit it once.
+async fn submit_manually(
+    opts: &Opts,
+    submit_opts: &SubmitOpts,
+    coingecko_url: &str,
+    cross_rates: &CrossRates,
+    conversion_factor: FixedU128,
+) -> Result<(), Error> {
+    let pair = manual::parse_pair(&submit_opts.pair)?;
+    let accounts = Accounts::new(opts.account_info.clone(), opts.pair_account.clone())?;
+    let parachain = connect(opts, &accounts, pair).await?;
+
+    // the sources may be the reason for the intervention, so they are optional
+    let source_value = match get_prices_from_coingecko(coingecko_url, cross_rates).await {
+        Ok(prices) => cross_rates
+            .price(&prices, "bitcoin", "polkadot", "dot")
+            .map(|quote| quote.price)
+            .ok(),
+        Err(err) => {
+            error!("Could not get prices from CoinGecko: {}", err);
+            None
+        }
+    };
+    let (on_chain, _, _) = parachain.get_exchange_rate_info().await?;
+    let on_chain_value = on_chain.into_inner() as f64 / conversion_factor.into_inner() as f64;
+
+    let deviations = manual::check_value(
+        submit_opts.value,
+        &[("CoinGecko", source_value), ("on-chain", Some(on_chain_value))],
+        submit_opts.max_deviation,
+        submit_opts.force,
+    )?;
+    if !submit_opts.yes && !manual::confirm(pair, submit_opts.value, &deviations)? {
+        return Err(Error::SubmissionAborted);
+    }
+
+    let exchange_rate = Quote {
+        price: submit_opts.value,
+        uncertainty: 0.0,
+    }
+    .to_fixed()?
+    .checked_mul(&conversion_factor)
+    .ok_or(Error::InvalidExchangeRate)?;
+    info!("Manually setting exchange rate of {}: {}", pair, exchange_rate);
+    parachain.set_exchange_rate_info(exchange_rate).await?;
+    Ok(())
 }
 
 #[tokio::main]
@@ -74,59 +344,182 @@ async fn main() -> Result<(), Error> {
     env_logger::init_from_env(
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, log::LevelFilter::Info.as_str()),
     );
-    let opts: Opts = Opts::parse();
-
-    let (key_pair, _) = opts.account_info.get_key_pair()?;
-    let signer = PairSigner::<InterBtcRuntime, _>::new(key_pair);
-
-    let interval = Duration::from_millis(opts.interval_ms);
-    let exchange_rate = FixedU128::checked_from_integer(opts.exchange_rate).ok_or(Error::InvalidExchangeRate)?;
+    let mut opts: Opts = Opts::parse();
 
     let conversion_factor = FixedU128::checked_from_rational(
-        10_u128.pow(opts.collateral_decimals),
+        10_u128.pow(opts.collateral_decimals()),
         10_u128.pow(opts.wrapped_decimals),
     )
     .unwrap();
 
+    let cross_rates = CrossRates {
+        bridges: opts.bridge_currency.clone(),
+        quote_uncertainty: opts.quote_uncertainty,
+        max_uncertainty: opts.max_cross_rate_uncertainty,
+    };
+
+    if let Some(SubCommand::Backtest(backtest_opts)) = &opts.subcmd {
+        let records = PriceHistory::read(&backtest_opts.history)?;
+        backtest::report(&backtest::backtest(&records, &cross_rates, conversion_factor));
+        return Ok(());
+    }
+
+    // connect through the local end of the proxy tunnels, if any
+    let proxy = opts.proxy.clone();
+    opts.btc_parachain_url = Some(route_through_proxy(proxy.as_ref(), &opts.parachain_url()).await?);
+    for url in opts
+        .heartbeat_url
+        .iter_mut()
+        .chain(opts.heartbeat_failure_url.iter_mut())
+    {
+        *url = route_through_proxy(proxy.as_ref(), url).await?;
+    }
+    let coingecko_url = route_through_proxy(proxy.as_ref(), COINGECKO_API_URL).await?;
+
+    if let Some(SubCommand::Submit(submit_opts)) = &opts.subcmd {
+        return submit_manually(&opts, submit_opts, &coingecko_url, &cross_rates, conversion_factor).await;
+    }
+
+    let accounts = Accounts::new(opts.account_info.clone(), opts.pair_account.clone())?;
+
+    let interval = Duration::from_millis(opts.interval_ms);
+    let exchange_rate = FixedU128::checked_from_integer(opts.exchange_rate).ok_or(Error::InvalidExchangeRate)?;
+    let price_history = opts.price_history.clone().map(PriceHistory::new);
+
+    let heartbeat = Heartbeat::new(opts.heartbeat_url.clone(), opts.heartbeat_failure_url.clone())?;
+
+    let lease = opts.lease_file.clone().map(|path| {
+        Lease::new(
+            path,
+            opts.instance_name.clone().unwrap_or_else(default_instance_name),
+            // give the holder time to renew before a standby takes over
+            interval * 2,
+        )
+    });
+
+    let mut last_exchange_rate = None;
+    let mut paused = false;
+    let mut listing = Listing::new(BTC_DOT)?;
+    // epoch of the lease while this instance holds it
+    let mut held = None;
+
     loop {
-        let exchange_rate = if opts.coingecko {
-            match get_exchange_rate_from_coingecko().await {
-                Ok(exchange_rate) => {
-                    // exchange_rate given in BTC/DOT so there is no need to adjust
-                    FixedU128::checked_from_integer(exchange_rate).unwrap()
+        if opts.in_maintenance_window() {
+            if !paused {
+                // a standby has no submission to finish, nor a lease to release
+                let is_leader = match (&lease, held) {
+                    (None, _) => true,
+                    (Some(lease), Some(epoch)) => {
+                        matches!(lease.try_acquire(chrono::Utc::now(), Some(epoch)), Ok(Some(_)))
+                    }
+                    (Some(_), None) => false,
+                };
+                match last_exchange_rate {
+                    Some(exchange_rate) if is_leader => {
+                        info!("Entering maintenance window, submitting final heartbeat");
+                        let result = submit_exchange_rate(&opts, &accounts, &mut listing, exchange_rate)
+                            .await
+                            .map(|_| ());
+                        if let Err(e) = &result {
+                            error!("Error: {}", e.to_string());
+                        }
+                        heartbeat.report(&result).await;
+                    }
+                    _ => info!("Entering maintenance window"),
+                }
+                if let (Some(lease), Some(epoch)) = (&lease, held.take()) {
+                    if let Err(e) = lease.release(epoch) {
+                        error!("Failed to release lease: {}", e);
+                    }
+                }
+                paused = true;
+            }
+            delay_for(STANDBY_POLL_INTERVAL).await;
+            continue;
+        } else if paused {
+            info!("Maintenance window ended, resuming");
+            paused = false;
+        }
+
+        if let Some(lease) = &lease {
+            match lease.try_acquire(chrono::Utc::now(), held) {
+                Ok(Some(epoch)) => {
+                    if held != Some(epoch) {
+                        info!("Acquired lease (epoch {})", epoch);
+                    }
+                    held = Some(epoch);
                 }
+                Ok(None) => {
+                    if held.take().is_some() {
+                        warn!("Lease was taken over by another instance");
+                    }
+                    info!("Lease is held by another instance, standing by");
+                    delay_for(STANDBY_POLL_INTERVAL).await;
+                    continue;
+                }
+                Err(e) => {
+                    error!("Failed to acquire lease: {}", e);
+                    delay_for(ERR_RETRY_WAIT).await;
+                    continue;
+                }
+            }
+        }
+
+        let (exchange_rate, prices) = if opts.coingecko {
+            let result = async {
+                let prices = get_prices_from_coingecko(&coingecko_url, &cross_rates).await?;
+                let exchange_rate = if opts.exchange_source.is_empty() {
+                    exchange_rate_from_prices(&cross_rates, &prices, conversion_factor)?
+                } else {
+                    exchange_rate_from_sources(&opts, &coingecko_url, &cross_rates, &prices, conversion_factor).await?
+                };
+                Ok::<_, Error>((exchange_rate, Some(prices)))
+            }
+            .await;
+            match result {
+                Ok(result) => result,
                 Err(err) => {
                     error!("Could not get exchange rate from CoinGecko: {}", err);
+                    heartbeat.report(&Err(err)).await;
                     delay_for(ERR_RETRY_WAIT).await;
                     continue;
                 }
             }
         } else {
-            exchange_rate
+            let exchange_rate = exchange_rate
+                .checked_mul(&conversion_factor)
+                .ok_or(Error::InvalidExchangeRate)?;
+            (exchange_rate, None)
         };
+        last_exchange_rate = Some(exchange_rate);
 
-        let exchange_rate = exchange_rate
-            .checked_mul(&conversion_factor)
-            .ok_or(Error::InvalidExchangeRate)?;
-
-        info!(
-            "Setting exchange rate: {} ({})",
-            exchange_rate,
-            chrono::offset::Local::now()
-        );
-
-        let result = InterBtcParachain::from_url_with_retry(
-            &opts.btc_parachain_url.clone(),
-            signer.clone(),
-            Duration::from_millis(opts.connection_timeout_ms),
-        )
-        .await?
-        .set_exchange_rate_info(exchange_rate)
-        .await;
+        // fetching the prices takes time, make sure we still hold the lease before submitting
+        if let Some(lease) = &lease {
+            if !matches!(lease.try_acquire(chrono::Utc::now(), held), Ok(Some(_))) {
+                warn!("Lost the lease before submitting, standing by");
+                held = None;
+                continue;
+            }
+        }
 
-        if let Err(e) = result {
+        let result = submit_exchange_rate(&opts, &accounts, &mut listing, exchange_rate).await;
+        if let Err(e) = &result {
             error!("Error: {}", e.to_string());
         }
+        // a delisted pair is not a failure of the round
+        let on_chain = result.as_ref().ok().copied().flatten();
+        heartbeat.report(&result.map(|_| ())).await;
+
+        if let (Some(price_history), Some(prices)) = (&price_history, prices) {
+            let record = PriceRecord {
+                timestamp: chrono::Utc::now().timestamp(),
+                prices,
+                on_chain: on_chain.map(|on_chain| on_chain.into_inner()),
+            };
+            if let Err(e) = price_history.append(&record) {
+                error!("Failed to record price history: {}", e);
+            }
+        }
 
         delay_for(interval).await;
     }
@@ -0,0 +1,231 @@
+use crate::error::Error;
+use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+use std::{ffi::OsString, fs, path::PathBuf, str::FromStr, time::Duration};
+
+/// Daily time window (UTC) during which the oracle does not submit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaintenanceWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl MaintenanceWindow {
+    /// Returns true if the given time falls inside the window. Windows
+    /// whose end is before their start wrap around midnight.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = Error;
+
+    /// Parses a window of the form `HH:MM-HH:MM`, e.g. `22:00-01:30`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '-');
+        let mut parse_time = || {
+            parts
+                .next()
+                .and_then(|time| NaiveTime::parse_from_str(time.trim(), "%H:%M").ok())
+                .ok_or(Error::InvalidMaintenanceWindow)
+        };
+        Ok(MaintenanceWindow {
+            start: parse_time()?,
+            end: parse_time()?,
+        })
+    }
+}
+
+/// Name of this instance in the lease if none is configured: the hostname and the
+/// process id, since process ids alone collide across containers.
+pub fn default_instance_name() -> String {
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "oracle".to_string());
+    format!("{}-{}", hostname, std::process::id())
+}
+
+/// Lease shared between a primary and a standby oracle (e.g. on a network file
+/// system), so that at most one instance submits at a time.
+///
+/// Every epoch of the lease has its own file `<path>.<epoch>` containing the name
+/// of the holder and the unix timestamp at which it expires, the highest epoch is
+/// current. An instance takes over an expired lease by linking the file of the next
+/// epoch into place, which fails if another instance did so first. The epoch serves
+/// as fencing token: the holder only renews the lease of its own epoch, and stops
+/// submitting as soon as a higher epoch exists.
+pub struct Lease {
+    path: PathBuf,
+    holder: String,
+    duration: Duration,
+}
+
+impl Lease {
+    pub fn new(path: PathBuf, holder: String, duration: Duration) -> Self {
+        Self { path, holder, duration }
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.file_name().map(OsString::from).unwrap_or_default();
+        name.push(format!(".{}", suffix));
+        self.path.with_file_name(name)
+    }
+
+    fn epoch_path(&self, epoch: u64) -> PathBuf {
+        self.sibling(&epoch.to_string())
+    }
+
+    fn epochs(&self) -> Result<Vec<u64>, Error> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let prefix = format!("{}.", self.path.file_name().unwrap_or_default().to_string_lossy());
+        let mut epochs = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            if let Some(Ok(epoch)) = name.to_string_lossy().strip_prefix(&prefix).map(str::parse::<u64>) {
+                epochs.push(epoch);
+            }
+        }
+        Ok(epochs)
+    }
+
+    /// The current epoch with its holder and expiry. A corrupt lease is treated as expired.
+    fn read(&self) -> Result<Option<(u64, Option<(String, DateTime<Utc>)>)>, Error> {
+        let epoch = match self.epochs()?.into_iter().max() {
+            Some(epoch) => epoch,
+            None => return Ok(None),
+        };
+        let contents = match fs::read_to_string(self.epoch_path(epoch)) {
+            Ok(contents) => contents,
+            // removed by the holder of a higher epoch in the meantime
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return self.read(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut parts = contents.split_whitespace();
+        let state = match (parts.next(), parts.next().and_then(|expiry| expiry.parse::<i64>().ok())) {
+            (Some(holder), Some(expiry)) => Some((holder.to_string(), Utc.timestamp(expiry, 0))),
+            _ => None,
+        };
+        Ok(Some((epoch, state)))
+    }
+
+    /// Write the lease to a temporary file, so that lease files are only ever seen complete.
+    fn write_tmp(&self, expiry: DateTime<Utc>) -> Result<PathBuf, Error> {
+        let tmp_path = self.sibling(&format!("{}.tmp", self.holder));
+        fs::write(&tmp_path, format!("{} {}", self.holder, expiry.timestamp()))?;
+        Ok(tmp_path)
+    }
+
+    fn expiry(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>, Error> {
+        Ok(now + chrono::Duration::from_std(self.duration).map_err(|_| Error::InvalidLeaseDuration)?)
+    }
+
+    /// Renew the lease of the given epoch, or take over the lease if it is free or expired.
+    /// Returns the epoch we hold afterwards, or None if the lease is held by another instance.
+    pub fn try_acquire(&self, now: DateTime<Utc>, held: Option<u64>) -> Result<Option<u64>, Error> {
+        let current = self.read()?;
+        if let Some(epoch) = held {
+            return match current {
+                Some((current, Some((holder, _)))) if current == epoch && holder == self.holder => {
+                    fs::rename(self.write_tmp(self.expiry(now)?)?, self.epoch_path(epoch))?;
+                    // another instance may have taken over just before the renewal
+                    Ok(matches!(self.read()?, Some((current, _)) if current == epoch).then(|| epoch))
+                }
+                // fenced: the lease expired and was taken over since we last renewed it
+                _ => Ok(None),
+            };
+        }
+
+        let next_epoch = match current {
+            Some((_, Some((holder, expiry)))) if holder != self.holder && expiry > now => return Ok(None),
+            Some((epoch, _)) => epoch + 1,
+            None => 1,
+        };
+        let tmp_path = self.write_tmp(self.expiry(now)?)?;
+        // unlike a rename, linking fails if another instance created the epoch first
+        let linked = fs::hard_link(&tmp_path, self.epoch_path(next_epoch));
+        let _ = fs::remove_file(&tmp_path);
+        match linked {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        for epoch in self.epochs()?.into_iter().filter(|epoch| *epoch < next_epoch) {
+            let _ = fs::remove_file(self.epoch_path(epoch));
+        }
+        Ok(Some(next_epoch))
+    }
+
+    /// Release the lease of the given epoch if it is still current, allowing a standby
+    /// instance to take over immediately.
+    pub fn release(&self, epoch: u64) -> Result<(), Error> {
+        if let Some((current, Some((holder, _)))) = self.read()? {
+            if current == epoch && holder == self.holder {
+                fs::rename(self.write_tmp(Utc::now())?, self.epoch_path(epoch))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance_window_contains() {
+        let window = MaintenanceWindow::from_str("10:00-11:30").unwrap();
+        assert!(window.contains(NaiveTime::from_hms(10, 0, 0)));
+        assert!(window.contains(NaiveTime::from_hms(11, 29, 59)));
+        assert!(!window.contains(NaiveTime::from_hms(11, 30, 0)));
+        assert!(!window.contains(NaiveTime::from_hms(9, 59, 59)));
+    }
+
+    #[test]
+    fn test_maintenance_window_wraps_around_midnight() {
+        let window = MaintenanceWindow::from_str("23:00-01:00").unwrap();
+        assert!(window.contains(NaiveTime::from_hms(23, 30, 0)));
+        assert!(window.contains(NaiveTime::from_hms(0, 30, 0)));
+        assert!(!window.contains(NaiveTime::from_hms(12, 0, 0)));
+    }
+
+    #[test]
+    fn test_maintenance_window_invalid() {
+        assert!(MaintenanceWindow::from_str("23:00").is_err());
+        assert!(MaintenanceWindow::from_str("25:00-01:00").is_err());
+    }
+
+    #[test]
+    fn test_lease_fencing() {
+        let dir = std::env::temp_dir().join(format!("oracle-lease-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("oracle.lease");
+        let primary = Lease::new(path.clone(), "primary".to_string(), Duration::from_secs(30));
+        let standby = Lease::new(path, "standby".to_string(), Duration::from_secs(30));
+
+        assert_eq!(primary.try_acquire(Utc.timestamp(100, 0), None).unwrap(), Some(1));
+        assert_eq!(standby.try_acquire(Utc.timestamp(110, 0), None).unwrap(), None);
+        assert_eq!(primary.try_acquire(Utc.timestamp(120, 0), Some(1)).unwrap(), Some(1));
+
+        // the primary misses its renewals, the standby takes over after expiry
+        assert_eq!(standby.try_acquire(Utc.timestamp(151, 0), None).unwrap(), Some(2));
+        assert_eq!(primary.try_acquire(Utc.timestamp(152, 0), Some(1)).unwrap(), None);
+        assert_eq!(primary.try_acquire(Utc.timestamp(152, 0), None).unwrap(), None);
+
+        // releasing a lease that changed hands has no effect
+        primary.release(1).unwrap();
+        assert_eq!(primary.try_acquire(Utc.timestamp(153, 0), None).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
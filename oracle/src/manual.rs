@@ -0,0 +1,131 @@
+use crate::{accounts::KNOWN_PAIRS, error::Error};
+use clap::Clap;
+use std::io::{self, BufRead, Write};
+
+#[derive(Clap)]
+pub struct SubmitOpts {
+    /// Currency pair of the exchange rate, e.g. BTC/DOT.
+    #[clap(long, default_value = "btc/dot")]
+    pub pair: String,
+
+    /// Exchange rate in units of the collateral currency per unit of the wrapped
+    /// currency, e.g. 2308.5 for 1 BTC = 2308.5 DOT.
+    #[clap(long)]
+    pub value: f64,
+
+    /// Refuse to submit if the value deviates from the CoinGecko price or from the
+    /// exchange rate currently set on chain by more than this, e.g. 0.1 for 10%.
+    #[clap(long, default_value = "0.1")]
+    pub max_deviation: f64,
+
+    /// Submit even if the value deviates by more than `--max-deviation`.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Do not ask for confirmation before submitting.
+    #[clap(long)]
+    pub yes: bool,
+}
+
+/// Parse a currency pair case-insensitively, e.g. `BTC/DOT`.
+pub fn parse_pair(pair: &str) -> Result<&'static str, Error> {
+    let pair = pair.trim().to_lowercase();
+    KNOWN_PAIRS
+        .iter()
+        .find(|known| **known == pair)
+        .copied()
+        .ok_or_else(|| Error::UnknownPair(pair, KNOWN_PAIRS.join(", ")))
+}
+
+/// Deviation of the manual value from a reference value, `None` if the reference is unavailable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deviation {
+    pub reference: &'static str,
+    pub value: Option<f64>,
+    pub deviation: Option<f64>,
+}
+
+/// Compare the manual value against the references and fail if it deviates from any of
+/// them by more than `max_deviation`, unless `force` is set.
+pub fn check_value(
+    value: f64,
+    references: &[(&'static str, Option<f64>)],
+    max_deviation: f64,
+    force: bool,
+) -> Result<Vec<Deviation>, Error> {
+    if !value.is_finite() || value <= 0.0 {
+        return Err(Error::InvalidExchangeRate);
+    }
+    let deviations: Vec<_> = references
+        .iter()
+        .map(|(reference, reference_value)| Deviation {
+            reference,
+            value: *reference_value,
+            deviation: reference_value
+                .filter(|reference_value| *reference_value > 0.0)
+                .map(|reference_value| (value - reference_value).abs() / reference_value),
+        })
+        .collect();
+    if !force {
+        if let Some(exceeded) = deviations
+            .iter()
+            .find(|deviation| deviation.deviation.map_or(false, |deviation| deviation > max_deviation))
+        {
+            return Err(Error::ManualRateDeviates {
+                reference: exceeded.reference,
+                deviation: exceeded.deviation.unwrap_or_default(),
+            });
+        }
+    }
+    Ok(deviations)
+}
+
+/// Print the deviations and ask the operator to confirm the submission on stdin.
+pub fn confirm(pair: &str, value: f64, deviations: &[Deviation]) -> Result<bool, Error> {
+    println!("Submitting {} = {}", pair.to_uppercase(), value);
+    for deviation in deviations {
+        match (deviation.value, deviation.deviation) {
+            (Some(reference_value), Some(relative)) => println!(
+                "  {}: {} (deviation {:.2}%)",
+                deviation.reference,
+                reference_value,
+                relative * 100.0
+            ),
+            _ => println!("  {}: unavailable", deviation.reference),
+        }
+    }
+    print!("Submit? [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pair() {
+        assert_eq!(parse_pair("BTC/DOT").unwrap(), "btc/dot");
+        assert!(matches!(parse_pair("KSM/BTC"), Err(Error::UnknownPair(..))));
+    }
+
+    #[test]
+    fn test_check_value() {
+        let references = [("CoinGecko", Some(2000.0)), ("on chain", None)];
+        let deviations = check_value(2100.0, &references, 0.1, false).unwrap();
+        assert_eq!(deviations[0].deviation, Some(0.05));
+        assert_eq!(deviations[1].deviation, None);
+
+        assert!(matches!(
+            check_value(2300.0, &references, 0.1, false),
+            Err(Error::ManualRateDeviates {
+                reference: "CoinGecko",
+                ..
+            })
+        ));
+        assert!(check_value(2300.0, &references, 0.1, true).is_ok());
+        assert!(check_value(0.0, &references, 0.1, true).is_err());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{cross_rate::Quote, error::Error};
+use serde::Deserialize;
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+/// Name of the CoinGecko aggregate price among the sources.
+pub const COINGECKO: &str = "coingecko";
+
+/// Base url of the CoinGecko API.
+pub const COINGECKO_API_URL: &str = "https://api.coingecko.com/api/v3";
+
+/// Time to wait for the tickers of an exchange before leaving it out of the aggregation.
+const EXCHANGE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Weight of a price source in the aggregation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weight {
+    Static(f64),
+    /// The sources weighted by volume together weigh as much as the same number of
+    /// sources of weight 1, split in proportion to their reported trading volume.
+    Volume,
+}
+
+impl FromStr for Weight {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "volume" {
+            return Ok(Weight::Volume);
+        }
+        match s.parse::<f64>() {
+            Ok(weight) if weight.is_finite() && weight >= 0.0 => Ok(Weight::Static(weight)),
+            _ => Err(Error::InvalidSourceWeight),
+        }
+    }
+}
+
+/// Weight of a named source, given as `SOURCE=WEIGHT`, e.g. `kraken=volume` or `coingecko=2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceWeight {
+    pub source: String,
+    pub weight: Weight,
+}
+
+impl FromStr for SourceWeight {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.splitn(2, '=').collect::<Vec<_>>().as_slice() {
+            [source, weight] if !source.is_empty() => Ok(SourceWeight {
+                source: source.to_lowercase(),
+                weight: weight.parse()?,
+            }),
+            _ => Err(Error::InvalidSourceWeight),
+        }
+    }
+}
+
+/// BTC/DOT price reported by one source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceQuote {
+    pub source: String,
+    pub price: f64,
+    /// Relative uncertainty of the price, e.g. 0.01 for ±1%.
+    pub uncertainty: f64,
+    /// Trading volume of the last 24 hours in USD, if reported.
+    pub volume: Option<f64>,
+}
+
+/// Combines the prices of several sources into the weighted median, so that a source with
+/// a small weight can move the result only up to the price of the neighbouring source.
+pub struct Aggregation {
+    pub weights: Vec<SourceWeight>,
+}
+
+impl Aggregation {
+    fn weight_of(&self, source: &str) -> Weight {
+        self.weights
+            .iter()
+            .find(|weight| weight.source == source)
+            .map_or(Weight::Static(1.0), |weight| weight.weight)
+    }
+
+    /// The effective weight of each quote, in the order of the quotes.
+    pub fn weights(&self, quotes: &[SourceQuote]) -> Vec<f64> {
+        let volume_weighted: Vec<_> = quotes
+            .iter()
+            .filter(|quote| self.weight_of(&quote.source) == Weight::Volume)
+            .collect();
+        let total_volume: f64 = volume_weighted
+            .iter()
+            .filter_map(|quote| quote.volume)
+            .filter(|volume| volume.is_finite() && *volume > 0.0)
+            .sum();
+        quotes
+            .iter()
+            .map(|quote| match self.weight_of(&quote.source) {
+                Weight::Static(weight) => weight,
+                Weight::Volume => match quote.volume {
+                    Some(volume) if volume.is_finite() && volume > 0.0 && total_volume > 0.0 => {
+                        volume / total_volume * volume_weighted.len() as f64
+                    }
+                    _ => {
+                        log::warn!("No trading volume reported by {}, ignoring its price", quote.source);
+                        0.0
+                    }
+                },
+            })
+            .collect()
+    }
+
+    /// The weighted median of the quotes, with the uncertainty of the quote it is taken from.
+    pub fn aggregate(&self, quotes: &[SourceQuote]) -> Result<Quote, Error> {
+        let mut weighted: Vec<_> = quotes
+            .iter()
+            .zip(self.weights(quotes))
+            .inspect(|(quote, weight)| log::debug!("Price of {}: {} (weight {:.3})", quote.source, quote.price, weight))
+            .filter(|(quote, weight)| quote.price.is_finite() && quote.price > 0.0 && *weight > 0.0)
+            .map(|(quote, weight)| {
+                let quote = Quote {
+                    price: quote.price,
+                    uncertainty: quote.uncertainty,
+                };
+                (quote, weight)
+            })
+            .collect();
+        weighted.sort_by(|a, b| a.0.price.partial_cmp(&b.0.price).expect("prices are finite"));
+
+        let total: f64 = weighted.iter().map(|(_, weight)| weight).sum();
+        let mut cumulative = 0.0;
+        for (index, (quote, weight)) in weighted.iter().enumerate() {
+            cumulative += weight;
+            if cumulative > total / 2.0 {
+                return Ok(*quote);
+            }
+            // exactly half of the weight below, take the midpoint with the next price
+            if cumulative == total / 2.0 {
+                return Ok(weighted.get(index + 1).map_or(*quote, |(next, _)| Quote {
+                    price: (quote.price + next.price) / 2.0,
+                    uncertainty: quote.uncertainty.max(next.uncertainty),
+                }));
+            }
+        }
+        Err(Error::InvalidExchangeRate)
+    }
+}
+
+#[derive(Deserialize)]
+struct Market {
+    identifier: String,
+}
+
+#[derive(Deserialize)]
+struct Ticker {
+    market: Market,
+    converted_last: HashMap<String, f64>,
+    converted_volume: HashMap<String, f64>,
+    #[serde(default)]
+    is_stale: bool,
+    #[serde(default)]
+    is_anomaly: bool,
+}
+
+#[derive(Deserialize)]
+struct Tickers {
+    tickers: Vec<Ticker>,
+}
+
+/// BTC/DOT price of an exchange from its DOT tickers, averaged by the volume of each ticker.
+fn exchange_quote(exchange: &str, tickers: Tickers, uncertainty: f64) -> Option<SourceQuote> {
+    let (mut weighted_price, mut volume) = (0.0, 0.0);
+    for ticker in tickers
+        .tickers
+        .iter()
+        .filter(|ticker| ticker.market.identifier == exchange && !ticker.is_stale && !ticker.is_anomaly)
+    {
+        if let (Some(price), Some(ticker_volume)) =
+            (ticker.converted_last.get("btc"), ticker.converted_volume.get("usd"))
+        {
+            weighted_price += price * ticker_volume;
+            volume += ticker_volume;
+        }
+    }
+    if volume <= 0.0 || weighted_price <= 0.0 {
+        return None;
+    }
+    Some(SourceQuote {
+        source: exchange.to_lowercase(),
+        // the tickers give the price of DOT in BTC
+        price: volume / weighted_price,
+        uncertainty,
+        volume: Some(volume),
+    })
+}
+
+/// Fetch the BTC/DOT price and the trading volume of an exchange from CoinGecko, e.g. `kraken`.
+async fn get_exchange_quote(
+    client: &reqwest::Client,
+    api_url: &str,
+    exchange: &str,
+    uncertainty: f64,
+) -> Result<SourceQuote, Error> {
+    // https://www.coingecko.com/api/documentations/v3
+    let tickers = client
+        .get(&format!("{}/coins/polkadot/tickers", api_url))
+        .query(&[("exchange_ids", exchange)])
+        .send()
+        .await?
+        .json::<Tickers>()
+        .await?;
+    exchange_quote(exchange, tickers, uncertainty).ok_or(Error::InvalidExchangeRate)
+}
+
+/// Fetch the quotes of the exchanges concurrently, with the given uncertainty for each price.
+/// Exchanges that fail to report within `EXCHANGE_REQUEST_TIMEOUT` are left out.
+pub async fn get_exchange_quotes(
+    api_url: &str,
+    exchanges: &[String],
+    uncertainty: f64,
+) -> Result<Vec<SourceQuote>, Error> {
+    let client = reqwest::Client::builder().timeout(EXCHANGE_REQUEST_TIMEOUT).build()?;
+    let requests: Vec<_> = exchanges
+        .iter()
+        .map(|exchange| {
+            let (client, api_url, exchange) = (client.clone(), api_url.to_string(), exchange.clone());
+            tokio::spawn(async move {
+                let result = get_exchange_quote(&client, &api_url, &exchange, uncertainty).await;
+                (exchange, result)
+            })
+        })
+        .collect();
+
+    let mut quotes = Vec::new();
+    for request in requests {
+        match request.await {
+            Ok((_, Ok(quote))) => quotes.push(quote),
+            Ok((exchange, Err(err))) => log::error!("Could not get price of {} from CoinGecko: {}", exchange, err),
+            Err(err) => log::error!("Exchange price request failed: {}", err),
+        }
+    }
+    Ok(quotes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(source: &str, price: f64, volume: Option<f64>) -> SourceQuote {
+        SourceQuote {
+            source: source.to_string(),
+            price,
+            uncertainty: 0.005,
+            volume,
+        }
+    }
+
+    #[test]
+    fn test_parse_source_weight() {
+        assert_eq!(
+            "Kraken=volume".parse::<SourceWeight>().unwrap(),
+            SourceWeight {
+                source: "kraken".to_string(),
+                weight: Weight::Volume
+            }
+        );
+        assert_eq!(
+            "coingecko=2.5".parse::<SourceWeight>().unwrap().weight,
+            Weight::Static(2.5)
+        );
+        assert!("kraken".parse::<SourceWeight>().is_err());
+        assert!("kraken=-1".parse::<SourceWeight>().is_err());
+    }
+
+    #[test]
+    fn test_weighted_median() {
+        let aggregation = Aggregation {
+            weights: vec![
+                "thin=0.2".parse().unwrap(),
+                "kraken=volume".parse().unwrap(),
+                "binance=volume".parse().unwrap(),
+            ],
+        };
+        let quotes = vec![
+            quote(COINGECKO, 2000.0, None),
+            // a thinly traded exchange far off during volatility
+            quote("thin", 3000.0, None),
+            quote("kraken", 2010.0, Some(1_000_000.0)),
+            quote("binance", 1990.0, Some(3_000_000.0)),
+        ];
+        assert_eq!(aggregation.weights(&quotes), vec![1.0, 0.2, 0.5, 1.5]);
+        // total 3.2, sorted: 1990 (1.5), 2000 (1.0) crosses half of the weight
+        assert_eq!(aggregation.aggregate(&quotes).unwrap().price, 2000.0);
+
+        // a volume-weighted source without volume does not count
+        let quotes = vec![quote("kraken", 2500.0, None), quote(COINGECKO, 2000.0, None)];
+        assert_eq!(aggregation.aggregate(&quotes).unwrap().price, 2000.0);
+
+        // equal weights, midpoint of the two prices with the larger uncertainty
+        let aggregation = Aggregation { weights: vec![] };
+        let cross_rate = SourceQuote {
+            uncertainty: 0.01,
+            ..quote(COINGECKO, 2000.0, None)
+        };
+        let quotes = vec![cross_rate, quote("kraken", 2100.0, None)];
+        assert_eq!(
+            aggregation.aggregate(&quotes).unwrap(),
+            Quote {
+                price: 2050.0,
+                uncertainty: 0.01
+            }
+        );
+        assert!(aggregation.aggregate(&[]).is_err());
+    }
+
+    #[test]
+    fn test_exchange_quote() {
+        let tickers: Tickers = serde_json::from_value(serde_json::json!({
+            "tickers": [
+                {
+                    "market": { "identifier": "kraken" },
+                    "converted_last": { "btc": 0.0005, "usd": 25.0 },
+                    "converted_volume": { "btc": 10.0, "usd": 500000.0 }
+                },
+                {
+                    "market": { "identifier": "kraken" },
+                    "converted_last": { "btc": 0.001, "usd": 50.0 },
+                    "converted_volume": { "btc": 1.0, "usd": 1.0 },
+                    "is_anomaly": true
+                }
+            ]
+        }))
+        .unwrap();
+        let quote = exchange_quote("kraken", tickers, 0.005).unwrap();
+        assert!((quote.price - 2000.0).abs() < 1e-6);
+        assert_eq!(quote.volume, Some(500000.0));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{CurrencyId, Error};
+use std::{convert::TryInto, fmt, hash::Hash, marker::PhantomData};
+
+/// A currency as a type, so that amounts of different currencies cannot be mixed.
+pub trait Currency: Copy + fmt::Debug + Default + Eq + Ord + Hash + Send + Sync + 'static {
+    const ID: CurrencyId;
+    /// Number of decimals of one unit, e.g. 10 for 1 DOT = 10^10 planck.
+    const DECIMALS: u32;
+    const SYMBOL: &'static str;
+
+    /// Number of planck (or satoshi) in one unit.
+    fn one() -> u128 {
+        10u128.pow(Self::DECIMALS)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dot;
+
+impl Currency for Dot {
+    const ID: CurrencyId = CurrencyId::DOT;
+    const DECIMALS: u32 = 10;
+    const SYMBOL: &'static str = "DOT";
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ksm;
+
+impl Currency for Ksm {
+    const ID: CurrencyId = CurrencyId::KSM;
+    const DECIMALS: u32 = 12;
+    const SYMBOL: &'static str = "KSM";
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InterBtc;
+
+impl Currency for InterBtc {
+    const ID: CurrencyId = CurrencyId::INTERBTC;
+    const DECIMALS: u32 = 8;
+    const SYMBOL: &'static str = "interBTC";
+}
+
+/// An amount of the currency `C` in its smallest unit (planck, or satoshi for the wrapped
+/// currency). Amounts are only added to and compared with amounts of the same currency,
+/// and arithmetic fails with `Error::ArithmeticError` rather than overflowing.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount<C: Currency> {
+    planck: u128,
+    currency: PhantomData<C>,
+}
+
+/// Amount of the collateral currency, DOT unless given otherwise.
+pub type CollateralAmount<C = Dot> = Amount<C>;
+
+/// Amount of the wrapped currency, in satoshi.
+pub type WrappedAmount = Amount<InterBtc>;
+
+impl<C: Currency> Amount<C> {
+    pub const fn from_planck(planck: u128) -> Self {
+        Self {
+            planck,
+            currency: PhantomData,
+        }
+    }
+
+    /// An amount of whole units, e.g. `from_units(2)` is 2 DOT.
+    pub fn from_units(units: u128) -> Result<Self, Error> {
+        units
+            .checked_mul(C::one())
+            .map(Self::from_planck)
+            .ok_or(Error::ArithmeticError)
+    }
+
+    /// Take a balance of the given currency, failing if it is a different currency.
+    pub fn from_balance(currency: CurrencyId, planck: u128) -> Result<Self, Error> {
+        if currency != C::ID {
+            return Err(Error::CurrencyMismatch {
+                expected: C::ID,
+                actual: currency,
+            });
+        }
+        Ok(Self::from_planck(planck))
+    }
+
+    /// Parse an amount in units with up to `C::DECIMALS` decimals, e.g. `1.5`.
+    pub fn from_decimal(src: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidAmount(src.to_string());
+        let mut parts = src.trim().splitn(2, '.');
+        let whole = parts.next().unwrap_or_default();
+        let fraction = parts.next().unwrap_or_default();
+        if (whole.is_empty() && fraction.is_empty())
+            || fraction.len() > C::DECIMALS as usize
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let whole: u128 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        let fraction: u128 = if fraction.is_empty() {
+            0
+        } else {
+            // right-pad to the number of decimals, e.g. `.5` is 5 * 10^(decimals - 1)
+            fraction.parse::<u128>().map_err(|_| invalid())? * 10u128.pow(C::DECIMALS - fraction.len() as u32)
+        };
+        Self::from_units(whole)?.checked_add(Self::from_planck(fraction))
+    }
+
+    pub fn planck(&self) -> u128 {
+        self.planck
+    }
+
+    /// The amount in units without trailing zeros, e.g. `1.5`.
+    pub fn to_decimal(&self) -> String {
+        let one = C::one();
+        let fraction = format!("{:0width$}", self.planck % one, width = C::DECIMALS as usize);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            (self.planck / one).to_string()
+        } else {
+            format!("{}.{}", self.planck / one, fraction)
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.planck == 0
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, Error> {
+        self.planck
+            .checked_add(other.planck)
+            .map(Self::from_planck)
+            .ok_or(Error::ArithmeticError)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, Error> {
+        self.planck
+            .checked_sub(other.planck)
+            .map(Self::from_planck)
+            .ok_or(Error::ArithmeticError)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self::from_planck(self.planck.saturating_add(other.planck))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self::from_planck(self.planck.saturating_sub(other.planck))
+    }
+
+    pub fn checked_mul(self, factor: u128) -> Result<Self, Error> {
+        self.planck
+            .checked_mul(factor)
+            .map(Self::from_planck)
+            .ok_or(Error::ArithmeticError)
+    }
+
+    pub fn checked_div(self, divisor: u128) -> Result<Self, Error> {
+        self.planck
+            .checked_div(divisor)
+            .map(Self::from_planck)
+            .ok_or(Error::ArithmeticError)
+    }
+}
+
+impl WrappedAmount {
+    pub fn from_sat(sat: u64) -> Self {
+        Self::from_planck(sat.into())
+    }
+
+    /// The amount in satoshi, e.g. for a bitcoin payment.
+    pub fn sat(&self) -> Result<u64, Error> {
+        Ok(self.planck.try_into()?)
+    }
+}
+
+impl<C: Currency> fmt::Debug for Amount<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} planck {}", self.planck, C::SYMBOL)
+    }
+}
+
+impl<C: Currency> fmt::Display for Amount<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.to_decimal(), C::SYMBOL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_conversion() {
+        assert_eq!(CollateralAmount::<Dot>::from_units(2).unwrap().planck(), 20_000_000_000);
+        assert_eq!(Amount::<Dot>::from_decimal("1.5").unwrap().planck(), 15_000_000_000);
+        assert_eq!(Amount::<Ksm>::from_decimal("1.5").unwrap().planck(), 1_500_000_000_000);
+        assert_eq!(
+            WrappedAmount::from_decimal(".00000001").unwrap(),
+            WrappedAmount::from_sat(1)
+        );
+        assert_eq!(WrappedAmount::from_decimal("3").unwrap().sat().unwrap(), 300_000_000);
+        assert!(WrappedAmount::from_decimal("0.000000001").is_err());
+        assert!(WrappedAmount::from_decimal("1.2.3").is_err());
+        assert!(WrappedAmount::from_decimal("-1").is_err());
+        assert!(WrappedAmount::from_decimal(".").is_err());
+
+        assert_eq!(WrappedAmount::from_sat(150_000_000).to_string(), "1.5 interBTC");
+        assert_eq!(Amount::<Dot>::from_units(2).unwrap().to_string(), "2 DOT");
+        assert_eq!(Amount::<Dot>::from_planck(1).to_decimal(), "0.0000000001");
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let one = Amount::<Dot>::from_units(1).unwrap();
+        assert_eq!(one.checked_add(one).unwrap(), Amount::from_units(2).unwrap());
+        assert!(matches!(
+            Amount::<Dot>::from_planck(0).checked_sub(one),
+            Err(Error::ArithmeticError)
+        ));
+        assert!(Amount::<Dot>::from_planck(u128::MAX).checked_mul(2).is_err());
+        assert!(one.checked_div(0).is_err());
+        assert!(Amount::<Dot>::from_units(u128::MAX).is_err());
+    }
+
+    #[test]
+    fn test_currency_mismatch() {
+        assert!(Amount::<Dot>::from_balance(CurrencyId::DOT, 1).is_ok());
+        assert!(matches!(
+            WrappedAmount::from_balance(CurrencyId::DOT, 1),
+            Err(Error::CurrencyMismatch {
+                expected: CurrencyId::INTERBTC,
+                actual: CurrencyId::DOT
+            })
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{Balance, Error};
+
+/// Minimum free balance to retain when submitting extrinsics. Dropping below the
+/// existential deposit would reap the account, after which the vault can no longer
+/// pay for the extrinsics needed to complete its requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BalanceGuard {
+    /// Existential deposit of the fee currency.
+    pub existential_deposit: Balance,
+    /// Additional balance to keep for future fees.
+    pub reserve: Balance,
+}
+
+impl BalanceGuard {
+    pub fn new(existential_deposit: Balance, reserve: Balance) -> Self {
+        Self {
+            existential_deposit,
+            reserve,
+        }
+    }
+
+    /// The balance that must remain after the extrinsic has been applied.
+    pub fn minimum(&self) -> Balance {
+        self.existential_deposit.saturating_add(self.reserve)
+    }
+
+    /// Checks that the free balance covers the (estimated) fee and the amount transferred
+    /// or reserved by the extrinsic, while retaining the minimum balance.
+    pub fn check(&self, free: Balance, fee: Balance, spent: Balance) -> Result<(), Error> {
+        let required = self.minimum().saturating_add(fee).saturating_add(spent);
+        if free < required {
+            Err(Error::InsufficientBalance { free, required })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_guard() {
+        let guard = BalanceGuard::new(100, 50);
+        assert_eq!(guard.minimum(), 150);
+        assert!(guard.check(1000, 200, 650).is_ok());
+        assert!(matches!(
+            guard.check(1000, 200, 651),
+            Err(Error::InsufficientBalance {
+                free: 1000,
+                required: 1001
+            })
+        ));
+        assert!(guard.check(1000, Balance::MAX, 1).is_err());
+    }
+}
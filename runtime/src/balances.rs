@@ -0,0 +1,183 @@
+use crate::{AccountId, Balance, BlockNumber, CurrencyId, COLLATERAL_CURRENCY, FEE_CURRENCY, WRAPPED_CURRENCY};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// Free and reserved balance of an account in one currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AccountBalance {
+    pub free: Balance,
+    pub reserved: Balance,
+}
+
+/// Balance of an account that changed in a block, `previous` is `None` for the balances
+/// delivered on the first block of the subscription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceChange {
+    pub account_id: AccountId,
+    pub currency_id: CurrencyId,
+    pub previous: Option<AccountBalance>,
+    pub current: AccountBalance,
+}
+
+impl BalanceChange {
+    /// Change of the free balance, positive if it increased.
+    pub fn free_delta(&self) -> i128 {
+        let previous = self.previous.map_or(0, |balance| balance.free);
+        (self.current.free as i128).saturating_sub(previous as i128)
+    }
+}
+
+/// All balance changes of a finalized block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceUpdate {
+    pub block: BlockNumber,
+    pub changes: Vec<BalanceChange>,
+}
+
+impl BalanceUpdate {
+    pub fn get(&self, account_id: &AccountId, currency_id: CurrencyId) -> Option<&BalanceChange> {
+        self.changes
+            .iter()
+            .find(|change| &change.account_id == account_id && change.currency_id == currency_id)
+    }
+}
+
+/// The accounts and currencies whose balances are followed by `on_balance_change`.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceSubscription {
+    accounts: Vec<AccountId>,
+    currencies: Vec<CurrencyId>,
+}
+
+impl BalanceSubscription {
+    pub fn new(accounts: Vec<AccountId>) -> Self {
+        Self {
+            accounts,
+            currencies: Vec::new(),
+        }
+    }
+
+    pub fn with_currency(mut self, currency_id: CurrencyId) -> Self {
+        if !self.currencies.contains(&currency_id) {
+            self.currencies.push(currency_id);
+        }
+        self
+    }
+
+    /// Follow the currency in which the fees of the extrinsics are paid.
+    pub fn with_fee_currency(self) -> Self {
+        self.with_currency(FEE_CURRENCY)
+    }
+
+    /// Follow the currency in which collateral is locked.
+    pub fn with_collateral_currency(self) -> Self {
+        self.with_currency(COLLATERAL_CURRENCY)
+    }
+
+    /// Follow the currency issued against the locked bitcoin.
+    pub fn with_wrapped_currency(self) -> Self {
+        self.with_currency(WRAPPED_CURRENCY)
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = (&AccountId, CurrencyId)> + '_ {
+        self.accounts.iter().flat_map(move |account_id| {
+            self.currencies
+                .iter()
+                .map(move |currency_id| (account_id, *currency_id))
+        })
+    }
+}
+
+/// Latest balances delivered by the subscriptions, shared by the clones of the parachain
+/// client so that e.g. the balance guard does not need to query them before each extrinsic.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceCache(Arc<RwLock<HashMap<(AccountId, CurrencyId), AccountBalance>>>);
+
+impl BalanceCache {
+    pub fn get(&self, account_id: &AccountId, currency_id: CurrencyId) -> Option<AccountBalance> {
+        self.0.read().ok()?.get(&(account_id.clone(), currency_id)).copied()
+    }
+
+    pub(crate) fn set(&self, account_id: &AccountId, currency_id: CurrencyId, balance: AccountBalance) {
+        if let Ok(mut balances) = self.0.write() {
+            balances.insert((account_id.clone(), currency_id), balance);
+        }
+    }
+}
+
+/// Balances last delivered to one subscription. Every subscription keeps its own, so that
+/// subscriptions following the same balance each see all of its changes, unlike with the
+/// shared `BalanceCache`.
+#[derive(Debug, Default)]
+pub(crate) struct BalanceTracker(HashMap<(AccountId, CurrencyId), AccountBalance>);
+
+impl BalanceTracker {
+    /// Store the balance, returns the change if it differs from the stored one.
+    pub(crate) fn update(
+        &mut self,
+        account_id: &AccountId,
+        currency_id: CurrencyId,
+        balance: AccountBalance,
+    ) -> Option<BalanceChange> {
+        let previous = self.0.insert((account_id.clone(), currency_id), balance);
+        if previous == Some(balance) {
+            return None;
+        }
+        Some(BalanceChange {
+            account_id: account_id.clone(),
+            currency_id,
+            previous,
+            current: balance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_changes() {
+        let alice = AccountId::new([1; 32]);
+        let bob = AccountId::new([2; 32]);
+        let subscription = BalanceSubscription::new(vec![alice.clone(), bob.clone()])
+            .with_fee_currency()
+            .with_collateral_currency()
+            .with_wrapped_currency();
+        // the fee and collateral currency are the same
+        assert_eq!(subscription.keys().count(), 4);
+
+        let mut tracker = BalanceTracker::default();
+        let balance = |free| AccountBalance { free, reserved: 0 };
+        let change = tracker.update(&alice, COLLATERAL_CURRENCY, balance(100)).unwrap();
+        assert_eq!(change.previous, None);
+        assert_eq!(change.free_delta(), 100);
+        assert_eq!(tracker.update(&alice, COLLATERAL_CURRENCY, balance(100)), None);
+
+        let change = tracker.update(&alice, COLLATERAL_CURRENCY, balance(40)).unwrap();
+        assert_eq!(change.free_delta(), -60);
+
+        let cache = BalanceCache::default();
+        cache.set(&alice, COLLATERAL_CURRENCY, balance(40));
+        assert_eq!(cache.get(&alice, COLLATERAL_CURRENCY), Some(balance(40)));
+        assert_eq!(cache.get(&bob, COLLATERAL_CURRENCY), None);
+    }
+
+    #[test]
+    fn test_subscriptions_see_all_changes() {
+        // e.g. the balance metrics of the vault and the fee top-up follow the same balance
+        let alice = AccountId::new([1; 32]);
+        let (mut metrics, mut top_up) = (BalanceTracker::default(), BalanceTracker::default());
+        let balance = |free| AccountBalance { free, reserved: 0 };
+        metrics.update(&alice, FEE_CURRENCY, balance(100));
+        top_up.update(&alice, FEE_CURRENCY, balance(100));
+
+        let change = metrics.update(&alice, FEE_CURRENCY, balance(10)).unwrap();
+        assert_eq!(change.free_delta(), -90);
+        // the change is not consumed by the other subscription
+        let change = top_up.update(&alice, FEE_CURRENCY, balance(10)).unwrap();
+        assert_eq!(change.free_delta(), -90);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::BlockNumber;
+use lazy_static::lazy_static;
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts};
+use std::ops::Range;
+
+lazy_static! {
+    pub static ref BLOCK_LATENCY: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "block_latency_seconds",
+            "Time between the block timestamp and its arrival on the subscription"
+        )
+        .buckets(vec![1.0, 3.0, 6.0, 12.0, 24.0, 48.0, 96.0]),
+        &["subscription"]
+    )
+    .expect("Failed to create metric");
+    pub static ref MISSED_BLOCKS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "missed_blocks",
+            "Number of blocks skipped by the subscription that had to be refetched"
+        ),
+        &["subscription"]
+    )
+    .expect("Failed to create metric");
+    pub static ref CHAIN_LAG: IntGauge = IntGauge::new(
+        "chain_lag_blocks",
+        "Number of blocks by which finality lags behind the best block"
+    )
+    .expect("Failed to create metric");
+}
+
+/// Tracks the block numbers delivered by a subscription to detect missed notifications.
+#[derive(Debug, Default)]
+pub(crate) struct BlockTracker {
+    last: Option<BlockNumber>,
+}
+
+impl BlockTracker {
+    /// Returns the block numbers that were skipped before `number` and need to be refetched.
+    /// Duplicates and numbers at or below the last one (i.e. re-orgs of the best chain) are
+    /// delivered as is, they restart the tracking from that number.
+    pub(crate) fn next(&mut self, number: BlockNumber) -> Range<BlockNumber> {
+        let missing = match self.last {
+            Some(last) if number > last => last.saturating_add(1)..number,
+            _ => number..number,
+        };
+        self.last = Some(number);
+        missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_tracker() {
+        let mut tracker = BlockTracker::default();
+        assert!(tracker.next(10).is_empty());
+        assert!(tracker.next(11).is_empty());
+        assert_eq!(tracker.next(14), 12..14);
+        // re-org of the best chain
+        assert!(tracker.next(13).is_empty());
+        assert!(tracker.next(14).is_empty());
+    }
+}
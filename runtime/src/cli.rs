@@ -1,6 +1,7 @@
 use crate::{
     error::{Error, KeyLoadingError},
-    InterBtcParachain, InterBtcSigner,
+    FeePayment, InterBtcParachain, InterBtcSigner, NetworkArg, NetworkProfile, TipBudget, WsClientOptions,
+    DEFAULT_PARACHAIN_URL,
 };
 use clap::Clap;
 use sp_core::{sr25519::Pair, Pair as _};
@@ -37,6 +38,12 @@ impl ProviderUserOpts {
         };
         Ok((pair, user_name))
     }
+
+    /// Get another key pair from the keyfile, e.g. to use different accounts for different tasks.
+    pub fn get_named_key_pair(&self, keyname: &str) -> Result<Pair, Error> {
+        let file_path = self.keyfile.as_ref().ok_or(KeyLoadingError::KeyfileRequired)?;
+        Ok(get_credentials_from_file(file_path, keyname)?)
+    }
 }
 
 /// Loads the credentials for the given user from the keyfile
@@ -64,9 +71,17 @@ pub fn parse_duration_minutes(src: &str) -> Result<Duration, ParseIntError> {
 
 #[derive(Clap, Debug, Clone)]
 pub struct ConnectionOpts {
-    /// Parachain websocket URL.
-    #[clap(long, default_value = "ws://127.0.0.1:9944")]
-    pub btc_parachain_url: String,
+    /// Network profile (interlay, kintsugi, testnet or local) to take the default parachain
+    /// url from. The client refuses to start against any other parachain than that of the
+    /// profile. The vault also takes the bitcoin network and the electrs url from it. The
+    /// bitcoin networks mainnet and regtest are accepted as deprecated alias of
+    /// `--bitcoin-network`.
+    #[clap(long)]
+    pub network: Option<NetworkArg>,
+
+    /// Parachain websocket URL, defaults to the url of the network profile or ws://127.0.0.1:9944.
+    #[clap(long)]
+    pub btc_parachain_url: Option<String>,
 
     /// Timeout in milliseconds to wait for connection to btc-parachain.
     #[clap(long, parse(try_from_str = parse_duration_ms), default_value = "60000")]
@@ -79,17 +94,127 @@ pub struct ConnectionOpts {
     /// Maximum notification capacity for each subscription
     #[clap(long)]
     pub max_notifs_per_subscription: Option<usize>,
+
+    /// Maximum size in bytes of a single websocket request or response. Raise it if
+    /// the connection fails while fetching large payloads such as the runtime metadata,
+    /// lower it to stay within the limits of a proxy.
+    #[clap(long, default_value = "10485760")]
+    pub max_message_size: u32,
+
+    /// Tip (in planck) to include in time-sensitive extrinsics, such as theft
+    /// reports and executions close to their expiry.
+    #[clap(long, default_value = "0")]
+    pub urgent_tip: u128,
+
+    /// Maximum total amount of tips to pay, urgent extrinsics are submitted
+    /// without tip once this is exhausted. Unlimited if not set.
+    #[clap(long)]
+    pub max_total_tips: Option<u128>,
+
+    /// Simulate execute and registration extrinsics via `system_dryRun` before
+    /// submitting them, so that predictable failures do not cost fees.
+    #[clap(long)]
+    pub dry_run_extrinsics: bool,
+
+    /// Free balance (in planck) to keep in addition to the existential deposit.
+    /// Extrinsics whose estimated fee and transferred amount would use it are
+    /// refused. Disabled if not set.
+    #[clap(long)]
+    pub balance_reserve: Option<u128>,
+
+    /// Maximum estimated fee (in planck) of a single extrinsic, extrinsics that are
+    /// checked before submission are refused if it is exceeded. Unlimited if not set.
+    #[clap(long)]
+    pub max_extrinsic_fee: Option<u128>,
+
+    /// Number of entries fetched per request when iterating over large storage
+    /// maps, such as all vaults or all issue requests.
+    #[clap(long, default_value = "100")]
+    pub storage_page_size: u32,
+
+    /// Parachain network the client is deployed for (interlay, kintsugi or testnet). If
+    /// set, the client refuses to start against any other network. Implied by `--network`.
+    #[clap(long)]
+    pub parachain_network: Option<String>,
+
+    /// How to pay the fees of extrinsics: `native`, or `asset` / `asset:<currency>` (e.g.
+    /// `asset:ksm`) to pay them via the charge-asset-tx-payment signed extension, which
+    /// the runtime must include.
+    #[clap(long, default_value = "native")]
+    pub fee_payment: FeePayment,
 }
 
 impl ConnectionOpts {
     pub async fn try_connect(&self, signer: InterBtcSigner) -> Result<InterBtcParachain, Error> {
         InterBtcParachain::from_url_and_config_with_retry(
-            &self.btc_parachain_url,
+            &self.parachain_url(),
             signer,
-            self.max_concurrent_requests,
-            self.max_notifs_per_subscription,
+            self.ws_client_options(),
             self.btc_parachain_connection_timeout_ms,
         )
         .await
+        .and_then(|parachain_rpc| self.expect_network(parachain_rpc))
+        .and_then(|parachain_rpc| parachain_rpc.with_fee_payment(self.fee_payment))
+        .and_then(|parachain_rpc| parachain_rpc.with_balance_reserve(self.balance_reserve))
+        .map(|parachain_rpc| {
+            parachain_rpc
+                .with_tip_budget(self.tip_budget())
+                .with_dry_run(self.dry_run_extrinsics)
+                .with_fee_budget(self.max_extrinsic_fee)
+                .with_storage_page_size(self.storage_page_size)
+        })
+    }
+
+    /// The network profile selected with `--network`, if any.
+    pub fn network_profile(&self) -> Option<NetworkProfile> {
+        match self.network {
+            Some(NetworkArg::Profile(profile)) => Some(profile),
+            _ => None,
+        }
+    }
+
+    /// The bitcoin network given with `--network` the deprecated way, if any.
+    pub fn deprecated_bitcoin_network(&self) -> Option<&'static str> {
+        match self.network {
+            Some(NetworkArg::BitcoinNetwork(network)) => Some(network),
+            _ => None,
+        }
+    }
+
+    /// The configured parachain url, or that of the network profile.
+    pub fn parachain_url(&self) -> String {
+        match (&self.btc_parachain_url, self.network_profile()) {
+            (Some(url), _) => url.clone(),
+            (None, Some(profile)) => profile.parachain_url.to_string(),
+            (None, None) => DEFAULT_PARACHAIN_URL.to_string(),
+        }
+    }
+
+    /// Fail unless the connected chain is the configured network and the parachain of the
+    /// network profile.
+    pub fn expect_network(&self, parachain_rpc: InterBtcParachain) -> Result<InterBtcParachain, Error> {
+        let parachain_rpc = match &self.parachain_network {
+            Some(network) => parachain_rpc.expect_network(network)?,
+            None => parachain_rpc,
+        };
+        match &self.network_profile() {
+            Some(profile) => parachain_rpc.expect_profile(profile),
+            None => Ok(parachain_rpc),
+        }
+    }
+
+    pub fn ws_client_options(&self) -> WsClientOptions {
+        let defaults = WsClientOptions::default();
+        WsClientOptions {
+            max_concurrent_requests: self.max_concurrent_requests.unwrap_or(defaults.max_concurrent_requests),
+            max_notifs_per_subscription: self
+                .max_notifs_per_subscription
+                .unwrap_or(defaults.max_notifs_per_subscription),
+            max_message_size: self.max_message_size,
+        }
+    }
+
+    pub fn tip_budget(&self) -> TipBudget {
+        TipBudget::new(self.urgent_tip, self.max_total_tips)
     }
 }
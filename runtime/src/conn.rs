@@ -6,19 +6,40 @@ use tokio::time::{delay_for, timeout};
 const RETRY_TIMEOUT: Duration = Duration::from_millis(1000);
 const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
 
-pub(crate) async fn new_websocket_client(
-    url: &str,
-    max_concurrent_requests: Option<usize>,
-    max_notifs_per_subscription: Option<usize>,
-) -> Result<WsClient, Error> {
+/// Large enough for the metadata of the current runtimes.
+pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 10 * 1024 * 1024;
+
+/// Options of the websocket connection to the parachain. Compression and keep-alive pings
+/// are not supported by the websocket client, the subscriptions to new blocks keep the
+/// connection active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WsClientOptions {
+    pub max_concurrent_requests: usize,
+    pub max_notifs_per_subscription: usize,
+    /// Maximum size in bytes of a single request or response, e.g. the runtime metadata.
+    pub max_message_size: u32,
+}
+
+impl Default for WsClientOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: 1024,
+            max_notifs_per_subscription: 256,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+pub(crate) async fn new_websocket_client(url: &str, options: WsClientOptions) -> Result<WsClient, Error> {
     let parsed_url = url::Url::parse(&url)?;
     let path = parsed_url.path().to_string();
 
     let ws_client = WsClientBuilder::default()
         .handshake_url(path.into())
         .connection_timeout(CONNECTION_TIMEOUT)
-        .max_concurrent_requests(max_concurrent_requests.unwrap_or(1024))
-        .max_notifs_per_subscription(max_notifs_per_subscription.unwrap_or(256))
+        .max_concurrent_requests(options.max_concurrent_requests)
+        .max_notifs_per_subscription(options.max_notifs_per_subscription)
+        .max_request_body_size(options.max_message_size)
         .build(url)
         .await?;
     Ok(ws_client)
@@ -26,14 +47,13 @@ pub(crate) async fn new_websocket_client(
 
 pub(crate) async fn new_websocket_client_with_retry(
     url: &str,
-    max_concurrent_requests: Option<usize>,
-    max_notifs_per_subscription: Option<usize>,
+    options: WsClientOptions,
     connection_timeout: Duration,
 ) -> Result<WsClient, Error> {
     log::info!("Connecting to the btc-parachain...");
     timeout(connection_timeout, async move {
         loop {
-            match new_websocket_client(url, max_concurrent_requests, max_notifs_per_subscription).await {
+            match new_websocket_client(url, options).await {
                 Err(Error::JsonRpseeError(JsonRpseeError::TransportError(err))) => {
                     log::trace!("could not connect to parachain: {}", err);
                     delay_for(RETRY_TIMEOUT).await;
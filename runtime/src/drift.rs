@@ -0,0 +1,57 @@
+use lazy_static::lazy_static;
+use prometheus::Gauge;
+use std::time::Duration;
+
+lazy_static! {
+    pub static ref TIMESTAMP_DRIFT: Gauge = Gauge::new(
+        "timestamp_drift_seconds",
+        "Timestamp of the latest best block minus the local time at which it arrived"
+    )
+    .expect("Failed to create metric");
+}
+
+/// Timestamp of a parachain block compared to the local time at which it arrived, both in
+/// milliseconds as defined by the `timestamp` pallet. The drift includes the propagation
+/// delay of the block, so it is slightly negative on a healthy chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampDrift {
+    pub block_timestamp: u64,
+    pub local_time: u64,
+}
+
+impl TimestampDrift {
+    /// Drift in milliseconds, positive if the block is ahead of the local clock.
+    pub fn drift_ms(&self) -> i64 {
+        (self.block_timestamp as i64).saturating_sub(self.local_time as i64)
+    }
+
+    /// Returns true if the parachain time is off from the local time by more than the
+    /// threshold in either direction, in which case deadlines computed from either clock
+    /// (e.g. the expiry of redeem and replace periods) can not be relied upon.
+    pub fn exceeds(&self, threshold: Duration) -> bool {
+        (self.drift_ms() as i128).abs() as u128 > threshold.as_millis()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_drift() {
+        let behind = TimestampDrift {
+            block_timestamp: 1_000,
+            local_time: 61_000,
+        };
+        assert_eq!(behind.drift_ms(), -60_000);
+        assert!(behind.exceeds(Duration::from_secs(30)));
+        assert!(!behind.exceeds(Duration::from_secs(60)));
+
+        let ahead = TimestampDrift {
+            block_timestamp: 61_000,
+            local_time: 1_000,
+        };
+        assert_eq!(ahead.drift_ms(), 60_000);
+        assert!(ahead.exceeds(Duration::from_secs(30)));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::Balance;
+use lazy_static::lazy_static;
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec};
+use serde::{Deserialize, Deserializer};
+
+lazy_static! {
+    pub static ref DRY_RUN_FAILURES: IntCounterVec = IntCounterVec::new(
+        prometheus::Opts::new("dry_run_failures", "Number of extrinsics that failed in simulation"),
+        &["call"]
+    )
+    .expect("Failed to create metric");
+    pub static ref EXTRINSIC_FEES: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "extrinsic_fees_planck",
+            "Fees of submitted extrinsics, as expected at submission and as realized on inclusion"
+        )
+        .buckets(prometheus::exponential_buckets(1e6, 10.0, 8).expect("Invalid buckets")),
+        &["call", "kind"]
+    )
+    .expect("Failed to create metric");
+}
+
+/// Subset of the response of `payment_queryInfo`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FeeInfo {
+    #[serde(deserialize_with = "deserialize_balance")]
+    pub partial_fee: Balance,
+}
+
+/// Balances are encoded as strings by recent nodes, and as numbers by older ones.
+fn deserialize_balance<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Balance, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(value) => value.parse().map_err(serde::de::Error::custom),
+        StringOrNumber::Number(value) => Ok(value.into()),
+    }
+}
+
+/// Outcome of simulating an extrinsic that would have been included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DryRunResult {
+    /// Estimated fee, excluding any tip.
+    pub fee: Balance,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_fee_info() {
+        let fee_info: FeeInfo =
+            serde_json::from_str(r#"{"weight": 1000, "class": "normal", "partialFee": "2000000000"}"#).unwrap();
+        assert_eq!(fee_info.partial_fee, 2_000_000_000);
+
+        let fee_info: FeeInfo =
+            serde_json::from_str(r#"{"weight": 1000, "class": "normal", "partialFee": 125}"#).unwrap();
+        assert_eq!(fee_info.partial_fee, 125);
+    }
+}
@@ -2,8 +2,9 @@ pub use jsonrpsee_types::error::Error as JsonRpseeError;
 pub use substrate_subxt::Error as SubxtError;
 
 use crate::{
-    BTC_RELAY_MODULE, COMMIT_PERIOD_EXPIRED_ERROR, DUPLICATE_BLOCK_ERROR, INVALID_CHAIN_ID_ERROR,
-    ISSUE_COMPLETED_ERROR, ISSUE_MODULE, REDEEM_MODULE,
+    CurrencyId, BTC_RELAY_MODULE, COMMIT_PERIOD_EXPIRED_ERROR, DUPLICATE_BLOCK_ERROR, INVALID_CHAIN_ID_ERROR,
+    ISSUE_COMPLETED_ERROR, ISSUE_MODULE, REDEEM_COMPLETED_ERROR, REDEEM_MODULE, REPLACE_COMPLETED_ERROR,
+    REPLACE_MODULE,
 };
 use codec::Error as CodecError;
 use jsonrpsee_types::{
@@ -45,6 +46,45 @@ pub enum Error {
     Timeout,
     #[error("Block is not in the relay main chain")]
     BlockNotInRelayMainChain,
+    #[error("Unknown runtime {0} with spec version {1}")]
+    UnknownRuntime(String, u32),
+    #[error("Runtime metadata is missing module {0}")]
+    MissingModule(String),
+    #[error("Runtime metadata is missing constant {0}::{1}")]
+    MissingConstant(&'static str, &'static str),
+    #[error("Chain {property} is {actual}, expected {expected} for {network}")]
+    ChainPropertyMismatch {
+        network: &'static str,
+        property: &'static str,
+        expected: String,
+        actual: String,
+    },
+    #[error("Connected to {actual}, but the client is configured for {expected}")]
+    NetworkMismatch { expected: String, actual: &'static str },
+    #[error("Unknown network {0}")]
+    UnknownNetwork(String),
+    #[error("{0} is a bitcoin network, not a network profile")]
+    BitcoinNetworkAsProfile(String),
+    #[error("Extrinsic would be invalid: {0}")]
+    DryRunInvalid(String),
+    #[error("Insufficient free balance {free}, require {required} including fees and reserve")]
+    InsufficientBalance { free: u128, required: u128 },
+    #[error("Estimated fee {fee} exceeds the budget of {budget}")]
+    FeeBudgetExceeded { fee: u128, budget: u128 },
+    #[error("Unknown fee payment {0}, expected native, asset or asset:<currency>")]
+    UnknownFeePayment(String),
+    #[error("Runtime does not support paying fees in another asset")]
+    AssetFeePaymentUnsupported,
+    #[error("Cannot convert fees to {0:?}, only the native and the wrapped currency are supported")]
+    UnconvertibleFeeCurrency(CurrencyId),
+    #[error("Unknown currency {0}")]
+    UnknownCurrency(String),
+    #[error("Expected an amount of {expected:?}, got {actual:?}")]
+    CurrencyMismatch { expected: CurrencyId, actual: CurrencyId },
+    #[error("Invalid amount {0}")]
+    InvalidAmount(String),
+    #[error("Arithmetic overflow or underflow")]
+    ArithmeticError,
 
     #[error("Failed to load credentials from file: {0}")]
     KeyLoadingFailure(#[from] KeyLoadingError),
@@ -97,6 +137,29 @@ impl Error {
         )
     }
 
+    pub fn is_redeem_completed(&self) -> bool {
+        matches!(self,
+            Error::SubxtError(SubxtError::Runtime(SubxtRuntimeError::Module(SubxtModuleError {
+                ref module,
+                ref error,
+            }))) if module == REDEEM_MODULE && error == REDEEM_COMPLETED_ERROR
+        )
+    }
+
+    pub fn is_replace_completed(&self) -> bool {
+        matches!(self,
+            Error::SubxtError(SubxtError::Runtime(SubxtRuntimeError::Module(SubxtModuleError {
+                ref module,
+                ref error,
+            }))) if module == REPLACE_MODULE && error == REPLACE_COMPLETED_ERROR
+        )
+    }
+
+    /// True if the execution failed because the request has already been completed.
+    pub fn is_request_completed(&self) -> bool {
+        self.is_issue_completed() || self.is_redeem_completed() || self.is_replace_completed()
+    }
+
     pub fn is_outdated_nonce(&self) -> bool {
         matches!(self,
             Error::SubxtError(SubxtError::Rpc(RequestError::Request(JsonRpcError { error, .. })))
@@ -106,6 +169,31 @@ impl Error {
         )
     }
 
+    pub fn is_priority_too_low(&self) -> bool {
+        matches!(self,
+            Error::SubxtError(SubxtError::Rpc(RequestError::Request(JsonRpcError { error, .. })))
+                if error.code == JsonRpcErrorCode::ServerError(POOL_TOO_LOW_PRIORITY) &&
+                error.message.starts_with(TOO_LOW_PRIORITY_MESSAGE)
+        )
+    }
+
+    /// True if the same extrinsic is already in the pool, e.g. submitted by a standby instance.
+    pub fn is_already_imported(&self) -> bool {
+        matches!(self,
+            Error::SubxtError(SubxtError::Rpc(RequestError::Request(JsonRpcError { error, .. })))
+                if error.code == JsonRpcErrorCode::ServerError(POOL_ALREADY_IMPORTED) &&
+                error.message.starts_with(ALREADY_IMPORTED_MESSAGE)
+        )
+    }
+
+    /// True if the node does not expose the called rpc method, e.g. a custom rpc of a pallet.
+    pub fn is_method_not_found(&self) -> bool {
+        matches!(self,
+            Error::SubxtError(SubxtError::Rpc(RequestError::Request(JsonRpcError { error, .. })))
+                if error.code == JsonRpcErrorCode::MethodNotFound
+        )
+    }
+
     pub fn is_commit_period_expired(&self) -> bool {
         matches!(self,
             Error::SubxtError(SubxtError::Runtime(SubxtRuntimeError::Module(SubxtModuleError {
@@ -131,6 +219,8 @@ impl Error {
 pub enum KeyLoadingError {
     #[error("Key not found in file")]
     KeyNotFound,
+    #[error("Named keys can only be loaded from a keyfile")]
+    KeyfileRequired,
     #[error("Json parsing error: {0}")]
     JsonError(#[from] SerdeJsonError),
     #[error("Io error: {0}")]
@@ -144,3 +234,7 @@ const BASE_ERROR: i32 = 1000;
 const POOL_INVALID_TX: i32 = BASE_ERROR + 10;
 const OUTDATED_NONCE_MESSAGE: &str = "Invalid Transaction";
 const OUTDATED_NONCE_DATA_STR: &str = "Transaction is outdated";
+const POOL_ALREADY_IMPORTED: i32 = BASE_ERROR + 13;
+const ALREADY_IMPORTED_MESSAGE: &str = "Transaction Already Imported";
+const POOL_TOO_LOW_PRIORITY: i32 = BASE_ERROR + 14;
+const TOO_LOW_PRIORITY_MESSAGE: &str = "Priority is too low";
@@ -0,0 +1,94 @@
+use crate::{metadata::RuntimeVersion, pagination::storage_prefix, Error, InterBtcRuntime};
+use codec::Decode;
+use frame_support::metadata::RuntimeMetadataPrefixed;
+use jsonrpsee_types::to_json_value;
+use sp_core::{Bytes, H256};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+};
+use substrate_subxt::{
+    system::Phase, Error as SubxtError, EventTypeRegistry, EventsDecoder, Metadata, Raw, RawEvent, RpcClient,
+};
+
+/// Event decoders by runtime spec version. The decoder of a version is built from the
+/// metadata of the first block seen under that version, so that blocks produced before a
+/// runtime upgrade are decoded with the metadata they were produced with.
+#[derive(Clone, Default)]
+pub(crate) struct EventDecoders(Arc<Mutex<HashMap<u32, Arc<EventsDecoder<InterBtcRuntime>>>>>);
+
+impl EventDecoders {
+    async fn get(&self, rpc_client: &RpcClient, at: H256) -> Result<Arc<EventsDecoder<InterBtcRuntime>>, Error> {
+        let version: RuntimeVersion = rpc_client
+            .request("state_getRuntimeVersion", &[to_json_value(at)?])
+            .await?;
+        if let Some(decoder) = self.0.lock().expect("poisoned").get(&version.spec_version) {
+            return Ok(decoder.clone());
+        }
+
+        log::info!("Loading the metadata of runtime version {}", version.spec_version);
+        let bytes: Bytes = rpc_client.request("state_getMetadata", &[to_json_value(at)?]).await?;
+        let prefixed = RuntimeMetadataPrefixed::decode(&mut &bytes.0[..])?;
+        let metadata = Metadata::try_from(prefixed).map_err(SubxtError::from)?;
+        let decoder = Arc::new(EventsDecoder::<InterBtcRuntime>::new(
+            metadata,
+            EventTypeRegistry::new(),
+        ));
+        self.0
+            .lock()
+            .expect("poisoned")
+            .insert(version.spec_version, decoder.clone());
+        Ok(decoder)
+    }
+
+    /// Decode the events emitted in the given block, with the phase in which they were emitted.
+    async fn decode_events_at(&self, rpc_client: &RpcClient, at: H256) -> Result<Vec<(Phase, Raw)>, Error> {
+        let data: Option<Bytes> = rpc_client
+            .request(
+                "state_getStorage",
+                &[to_json_value(storage_prefix("System", "Events"))?, to_json_value(at)?],
+            )
+            .await?;
+        let data = match data {
+            Some(data) => data,
+            None => return Ok(vec![]),
+        };
+        let decoder = self.get(rpc_client, at).await?;
+        Ok(decoder.decode_events(&mut &data.0[..])?)
+    }
+
+    /// Decode the events emitted in the given block.
+    pub(crate) async fn events_at(&self, rpc_client: &RpcClient, at: H256) -> Result<Vec<RawEvent>, Error> {
+        Ok(self
+            .decode_events_at(rpc_client, at)
+            .await?
+            .into_iter()
+            .filter_map(|(_, raw)| match raw {
+                Raw::Event(event) => Some(event),
+                Raw::Error(_) => None,
+            })
+            .collect())
+    }
+
+    /// Decode the events emitted by the extrinsic at the index of the given block, or its
+    /// error if it failed.
+    pub(crate) async fn extrinsic_events_at(
+        &self,
+        rpc_client: &RpcClient,
+        at: H256,
+        index: u32,
+    ) -> Result<Vec<RawEvent>, Error> {
+        let mut events = Vec::new();
+        for (phase, raw) in self.decode_events_at(rpc_client, at).await? {
+            match (phase, raw) {
+                (Phase::ApplyExtrinsic(i), Raw::Event(event)) if i == index => events.push(event),
+                (Phase::ApplyExtrinsic(i), Raw::Error(err)) if i == index => {
+                    return Err(Error::SubxtError(SubxtError::Runtime(err)))
+                }
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+}
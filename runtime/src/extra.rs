@@ -0,0 +1,299 @@
+use crate::{CurrencyId, Error};
+use codec::{Decode, Encode, Input, Output};
+use core::marker::PhantomData;
+use futures::Future;
+use lazy_static::lazy_static;
+use prometheus::IntCounter;
+use sp_runtime::{generic::Era, traits::SignedExtension, transaction_validity::TransactionValidityError};
+use std::{
+    fmt::Debug,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use substrate_subxt::{
+    balances::Balances,
+    extrinsic::{
+        ChargeTransactionPayment, CheckEra, CheckGenesis, CheckNonce, CheckSpecVersion, CheckTxVersion, CheckWeight,
+        SignedExtra,
+    },
+    system::System,
+};
+
+/// Module of the runtime that provides the charge-asset-tx-payment signed extension.
+pub(crate) const ASSET_TX_PAYMENT_MODULE: &str = "AssetTxPayment";
+
+/// Era with which extrinsics are signed.
+pub(crate) const EXTRINSIC_ERA: Era = Era::Immortal;
+
+tokio::task_local! {
+    static URGENT: bool;
+    static REMARK: Vec<u8>;
+    static TIP: u128;
+    static FEE_PAYMENT: FeePayment;
+}
+
+lazy_static! {
+    pub static ref TIPS_SPENT: IntCounter =
+        IntCounter::new("tips_spent", "Total tips paid for urgent extrinsics").expect("Failed to create metric");
+}
+
+/// Mark all extrinsics submitted by the given future as urgent, so that they
+/// include the configured tip to improve their inclusion priority.
+pub async fn urgent<F: Future>(future: F) -> F::Output {
+    URGENT.scope(true, future).await
+}
+
+pub(crate) fn is_urgent() -> bool {
+    URGENT.try_with(|urgent| *urgent).unwrap_or_default()
+}
+
+/// Batch the execute and cancel extrinsics submitted by the given future with a
+/// `system.remark` of `remark`, e.g. the correlation id of the request they belong to.
+pub async fn with_remark<F: Future>(remark: Vec<u8>, future: F) -> F::Output {
+    REMARK.scope(remark, future).await
+}
+
+pub(crate) fn current_remark() -> Option<Vec<u8>> {
+    REMARK.try_with(|remark| remark.clone()).ok()
+}
+
+pub(crate) async fn with_tip<F: Future>(tip: u128, future: F) -> F::Output {
+    TIP.scope(tip, future).await
+}
+
+pub(crate) fn current_tip() -> u128 {
+    TIP.try_with(|tip| *tip).unwrap_or_default()
+}
+
+pub(crate) async fn with_fee_payment<F: Future>(fee_payment: FeePayment, future: F) -> F::Output {
+    FEE_PAYMENT.scope(fee_payment, future).await
+}
+
+pub(crate) fn current_fee_payment() -> FeePayment {
+    FEE_PAYMENT.try_with(|fee_payment| *fee_payment).unwrap_or_default()
+}
+
+/// How the fees of extrinsics are paid.
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeePayment {
+    /// In the native token, via the transaction-payment signed extension.
+    Native,
+    /// Via the charge-asset-tx-payment signed extension, in the given asset or in the
+    /// native token if none is given. Only available if the runtime includes the extension.
+    Asset(Option<CurrencyId>),
+}
+
+impl Default for FeePayment {
+    fn default() -> Self {
+        FeePayment::Native
+    }
+}
+
+impl FeePayment {
+    /// The asset the fees are paid in, `None` for the native token.
+    pub fn asset(&self) -> Option<CurrencyId> {
+        match self {
+            FeePayment::Native | FeePayment::Asset(None) => None,
+            FeePayment::Asset(asset) => *asset,
+        }
+    }
+}
+
+impl FromStr for FeePayment {
+    type Err = Error;
+
+    /// Parse `native`, `asset` or `asset:<currency>`, e.g. `asset:ksm`.
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let src_lower = src.to_lowercase();
+        let mut parts = src_lower.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("native"), None) => Ok(FeePayment::Native),
+            (Some("asset"), None) => Ok(FeePayment::Asset(None)),
+            (Some("asset"), Some(currency)) => Ok(FeePayment::Asset(Some(currency.parse()?))),
+            _ => Err(Error::UnknownFeePayment(src.to_string())),
+        }
+    }
+}
+
+/// Signed extension paying the fee and tip, encoded as the transaction-payment extension
+/// (the compact tip) or as the charge-asset-tx-payment extension (the compact tip followed
+/// by the optional asset) depending on the fee payment. The runtime only accepts the
+/// encoding of the extension it includes.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ChargeFee<T: Balances> {
+    tip: T::Balance,
+    fee_payment: FeePayment,
+}
+
+impl<T: Balances> Encode for ChargeFee<T> {
+    fn encode_to<W: Output + ?Sized>(&self, dest: &mut W) {
+        ChargeTransactionPayment::<T>(self.tip).encode_to(dest);
+        if let FeePayment::Asset(asset) = self.fee_payment {
+            asset.encode_to(dest);
+        }
+    }
+}
+
+impl<T: Balances> Decode for ChargeFee<T> {
+    /// Decodes the encoding of the transaction-payment extension, the encoding alone does
+    /// not tell which of the extensions it belongs to.
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        Ok(ChargeFee {
+            tip: ChargeTransactionPayment::<T>::decode(input)?.0,
+            fee_payment: FeePayment::Native,
+        })
+    }
+}
+
+impl<T: Balances + Clone + Debug + Eq + Send + Sync> SignedExtension for ChargeFee<T> {
+    const IDENTIFIER: &'static str = "ChargeFee";
+    type AccountId = T::AccountId;
+    type Call = ();
+    type AdditionalSigned = ();
+    type Pre = ();
+
+    fn additional_signed(&self) -> Result<Self::AdditionalSigned, TransactionValidityError> {
+        Ok(())
+    }
+}
+
+/// Tip paid for urgent extrinsics, limited by a total budget.
+#[derive(Clone, Debug, Default)]
+pub struct TipBudget {
+    tip: u128,
+    max_total: Option<u128>,
+    spent: Arc<Mutex<u128>>,
+}
+
+impl TipBudget {
+    pub fn new(tip: u128, max_total: Option<u128>) -> Self {
+        Self {
+            tip,
+            max_total,
+            spent: Default::default(),
+        }
+    }
+
+    /// Total amount of tips paid so far.
+    pub fn spent(&self) -> u128 {
+        *self.spent.lock().expect("poisoned")
+    }
+
+    /// Reserve the tip for the next extrinsic, zero if the budget is exhausted.
+    pub(crate) fn reserve(&self) -> u128 {
+        let mut spent = self.spent.lock().expect("poisoned");
+        match spent.checked_add(self.tip) {
+            Some(total) if self.max_total.map_or(true, |max_total| total <= max_total) => {
+                *spent = total;
+                self.tip
+            }
+            _ => {
+                log::warn!("Tip budget exhausted, submitting without tip");
+                0
+            }
+        }
+    }
+
+    /// Return a reserved tip that was not paid, e.g. because the extrinsic failed.
+    pub(crate) fn refund(&self, tip: u128) {
+        let mut spent = self.spent.lock().expect("poisoned");
+        *spent = spent.saturating_sub(tip);
+    }
+}
+
+/// Same as the default signed extra, except the tip is taken from the task-local
+/// set by `with_tip` (zero otherwise) and the fee is paid as set by `with_fee_payment`.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Debug)]
+pub struct InterBtcExtra<T: System> {
+    spec_version: u32,
+    tx_version: u32,
+    nonce: T::Index,
+    genesis_hash: T::Hash,
+    tip: u128,
+    fee_payment: FeePayment,
+}
+
+impl<T: System + Balances + Clone + Debug + Eq + Send + Sync> SignedExtra<T> for InterBtcExtra<T>
+where
+    <T as Balances>::Balance: From<u128>,
+{
+    type Extra = (
+        CheckSpecVersion<T>,
+        CheckTxVersion<T>,
+        CheckGenesis<T>,
+        CheckEra<T>,
+        CheckNonce<T>,
+        CheckWeight<T>,
+        ChargeFee<T>,
+    );
+
+    fn new(spec_version: u32, tx_version: u32, nonce: T::Index, genesis_hash: T::Hash) -> Self {
+        InterBtcExtra {
+            spec_version,
+            tx_version,
+            nonce,
+            genesis_hash,
+            tip: current_tip(),
+            fee_payment: current_fee_payment(),
+        }
+    }
+
+    fn extra(&self) -> Self::Extra {
+        (
+            CheckSpecVersion(PhantomData, self.spec_version),
+            CheckTxVersion(PhantomData, self.tx_version),
+            CheckGenesis(PhantomData, self.genesis_hash),
+            CheckEra((EXTRINSIC_ERA, PhantomData), self.genesis_hash),
+            CheckNonce(self.nonce),
+            CheckWeight(PhantomData),
+            ChargeFee {
+                tip: self.tip.into(),
+                fee_payment: self.fee_payment,
+            },
+        )
+    }
+}
+
+impl<T: System + Balances + Clone + Debug + Eq + Send + Sync> SignedExtension for InterBtcExtra<T>
+where
+    <T as Balances>::Balance: From<u128>,
+{
+    const IDENTIFIER: &'static str = "InterBtcExtra";
+    type AccountId = T::AccountId;
+    type Call = ();
+    type AdditionalSigned = <<Self as SignedExtra<T>>::Extra as SignedExtension>::AdditionalSigned;
+    type Pre = ();
+
+    fn additional_signed(&self) -> Result<Self::AdditionalSigned, TransactionValidityError> {
+        self.extra().additional_signed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InterBtcRuntime;
+
+    #[test]
+    fn test_parse_fee_payment() {
+        assert_eq!("native".parse::<FeePayment>().unwrap(), FeePayment::Native);
+        assert_eq!("Asset".parse::<FeePayment>().unwrap(), FeePayment::Asset(None));
+        assert_eq!(
+            "asset:ksm".parse::<FeePayment>().unwrap(),
+            FeePayment::Asset(Some(CurrencyId::KSM))
+        );
+        assert!("asset:foo".parse::<FeePayment>().is_err());
+        assert!("native:dot".parse::<FeePayment>().is_err());
+    }
+
+    #[test]
+    fn test_encode_charge_fee() {
+        let charge = |fee_payment| ChargeFee::<InterBtcRuntime> { tip: 1, fee_payment };
+        assert_eq!(charge(FeePayment::Native).encode(), vec![4]);
+        assert_eq!(charge(FeePayment::Asset(None)).encode(), vec![4, 0]);
+        assert_eq!(
+            charge(FeePayment::Asset(Some(CurrencyId::KSM))).encode(),
+            vec![4, 1, CurrencyId::KSM as u8]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{BlockNumber, BtcAddress, H256Le};
+use serde::Serialize;
+use sp_core::H256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoricalRequestKind {
+    Issue,
+    Redeem,
+    Replace,
+}
+
+/// Part played by the account in a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestRole {
+    /// Requested the issue or redeem.
+    User,
+    /// Vault of the issue or redeem.
+    Vault,
+    /// Vault whose tokens are replaced.
+    OldVault,
+    /// Vault taking over the tokens.
+    NewVault,
+}
+
+/// Status of a request, common to all kinds of requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoricalStatus {
+    Pending,
+    Completed,
+    Cancelled,
+    /// Redeem that failed and was reimbursed in collateral, `true` if the tokens were burned.
+    Reimbursed(bool),
+    /// Redeem that failed and was retried with another vault.
+    Retried,
+}
+
+/// Request that involves an account, as stored on the parachain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalRequest {
+    pub id: H256,
+    pub kind: HistoricalRequestKind,
+    pub role: RequestRole,
+    /// Amount of bitcoin to be transferred, in satoshis.
+    pub amount: u128,
+    /// Address to which the bitcoin are to be transferred.
+    pub btc_address: BtcAddress,
+    /// Block at which the request was opened, or accepted for replace requests.
+    pub opentime: BlockNumber,
+    pub status: HistoricalStatus,
+    /// Bitcoin transactions paying for the request, see `link_payments`.
+    pub btc_txids: Vec<H256Le>,
+}
+
+/// Bitcoin transaction that may pay for requests. The parachain does not store the payments
+/// of requests, so they are matched against transactions from the bitcoin chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BtcPayment {
+    pub txid: H256Le,
+    /// Request id in the OP_RETURN output of the transaction, if any.
+    pub op_return: Option<H256>,
+    /// Addresses of the outputs of the transaction.
+    pub output_addresses: Vec<BtcAddress>,
+}
+
+impl HistoricalRequest {
+    /// Whether the transaction pays for this request: redeems and replaces commit to the
+    /// request id in an OP_RETURN output, issues are paid to a deposit address of their own.
+    pub fn is_paid_by(&self, payment: &BtcPayment) -> bool {
+        match self.kind {
+            HistoricalRequestKind::Issue => payment.output_addresses.contains(&self.btc_address),
+            HistoricalRequestKind::Redeem | HistoricalRequestKind::Replace => {
+                payment.op_return == Some(self.id) && payment.output_addresses.contains(&self.btc_address)
+            }
+        }
+    }
+}
+
+/// Link the transactions to the requests they pay for.
+pub fn link_payments(history: &mut [HistoricalRequest], payments: &[BtcPayment]) {
+    for request in history.iter_mut() {
+        for payment in payments.iter().filter(|payment| request.is_paid_by(payment)) {
+            if !request.btc_txids.contains(&payment.txid) {
+                request.btc_txids.push(payment.txid);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H160;
+
+    fn request(kind: HistoricalRequestKind, id: u64, btc_address: BtcAddress) -> HistoricalRequest {
+        HistoricalRequest {
+            id: H256::from_low_u64_be(id),
+            kind,
+            role: RequestRole::Vault,
+            amount: 1000,
+            btc_address,
+            opentime: 1,
+            status: HistoricalStatus::Completed,
+            btc_txids: vec![],
+        }
+    }
+
+    #[test]
+    fn test_link_payments() {
+        let (deposit, redeemer) = (
+            BtcAddress::P2WPKHv0(H160::from_low_u64_be(1)),
+            BtcAddress::P2WPKHv0(H160::from_low_u64_be(2)),
+        );
+        let mut history = vec![
+            request(HistoricalRequestKind::Issue, 1, deposit),
+            request(HistoricalRequestKind::Redeem, 2, redeemer),
+            request(HistoricalRequestKind::Redeem, 3, redeemer),
+        ];
+        let payments = vec![
+            BtcPayment {
+                txid: H256Le::from_bytes_le(&[1; 32]),
+                op_return: None,
+                output_addresses: vec![deposit],
+            },
+            BtcPayment {
+                txid: H256Le::from_bytes_le(&[2; 32]),
+                op_return: Some(H256::from_low_u64_be(2)),
+                output_addresses: vec![redeemer],
+            },
+        ];
+        link_payments(&mut history, &payments);
+        link_payments(&mut history, &payments);
+
+        assert_eq!(history[0].btc_txids, vec![H256Le::from_bytes_le(&[1; 32])]);
+        assert_eq!(history[1].btc_txids, vec![H256Le::from_bytes_le(&[2; 32])]);
+        // paid to the same address, but committed to another request
+        assert!(history[2].btc_txids.is_empty());
+    }
+}
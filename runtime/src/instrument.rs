@@ -0,0 +1,114 @@
+use futures::Future;
+use lazy_static::lazy_static;
+use prometheus::{HistogramOpts, HistogramVec};
+use std::{fmt::Display, time::Instant};
+use substrate_subxt::Store;
+use tracing::Instrument;
+
+use crate::InterBtcRuntime;
+
+lazy_static! {
+    pub static ref CALL_LATENCY: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "parachain_call_latency_seconds",
+            "Time taken by storage queries, custom RPCs and extrinsic submissions, by pallet and call"
+        )
+        .buckets(vec![0.01, 0.05, 0.1, 0.5, 1.0, 3.0, 6.0, 12.0, 30.0, 60.0, 120.0]),
+        &["pallet", "call", "kind"]
+    )
+    .expect("Failed to create metric");
+}
+
+/// Kind of a parachain call, submissions include the wait for inclusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CallKind {
+    Query,
+    Rpc,
+    Submission,
+}
+
+impl CallKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CallKind::Query => "query",
+            CallKind::Rpc => "rpc",
+            CallKind::Submission => "submission",
+        }
+    }
+}
+
+/// Runs the call in a span named after the pallet and call, and records its latency
+/// (successful or not) in `CALL_LATENCY`.
+pub(crate) async fn instrument<F, T, E>(
+    kind: CallKind,
+    pallet: &'static str,
+    call: &'static str,
+    future: F,
+) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let span = tracing::debug_span!("parachain_call", kind = kind.as_str(), pallet, call);
+    let start = Instant::now();
+    let result = future.instrument(span.clone()).await;
+    let elapsed = start.elapsed();
+    CALL_LATENCY
+        .with_label_values(&[pallet, call, kind.as_str()])
+        .observe(elapsed.as_secs_f64());
+    span.in_scope(|| match &result {
+        Ok(_) => tracing::trace!(elapsed_ms = elapsed.as_millis() as u64, "Completed"),
+        Err(err) => tracing::debug!(elapsed_ms = elapsed.as_millis() as u64, "Failed: {}", err),
+    });
+    result
+}
+
+/// Shorthand of `instrument` for queries of the storage item `S`.
+pub(crate) async fn query<S, F, T, E>(future: F) -> Result<T, E>
+where
+    S: Store<InterBtcRuntime>,
+    F: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    instrument(CallKind::Query, S::MODULE, S::FIELD, future).await
+}
+
+/// Shorthand of `instrument` for custom RPCs, labelled by the pallet and method of their
+/// `pallet_method` name.
+pub(crate) async fn rpc<F, T, E>(method: &'static str, future: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let (pallet, call) = match method.find('_') {
+        Some(index) => (&method[..index], &method[index + 1..]),
+        None => ("", method),
+    };
+    instrument(CallKind::Rpc, pallet, call, future).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::btc_relay::BestBlockStore;
+
+    #[tokio::test]
+    async fn test_records_latency_by_call() {
+        let count = || {
+            CALL_LATENCY
+                .with_label_values(&["BTCRelay", "best_block", "query"])
+                .get_sample_count()
+        };
+        let before = count();
+        assert_eq!(
+            query::<BestBlockStore<InterBtcRuntime>, _, _, _>(async { Ok::<_, String>(1) }).await,
+            Ok(1)
+        );
+        assert!(
+            query::<BestBlockStore<InterBtcRuntime>, _, _, _>(async { Err::<u32, _>("failed".to_string()) })
+                .await
+                .is_err()
+        );
+        assert_eq!(count(), before + 2);
+    }
+}
@@ -6,17 +6,14 @@ use crate::{rpc::StakedRelayerPallet, BtcAddress, BtcRelayPallet, InterBtcParach
 use async_trait::async_trait;
 use bitcoin::{
     secp256k1::{rand::rngs::OsRng, PublicKey, Secp256k1, SecretKey},
-    serialize, BitcoinCoreApi, Block, BlockHash, BlockHeader, Error as BitcoinError, GetBlockResult, Hash,
-    LockedTransaction, Network, OutPoint, PartialAddress, PartialMerkleTree, PrivateKey, Script, Transaction,
-    TransactionMetadata, TxIn, TxOut, Txid, Uint256, PUBLIC_KEY_SIZE,
+    serialize, BitcoinCoreApi, Block, BlockHash, BlockHeader, Error as BitcoinError, FeeEstimation, GetBlockResult,
+    Hash, LockedTransaction, Network, OutPoint, OutputMetadata, PartialAddress, PartialMerkleTree, PrivateKey, Script,
+    Transaction, TransactionMetadata, TxIn, TxOut, Txid, Uint256, PUBLIC_KEY_SIZE,
 };
 use rand::{thread_rng, Rng};
 use sp_core::{H160, H256, U256};
 use std::{convert::TryInto, sync::Arc, time::Duration};
-use tokio::{
-    sync::{Mutex, RwLock},
-    time::delay_for,
-};
+use tokio::{sync::RwLock, time::delay_for};
 
 /// A simulated bitcoin-core interface. It combines the roles of bitcoin-core and the
 /// staked relayer: it automatically relays the generated transactions to the parachain.
@@ -28,7 +25,6 @@ pub struct MockBitcoinCore {
     parachain_rpc: InterBtcParachain,
     blocks: Arc<RwLock<Vec<Block>>>,
     mempool: Arc<RwLock<Vec<Transaction>>>,
-    transaction_creation_lock: Arc<Mutex<()>>,
 }
 
 impl MockBitcoinCore {
@@ -38,7 +34,6 @@ impl MockBitcoinCore {
             parachain_rpc,
             blocks: Arc::new(RwLock::new(vec![])),
             mempool: Arc::new(RwLock::new(vec![])),
-            transaction_creation_lock: Arc::new(Mutex::new(())),
         };
 
         let address = BtcAddress::P2PKH(H160::from([0; 20]));
@@ -68,7 +63,6 @@ impl MockBitcoinCore {
             parachain_rpc,
             blocks: Arc::new(RwLock::new(vec![])),
             mempool: Arc::new(RwLock::new(vec![])),
-            transaction_creation_lock: Arc::new(Mutex::new(())),
         }
     }
 
@@ -333,6 +327,16 @@ impl BitcoinCoreApi for MockBitcoinCore {
         let transactions = (*self.mempool.read().await).clone();
         Ok(Box::new(transactions.into_iter().map(Ok)))
     }
+    async fn is_transaction_live(&self, txid: Txid) -> Result<bool, BitcoinError> {
+        let in_mempool = self.mempool.read().await.iter().any(|tx| tx.txid() == txid);
+        let in_block = self
+            .blocks
+            .read()
+            .await
+            .iter()
+            .any(|block| block.txdata.iter().any(|tx| tx.txid() == txid));
+        Ok(in_mempool || in_block)
+    }
     async fn wait_for_transaction_metadata(
         &self,
         txid: Txid,
@@ -351,19 +355,25 @@ impl BitcoinCoreApi for MockBitcoinCore {
         let proof = self.get_proof(txid, &block_hash).await.unwrap();
         let raw_tx = self.get_raw_tx(&txid, &block_hash).await.unwrap();
 
+        // the simulated wallet makes no change outputs
+        let outputs = OutputMetadata::new(&block.txdata[1], |_, _| false);
+
         Ok(TransactionMetadata {
             block_hash,
             proof,
             raw_tx,
             txid,
             block_height: block_height as u32,
+            outputs,
         })
     }
-    async fn create_transaction<A: PartialAddress + Send + Sync + 'static>(
+    async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
         &self,
         address: A,
         sat: u64,
         request_id: Option<H256>,
+        // no fees are paid on the simulated chain
+        _fee_estimation: FeeEstimation,
     ) -> Result<LockedTransaction, BitcoinError> {
         let mut transaction = MockBitcoinCore::generate_normal_transaction(&address, sat);
 
@@ -378,11 +388,7 @@ impl BitcoinCoreApi for MockBitcoinCore {
             transaction.output.push(op_return);
         }
 
-        Ok(LockedTransaction::new(
-            transaction,
-            Default::default(),
-            Some(self.transaction_creation_lock.clone().lock_owned().await),
-        ))
+        Ok(LockedTransaction::new(transaction, Default::default(), None))
     }
     async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError> {
         let block = self.generate_block_with_transaction(&transaction.transaction).await;
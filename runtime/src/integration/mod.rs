@@ -4,7 +4,7 @@ mod bitcoin_simulator;
 
 use crate::{
     rpc::{IssuePallet, VaultRegistryPallet},
-    AccountId, BtcRelayPallet, H256Le, InterBtcParachain, InterBtcRuntime,
+    AccountId, BtcRelayPallet, CollateralAmount, H256Le, InterBtcParachain, InterBtcRuntime, WrappedAmount,
 };
 use bitcoin::{BitcoinCoreApi, BlockHash, Txid};
 use futures::{
@@ -99,7 +99,14 @@ pub async fn assert_issue(
     vault_id: &AccountId,
     amount: u128,
 ) {
-    let issue = parachain_rpc.request_issue(amount, vault_id, 10000).await.unwrap();
+    let issue = parachain_rpc
+        .request_issue(
+            WrappedAmount::from_planck(amount),
+            vault_id,
+            CollateralAmount::from_planck(10000),
+        )
+        .await
+        .unwrap();
 
     let metadata = btc_rpc
         .send_to_address(issue.vault_btc_address, (issue.amount_btc + issue.fee) as u64, None, 0)
@@ -113,8 +120,14 @@ pub async fn assert_issue(
 }
 
 /// calculate how much collateral the vault requires to accept an issue of the given size
-pub async fn get_required_vault_collateral_for_issue(parachain_rpc: &InterBtcParachain, amount: u128) -> u128 {
-    parachain_rpc.get_required_collateral_for_wrapped(amount).await.unwrap()
+pub async fn get_required_vault_collateral_for_issue(
+    parachain_rpc: &InterBtcParachain,
+    amount: u128,
+) -> CollateralAmount {
+    parachain_rpc
+        .get_required_collateral_for_wrapped(WrappedAmount::from_planck(amount))
+        .await
+        .unwrap()
 }
 
 /// wait for an event to occur. After the specified error, this will panic. This returns the event.
@@ -1,10 +1,28 @@
 pub mod cli;
 pub mod pallets;
 
+mod amount;
+mod balance_guard;
+mod balances;
+mod blocks;
 mod conn;
+mod drift;
+mod dry_run;
 mod error;
+mod event_decoders;
+mod extra;
+mod history;
+mod instrument;
+mod liquidation;
+mod metadata;
+mod network;
+mod pagination;
+mod pool_conflict;
+mod read_only;
+mod receipt;
 mod retry;
 mod rpc;
+mod staleness;
 mod types;
 
 #[cfg(test)]
@@ -13,16 +31,36 @@ mod tests;
 #[cfg(feature = "testing-utils")]
 pub mod integration;
 
+pub use amount::{Amount, CollateralAmount, Currency, Dot, InterBtc, Ksm, WrappedAmount};
+pub use balance_guard::BalanceGuard;
+pub use balances::{AccountBalance, BalanceCache, BalanceChange, BalanceSubscription, BalanceUpdate};
+pub use blocks::{BLOCK_LATENCY, CHAIN_LAG, MISSED_BLOCKS};
+pub use conn::{WsClientOptions, DEFAULT_MAX_MESSAGE_SIZE};
+pub use drift::{TimestampDrift, TIMESTAMP_DRIFT};
+pub use dry_run::{DryRunResult, DRY_RUN_FAILURES, EXTRINSIC_FEES};
 pub use error::{Error, SubxtError};
+pub use extra::{urgent, with_remark, FeePayment, InterBtcExtra, TipBudget, TIPS_SPENT};
+pub use history::{link_payments, BtcPayment, HistoricalRequest, HistoricalRequestKind, HistoricalStatus, RequestRole};
+pub use instrument::CALL_LATENCY;
+pub use liquidation::LiquidationVault;
+pub use metadata::{KnownRuntime, KNOWN_RUNTIMES, REQUIRED_MODULES};
+pub use network::{NetworkArg, NetworkProfile, DEFAULT_PARACHAIN_URL, NETWORK_PROFILES};
+pub use pagination::{StoragePages, DEFAULT_STORAGE_PAGE_SIZE};
 pub use pallets::*;
-pub use retry::{notify_retry, RetryPolicy};
+pub use pool_conflict::POOL_CONFLICTS;
+pub use read_only::ReadOnlyParachainRpc;
+pub use receipt::{CallId, SubmissionReceipt};
+pub use retry::{ErrorClass, CALL_RETRIES};
+#[cfg(feature = "testnet-utils")]
+pub use rpc::TestnetUtils;
 pub use rpc::{
     BtcRelayPallet, BtcTxFeesPerByte, CollateralBalancesPallet, ExchangeRateOraclePallet, FeePallet, InterBtcParachain,
-    IssuePallet, RedeemPallet, RefundPallet, ReplacePallet, SecurityPallet, StakedRelayerPallet, TimestampPallet,
-    UtilFuncs, VaultRegistryPallet,
+    IssuePallet, LiquidationPallet, RedeemPallet, RefundPallet, ReplacePallet, RequestHistory, SecurityPallet,
+    StakedRelayerPallet, TimestampPallet, UtilFuncs, VaultRegistryPallet,
 };
 pub use sp_arithmetic::{traits as FixedPointTraits, FixedI128, FixedPointNumber, FixedU128};
 pub use sp_runtime;
+pub use staleness::OracleStaleness;
 pub use substrate_subxt;
 pub use types::*;
 
@@ -33,10 +71,9 @@ use sp_runtime::{
     traits::{BlakeTwo256, IdentifyAccount, Verify},
     MultiSignature, OpaqueExtrinsic,
 };
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, str::FromStr};
 use substrate_subxt::{
-    balances, extrinsic::DefaultExtra, register_default_type_sizes, sudo, system, system::SystemEventTypeRegistry,
-    EventTypeRegistry, Runtime,
+    balances, register_default_type_sizes, sudo, system, system::SystemEventTypeRegistry, EventTypeRegistry, Runtime,
 };
 
 // cumulus / polkadot types
@@ -59,7 +96,7 @@ pub struct InterBtcRuntime;
 
 impl Runtime for InterBtcRuntime {
     type Signature = MultiSignature;
-    type Extra = DefaultExtra<Self>;
+    type Extra = InterBtcExtra<Self>;
 
     fn register_type_sizes(registry: &mut EventTypeRegistry<Self>) {
         registry.with_core();
@@ -90,6 +127,33 @@ pub enum CurrencyId {
     INTERBTC,
 }
 
+impl FromStr for CurrencyId {
+    type Err = Error;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src.to_uppercase().as_str() {
+            "DOT" => Ok(CurrencyId::DOT),
+            "KSM" => Ok(CurrencyId::KSM),
+            "INTERBTC" => Ok(CurrencyId::INTERBTC),
+            _ => Err(Error::UnknownCurrency(src.to_string())),
+        }
+    }
+}
+
+/// Feed for which the authorized oracles submit values.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OracleKey {
+    ExchangeRate(CurrencyId),
+    FeeEstimation,
+}
+
+/// Currency in which vaults lock collateral and fees are paid.
+pub const COLLATERAL_CURRENCY: CurrencyId = CurrencyId::DOT;
+/// Currency in which the fees of extrinsics are paid.
+pub const FEE_CURRENCY: CurrencyId = CurrencyId::DOT;
+/// Currency issued against the locked bitcoin.
+pub const WRAPPED_CURRENCY: CurrencyId = CurrencyId::INTERBTC;
+
 // TODO: use types from actual runtime
 impl system::System for InterBtcRuntime {
     type Index = Index;
@@ -170,13 +234,19 @@ impl sla::Sla for InterBtcRuntime {}
 impl utility::Utility for InterBtcRuntime {}
 
 pub const BTC_RELAY_MODULE: &str = "BTCRelay";
+pub const EXCHANGE_RATE_ORACLE_MODULE: &str = "ExchangeRateOracle";
 pub const ISSUE_MODULE: &str = "Issue";
 pub const REDEEM_MODULE: &str = "Redeem";
+pub const REPLACE_MODULE: &str = "Replace";
+pub const TOKENS_MODULE: &str = "Tokens";
 
 pub const STABLE_BITCOIN_CONFIRMATIONS: &str = "StableBitcoinConfirmations";
 pub const STABLE_PARACHAIN_CONFIRMATIONS: &str = "StableParachainConfirmations";
+pub const EXISTENTIAL_DEPOSIT: &str = "ExistentialDeposit";
 
 pub const DUPLICATE_BLOCK_ERROR: &str = "DuplicateBlock";
 pub const INVALID_CHAIN_ID_ERROR: &str = "InvalidChainID";
 pub const ISSUE_COMPLETED_ERROR: &str = "IssueCompleted";
+pub const REDEEM_COMPLETED_ERROR: &str = "RedeemCompleted";
+pub const REPLACE_COMPLETED_ERROR: &str = "ReplaceCompleted";
 pub const COMMIT_PERIOD_EXPIRED_ERROR: &str = "CommitPeriodExpired";
@@ -0,0 +1,49 @@
+use sp_core::U256;
+
+/// State of the liquidation vault, which takes over the tokens and the collateral of
+/// liquidated vaults. Users can burn tokens against it for a share of the collateral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LiquidationVault {
+    pub issued_tokens: u128,
+    pub to_be_issued_tokens: u128,
+    pub to_be_redeemed_tokens: u128,
+    /// Collateral held by the liquidation vault.
+    pub collateral: u128,
+}
+
+impl LiquidationVault {
+    /// Tokens that can currently be burned against the liquidation vault.
+    pub fn redeemable_tokens(&self) -> u128 {
+        self.issued_tokens.saturating_sub(self.to_be_redeemed_tokens)
+    }
+
+    /// Collateral received for burning `amount` tokens, the collateral is shared among all
+    /// tokens backed by the liquidation vault, including those still to be issued.
+    pub fn collateral_for(&self, amount: u128) -> u128 {
+        let backed = self.issued_tokens.saturating_add(self.to_be_issued_tokens);
+        if backed == 0 {
+            return 0;
+        }
+        let amount = amount.min(backed);
+        (U256::from(self.collateral) * U256::from(amount) / U256::from(backed)).low_u128()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liquidation_vault() {
+        let liquidation_vault = LiquidationVault {
+            issued_tokens: 80,
+            to_be_issued_tokens: 20,
+            to_be_redeemed_tokens: 30,
+            collateral: 1_000,
+        };
+        assert_eq!(liquidation_vault.redeemable_tokens(), 50);
+        assert_eq!(liquidation_vault.collateral_for(10), 100);
+        assert_eq!(liquidation_vault.collateral_for(1_000), 1_000);
+        assert_eq!(LiquidationVault::default().collateral_for(10), 0);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::Error;
+use serde::Deserialize;
+use sp_core::H256;
+use std::{fmt, str::FromStr};
+use substrate_subxt::Metadata;
+
+/// Runtime that the clients have been built against. A single build supports all
+/// known runtimes, the one in use is selected by the spec name of the connected chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownRuntime {
+    /// Name of the network, used for logging and to select the expected network.
+    pub network: &'static str,
+    pub spec_name: &'static str,
+    pub min_spec_version: u32,
+    /// Set once a runtime upgrade breaks compatibility with this build.
+    pub max_spec_version: Option<u32>,
+    /// Hash of the genesis block, not checked if `None` (e.g. for testnets that are reset).
+    pub genesis_hash: Option<&'static str>,
+    pub ss58_prefix: u16,
+    /// Decimals of the native token, in which fees are paid.
+    pub token_decimals: u8,
+}
+
+pub const KNOWN_RUNTIMES: &[KnownRuntime] = &[
+    KnownRuntime {
+        network: "interlay",
+        spec_name: "interlay-parachain",
+        min_spec_version: 1,
+        max_spec_version: None,
+        genesis_hash: Some("0xbf88efe70e9e0e916416e8bed61f2b45717f517d7f3523e33c7b001e5ffcbc72"),
+        ss58_prefix: 2032,
+        token_decimals: 10,
+    },
+    KnownRuntime {
+        network: "kintsugi",
+        spec_name: "kintsugi-parachain",
+        min_spec_version: 1,
+        max_spec_version: None,
+        genesis_hash: Some("0x9af9a64e6e4da8e3073901c3ff0cc4c3aad9563786d89daf6ad820b6e14a0b8b"),
+        ss58_prefix: 2092,
+        token_decimals: 12,
+    },
+    KnownRuntime {
+        network: "testnet",
+        spec_name: "btc-parachain",
+        min_spec_version: 1,
+        max_spec_version: None,
+        genesis_hash: None,
+        ss58_prefix: 42,
+        token_decimals: 10,
+    },
+];
+
+/// Modules the clients submit calls to, subscribe to or read storage from.
+/// These must be present in the metadata of every supported runtime.
+pub const REQUIRED_MODULES: &[&str] = &[
+    "BTCRelay",
+    "ExchangeRateOracle",
+    "Fee",
+    "Issue",
+    "Redeem",
+    "Refund",
+    "Replace",
+    "Security",
+    "Sla",
+    "StakedRelayers",
+    "Timestamp",
+    "Tokens",
+    "Utility",
+    "VaultRegistry",
+];
+
+/// Subset of the response of `state_getRuntimeVersion`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RuntimeVersion {
+    pub spec_name: String,
+    pub spec_version: u32,
+}
+
+/// Decimals of the chain tokens, a list if the chain has several.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum TokenDecimals {
+    Single(u8),
+    Multiple(Vec<u8>),
+}
+
+/// Subset of the response of `system_properties`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SystemProperties {
+    pub ss58_format: Option<u16>,
+    pub token_decimals: Option<TokenDecimals>,
+}
+
+impl SystemProperties {
+    /// Decimals of the native token, which is listed first.
+    fn native_token_decimals(&self) -> Option<u8> {
+        match &self.token_decimals {
+            Some(TokenDecimals::Single(decimals)) => Some(*decimals),
+            Some(TokenDecimals::Multiple(decimals)) => decimals.first().copied(),
+            None => None,
+        }
+    }
+}
+
+impl KnownRuntime {
+    fn supports(&self, spec_version: u32) -> bool {
+        spec_version >= self.min_spec_version && self.max_spec_version.map_or(true, |max| spec_version <= max)
+    }
+}
+
+fn find_runtime(spec_name: &str, spec_version: u32) -> Option<KnownRuntime> {
+    KNOWN_RUNTIMES
+        .iter()
+        .find(|runtime| runtime.spec_name == spec_name && runtime.supports(spec_version))
+        .copied()
+}
+
+/// Select the known runtime matching the connected chain, failing if the chain runs a
+/// runtime this build has not been built against or if its metadata lacks a required module.
+pub(crate) fn select_runtime(version: &RuntimeVersion, metadata: &Metadata) -> Result<KnownRuntime, Error> {
+    let runtime = find_runtime(&version.spec_name, version.spec_version)
+        .ok_or_else(|| Error::UnknownRuntime(version.spec_name.clone(), version.spec_version))?;
+
+    for module in REQUIRED_MODULES {
+        metadata
+            .module(module)
+            .map_err(|_| Error::MissingModule(module.to_string()))?;
+    }
+
+    Ok(runtime)
+}
+
+/// Fail if the chain upgraded to a runtime this build does not support, i.e. another runtime
+/// or a spec version outside the supported range of the selected one.
+pub(crate) fn expect_supported(runtime: &KnownRuntime, version: &RuntimeVersion) -> Result<(), Error> {
+    if version.spec_name == runtime.spec_name && runtime.supports(version.spec_version) {
+        Ok(())
+    } else {
+        Err(Error::UnknownRuntime(version.spec_name.clone(), version.spec_version))
+    }
+}
+
+fn check_property<T: PartialEq + fmt::Debug>(
+    runtime: &KnownRuntime,
+    property: &'static str,
+    expected: T,
+    actual: Option<T>,
+) -> Result<(), Error> {
+    match actual {
+        Some(actual) if actual != expected => Err(Error::ChainPropertyMismatch {
+            network: runtime.network,
+            property,
+            expected: format!("{:?}", expected),
+            actual: format!("{:?}", actual),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Verify that the connected chain is the network of the selected runtime, so that a chain
+/// running the same runtime under a different genesis or token is not mistaken for it.
+/// Properties the chain does not report are not checked.
+pub(crate) fn validate_chain(
+    runtime: &KnownRuntime,
+    genesis_hash: H256,
+    properties: &SystemProperties,
+) -> Result<(), Error> {
+    if let Some(expected) = runtime.genesis_hash {
+        expect_genesis(runtime, expected, genesis_hash)?;
+    }
+    check_property(runtime, "ss58 prefix", runtime.ss58_prefix, properties.ss58_format)?;
+    check_property(
+        runtime,
+        "token decimals",
+        runtime.token_decimals,
+        properties.native_token_decimals(),
+    )?;
+    Ok(())
+}
+
+/// Fail if the genesis hash of the connected chain is not the expected one.
+pub(crate) fn expect_genesis(runtime: &KnownRuntime, expected: &str, genesis_hash: H256) -> Result<(), Error> {
+    let expected = H256::from_str(expected.trim_start_matches("0x")).expect("known genesis hash is valid; qed");
+    check_property(runtime, "genesis hash", expected, Some(genesis_hash))
+}
+
+/// Fail if the connected chain is not the network the client was configured for, e.g. to
+/// stop a vault configured for Kintsugi from running against Interlay.
+pub(crate) fn expect_network(runtime: &KnownRuntime, network: &str) -> Result<(), Error> {
+    if runtime.network == network {
+        Ok(())
+    } else if KNOWN_RUNTIMES.iter().any(|known| known.network == network) {
+        Err(Error::NetworkMismatch {
+            expected: network.to_string(),
+            actual: runtime.network,
+        })
+    } else {
+        Err(Error::UnknownNetwork(network.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_known_runtime() {
+        assert_eq!(
+            find_runtime("kintsugi-parachain", 1).map(|runtime| runtime.network),
+            Some("kintsugi")
+        );
+        assert_eq!(find_runtime("kintsugi-parachain", 0), None);
+        assert_eq!(find_runtime("unknown-parachain", 1), None);
+    }
+
+    #[test]
+    fn test_supports_spec_version_range() {
+        let runtime = KnownRuntime {
+            network: "test",
+            spec_name: "test",
+            min_spec_version: 2,
+            max_spec_version: Some(4),
+            genesis_hash: None,
+            ss58_prefix: 42,
+            token_decimals: 10,
+        };
+        assert!(!runtime.supports(1));
+        assert!(runtime.supports(2));
+        assert!(runtime.supports(4));
+        assert!(!runtime.supports(5));
+    }
+
+    #[test]
+    fn test_expect_supported() {
+        let runtime = KnownRuntime {
+            max_spec_version: Some(4),
+            ..kintsugi()
+        };
+        let version = |spec_name: &str, spec_version| RuntimeVersion {
+            spec_name: spec_name.to_string(),
+            spec_version,
+        };
+        assert!(expect_supported(&runtime, &version("kintsugi-parachain", 4)).is_ok());
+        assert!(matches!(
+            expect_supported(&runtime, &version("kintsugi-parachain", 5)),
+            Err(Error::UnknownRuntime(_, 5))
+        ));
+        assert!(matches!(
+            expect_supported(&runtime, &version("interlay-parachain", 4)),
+            Err(Error::UnknownRuntime(_, 4))
+        ));
+    }
+
+    fn kintsugi() -> KnownRuntime {
+        find_runtime("kintsugi-parachain", 1).unwrap()
+    }
+
+    #[test]
+    fn test_known_genesis_hashes_are_valid() {
+        for runtime in KNOWN_RUNTIMES {
+            if let Some(genesis_hash) = runtime.genesis_hash {
+                assert!(H256::from_str(genesis_hash.trim_start_matches("0x")).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_chain() {
+        let runtime = kintsugi();
+        let genesis_hash = H256::from_str(runtime.genesis_hash.unwrap().trim_start_matches("0x")).unwrap();
+        let properties: SystemProperties =
+            serde_json::from_str(r#"{"ss58Format": 2092, "tokenDecimals": [12, 8], "tokenSymbol": ["KINT", "KBTC"]}"#)
+                .unwrap();
+        assert!(validate_chain(&runtime, genesis_hash, &properties).is_ok());
+        assert!(matches!(
+            validate_chain(&runtime, H256::zero(), &properties),
+            Err(Error::ChainPropertyMismatch {
+                property: "genesis hash",
+                ..
+            })
+        ));
+
+        let properties: SystemProperties =
+            serde_json::from_str(r#"{"ss58Format": 2032, "tokenDecimals": 12}"#).unwrap();
+        assert!(matches!(
+            validate_chain(&runtime, genesis_hash, &properties),
+            Err(Error::ChainPropertyMismatch {
+                property: "ss58 prefix",
+                ..
+            })
+        ));
+
+        let properties: SystemProperties = serde_json::from_str("{}").unwrap();
+        assert!(validate_chain(&runtime, genesis_hash, &properties).is_ok());
+    }
+
+    #[test]
+    fn test_expect_network() {
+        let runtime = kintsugi();
+        assert!(expect_network(&runtime, "kintsugi").is_ok());
+        assert!(matches!(
+            expect_network(&runtime, "interlay"),
+            Err(Error::NetworkMismatch { .. })
+        ));
+        assert!(matches!(
+            expect_network(&runtime, "polkadot"),
+            Err(Error::UnknownNetwork(_))
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{Currency, Dot, Error, KnownRuntime, Ksm, KNOWN_RUNTIMES};
+use std::str::FromStr;
+
+/// Parachain url used if neither `--btc-parachain-url` nor `--network` is given.
+pub const DEFAULT_PARACHAIN_URL: &str = "ws://127.0.0.1:9944";
+
+/// Named deployment, bundling the settings that need to be consistent across the clients
+/// and their crates: the parachain to connect to and the runtime it must run, and the
+/// bitcoin network and electrs instance matching it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkProfile {
+    pub name: &'static str,
+    pub parachain_url: &'static str,
+    /// Network of the known runtime the parachain must run, which determines the expected
+    /// ss58 prefix and token decimals.
+    pub runtime_network: &'static str,
+    /// Hash of the genesis block of the parachain, not checked if `None` (e.g. for chains that are reset).
+    pub genesis_hash: Option<&'static str>,
+    /// Decimals of the collateral currency the oracle quotes bitcoin in.
+    pub collateral_decimals: u32,
+    /// Bitcoin network, as accepted by `--bitcoin-network`.
+    pub bitcoin_network: &'static str,
+    /// Esplora API of the electrs instance of the network.
+    pub electrs_url: &'static str,
+}
+
+pub const NETWORK_PROFILES: &[NetworkProfile] = &[
+    NetworkProfile {
+        name: "interlay",
+        parachain_url: "wss://api.interlay.io/parachain",
+        runtime_network: "interlay",
+        genesis_hash: Some("0xbf88efe70e9e0e916416e8bed61f2b45717f517d7f3523e33c7b001e5ffcbc72"),
+        collateral_decimals: Dot::DECIMALS,
+        bitcoin_network: "mainnet",
+        electrs_url: "https://api.interlay.io/electrs",
+    },
+    NetworkProfile {
+        name: "kintsugi",
+        parachain_url: "wss://api-kusama.interlay.io/parachain",
+        runtime_network: "kintsugi",
+        genesis_hash: Some("0x9af9a64e6e4da8e3073901c3ff0cc4c3aad9563786d89daf6ad820b6e14a0b8b"),
+        collateral_decimals: Ksm::DECIMALS,
+        bitcoin_network: "mainnet",
+        electrs_url: "https://api-kusama.interlay.io/electrs",
+    },
+    NetworkProfile {
+        name: "testnet",
+        parachain_url: "wss://api-testnet.interlay.io/parachain",
+        runtime_network: "testnet",
+        genesis_hash: None,
+        collateral_decimals: Dot::DECIMALS,
+        bitcoin_network: "testnet",
+        electrs_url: "https://api-testnet.interlay.io/electrs",
+    },
+    NetworkProfile {
+        name: "local",
+        parachain_url: DEFAULT_PARACHAIN_URL,
+        // a local development chain runs the testnet runtime
+        runtime_network: "testnet",
+        genesis_hash: None,
+        collateral_decimals: Dot::DECIMALS,
+        bitcoin_network: "regtest",
+        electrs_url: "http://localhost:3002",
+    },
+];
+
+impl NetworkProfile {
+    /// The known runtime of the parachain of this network.
+    pub fn known_runtime(&self) -> KnownRuntime {
+        *KNOWN_RUNTIMES
+            .iter()
+            .find(|runtime| runtime.network == self.runtime_network)
+            .expect("profiles refer to known runtimes; qed")
+    }
+}
+
+impl FromStr for NetworkProfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(profile) = NETWORK_PROFILES.iter().find(|profile| profile.name == s) {
+            return Ok(*profile);
+        }
+        match s {
+            "mainnet" | "bitcoin" | "regtest" => Err(Error::BitcoinNetworkAsProfile(s.to_string())),
+            _ => Err(Error::UnknownNetwork(s.to_string())),
+        }
+    }
+}
+
+/// Value of `--network`, which selected the bitcoin network of the vault before it selected
+/// the network profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkArg {
+    Profile(NetworkProfile),
+    /// Bitcoin network (mainnet or regtest) given the old way, deprecated in favour of
+    /// `--bitcoin-network`.
+    BitcoinNetwork(&'static str),
+}
+
+impl FromStr for NetworkArg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            // `testnet` is both, the profile is taken since its bitcoin network is testnet as well
+            "testnet" => s.parse().map(NetworkArg::Profile),
+            "mainnet" | "bitcoin" => Ok(NetworkArg::BitcoinNetwork("mainnet")),
+            "regtest" => Ok(NetworkArg::BitcoinNetwork("regtest")),
+            _ => s.parse().map(NetworkArg::Profile),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_profiles() {
+        for profile in NETWORK_PROFILES {
+            assert!(KNOWN_RUNTIMES
+                .iter()
+                .any(|runtime| runtime.network == profile.runtime_network));
+            assert!(matches!(profile.bitcoin_network, "mainnet" | "testnet" | "regtest"));
+            if let Some(genesis_hash) = profile.genesis_hash {
+                assert_eq!(profile.known_runtime().genesis_hash, Some(genesis_hash));
+            }
+        }
+
+        let kintsugi = "kintsugi".parse::<NetworkProfile>().unwrap();
+        assert_eq!(kintsugi.known_runtime().ss58_prefix, 2092);
+        assert_eq!(kintsugi.collateral_decimals, 12);
+        assert_eq!("local".parse::<NetworkProfile>().unwrap().bitcoin_network, "regtest");
+        assert!(matches!(
+            "polkadot".parse::<NetworkProfile>(),
+            Err(Error::UnknownNetwork(_))
+        ));
+        assert!(matches!(
+            "regtest".parse::<NetworkProfile>(),
+            Err(Error::BitcoinNetworkAsProfile(_))
+        ));
+    }
+
+    #[test]
+    fn test_network_arg() {
+        assert_eq!(
+            "kintsugi".parse::<NetworkArg>().unwrap(),
+            NetworkArg::Profile("kintsugi".parse().unwrap())
+        );
+        assert_eq!(
+            "regtest".parse::<NetworkArg>().unwrap(),
+            NetworkArg::BitcoinNetwork("regtest")
+        );
+        assert_eq!(
+            "bitcoin".parse::<NetworkArg>().unwrap(),
+            NetworkArg::BitcoinNetwork("mainnet")
+        );
+        // selects the bitcoin network it used to, and the testnet parachain
+        match "testnet".parse::<NetworkArg>().unwrap() {
+            NetworkArg::Profile(profile) => {
+                assert_eq!(profile.name, "testnet");
+                assert_eq!(profile.bitcoin_network, "testnet");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            "polkadot".parse::<NetworkArg>(),
+            Err(Error::UnknownNetwork(_))
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    instrument::{self, CallKind},
+    Error,
+};
+use codec::Decode;
+use jsonrpsee_types::to_json_value;
+use sp_core::{
+    storage::{StorageChangeSet, StorageKey},
+    twox_128, H256,
+};
+use std::{collections::VecDeque, marker::PhantomData};
+use substrate_subxt::{RpcClient, Store};
+
+/// Number of entries fetched per page if not configured otherwise.
+pub const DEFAULT_STORAGE_PAGE_SIZE: u32 = 100;
+
+/// Iterates over all entries of a storage map, fetching the keys and values of one page
+/// per request instead of the whole map. All pages are read at the same block, so the
+/// result is consistent even if the map changes during the iteration. The next page is
+/// only requested once the previous one has been consumed.
+pub struct StoragePages<T, F: Store<T>> {
+    rpc_client: RpcClient,
+    prefix: StorageKey,
+    at: H256,
+    page_size: u32,
+    start_key: Option<StorageKey>,
+    buffer: VecDeque<(StorageKey, F::Returns)>,
+    exhausted: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<T, F: Store<T>> StoragePages<T, F> {
+    pub(crate) fn new(rpc_client: RpcClient, at: H256, page_size: u32) -> Self {
+        Self {
+            rpc_client,
+            prefix: storage_prefix(F::MODULE, F::FIELD),
+            at,
+            page_size: page_size.max(1),
+            start_key: None,
+            buffer: VecDeque::new(),
+            exhausted: false,
+            _marker: PhantomData,
+        }
+    }
+
+    async fn fetch_page(&mut self) -> Result<(), Error> {
+        let keys: Vec<StorageKey> = self
+            .rpc_client
+            .request(
+                "state_getKeysPaged",
+                &[
+                    to_json_value(&self.prefix)?,
+                    to_json_value(self.page_size)?,
+                    to_json_value(&self.start_key)?,
+                    to_json_value(self.at)?,
+                ],
+            )
+            .await?;
+        self.exhausted = keys.len() < self.page_size as usize;
+        self.start_key = keys.last().cloned();
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let change_sets: Vec<StorageChangeSet<H256>> = self
+            .rpc_client
+            .request(
+                "state_queryStorageAt",
+                &[to_json_value(&keys)?, to_json_value(self.at)?],
+            )
+            .await?;
+        for (key, data) in change_sets.into_iter().flat_map(|change_set| change_set.changes) {
+            // the entry was removed since the keys were listed
+            if let Some(data) = data {
+                let value = F::Returns::decode(&mut &data.0[..])?;
+                self.buffer.push_back((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next entry of the map, fetching the next page if the current one is consumed.
+    pub async fn next(&mut self) -> Result<Option<(StorageKey, F::Returns)>, Error> {
+        loop {
+            if let Some(entry) = self.buffer.pop_front() {
+                return Ok(Some(entry));
+            }
+            if self.exhausted {
+                return Ok(None);
+            }
+            instrument::instrument(CallKind::Query, F::MODULE, F::FIELD, self.fetch_page()).await?;
+        }
+    }
+}
+
+/// Key of a storage value, or the prefix shared by the keys of all entries of a storage map.
+pub(crate) fn storage_prefix(module: &str, field: &str) -> StorageKey {
+    let mut prefix = twox_128(module.as_bytes()).to_vec();
+    prefix.extend_from_slice(&twox_128(field.as_bytes()));
+    StorageKey(prefix)
+}
+
+/// The last 32 bytes of a `Blake2_128Concat` hashed key are the raw `H256` key.
+pub(crate) fn h256_key(key: &StorageKey) -> H256 {
+    H256::from_slice(&key.0[key.0.len() - 32..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_prefix() {
+        // well-known prefix of `System::Account`
+        let expected: H256 = "26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9"
+            .parse()
+            .unwrap();
+        assert_eq!(storage_prefix("System", "Account").0, expected.as_bytes());
+    }
+}
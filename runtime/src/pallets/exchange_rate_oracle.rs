@@ -1,5 +1,5 @@
 use super::Core;
-use crate::timestamp::Timestamp;
+use crate::{timestamp::Timestamp, OracleKey};
 use codec::{Decode, Encode};
 use core::marker::PhantomData;
 use module_exchange_rate_oracle::BtcTxFeesPerByte;
@@ -30,6 +30,13 @@ pub struct MaxDelayStore<T: ExchangeRateOracle> {
     pub _runtime: PhantomData<T>,
 }
 
+/// Feeds listed for submission, a runtime upgrade may remove or re-add them
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct OracleKeysStore<T: ExchangeRateOracle> {
+    #[store(returns = Vec<OracleKey>)]
+    pub _runtime: PhantomData<T>,
+}
+
 #[derive(Clone, Debug, PartialEq, Call, Encode)]
 pub struct SetExchangeRateCall<T: ExchangeRateOracle> {
     pub rate: T::UnsignedFixedPoint,
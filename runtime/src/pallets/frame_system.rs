@@ -25,6 +25,12 @@ pub struct AccountStore<T: System> {
     pub account_id: T::AccountId,
 }
 
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct RemarkCall<T: System> {
+    pub remark: Vec<u8>,
+    pub _runtime: PhantomData<T>,
+}
+
 #[derive(Clone, Debug, PartialEq, Call, Encode)]
 pub struct SetStorageCall<T: System> {
     pub items: Vec<(Vec<u8>, Vec<u8>)>,
@@ -11,6 +11,7 @@ pub mod sla;
 pub mod staked_relayers;
 pub mod timestamp;
 pub mod tokens;
+pub mod transaction_payment;
 pub mod utility;
 pub mod vault_registry;
 
@@ -22,7 +23,7 @@ pub use module_refund::RefundRequest;
 pub use module_replace::{ReplaceRequest, ReplaceRequestStatus};
 pub use module_security::{ErrorCode, StatusCode};
 pub use module_staked_relayers::Error as StakedRelayersError;
-pub use module_vault_registry::{Vault, VaultStatus};
+pub use module_vault_registry::{SystemVault, Vault, VaultStatus};
 
 pub use sp_core::{H160, H256, U256};
 
@@ -63,6 +63,18 @@ pub struct CancelRedeemEvent<T: Redeem> {
     pub reimburse: bool,
 }
 
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct LiquidationRedeemCall<T: Redeem> {
+    #[codec(compact)]
+    pub amount_wrapped: T::Wrapped,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode, Serialize)]
+pub struct LiquidationRedeemEvent<T: Redeem> {
+    pub redeemer: T::AccountId,
+    pub amount: T::Wrapped,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
 pub struct RedeemRequestsStore<T: Redeem> {
     #[store(returns = RedeemRequest<T::AccountId, T::BlockNumber, T::Wrapped, T::Collateral>)]
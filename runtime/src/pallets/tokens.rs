@@ -47,6 +47,17 @@ pub struct TransferCall<'a, T: Tokens> {
     pub amount: T::Balance,
 }
 
+/// Root call setting the balance of an account, only available on test networks.
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct SetBalanceCall<'a, T: Tokens> {
+    pub who: &'a <T as System>::Address,
+    pub currency_id: T::CurrencyId,
+    #[codec(compact)]
+    pub new_free: T::Balance,
+    #[codec(compact)]
+    pub new_reserved: T::Balance,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
 pub struct TransferEvent<T: Tokens> {
     pub currency_id: T::CurrencyId,
@@ -0,0 +1,40 @@
+use crate::{AccountId, Balance};
+use codec::Decode;
+use substrate_subxt::RawEvent;
+
+/// Fee and tip charged for an extrinsic, decoded from the `TransactionFeePaid` event of its
+/// signer. The fee includes the tip. `None` if the runtime does not emit the event.
+pub fn find_fee_paid(events: &[RawEvent]) -> Result<Option<(Balance, Balance)>, codec::Error> {
+    events
+        .iter()
+        .find(|event| event.module == "TransactionPayment" && event.variant == "TransactionFeePaid")
+        .map(|event| <(AccountId, Balance, Balance)>::decode(&mut &event.data[..]))
+        .transpose()
+        .map(|fee_paid| fee_paid.map(|(_, actual_fee, tip)| (actual_fee, tip)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    #[test]
+    fn test_find_fee_paid() {
+        let event = |module: &str, variant: &str, data: Vec<u8>| RawEvent {
+            module: module.to_string(),
+            variant: variant.to_string(),
+            data,
+        };
+        assert_eq!(
+            find_fee_paid(&[event("System", "ExtrinsicSuccess", vec![])]).unwrap(),
+            None
+        );
+
+        let fee_paid = event(
+            "TransactionPayment",
+            "TransactionFeePaid",
+            (AccountId::from([1; 32]), 150u128, 50u128).encode(),
+        );
+        assert_eq!(find_fee_paid(&[fee_paid]).unwrap(), Some((150, 50)));
+    }
+}
@@ -1,8 +1,9 @@
 use super::Core;
-use codec::Encode;
+use codec::{Decode, Encode};
 use core::marker::PhantomData;
+use sp_runtime::DispatchError;
 use std::fmt::Debug;
-use substrate_subxt::Encoded;
+use substrate_subxt::{Encoded, RawEvent};
 use substrate_subxt_proc_macro::{module, Call};
 
 #[module]
@@ -13,3 +14,40 @@ pub struct BatchCall<T: Utility> {
     pub _runtime: PhantomData<T>,
     pub calls: Vec<Encoded>,
 }
+
+/// Index and error of the call at which a batch stopped, decoded from the
+/// `BatchInterrupted` event emitted instead of `BatchCompleted`.
+pub fn find_batch_interruption(events: &[RawEvent]) -> Result<Option<(u32, DispatchError)>, codec::Error> {
+    events
+        .iter()
+        .find(|event| event.module == "Utility" && event.variant == "BatchInterrupted")
+        .map(|event| <(u32, DispatchError)>::decode(&mut &event.data[..]))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_event(variant: &str, data: Vec<u8>) -> RawEvent {
+        RawEvent {
+            module: "Utility".to_string(),
+            variant: variant.to_string(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_find_batch_interruption() {
+        assert_eq!(
+            find_batch_interruption(&[raw_event("BatchCompleted", vec![])]).unwrap(),
+            None
+        );
+
+        let interrupted = raw_event("BatchInterrupted", (2u32, DispatchError::BadOrigin).encode());
+        assert_eq!(
+            find_batch_interruption(&[interrupted]).unwrap(),
+            Some((2, DispatchError::BadOrigin))
+        );
+    }
+}
@@ -1,7 +1,7 @@
 #![allow(clippy::type_complexity)]
 
 use super::Core;
-use crate::{Vault, VaultStatus};
+use crate::{SystemVault, Vault, VaultStatus};
 use codec::{Decode, Encode};
 use core::marker::PhantomData;
 use std::fmt::Debug;
@@ -57,6 +57,12 @@ pub struct VaultsStore<T: VaultRegistry> {
     pub account_id: T::AccountId,
 }
 
+#[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
+pub struct LiquidationVaultStore<T: VaultRegistry> {
+    #[store(returns = SystemVault<T::AccountId, T::Wrapped>)]
+    pub _runtime: PhantomData<T>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Store, Encode)]
 pub struct LiquidationCollateralThresholdStore<T: VaultRegistry> {
     #[store(returns = u128)]
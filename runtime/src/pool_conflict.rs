@@ -0,0 +1,180 @@
+use crate::{AccountId, Index};
+use codec::{Compact, Decode};
+use lazy_static::lazy_static;
+use prometheus::{IntCounterVec, Opts};
+use sp_runtime::{generic::Era, MultiSignature};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Maximum time to wait for a conflicting extrinsic in the pool to be finalized.
+pub(crate) const CONFLICT_WATCH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Version byte of signed extrinsics.
+const SIGNED_EXTRINSIC_BIT: u8 = 0b1000_0000;
+
+lazy_static! {
+    pub static ref POOL_CONFLICTS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "pool_conflicts",
+            "Submissions that conflicted with an extrinsic of the same account and nonce in the pool, by resolution"
+        ),
+        &["resolution"]
+    )
+    .expect("Failed to create metric");
+}
+
+/// Nonce and tip of the next attempt to submit an extrinsic, changed to replace a
+/// conflicting extrinsic in the pool.
+#[derive(Debug, Default)]
+pub(crate) struct Replacement {
+    /// Nonce of the replaced extrinsic, reused by the next attempt only.
+    pub(crate) nonce: Option<Index>,
+    pub(crate) tip: u128,
+    /// Part of the tip reserved from the budget for replacements.
+    pub(crate) extra_tip: u128,
+}
+
+/// Nonces of the submissions of this client that are in flight. A conflict with one of them
+/// is not resolved against the pool, the conflicting submission just takes the next nonce.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PendingNonces(Arc<Mutex<HashSet<Index>>>);
+
+impl PendingNonces {
+    pub(crate) fn insert(&self, nonce: Index) {
+        self.0.lock().expect("poisoned").insert(nonce);
+    }
+
+    pub(crate) fn remove(&self, nonce: Index) {
+        self.0.lock().expect("poisoned").remove(&nonce);
+    }
+
+    pub(crate) fn contains(&self, nonce: Index) -> bool {
+        self.0.lock().expect("poisoned").contains(&nonce)
+    }
+}
+
+/// How a submission that conflicts with an extrinsic in the pool is resolved. The other
+/// extrinsic was usually submitted by a standby instance with the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resolution {
+    /// The same extrinsic is in the pool, its inclusion is watched instead.
+    Watch,
+    /// Another extrinsic with the same nonce is in the pool: it is replaced by resubmitting
+    /// with the same nonce and a higher tip than it pays.
+    Replace { tip: u128 },
+    /// The nonce is taken by an extrinsic that is left in the pool, the submission is retried
+    /// right away with the next free nonce of the account.
+    Resubmit,
+}
+
+impl Resolution {
+    /// Resolution of a conflict with another extrinsic paying `pooled_tip`: it is replaced if
+    /// the submission is urgent and a tip can be added, `extra_tip` is the tip reserved for the
+    /// replacement. The replacement outbids the tip of the other extrinsic by `extra_tip`, since
+    /// the pool only replaces it by one with a higher priority.
+    pub(crate) fn of_conflict(current_tip: u128, pooled_tip: u128, extra_tip: u128) -> Self {
+        match extra_tip {
+            0 => Resolution::Resubmit,
+            extra_tip => Resolution::Replace {
+                tip: current_tip.max(pooled_tip).saturating_add(extra_tip),
+            },
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Watch => "watch",
+            Resolution::Replace { .. } => "replace",
+            Resolution::Resubmit => "resubmit",
+        }
+    }
+}
+
+/// The signer, nonce and tip of a signed extrinsic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SignedPrefix {
+    pub(crate) signer: AccountId,
+    pub(crate) nonce: Index,
+    pub(crate) tip: u128,
+}
+
+/// The signer, nonce and tip of an encoded extrinsic, `None` if it is not signed. Only the
+/// prefix of the extrinsic is decoded: the length, the version, the signer, the signature,
+/// the era, the nonce and the tip, which precede the call.
+pub(crate) fn signed_prefix(encoded: &[u8]) -> Option<SignedPrefix> {
+    let input = &mut &encoded[..];
+    let _length = Compact::<u32>::decode(input).ok()?;
+    let version = u8::decode(input).ok()?;
+    if version & SIGNED_EXTRINSIC_BIT == 0 {
+        return None;
+    }
+    let signer = AccountId::decode(input).ok()?;
+    let _signature = MultiSignature::decode(input).ok()?;
+    let _era = Era::decode(input).ok()?;
+    let nonce = Compact::<Index>::decode(input).ok()?;
+    let tip = Compact::<u128>::decode(input).ok()?;
+    Some(SignedPrefix {
+        signer,
+        nonce: nonce.0,
+        tip: tip.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use sp_core::sr25519::Signature;
+    use sp_keyring::AccountKeyring;
+
+    fn extrinsic(signer: &AccountId, nonce: Index, tip: u128) -> Vec<u8> {
+        let mut body = vec![SIGNED_EXTRINSIC_BIT | 4];
+        signer.encode_to(&mut body);
+        MultiSignature::Sr25519(Signature::from_raw([1; 64])).encode_to(&mut body);
+        Era::Immortal.encode_to(&mut body);
+        Compact(nonce).encode_to(&mut body);
+        Compact(tip).encode_to(&mut body);
+        // call
+        body.extend(vec![7, 0, 42]);
+        body.encode()
+    }
+
+    #[test]
+    fn test_signed_prefix() {
+        let alice = AccountKeyring::Alice.to_account_id();
+        assert_eq!(
+            signed_prefix(&extrinsic(&alice, 300, 5)),
+            Some(SignedPrefix {
+                signer: alice.clone(),
+                nonce: 300,
+                tip: 5,
+            })
+        );
+
+        // unsigned, e.g. the timestamp inherent
+        let inherent = vec![4, 3, 0, 1].encode();
+        assert_eq!(signed_prefix(&inherent), None);
+        assert_eq!(signed_prefix(&[]), None);
+    }
+
+    #[test]
+    fn test_resolution() {
+        assert_eq!(Resolution::of_conflict(0, 0, 0), Resolution::Resubmit);
+        assert_eq!(Resolution::of_conflict(100, 500, 0), Resolution::Resubmit);
+        assert_eq!(Resolution::of_conflict(100, 0, 100), Resolution::Replace { tip: 200 });
+        // outbid the tip of the pooled extrinsic
+        assert_eq!(Resolution::of_conflict(100, 500, 100), Resolution::Replace { tip: 600 });
+    }
+
+    #[test]
+    fn test_pending_nonces() {
+        let pending = PendingNonces::default();
+        pending.clone().insert(7);
+        assert!(pending.contains(7));
+        pending.remove(7);
+        assert!(!pending.contains(7));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    conn::{new_websocket_client_with_retry, WsClientOptions},
+    pallets::*,
+    types::*,
+    AccountId, BtcRelayPallet, CollateralBalancesPallet, Error, ExchangeRateOraclePallet, InterBtcParachain,
+    InterBtcRuntime, IssuePallet, KnownRuntime, OracleStaleness, RedeemPallet, ReplacePallet, SecurityPallet,
+    UtilFuncs, VaultRegistryPallet,
+};
+use sp_arithmetic::FixedU128;
+use sp_core::{sr25519::Pair, Pair as _, H256};
+use std::{collections::BTreeSet, future::Future, time::Duration};
+use substrate_subxt::{Error as SubxtError, Event, PairSigner, RpcClient};
+
+/// Connection to the parachain that can only be used for queries and subscriptions,
+/// e.g. by monitoring tools. No submission methods are exposed, so holding this type
+/// guarantees that no extrinsics are sent and that no keys need to be loaded.
+#[derive(Clone)]
+pub struct ReadOnlyParachainRpc {
+    // the client requires a signer to be constructed, we use an ephemeral key that is
+    // never funded and never used for signing since the client is not exposed
+    inner: InterBtcParachain,
+}
+
+impl ReadOnlyParachainRpc {
+    pub async fn new<P: Into<RpcClient>>(rpc_client: P) -> Result<Self, Error> {
+        let (pair, _) = Pair::generate();
+        let signer = PairSigner::<InterBtcRuntime, _>::new(pair);
+        Ok(Self {
+            inner: InterBtcParachain::new(rpc_client, signer).await?,
+        })
+    }
+
+    pub async fn from_url_with_retry(url: &str, connection_timeout: Duration) -> Result<Self, Error> {
+        let ws_client = new_websocket_client_with_retry(url, WsClientOptions::default(), connection_timeout).await?;
+        Self::new(ws_client).await
+    }
+
+    pub fn get_runtime(&self) -> KnownRuntime {
+        self.inner.get_runtime()
+    }
+
+    pub async fn get_latest_block_hash(&self) -> Result<Option<H256>, Error> {
+        self.inner.get_latest_block_hash().await
+    }
+
+    pub async fn get_latest_block(&self) -> Result<Option<InterBtcBlock>, Error> {
+        self.inner.get_latest_block().await
+    }
+
+    pub async fn get_current_chain_height(&self) -> Result<u32, Error> {
+        self.inner.get_current_chain_height().await
+    }
+
+    /// Subscribe to new parachain blocks.
+    pub async fn on_block<F, R>(&self, on_block: F) -> Result<(), Error>
+    where
+        F: Fn(InterBtcHeader) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        self.inner.on_block(on_block).await
+    }
+
+    /// Subscribe to events of type `T`, see [`InterBtcParachain::on_event`].
+    pub async fn on_event<T, F, R, E>(&self, on_event: F, on_error: E) -> Result<(), Error>
+    where
+        T: Event<InterBtcRuntime> + core::fmt::Debug,
+        F: FnMut(T) -> R,
+        R: Future<Output = ()>,
+        E: Fn(SubxtError),
+    {
+        self.inner.on_event(on_event, on_error).await
+    }
+
+    pub async fn listen_for_parachain_status(&self) -> Result<(), Error> {
+        self.inner.listen_for_parachain_status().await
+    }
+
+    pub fn is_parachain_shutdown(&self) -> bool {
+        self.inner.is_parachain_shutdown()
+    }
+
+    pub async fn get_parachain_status(&self) -> Result<StatusCode, Error> {
+        self.inner.get_parachain_status().await
+    }
+
+    pub async fn get_error_codes(&self) -> Result<BTreeSet<ErrorCode>, Error> {
+        self.inner.get_error_codes().await
+    }
+
+    pub async fn get_exchange_rate_info(&self) -> Result<(FixedU128, u64, u64), Error> {
+        self.inner.get_exchange_rate_info().await
+    }
+
+    pub async fn get_oracle_staleness(&self) -> Result<OracleStaleness, Error> {
+        self.inner.get_oracle_staleness().await
+    }
+
+    pub async fn get_free_balance_for_id(&self, id: AccountId) -> Result<u128, Error> {
+        self.inner.get_free_balance_for_id(id).await
+    }
+
+    pub async fn get_reserved_balance_for_id(&self, id: AccountId) -> Result<u128, Error> {
+        self.inner.get_reserved_balance_for_id(id).await
+    }
+
+    pub async fn get_vault(&self, vault_id: AccountId) -> Result<InterBtcVault, Error> {
+        self.inner.get_vault(vault_id).await
+    }
+
+    pub async fn get_all_vaults(&self) -> Result<Vec<InterBtcVault>, Error> {
+        self.inner.get_all_vaults().await
+    }
+
+    pub async fn get_issue_request(&self, issue_id: H256) -> Result<InterBtcIssueRequest, Error> {
+        self.inner.get_issue_request(issue_id).await
+    }
+
+    pub async fn get_redeem_request(&self, redeem_id: H256) -> Result<InterBtcRedeemRequest, Error> {
+        self.inner.get_redeem_request(redeem_id).await
+    }
+
+    pub async fn get_replace_request(&self, replace_id: H256) -> Result<InterBtcReplaceRequest, Error> {
+        self.inner.get_replace_request(replace_id).await
+    }
+
+    pub async fn get_best_block_height(&self) -> Result<u32, Error> {
+        self.inner.get_best_block_height().await
+    }
+}
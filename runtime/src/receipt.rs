@@ -0,0 +1,117 @@
+use crate::{Balance, InterBtcRuntime};
+use codec::Encode;
+use sp_core::{blake2_256, hexdisplay::HexDisplay};
+use sp_runtime::generic::Era;
+use std::{fmt, ops::Deref};
+use substrate_subxt::ExtrinsicSuccess;
+
+/// Stable, human-readable identifier of a call, e.g. `Issue.execute_issue#1a2b3c4d`. The
+/// digest of the arguments tells apart calls of the same function without logging them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallId {
+    pub module: &'static str,
+    pub function: &'static str,
+    /// First bytes of the blake2 hash of the SCALE-encoded arguments.
+    pub args_digest: [u8; 4],
+}
+
+impl CallId {
+    pub fn new<A: Encode>(module: &'static str, function: &'static str, args: &A) -> Self {
+        let mut args_digest = [0; 4];
+        args_digest.copy_from_slice(&blake2_256(&args.encode())[..4]);
+        Self {
+            module,
+            function,
+            args_digest,
+        }
+    }
+}
+
+impl fmt::Display for CallId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}#{}",
+            self.module,
+            self.function,
+            HexDisplay::from(&self.args_digest)
+        )
+    }
+}
+
+/// Inclusion details of a submitted extrinsic. Dereferences to the `ExtrinsicSuccess`, so
+/// that the emitted events can be inspected as before.
+#[derive(Debug)]
+pub struct SubmissionReceipt {
+    pub call: CallId,
+    pub nonce: u32,
+    pub tip: u128,
+    /// Era with which the extrinsic was signed.
+    pub era: Era,
+    /// Fee charged for the extrinsic, excluding the tip, as reported by the runtime. `None`
+    /// if the runtime does not report it.
+    pub fee: Option<Balance>,
+    pub success: ExtrinsicSuccess<InterBtcRuntime>,
+}
+
+impl Deref for SubmissionReceipt {
+    type Target = ExtrinsicSuccess<InterBtcRuntime>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.success
+    }
+}
+
+impl fmt::Display for SubmissionReceipt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+        write!(
+            f,
+            "{} (nonce {}, tip {}, era {}) in block {:?}, extrinsic {:?}, fee {}",
+            self.call,
+            self.nonce,
+            self.tip,
+            DisplayEra(&self.era),
+            self.success.block,
+            self.success.extrinsic,
+            or_unknown(self.fee.map(|fee| fee.to_string())),
+        )
+    }
+}
+
+/// Formats an era as `immortal` or `mortal (period <period>, phase <phase>)`.
+pub(crate) struct DisplayEra<'a>(pub(crate) &'a Era);
+
+impl fmt::Display for DisplayEra<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Era::Immortal => write!(f, "immortal"),
+            Era::Mortal(period, phase) => write!(f, "mortal (period {}, phase {})", period, phase),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H256;
+
+    #[test]
+    fn test_call_id() {
+        let issue_id = H256::from_low_u64_be(1);
+        let call_id = CallId::new("Issue", "cancel_issue", &issue_id);
+        assert_eq!(call_id, CallId::new("Issue", "cancel_issue", &issue_id));
+        assert_ne!(call_id, CallId::new("Issue", "cancel_issue", &H256::from_low_u64_be(2)));
+        assert!(call_id.to_string().starts_with("Issue.cancel_issue#"));
+        assert_eq!(call_id.to_string().len(), "Issue.cancel_issue#".len() + 8);
+    }
+
+    #[test]
+    fn test_display_era() {
+        assert_eq!(DisplayEra(&Era::Immortal).to_string(), "immortal");
+        assert_eq!(
+            DisplayEra(&Era::mortal(64, 100)).to_string(),
+            "mortal (period 64, phase 36)"
+        );
+    }
+}
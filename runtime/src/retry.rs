@@ -1,7 +1,25 @@
 use crate::Error;
 use backoff::{backoff::Backoff, ExponentialBackoff};
 use futures::Future;
-use std::{fmt::Debug, time::Duration};
+use lazy_static::lazy_static;
+use prometheus::{IntCounterVec, Opts};
+use std::time::Duration;
+
+/// Number of times an extrinsic is resubmitted if its priority is too low.
+const PRIORITY_TOO_LOW_RETRIES: u32 = 3;
+/// Delay before resubmitting an extrinsic with too low priority, roughly one block.
+const PRIORITY_TOO_LOW_DELAY: Duration = Duration::from_secs(6);
+
+lazy_static! {
+    pub static ref CALL_RETRIES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "call_retries",
+            "Number of retried parachain calls by call and error class"
+        ),
+        &["call", "class"]
+    )
+    .expect("Failed to create metric");
+}
 
 /// Gets the default retrying policy. This should be used for unexpected errors, not for operations
 /// that are expected to take a while to succeed. That is, it is unsuitable for e.g. awaiting bitcoin
@@ -19,34 +37,169 @@ fn get_exponential_backoff() -> ExponentialBackoff {
     }
 }
 
-pub enum RetryPolicy<E> {
-    Skip(E),
-    Throw(E),
+/// Class of an error returned by a chain call, determines how the call is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The websocket connection was lost. The client cannot recover from this, so the call
+    /// fails immediately and the connection is re-established by the service.
+    Disconnected,
+    /// The nonce was already used, retried with a fresh nonce and exponential backoff.
+    OutdatedNonce,
+    /// Another extrinsic with the same nonce is in the pool, retried a fixed number of times.
+    PriorityTooLow,
+    /// The same extrinsic is already in the pool, it is watched instead of being resubmitted.
+    AlreadyImported,
+    /// Any other error (e.g. a dispatch error), retrying would fail in the same way.
+    Logic,
+}
+
+impl ErrorClass {
+    pub fn of(err: &Error) -> Self {
+        if err.is_rpc_disconnect_error() {
+            ErrorClass::Disconnected
+        } else if err.is_outdated_nonce() {
+            ErrorClass::OutdatedNonce
+        } else if err.is_priority_too_low() {
+            ErrorClass::PriorityTooLow
+        } else if err.is_already_imported() {
+            ErrorClass::AlreadyImported
+        } else {
+            ErrorClass::Logic
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ErrorClass::Disconnected => "disconnected",
+            ErrorClass::OutdatedNonce => "outdated_nonce",
+            ErrorClass::PriorityTooLow => "priority_too_low",
+            ErrorClass::AlreadyImported => "already_imported",
+            ErrorClass::Logic => "logic",
+        }
+    }
+}
+
+/// Tracks the retries of a single call and decides whether and when to retry.
+struct Retries {
+    backoff: ExponentialBackoff,
+    priority_too_low: u32,
+}
+
+impl Retries {
+    fn new() -> Self {
+        Self {
+            backoff: get_exponential_backoff(),
+            priority_too_low: 0,
+        }
+    }
+
+    /// Returns the delay before the next attempt, or `None` if the error should be returned.
+    fn next(&mut self, class: ErrorClass) -> Option<Duration> {
+        match class {
+            ErrorClass::OutdatedNonce => self.backoff.next_backoff(),
+            ErrorClass::PriorityTooLow if self.priority_too_low < PRIORITY_TOO_LOW_RETRIES => {
+                self.priority_too_low += 1;
+                Some(PRIORITY_TOO_LOW_DELAY)
+            }
+            ErrorClass::PriorityTooLow | ErrorClass::AlreadyImported | ErrorClass::Disconnected | ErrorClass::Logic => {
+                None
+            }
+        }
+    }
+}
+
+/// Retry middleware for chain calls, applies the retry policy of the error class and counts
+/// the retries per call. `before_retry` is awaited before every retry, e.g. to refresh the nonce.
+pub(crate) async fn retry_call<L, FL, B, FB, T>(call_name: &str, call: L, before_retry: B) -> Result<T, Error>
+where
+    L: Fn() -> FL,
+    FL: Future<Output = Result<T, Error>>,
+    B: Fn(ErrorClass) -> FB,
+    FB: Future<Output = ()>,
+{
+    let mut retries = Retries::new();
+    loop {
+        let err = match call().await {
+            Ok(ok) => return Ok(ok),
+            Err(err) => err,
+        };
+
+        let class = ErrorClass::of(&err);
+        match retries.next(class) {
+            Some(wait) => {
+                CALL_RETRIES.with_label_values(&[call_name, class.as_str()]).inc();
+                log::warn!(
+                    "{} failed ({}): {} - next retry in {:.3} s",
+                    call_name,
+                    class.as_str(),
+                    err,
+                    wait.as_secs_f64()
+                );
+                before_retry(class).await;
+                tokio::time::delay_for(wait).await;
+            }
+            None => return Err(err),
+        }
+    }
+}
+
+/// Whether a request execution failing with the error may succeed later, e.g. once the relay
+/// has caught up with the block of the proof. It is final if the request expired or was
+/// completed, the connection was lost or the proof is for another chain.
+fn is_retryable_execution(err: &Error) -> bool {
+    !(err.is_commit_period_expired()
+        || err.is_request_completed()
+        || err.is_rpc_disconnect_error()
+        || err.is_invalid_chain_id())
 }
 
-pub async fn notify_retry<E, L, FL, R, FR, T>(call: L, verify: R) -> Result<T, Error>
+/// Retry middleware for request executions. Unlike other calls, executions are also retried on
+/// logic errors, with exponential backoff until the error is final or the backoff times out.
+pub(crate) async fn retry_execution<L, FL, T>(call_name: &str, call: L) -> Result<T, Error>
 where
-    E: Debug,
     L: Fn() -> FL,
-    FL: Future<Output = Result<T, E>>,
-    R: Fn(Result<T, E>) -> FR,
-    FR: Future<Output = Result<T, RetryPolicy<Error>>>,
+    FL: Future<Output = Result<T, Error>>,
 {
     let mut backoff = get_exponential_backoff();
     loop {
-        let err = match verify(call().await).await {
+        let err = match call().await {
             Ok(ok) => return Ok(ok),
-            Err(RetryPolicy::Skip(err)) => err,
-            Err(RetryPolicy::Throw(err)) => return Err(err),
+            Err(err) if !is_retryable_execution(&err) => return Err(err),
+            Err(err) => err,
         };
 
+        let class = ErrorClass::of(&err);
         match backoff.next_backoff() {
             Some(wait) => {
-                // error occurred, sleep before retrying
-                log::warn!("{:?} - next retry in {:.3} s", err, wait.as_secs_f64());
+                CALL_RETRIES.with_label_values(&[call_name, class.as_str()]).inc();
+                log::warn!(
+                    "{} failed ({}): {} - next retry in {:.3} s",
+                    call_name,
+                    class.as_str(),
+                    err,
+                    wait.as_secs_f64()
+                );
                 tokio::time::delay_for(wait).await;
             }
-            None => break Err(Error::Timeout),
+            None => return Err(Error::Timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_per_error_class() {
+        let mut retries = Retries::new();
+        assert_eq!(retries.next(ErrorClass::Logic), None);
+        assert_eq!(retries.next(ErrorClass::Disconnected), None);
+        assert_eq!(retries.next(ErrorClass::AlreadyImported), None);
+        assert!(retries.next(ErrorClass::OutdatedNonce).is_some());
+        for _ in 0..PRIORITY_TOO_LOW_RETRIES {
+            assert_eq!(retries.next(ErrorClass::PriorityTooLow), Some(PRIORITY_TOO_LOW_DELAY));
         }
+        assert_eq!(retries.next(ErrorClass::PriorityTooLow), None);
     }
 }
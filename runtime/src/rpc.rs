@@ -1,4 +1,4 @@
-use codec::Encode;
+use codec::{Decode, Encode};
 pub use module_exchange_rate_oracle::BtcTxFeesPerByte;
 
 use async_trait::async_trait;
@@ -6,21 +6,42 @@ use core::marker::PhantomData;
 use futures::{stream::StreamExt, FutureExt, SinkExt};
 use jsonrpsee_types::to_json_value;
 use module_exchange_rate_oracle_rpc_runtime_api::BalanceWrapper;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
 use sp_arithmetic::FixedU128;
-use sp_core::H256;
-use sp_runtime::DispatchError;
-use std::{collections::BTreeSet, future::Future, sync::Arc, time::Duration};
+use sp_core::{Bytes, H256};
+use sp_runtime::{
+    traits::{BlakeTwo256, Hash as _, Header as _},
+    ApplyExtrinsicResult, DispatchError,
+};
+use std::{
+    collections::BTreeSet,
+    future::Future,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use substrate_subxt::{
-    sudo::*, Call, Client as SubxtClient, ClientBuilder as SubxtClientBuilder, Error as SubxtError, Event,
-    EventSubscription, EventTypeRegistry, EventsDecoder, RpcClient, RuntimeError as SubxtRuntimeError, Signer,
+    sudo::*, Call, Client as SubxtClient, ClientBuilder as SubxtClientBuilder, Encoded, Error as SubxtError, Event,
+    EventSubscription, EventTypeRegistry, EventsDecoder, ExtrinsicSuccess, RawEvent, RpcClient,
+    RuntimeError as SubxtRuntimeError, Signer, Store,
+};
+use tokio::{
+    sync::{watch, RwLock},
+    time::delay_for,
 };
-use tokio::{sync::RwLock, time::delay_for};
 
 use crate::{
-    btc_relay::*, conn::*, exchange_rate_oracle::*, fee::*, issue::*, pallets::*, redeem::*, refund::*, replace::*,
-    retry::*, security::*, staked_relayers::*, timestamp::*, tokens::*, types::*, utility::*, vault_registry::*,
-    AccountId, BlockNumber, CurrencyId, Error, InterBtcRuntime, BTC_RELAY_MODULE, STABLE_BITCOIN_CONFIRMATIONS,
-    STABLE_PARACHAIN_CONFIRMATIONS,
+    balance_guard::*, balances::*, blocks::*, btc_relay::*, conn::*, drift::*, dry_run::*, event_decoders::*,
+    exchange_rate_oracle::*, extra::*, fee::*, history::*, instrument::*, issue::*, liquidation::*, metadata::*,
+    pagination::*, pallets::*, pool_conflict::*, receipt::*, redeem::*, refund::*, replace::*, retry::*, security::*,
+    staked_relayers::*, staleness::*, timestamp::*, tokens::*, transaction_payment::*, types::*, utility::*,
+    vault_registry::*, AccountId, Amount, Balance, BlockNumber, CollateralAmount, CurrencyId, Error, Index,
+    InterBtcRuntime, NetworkProfile, OracleKey, WrappedAmount, BTC_RELAY_MODULE, COLLATERAL_CURRENCY,
+    EXCHANGE_RATE_ORACLE_MODULE, EXISTENTIAL_DEPOSIT, FEE_CURRENCY, STABLE_BITCOIN_CONFIRMATIONS,
+    STABLE_PARACHAIN_CONFIRMATIONS, TOKENS_MODULE, WRAPPED_CURRENCY,
 };
 
 #[derive(Clone)]
@@ -29,6 +50,20 @@ pub struct InterBtcParachain {
     ext_client: SubxtClient<InterBtcRuntime>,
     signer: Arc<RwLock<InterBtcSigner>>,
     account_id: AccountId,
+    status_tx: Arc<watch::Sender<StatusCode>>,
+    status_rx: watch::Receiver<StatusCode>,
+    tip_budget: TipBudget,
+    runtime: KnownRuntime,
+    /// Spec version of the runtime when connecting.
+    spec_version: u32,
+    dry_run: bool,
+    balance_guard: Option<BalanceGuard>,
+    balances: BalanceCache,
+    fee_budget: Option<Balance>,
+    storage_page_size: u32,
+    event_decoders: EventDecoders,
+    fee_payment: FeePayment,
+    pending_nonces: PendingNonces,
 }
 
 impl InterBtcParachain {
@@ -40,18 +75,44 @@ impl InterBtcParachain {
             .build()
             .await?;
 
+        let version: RuntimeVersion = rpc_client.request("state_getRuntimeVersion", &[]).await?;
+        let runtime = select_runtime(&version, ext_client.metadata())?;
+        let properties: SystemProperties = rpc_client.request("system_properties", &[]).await?;
+        validate_chain(&runtime, *ext_client.genesis(), &properties)?;
+        log::info!(
+            "Connected to {} runtime {} (spec version {})",
+            runtime.network,
+            version.spec_name,
+            version.spec_version
+        );
+
+        let (status_tx, status_rx) = watch::channel(StatusCode::default());
+
         let parachain_rpc = Self {
             rpc_client,
             ext_client,
             signer: Arc::new(RwLock::new(signer)),
             account_id,
+            status_tx: Arc::new(status_tx),
+            status_rx,
+            tip_budget: TipBudget::default(),
+            runtime,
+            spec_version: version.spec_version,
+            dry_run: false,
+            balance_guard: None,
+            balances: BalanceCache::default(),
+            fee_budget: None,
+            storage_page_size: DEFAULT_STORAGE_PAGE_SIZE,
+            event_decoders: EventDecoders::default(),
+            fee_payment: FeePayment::default(),
+            pending_nonces: PendingNonces::default(),
         };
         parachain_rpc.refresh_nonce().await;
         Ok(parachain_rpc)
     }
 
     pub async fn from_url(url: &str, signer: InterBtcSigner) -> Result<Self, Error> {
-        let ws_client = new_websocket_client(url, None, None).await?;
+        let ws_client = new_websocket_client(url, WsClientOptions::default()).await?;
         Self::new(ws_client, signer).await
     }
 
@@ -60,73 +121,748 @@ impl InterBtcParachain {
         signer: InterBtcSigner,
         connection_timeout: Duration,
     ) -> Result<Self, Error> {
-        Self::from_url_and_config_with_retry(url, signer, None, None, connection_timeout).await
+        Self::from_url_and_config_with_retry(url, signer, WsClientOptions::default(), connection_timeout).await
     }
 
     pub async fn from_url_and_config_with_retry(
         url: &str,
         signer: InterBtcSigner,
-        max_concurrent_requests: Option<usize>,
-        max_notifs_per_subscription: Option<usize>,
+        options: WsClientOptions,
         connection_timeout: Duration,
     ) -> Result<Self, Error> {
-        let ws_client = new_websocket_client_with_retry(
-            url,
-            max_concurrent_requests,
-            max_notifs_per_subscription,
-            connection_timeout,
-        )
-        .await?;
+        let ws_client = new_websocket_client_with_retry(url, options, connection_timeout).await?;
         Self::new(ws_client, signer).await
     }
 
+    /// Set the tip paid for extrinsics submitted within `runtime::urgent`.
+    pub fn with_tip_budget(mut self, tip_budget: TipBudget) -> Self {
+        self.tip_budget = tip_budget;
+        self
+    }
+
+    /// Total amount of tips paid for urgent extrinsics.
+    pub fn get_tips_spent(&self) -> u128 {
+        self.tip_budget.spent()
+    }
+
+    /// Simulate execute and registration calls before submitting them, so that predictable
+    /// failures are reported without paying fees.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Refuse to submit extrinsics that would leave less than the existential deposit of the
+    /// runtime plus the given reserve as free balance. Disabled if no reserve is given.
+    pub fn with_balance_reserve(mut self, reserve: Option<Balance>) -> Result<Self, Error> {
+        self.balance_guard = match reserve {
+            Some(reserve) => Some(BalanceGuard::new(self.existential_deposit()?, reserve)),
+            None => None,
+        };
+        Ok(self)
+    }
+
+    /// The existential deposit of the currencies held in the `Tokens` pallet, as declared
+    /// in the metadata of the runtime at the time of connecting.
+    pub fn existential_deposit(&self) -> Result<Balance, Error> {
+        self.ext_client
+            .metadata()
+            .module(TOKENS_MODULE)
+            .and_then(|module| module.constant(EXISTENTIAL_DEPOSIT))
+            .and_then(|constant| constant.value::<Balance>())
+            .map_err(|_| Error::MissingConstant(TOKENS_MODULE, EXISTENTIAL_DEPOSIT))
+    }
+
+    /// Refuse to submit checked extrinsics whose estimated fee exceeds the budget.
+    pub fn with_fee_budget(mut self, fee_budget: Option<Balance>) -> Self {
+        self.fee_budget = fee_budget;
+        self
+    }
+
+    /// Set the number of entries fetched per request when iterating over storage maps.
+    pub fn with_storage_page_size(mut self, storage_page_size: u32) -> Self {
+        self.storage_page_size = storage_page_size;
+        self
+    }
+
+    /// Pay the fees of extrinsics as given, failing if the runtime does not include the
+    /// charge-asset-tx-payment signed extension required to pay them in an asset.
+    pub fn with_fee_payment(mut self, fee_payment: FeePayment) -> Result<Self, Error> {
+        if let FeePayment::Asset(_) = fee_payment {
+            self.ext_client
+                .metadata()
+                .module(ASSET_TX_PAYMENT_MODULE)
+                .map_err(|_| Error::AssetFeePaymentUnsupported)?;
+        }
+        self.fee_payment = fee_payment;
+        Ok(self)
+    }
+
+    /// Iterate over all entries of the storage map at the given block, one page at a time.
+    pub fn storage_pages<F: Store<InterBtcRuntime>>(&self, at: H256) -> StoragePages<InterBtcRuntime, F> {
+        StoragePages::new(self.rpc_client.clone(), at, self.storage_page_size)
+    }
+
+    /// Make the RPC request, recording its latency under the pallet and method of its name.
+    async fn request<T: DeserializeOwned>(&self, method: &'static str, params: &[JsonValue]) -> Result<T, SubxtError> {
+        rpc(method, self.rpc_client.request(method, params)).await
+    }
+
+    /// Sign the call with the current nonce, without incrementing it.
+    async fn sign_for_estimate<C: Call<InterBtcRuntime> + Send + Sync>(&self, call: C) -> Result<Bytes, Error> {
+        let signer = self.signer.read().await.clone();
+        let extrinsic = with_fee_payment(self.fee_payment, self.ext_client.create_signed(call, &signer)).await?;
+        Ok(Bytes(extrinsic.encode()))
+    }
+
+    /// Estimate the weight-based fee of the call via `payment_queryInfo`, excluding any tip.
+    pub async fn estimate_fee<C: Call<InterBtcRuntime> + Send + Sync>(&self, call: C) -> Result<Balance, Error> {
+        let extrinsic = self.sign_for_estimate(call).await?;
+        self.query_fee(extrinsic, None).await
+    }
+
+    /// Query the fee of the encoded extrinsic at the given block, or the best block if `None`.
+    async fn query_fee(&self, extrinsic: Bytes, at: Option<H256>) -> Result<Balance, Error> {
+        let fee_info: FeeInfo = self
+            .request("payment_queryInfo", &[to_json_value(extrinsic)?, to_json_value(at)?])
+            .await?;
+        Ok(fee_info.partial_fee)
+    }
+
+    /// Compare the fee expected for the signed extrinsic when it was submitted against
+    /// `submitted_at` with the fee paid for it, if the runtime reported it.
+    async fn record_fees(
+        &self,
+        call_id: &CallId,
+        submitted_at: Option<H256>,
+        block_hash: H256,
+        extrinsic_hash: H256,
+        paid: Option<Balance>,
+    ) -> Result<(), Error> {
+        let block = self
+            .ext_client
+            .block(Some(block_hash))
+            .await?
+            .ok_or(Error::BlockNotFound)?
+            .block;
+        let (index, extrinsic) = block
+            .extrinsics
+            .iter()
+            .map(Encode::encode)
+            .enumerate()
+            .find(|(_, encoded)| BlakeTwo256::hash(encoded) == extrinsic_hash)
+            .ok_or(Error::BlockNotFound)?;
+
+        let expected = self.query_fee(Bytes(extrinsic), submitted_at).await?;
+        EXTRINSIC_FEES
+            .with_label_values(&[call_id.function, "expected"])
+            .observe(expected as f64);
+        match paid {
+            Some(paid) => {
+                log::info!(
+                    "Fee of {} at index {}: {} (expected {})",
+                    call_id,
+                    index,
+                    paid,
+                    expected
+                );
+                EXTRINSIC_FEES
+                    .with_label_values(&[call_id.function, "realized"])
+                    .observe(paid as f64);
+            }
+            None => log::info!("Fee of {} at index {}: unknown (expected {})", call_id, index, expected),
+        }
+        Ok(())
+    }
+
+    /// Simulate the call against the latest block via `system_dryRun` and estimate its fee,
+    /// without submitting it. The call is signed with the current nonce.
+    pub async fn dry_run<C: Call<InterBtcRuntime> + Send + Sync>(&self, call: C) -> Result<DryRunResult, Error> {
+        let extrinsic = self.sign_for_estimate(call).await?;
+        let head = self.get_latest_block_hash().await?;
+        let params = [to_json_value(&extrinsic)?, to_json_value(head)?];
+
+        let fee_info: FeeInfo = self.request("payment_queryInfo", &params).await?;
+        let result: Bytes = self.request("system_dryRun", &params).await?;
+        match ApplyExtrinsicResult::decode(&mut &result[..])? {
+            Ok(Ok(())) => Ok(DryRunResult {
+                fee: fee_info.partial_fee,
+            }),
+            Ok(Err(err)) => Err(
+                match SubxtRuntimeError::from_dispatch(self.ext_client.metadata(), err) {
+                    Ok(err) => Error::SubxtError(SubxtError::Runtime(err)),
+                    Err(err) => Error::SubxtError(err),
+                },
+            ),
+            Err(err) => Err(Error::DryRunInvalid(format!("{:?}", err))),
+        }
+    }
+
+    /// Check the call before submission: the fee budget and balance guard are applied if
+    /// configured, where `spent` is the amount of the fee currency transferred or reserved
+    /// by the call, and the call is dry-run if enabled.
+    async fn check_call<C: Call<InterBtcRuntime> + Clone + Send + Sync>(
+        &self,
+        call: C,
+        spent: Balance,
+    ) -> Result<(), Error> {
+        if self.balance_guard.is_some() || self.fee_budget.is_some() {
+            let fee = self.estimate_fee(call.clone()).await?;
+            log::debug!("Estimated fee of {}::{}: {}", C::MODULE, C::FUNCTION, fee);
+            let result = match (self.fee_budget, self.balance_guard) {
+                (Some(budget), _) if fee > budget => Err(Error::FeeBudgetExceeded { fee, budget }),
+                (_, Some(guard)) => match self.fee_payment.asset() {
+                    // the amount spent is given in the native currency
+                    Some(currency) if currency != FEE_CURRENCY => guard.check(
+                        self.get_fee_balance().await?,
+                        self.fee_in_asset(currency, fee).await?,
+                        0,
+                    ),
+                    _ => guard.check(self.get_fee_balance().await?, fee, spent),
+                },
+                _ => Ok(()),
+            };
+            if let Err(err) = result {
+                log::error!("Refusing to submit {}::{}: {}", C::MODULE, C::FUNCTION, err);
+                return Err(err);
+            }
+        }
+        if self.dry_run {
+            self.simulate(call).await?;
+        }
+        Ok(())
+    }
+
+    /// Convert the fee, estimated in the native currency, to the asset it is paid in at the
+    /// exchange rate of the oracle. The native currency is the collateral currency, so only
+    /// the wrapped currency can be converted to.
+    async fn fee_in_asset(&self, currency: CurrencyId, fee: Balance) -> Result<Balance, Error> {
+        if currency != WRAPPED_CURRENCY {
+            return Err(Error::UnconvertibleFeeCurrency(currency));
+        }
+        let fee = CollateralAmount::from_balance(FEE_CURRENCY, fee)?;
+        Ok(self.collateral_to_wrapped(fee).await?.planck())
+    }
+
+    /// Free balance of the currency fees are paid in, from the balance subscriptions if the
+    /// account is followed.
+    async fn get_fee_balance(&self) -> Result<Balance, Error> {
+        let currency = self.fee_payment.asset().unwrap_or(FEE_CURRENCY);
+        match self.balances.get(&self.account_id, currency) {
+            Some(balance) => Ok(balance.free),
+            None => {
+                let head = self.get_latest_block_hash().await?;
+                Ok(query::<AccountsStore<_>, _, _, _>(self.ext_client.accounts(
+                    self.account_id.clone(),
+                    currency,
+                    head,
+                ))
+                .await?
+                .free)
+            }
+        }
+    }
+
+    /// Dry-run the call, returns the error the call would have failed with.
+    async fn simulate<C: Call<InterBtcRuntime> + Send + Sync>(&self, call: C) -> Result<(), Error> {
+        match self.dry_run(call).await {
+            Ok(result) => {
+                log::debug!(
+                    "Dry-run of {}::{} succeeded, estimated fee {}",
+                    C::MODULE,
+                    C::FUNCTION,
+                    result.fee
+                );
+                Ok(())
+            }
+            Err(err) => {
+                log::warn!("Dry-run of {}::{} failed: {}", C::MODULE, C::FUNCTION, err);
+                DRY_RUN_FAILURES.with_label_values(&[C::FUNCTION]).inc();
+                Err(err)
+            }
+        }
+    }
+
+    /// The known runtime selected for the connected chain.
+    pub fn get_runtime(&self) -> KnownRuntime {
+        self.runtime
+    }
+
+    /// Check the runtime version at every finalized block, and fail once the chain upgraded
+    /// to a runtime this build does not support, so that the client stops instead of
+    /// submitting calls for the previous runtime. Reconnecting then fails as well.
+    pub async fn watch_runtime_version(&self) -> Result<(), Error> {
+        let last_version = std::sync::Mutex::new(self.spec_version);
+        let last_version = &last_version;
+        self.on_block(|header| async move {
+            let version: RuntimeVersion = self
+                .request("state_getRuntimeVersion", &[to_json_value(header.hash())?])
+                .await?;
+            let previous = std::mem::replace(&mut *last_version.lock().expect("poisoned"), version.spec_version);
+            if previous != version.spec_version {
+                expect_supported(&self.runtime, &version)?;
+                log::info!(
+                    "Runtime upgraded from spec version {} to {}",
+                    previous,
+                    version.spec_version
+                );
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Fail unless the connected chain is the given network (e.g. `kintsugi`).
+    pub fn expect_network(self, network: &str) -> Result<Self, Error> {
+        expect_network(&self.runtime, network)?;
+        Ok(self)
+    }
+
+    /// Fail unless the connected chain is the parachain of the network profile.
+    pub fn expect_profile(self, profile: &NetworkProfile) -> Result<Self, Error> {
+        expect_network(&self.runtime, profile.runtime_network)?;
+        if let Some(genesis_hash) = profile.genesis_hash {
+            expect_genesis(&self.runtime, genesis_hash, *self.ext_client.genesis())?;
+        }
+        Ok(self)
+    }
+
     async fn refresh_nonce(&self) {
         let mut signer = self.signer.write().await;
-        // For getting the nonce, use latest, possibly non-finalized block.
-        // TODO: we might want to wait until the latest block is actually finalized
-        // query account info in order to get the nonce value used for communication
-        let account_info = crate::frame_system::AccountStoreExt::account(
-            &self.ext_client,
-            self.account_id.clone(),
-            Option::<H256>::None,
-        )
-        .await
-        .unwrap_or_default();
-        log::info!("Refreshing nonce: {}", account_info.nonce);
-        signer.set_nonce(account_info.nonce);
+        // the next index accounts for the extrinsics of this account in the pool, unlike the
+        // nonce in the account info, which only counts those in the latest block
+        let next_index: Result<Index, Error> = match to_json_value(&self.account_id) {
+            Ok(account_id) => self
+                .request("system_accountNextIndex", &[account_id])
+                .await
+                .map_err(Into::into),
+            Err(err) => Err(err.into()),
+        };
+        let nonce = match next_index {
+            Ok(nonce) => nonce,
+            Err(err) => {
+                log::warn!("Failed to get the next index of the account, using its nonce: {}", err);
+                query::<crate::frame_system::AccountStore<_>, _, _, _>(crate::frame_system::AccountStoreExt::account(
+                    &self.ext_client,
+                    self.account_id.clone(),
+                    Option::<H256>::None,
+                ))
+                .await
+                .unwrap_or_default()
+                .nonce
+            }
+        };
+        log::info!("Refreshing nonce: {}", nonce);
+        signer.set_nonce(nonce);
+    }
+
+    /// Gets a copy of the signer with a unique nonce. If the parachain is shut down,
+    /// this waits until it is running again before submitting.
+    async fn with_unique_signer<F, R>(&self, call_id: CallId, call: F) -> Result<SubmissionReceipt, Error>
+    where
+        F: Fn(InterBtcSigner) -> R,
+        R: Future<Output = Result<ExtrinsicSuccess<InterBtcRuntime>, SubxtError>>,
+    {
+        self.wait_for_parachain_running().await;
+        if !is_urgent() {
+            return self.with_unique_signer_unchecked(call_id, call).await;
+        }
+
+        let tip = self.tip_budget.reserve();
+        let result = with_tip(tip, self.with_unique_signer_unchecked(call_id, call)).await;
+        match result {
+            // the tip may have been raised to replace a conflicting extrinsic
+            Ok(ref receipt) if receipt.tip > 0 => {
+                log::info!("Paid tip of {} for urgent extrinsic", receipt.tip);
+                TIPS_SPENT.inc_by(receipt.tip as u64);
+            }
+            Err(_) => self.tip_budget.refund(tip),
+            _ => {}
+        }
+        result
     }
 
-    /// Gets a copy of the signer with a unique nonce
-    async fn with_unique_signer<F, R, T>(&self, call: F) -> Result<T, Error>
+    /// Gets a copy of the signer with a unique nonce, regardless of the parachain status.
+    /// Failed submissions are retried according to the class of the error, the fee of
+    /// successful submissions is compared against the fee expected at submission. Every
+    /// submission and the resulting inclusion is logged with the identifier of the call.
+    /// Submissions that conflict with an extrinsic in the pool are resolved by watching or
+    /// replacing that extrinsic, or by taking the next nonce, see [`Resolution`].
+    async fn with_unique_signer_unchecked<F, R>(&self, call_id: CallId, call: F) -> Result<SubmissionReceipt, Error>
     where
         F: Fn(InterBtcSigner) -> R,
-        R: Future<Output = Result<T, SubxtError>>,
+        R: Future<Output = Result<ExtrinsicSuccess<InterBtcRuntime>, SubxtError>>,
     {
-        notify_retry(
+        let submitted_at = self.get_latest_block_hash().await?;
+        let nonce = AtomicU32::new(0);
+        let replacement = Mutex::new(Replacement {
+            tip: current_tip(),
+            ..Default::default()
+        });
+        let submission = retry_call(
+            call_id.function,
             || async {
+                let (replace_nonce, tip) = {
+                    let mut replacement = replacement.lock().expect("poisoned");
+                    (replacement.nonce.take(), replacement.tip)
+                };
                 let signer = {
                     let mut signer = self.signer.write().await;
                     // return the current value, increment afterwards
-                    let cloned_signer = signer.clone();
-                    signer.increment_nonce();
+                    let mut cloned_signer = signer.clone();
+                    match replace_nonce {
+                        Some(replace_nonce) => cloned_signer.set_nonce(replace_nonce),
+                        None => signer.increment_nonce(),
+                    }
                     cloned_signer
                 };
-                call(signer).await
+                let signer_nonce = signer.nonce().unwrap_or_default();
+                nonce.store(signer_nonce, Ordering::SeqCst);
+                log::info!(
+                    "Submitting {} (nonce {}, tip {}, era {})",
+                    call_id,
+                    signer_nonce,
+                    tip,
+                    DisplayEra(&EXTRINSIC_ERA)
+                );
+                self.pending_nonces.insert(signer_nonce);
+                let result = with_tip(tip, with_fee_payment(self.fee_payment, call(signer))).await;
+                self.pending_nonces.remove(signer_nonce);
+                Ok(result?)
             },
-            |result| async {
-                match result.map_err(Into::<Error>::into) {
-                    Ok(ok) => Ok(ok),
-                    Err(err) if err.is_outdated_nonce() => {
-                        self.refresh_nonce().await;
-                        Err(RetryPolicy::Skip(Error::InvalidTransaction))
+            |class| async move {
+                match class {
+                    ErrorClass::OutdatedNonce => self.refresh_nonce().await,
+                    ErrorClass::PriorityTooLow => {
+                        self.resolve_conflict(&call_id, nonce.load(Ordering::SeqCst), &replacement)
+                            .await
                     }
-                    Err(err) => Err(RetryPolicy::Throw(err)),
+                    _ => {}
                 }
             },
-        )
+        );
+        let result = match instrument(CallKind::Submission, call_id.module, call_id.function, submission).await {
+            Err(err) if err.is_already_imported() => {
+                let nonce = nonce.load(Ordering::SeqCst);
+                POOL_CONFLICTS.with_label_values(&[Resolution::Watch.as_str()]).inc();
+                log::info!("{} (nonce {}) is already in the pool, watching it", call_id, nonce);
+                self.watch_nonce(nonce).await
+            }
+            result => result,
+        };
+        let Replacement { tip, extra_tip, .. } = replacement.into_inner().expect("poisoned");
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                self.tip_budget.refund(extra_tip);
+                return Err(err);
+            }
+        };
+        let fee = match find_fee_paid(&result.events) {
+            Ok(fee_paid) => fee_paid.map(|(fee, tip)| fee.saturating_sub(tip)),
+            Err(err) => {
+                log::warn!("Failed to decode fee of {}: {}", call_id, err);
+                None
+            }
+        };
+        // looking up the extrinsic takes further requests, which the caller need not wait for
+        let parachain_rpc = self.clone();
+        let (recorded_call, block_hash, extrinsic_hash) = (call_id.clone(), result.block, result.extrinsic);
+        tokio::spawn(async move {
+            if let Err(err) = parachain_rpc
+                .record_fees(&recorded_call, submitted_at, block_hash, extrinsic_hash, fee)
+                .await
+            {
+                log::warn!("Failed to record fee of {}: {}", recorded_call, err);
+            }
+        });
+        let receipt = SubmissionReceipt {
+            call: call_id,
+            nonce: nonce.into_inner(),
+            tip,
+            era: EXTRINSIC_ERA,
+            fee,
+            success: result,
+        };
+        log::info!("Included {}", receipt);
+        Ok(receipt)
+    }
+
+    /// Resolve a submission with `nonce` that conflicts with another extrinsic in the pool.
+    /// If that extrinsic was submitted by this client, or by another instance without urgency
+    /// or tip budget, the submission takes the next free nonce. Otherwise urgent submissions
+    /// replace it by outbidding its tip.
+    async fn resolve_conflict(&self, call_id: &CallId, nonce: Index, replacement: &Mutex<Replacement>) {
+        let pooled_tip = if self.pending_nonces.contains(nonce) {
+            // another submission of ours, which must not be replaced
+            None
+        } else {
+            match self.pooled_extrinsic(nonce).await {
+                Ok(pooled) => pooled.map(|pooled| pooled.tip),
+                Err(err) => {
+                    log::warn!("Failed to find extrinsic with nonce {} in the pool: {}", nonce, err);
+                    None
+                }
+            }
+        };
+        let resolution = match pooled_tip {
+            Some(pooled_tip) => {
+                let extra_tip = if is_urgent() { self.tip_budget.reserve() } else { 0 };
+                let mut replacement = replacement.lock().expect("poisoned");
+                let resolution = Resolution::of_conflict(replacement.tip, pooled_tip, extra_tip);
+                if let Resolution::Replace { tip } = resolution {
+                    replacement.nonce = Some(nonce);
+                    replacement.tip = tip;
+                    replacement.extra_tip += extra_tip;
+                }
+                resolution
+            }
+            None => Resolution::Resubmit,
+        };
+        POOL_CONFLICTS.with_label_values(&[resolution.as_str()]).inc();
+        match resolution {
+            Resolution::Replace { tip } => log::info!(
+                "Replacing extrinsic with nonce {} (tip {}) in the pool by {} (tip {})",
+                nonce,
+                pooled_tip.unwrap_or_default(),
+                call_id,
+                tip
+            ),
+            _ => {
+                log::info!(
+                    "Nonce {} is taken by an extrinsic in the pool, resubmitting {} with the next nonce",
+                    nonce,
+                    call_id
+                );
+                self.refresh_nonce().await;
+            }
+        }
+    }
+
+    /// The extrinsic of this account with the given nonce in the pool, if any.
+    async fn pooled_extrinsic(&self, nonce: Index) -> Result<Option<SignedPrefix>, Error> {
+        let pending: Vec<Bytes> = self.request("author_pendingExtrinsics", &[]).await?;
+        Ok(pending
+            .iter()
+            .filter_map(|encoded| signed_prefix(encoded))
+            .find(|prefix| prefix.signer == self.account_id && prefix.nonce == nonce))
+    }
+
+    /// Wait until the extrinsic of this account with the given nonce is finalized, e.g. one
+    /// submitted by a standby instance with the same key, and get its events.
+    async fn watch_nonce(&self, nonce: Index) -> Result<ExtrinsicSuccess<InterBtcRuntime>, Error> {
+        let mut sub = self.ext_client.subscribe_finalized_blocks().await?;
+        let watch = async {
+            // it may have been included before subscribing
+            if let Some(head) = self.get_latest_block_hash().await? {
+                if let Some(success) = self.find_extrinsic(head, nonce).await? {
+                    return Ok(success);
+                }
+            }
+            loop {
+                let header = sub.next().await.ok_or(Error::ChannelClosed)?;
+                if let Some(success) = self.find_extrinsic(header.hash(), nonce).await? {
+                    return Ok(success);
+                }
+            }
+        };
+        tokio::time::timeout(CONFLICT_WATCH_TIMEOUT, watch).await?
+    }
+
+    /// Find the extrinsic of this account with the given nonce in the block.
+    async fn find_extrinsic(
+        &self,
+        block_hash: H256,
+        nonce: Index,
+    ) -> Result<Option<ExtrinsicSuccess<InterBtcRuntime>>, Error> {
+        let block = self
+            .ext_client
+            .block(Some(block_hash))
+            .await?
+            .ok_or(Error::BlockNotFound)?
+            .block;
+        let found = block
+            .extrinsics
+            .iter()
+            .map(Encode::encode)
+            .enumerate()
+            .find(|(_, encoded)| {
+                signed_prefix(encoded).map_or(false, |prefix| {
+                    prefix.signer == self.account_id && prefix.nonce == nonce
+                })
+            });
+        let (index, encoded) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let events = self
+            .event_decoders
+            .extrinsic_events_at(&self.rpc_client, block_hash, index as u32)
+            .await?;
+        Ok(Some(ExtrinsicSuccess {
+            block: block_hash,
+            extrinsic: BlakeTwo256::hash(&encoded),
+            events,
+        }))
+    }
+
+    /// Returns true if the last observed parachain status is `Shutdown`. The status is
+    /// only kept up-to-date while `listen_for_parachain_status` is running.
+    pub fn is_parachain_shutdown(&self) -> bool {
+        *self.status_rx.borrow() == StatusCode::Shutdown
+    }
+
+    /// Wait until the parachain is no longer shut down.
+    pub async fn wait_for_parachain_running(&self) {
+        let mut status_rx = self.status_rx.clone();
+        let mut logged = false;
+        while self.is_parachain_shutdown() {
+            if !logged {
+                log::info!("Parachain is shut down, pausing extrinsic submission");
+                logged = true;
+            }
+            if status_rx.recv().await.is_none() {
+                return;
+            }
+        }
+        if logged {
+            log::info!("Parachain is running, resuming extrinsic submission");
+        }
+    }
+
+    /// Keeps track of the parachain status, so that extrinsic submission is paused
+    /// while the parachain is shut down (e.g. during maintenance) and resumed afterwards.
+    pub async fn listen_for_parachain_status(&self) -> Result<(), Error> {
+        self.on_block(|_| async move {
+            let status = self.get_parachain_status().await?;
+            let previous = self.status_rx.borrow().clone();
+            if status != previous {
+                log::info!("Parachain status changed from {:?} to {:?}", previous, status);
+                // only fails if there are no receivers, but we hold one ourselves
+                let _ = self.status_tx.broadcast(status);
+            }
+            Ok(())
+        })
         .await
     }
 
+    /// Fetch a vault regardless of its status, unlike `get_vault` this also returns
+    /// liquidated vaults and vaults that have been flagged for theft.
+    pub async fn get_vault_unchecked(&self, vault_id: AccountId) -> Result<InterBtcVault, Error> {
+        let head = self.get_latest_block_hash().await?;
+        match query::<VaultsStore<_>, _, _, _>(self.ext_client.vaults(vault_id.clone(), head)).await? {
+            vault if vault.id == vault_id => Ok(vault),
+            _ => Err(Error::VaultNotFound),
+        }
+    }
+
+    /// Get how long ago the exchange rate was last updated by the oracles.
+    pub async fn get_oracle_staleness(&self) -> Result<OracleStaleness, Error> {
+        let (get_info, get_now) = futures::join!(self.get_exchange_rate_info(), self.get_time_now());
+        let (_, last_updated, max_delay) = get_info?;
+        Ok(OracleStaleness {
+            last_updated,
+            now: get_now?,
+            max_delay,
+        })
+    }
+
+    /// Checks the staleness of the exchange rate on every block and calls `on_change`
+    /// whenever it crosses the given threshold, as well as once with the initial state.
+    pub async fn on_oracle_staleness_change<F, R>(&self, threshold: Duration, on_change: F) -> Result<(), Error>
+    where
+        F: Fn(bool, OracleStaleness) -> R,
+        R: Future<Output = ()>,
+    {
+        let was_stale = std::sync::Mutex::new(None);
+        let was_stale = &was_stale;
+        let on_change = &on_change;
+        self.on_block(|_| async move {
+            let staleness = self.get_oracle_staleness().await?;
+            let is_stale = staleness.is_stale(threshold);
+            let changed = was_stale.lock().expect("poisoned").replace(is_stale) != Some(is_stale);
+            if changed {
+                on_change(is_stale, staleness).await;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Compares the timestamp of every new best block with the local time at which it arrived
+    /// and calls `on_change` whenever the drift crosses the given threshold, as well as once with
+    /// the initial state. Blocks that do not extend the highest block seen so far (e.g. re-orgs
+    /// or blocks delivered again after a reconnect) arrived long after their timestamp, so they
+    /// are not compared. For the same reason, blocks skipped by the subscription are not refetched.
+    pub async fn on_timestamp_drift_change<F, R>(&self, threshold: Duration, on_change: F) -> Result<(), Error>
+    where
+        F: Fn(bool, TimestampDrift) -> R,
+        R: Future<Output = ()>,
+    {
+        let mut sub = self.ext_client.subscribe_blocks().await?;
+        let mut tip: Option<BlockNumber> = None;
+        let mut was_drifting = None;
+        loop {
+            let header = sub.next().await.ok_or(Error::ChannelClosed)?;
+            if matches!(tip, Some(tip) if header.number <= tip) {
+                continue;
+            }
+            tip = Some(header.number);
+
+            let local_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_millis() as u64)
+                .unwrap_or_default();
+            let drift = TimestampDrift {
+                block_timestamp: query::<NowStore<_>, _, _, _>(self.ext_client.now(Some(header.hash()))).await?,
+                local_time,
+            };
+            TIMESTAMP_DRIFT.set(drift.drift_ms() as f64 / 1000.0);
+            let is_drifting = drift.exceeds(threshold);
+            if was_drifting.replace(is_drifting) != Some(is_drifting) {
+                on_change(is_drifting, drift).await;
+            }
+        }
+    }
+
+    /// Submits the execution of a request such that retrying it is safe: nothing is submitted
+    /// if the request is already completed, and a failed submission counts as success if the
+    /// request turns out to be completed, e.g. when the response to an included extrinsic was
+    /// lost and the retry failed with `IssueCompleted`. The submission is retried with
+    /// [`retry_execution`]. Returns the receipt of the submission, `None` if the request was
+    /// completed without it.
+    async fn execute_idempotent<C, F, S, FS>(
+        &self,
+        call_id: CallId,
+        is_completed: C,
+        submit: S,
+    ) -> Result<Option<SubmissionReceipt>, Error>
+    where
+        C: Fn() -> F,
+        F: Future<Output = Result<bool, Error>>,
+        S: Fn() -> FS,
+        FS: Future<Output = Result<SubmissionReceipt, Error>>,
+    {
+        if is_completed().await? {
+            log::info!("Not submitting {}, the request is already completed", call_id);
+            return Ok(None);
+        }
+        match retry_execution(call_id.function, submit).await {
+            Ok(receipt) => Ok(Some(receipt)),
+            Err(err) if err.is_request_completed() => {
+                log::info!("{} has already been completed", call_id);
+                Ok(None)
+            }
+            Err(err) => match is_completed().await {
+                Ok(true) => {
+                    log::info!("{} has been completed despite error: {}", call_id, err);
+                    Ok(None)
+                }
+                _ => Err(err),
+            },
+        }
+    }
+
     pub async fn get_latest_block_hash(&self) -> Result<Option<H256>, Error> {
         Ok(Some(self.ext_client.finalized_head().await?))
     }
@@ -136,18 +872,142 @@ impl InterBtcParachain {
         Ok(self.ext_client.block::<H256>(head).await?)
     }
 
-    /// Subscribe to new parachain blocks.
+    /// Subscribe to new finalized parachain blocks. Blocks skipped by the subscription
+    /// are refetched, so that every block is delivered in order.
     pub async fn on_block<F, R>(&self, on_block: F) -> Result<(), Error>
     where
         F: Fn(InterBtcHeader) -> R,
         R: Future<Output = Result<(), Error>>,
     {
-        let mut sub = self.ext_client.subscribe_finalized_blocks().await?;
+        self.track_blocks(true, on_block).await
+    }
+
+    /// Subscribe to new best parachain blocks, which may be re-orged. Blocks skipped by
+    /// the subscription are refetched.
+    pub async fn on_best_block<F, R>(&self, on_block: F) -> Result<(), Error>
+    where
+        F: Fn(InterBtcHeader) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        self.track_blocks(false, on_block).await
+    }
+
+    /// Follow the balances of the subscription, `on_update` is called with the changes of each
+    /// finalized block that changed any of them, starting with all balances on the first block.
+    /// The balances are also shared with the clones of this client, see `get_cached_balance`.
+    pub async fn on_balance_change<F, R>(&self, subscription: BalanceSubscription, on_update: F) -> Result<(), Error>
+    where
+        F: Fn(BalanceUpdate) -> R,
+        R: Future<Output = ()>,
+    {
+        let subscription = &subscription;
+        let on_update = &on_update;
+        // changes are relative to what this subscription last saw, not to the shared cache,
+        // which other subscriptions of the same balance update as well
+        let tracker = &Mutex::new(BalanceTracker::default());
+        self.on_block(|header| async move {
+            let hash = Some(header.hash());
+            let mut changes = Vec::new();
+            for (account_id, currency_id) in subscription.keys() {
+                let data =
+                    query::<AccountsStore<_>, _, _, _>(self.ext_client.accounts(account_id.clone(), currency_id, hash))
+                        .await?;
+                let balance = AccountBalance {
+                    free: data.free,
+                    reserved: data.reserved,
+                };
+                self.balances.set(account_id, currency_id, balance);
+                changes.extend(
+                    tracker
+                        .lock()
+                        .expect("poisoned")
+                        .update(account_id, currency_id, balance),
+                );
+            }
+            if !changes.is_empty() {
+                on_update(BalanceUpdate {
+                    block: header.number,
+                    changes,
+                })
+                .await;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// The latest balance delivered by `on_balance_change`, `None` if it is not followed.
+    pub fn get_cached_balance(&self, account_id: &AccountId, currency_id: CurrencyId) -> Option<AccountBalance> {
+        self.balances.get(account_id, currency_id)
+    }
+
+    async fn track_blocks<F, R>(&self, finalized: bool, on_block: F) -> Result<(), Error>
+    where
+        F: Fn(InterBtcHeader) -> R,
+        R: Future<Output = Result<(), Error>>,
+    {
+        let (subscription, mut sub) = if finalized {
+            ("finalized", self.ext_client.subscribe_finalized_blocks().await?)
+        } else {
+            ("best", self.ext_client.subscribe_blocks().await?)
+        };
+        let mut tracker = BlockTracker::default();
         loop {
-            on_block(sub.next().await.ok_or(Error::ChannelClosed)?).await?;
+            let header = sub.next().await.ok_or(Error::ChannelClosed)?;
+            self.observe_block_latency(subscription, &header).await;
+
+            let missing = tracker.next(header.number);
+            if !missing.is_empty() {
+                log::warn!(
+                    "Missed {} {} blocks before block {}, refetching",
+                    missing.len(),
+                    subscription,
+                    header.number
+                );
+                MISSED_BLOCKS
+                    .with_label_values(&[subscription])
+                    .inc_by(missing.len() as u64);
+                for number in missing {
+                    on_block(self.get_header_at(number).await?).await?;
+                }
+            }
+
+            if finalized {
+                if let Ok(Some(best)) = self.ext_client.header::<H256>(None).await {
+                    CHAIN_LAG.set(best.number.saturating_sub(header.number) as i64);
+                }
+            }
+            on_block(header).await?;
         }
     }
 
+    async fn get_header_at(&self, number: BlockNumber) -> Result<InterBtcHeader, Error> {
+        let hash = self
+            .ext_client
+            .block_hash(Some(number.into()))
+            .await?
+            .ok_or(Error::BlockNotFound)?;
+        self.ext_client.header(Some(hash)).await?.ok_or(Error::BlockNotFound)
+    }
+
+    /// Record the time between the block's timestamp and now.
+    async fn observe_block_latency(&self, subscription: &str, header: &InterBtcHeader) {
+        let timestamp = match query::<NowStore<_>, _, _, _>(self.ext_client.now(Some(header.hash()))).await {
+            Ok(timestamp) => timestamp,
+            Err(err) => {
+                log::debug!("Failed to get timestamp of block {}: {}", header.number, err);
+                return;
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_millis() as u64)
+            .unwrap_or_default();
+        BLOCK_LATENCY
+            .with_label_values(&[subscription])
+            .observe(now.saturating_sub(timestamp) as f64 / 1000.0);
+    }
+
     /// Subscription service that should listen forever, only returns if the initial subscription
     /// cannot be established. Calls `on_error` when an error event has been received, or when an
     /// event has been received that failed to be decoded into a raw event.
@@ -239,25 +1099,106 @@ impl InterBtcParachain {
         Ok(())
     }
 
+    /// Decode the events of a past block with the metadata of the runtime version it was
+    /// produced with, which may differ from the one of the subscriptions after an upgrade.
+    pub async fn get_events_at(&self, hash: H256) -> Result<Vec<RawEvent>, Error> {
+        self.event_decoders.events_at(&self.rpc_client, hash).await
+    }
+
+    /// Get the events of type `T` emitted in the blocks `from..=to`, e.g. to recover the
+    /// events missed during an outage spanning runtime upgrades.
+    pub async fn get_past_events<T: Event<InterBtcRuntime>>(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> Result<Vec<(BlockNumber, T)>, Error> {
+        let mut events = Vec::new();
+        for number in from..=to {
+            let hash = self.get_header_at(number).await?.hash();
+            for raw_event in self.get_events_at(hash).await? {
+                if raw_event.module == T::MODULE && raw_event.variant == T::EVENT {
+                    events.push((number, T::decode(&mut &raw_event.data[..])?));
+                }
+            }
+        }
+        Ok(events)
+    }
+
     async fn sudo<C: Call<InterBtcRuntime> + Clone>(&self, call: C) -> Result<(), Error> {
         let encoded_call = &self.ext_client.encode(call.clone())?;
-        self.with_unique_signer(|signer| async move { self.ext_client.sudo_and_watch(&signer, encoded_call).await })
-            .await?;
+        // sudo must not be blocked by the parachain status, since it is used to change it
+        self.with_unique_signer_unchecked(CallId::new("Sudo", "sudo", &encoded_call), |signer| async move {
+            self.ext_client.sudo_and_watch(&signer, encoded_call).await
+        })
+        .await?;
         Ok(())
     }
 
+    /// Submit the calls in a single `utility.batch`. The batch is included even if one of
+    /// the calls fails, in which case the calls before it are applied and the error of the
+    /// failing call is returned.
     async fn batch<C: Call<InterBtcRuntime>>(&self, calls: Vec<C>) -> Result<(), Error> {
-        let encoded_calls = &calls
+        let encoded_calls = calls
             .into_iter()
             .map(|call| self.ext_client.encode(call))
             .collect::<Result<Vec<_>, _>>()?;
-        self.with_unique_signer(|signer| async move {
-            self.ext_client.batch_and_watch(&signer, encoded_calls.clone()).await
-        })
-        .await?;
+        let call_id = CallId::new("Utility", "batch", &encoded_calls);
+        self.submit_batch(call_id, encoded_calls).await?;
         Ok(())
     }
 
+    async fn submit_batch(&self, call_id: CallId, encoded_calls: Vec<Encoded>) -> Result<SubmissionReceipt, Error> {
+        let encoded_calls = &encoded_calls;
+        let receipt = self
+            .with_unique_signer(call_id, |signer| async move {
+                self.ext_client.batch_and_watch(&signer, encoded_calls.clone()).await
+            })
+            .await?;
+        match find_batch_interruption(&receipt.events)? {
+            Some((index, err)) => {
+                log::warn!(
+                    "Call {} of {} in {} failed, the calls before it were applied",
+                    index,
+                    encoded_calls.len(),
+                    receipt.call
+                );
+                Err(
+                    match SubxtRuntimeError::from_dispatch(self.ext_client.metadata(), err) {
+                        Ok(err) => Error::SubxtError(SubxtError::Runtime(err)),
+                        Err(err) => Error::SubxtError(err),
+                    },
+                )
+            }
+            None => Ok(receipt),
+        }
+    }
+
+    /// Submit the call, in a batch with a `system.remark` if a remark is set by `with_remark`,
+    /// so that the extrinsic can be traced back to the request it belongs to.
+    async fn submit_remarked<C: Call<InterBtcRuntime> + Clone + Send + Sync>(
+        &self,
+        call_id: CallId,
+        call: C,
+    ) -> Result<SubmissionReceipt, Error> {
+        match current_remark() {
+            Some(remark) => {
+                let remark = crate::frame_system::RemarkCall {
+                    remark,
+                    _runtime: PhantomData {},
+                };
+                let encoded_calls = vec![self.ext_client.encode(call)?, self.ext_client.encode(remark)?];
+                self.submit_batch(call_id, encoded_calls).await
+            }
+            None => {
+                let call = &call;
+                self.with_unique_signer(call_id, |signer| async move {
+                    self.ext_client.watch(call.clone(), &signer).await
+                })
+                .await
+            }
+        }
+    }
+
     async fn set_storage<V: Encode>(&self, module: &str, key: &str, value: V) -> Result<(), Error> {
         let module = sp_core::twox_128(module.as_bytes());
         let item = sp_core::twox_128(key.as_bytes());
@@ -331,7 +1272,11 @@ impl CollateralBalancesPallet for InterBtcParachain {
 
     async fn get_free_balance_for_id(&self, id: AccountId) -> Result<<InterBtcRuntime as Core>::Balance, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.accounts(id.clone(), CurrencyId::DOT, head).await?.free)
+        Ok(
+            query::<AccountsStore<_>, _, _, _>(self.ext_client.accounts(id.clone(), COLLATERAL_CURRENCY, head))
+                .await?
+                .free,
+        )
     }
 
     async fn get_reserved_balance(&self) -> Result<<InterBtcRuntime as Core>::Balance, Error> {
@@ -340,19 +1285,31 @@ impl CollateralBalancesPallet for InterBtcParachain {
 
     async fn get_reserved_balance_for_id(&self, id: AccountId) -> Result<<InterBtcRuntime as Core>::Balance, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self
-            .ext_client
-            .accounts(id.clone(), CurrencyId::DOT, head)
-            .await?
-            .reserved)
+        Ok(
+            query::<AccountsStore<_>, _, _, _>(self.ext_client.accounts(id.clone(), COLLATERAL_CURRENCY, head))
+                .await?
+                .reserved,
+        )
     }
 
     async fn transfer_to(&self, recipient: &AccountId, amount: u128) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .transfer_and_watch(&signer, &recipient, CurrencyId::DOT, amount)
-                .await
-        })
+        self.check_call(
+            TransferCall {
+                dest: recipient,
+                currency_id: COLLATERAL_CURRENCY,
+                amount,
+            },
+            amount,
+        )
+        .await?;
+        self.with_unique_signer(
+            CallId::new("Tokens", "transfer", &(&recipient, &COLLATERAL_CURRENCY, &amount)),
+            |signer| async move {
+                self.ext_client
+                    .transfer_and_watch(&signer, &recipient, COLLATERAL_CURRENCY, amount)
+                    .await
+            },
+        )
         .await?;
         Ok(())
     }
@@ -402,7 +1359,14 @@ pub trait ReplacePallet {
     /// * `replace_id` - the ID of the replacement request
     /// * 'merkle_proof' - the merkle root of the block
     /// * `raw_tx` - the transaction id in bytes
-    async fn execute_replace(&self, replace_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error>;
+    ///
+    /// Returns the receipt of the submission, `None` if the request was already executed.
+    async fn execute_replace(
+        &self,
+        replace_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error>;
 
     /// Cancel vault replacement
     ///
@@ -442,17 +1406,29 @@ pub trait ReplacePallet {
 #[async_trait]
 impl ReplacePallet for InterBtcParachain {
     async fn request_replace(&self, amount: u128, griefing_collateral: u128) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .request_replace_and_watch(&signer, amount, griefing_collateral)
-                .await
-        })
+        self.check_call(
+            RequestReplaceCall {
+                btc_amount: amount,
+                griefing_collateral,
+            },
+            griefing_collateral,
+        )
+        .await?;
+        self.with_unique_signer(
+            CallId::new("Replace", "request_replace", &(&amount, &griefing_collateral)),
+            |signer| async move {
+                self.ext_client
+                    .request_replace_and_watch(&signer, amount, griefing_collateral)
+                    .await
+            },
+        )
         .await?;
         Ok(())
     }
 
     async fn withdraw_replace(&self, amount: u128) -> Result<(), Error> {
         self.with_unique_signer(
+            CallId::new("Replace", "withdraw_replace", &amount),
             |signer| async move { self.ext_client.withdraw_replace_and_watch(&signer, amount).await },
         )
         .await?;
@@ -466,28 +1442,52 @@ impl ReplacePallet for InterBtcParachain {
         collateral: u128,
         btc_address: BtcAddress,
     ) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .accept_replace_and_watch(&signer, old_vault, amount_btc, collateral, btc_address)
-                .await
-        })
+        self.with_unique_signer(
+            CallId::new(
+                "Replace",
+                "accept_replace",
+                &(&old_vault, &amount_btc, &collateral, &btc_address),
+            ),
+            |signer| async move {
+                self.ext_client
+                    .accept_replace_and_watch(&signer, old_vault, amount_btc, collateral, btc_address)
+                    .await
+            },
+        )
         .await?;
         Ok(())
     }
 
-    async fn execute_replace(&self, replace_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .execute_replace_and_watch(&signer, replace_id, merkle_proof, raw_tx)
-                .await
-        })
-        .await?;
-        Ok(())
+    async fn execute_replace(
+        &self,
+        replace_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error> {
+        let call_id = CallId::new("Replace", "execute_replace", &(&replace_id, &merkle_proof, &raw_tx));
+        self.execute_idempotent(
+            call_id.clone(),
+            || async move {
+                let request = self.get_replace_request(replace_id).await?;
+                Ok(request.status == ReplaceRequestStatus::Completed)
+            },
+            || async {
+                let call = ExecuteReplaceCall {
+                    replace_id,
+                    merkle_proof,
+                    raw_tx,
+                };
+                self.check_call(call.clone(), 0).await?;
+                self.submit_remarked(call_id.clone(), call).await
+            },
+        )
+        .await
     }
 
     async fn cancel_replace(&self, replace_id: H256) -> Result<(), Error> {
-        self.with_unique_signer(
-            |signer| async move { self.ext_client.cancel_replace_and_watch(&signer, replace_id).await },
+        self.submit_remarked(
+            CallId::new("Replace", "cancel_replace", &replace_id),
+            CancelReplaceCall { replace_id },
         )
         .await?;
         Ok(())
@@ -500,7 +1500,6 @@ impl ReplacePallet for InterBtcParachain {
     ) -> Result<Vec<(H256, InterBtcReplaceRequest)>, Error> {
         let head = self.get_latest_block_hash().await?;
         let result: Vec<(H256, InterBtcReplaceRequest)> = self
-            .rpc_client
             .request(
                 "replace_getNewVaultReplaceRequests",
                 &[to_json_value(account_id)?, to_json_value(head)?],
@@ -517,7 +1516,6 @@ impl ReplacePallet for InterBtcParachain {
     ) -> Result<Vec<(H256, InterBtcReplaceRequest)>, Error> {
         let head = self.get_latest_block_hash().await?;
         let result: Vec<(H256, InterBtcReplaceRequest)> = self
-            .rpc_client
             .request(
                 "replace_getOldVaultReplaceRequests",
                 &[to_json_value(account_id)?, to_json_value(head)?],
@@ -529,7 +1527,7 @@ impl ReplacePallet for InterBtcParachain {
 
     async fn get_replace_period(&self) -> Result<u32, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.replace_period(head).await?)
+        Ok(query::<ReplacePeriodStore<_>, _, _, _>(self.ext_client.replace_period(head)).await?)
     }
 
     async fn set_replace_period(&self, period: u32) -> Result<(), Error> {
@@ -543,12 +1541,12 @@ impl ReplacePallet for InterBtcParachain {
 
     async fn get_replace_request(&self, replace_id: H256) -> Result<InterBtcReplaceRequest, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.replace_requests(replace_id, head).await?)
+        Ok(query::<ReplaceRequestsStore<_>, _, _, _>(self.ext_client.replace_requests(replace_id, head)).await?)
     }
 
     async fn get_replace_dust_amount(&self) -> Result<u128, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.replace_btc_dust_value(head).await?)
+        Ok(query::<ReplaceBtcDustValueStore<_>, _, _, _>(self.ext_client.replace_btc_dust_value(head)).await?)
     }
 }
 
@@ -562,7 +1560,7 @@ impl TimestampPallet for InterBtcParachain {
     /// Get the current time as defined by the `timestamp` pallet.
     async fn get_time_now(&self) -> Result<u64, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.now(head).await?)
+        Ok(query::<NowStore<_>, _, _, _>(self.ext_client.now(head)).await?)
     }
 }
 
@@ -572,15 +1570,17 @@ pub trait ExchangeRateOraclePallet {
 
     async fn set_exchange_rate_info(&self, collateral_per_wrapped: FixedU128) -> Result<(), Error>;
 
+    async fn is_exchange_rate_listed(&self, currency_id: CurrencyId) -> Result<bool, Error>;
+
     async fn insert_authorized_oracle(&self, account_id: AccountId, name: String) -> Result<(), Error>;
 
     async fn set_btc_tx_fees_per_byte(&self, fast: u32, half: u32, hour: u32) -> Result<(), Error>;
 
     async fn get_btc_tx_fees_per_byte(&self) -> Result<BtcTxFeesPerByte, Error>;
 
-    async fn wrapped_to_collateral(&self, amount: u128) -> Result<u128, Error>;
+    async fn wrapped_to_collateral(&self, amount: WrappedAmount) -> Result<CollateralAmount, Error>;
 
-    async fn collateral_to_wrapped(&self, amount: u128) -> Result<u128, Error>;
+    async fn collateral_to_wrapped(&self, amount: CollateralAmount) -> Result<WrappedAmount, Error>;
 }
 
 #[async_trait]
@@ -589,9 +1589,9 @@ impl ExchangeRateOraclePallet for InterBtcParachain {
     /// and the configured max delay.
     async fn get_exchange_rate_info(&self) -> Result<(FixedU128, u64, u64), Error> {
         let head = self.get_latest_block_hash().await?;
-        let get_rate = self.ext_client.exchange_rate(head);
-        let get_time = self.ext_client.last_exchange_rate_time(head);
-        let get_delay = self.ext_client.max_delay(head);
+        let get_rate = query::<ExchangeRateStore<_>, _, _, _>(self.ext_client.exchange_rate(head));
+        let get_time = query::<LastExchangeRateTimeStore<_>, _, _, _>(self.ext_client.last_exchange_rate_time(head));
+        let get_delay = query::<MaxDelayStore<_>, _, _, _>(self.ext_client.max_delay(head));
 
         match tokio::try_join!(get_rate, get_time, get_delay) {
             Ok((rate, time, delay)) => Ok((rate, time, delay)),
@@ -604,15 +1604,44 @@ impl ExchangeRateOraclePallet for InterBtcParachain {
     /// # Arguments
     /// * `collateral_per_wrapped` - the current exchange rate
     async fn set_exchange_rate_info(&self, collateral_per_wrapped: FixedU128) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .set_exchange_rate_and_watch(&signer, collateral_per_wrapped)
-                .await
-        })
+        self.with_unique_signer(
+            CallId::new("ExchangeRateOracle", "set_exchange_rate", &collateral_per_wrapped),
+            |signer| async move {
+                self.ext_client
+                    .set_exchange_rate_and_watch(&signer, collateral_per_wrapped)
+                    .await
+            },
+        )
         .await?;
         Ok(())
     }
 
+    /// Whether the exchange rate to the currency is listed, i.e. whether its feed is in the
+    /// oracle keys of the runtime. A runtime upgrade may remove or re-add the key. Runtimes
+    /// without keyed feeds accept the exchange rate if their metadata has the call setting it
+    /// and the storage keeping it, the metadata is that of the runtime at the time of connecting.
+    async fn is_exchange_rate_listed(&self, currency_id: CurrencyId) -> Result<bool, Error> {
+        let metadata = self.ext_client.metadata();
+        let has_oracle_keys = metadata
+            .module(EXCHANGE_RATE_ORACLE_MODULE)
+            .and_then(|module| module.storage("OracleKeys"))
+            .is_ok();
+        if !has_oracle_keys {
+            let has_call = metadata
+                .module_with_calls(EXCHANGE_RATE_ORACLE_MODULE)
+                .and_then(|module| module.call("set_exchange_rate", FixedU128::default()))
+                .is_ok();
+            let has_storage = metadata
+                .module(EXCHANGE_RATE_ORACLE_MODULE)
+                .and_then(|module| module.storage("ExchangeRate"))
+                .is_ok();
+            return Ok(has_call && has_storage);
+        }
+        let head = self.get_latest_block_hash().await?;
+        let oracle_keys = query::<OracleKeysStore<_>, _, _, _>(self.ext_client.oracle_keys(head)).await?;
+        Ok(oracle_keys.contains(&OracleKey::ExchangeRate(currency_id)))
+    }
+
     /// Adds a new authorized oracle with the given name and the signer's AccountId
     ///
     /// # Arguments
@@ -636,11 +1665,14 @@ impl ExchangeRateOraclePallet for InterBtcParachain {
     /// * `half` - The estimated Satoshis per bytes to get included in the next 3 blocks (~half hour)
     /// * `hour` - The estimated Satoshis per bytes to get included in the next 6 blocks (~hour)
     async fn set_btc_tx_fees_per_byte(&self, fast: u32, half: u32, hour: u32) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .set_btc_tx_fees_per_byte_and_watch(&signer, fast, half, hour)
-                .await
-        })
+        self.with_unique_signer(
+            CallId::new("ExchangeRateOracle", "set_btc_tx_fees_per_byte", &(&fast, &half, &hour)),
+            |signer| async move {
+                self.ext_client
+                    .set_btc_tx_fees_per_byte_and_watch(&signer, fast, half, hour)
+                    .await
+            },
+        )
         .await?;
         Ok(())
     }
@@ -649,35 +1681,43 @@ impl ExchangeRateOraclePallet for InterBtcParachain {
     /// in the next x blocks
     async fn get_btc_tx_fees_per_byte(&self) -> Result<BtcTxFeesPerByte, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.satoshi_per_bytes(head).await?)
+        Ok(query::<SatoshiPerBytesStore<_>, _, _, _>(self.ext_client.satoshi_per_bytes(head)).await?)
     }
 
     /// Converts the amount in btc to dot, based on the current set exchange rate.
-    async fn wrapped_to_collateral(&self, amount: u128) -> Result<u128, Error> {
+    async fn wrapped_to_collateral(&self, amount: WrappedAmount) -> Result<CollateralAmount, Error> {
         let head = self.get_latest_block_hash().await?;
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "exchangeRateOracle_wrappedToCollateral",
-                &[to_json_value(BalanceWrapper { amount })?, to_json_value(head)?],
+                &[
+                    to_json_value(BalanceWrapper {
+                        amount: amount.planck(),
+                    })?,
+                    to_json_value(head)?,
+                ],
             )
             .await?;
 
-        Ok(result.amount)
+        Ok(Amount::from_planck(result.amount))
     }
 
     /// Converts the amount in dot to btc, based on the current set exchange rate.
-    async fn collateral_to_wrapped(&self, amount: u128) -> Result<u128, Error> {
+    async fn collateral_to_wrapped(&self, amount: CollateralAmount) -> Result<WrappedAmount, Error> {
         let head = self.get_latest_block_hash().await?;
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "exchangeRateOracle_collateralToWrapped",
-                &[to_json_value(BalanceWrapper { amount })?, to_json_value(head)?],
+                &[
+                    to_json_value(BalanceWrapper {
+                        amount: amount.planck(),
+                    })?,
+                    to_json_value(head)?,
+                ],
             )
             .await?;
 
-        Ok(result.amount)
+        Ok(Amount::from_planck(result.amount))
     }
 }
 
@@ -705,11 +1745,19 @@ impl StakedRelayerPallet for InterBtcParachain {
     /// * `merkle_proof` - merkle proof to verify inclusion
     /// * `raw_tx` - raw transaction
     async fn report_vault_theft(&self, vault_id: &AccountId, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .report_vault_theft_and_watch(&signer, vault_id, merkle_proof, raw_tx)
-                .await
-        })
+        // theft reports race against other reporters, so always tip
+        urgent(self.with_unique_signer(
+            CallId::new(
+                "StakedRelayers",
+                "report_vault_theft",
+                &(&vault_id, &merkle_proof, &raw_tx),
+            ),
+            |signer| async move {
+                self.ext_client
+                    .report_vault_theft_and_watch(&signer, vault_id, merkle_proof, raw_tx)
+                    .await
+            },
+        ))
         .await?;
         Ok(())
     }
@@ -727,12 +1775,11 @@ impl StakedRelayerPallet for InterBtcParachain {
     async fn is_transaction_invalid(&self, vault_id: &AccountId, raw_tx: &[u8]) -> Result<bool, Error> {
         let head = self.get_latest_block_hash().await?;
         Ok(matches!(
-            self.rpc_client
-                .request(
-                    "stakedRelayers_isTransactionInvalid",
-                    &[to_json_value(vault_id)?, to_json_value(raw_tx)?, to_json_value(head)?],
-                )
-                .await,
+            self.request(
+                "stakedRelayers_isTransactionInvalid",
+                &[to_json_value(vault_id)?, to_json_value(raw_tx)?, to_json_value(head)?],
+            )
+            .await,
             Ok(()),
         ))
     }
@@ -748,6 +1795,7 @@ impl StakedRelayerPallet for InterBtcParachain {
         // TODO: can we initialize the relay through the chain-spec?
         // we would also need to consider re-initialization per governance
         self.with_unique_signer(
+            CallId::new("StakedRelayers", "initialize", &(&header, &height)),
             |signer| async move { self.ext_client.initialize_and_watch(&signer, header, height).await },
         )
         .await?;
@@ -760,6 +1808,7 @@ impl StakedRelayerPallet for InterBtcParachain {
     /// * `header` - raw block header
     async fn store_block_header(&self, header: RawBlockHeader) -> Result<(), Error> {
         self.with_unique_signer(
+            CallId::new("StakedRelayers", "store_block_header", &header),
             |signer| async move { self.ext_client.store_block_header_and_watch(&signer, header).await },
         )
         .await?;
@@ -800,18 +1849,18 @@ impl SecurityPallet for InterBtcParachain {
     /// Should be one of; `Running`, `Error` or `Shutdown`.
     async fn get_parachain_status(&self) -> Result<StatusCode, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.parachain_status(head).await?)
+        Ok(query::<ParachainStatusStore<_>, _, _, _>(self.ext_client.parachain_status(head)).await?)
     }
     /// Return any `ErrorCode`s set in the security module.
     async fn get_error_codes(&self) -> Result<BTreeSet<ErrorCode>, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.errors(head).await?)
+        Ok(query::<ErrorsStore<_>, _, _, _>(self.ext_client.errors(head)).await?)
     }
 
     /// Gets the current active block number of the parachain
     async fn get_current_active_block_number(&self) -> Result<u32, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.active_block_count(head).await?)
+        Ok(query::<ActiveBlockCountStore<_>, _, _, _>(self.ext_client.active_block_count(head)).await?)
     }
 }
 
@@ -820,13 +1869,19 @@ pub trait IssuePallet {
     /// Request a new issue
     async fn request_issue(
         &self,
-        amount: u128,
+        amount: WrappedAmount,
         vault_id: &AccountId,
-        griefing_collateral: u128,
+        griefing_collateral: CollateralAmount,
     ) -> Result<InterBtcRequestIssueEvent, Error>;
 
-    /// Execute a issue request by providing a Bitcoin transaction inclusion proof
-    async fn execute_issue(&self, issue_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error>;
+    /// Execute a issue request by providing a Bitcoin transaction inclusion proof. Returns the
+    /// receipt of the submission, `None` if the request was already executed.
+    async fn execute_issue(
+        &self,
+        issue_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error>;
 
     /// Cancel an ongoing issue request
     async fn cancel_issue(&self, issue_id: H256) -> Result<(), Error>;
@@ -847,33 +1902,67 @@ pub trait IssuePallet {
 impl IssuePallet for InterBtcParachain {
     async fn request_issue(
         &self,
-        amount: u128,
+        amount: WrappedAmount,
         vault_id: &AccountId,
-        griefing_collateral: u128,
+        griefing_collateral: CollateralAmount,
     ) -> Result<InterBtcRequestIssueEvent, Error> {
+        let (amount, griefing_collateral) = (amount.planck(), griefing_collateral.planck());
+        self.check_call(
+            RequestIssueCall {
+                amount,
+                vault_id,
+                griefing_collateral,
+            },
+            griefing_collateral,
+        )
+        .await?;
         let result = self
-            .with_unique_signer(|signer| async move {
-                self.ext_client
-                    .request_issue_and_watch(&signer, amount, vault_id, griefing_collateral)
-                    .await
-            })
+            .with_unique_signer(
+                CallId::new("Issue", "request_issue", &(&amount, &vault_id, &griefing_collateral)),
+                |signer| async move {
+                    self.ext_client
+                        .request_issue_and_watch(&signer, amount, vault_id, griefing_collateral)
+                        .await
+                },
+            )
             .await?;
         result.request_issue()?.ok_or(Error::RequestIssueIDNotFound)
     }
 
-    async fn execute_issue(&self, issue_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .execute_issue_and_watch(&signer, issue_id, merkle_proof, raw_tx)
-                .await
-        })
-        .await?;
-        Ok(())
+    async fn execute_issue(
+        &self,
+        issue_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error> {
+        let call_id = CallId::new("Issue", "execute_issue", &(&issue_id, &merkle_proof, &raw_tx));
+        self.execute_idempotent(
+            call_id.clone(),
+            || async move {
+                let request = self.get_issue_request(issue_id).await?;
+                Ok(matches!(request.status, IssueRequestStatus::Completed(_)))
+            },
+            || async {
+                let call = ExecuteIssueCall {
+                    issue_id,
+                    merkle_proof,
+                    raw_tx,
+                    _runtime: PhantomData,
+                };
+                self.check_call(call.clone(), 0).await?;
+                self.submit_remarked(call_id.clone(), call).await
+            },
+        )
+        .await
     }
 
     async fn cancel_issue(&self, issue_id: H256) -> Result<(), Error> {
-        self.with_unique_signer(
-            |signer| async move { self.ext_client.cancel_issue_and_watch(&signer, issue_id).await },
+        self.submit_remarked(
+            CallId::new("Issue", "cancel_issue", &issue_id),
+            CancelIssueCall {
+                issue_id,
+                _runtime: PhantomData,
+            },
         )
         .await?;
         Ok(())
@@ -881,7 +1970,7 @@ impl IssuePallet for InterBtcParachain {
 
     async fn get_issue_request(&self, issue_id: H256) -> Result<InterBtcIssueRequest, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.issue_requests(issue_id, head).await?)
+        Ok(query::<IssueRequestsStore<_>, _, _, _>(self.ext_client.issue_requests(issue_id, head)).await?)
     }
 
     async fn get_vault_issue_requests(
@@ -889,20 +1978,32 @@ impl IssuePallet for InterBtcParachain {
         account_id: AccountId,
     ) -> Result<Vec<(H256, InterBtcIssueRequest)>, Error> {
         let head = self.get_latest_block_hash().await?;
-        let result: Vec<(H256, InterBtcIssueRequest)> = self
-            .rpc_client
+        let result: Result<Vec<(H256, InterBtcIssueRequest)>, Error> = self
             .request(
                 "issue_getVaultIssueRequests",
-                &[to_json_value(account_id)?, to_json_value(head)?],
+                &[to_json_value(account_id.clone())?, to_json_value(head)?],
             )
-            .await?;
+            .await
+            .map_err(Into::into);
+        match result {
+            Err(err) if err.is_method_not_found() => {}
+            result => return result,
+        }
 
-        Ok(result)
+        // the node does not index the requests by vault, scan all of them instead
+        let mut issue_requests = Vec::new();
+        let mut pages = self.storage_pages::<IssueRequestsStore<_>>(head);
+        while let Some((key, request)) = pages.next().await? {
+            if request.vault == account_id {
+                issue_requests.push((h256_key(&key), request));
+            }
+        }
+        Ok(issue_requests)
     }
 
     async fn get_issue_period(&self) -> Result<u32, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.issue_period(head).await?)
+        Ok(query::<IssuePeriodStore<_>, _, _, _>(self.ext_client.issue_period(head)).await?)
     }
 
     async fn set_issue_period(&self, period: u32) -> Result<(), Error> {
@@ -920,13 +2021,10 @@ impl IssuePallet for InterBtcParachain {
 
         let mut issue_requests = Vec::new();
         let head = self.get_latest_block_hash().await?;
-        let mut iter = self.ext_client.issue_requests_iter(head).await?;
-        while let Some((issue_id, request)) = iter.next().await? {
+        let mut pages = self.storage_pages::<IssueRequestsStore<_>>(head);
+        while let Some((key, request)) = pages.next().await? {
             if request.status == IssueRequestStatus::Pending && request.opentime + issue_period > current_height {
-                let key_hash = issue_id.0.as_slice();
-                // last bytes are the raw key
-                let key = &key_hash[key_hash.len() - 32..];
-                issue_requests.push((H256::from_slice(key), request));
+                issue_requests.push((h256_key(&key), request));
             }
         }
         Ok(issue_requests)
@@ -938,8 +2036,14 @@ pub trait RedeemPallet {
     /// Request a new redeem
     async fn request_redeem(&self, amount: u128, btc_address: BtcAddress, vault_id: &AccountId) -> Result<H256, Error>;
 
-    /// Execute a redeem request by providing a Bitcoin transaction inclusion proof
-    async fn execute_redeem(&self, redeem_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error>;
+    /// Execute a redeem request by providing a Bitcoin transaction inclusion proof. Returns the
+    /// receipt of the submission, `None` if the request was already executed.
+    async fn execute_redeem(
+        &self,
+        redeem_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error>;
 
     /// Cancel an ongoing redeem request
     async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), Error>;
@@ -952,6 +2056,9 @@ pub trait RedeemPallet {
         account_id: AccountId,
     ) -> Result<Vec<(H256, InterBtcRedeemRequest)>, Error>;
 
+    /// Get all pending redeem requests of all vaults
+    async fn get_all_open_redeem_requests(&self) -> Result<Vec<(H256, InterBtcRedeemRequest)>, Error>;
+
     async fn get_redeem_period(&self) -> Result<BlockNumber, Error>;
 
     async fn set_redeem_period(&self, period: u32) -> Result<(), Error>;
@@ -961,11 +2068,14 @@ pub trait RedeemPallet {
 impl RedeemPallet for InterBtcParachain {
     async fn request_redeem(&self, amount: u128, btc_address: BtcAddress, vault_id: &AccountId) -> Result<H256, Error> {
         let result = self
-            .with_unique_signer(|signer| async move {
-                self.ext_client
-                    .request_redeem_and_watch(&signer, amount, btc_address, vault_id)
-                    .await
-            })
+            .with_unique_signer(
+                CallId::new("Redeem", "request_redeem", &(&amount, &btc_address, &vault_id)),
+                |signer| async move {
+                    self.ext_client
+                        .request_redeem_and_watch(&signer, amount, btc_address, vault_id)
+                        .await
+                },
+            )
             .await?;
         if let Some(event) = result.request_redeem()? {
             Ok(event.redeem_id)
@@ -974,29 +2084,49 @@ impl RedeemPallet for InterBtcParachain {
         }
     }
 
-    async fn execute_redeem(&self, redeem_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .execute_redeem_and_watch(&signer, redeem_id, merkle_proof, raw_tx)
-                .await
-        })
-        .await?;
-        Ok(())
+    async fn execute_redeem(
+        &self,
+        redeem_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error> {
+        let call_id = CallId::new("Redeem", "execute_redeem", &(&redeem_id, &merkle_proof, &raw_tx));
+        self.execute_idempotent(
+            call_id.clone(),
+            || async move {
+                let request = self.get_redeem_request(redeem_id).await?;
+                Ok(request.status == RedeemRequestStatus::Completed)
+            },
+            || async {
+                let call = ExecuteRedeemCall {
+                    redeem_id,
+                    merkle_proof,
+                    raw_tx,
+                    _runtime: PhantomData,
+                };
+                self.check_call(call.clone(), 0).await?;
+                self.submit_remarked(call_id.clone(), call).await
+            },
+        )
+        .await
     }
 
     async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .cancel_redeem_and_watch(&signer, redeem_id, reimburse)
-                .await
-        })
+        self.submit_remarked(
+            CallId::new("Redeem", "cancel_redeem", &(&redeem_id, &reimburse)),
+            CancelRedeemCall {
+                redeem_id,
+                reimburse,
+                _runtime: PhantomData,
+            },
+        )
         .await?;
         Ok(())
     }
 
     async fn get_redeem_request(&self, redeem_id: H256) -> Result<InterBtcRedeemRequest, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.redeem_requests(redeem_id, head).await?)
+        Ok(query::<RedeemRequestsStore<_>, _, _, _>(self.ext_client.redeem_requests(redeem_id, head)).await?)
     }
 
     async fn get_vault_redeem_requests(
@@ -1004,20 +2134,44 @@ impl RedeemPallet for InterBtcParachain {
         account_id: AccountId,
     ) -> Result<Vec<(H256, InterBtcRedeemRequest)>, Error> {
         let head = self.get_latest_block_hash().await?;
-        let requests: Vec<(H256, InterBtcRedeemRequest)> = self
-            .rpc_client
+        let result: Result<Vec<(H256, InterBtcRedeemRequest)>, Error> = self
             .request(
                 "redeem_getVaultRedeemRequests",
-                &[to_json_value(account_id)?, to_json_value(head)?],
+                &[to_json_value(account_id.clone())?, to_json_value(head)?],
             )
-            .await?;
+            .await
+            .map_err(Into::into);
+        match result {
+            Err(err) if err.is_method_not_found() => {}
+            result => return result,
+        }
 
-        Ok(requests)
+        // the node does not index the requests by vault, scan all of them instead
+        let mut redeem_requests = Vec::new();
+        let mut pages = self.storage_pages::<RedeemRequestsStore<_>>(head);
+        while let Some((key, request)) = pages.next().await? {
+            if request.vault == account_id {
+                redeem_requests.push((h256_key(&key), request));
+            }
+        }
+        Ok(redeem_requests)
+    }
+
+    async fn get_all_open_redeem_requests(&self) -> Result<Vec<(H256, InterBtcRedeemRequest)>, Error> {
+        let mut redeem_requests = Vec::new();
+        let head = self.get_latest_block_hash().await?;
+        let mut pages = self.storage_pages::<RedeemRequestsStore<_>>(head);
+        while let Some((key, request)) = pages.next().await? {
+            if request.status == RedeemRequestStatus::Pending {
+                redeem_requests.push((h256_key(&key), request));
+            }
+        }
+        Ok(redeem_requests)
     }
 
     async fn get_redeem_period(&self) -> Result<BlockNumber, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.redeem_period(head).await?)
+        Ok(query::<RedeemPeriodStore<_>, _, _, _>(self.ext_client.redeem_period(head)).await?)
     }
 
     async fn set_redeem_period(&self, period: BlockNumber) -> Result<(), Error> {
@@ -1032,8 +2186,14 @@ impl RedeemPallet for InterBtcParachain {
 
 #[async_trait]
 pub trait RefundPallet {
-    /// Execute a refund request by providing a Bitcoin transaction inclusion proof
-    async fn execute_refund(&self, refund_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error>;
+    /// Execute a refund request by providing a Bitcoin transaction inclusion proof. Returns
+    /// the receipt of the submission.
+    async fn execute_refund(
+        &self,
+        refund_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error>;
 
     /// Get all open refund requests requested of the given vault
     async fn get_vault_refund_requests(
@@ -1044,14 +2204,25 @@ pub trait RefundPallet {
 
 #[async_trait]
 impl RefundPallet for InterBtcParachain {
-    async fn execute_refund(&self, refund_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .execute_refund_and_watch(&signer, refund_id, merkle_proof, raw_tx)
-                .await
+    async fn execute_refund(
+        &self,
+        refund_id: H256,
+        merkle_proof: &[u8],
+        raw_tx: &[u8],
+    ) -> Result<Option<SubmissionReceipt>, Error> {
+        let call = ExecuteRefundCall {
+            refund_id,
+            merkle_proof,
+            raw_tx,
+            _runtime: PhantomData,
+        };
+        let call_id = CallId::new("Refund", "execute_refund", &(&refund_id, &merkle_proof, &raw_tx));
+        let receipt = retry_execution(call_id.function, || async {
+            self.check_call(call.clone(), 0).await?;
+            self.submit_remarked(call_id.clone(), call.clone()).await
         })
         .await?;
-        Ok(())
+        Ok(Some(receipt))
     }
 
     async fn get_vault_refund_requests(
@@ -1060,7 +2231,6 @@ impl RefundPallet for InterBtcParachain {
     ) -> Result<Vec<(H256, InterBtcRefundRequest)>, Error> {
         let head = self.get_latest_block_hash().await?;
         let result: Vec<(H256, InterBtcRefundRequest)> = self
-            .rpc_client
             .request(
                 "refund_getVaultRefundRequests",
                 &[to_json_value(account_id)?, to_json_value(head)?],
@@ -1073,6 +2243,103 @@ impl RefundPallet for InterBtcParachain {
 
 const BLOCK_WAIT_TIMEOUT: u64 = 6;
 
+#[async_trait]
+pub trait RequestHistory {
+    /// Get all issue, redeem and replace requests involving the account, as requester or
+    /// as vault, with their current status. Completed requests remain in storage, so this
+    /// includes past requests. The payments are not stored on the parachain, see
+    /// `link_payments` to add them from the bitcoin chain.
+    async fn get_request_history(&self, account_id: AccountId) -> Result<Vec<HistoricalRequest>, Error>;
+}
+
+#[async_trait]
+impl RequestHistory for InterBtcParachain {
+    async fn get_request_history(&self, account_id: AccountId) -> Result<Vec<HistoricalRequest>, Error> {
+        let mut history = Vec::new();
+        // all pages are read at the same block, so that the history is consistent
+        let head = self.get_latest_block_hash().await?;
+
+        let mut pages = self.storage_pages::<IssueRequestsStore<_>>(head);
+        while let Some((key, request)) = pages.next().await? {
+            let role = if request.requester == account_id {
+                RequestRole::User
+            } else if request.vault == account_id {
+                RequestRole::Vault
+            } else {
+                continue;
+            };
+            history.push(HistoricalRequest {
+                id: h256_key(&key),
+                kind: HistoricalRequestKind::Issue,
+                role,
+                amount: request.amount.saturating_add(request.fee),
+                btc_address: request.btc_address,
+                opentime: request.opentime,
+                status: match request.status {
+                    IssueRequestStatus::Pending => HistoricalStatus::Pending,
+                    IssueRequestStatus::Completed(_) => HistoricalStatus::Completed,
+                    IssueRequestStatus::Cancelled => HistoricalStatus::Cancelled,
+                },
+                btc_txids: vec![],
+            });
+        }
+
+        let mut pages = self.storage_pages::<RedeemRequestsStore<_>>(head);
+        while let Some((key, request)) = pages.next().await? {
+            let role = if request.redeemer == account_id {
+                RequestRole::User
+            } else if request.vault == account_id {
+                RequestRole::Vault
+            } else {
+                continue;
+            };
+            history.push(HistoricalRequest {
+                id: h256_key(&key),
+                kind: HistoricalRequestKind::Redeem,
+                role,
+                amount: request.amount_btc,
+                btc_address: request.btc_address,
+                opentime: request.opentime,
+                status: match request.status {
+                    RedeemRequestStatus::Pending => HistoricalStatus::Pending,
+                    RedeemRequestStatus::Completed => HistoricalStatus::Completed,
+                    RedeemRequestStatus::Reimbursed(burned) => HistoricalStatus::Reimbursed(burned),
+                    RedeemRequestStatus::Retried => HistoricalStatus::Retried,
+                },
+                btc_txids: vec![],
+            });
+        }
+
+        let mut pages = self.storage_pages::<ReplaceRequestsStore<_>>(head);
+        while let Some((key, request)) = pages.next().await? {
+            let role = if request.old_vault == account_id {
+                RequestRole::OldVault
+            } else if request.new_vault == account_id {
+                RequestRole::NewVault
+            } else {
+                continue;
+            };
+            history.push(HistoricalRequest {
+                id: h256_key(&key),
+                kind: HistoricalRequestKind::Replace,
+                role,
+                amount: request.amount,
+                btc_address: request.btc_address,
+                opentime: request.accept_time,
+                status: match request.status {
+                    ReplaceRequestStatus::Pending => HistoricalStatus::Pending,
+                    ReplaceRequestStatus::Completed => HistoricalStatus::Completed,
+                    ReplaceRequestStatus::Cancelled => HistoricalStatus::Cancelled,
+                },
+                btc_txids: vec![],
+            });
+        }
+
+        history.sort_by_key(|request| request.opentime);
+        Ok(history)
+    }
+}
+
 #[async_trait]
 pub trait BtcRelayPallet {
     async fn get_best_block(&self) -> Result<H256Le, Error>;
@@ -1105,13 +2372,13 @@ impl BtcRelayPallet for InterBtcParachain {
     /// Get the hash of the current best tip.
     async fn get_best_block(&self) -> Result<H256Le, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.best_block(head).await?)
+        Ok(query::<BestBlockStore<_>, _, _, _>(self.ext_client.best_block(head)).await?)
     }
 
     /// Get the current best known height.
     async fn get_best_block_height(&self) -> Result<u32, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.best_block_height(head).await?)
+        Ok(query::<BestBlockHeightStore<_>, _, _, _>(self.ext_client.best_block_height(head)).await?)
     }
 
     /// Get the block hash for the main chain at the specified height.
@@ -1120,7 +2387,7 @@ impl BtcRelayPallet for InterBtcParachain {
     /// * `height` - chain height
     async fn get_block_hash(&self, height: u32) -> Result<H256Le, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.chains_hashes(0, height, head).await?)
+        Ok(query::<ChainsHashesStore<_>, _, _, _>(self.ext_client.chains_hashes(0, height, head)).await?)
     }
 
     /// Get the corresponding block header for the given hash.
@@ -1129,13 +2396,16 @@ impl BtcRelayPallet for InterBtcParachain {
     /// * `hash` - little endian block hash
     async fn get_block_header(&self, hash: H256Le) -> Result<InterBtcRichBlockHeader, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.block_headers(hash, head).await?)
+        Ok(query::<BlockHeadersStore<_>, _, _, _>(self.ext_client.block_headers(hash, head)).await?)
     }
 
     /// Get the global security parameter k for stable Bitcoin transactions
     async fn get_bitcoin_confirmations(&self) -> Result<u32, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.stable_bitcoin_confirmations(head).await?)
+        Ok(
+            query::<StableBitcoinConfirmationsStore<_>, _, _, _>(self.ext_client.stable_bitcoin_confirmations(head))
+                .await?,
+        )
     }
 
     /// Set the global security parameter k for stable Bitcoin transactions
@@ -1147,7 +2417,12 @@ impl BtcRelayPallet for InterBtcParachain {
     /// Get the global security parameter for stable parachain confirmations
     async fn get_parachain_confirmations(&self) -> Result<BlockNumber, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.stable_parachain_confirmations(head).await?)
+        Ok(
+            query::<StableParachainConfirmationsStore<_>, _, _, _>(
+                self.ext_client.stable_parachain_confirmations(head),
+            )
+            .await?,
+        )
     }
 
     /// Set the global security parameter for stable parachain confirmations
@@ -1183,7 +2458,6 @@ impl BtcRelayPallet for InterBtcParachain {
     async fn verify_block_header_inclusion(&self, block_hash: H256Le) -> Result<(), Error> {
         let head = self.get_latest_block_hash().await?;
         let result: Result<(), DispatchError> = self
-            .rpc_client
             .request(
                 "btcRelay_verifyBlockHeaderInclusion",
                 &[to_json_value(block_hash)?, to_json_value(head)?],
@@ -1205,19 +2479,19 @@ pub trait VaultRegistryPallet {
 
     async fn get_all_vaults(&self) -> Result<Vec<InterBtcVault>, Error>;
 
-    async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey) -> Result<(), Error>;
+    async fn register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), Error>;
 
-    async fn deposit_collateral(&self, amount: u128) -> Result<(), Error>;
+    async fn deposit_collateral(&self, amount: CollateralAmount) -> Result<(), Error>;
 
-    async fn withdraw_collateral(&self, amount: u128) -> Result<(), Error>;
+    async fn withdraw_collateral(&self, amount: CollateralAmount) -> Result<(), Error>;
 
     async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), Error>;
 
     async fn register_address(&self, btc_address: BtcAddress) -> Result<(), Error>;
 
-    async fn get_required_collateral_for_wrapped(&self, amount_btc: u128) -> Result<u128, Error>;
+    async fn get_required_collateral_for_wrapped(&self, amount_btc: WrappedAmount) -> Result<CollateralAmount, Error>;
 
-    async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, Error>;
+    async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<CollateralAmount, Error>;
 }
 
 #[async_trait]
@@ -1233,7 +2507,7 @@ impl VaultRegistryPallet for InterBtcParachain {
     /// * `VaultCommittedTheft` - if the vault is stole BTC
     async fn get_vault(&self, vault_id: AccountId) -> Result<InterBtcVault, Error> {
         let head = self.get_latest_block_hash().await?;
-        match self.ext_client.vaults(vault_id.clone(), head).await {
+        match query::<VaultsStore<_>, _, _, _>(self.ext_client.vaults(vault_id.clone(), head)).await {
             Ok(InterBtcVault {
                 status: VaultStatus::Liquidated,
                 ..
@@ -1252,8 +2526,8 @@ impl VaultRegistryPallet for InterBtcParachain {
     async fn get_all_vaults(&self) -> Result<Vec<InterBtcVault>, Error> {
         let mut vaults = Vec::new();
         let head = self.get_latest_block_hash().await?;
-        let mut iter = self.ext_client.vaults_iter(head).await?;
-        while let Some((_, account)) = iter.next().await? {
+        let mut pages = self.storage_pages::<VaultsStore<_>>(head);
+        while let Some((_, account)) = pages.next().await? {
             if let VaultStatus::Active(..) = account.status {
                 vaults.push(account);
             }
@@ -1266,13 +2540,25 @@ impl VaultRegistryPallet for InterBtcParachain {
     /// # Arguments
     /// * `collateral` - deposit
     /// * `public_key` - Bitcoin public key
-    async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey) -> Result<(), Error> {
+    async fn register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), Error> {
+        let collateral = collateral.planck();
+        self.check_call(
+            RegisterVaultCall {
+                collateral,
+                public_key: public_key.clone(),
+            },
+            collateral,
+        )
+        .await?;
         let public_key = &public_key.clone();
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .register_vault_and_watch(&signer, collateral, public_key.clone())
-                .await
-        })
+        self.with_unique_signer(
+            CallId::new("VaultRegistry", "register_vault", &(&collateral, &public_key)),
+            |signer| async move {
+                self.ext_client
+                    .register_vault_and_watch(&signer, collateral, public_key.clone())
+                    .await
+            },
+        )
         .await?;
         Ok(())
     }
@@ -1282,8 +2568,11 @@ impl VaultRegistryPallet for InterBtcParachain {
     ///
     /// # Arguments
     /// * `amount` - the amount of extra collateral to lock
-    async fn deposit_collateral(&self, amount: u128) -> Result<(), Error> {
+    async fn deposit_collateral(&self, amount: CollateralAmount) -> Result<(), Error> {
+        let amount = amount.planck();
+        self.check_call(DepositCollateralCall { amount }, amount).await?;
         self.with_unique_signer(
+            CallId::new("VaultRegistry", "deposit_collateral", &amount),
             |signer| async move { self.ext_client.deposit_collateral_and_watch(&signer, amount).await },
         )
         .await?;
@@ -1299,10 +2588,12 @@ impl VaultRegistryPallet for InterBtcParachain {
     ///
     /// # Arguments
     /// * `amount` - the amount of collateral to withdraw
-    async fn withdraw_collateral(&self, amount: u128) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client.withdraw_collateral_and_watch(&signer, amount).await
-        })
+    async fn withdraw_collateral(&self, amount: CollateralAmount) -> Result<(), Error> {
+        let amount = amount.planck();
+        self.with_unique_signer(
+            CallId::new("VaultRegistry", "withdraw_collateral", &amount),
+            |signer| async move { self.ext_client.withdraw_collateral_and_watch(&signer, amount).await },
+        )
         .await?;
         Ok(())
     }
@@ -1313,11 +2604,14 @@ impl VaultRegistryPallet for InterBtcParachain {
     /// * `public_key` - the new public key of the vault
     async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), Error> {
         let public_key = &public_key.clone();
-        self.with_unique_signer(|signer| async move {
-            self.ext_client
-                .update_public_key_and_watch(&signer, public_key.clone())
-                .await
-        })
+        self.with_unique_signer(
+            CallId::new("VaultRegistry", "update_public_key", &public_key),
+            |signer| async move {
+                self.ext_client
+                    .update_public_key_and_watch(&signer, public_key.clone())
+                    .await
+            },
+        )
         .await?;
         Ok(())
     }
@@ -1327,9 +2621,10 @@ impl VaultRegistryPallet for InterBtcParachain {
     /// # Arguments
     /// * `btc_address` - the new btc address of the vault
     async fn register_address(&self, btc_address: BtcAddress) -> Result<(), Error> {
-        self.with_unique_signer(|signer| async move {
-            self.ext_client.register_address_and_watch(&signer, btc_address).await
-        })
+        self.with_unique_signer(
+            CallId::new("VaultRegistry", "register_address", &btc_address),
+            |signer| async move { self.ext_client.register_address_and_watch(&signer, btc_address).await },
+        )
         .await?;
         Ok(())
     }
@@ -1338,35 +2633,35 @@ impl VaultRegistryPallet for InterBtcParachain {
     ///
     /// # Arguments
     /// * `amount_btc` - amount of btc to cover
-    async fn get_required_collateral_for_wrapped(&self, amount_btc: u128) -> Result<u128, Error> {
+    async fn get_required_collateral_for_wrapped(&self, amount_btc: WrappedAmount) -> Result<CollateralAmount, Error> {
         let head = self.get_latest_block_hash().await?;
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "vaultRegistry_getRequiredCollateralForWrapped",
                 &[
-                    to_json_value(BalanceWrapper { amount: amount_btc })?,
+                    to_json_value(BalanceWrapper {
+                        amount: amount_btc.planck(),
+                    })?,
                     to_json_value(head)?,
                 ],
             )
             .await?;
 
-        Ok(result.amount)
+        Ok(Amount::from_planck(result.amount))
     }
 
     /// Get the amount of collateral required for the given vault to be at the
     /// current SecureCollateralThreshold with the current exchange rate
-    async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, Error> {
+    async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<CollateralAmount, Error> {
         let head = self.get_latest_block_hash().await?;
         let result: BalanceWrapper<_> = self
-            .rpc_client
             .request(
                 "vaultRegistry_getRequiredCollateralForVault",
                 &[to_json_value(vault_id)?, to_json_value(head)?],
             )
             .await?;
 
-        Ok(result.amount)
+        Ok(Amount::from_planck(result.amount))
     }
 }
 
@@ -1381,16 +2676,122 @@ pub trait FeePallet {
 impl FeePallet for InterBtcParachain {
     async fn get_issue_griefing_collateral(&self) -> Result<FixedU128, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.issue_griefing_collateral(head).await?)
+        Ok(query::<IssueGriefingCollateralStore<_>, _, _, _>(self.ext_client.issue_griefing_collateral(head)).await?)
     }
 
     async fn get_issue_fee(&self) -> Result<FixedU128, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.issue_fee(head).await?)
+        Ok(query::<IssueFeeStore<_>, _, _, _>(self.ext_client.issue_fee(head)).await?)
     }
 
     async fn get_replace_griefing_collateral(&self) -> Result<FixedU128, Error> {
         let head = self.get_latest_block_hash().await?;
-        Ok(self.ext_client.replace_griefing_collateral(head).await?)
+        Ok(
+            query::<ReplaceGriefingCollateralStore<_>, _, _, _>(self.ext_client.replace_griefing_collateral(head))
+                .await?,
+        )
+    }
+}
+
+#[async_trait]
+pub trait LiquidationPallet {
+    /// Get the tokens and the collateral taken over from liquidated vaults.
+    async fn get_liquidation_vault(&self) -> Result<LiquidationVault, Error>;
+
+    /// Get the free balance of wrapped tokens of the signer.
+    async fn get_free_wrapped_balance(&self) -> Result<u128, Error>;
+
+    /// Burn wrapped tokens against the liquidation vault in exchange for its collateral.
+    async fn liquidation_redeem(&self, amount_wrapped: u128) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl LiquidationPallet for InterBtcParachain {
+    async fn get_liquidation_vault(&self) -> Result<LiquidationVault, Error> {
+        let head = self.get_latest_block_hash().await?;
+        let system_vault = query::<LiquidationVaultStore<_>, _, _, _>(self.ext_client.liquidation_vault(head)).await?;
+        let account = query::<AccountsStore<_>, _, _, _>(self.ext_client.accounts(
+            system_vault.id.clone(),
+            COLLATERAL_CURRENCY,
+            head,
+        ))
+        .await?;
+        Ok(LiquidationVault {
+            issued_tokens: system_vault.issued_tokens,
+            to_be_issued_tokens: system_vault.to_be_issued_tokens,
+            to_be_redeemed_tokens: system_vault.to_be_redeemed_tokens,
+            collateral: account.free.saturating_add(account.reserved),
+        })
+    }
+
+    async fn get_free_wrapped_balance(&self) -> Result<u128, Error> {
+        let head = self.get_latest_block_hash().await?;
+        Ok(query::<AccountsStore<_>, _, _, _>(self.ext_client.accounts(
+            self.account_id.clone(),
+            WRAPPED_CURRENCY,
+            head,
+        ))
+        .await?
+        .free)
+    }
+
+    async fn liquidation_redeem(&self, amount_wrapped: u128) -> Result<(), Error> {
+        self.check_call(LiquidationRedeemCall { amount_wrapped }, 0).await?;
+        self.with_unique_signer(
+            CallId::new("Redeem", "liquidation_redeem", &amount_wrapped),
+            |signer| async move {
+                self.ext_client
+                    .liquidation_redeem_and_watch(&signer, amount_wrapped)
+                    .await
+            },
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Convenience calls for test networks and local development, not compiled into production
+/// builds. The signer must be the sudo key.
+#[cfg(feature = "testnet-utils")]
+#[async_trait]
+pub trait TestnetUtils {
+    /// Authorize the signer as oracle and set the exchange rate.
+    async fn force_set_exchange_rate(&self, collateral_per_wrapped: FixedU128) -> Result<(), Error>;
+
+    /// Mint the collateral and fees to the signer and register it as vault.
+    async fn force_register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), Error>;
+
+    /// Initialize the relay at the given block, if it is not initialized yet.
+    async fn set_relay_genesis(&self, header: RawBlockHeader, height: BitcoinBlockHeight) -> Result<(), Error>;
+}
+
+#[cfg(feature = "testnet-utils")]
+#[async_trait]
+impl TestnetUtils for InterBtcParachain {
+    async fn force_set_exchange_rate(&self, collateral_per_wrapped: FixedU128) -> Result<(), Error> {
+        self.insert_authorized_oracle(self.account_id.clone(), "testnet".to_string())
+            .await?;
+        self.set_exchange_rate_info(collateral_per_wrapped).await
+    }
+
+    async fn force_register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), Error> {
+        let (free, reserved) = tokio::try_join!(self.get_free_balance(), self.get_reserved_balance())?;
+        let new_free = free.saturating_add(collateral.planck()).saturating_add(crate::TX_FEES);
+        self.sudo(SetBalanceCall {
+            who: &self.account_id,
+            currency_id: COLLATERAL_CURRENCY,
+            new_free,
+            new_reserved: reserved,
+        })
+        .await?;
+        self.register_vault(collateral, public_key).await
+    }
+
+    async fn set_relay_genesis(&self, header: RawBlockHeader, height: BitcoinBlockHeight) -> Result<(), Error> {
+        if !self.get_best_block().await?.is_zero() {
+            log::info!("Relay is already initialized");
+            return Ok(());
+        }
+        self.initialize_btc_relay(header, height).await
     }
 }
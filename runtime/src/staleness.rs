@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+/// Staleness of the exchange rate reported by the oracles, all times
+/// are in milliseconds as defined by the `timestamp` pallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OracleStaleness {
+    /// Time at which the exchange rate was last updated.
+    pub last_updated: u64,
+    /// Current time of the parachain.
+    pub now: u64,
+    /// Maximum delay after which the parachain no longer accepts the exchange rate.
+    pub max_delay: u64,
+}
+
+impl OracleStaleness {
+    /// Time since the exchange rate was last updated.
+    pub fn age(&self) -> Duration {
+        Duration::from_millis(self.now.saturating_sub(self.last_updated))
+    }
+
+    /// Returns true if the exchange rate has not been updated within the given threshold.
+    pub fn is_stale(&self, threshold: Duration) -> bool {
+        self.age() > threshold
+    }
+
+    /// Returns true if the exchange rate is older than the maximum delay, in which case
+    /// the parachain rejects operations that depend on it.
+    pub fn is_expired(&self) -> bool {
+        self.is_stale(Duration::from_millis(self.max_delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oracle_staleness() {
+        let staleness = OracleStaleness {
+            last_updated: 1_000,
+            now: 61_000,
+            max_delay: 120_000,
+        };
+        assert_eq!(staleness.age(), Duration::from_secs(60));
+        assert!(staleness.is_stale(Duration::from_secs(30)));
+        assert!(!staleness.is_stale(Duration::from_secs(60)));
+        assert!(!staleness.is_expired());
+    }
+}
@@ -1,8 +1,8 @@
 #![cfg(test)]
 
 use super::{
-    BtcAddress, BtcPublicKey, BtcRelayPallet, CollateralBalancesPallet, ExchangeRateOraclePallet, FixedPointNumber,
-    FixedU128, ReplacePallet, SecurityPallet, StakedRelayerPallet, StatusCode, VaultRegistryPallet,
+    BtcAddress, BtcPublicKey, BtcRelayPallet, CollateralAmount, CollateralBalancesPallet, ExchangeRateOraclePallet,
+    FixedPointNumber, FixedU128, ReplacePallet, SecurityPallet, StakedRelayerPallet, StatusCode, VaultRegistryPallet,
 };
 use crate::{exchange_rate_oracle::SetExchangeRateEvent, integration::*, InterBtcRuntime};
 use module_bitcoin::{
@@ -95,7 +95,10 @@ async fn test_register_vault() {
     let parachain_rpc = setup_provider(client.clone(), AccountKeyring::Alice).await;
     set_exchange_rate(client.clone()).await;
 
-    parachain_rpc.register_vault(100, dummy_public_key()).await.unwrap();
+    parachain_rpc
+        .register_vault(CollateralAmount::from_planck(100), dummy_public_key())
+        .await
+        .unwrap();
     let vault = parachain_rpc
         .get_vault(AccountKeyring::Alice.to_account_id())
         .await
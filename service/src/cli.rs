@@ -1,3 +1,4 @@
+use crate::{proxy::route_through_proxy, Error, Proxy};
 use clap::Clap;
 use std::str::FromStr;
 
@@ -48,6 +49,11 @@ impl LoggingFormat {
             Self::Json => crate::trace::init_json_subscriber(),
         }
     }
+
+    /// Like `init_subscriber`, but logs to stderr.
+    pub fn init_stderr_subscriber(&self) {
+        crate::trace::init_stderr_subscriber(matches!(self, Self::Json));
+    }
 }
 
 #[derive(Clap, Debug, Clone)]
@@ -63,4 +69,43 @@ pub struct ServiceConfig {
     /// Telemetry endpoint.
     #[clap(long)]
     pub telemetry_url: Option<String>,
+
+    /// SOCKS5 proxy (e.g. Tor) for all outbound connections, of the form
+    /// socks5://[user:password@]host:port. Telemetry is disabled when set.
+    #[clap(long)]
+    pub proxy: Option<Proxy>,
+
+    /// SOCKS5 proxy for the parachain connection, overrides --proxy. Use different
+    /// credentials than the bitcoin proxy to isolate the Tor circuits.
+    #[clap(long)]
+    pub parachain_proxy: Option<Proxy>,
+
+    /// SOCKS5 proxy for the bitcoin-core and esplora connections, overrides --proxy.
+    #[clap(long)]
+    pub bitcoin_proxy: Option<Proxy>,
+}
+
+impl ServiceConfig {
+    /// Proxy for the parachain connection, if any.
+    pub fn parachain_proxy(&self) -> Option<&Proxy> {
+        self.parachain_proxy.as_ref().or_else(|| self.proxy.as_ref())
+    }
+
+    /// Proxy for the bitcoin connections, i.e. to bitcoin-core and esplora, if any.
+    pub fn bitcoin_proxy(&self) -> Option<&Proxy> {
+        self.bitcoin_proxy.as_ref().or_else(|| self.proxy.as_ref())
+    }
+
+    /// Route the connections to the given parachain and bitcoin-core urls through the
+    /// configured proxies, returns the urls to connect to instead.
+    pub async fn route_through_proxies(
+        &self,
+        parachain_url: &str,
+        bitcoin_url: &str,
+    ) -> Result<(String, String), Error> {
+        Ok((
+            route_through_proxy(self.parachain_proxy(), parachain_url).await?,
+            route_through_proxy(self.bitcoin_proxy(), bitcoin_url).await?,
+        ))
+    }
 }
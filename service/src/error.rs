@@ -10,7 +10,13 @@ pub enum Error {
     InvalidResponse,
     #[error("Client has shutdown")]
     ClientShutdown,
+    #[error("Cannot connect to {0} through a proxy, only http(s) and ws(s) endpoints are supported")]
+    InvalidProxyTarget(String),
+    #[error("Proxy error: {0}")]
+    ProxyError(String),
 
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
     #[error("SerdeJsonError: {0}")]
     SerdeJsonError(#[from] SerdeJsonError),
     #[error("HyperError: {0}")]
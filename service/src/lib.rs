@@ -10,6 +10,7 @@ use std::marker::PhantomData;
 
 mod cli;
 mod error;
+mod proxy;
 mod telemetry;
 mod trace;
 
@@ -17,6 +18,7 @@ use telemetry::TelemetryClient;
 
 pub use cli::{LoggingFormat, RestartPolicy, ServiceConfig};
 pub use error::Error;
+pub use proxy::{route_through_proxy, Proxy};
 pub use trace::init_subscriber;
 
 pub type ShutdownSender = tokio::sync::broadcast::Sender<Option<()>>;
@@ -68,7 +70,10 @@ impl<Config: Clone + Send + 'static, S: Service<Config>> ConnectionManager<Confi
 
 impl<Config: Clone + Send + 'static, S: Service<Config>> ConnectionManager<Config, S> {
     pub async fn start(&self) -> Result<(), Error> {
-        if let Some(uri) = &self.service_config.telemetry_url {
+        if self.service_config.telemetry_url.is_some() && self.service_config.proxy.is_some() {
+            // the telemetry client connects directly, which would reveal the vault's address
+            tracing::warn!("Telemetry is disabled since connections are routed through a proxy");
+        } else if let Some(uri) = &self.service_config.telemetry_url {
             // run telemetry client heartbeat
             let telemetry_client = TelemetryClient::new(uri.clone(), self.signer.clone());
             tokio::spawn(async move { telemetry::do_update(&telemetry_client, S::NAME, S::VERSION).await });
@@ -87,13 +92,20 @@ impl<Config: Clone + Send + 'static, S: Service<Config>> ConnectionManager<Confi
             // only open connection to parachain after bitcoind sync to prevent timeout
             let signer = self.signer.clone();
             let btc_parachain = BtcParachain::from_url_and_config_with_retry(
-                &self.parachain_config.btc_parachain_url,
+                &self.parachain_config.parachain_url(),
                 signer,
-                self.parachain_config.max_concurrent_requests,
-                self.parachain_config.max_notifs_per_subscription,
+                self.parachain_config.ws_client_options(),
                 self.parachain_config.btc_parachain_connection_timeout_ms,
             )
             .await?;
+            let btc_parachain = self
+                .parachain_config
+                .expect_network(btc_parachain)?
+                .with_balance_reserve(self.parachain_config.balance_reserve)?
+                .with_tip_budget(self.parachain_config.tip_budget())
+                .with_dry_run(self.parachain_config.dry_run_extrinsics)
+                .with_fee_budget(self.parachain_config.max_extrinsic_fee)
+                .with_storage_page_size(self.parachain_config.storage_page_size);
 
             let service = S::new_service(btc_parachain, bitcoin_core, config, shutdown_tx);
             if let Err(outer) = service.start().await {
@@ -23,3 +23,15 @@ pub fn init_subscriber() {
         .with(fmt_layer)
         .try_init();
 }
+
+/// Log to stderr, so that stdout only carries the output of a command.
+pub fn init_stderr_subscriber(json: bool) {
+    let fmt_layer = fmt::layer().with_writer(std::io::stderr);
+
+    let registry = tracing_subscriber::registry().with(init_filter());
+    let _ = if json {
+        registry.with(fmt_layer.json()).try_init()
+    } else {
+        registry.with(fmt_layer).try_init()
+    };
+}
@@ -0,0 +1,428 @@
+use crate::request_state::{RequestKind, RequestState, Transition};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use prometheus::{IntCounterVec, Opts};
+use runtime::{BtcAddress, SubmissionReceipt};
+use sp_core::H256;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+/// Number of events buffered for the database, further events are dropped while it is full.
+const EVENT_CHANNEL_CAPACITY: usize = 4096;
+
+lazy_static! {
+    static ref SINK: Mutex<Option<mpsc::Sender<Record>>> = Mutex::new(None);
+    pub static ref ANALYTICS_EVENTS: IntCounterVec = IntCounterVec::new(
+        Opts::new("analytics_events", "Events sent to the analytics database by outcome"),
+        &["outcome"]
+    )
+    .expect("Failed to create prometheus metric");
+}
+
+/// Something the vault did or observed, written to the analytics database. The state
+/// transitions of the requests are recorded as well, without being passed here.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum AnalyticsEvent {
+    /// A request of this vault, with the details that are not part of its transitions.
+    Request {
+        request_id: H256,
+        kind: RequestKind,
+        /// Amount in satoshis.
+        amount: u128,
+        /// Fee of the request in satoshis, if known.
+        fee: Option<u128>,
+        btc_address: BtcAddress,
+    },
+    /// A bitcoin payment broadcast by the vault.
+    Payment {
+        request_id: H256,
+        kind: RequestKind,
+        txid: String,
+        /// Amount in satoshis.
+        amount: u64,
+        /// Bitcoin fee of the transaction in satoshis, if known.
+        fee: Option<u64>,
+    },
+    /// A proof of payment with which the request was executed.
+    Proof {
+        request_id: H256,
+        kind: RequestKind,
+        txid: String,
+        block_hash: String,
+        block_height: Option<u32>,
+        /// Fee of the extrinsic that executed the request, including the tip, in planck of
+        /// the fee currency. `None` if not reported by the runtime.
+        parachain_fee: Option<u128>,
+    },
+    Transition(Transition),
+}
+
+#[derive(Debug)]
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+struct Record {
+    event: AnalyticsEvent,
+    at: DateTime<Utc>,
+}
+
+/// Record an event if the analytics sink is enabled, the event is only built in that case.
+pub(crate) fn record(event: impl FnOnce() -> AnalyticsEvent) {
+    let mut sink = match SINK.lock() {
+        Ok(sink) => sink,
+        Err(_) => return,
+    };
+    if let Some(sender) = sink.as_mut() {
+        send(sender, event());
+    }
+}
+
+/// Buffer the event for the database, returns false if it was dropped because the buffer is full.
+fn send(sender: &mut mpsc::Sender<Record>, event: AnalyticsEvent) -> bool {
+    let record = Record { event, at: Utc::now() };
+    if sender.try_send(record).is_err() {
+        ANALYTICS_EVENTS.with_label_values(&["dropped"]).inc();
+        return false;
+    }
+    true
+}
+
+/// Fee of the extrinsic of the receipt including the tip, `None` if the runtime did not report it.
+pub(crate) fn parachain_fee(receipt: &SubmissionReceipt) -> Option<u128> {
+    receipt.fee.map(|fee| fee.saturating_add(receipt.tip))
+}
+
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+fn kind_name(kind: RequestKind) -> &'static str {
+    match kind {
+        RequestKind::Issue => "issue",
+        RequestKind::Redeem => "redeem",
+        RequestKind::Replace => "replace",
+        RequestKind::Refund => "refund",
+    }
+}
+
+/// Columns of a state in the `transitions` table: its name, txid, block hash and failure reason.
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+fn state_columns(state: &RequestState) -> (&'static str, Option<&str>, Option<&str>, Option<&str>) {
+    match state {
+        RequestState::Seen => ("seen", None, None, None),
+        RequestState::Paid { txid } => ("paid", Some(txid), None, None),
+        RequestState::Confirmed { txid, block_hash } => ("confirmed", Some(txid), Some(block_hash), None),
+        RequestState::Executed => ("executed", None, None, None),
+        RequestState::Finalized => ("finalized", None, None, None),
+        RequestState::Failed { reason } => ("failed", None, None, Some(reason)),
+        RequestState::Expired => ("expired", None, None, None),
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub use self::sink::run_analytics_sink;
+
+#[cfg(feature = "postgres")]
+mod sink {
+    use super::*;
+    use crate::request_state;
+    use bitcoin::{Network, PartialAddress};
+    use native_tls::{Certificate, TlsConnector};
+    use postgres_native_tls::MakeTlsConnector;
+    use service::Error as ServiceError;
+    use std::{convert::TryFrom, path::PathBuf, time::Duration};
+    use tokio::{sync::broadcast::RecvError, time::delay_for};
+    use tokio_postgres::Client;
+
+    /// Delay before reconnecting after the connection to the database was lost.
+    const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+    /// Every row refers to a request, which is inserted when first referenced and filled in
+    /// with its details once the `Request` event is written.
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS requests (
+            request_id TEXT PRIMARY KEY,
+            kind TEXT NOT NULL,
+            amount BIGINT,
+            fee BIGINT,
+            btc_address TEXT,
+            first_seen TIMESTAMPTZ NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS payments (
+            txid TEXT PRIMARY KEY,
+            request_id TEXT NOT NULL REFERENCES requests (request_id),
+            amount BIGINT,
+            fee BIGINT,
+            broadcast_at TIMESTAMPTZ NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS proofs (
+            request_id TEXT NOT NULL REFERENCES requests (request_id),
+            txid TEXT NOT NULL,
+            block_hash TEXT NOT NULL,
+            block_height BIGINT,
+            parachain_fee BIGINT,
+            executed_at TIMESTAMPTZ NOT NULL,
+            PRIMARY KEY (request_id, txid)
+        );
+        ALTER TABLE proofs ADD COLUMN IF NOT EXISTS parachain_fee BIGINT;
+        CREATE TABLE IF NOT EXISTS transitions (
+            id BIGSERIAL PRIMARY KEY,
+            request_id TEXT NOT NULL REFERENCES requests (request_id),
+            from_state TEXT,
+            to_state TEXT NOT NULL,
+            txid TEXT,
+            block_hash TEXT,
+            reason TEXT,
+            at TIMESTAMPTZ NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS transitions_request_id ON transitions (request_id);
+    ";
+
+    /// TLS connector trusting the system's certificates and the given CA certificate, if any.
+    /// Whether TLS is required is set by `sslmode` in the url, by default it is used if the
+    /// server supports it.
+    async fn tls_connector(ca_file: Option<PathBuf>) -> Result<MakeTlsConnector, ServiceError> {
+        let mut builder = TlsConnector::builder();
+        if let Some(ca_file) = ca_file {
+            let pem = tokio::fs::read(&ca_file).await?;
+            let certificate = Certificate::from_pem(&pem)
+                .map_err(|err| ServiceError::Other(format!("Invalid certificate {}: {}", ca_file.display(), err)))?;
+            builder.add_root_certificate(certificate);
+        }
+        let connector = builder.build().map_err(|err| ServiceError::Other(err.to_string()))?;
+        Ok(MakeTlsConnector::new(connector))
+    }
+
+    async fn connect(database_url: &str, tls: MakeTlsConnector) -> Result<Client, tokio_postgres::Error> {
+        let (client, connection) = tokio_postgres::connect(database_url, tls).await?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::error!("Analytics database connection error: {}", err);
+            }
+        });
+        client.batch_execute(SCHEMA).await?;
+        Ok(client)
+    }
+
+    /// Amounts are stored as BIGINT, which holds any amount of satoshis.
+    fn sql_amount<T>(amount: T) -> Option<i64>
+    where
+        i64: TryFrom<T>,
+    {
+        i64::try_from(amount).ok()
+    }
+
+    async fn insert_request(
+        client: &Client,
+        request_id: &str,
+        kind: RequestKind,
+        at: &DateTime<Utc>,
+    ) -> Result<(), tokio_postgres::Error> {
+        client
+            .execute(
+                "INSERT INTO requests (request_id, kind, first_seen) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                &[&request_id, &kind_name(kind), at],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn write(client: &Client, network: Network, record: &Record) -> Result<(), tokio_postgres::Error> {
+        let at = &record.at;
+        match &record.event {
+            AnalyticsEvent::Request {
+                request_id,
+                kind,
+                amount,
+                fee,
+                btc_address,
+            } => {
+                client
+                    .execute(
+                        "INSERT INTO requests (request_id, kind, amount, fee, btc_address, first_seen) \
+                         VALUES ($1, $2, $3, $4, $5, $6) \
+                         ON CONFLICT (request_id) DO UPDATE \
+                         SET amount = EXCLUDED.amount, fee = EXCLUDED.fee, btc_address = EXCLUDED.btc_address",
+                        &[
+                            &format!("{:?}", request_id),
+                            &kind_name(*kind),
+                            &sql_amount(*amount),
+                            &fee.and_then(sql_amount),
+                            &btc_address.encode_str(network).ok(),
+                            at,
+                        ],
+                    )
+                    .await?;
+            }
+            AnalyticsEvent::Payment {
+                request_id,
+                kind,
+                txid,
+                amount,
+                fee,
+            } => {
+                let request_id = format!("{:?}", request_id);
+                insert_request(client, &request_id, *kind, at).await?;
+                client
+                    .execute(
+                        "INSERT INTO payments (txid, request_id, amount, fee, broadcast_at) \
+                         VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+                        &[txid, &request_id, &sql_amount(*amount), &fee.and_then(sql_amount), at],
+                    )
+                    .await?;
+            }
+            AnalyticsEvent::Proof {
+                request_id,
+                kind,
+                txid,
+                block_hash,
+                block_height,
+                parachain_fee,
+            } => {
+                let request_id = format!("{:?}", request_id);
+                insert_request(client, &request_id, *kind, at).await?;
+                client
+                    .execute(
+                        "INSERT INTO proofs (request_id, txid, block_hash, block_height, parachain_fee, executed_at) \
+                         VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+                        &[
+                            &request_id,
+                            txid,
+                            block_hash,
+                            &block_height.map(|height| height as i64),
+                            &parachain_fee.and_then(sql_amount),
+                            at,
+                        ],
+                    )
+                    .await?;
+            }
+            AnalyticsEvent::Transition(transition) => {
+                let request_id = format!("{:?}", transition.request_id);
+                insert_request(client, &request_id, transition.kind, at).await?;
+                let from = transition.from.as_ref().map(|from| state_columns(from).0);
+                let (to, txid, block_hash, reason) = state_columns(&transition.to);
+                client
+                    .execute(
+                        "INSERT INTO transitions (request_id, from_state, to_state, txid, block_hash, reason, at) \
+                         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                        &[&request_id, &from, &to, &txid, &block_hash, &reason, at],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the requests, payments, proofs and state transitions of this vault to a
+    /// Postgres database, in normalized tables that are created if they do not exist.
+    /// Events are buffered while the database is unreachable, up to a limit after which
+    /// they are dropped, so that the vault never waits for the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_url` - connection string of the database, e.g. `postgres://user@host/vault`
+    /// * `ca_file` - PEM certificate of the CA of the database, if not trusted by the system
+    /// * `network` - the bitcoin network, to encode the addresses
+    pub async fn run_analytics_sink(
+        database_url: String,
+        ca_file: Option<PathBuf>,
+        network: Network,
+    ) -> Result<(), ServiceError> {
+        let tls = tls_connector(ca_file).await?;
+        let (sender, mut receiver) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        *SINK.lock().expect("poisoned") = Some(sender);
+
+        let forward_transitions = async {
+            let mut transitions = request_state::subscribe_transitions();
+            loop {
+                match transitions.recv().await {
+                    Ok(transition) => record(|| AnalyticsEvent::Transition(transition)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        ANALYTICS_EVENTS.with_label_values(&["dropped"]).inc_by(skipped as _)
+                    }
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        };
+
+        let write_events = async {
+            let mut pending = None;
+            loop {
+                let client = match connect(&database_url, tls.clone()).await {
+                    Ok(client) => client,
+                    Err(err) => {
+                        tracing::error!("Failed to connect to the analytics database: {}", err);
+                        delay_for(RECONNECT_DELAY).await;
+                        continue;
+                    }
+                };
+                tracing::info!("Connected to the analytics database");
+
+                loop {
+                    let record = match pending.take() {
+                        Some(record) => record,
+                        None => match receiver.recv().await {
+                            Some(record) => record,
+                            None => return,
+                        },
+                    };
+                    match write(&client, network, &record).await {
+                        Ok(()) => ANALYTICS_EVENTS.with_label_values(&["written"]).inc(),
+                        Err(err) if client.is_closed() => {
+                            tracing::error!("Lost the connection to the analytics database: {}", err);
+                            // written again once reconnected
+                            pending = Some(record);
+                            break;
+                        }
+                        Err(err) => {
+                            tracing::error!("Failed to write {:?} to the analytics database: {}", record.event, err);
+                            ANALYTICS_EVENTS.with_label_values(&["failed"]).inc();
+                        }
+                    }
+                }
+                delay_for(RECONNECT_DELAY).await;
+            }
+        };
+
+        futures::future::join(forward_transitions, write_events).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_columns() {
+        assert_eq!(state_columns(&RequestState::Seen), ("seen", None, None, None));
+        assert_eq!(
+            state_columns(&RequestState::Confirmed {
+                txid: "ab".to_string(),
+                block_hash: "cd".to_string(),
+            }),
+            ("confirmed", Some("ab"), Some("cd"), None)
+        );
+        assert_eq!(
+            state_columns(&RequestState::Failed {
+                reason: "timeout".to_string(),
+            }),
+            ("failed", None, None, Some("timeout"))
+        );
+    }
+
+    #[test]
+    fn test_record_without_sink() {
+        // nothing is built unless the sink is running
+        record(|| panic!("event built without a sink"));
+    }
+
+    #[test]
+    fn test_send_drops_when_full() {
+        let (mut sender, mut receiver) = mpsc::channel(1);
+        let event = AnalyticsEvent::Transition(Transition {
+            request_id: H256::zero(),
+            kind: RequestKind::Issue,
+            from: None,
+            to: RequestState::Seen,
+        });
+        assert!(send(&mut sender, event.clone()));
+        assert!(!send(&mut sender, event.clone()));
+        assert_eq!(receiver.try_recv().unwrap().event, event);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    metrics::{EXTERNAL_SPENDS, THEFT_FLAGGED},
+    Error,
+};
+use bitcoin::{Address, BitcoinCore, BitcoinCoreApi, Hash, OutPoint, PartialAddress, TransactionExt, Txid};
+use runtime::{
+    pallets::staked_relayers::VaultTheftEvent, AccountId, BtcAddress, InterBtcParachain, InterBtcRuntime, UtilFuncs,
+    VaultStatus,
+};
+use serde::Serialize;
+use service::Error as ServiceError;
+use sp_core::H256;
+use std::{collections::HashSet, time::Duration};
+use tokio::time::delay_for;
+
+const EXTERNAL_SPEND_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Stop all bitcoin spends and alert the operator, further payments would only
+/// increase the amount that needs to be recovered through governance.
+fn on_theft_flagged(bitcoin_core: &BitcoinCore, reason: &str) {
+    bitcoin_core.halt_spends();
+    THEFT_FLAGGED.set(1);
+    tracing::error!(
+        "Vault has been flagged for theft ({}), halted all bitcoin spends. Run `vault appeal-info` to collect \
+         the evidence for an appeal",
+        reason
+    );
+}
+
+/// Watch for theft reports against this vault, both on startup and for new events.
+pub async fn listen_for_own_theft(
+    parachain_rpc: InterBtcParachain,
+    bitcoin_core: BitcoinCore,
+) -> Result<(), ServiceError> {
+    let vault_id = parachain_rpc.get_account_id();
+    match parachain_rpc.get_vault_unchecked(vault_id.clone()).await {
+        Ok(vault) if matches!(vault.status, VaultStatus::CommittedTheft) => {
+            on_theft_flagged(&bitcoin_core, "status is CommittedTheft");
+        }
+        Ok(_) => {}
+        Err(err) => tracing::warn!("Failed to check vault status: {}", err),
+    }
+
+    let bitcoin_core = &bitcoin_core;
+    parachain_rpc
+        .on_event::<VaultTheftEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if &event.vault_id == vault_id {
+                    let txid = Txid::from_slice(&event.txid.to_bytes_le()).map(|txid| txid.to_string());
+                    on_theft_flagged(bitcoin_core, &format!("reported tx {}", txid.unwrap_or_default()));
+                }
+            },
+            |error| tracing::error!("Error reading vault theft event: {}", error.to_string()),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Check whether any of the watched wallet outputs is spent in the mempool by a transaction
+/// that was not created by this vault, then watch the currently unspent outputs.
+async fn check_external_spends(bitcoin_core: &BitcoinCore, watched: &mut HashSet<OutPoint>) -> Result<(), Error> {
+    let outpoints: Vec<_> = watched.iter().copied().collect();
+    for (outpoint, txid) in bitcoin_core.get_spending_txs(&outpoints).await? {
+        if !bitcoin_core.is_own_transaction(&txid).await {
+            EXTERNAL_SPENDS.inc();
+            tracing::error!(
+                "Wallet output {} is spent by transaction {} that was not created by this vault, the keys of \
+                 the wallet may be compromised",
+                outpoint,
+                txid
+            );
+        }
+    }
+    *watched = bitcoin_core.list_unspent_outpoints().await?.into_iter().collect();
+    Ok(())
+}
+
+/// Detect spends of wallet outputs from the mempool before they are confirmed and reported
+/// as theft, e.g. if the keys of the wallet leaked.
+pub async fn monitor_external_spends(bitcoin_core: BitcoinCore) -> Result<(), ServiceError> {
+    let mut watched = HashSet::new();
+    loop {
+        if let Err(err) = check_external_spends(&bitcoin_core, &mut watched).await {
+            tracing::warn!("Failed to check for external spends: {}", err);
+        }
+        delay_for(EXTERNAL_SPEND_INTERVAL).await;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputEvidence {
+    pub value: u64,
+    pub address: Option<String>,
+    /// True if the output pays to an address registered by the vault, e.g. change.
+    pub registered: bool,
+}
+
+/// A bitcoin transaction spending from the vault's wallet, as reported for theft.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionEvidence {
+    pub txid: Txid,
+    /// Request id of the OP_RETURN output, if any.
+    pub op_return: Option<String>,
+    pub outputs: Vec<OutputEvidence>,
+    pub fee: Option<u64>,
+}
+
+/// Bitcoin payment made by the vault for a redeem, replace or refund request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Payment {
+    pub txid: Txid,
+    pub request_id: H256,
+}
+
+/// Evidence needed for a governance appeal against a theft report.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppealInfo {
+    pub vault_id: AccountId,
+    pub status: String,
+    pub parachain_height: u32,
+    pub bitcoin_height: u64,
+    pub registered_addresses: Vec<String>,
+    /// Payments made by the wallet for redeem, replace and refund requests.
+    pub payments: Vec<Payment>,
+    pub transactions: Vec<TransactionEvidence>,
+}
+
+fn encode_address(address: &BtcAddress, bitcoin_core: &BitcoinCore) -> Option<String> {
+    address.encode_str(bitcoin_core.network()).ok()
+}
+
+/// Collect the state of the vault, its payments and the given (reported) transactions.
+pub async fn collect_appeal_info(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    txids: &[Txid],
+) -> Result<AppealInfo, Error> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+    let vault = parachain_rpc.get_vault_unchecked(vault_id.clone()).await?;
+    let registered = &vault.wallet.addresses;
+
+    let mut transactions = Vec::new();
+    for txid in txids {
+        let tx = bitcoin_core.get_transaction_with_prevouts(txid, None).await?;
+        let outputs = tx
+            .transaction
+            .output
+            .iter()
+            .map(|output| {
+                let address = Address::from_script(&output.script_pubkey, bitcoin_core.network());
+                let partial = address
+                    .as_ref()
+                    .and_then(|address| BtcAddress::from_payload(address.payload.clone()).ok());
+                OutputEvidence {
+                    value: output.value,
+                    address: address.map(|address| address.to_string()),
+                    registered: partial.map_or(false, |partial| registered.contains(&partial)),
+                }
+            })
+            .collect();
+        transactions.push(TransactionEvidence {
+            txid: *txid,
+            op_return: tx.transaction.get_op_return().map(|id| format!("{:?}", id)),
+            outputs,
+            fee: tx.fee(),
+        });
+    }
+
+    Ok(AppealInfo {
+        vault_id,
+        status: format!("{:?}", vault.status),
+        parachain_height: parachain_rpc.get_current_chain_height().await?,
+        bitcoin_height: bitcoin_core.get_block_count().await?,
+        registered_addresses: registered
+            .iter()
+            .filter_map(|address| encode_address(address, bitcoin_core))
+            .collect(),
+        payments: bitcoin_core
+            .get_outgoing_payments()
+            .await?
+            .into_iter()
+            .map(|(txid, request_id)| Payment { txid, request_id })
+            .collect(),
+        transactions,
+    })
+}
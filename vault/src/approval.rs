@@ -0,0 +1,141 @@
+use crate::{
+    concurrency::TaskLimiter,
+    metrics::{APPROVAL_TIMEOUTS, PENDING_APPROVALS},
+    Error,
+};
+use runtime::BtcAddress;
+use sp_core::H256;
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::time::delay_for;
+
+const APPROVAL_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Gate holding back payments above a threshold until an operator approves them. There is
+/// no operator API yet, so a pending payment is written to `<id>.pending` in the approval
+/// directory and the operator confirms it by creating `<id>.approved`, or refuses it by
+/// creating `<id>.rejected`. Payments that are not confirmed in time are aborted.
+#[derive(Debug, Clone, Default)]
+pub struct PaymentApproval {
+    /// Amount (in satoshi) above which payments require approval, disabled if not set.
+    threshold: Option<u128>,
+    dir: PathBuf,
+    timeout: Duration,
+    /// Limiter of the calling task, whose slot is freed while waiting for the operator.
+    task_limiter: Option<TaskLimiter>,
+}
+
+impl PaymentApproval {
+    pub fn new(threshold: Option<u128>, dir: PathBuf, timeout: Duration) -> Self {
+        Self {
+            threshold,
+            dir,
+            timeout,
+            task_limiter: None,
+        }
+    }
+
+    /// Free the slot of the calling task in the limiter while waiting for approval, so that
+    /// payments pending approval do not hold back other requests.
+    pub fn releasing_slot_of(mut self, task_limiter: TaskLimiter) -> Self {
+        self.task_limiter = Some(task_limiter);
+        self
+    }
+
+    pub fn requires_approval(&self, amount: u128) -> bool {
+        matches!(self.threshold, Some(threshold) if amount > threshold)
+    }
+
+    fn path(&self, request_id: H256, status: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.{}", hex::encode(request_id.as_bytes()), status))
+    }
+
+    /// Waits until the payment is approved. Returns immediately if the amount does not
+    /// require approval.
+    pub async fn await_approval(&self, request_id: H256, amount: u128, btc_address: &BtcAddress) -> Result<(), Error> {
+        if !self.requires_approval(amount) {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let pending = self.path(request_id, "pending");
+        fs::write(&pending, format!("amount={}\naddress={:?}\n", amount, btc_address))?;
+        tracing::warn!(
+            "Payment of {} sat to {:?} for request #{} requires approval, create {} to confirm",
+            amount,
+            btc_address,
+            request_id,
+            self.path(request_id, "approved").display()
+        );
+
+        PENDING_APPROVALS.inc();
+        let result = match &self.task_limiter {
+            Some(task_limiter) => task_limiter.release_while(self.poll(request_id)).await,
+            None => self.poll(request_id).await,
+        };
+        PENDING_APPROVALS.dec();
+        let _ = fs::remove_file(&pending);
+
+        match &result {
+            Ok(()) => tracing::info!("Payment for request #{} was approved", request_id),
+            Err(Error::ApprovalTimeout) => {
+                APPROVAL_TIMEOUTS.inc();
+                tracing::error!("Payment for request #{} was not approved in time, aborting", request_id);
+            }
+            Err(err) => tracing::error!("Payment for request #{} was not approved: {}", request_id, err),
+        }
+        result
+    }
+
+    async fn poll(&self, request_id: H256) -> Result<(), Error> {
+        let approved = self.path(request_id, "approved");
+        let rejected = self.path(request_id, "rejected");
+        let start = Instant::now();
+        loop {
+            if rejected.exists() {
+                return Err(Error::PaymentRejected);
+            }
+            if approved.exists() {
+                return Ok(());
+            }
+            if start.elapsed() >= self.timeout {
+                return Err(Error::ApprovalTimeout);
+            }
+            delay_for(APPROVAL_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_await_approval() {
+        let dir = std::env::temp_dir().join(format!("payment-approvals-{}", std::process::id()));
+        let approval = PaymentApproval::new(Some(100), dir.clone(), Duration::from_secs(0));
+        let request_id = H256::from_slice(&[1; 32]);
+
+        // small payments do not require approval
+        assert!(approval
+            .await_approval(request_id, 100, &BtcAddress::default())
+            .await
+            .is_ok());
+        assert!(matches!(
+            approval.await_approval(request_id, 101, &BtcAddress::default()).await,
+            Err(Error::ApprovalTimeout)
+        ));
+
+        fs::write(approval.path(request_id, "approved"), "").unwrap();
+        assert!(approval
+            .await_approval(request_id, 101, &BtcAddress::default())
+            .await
+            .is_ok());
+        assert!(!approval.path(request_id, "pending").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use crate::metrics::BAN_BLOCKS_REMAINING;
+use runtime::{
+    pallets::security::UpdateActiveBlockEvent, BlockNumber, InterBtcParachain, InterBtcRuntime, UtilFuncs,
+    VaultRegistryPallet,
+};
+use service::Error as ServiceError;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+/// Number of active blocks for which this vault remains banned, shared between tasks so
+/// that actions that would fail on chain (e.g. accepting replace requests) are paused.
+#[derive(Clone, Default)]
+pub struct BanStatus {
+    remaining: Arc<AtomicU32>,
+}
+
+impl BanStatus {
+    /// Update the status from the `banned_until` block of the vault at the given active block,
+    /// returns the number of remaining blocks.
+    pub fn update(&self, banned_until: Option<BlockNumber>, active_block: BlockNumber) -> u32 {
+        let remaining = banned_until.map_or(0, |banned_until| banned_until.saturating_sub(active_block));
+        self.remaining.store(remaining, Ordering::SeqCst);
+        BAN_BLOCKS_REMAINING.set(remaining as i64);
+        remaining
+    }
+
+    pub fn is_banned(&self) -> bool {
+        self.blocks_remaining() > 0
+    }
+
+    pub fn blocks_remaining(&self) -> u32 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+}
+
+async fn refresh_ban_status(
+    parachain_rpc: &InterBtcParachain,
+    ban_status: &BanStatus,
+    active_block: BlockNumber,
+) -> Result<(), runtime::Error> {
+    let vault = match parachain_rpc.get_vault(parachain_rpc.get_account_id().clone()).await {
+        Ok(vault) => vault,
+        // not registered (yet) or liquidated, nothing to pause
+        Err(runtime::Error::VaultNotFound) | Err(runtime::Error::VaultLiquidated) => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let was_banned = ban_status.is_banned();
+    let remaining = ban_status.update(vault.banned_until, active_block);
+    match (was_banned, remaining > 0) {
+        (false, true) => tracing::warn!(
+            "Vault is banned for {} blocks (until block {:?}), pausing replace requests",
+            remaining,
+            vault.banned_until
+        ),
+        (true, false) => tracing::info!("Vault ban has lapsed, resuming normal operation"),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Track whether this vault is banned, re-checking on every active block.
+pub async fn monitor_ban_status(parachain_rpc: InterBtcParachain, ban_status: BanStatus) -> Result<(), ServiceError> {
+    let parachain_rpc = &parachain_rpc;
+    let ban_status = &ban_status;
+    parachain_rpc
+        .on_event::<UpdateActiveBlockEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if let Err(err) = refresh_ban_status(parachain_rpc, ban_status, event.height).await {
+                    tracing::warn!("Failed to check ban status: {}", err);
+                }
+            },
+            |err| tracing::error!("Error (UpdateActiveBlockEvent): {}", err.to_string()),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ban_status() {
+        let ban_status = BanStatus::default();
+        assert!(!ban_status.is_banned());
+
+        assert_eq!(ban_status.update(Some(110), 100), 10);
+        assert!(ban_status.clone().is_banned());
+
+        assert_eq!(ban_status.update(Some(110), 110), 0);
+        assert!(!ban_status.is_banned());
+        assert_eq!(ban_status.update(None, 120), 0);
+    }
+}
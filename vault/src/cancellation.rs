@@ -1,18 +1,21 @@
 use super::Error;
-use crate::execution::parachain_blocks_to_bitcoin_blocks_rounded_up;
+use crate::{execution::parachain_blocks_to_bitcoin_blocks_rounded_up, request_state};
 use async_trait::async_trait;
 use futures::{channel::mpsc::Receiver, *};
 use runtime::{
-    AccountId, BlockNumber, Error as RuntimeError, IssuePallet, IssueRequestStatus, ReplacePallet,
+    AccountId, BlockNumber, Error as RuntimeError, IssuePallet, IssueRequestStatus, RedeemPallet, ReplacePallet,
     ReplaceRequestStatus, SecurityPallet, UtilFuncs,
 };
 use sp_core::H256;
-use std::marker::{Send, Sync};
+use std::{
+    marker::{Send, Sync},
+    str::FromStr,
+};
 
 pub enum Event {
-    /// new issue requested / replace accepted
+    /// new issue requested / replace accepted / redeem requested by this vault
     Opened,
-    /// issue / replace successfully executed
+    /// issue / replace / redeem successfully executed (or canceled, for redeems)
     Executed(H256),
     ParachainBlock(BlockNumber),
     BitcoinBlock(u32),
@@ -27,7 +30,7 @@ pub struct CancellationScheduler<P: IssuePallet + ReplacePallet + UtilFuncs + Cl
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-struct ActiveRequest {
+pub(crate) struct ActiveRequest {
     id: H256,
     parachain_deadline_height: u32,
     bitcoin_deadline_height: u32,
@@ -40,7 +43,7 @@ pub struct UnconvertedOpenTime {
 }
 
 #[derive(PartialEq, Debug)]
-enum ListState {
+pub(crate) enum ListState {
     Valid,
     Invalid,
 }
@@ -145,6 +148,70 @@ impl<P: IssuePallet + ReplacePallet + Send + Sync> Canceller<P> for ReplaceCance
     }
 }
 
+/// What to do with redeems requested by this vault's account when the redeeming vault
+/// misses the deadline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedeemCancelPolicy {
+    /// Burn the tokens and receive their value plus a punishment in collateral.
+    Reimburse,
+    /// Keep the tokens and receive a punishment in collateral, so that the redeem can be
+    /// requested again from another vault.
+    Retry,
+}
+
+impl FromStr for RedeemCancelPolicy {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "reimburse" => Ok(RedeemCancelPolicy::Reimburse),
+            "retry" => Ok(RedeemCancelPolicy::Retry),
+            _ => Err(format!("expected reimburse or retry, got {}", src)),
+        }
+    }
+}
+
+/// Cancels redeems requested by this vault's account from other vaults (e.g. when
+/// rebalancing), reimbursing the tokens if `REIMBURSE` is set, see [`RedeemCancelPolicy`].
+pub struct RedeemCanceller<const REIMBURSE: bool>;
+
+#[async_trait]
+impl<P: RedeemPallet + Send + Sync, const REIMBURSE: bool> Canceller<P> for RedeemCanceller<REIMBURSE> {
+    const TYPE_NAME: &'static str = "redeem";
+
+    async fn get_open_requests(parachain_rpc: &P, vault_id: AccountId) -> Result<Vec<UnconvertedOpenTime>, Error>
+    where
+        P: 'async_trait,
+    {
+        let ret = parachain_rpc
+            .get_all_open_redeem_requests()
+            .await?
+            .iter()
+            .filter(|(_, redeem)| redeem.redeemer == vault_id)
+            .map(|(id, redeem)| UnconvertedOpenTime {
+                id: *id,
+                parachain_open_height: redeem.opentime,
+                bitcoin_open_height: redeem.btc_height,
+            })
+            .collect();
+        Ok(ret)
+    }
+
+    async fn get_period(parachain_rpc: &P) -> Result<u32, Error>
+    where
+        P: 'async_trait,
+    {
+        Ok(parachain_rpc.get_redeem_period().await?)
+    }
+
+    async fn cancel_request(parachain_rpc: &P, request_id: H256) -> Result<(), Error>
+    where
+        P: 'async_trait,
+    {
+        Ok(parachain_rpc.cancel_redeem(request_id, REIMBURSE).await?)
+    }
+}
+
 // verbose drain_filter
 fn drain_expired(requests: &mut Vec<ActiveRequest>, current_height: u32, bitcoin_height: u32) -> Vec<ActiveRequest> {
     let mut expired = Vec::new();
@@ -206,7 +273,7 @@ impl<P: IssuePallet + ReplacePallet + UtilFuncs + SecurityPallet + Clone> Cancel
         let cancellable_requests = drain_expired(active_requests, self.parachain_height, self.bitcoin_height);
 
         for request in cancellable_requests {
-            match T::cancel_request(&self.parachain_rpc, request.id).await {
+            match request_state::correlate(request.id, T::cancel_request(&self.parachain_rpc, request.id)).await {
                 Ok(_) => tracing::info!("Canceled {} #{:?}", T::TYPE_NAME, request.id),
                 Err(e) => {
                     // failed to cancel; get up-to-date request list in next iteration
@@ -220,7 +287,7 @@ impl<P: IssuePallet + ReplacePallet + UtilFuncs + SecurityPallet + Clone> Cancel
 
     /// Handles one timeout or event_listener event. This method is split from handle_cancellation for
     /// testing purposes
-    async fn process_event<T: Canceller<P>>(
+    pub(crate) async fn process_event<T: Canceller<P>>(
         &mut self,
         event: Event,
         active_requests: &mut Vec<ActiveRequest>,
@@ -325,8 +392,8 @@ mod tests {
     use async_trait::async_trait;
     use futures::channel::mpsc;
     use runtime::{
-        AccountId, BtcAddress, ErrorCode, InterBtcIssueRequest, InterBtcReplaceRequest, InterBtcRequestIssueEvent,
-        StatusCode,
+        AccountId, BtcAddress, CollateralAmount, ErrorCode, InterBtcIssueRequest, InterBtcRedeemRequest,
+        InterBtcReplaceRequest, InterBtcRequestIssueEvent, StatusCode, SubmissionReceipt, WrappedAmount,
     };
     use sp_core::H256;
     use std::collections::BTreeSet;
@@ -348,16 +415,16 @@ mod tests {
         pub trait IssuePallet {
             async fn request_issue(
                 &self,
-                amount: u128,
+                amount: WrappedAmount,
                 vault_id: &AccountId,
-                griefing_collateral: u128,
+                griefing_collateral: CollateralAmount,
             ) -> Result<InterBtcRequestIssueEvent, RuntimeError>;
             async fn execute_issue(
                 &self,
                 issue_id: H256,
                 merkle_proof: &[u8],
                 raw_tx: &[u8],
-            ) -> Result<(), RuntimeError>;
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
             async fn cancel_issue(&self, issue_id: H256) -> Result<(), RuntimeError>;
             async fn get_issue_request(&self, issue_id: H256) -> Result<InterBtcIssueRequest, RuntimeError>;
             async fn get_vault_issue_requests(
@@ -369,6 +436,31 @@ mod tests {
             async fn get_all_active_issues(&self) -> Result<Vec<(H256, InterBtcIssueRequest)>, RuntimeError>;
         }
 
+        #[async_trait]
+        pub trait RedeemPallet {
+            async fn request_redeem(
+                &self,
+                amount: u128,
+                btc_address: BtcAddress,
+                vault_id: &AccountId,
+            ) -> Result<H256, RuntimeError>;
+            async fn execute_redeem(
+                &self,
+                redeem_id: H256,
+                merkle_proof: &[u8],
+                raw_tx: &[u8],
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
+            async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), RuntimeError>;
+            async fn get_redeem_request(&self, redeem_id: H256) -> Result<InterBtcRedeemRequest, RuntimeError>;
+            async fn get_vault_redeem_requests(
+                &self,
+                account_id: AccountId,
+            ) -> Result<Vec<(H256, InterBtcRedeemRequest)>, RuntimeError>;
+            async fn get_all_open_redeem_requests(&self) -> Result<Vec<(H256, InterBtcRedeemRequest)>, RuntimeError>;
+            async fn get_redeem_period(&self) -> Result<BlockNumber, RuntimeError>;
+            async fn set_redeem_period(&self, period: u32) -> Result<(), RuntimeError>;
+        }
+
         #[async_trait]
         pub trait ReplacePallet {
             async fn request_replace(&self, amount: u128, griefing_collateral: u128) -> Result<(), RuntimeError>;
@@ -385,7 +477,7 @@ mod tests {
                 replace_id: H256,
                 merkle_proof: &[u8],
                 raw_tx: &[u8],
-            ) -> Result<(), RuntimeError>;
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
             async fn cancel_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
             async fn get_replace_request(&self, replace_id: H256) -> Result<InterBtcReplaceRequest, RuntimeError>;
             async fn get_new_vault_replace_requests(
@@ -486,6 +578,56 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_cancel_own_redeem_requests() {
+        // only redeems requested by this vault are cancelled, with the configured policy
+        let vault_id = AccountId::new([1; 32]);
+        let other_id = AccountId::new([2; 32]);
+        let mut parachain_rpc = MockProvider::default();
+        parachain_rpc.expect_get_all_open_redeem_requests().returning(move || {
+            Ok(vec![
+                (
+                    H256::from_slice(&[1; 32]),
+                    InterBtcRedeemRequest {
+                        redeemer: AccountId::new([1; 32]),
+                        opentime: 10_000,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    H256::from_slice(&[2; 32]),
+                    InterBtcRedeemRequest {
+                        redeemer: other_id.clone(),
+                        opentime: 10_000,
+                        ..Default::default()
+                    },
+                ),
+            ])
+        });
+        parachain_rpc.expect_get_redeem_period().returning(|| Ok(100));
+        parachain_rpc
+            .expect_cancel_redeem()
+            .withf(|redeem_id, reimburse| *redeem_id == H256::from_slice(&[1; 32]) && !*reimburse)
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut active_processes: Vec<ActiveRequest> = vec![];
+        let mut cancellation_scheduler = CancellationScheduler::new(parachain_rpc, 0, 2, vault_id);
+
+        assert_eq!(
+            cancellation_scheduler
+                .process_event::<RedeemCanceller<false>>(
+                    Event::ParachainBlock(15000),
+                    &mut active_processes,
+                    ListState::Invalid,
+                )
+                .await
+                .unwrap(),
+            ListState::Valid
+        );
+        assert!(active_processes.is_empty());
+    }
+
     #[tokio::test]
     async fn test_process_event_succeeds() {
         // check that we actually cancel the issue when it expires
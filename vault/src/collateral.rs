@@ -1,16 +1,24 @@
-use crate::error::Error;
+use crate::{
+    error::Error,
+    extrinsic_queue::{is_unreachable, ExtrinsicQueue, QueuedExtrinsic},
+};
 use futures::future;
 use runtime::{
-    pallets::exchange_rate_oracle::SetExchangeRateEvent, AccountId, CollateralBalancesPallet, InterBtcParachain,
-    InterBtcRuntime, UtilFuncs, VaultRegistryPallet, VaultStatus,
+    pallets::exchange_rate_oracle::SetExchangeRateEvent, AccountId, BalanceUpdate, CollateralAmount,
+    CollateralBalancesPallet, InterBtcParachain, InterBtcRuntime, UtilFuncs, VaultRegistryPallet, VaultStatus,
+    COLLATERAL_CURRENCY,
 };
 use service::Error as ServiceError;
 
+/// Keep the collateral at the required level when the exchange rate changes. Deposits that
+/// are interrupted by a parachain outage are added to the `extrinsic_queue`, if any.
 pub async fn maintain_collateralization_rate(
     parachain_rpc: InterBtcParachain,
-    maximum_collateral: Option<u128>,
+    maximum_collateral: Option<CollateralAmount>,
+    extrinsic_queue: Option<ExtrinsicQueue>,
 ) -> Result<(), ServiceError> {
     let parachain_rpc = &parachain_rpc;
+    let extrinsic_queue = &extrinsic_queue;
     parachain_rpc
         .on_event::<SetExchangeRateEvent<InterBtcRuntime>, _, _, _>(
             |_| async move {
@@ -26,6 +34,14 @@ pub async fn maintain_collateralization_rate(
                 {
                     // vault not being registered is ok, no need to log it
                     Err(Error::RuntimeError(runtime::Error::VaultNotFound)) => {}
+                    Err(Error::DepositInterrupted { amount, target }) if extrinsic_queue.is_some() => {
+                        tracing::warn!("Parachain is unreachable, queueing deposit of {} collateral", amount);
+                        let queue = extrinsic_queue.as_ref().expect("checked above");
+                        let target = target.planck();
+                        if let Err(e) = queue.push(QueuedExtrinsic::DepositCollateral { target }) {
+                            tracing::error!("Failed to queue collateral deposit: {}", e);
+                        }
+                    }
                     Err(e) => tracing::error!("Failed to maintain collateral level: {}", e),
                     _ => {} // success
                 }
@@ -36,6 +52,27 @@ pub async fn maintain_collateralization_rate(
     Ok(())
 }
 
+/// Lock the required collateral when the free collateral balance of the vault increases, so
+/// that a shortfall is resolved as soon as the operator tops up the account rather than on
+/// the next exchange rate update.
+pub async fn lock_collateral_on_deposit(
+    parachain_rpc: &InterBtcParachain,
+    maximum_collateral: Option<CollateralAmount>,
+    update: &BalanceUpdate,
+) {
+    let vault_id = parachain_rpc.get_account_id();
+    match update.get(vault_id, COLLATERAL_CURRENCY) {
+        // the first update only reports the current balance
+        Some(change) if change.previous.is_some() && change.free_delta() > 0 => {}
+        _ => return,
+    }
+    match lock_required_collateral(parachain_rpc.clone(), vault_id.clone(), maximum_collateral).await {
+        Err(Error::RuntimeError(runtime::Error::VaultNotFound)) => {}
+        Err(e) => tracing::error!("Failed to lock required collateral after deposit: {}", e),
+        _ => {}
+    }
+}
+
 /// Gets the required collateral for this vault, and if it is more than the actual
 /// collateral (which can happen when the exchange rate changes), attempts to
 /// increase up to maximum_collateral.
@@ -52,7 +89,7 @@ pub async fn maintain_collateralization_rate(
 pub async fn lock_required_collateral<P: VaultRegistryPallet + CollateralBalancesPallet>(
     parachain_rpc: P,
     vault_id: AccountId,
-    maximum_collateral: Option<u128>,
+    maximum_collateral: Option<CollateralAmount>,
 ) -> Result<(), Error> {
     // check that the vault is registered and active
     let vault = parachain_rpc.get_vault(vault_id.clone()).await?;
@@ -60,7 +97,7 @@ pub async fn lock_required_collateral<P: VaultRegistryPallet + CollateralBalance
         return Err(Error::RuntimeError(runtime::Error::VaultNotFound));
     }
 
-    let actual_collateral = vault.backing_collateral;
+    let actual_collateral = CollateralAmount::from_planck(vault.backing_collateral);
 
     let (required_collateral, maximum_collateral) = future::try_join(
         async { Ok(parachain_rpc.get_required_collateral_for_vault(vault_id).await?) },
@@ -69,8 +106,8 @@ pub async fn lock_required_collateral<P: VaultRegistryPallet + CollateralBalance
                 Ok(max)
             } else {
                 // allow all balance to be used as collateral
-                let free = parachain_rpc.get_free_balance().await?;
-                free.checked_add(actual_collateral).ok_or(Error::ArithmeticOverflow)
+                let free = CollateralAmount::from_planck(parachain_rpc.get_free_balance().await?);
+                free.checked_add(actual_collateral).map_err(|_| Error::ArithmeticOverflow)
             }
         },
     )
@@ -106,9 +143,18 @@ pub async fn lock_required_collateral<P: VaultRegistryPallet + CollateralBalance
     // if we can add more collateral
     if actual_collateral < target_collateral {
         // cases 5 & 6
-        let amount_to_increase = target_collateral - actual_collateral;
+        let amount_to_increase = target_collateral.saturating_sub(actual_collateral);
         tracing::info!("Locking additional collateral");
-        parachain_rpc.deposit_collateral(amount_to_increase).await?;
+        parachain_rpc
+            .deposit_collateral(amount_to_increase)
+            .await
+            .map_err(|err| match err {
+                err if is_unreachable(&err) => Error::DepositInterrupted {
+                    amount: amount_to_increase,
+                    target: target_collateral,
+                },
+                err => err.into(),
+            })?;
     }
 
     // if we were unable to add the required amount, return error
@@ -124,7 +170,9 @@ pub async fn lock_required_collateral<P: VaultRegistryPallet + CollateralBalance
 mod tests {
     use super::*;
     use async_trait::async_trait;
-    use runtime::{AccountId, BtcAddress, BtcPublicKey, Error as RuntimeError, InterBtcBalance, InterBtcVault};
+    use runtime::{
+        AccountId, BtcAddress, BtcPublicKey, Error as RuntimeError, InterBtcBalance, InterBtcVault, WrappedAmount,
+    };
 
     macro_rules! assert_ok {
         ( $x:expr $(,)? ) => {
@@ -156,13 +204,13 @@ mod tests {
         pub trait VaultRegistryPallet {
             async fn get_vault(&self, vault_id: AccountId) -> Result<InterBtcVault, RuntimeError>;
             async fn get_all_vaults(&self) -> Result<Vec<InterBtcVault>, RuntimeError>;
-            async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
-            async fn deposit_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-            async fn withdraw_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
+            async fn register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
+            async fn deposit_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
+            async fn withdraw_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
             async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
             async fn register_address(&self, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-            async fn get_required_collateral_for_wrapped(&self, amount_btc: u128) -> Result<u128, RuntimeError>;
-            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, RuntimeError>;
+            async fn get_required_collateral_for_wrapped(&self, amount_btc: WrappedAmount) -> Result<CollateralAmount, RuntimeError>;
+            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<CollateralAmount, RuntimeError>;
         }
 
         #[async_trait]
@@ -182,11 +230,15 @@ mod tests {
         }
     }
 
+    fn dot(planck: u128) -> CollateralAmount {
+        CollateralAmount::from_planck(planck)
+    }
+
     fn setup_mocks(required: u128, actual: u128) -> MockProvider {
         let mut parachain_rpc = MockProvider::default();
         parachain_rpc
             .expect_get_required_collateral_for_vault()
-            .returning(move |_| Ok(required));
+            .returning(move |_| Ok(dot(required)));
 
         parachain_rpc.expect_get_vault().returning(move |x| {
             Ok(InterBtcVault {
@@ -206,7 +258,7 @@ mod tests {
         // check that deposit_collateral is not called
         let parachain_rpc = setup_mocks(50, 75);
 
-        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(100)).await);
+        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(100))).await);
     }
 
     #[tokio::test]
@@ -216,7 +268,7 @@ mod tests {
         // check that deposit_collateral is not called
         let parachain_rpc = setup_mocks(100, 200);
 
-        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(150)).await);
+        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(150))).await);
     }
 
     #[tokio::test]
@@ -226,7 +278,7 @@ mod tests {
         // check that deposit_collateral is not called
         let parachain_rpc = setup_mocks(100, 150);
 
-        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(75)).await);
+        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(75))).await);
     }
 
     #[tokio::test]
@@ -237,7 +289,7 @@ mod tests {
         let parachain_rpc = setup_mocks(100, 75);
 
         assert_err!(
-            lock_required_collateral(parachain_rpc, AccountId::default(), Some(50)).await,
+            lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(50))).await,
             Error::InsufficientFunds
         );
     }
@@ -249,12 +301,12 @@ mod tests {
         let mut parachain_rpc = setup_mocks(100, 25);
         parachain_rpc
             .expect_deposit_collateral()
-            .withf(|&amount| amount == 50)
+            .withf(|&amount| amount == dot(50))
             .times(1)
             .returning(|_| Ok(()));
 
         assert_err!(
-            lock_required_collateral(parachain_rpc, AccountId::default(), Some(75)).await,
+            lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(75))).await,
             Error::InsufficientFunds
         );
     }
@@ -265,11 +317,11 @@ mod tests {
         let mut parachain_rpc = setup_mocks(100, 25);
         parachain_rpc
             .expect_deposit_collateral()
-            .withf(|&amount| amount == 75)
+            .withf(|&amount| amount == dot(75))
             .times(1)
             .returning(|_| Ok(()));
 
-        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(200)).await);
+        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(200))).await);
     }
 
     #[tokio::test]
@@ -279,7 +331,7 @@ mod tests {
         let parachain_rpc = setup_mocks(100, 25);
 
         assert_err!(
-            lock_required_collateral(parachain_rpc, AccountId::default(), Some(25)).await,
+            lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(25))).await,
             Error::InsufficientFunds
         );
     }
@@ -290,7 +342,7 @@ mod tests {
         // check that deposit_collateral is not called with amount 0
         let parachain_rpc = setup_mocks(100, 100);
 
-        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(200)).await);
+        assert_ok!(lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(200))).await);
     }
 
     #[tokio::test]
@@ -305,7 +357,7 @@ mod tests {
         });
 
         assert_err!(
-            lock_required_collateral(parachain_rpc, AccountId::default(), Some(75)).await,
+            lock_required_collateral(parachain_rpc, AccountId::default(), Some(dot(75))).await,
             Error::RuntimeError(runtime::Error::VaultNotFound)
         );
     }
@@ -0,0 +1,186 @@
+use crate::metrics::{QUEUED_TASKS, RUNNING_TASKS};
+use futures::Future;
+use std::sync::Arc;
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+/// Bounds the number of concurrently running tasks of a single kind. Tasks
+/// that are spawned while all slots are taken are queued until a running
+/// task completes. Queue depth and active tasks are exported as metrics.
+#[derive(Clone, Debug)]
+pub struct TaskLimiter {
+    name: &'static str,
+    semaphore: Arc<Semaphore>,
+}
+
+impl TaskLimiter {
+    /// Create a new limiter allowing at most `max_concurrent` tasks to run at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - label under which the metrics of these tasks are reported
+    /// * `max_concurrent` - the maximum number of tasks to run in parallel
+    pub fn new(name: &'static str, max_concurrent: usize) -> Self {
+        Self {
+            name,
+            // a limit of zero would block all tasks forever
+            semaphore: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Spawn the task as soon as a slot is available.
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let name = self.name;
+        let semaphore = self.semaphore.clone();
+        QUEUED_TASKS.with_label_values(&[name]).inc();
+        tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            QUEUED_TASKS.with_label_values(&[name]).dec();
+            RUNNING_TASKS.with_label_values(&[name]).inc();
+            let result = task.await;
+            RUNNING_TASKS.with_label_values(&[name]).dec();
+            result
+        })
+    }
+
+    /// Await the task in the calling task as soon as a slot is available, e.g. for tasks
+    /// borrowing from the caller.
+    pub async fn run<F: Future>(&self, task: F) -> F::Output {
+        QUEUED_TASKS.with_label_values(&[self.name]).inc();
+        let _permit = self.semaphore.acquire().await;
+        QUEUED_TASKS.with_label_values(&[self.name]).dec();
+        RUNNING_TASKS.with_label_values(&[self.name]).inc();
+        let result = task.await;
+        RUNNING_TASKS.with_label_values(&[self.name]).dec();
+        result
+    }
+
+    /// Lend the slot of the calling task to other tasks while awaiting `future`, e.g. while
+    /// waiting for an operator, and take a slot again before continuing. Must only be called
+    /// from a task spawned by this limiter.
+    pub async fn release_while<F: Future>(&self, future: F) -> F::Output {
+        self.semaphore.add_permits(1);
+        RUNNING_TASKS.with_label_values(&[self.name]).dec();
+        let mut lent = LentSlot {
+            name: self.name,
+            semaphore: self.semaphore.clone(),
+            reclaimed: false,
+        };
+        let result = future.await;
+        self.semaphore.acquire().await.forget();
+        lent.reclaimed = true;
+        RUNNING_TASKS.with_label_values(&[self.name]).inc();
+        result
+    }
+}
+
+/// Slot lent out by [`TaskLimiter::release_while`]. Takes it back if the future is dropped
+/// before it completes, e.g. if the task is cancelled, which would otherwise raise the limit
+/// for good.
+struct LentSlot {
+    name: &'static str,
+    semaphore: Arc<Semaphore>,
+    reclaimed: bool,
+}
+
+impl Drop for LentSlot {
+    fn drop(&mut self) {
+        if self.reclaimed {
+            return;
+        }
+        RUNNING_TASKS.with_label_values(&[self.name]).inc();
+        match self.semaphore.try_acquire() {
+            Ok(permit) => permit.forget(),
+            Err(_) => {
+                // all slots are taken, take back the next one that is released
+                let semaphore = self.semaphore.clone();
+                tokio::spawn(async move { semaphore.acquire().await.forget() });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_task_limiter_bounds_concurrency() {
+        let limiter = TaskLimiter::new("test", 2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let running = running.clone();
+                let max_running = max_running.clone();
+                limiter.spawn(async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::delay_for(std::time::Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        futures::future::join_all(handles).await;
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_task_limiter_run_bounds_concurrency() {
+        let limiter = TaskLimiter::new("test", 2);
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        futures::future::join_all((0..8).map(|_| {
+            limiter.run(async {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::delay_for(std::time::Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+        }))
+        .await;
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_task_limiter_release_while() {
+        let limiter = TaskLimiter::new("test", 1);
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let waiting = {
+            let limiter = limiter.clone();
+            limiter
+                .clone()
+                .spawn(async move { limiter.release_while(rx).await.unwrap() })
+        };
+        // runs although the only slot is taken by the waiting task
+        limiter.spawn(async move { tx.send(()).unwrap() }).await.unwrap();
+        waiting.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_task_limiter_release_while_cancelled() {
+        let limiter = TaskLimiter::new("test", 1);
+        let (_tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let (waiting, abort_handle) = {
+            let limiter = limiter.clone();
+            futures::future::abortable(async move { limiter.run(limiter.release_while(rx)).await })
+        };
+        let waiting = tokio::spawn(waiting);
+        tokio::task::yield_now().await;
+        abort_handle.abort();
+        assert!(waiting.await.unwrap().is_err());
+
+        // the lent slot was taken back, the limit is still one
+        assert_eq!(limiter.semaphore.available_permits(), 1);
+    }
+}
@@ -0,0 +1,168 @@
+use lazy_static::lazy_static;
+use prometheus::{Gauge, IntGauge};
+use runtime::{InterBtcParachain, UtilFuncs};
+use service::Error as ServiceError;
+use std::{
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::time::{delay_for, timeout};
+
+/// Interval at which the latency of the parachain RPC is probed.
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Weight of the latest probe in the smoothed latency.
+const SMOOTHING: f64 = 0.3;
+
+/// Maximum time a non-critical submission is deferred while degraded.
+const MAX_DEFERRAL: Duration = Duration::from_secs(10 * 60);
+
+lazy_static! {
+    pub static ref RPC_LATENCY: Gauge = Gauge::new(
+        "rpc_latency_seconds",
+        "Smoothed latency of the parachain RPC as measured by periodic probes"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref DEGRADED_MODE: IntGauge = IntGauge::new(
+        "degraded_mode",
+        "Set to 1 while the vault operates in slow-RPC degradation mode"
+    )
+    .expect("Failed to create prometheus metric");
+}
+
+static DEGRADED: AtomicBool = AtomicBool::new(false);
+static MULTIPLIER: AtomicU32 = AtomicU32::new(1);
+
+/// Latencies at which the vault enters and leaves degradation mode. The recovery threshold
+/// is lower than the degradation threshold, so that the mode does not flap.
+#[derive(Debug, Clone, Copy)]
+pub struct DegradationThresholds {
+    pub degrade_above: Duration,
+    pub recover_below: Duration,
+    /// Factor by which non-critical polling intervals and batch sizes are increased.
+    pub multiplier: u32,
+}
+
+#[derive(Debug, Default)]
+struct LatencyTracker {
+    average: Option<f64>,
+    degraded: bool,
+}
+
+impl LatencyTracker {
+    /// Add a probe to the smoothed latency, returns the new mode if it changed.
+    fn observe(&mut self, latency: Duration, thresholds: &DegradationThresholds) -> Option<bool> {
+        let latency = latency.as_secs_f64();
+        let average = match self.average {
+            Some(average) => average * (1.0 - SMOOTHING) + latency * SMOOTHING,
+            None => latency,
+        };
+        self.average = Some(average);
+
+        let degraded = if self.degraded {
+            average >= thresholds.recover_below.as_secs_f64()
+        } else {
+            average > thresholds.degrade_above.as_secs_f64()
+        };
+        if degraded == std::mem::replace(&mut self.degraded, degraded) {
+            None
+        } else {
+            Some(degraded)
+        }
+    }
+}
+
+/// True while the parachain RPC is slow.
+pub fn is_degraded() -> bool {
+    DEGRADED.load(Ordering::Relaxed)
+}
+
+fn multiplier() -> u32 {
+    if is_degraded() {
+        MULTIPLIER.load(Ordering::Relaxed).max(1)
+    } else {
+        1
+    }
+}
+
+/// Interval of a non-critical polling task, stretched while degraded.
+pub fn stretch(interval: Duration) -> Duration {
+    interval * multiplier()
+}
+
+/// Number of items to submit at once, increased while degraded to save round trips.
+pub fn batch_size(size: u32) -> u32 {
+    size.saturating_mul(multiplier())
+}
+
+/// Defer a non-critical submission while degraded, so that deadline-critical submissions
+/// (i.e. the executions of redeem, replace and refund payments) are not slowed down further.
+pub async fn yield_to_critical() {
+    let start = Instant::now();
+    while is_degraded() && start.elapsed() < MAX_DEFERRAL {
+        delay_for(PROBE_INTERVAL).await;
+    }
+}
+
+/// Periodically probe the latency of the parachain RPC and switch between the normal and
+/// the degradation mode. A failed probe counts as slow as the time it took to fail, a probe
+/// that does not return within twice the degradation threshold is abandoned and counts as that slow.
+pub async fn monitor_rpc_latency(
+    parachain_rpc: InterBtcParachain,
+    thresholds: DegradationThresholds,
+) -> Result<(), ServiceError> {
+    MULTIPLIER.store(thresholds.multiplier, Ordering::Relaxed);
+    let mut tracker = LatencyTracker::default();
+    loop {
+        let start = Instant::now();
+        match timeout(thresholds.degrade_above * 2, parachain_rpc.get_current_chain_height()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => tracing::debug!("Latency probe failed: {}", err),
+            Err(_) => tracing::debug!("Latency probe timed out"),
+        }
+        let latency = start.elapsed();
+
+        match tracker.observe(latency, &thresholds) {
+            Some(true) => tracing::warn!(
+                "Parachain RPC latency of {:.1}s exceeds {:?}, entering degradation mode",
+                tracker.average.unwrap_or_default(),
+                thresholds.degrade_above
+            ),
+            Some(false) => tracing::info!("Parachain RPC latency recovered, leaving degradation mode"),
+            None => {}
+        }
+        DEGRADED.store(tracker.degraded, Ordering::Relaxed);
+        DEGRADED_MODE.set(tracker.degraded as i64);
+        RPC_LATENCY.set(tracker.average.unwrap_or_default());
+
+        delay_for(PROBE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degradation_hysteresis() {
+        let thresholds = DegradationThresholds {
+            degrade_above: Duration::from_secs(2),
+            recover_below: Duration::from_millis(500),
+            multiplier: 4,
+        };
+        let mut tracker = LatencyTracker::default();
+        assert_eq!(tracker.observe(Duration::from_millis(100), &thresholds), None);
+        // a single slow probe is smoothed out
+        assert_eq!(tracker.observe(Duration::from_secs(5), &thresholds), None);
+        assert_eq!(tracker.observe(Duration::from_secs(5), &thresholds), Some(true));
+        // stays degraded between the thresholds
+        for _ in 0..3 {
+            assert_eq!(tracker.observe(Duration::from_secs(1), &thresholds), None);
+        }
+        let mut recovered = None;
+        for _ in 0..10 {
+            recovered = recovered.or(tracker.observe(Duration::from_millis(100), &thresholds));
+        }
+        assert_eq!(recovered, Some(false));
+    }
+}
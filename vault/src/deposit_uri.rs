@@ -0,0 +1,69 @@
+use bitcoin::{payment_uri, Network};
+use lazy_static::lazy_static;
+use runtime::BtcAddress;
+use sp_core::H256;
+use std::{collections::HashMap, str::FromStr, sync::RwLock};
+
+lazy_static! {
+    static ref DEPOSITS: RwLock<HashMap<H256, Deposit>> = RwLock::new(HashMap::new());
+}
+
+/// Payment expected for an open issue request of this vault.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Deposit {
+    address: BtcAddress,
+    /// Amount including the fee, in satoshis.
+    amount: u128,
+}
+
+/// Remember the payment expected for the issue request, until it is executed or cancelled.
+pub(crate) fn insert(issue_id: H256, address: BtcAddress, amount: u128) {
+    if let Ok(mut deposits) = DEPOSITS.write() {
+        deposits.insert(issue_id, Deposit { address, amount });
+    }
+}
+
+pub(crate) fn remove(issue_id: &H256) {
+    if let Ok(mut deposits) = DEPOSITS.write() {
+        deposits.remove(issue_id);
+    }
+}
+
+/// BIP21 URI for the payment of an open issue request of this vault, labelled with the issue id.
+pub fn deposit_uri(issue_id: &H256, network: Network) -> Option<String> {
+    let deposit = *DEPOSITS.read().ok()?.get(issue_id)?;
+    let label = format!("issue {:?}", issue_id);
+    payment_uri(&deposit.address, network, deposit.amount as u64, Some(&label)).ok()
+}
+
+/// Parse the issue id of a request for `/issues/<id>/payment-uri`.
+pub(crate) fn parse_path(path: &str) -> Option<H256> {
+    let id = path.strip_prefix("/issues/")?.strip_suffix("/payment-uri")?;
+    H256::from_str(id.trim_start_matches("0x")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H160;
+
+    #[test]
+    fn test_deposit_uri() {
+        let issue_id = H256::from_low_u64_be(1);
+        assert_eq!(deposit_uri(&issue_id, Network::Regtest), None);
+
+        insert(issue_id, BtcAddress::P2WPKHv0(H160::zero()), 1000);
+        let uri = deposit_uri(&issue_id, Network::Regtest).unwrap();
+        assert!(uri.starts_with("bitcoin:bcrt1q"));
+        assert!(uri.contains("?amount=0.00001&label=issue%200x"));
+
+        remove(&issue_id);
+        assert_eq!(deposit_uri(&issue_id, Network::Regtest), None);
+
+        assert_eq!(
+            parse_path(&format!("/issues/{:?}/payment-uri", issue_id)),
+            Some(issue_id)
+        );
+        assert_eq!(parse_path("/metrics"), None);
+    }
+}
@@ -1,9 +1,11 @@
 use crate::relay::Error as RelayError;
 use bitcoin::Error as BitcoinError;
 use hex::FromHexError;
+use hyper::Error as HyperError;
 use jsonrpc_core_client::RpcError;
 use parity_scale_codec::Error as CodecError;
-use runtime::{substrate_subxt::Error as SubxtError, Error as RuntimeError};
+use prometheus::Error as PrometheusError;
+use runtime::{substrate_subxt::Error as SubxtError, BtcAddress, CollateralAmount, Error as RuntimeError};
 use service::Error as ServiceError;
 use thiserror::Error;
 
@@ -25,6 +27,33 @@ pub enum Error {
     TryIntoIntError(#[from] std::num::TryFromIntError),
     #[error("Deadline has expired")]
     DeadlineExpired,
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedSnapshotVersion(u32),
+    #[error("Snapshot belongs to a different vault")]
+    SnapshotVaultMismatch,
+    #[error("Deposit of {amount} collateral was interrupted, the parachain is unreachable")]
+    DepositInterrupted {
+        amount: CollateralAmount,
+        target: CollateralAmount,
+    },
+    #[error("Block containing the payment is no longer in the main chain")]
+    PaymentReorganized,
+    #[error("Block containing the payment did not reach the required depth in time")]
+    ProofSafetyTimeout,
+    #[error("Payment was rejected by the operator")]
+    PaymentRejected,
+    #[error("Payment was not approved in time")]
+    ApprovalTimeout,
+    #[error("Payment {0} was already made for this request")]
+    PaymentAlreadyRecorded(String),
+    #[error("Payment was refused: {0}")]
+    PaymentRefused(String),
+    #[error("No heartbeat recorded, see --heartbeat-file")]
+    MissingHeartbeat,
+    #[error("Deposit address {found:?} differs from the derived address {expected:?}")]
+    DepositAddressMismatch { expected: BtcAddress, found: BtcAddress },
+    #[error("Deposit key does not belong to the wallet")]
+    ForeignDepositKey,
 
     #[error("ServiceError: {0}")]
     ServiceError(#[from] ServiceError),
@@ -42,4 +71,14 @@ pub enum Error {
     CodecError(#[from] CodecError),
     #[error("RelayError: {0}")]
     RelayError(#[from] RelayError),
+    #[error("HyperError: {0}")]
+    HyperError(#[from] HyperError),
+    #[error("PrometheusError: {0}")]
+    PrometheusError(#[from] PrometheusError),
+    #[error("IoError: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("SerdeJsonError: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error("JoinError: {0}")]
+    JoinError(#[from] tokio::task::JoinError),
 }
@@ -1,6 +1,17 @@
-use crate::error::Error;
+use crate::{
+    analytics::{self, AnalyticsEvent},
+    approval::PaymentApproval,
+    concurrency::TaskLimiter,
+    error::Error,
+    hooks::{self, Payment},
+    latency::{self, Stage},
+    proof_safety::ProofSafety,
+    reconciliation::{OnChainStatus, Reconciliation},
+    request_state::{self, RequestState},
+};
 use bitcoin::{
-    BitcoinCoreApi, Transaction, TransactionExt, TransactionMetadata, BLOCK_INTERVAL as BITCOIN_BLOCK_INTERVAL,
+    BitcoinCoreApi, FeeEstimation, Transaction, TransactionExt, TransactionMetadata, Txid,
+    BLOCK_INTERVAL as BITCOIN_BLOCK_INTERVAL,
 };
 use futures::{stream::StreamExt, try_join};
 use runtime::{
@@ -56,6 +67,16 @@ pub enum RequestType {
     Refund,
 }
 
+impl RequestType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestType::Redeem => "redeem",
+            RequestType::Replace => "replace",
+            RequestType::Refund => "refund",
+        }
+    }
+}
+
 impl Request {
     fn duration_to_parachain_blocks(duration: Duration) -> Result<u32, Error> {
         let num_blocks = duration.as_millis() / (runtime::MILLISECS_PER_BLOCK as u128);
@@ -94,18 +115,34 @@ impl Request {
         hash: H256,
         request: InterBtcRedeemRequest,
         payment_margin: Duration,
+    ) -> Result<Request, Error> {
+        Self::redeem(
+            hash,
+            request.amount_btc,
+            request.btc_address,
+            request.opentime,
+            request.btc_height,
+            request.period,
+            payment_margin,
+        )
+    }
+
+    /// Constructs a Request for a redeem opened at the given heights, e.g. one replayed from a recording
+    pub(crate) fn redeem(
+        hash: H256,
+        amount: u128,
+        btc_address: BtcAddress,
+        opentime: u32,
+        btc_height: u32,
+        period: u32,
+        payment_margin: Duration,
     ) -> Result<Request, Error> {
         Ok(Request {
             hash,
-            deadline: Some(Self::calculate_deadline(
-                request.opentime,
-                request.btc_height,
-                request.period,
-                payment_margin,
-            )?),
-            btc_height: Some(request.btc_height),
-            amount: request.amount_btc,
-            btc_address: request.btc_address,
+            deadline: Some(Self::calculate_deadline(opentime, btc_height, period, payment_margin)?),
+            btc_height: Some(btc_height),
+            amount,
+            btc_address,
             request_type: RequestType::Redeem,
         })
     }
@@ -173,8 +210,104 @@ impl Request {
         parachain_rpc: P,
         btc_rpc: B,
         num_confirmations: u32,
+        proof_safety: ProofSafety,
+        approval: PaymentApproval,
+    ) -> Result<(), Error> {
+        // no-op if the request was already observed when its event was received
+        latency::observe(self.hash, self.request_type.as_str());
+        request_state::seen(self.hash, (&self.request_type).into());
+        analytics::record(|| AnalyticsEvent::Request {
+            request_id: self.hash,
+            kind: (&self.request_type).into(),
+            amount: self.amount,
+            fee: None,
+            btc_address: self.btc_address,
+        });
+
+        let result = request_state::correlate(self.hash, async {
+            // never pay twice for a request, e.g. when its payment was not found after a restart,
+            // but prove the recorded payment unless it was dropped from the mempool or conflicted
+            let recorded_payment = match request_state::recorded_payment(self.hash) {
+                Some(txid) => match txid.parse() {
+                    Ok(parsed) if btc_rpc.is_transaction_live(parsed).await? => Some(parsed),
+                    Ok(_) => {
+                        tracing::warn!(
+                            "Payment {} of request #{} is neither in the mempool nor confirmed, paying again",
+                            txid,
+                            self.hash
+                        );
+                        None
+                    }
+                    Err(_) => return Err(Error::PaymentAlreadyRecorded(txid)),
+                },
+                None => None,
+            };
+
+            let tx_metadata = match recorded_payment {
+                Some(txid) => {
+                    // the startup scan missed the payment, prove it before the request expires
+                    tracing::info!("Resuming payment {} of request #{}", txid, self.hash);
+                    self.await_payment(&parachain_rpc, &btc_rpc, txid, num_confirmations, &proof_safety)
+                        .await?
+                }
+                None => {
+                    // ensure the deadline has not expired yet
+                    self.check_deadline(&parachain_rpc, &btc_rpc).await?;
+
+                    hooks::before_payment(&Payment {
+                        request_id: self.hash,
+                        kind: (&self.request_type).into(),
+                        amount: self.amount,
+                        btc_address: self.btc_address,
+                    })
+                    .await?;
+
+                    if approval.requires_approval(self.amount) {
+                        approval
+                            .await_approval(self.hash, self.amount, &self.btc_address)
+                            .await?;
+                        // the operator may have taken until after the deadline
+                        self.check_deadline(&parachain_rpc, &btc_rpc).await?;
+                    }
+
+                    self.transfer_btc(&parachain_rpc, btc_rpc, num_confirmations, &proof_safety)
+                        .await?
+                }
+            };
+
+            // past the deadline we are within the payment margin of the expiry, so tip
+            // the execution to avoid it being delayed past the expiry during congestion
+            let urgent = match self.deadline {
+                Some(ref deadline) => parachain_rpc.get_current_active_block_number().await? >= deadline.parachain,
+                None => false,
+            };
+            if urgent {
+                runtime::urgent(self.execute(parachain_rpc, tx_metadata)).await
+            } else {
+                self.execute(parachain_rpc, tx_metadata).await
+            }
+        })
+        .await;
+
+        match &result {
+            Ok(()) | Err(Error::PaymentAlreadyRecorded(_)) => {}
+            Err(Error::DeadlineExpired) => request_state::transition(self.hash, RequestState::Expired),
+            Err(err) => request_state::transition(
+                self.hash,
+                RequestState::Failed {
+                    reason: err.to_string(),
+                },
+            ),
+        }
+        result
+    }
+
+    /// Fails with `DeadlineExpired` once both the parachain and the bitcoin deadline passed.
+    async fn check_deadline<B: BitcoinCoreApi, P: SecurityPallet>(
+        &self,
+        parachain_rpc: &P,
+        btc_rpc: &B,
     ) -> Result<(), Error> {
-        // ensure the deadline has not expired yet
         if let Some(ref deadline) = self.deadline {
             if parachain_rpc.get_current_active_block_number().await? >= deadline.parachain
                 && btc_rpc.get_block_count().await? >= deadline.bitcoin as u64
@@ -182,9 +315,7 @@ impl Request {
                 return Err(Error::DeadlineExpired);
             }
         }
-
-        let tx_metadata = self.transfer_btc(&parachain_rpc, btc_rpc, num_confirmations).await?;
-        self.execute(parachain_rpc, tx_metadata).await
+        Ok(())
     }
 
     /// Make a bitcoin transfer to fulfil the request
@@ -204,9 +335,19 @@ impl Request {
         parachain_rpc: &P,
         btc_rpc: B,
         num_confirmations: u32,
+        proof_safety: &ProofSafety,
     ) -> Result<TransactionMetadata, Error> {
+        let fee_estimation = match self.deadline {
+            Some(_) => btc_rpc.deadline_fee_estimation(),
+            None => FeeEstimation::default(),
+        };
         let tx = btc_rpc
-            .create_transaction(self.btc_address, self.amount as u64, Some(self.hash))
+            .create_transaction_with_fee_estimation(
+                self.btc_address,
+                self.amount as u64,
+                Some(self.hash),
+                fee_estimation,
+            )
             .await?;
         let recipient = tx.recipient.clone();
         tracing::info!("Sending bitcoin to {}", recipient);
@@ -233,10 +374,34 @@ impl Request {
             _ => return Err(Error::TooManyReturnToSelfAddresses),
         };
 
+        let fee = tx.fee();
         let txid = btc_rpc.send_transaction(tx).await?;
+        latency::mark(self.hash, Stage::PaymentBroadcast);
+        analytics::record(|| AnalyticsEvent::Payment {
+            request_id: self.hash,
+            kind: (&self.request_type).into(),
+            txid: txid.to_string(),
+            amount: self.amount as u64,
+            fee: fee.map(|fee| fee.as_sat()),
+        });
+        request_state::transition(self.hash, RequestState::Paid { txid: txid.to_string() });
+
+        self.await_payment(parachain_rpc, &btc_rpc, txid, num_confirmations, proof_safety)
+            .await
+    }
 
+    /// Wait until the payment is confirmed, relayed and deep enough to be proven.
+    async fn await_payment<B: BitcoinCoreApi, P: BtcRelayPallet>(
+        &self,
+        parachain_rpc: &P,
+        btc_rpc: &B,
+        txid: Txid,
+        num_confirmations: u32,
+        proof_safety: &ProofSafety,
+    ) -> Result<TransactionMetadata, Error> {
         loop {
             let tx_metadata = btc_rpc.wait_for_transaction_metadata(txid, num_confirmations).await?;
+            latency::mark(self.hash, Stage::PaymentConfirmed);
 
             tracing::info!("Awaiting parachain confirmations...");
 
@@ -247,10 +412,7 @@ impl Request {
                 )
                 .await
             {
-                Ok(_) => {
-                    tracing::info!("Bitcoin successfully sent and relayed");
-                    return Ok(tx_metadata);
-                }
+                Ok(_) => {}
                 Err(e) if e.is_invalid_chain_id() => {
                     // small delay to prevent spamming
                     delay_for(ON_FORK_RETRY_DELAY).await;
@@ -259,11 +421,33 @@ impl Request {
                 }
                 Err(e) => return Err(e.into()),
             }
+
+            match proof_safety
+                .wait_until_safe(
+                    parachain_rpc,
+                    btc_rpc,
+                    tx_metadata.block_height,
+                    &tx_metadata.block_hash,
+                    self.amount,
+                )
+                .await
+            {
+                Ok(()) => {
+                    tracing::info!("Bitcoin successfully sent and relayed");
+                    request_state::transition(self.hash, RequestState::confirmed(&tx_metadata));
+                    return Ok(tx_metadata);
+                }
+                Err(Error::PaymentReorganized) => {
+                    delay_for(ON_FORK_RETRY_DELAY).await;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
     /// Executes the request. Upon failure it will retry
-    async fn execute<P: ReplacePallet + RedeemPallet + RefundPallet>(
+    async fn execute<P: ReplacePallet + RedeemPallet + RefundPallet + BtcRelayPallet>(
         &self,
         parachain_rpc: P,
         tx_metadata: TransactionMetadata,
@@ -275,35 +459,57 @@ impl Request {
             RequestType::Refund => RefundPallet::execute_refund,
         };
 
-        // Retry until success or timeout, explicitly handle the cases
-        // where the redeem has expired or the rpc has disconnected
-        runtime::notify_retry(
-            || (execute)(&parachain_rpc, self.hash, &tx_metadata.proof, &tx_metadata.raw_tx),
-            |result| async {
-                match result {
-                    Ok(ok) => Ok(ok),
-                    Err(err) if err.is_commit_period_expired() => Err(runtime::RetryPolicy::Throw(err)),
-                    Err(err) if err.is_rpc_disconnect_error() => Err(runtime::RetryPolicy::Throw(err)),
-                    Err(err) if err.is_invalid_chain_id() => Err(runtime::RetryPolicy::Throw(err)),
-                    Err(err) => Err(runtime::RetryPolicy::Skip(err)),
-                }
-            },
-        )
-        .await?;
+        if tx_metadata
+            .outputs
+            .op_return
+            .map_or(false, |op_return| op_return != self.hash)
+        {
+            tracing::warn!(
+                "Payment {} of request #{} has the OP_RETURN of another request",
+                tx_metadata.txid,
+                self.hash
+            );
+        }
+        // the proof is rejected unless its block is in the main chain of the relay, which may
+        // have reorganized since the payment was confirmed
+        parachain_rpc
+            .verify_block_header_inclusion(H256Le::from_bytes_le(&tx_metadata.block_hash.to_vec()))
+            .await?;
+        latency::mark(self.hash, Stage::ProofSubmitted);
+
+        // the parachain client retries the execution until it succeeds, times out or fails
+        // with a final error, e.g. because the redeem has expired or the rpc has disconnected
+        let receipt = (execute)(&parachain_rpc, self.hash, &tx_metadata.proof, &tx_metadata.raw_tx).await?;
+        latency::mark(self.hash, Stage::Executed);
+        analytics::record(|| AnalyticsEvent::Proof {
+            request_id: self.hash,
+            kind: (&self.request_type).into(),
+            txid: tx_metadata.txid.to_string(),
+            block_hash: tx_metadata.block_hash.to_string(),
+            block_height: Some(tx_metadata.block_height),
+            parachain_fee: receipt.as_ref().and_then(analytics::parachain_fee),
+        });
+        request_state::transition(self.hash, RequestState::Executed);
+        hooks::after_execution(self.hash, (&self.request_type).into()).await;
 
         Ok(())
     }
 }
 
 /// Queries the parachain for open requests and executes them. It checks the
-/// bitcoin blockchain to see if a payment has already been made.
+/// bitcoin blockchain to see if a payment has already been made, and the recorded
+/// request states for executions that were not finalized. Returns what was found.
 pub async fn execute_open_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     parachain_rpc: InterBtcParachain,
     btc_rpc: B,
     num_confirmations: u32,
     payment_margin: Duration,
-) -> Result<(), Error> {
+    proof_safety: ProofSafety,
+    approval: PaymentApproval,
+    task_limiter: TaskLimiter,
+) -> Result<Reconciliation, Error> {
     let vault_id = parachain_rpc.get_account_id().clone();
+    let mut reconciliation = Reconciliation::default();
 
     // get all redeem, replace and refund requests
     let (redeem_requests, replace_requests, refund_requests) = try_join!(
@@ -312,6 +518,36 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'st
         parachain_rpc.get_vault_refund_requests(vault_id),
     )?;
 
+    // the on-chain status of all requests, to reconcile the ones recorded as executed
+    let statuses = redeem_requests
+        .iter()
+        .map(|(hash, request)| {
+            let status = match request.status {
+                RedeemRequestStatus::Pending => OnChainStatus::Open,
+                RedeemRequestStatus::Completed => OnChainStatus::Completed,
+                RedeemRequestStatus::Reimbursed(_) | RedeemRequestStatus::Retried => OnChainStatus::Cancelled,
+            };
+            (*hash, status)
+        })
+        .chain(replace_requests.iter().map(|(hash, request)| {
+            let status = match request.status {
+                ReplaceRequestStatus::Pending => OnChainStatus::Open,
+                ReplaceRequestStatus::Completed => OnChainStatus::Completed,
+                ReplaceRequestStatus::Cancelled => OnChainStatus::Cancelled,
+            };
+            (*hash, status)
+        }))
+        .chain(refund_requests.iter().map(|(hash, request)| {
+            let status = if request.completed {
+                OnChainStatus::Completed
+            } else {
+                OnChainStatus::Open
+            };
+            (*hash, status)
+        }))
+        .collect::<HashMap<_, _>>();
+    reconciliation.check_executed(|request_id| statuses.get(request_id).copied());
+
     let open_redeems = redeem_requests
         .into_iter()
         .filter(|(_, request)| request.status == RedeemRequestStatus::Pending)
@@ -341,7 +577,7 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'st
         .min()
     {
         Some(x) => x,
-        None => return Ok(()), // the iterator is empty so we have nothing to do
+        None => return Ok(reconciliation), // the iterator is empty so we have nothing to do
     };
 
     // iterate through transactions in reverse order, starting from those in the mempool
@@ -352,6 +588,7 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'st
         // get the request this transaction corresponds to, if any
         if let Some(request) = get_request_for_btc_tx(&tx, &open_requests) {
             // remove request from the hashmap
+            reconciliation.paid_unproven.push(request.hash);
             open_requests.retain(|&key, _| key != request.hash);
 
             tracing::info!(
@@ -364,7 +601,16 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'st
             // make copies of the variables we move into the task
             let parachain_rpc = parachain_rpc.clone();
             let btc_rpc = btc_rpc.clone();
-            tokio::spawn(async move {
+            let proof_safety = proof_safety.clone();
+            request_state::seen(request.hash, (&request.request_type).into());
+            request_state::transition(
+                request.hash,
+                RequestState::Paid {
+                    txid: tx.txid().to_string(),
+                },
+            );
+            let request_id = request.hash;
+            let execution = async move {
                 // Payment has been made, but it might not have been confirmed enough times yet
                 let tx_metadata = btc_rpc
                     .clone()
@@ -389,40 +635,69 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'st
                             // continue; try to execute anyway
                         }
 
+                        if let Err(e) = proof_safety
+                            .wait_until_safe(
+                                &parachain_rpc,
+                                &btc_rpc,
+                                tx_metadata.block_height,
+                                &tx_metadata.block_hash,
+                                request.amount,
+                            )
+                            .await
+                        {
+                            tracing::error!("Payment for request #{} is not safe to prove: {}", request.hash, e);
+                            request_state::transition(request.hash, RequestState::Failed { reason: e.to_string() });
+                            return;
+                        }
+                        request_state::transition(request.hash, RequestState::confirmed(&tx_metadata));
+
                         match request.execute(parachain_rpc.clone(), tx_metadata).await {
                             Ok(_) => {
                                 tracing::info!("Executed request #{:?}", request.hash);
                             }
-                            Err(e) => tracing::error!("Failed to execute request #{}: {}", request.hash, e),
+                            Err(e) => {
+                                tracing::error!("Failed to execute request #{}: {}", request.hash, e);
+                                request_state::transition(request.hash, RequestState::Failed { reason: e.to_string() });
+                            }
                         }
                     }
-                    Err(e) => tracing::error!(
-                        "Failed to confirm bitcoin transaction for request {}: {}",
-                        request.hash,
-                        e
-                    ),
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to confirm bitcoin transaction for request {}: {}",
+                            request.hash,
+                            e
+                        );
+                        request_state::transition(request.hash, RequestState::Failed { reason: e.to_string() });
+                    }
                 }
-            });
+            };
+            task_limiter.spawn(request_state::correlate(request_id, execution));
         }
     }
 
     // All requests remaining in the hashmap did not have a bitcoin payment yet, so pay
     // and execute all of these
     for (_, request) in open_requests {
+        reconciliation.unpaid.push(request.hash);
         // there are potentially a large number of open requests - pay and execute each
         // in a separate task to ensure that awaiting confirmations does not significantly
         // delay other requests
         // make copies of the variables we move into the task
         let parachain_rpc = parachain_rpc.clone();
         let btc_rpc = btc_rpc.clone();
-        tokio::spawn(async move {
+        let proof_safety = proof_safety.clone();
+        let approval = approval.clone().releasing_slot_of(task_limiter.clone());
+        task_limiter.spawn(async move {
             tracing::info!(
                 "{:?} request #{:?} found without bitcoin payment - processing...",
                 request.request_type,
                 request.hash
             );
 
-            match request.pay_and_execute(parachain_rpc, btc_rpc, num_confirmations).await {
+            match request
+                .pay_and_execute(parachain_rpc, btc_rpc, num_confirmations, proof_safety, approval)
+                .await
+            {
                 Ok(_) => tracing::info!(
                     "{:?} request #{:?} successfully executed",
                     request.request_type,
@@ -438,7 +713,7 @@ pub async fn execute_open_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'st
         });
     }
 
-    Ok(())
+    Ok(reconciliation)
 }
 
 /// Get the Request from the hashmap that the given Transaction satisfies, based
@@ -463,8 +738,8 @@ mod tests {
         PrivateKey, Transaction, TransactionMetadata, Txid, PUBLIC_KEY_SIZE,
     };
     use runtime::{
-        AccountId, BlockNumber, BtcPublicKey, Error as RuntimeError, ErrorCode, InterBtcRichBlockHeader, InterBtcVault,
-        StatusCode,
+        AccountId, BlockNumber, BtcPublicKey, CollateralAmount, Error as RuntimeError, ErrorCode,
+        InterBtcRichBlockHeader, InterBtcVault, StatusCode, SubmissionReceipt, WrappedAmount,
     };
     use sp_core::H160;
     use std::collections::BTreeSet;
@@ -495,13 +770,13 @@ mod tests {
         pub trait VaultRegistryPallet {
             async fn get_vault(&self, vault_id: AccountId) -> Result<InterBtcVault, RuntimeError>;
             async fn get_all_vaults(&self) -> Result<Vec<InterBtcVault>, RuntimeError>;
-            async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
-            async fn deposit_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-            async fn withdraw_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
+            async fn register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
+            async fn deposit_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
+            async fn withdraw_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
             async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
             async fn register_address(&self, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-            async fn get_required_collateral_for_wrapped(&self, amount_btc: u128) -> Result<u128, RuntimeError>;
-            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, RuntimeError>;
+            async fn get_required_collateral_for_wrapped(&self, amount_btc: WrappedAmount) -> Result<CollateralAmount, RuntimeError>;
+            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<CollateralAmount, RuntimeError>;
         }
 
         #[async_trait]
@@ -517,13 +792,14 @@ mod tests {
                 redeem_id: H256,
                 merkle_proof: &[u8],
                 raw_tx: &[u8],
-            ) -> Result<(), RuntimeError>;
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
             async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), RuntimeError>;
             async fn get_redeem_request(&self, redeem_id: H256) -> Result<InterBtcRedeemRequest, RuntimeError>;
             async fn get_vault_redeem_requests(
                 &self,
                 account_id: AccountId,
             ) -> Result<Vec<(H256, InterBtcRedeemRequest)>, RuntimeError>;
+            async fn get_all_open_redeem_requests(&self) -> Result<Vec<(H256, InterBtcRedeemRequest)>, RuntimeError>;
             async fn get_redeem_period(&self) -> Result<BlockNumber, RuntimeError>;
             async fn set_redeem_period(&self, period: u32) -> Result<(), RuntimeError>;
         }
@@ -544,7 +820,7 @@ mod tests {
                 replace_id: H256,
                 merkle_proof: &[u8],
                 raw_tx: &[u8],
-            ) -> Result<(), RuntimeError>;
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
             async fn cancel_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
             async fn get_replace_request(&self, replace_id: H256) -> Result<InterBtcReplaceRequest, RuntimeError>;
             async fn get_new_vault_replace_requests(
@@ -567,7 +843,7 @@ mod tests {
                 refund_id: H256,
                 merkle_proof: &[u8],
                 raw_tx: &[u8],
-            ) -> Result<(), RuntimeError>;
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
             async fn get_vault_refund_requests(
                 &self,
                 account_id: AccountId,
@@ -640,11 +916,12 @@ mod tests {
                 txid: Txid,
                 num_confirmations: u32,
             ) -> Result<TransactionMetadata, BitcoinError>;
-            async fn create_transaction<A: PartialAddress + Send + Sync + 'static>(
+            async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
                 &self,
                 address: A,
                 sat: u64,
                 request_id: Option<H256>,
+                fee_estimation: FeeEstimation,
             ) -> Result<LockedTransaction, BitcoinError>;
             async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError>;
             async fn create_and_send_transaction<A: PartialAddress + Send + Sync + 'static>(
@@ -735,8 +1012,11 @@ mod tests {
             parachain_rpc
                 .expect_get_current_active_block_number()
                 .returning(move || Ok(current_parachain_height));
-            parachain_rpc.expect_execute_redeem().returning(|_, _, _| Ok(()));
+            parachain_rpc.expect_execute_redeem().returning(|_, _, _| Ok(None));
             parachain_rpc.expect_wait_for_block_in_relay().returning(|_, _| Ok(()));
+            parachain_rpc
+                .expect_verify_block_header_inclusion()
+                .returning(|_| Ok(()));
 
             let mut btc_rpc = MockBitcoin::default();
 
@@ -744,18 +1024,20 @@ mod tests {
                 .expect_get_block_count()
                 .returning(move || Ok(current_bitcoin_height as u64));
 
-            btc_rpc.expect_create_transaction::<BtcAddress>().returning(|_, _, _| {
-                Ok(LockedTransaction::new(
-                    Transaction {
-                        version: 0,
-                        lock_time: 0,
-                        input: vec![],
-                        output: vec![],
-                    },
-                    Default::default(),
-                    None,
-                ))
-            });
+            btc_rpc
+                .expect_create_transaction_with_fee_estimation::<BtcAddress>()
+                .returning(|_, _, _, _| {
+                    Ok(LockedTransaction::new(
+                        Transaction {
+                            version: 0,
+                            lock_time: 0,
+                            input: vec![],
+                            output: vec![],
+                        },
+                        Default::default(),
+                        None,
+                    ))
+                });
 
             btc_rpc.expect_send_transaction().returning(|_| Ok(Txid::default()));
 
@@ -766,6 +1048,7 @@ mod tests {
                     raw_tx: vec![],
                     block_height: 0,
                     block_hash: BlockHash::default(),
+                    outputs: Default::default(),
                 })
             });
 
@@ -788,21 +1071,51 @@ mod tests {
         async fn should_pay_and_execute_redeem_if_neither_parachain_nor_bitcoin_deadlines_expired() {
             let (request, parachain_rpc, btc_rpc) = should_pay_and_execute_with_deadlines(100, 50, 100, 50);
 
-            assert_ok!(request.pay_and_execute(parachain_rpc, btc_rpc, 6).await);
+            assert_ok!(
+                request
+                    .pay_and_execute(
+                        parachain_rpc,
+                        btc_rpc,
+                        6,
+                        ProofSafety::default(),
+                        PaymentApproval::default()
+                    )
+                    .await
+            );
         }
 
         #[tokio::test]
         async fn should_pay_and_execute_redeem_if_only_parachain_deadline_expired() {
             let (request, parachain_rpc, btc_rpc) = should_pay_and_execute_with_deadlines(100, 101, 100, 50);
 
-            assert_ok!(request.pay_and_execute(parachain_rpc, btc_rpc, 6).await);
+            assert_ok!(
+                request
+                    .pay_and_execute(
+                        parachain_rpc,
+                        btc_rpc,
+                        6,
+                        ProofSafety::default(),
+                        PaymentApproval::default()
+                    )
+                    .await
+            );
         }
 
         #[tokio::test]
         async fn should_pay_and_execute_redeem_if_only_bitcoin_deadline_expired() {
             let (request, parachain_rpc, btc_rpc) = should_pay_and_execute_with_deadlines(100, 50, 100, 101);
 
-            assert_ok!(request.pay_and_execute(parachain_rpc, btc_rpc, 6).await);
+            assert_ok!(
+                request
+                    .pay_and_execute(
+                        parachain_rpc,
+                        btc_rpc,
+                        6,
+                        ProofSafety::default(),
+                        PaymentApproval::default()
+                    )
+                    .await
+            );
         }
 
         #[tokio::test]
@@ -810,7 +1123,15 @@ mod tests {
             let (request, parachain_rpc, btc_rpc) = should_pay_and_execute_with_deadlines(100, 101, 100, 101);
 
             assert_err!(
-                request.pay_and_execute(parachain_rpc, btc_rpc, 6).await,
+                request
+                    .pay_and_execute(
+                        parachain_rpc,
+                        btc_rpc,
+                        6,
+                        ProofSafety::default(),
+                        PaymentApproval::default()
+                    )
+                    .await,
                 Error::DeadlineExpired
             );
         }
@@ -840,40 +1161,97 @@ mod tests {
         };
 
         assert_err!(
-            request.pay_and_execute(parachain_rpc, btc_rpc, 6).await,
+            request
+                .pay_and_execute(
+                    parachain_rpc,
+                    btc_rpc,
+                    6,
+                    ProofSafety::default(),
+                    PaymentApproval::default()
+                )
+                .await,
+            Error::DeadlineExpired
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_pay_if_expired_while_awaiting_approval() {
+        let mut parachain_rpc = MockProvider::default();
+        let mut heights = vec![90, 110].into_iter();
+        parachain_rpc
+            .expect_get_current_active_block_number()
+            .times(2)
+            .returning(move || Ok(heights.next().unwrap()));
+        let mut btc_rpc = MockBitcoin::default();
+        btc_rpc.expect_get_block_count().times(1).returning(|| Ok(110));
+        // omitting the payment mocks to test that they do not get called
+
+        let request = Request {
+            amount: 100,
+            deadline: Some(Deadline {
+                parachain: 100,
+                bitcoin: 100,
+            }),
+            btc_address: BtcAddress::P2SH(H160::from_slice(&[1; 20])),
+            hash: H256::from_slice(&[7; 32]),
+            btc_height: None,
+            request_type: RequestType::Redeem,
+        };
+
+        let dir = std::env::temp_dir().join(format!("expired-approvals-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(format!("{}.approved", hex::encode(request.hash.as_bytes()))),
+            "",
+        )
+        .unwrap();
+        let approval = PaymentApproval::new(Some(0), dir.clone(), Duration::from_secs(0));
+
+        assert_err!(
+            request
+                .pay_and_execute(parachain_rpc, btc_rpc, 6, ProofSafety::default(), approval)
+                .await,
             Error::DeadlineExpired
         );
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[tokio::test]
     async fn should_pay_and_execute_replace() {
         let mut parachain_rpc = MockProvider::default();
+        // checked against the deadline both before paying and before executing
         parachain_rpc
             .expect_get_current_active_block_number()
-            .times(1)
+            .times(2)
             .returning(|| Ok(50));
         parachain_rpc
             .expect_execute_replace()
             .times(1)
-            .returning(|_, _, _| Ok(()));
+            .returning(|_, _, _| Ok(None));
         parachain_rpc
             .expect_wait_for_block_in_relay()
             .times(1)
             .returning(|_, _| Ok(()));
+        parachain_rpc
+            .expect_verify_block_header_inclusion()
+            .times(1)
+            .returning(|_| Ok(()));
 
         let mut btc_rpc = MockBitcoin::default();
-        btc_rpc.expect_create_transaction::<BtcAddress>().returning(|_, _, _| {
-            Ok(LockedTransaction::new(
-                Transaction {
-                    version: 0,
-                    lock_time: 0,
-                    input: vec![],
-                    output: vec![],
-                },
-                Default::default(),
-                None,
-            ))
-        });
+        btc_rpc
+            .expect_create_transaction_with_fee_estimation::<BtcAddress>()
+            .returning(|_, _, _, _| {
+                Ok(LockedTransaction::new(
+                    Transaction {
+                        version: 0,
+                        lock_time: 0,
+                        input: vec![],
+                        output: vec![],
+                    },
+                    Default::default(),
+                    None,
+                ))
+            });
 
         btc_rpc.expect_send_transaction().returning(|_| Ok(Txid::default()));
 
@@ -884,6 +1262,7 @@ mod tests {
                 raw_tx: vec![],
                 block_height: 0,
                 block_hash: BlockHash::default(),
+                outputs: Default::default(),
             })
         });
 
@@ -899,6 +1278,16 @@ mod tests {
             request_type: RequestType::Replace,
         };
 
-        assert_ok!(request.pay_and_execute(parachain_rpc, btc_rpc, 6).await);
+        assert_ok!(
+            request
+                .pay_and_execute(
+                    parachain_rpc,
+                    btc_rpc,
+                    6,
+                    ProofSafety::default(),
+                    PaymentApproval::default()
+                )
+                .await
+        );
     }
 }
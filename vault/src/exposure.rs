@@ -0,0 +1,318 @@
+use futures::try_join;
+use lazy_static::lazy_static;
+use prometheus::IntGauge;
+use runtime::{
+    pallets::{
+        issue::{CancelIssueEvent, ExecuteIssueEvent, RequestIssueEvent},
+        redeem::{CancelRedeemEvent, ExecuteRedeemEvent, RequestRedeemEvent},
+    },
+    substrate_subxt::Error as SubxtError,
+    AccountId, InterBtcParachain, InterBtcRuntime, IssuePallet, IssueRequestStatus, RedeemPallet, RedeemRequestStatus,
+    UtilFuncs,
+};
+use serde::Serialize;
+use service::Error as ServiceError;
+use sp_core::{crypto::Ss58Codec, H256};
+use std::{collections::HashMap, sync::RwLock};
+
+lazy_static! {
+    static ref EXPOSURES: RwLock<Exposures> = RwLock::new(Exposures::default());
+    pub static ref OVER_LIMIT_ACCOUNTS: IntGauge = IntGauge::new(
+        "exposure_over_limit_accounts",
+        "Number of accounts whose open requests exceed the exposure limit"
+    )
+    .expect("Failed to create prometheus metric");
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExposureKind {
+    Issue,
+    Redeem,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OpenRequest {
+    account: AccountId,
+    kind: ExposureKind,
+    /// Amount including the fee, in satoshis.
+    amount: u128,
+}
+
+/// Open issue and redeem requests of this vault by the requesting account.
+#[derive(Default)]
+struct Exposures {
+    /// Maximum outstanding amount of any single account, not limited if not set.
+    limit: Option<u128>,
+    requests: HashMap<H256, OpenRequest>,
+}
+
+/// Outstanding issue and redeem amounts of one account, as served by the operator API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountExposure {
+    pub account: String,
+    pub issue: u128,
+    pub redeem: u128,
+    /// Share of the outstanding amount of all accounts.
+    pub share: f64,
+    /// Whether the open requests of the account exceed the limit.
+    pub over_limit: bool,
+}
+
+impl Exposures {
+    fn exposure(&self, account: &AccountId) -> u128 {
+        self.requests
+            .values()
+            .filter(|request| &request.account == account)
+            .fold(0u128, |total, request| total.saturating_add(request.amount))
+    }
+
+    fn is_over_limit(&self, account: &AccountId) -> bool {
+        matches!(self.limit, Some(limit) if self.exposure(account) > limit)
+    }
+
+    /// Exposure by account, the largest first.
+    fn concentration(&self) -> Vec<AccountExposure> {
+        let mut by_account: HashMap<&AccountId, (u128, u128)> = HashMap::new();
+        for request in self.requests.values() {
+            let (issue, redeem) = by_account.entry(&request.account).or_default();
+            match request.kind {
+                ExposureKind::Issue => *issue = issue.saturating_add(request.amount),
+                ExposureKind::Redeem => *redeem = redeem.saturating_add(request.amount),
+            }
+        }
+        let total = by_account.values().fold(0u128, |total, (issue, redeem)| {
+            total.saturating_add(issue.saturating_add(*redeem))
+        });
+        let mut concentration: Vec<_> = by_account
+            .into_iter()
+            .map(|(account, (issue, redeem))| AccountExposure {
+                account: account.to_ss58check(),
+                issue,
+                redeem,
+                share: issue.saturating_add(redeem) as f64 / total as f64,
+                over_limit: self.is_over_limit(account),
+            })
+            .collect();
+        concentration.sort_by_key(|exposure| std::cmp::Reverse(exposure.issue.saturating_add(exposure.redeem)));
+        concentration
+    }
+
+    fn over_limit_accounts(&self) -> usize {
+        self.concentration()
+            .iter()
+            .filter(|exposure| exposure.over_limit)
+            .count()
+    }
+}
+
+/// Limit the outstanding amount (in satoshis) of any single account, `None` lifts the limit.
+pub(crate) fn set_limit(limit: Option<u128>) {
+    if let Ok(mut exposures) = EXPOSURES.write() {
+        exposures.limit = limit;
+    }
+}
+
+/// Count the request towards the exposure to the account, until it is closed.
+pub(crate) fn open(request_id: H256, account: AccountId, kind: ExposureKind, amount: u128) {
+    if let Ok(mut exposures) = EXPOSURES.write() {
+        let was_over_limit = exposures.is_over_limit(&account);
+        exposures.requests.insert(
+            request_id,
+            OpenRequest {
+                account: account.clone(),
+                kind,
+                amount,
+            },
+        );
+        if !was_over_limit && exposures.is_over_limit(&account) {
+            tracing::warn!(
+                "Exposure to {} is {} sat after request #{}, over the limit of {} sat",
+                account,
+                exposures.exposure(&account),
+                request_id,
+                exposures.limit.unwrap_or_default()
+            );
+        }
+        OVER_LIMIT_ACCOUNTS.set(exposures.over_limit_accounts() as i64);
+    }
+}
+
+pub(crate) fn close(request_id: &H256) {
+    if let Ok(mut exposures) = EXPOSURES.write() {
+        if let Some(request) = exposures.requests.remove(request_id) {
+            if exposures.limit.map_or(false, |limit| {
+                let exposure = exposures.exposure(&request.account);
+                exposure <= limit && exposure.saturating_add(request.amount) > limit
+            }) {
+                tracing::info!("Exposure to {} is back within the limit", request.account);
+            }
+            OVER_LIMIT_ACCOUNTS.set(exposures.over_limit_accounts() as i64);
+        }
+    }
+}
+
+/// Outstanding issue and redeem amounts by account, the largest first.
+pub fn concentration() -> Vec<AccountExposure> {
+    EXPOSURES
+        .read()
+        .map(|exposures| exposures.concentration())
+        .unwrap_or_default()
+}
+
+/// Replace the tracked requests by the open requests on chain, dropping those closed while
+/// the service was restarting.
+async fn initialize_exposures(parachain_rpc: &InterBtcParachain) -> Result<(), runtime::Error> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+    let (issues, redeems) = futures::future::try_join(
+        parachain_rpc.get_vault_issue_requests(vault_id.clone()),
+        parachain_rpc.get_vault_redeem_requests(vault_id),
+    )
+    .await?;
+    let issues = issues
+        .into_iter()
+        .filter(|(_, issue)| issue.status == IssueRequestStatus::Pending)
+        .map(|(issue_id, issue)| {
+            let request = OpenRequest {
+                account: issue.requester,
+                kind: ExposureKind::Issue,
+                amount: issue.amount.saturating_add(issue.fee),
+            };
+            (issue_id, request)
+        });
+    let redeems = redeems
+        .into_iter()
+        .filter(|(_, redeem)| redeem.status == RedeemRequestStatus::Pending)
+        .map(|(redeem_id, redeem)| {
+            let request = OpenRequest {
+                account: redeem.redeemer,
+                kind: ExposureKind::Redeem,
+                amount: redeem.amount_btc,
+            };
+            (redeem_id, request)
+        });
+    if let Ok(mut exposures) = EXPOSURES.write() {
+        exposures.requests = issues.chain(redeems).collect();
+        for exposure in exposures.concentration().iter().filter(|exposure| exposure.over_limit) {
+            tracing::warn!(
+                "Exposure to {} is {} sat, over the limit",
+                exposure.account,
+                exposure.issue.saturating_add(exposure.redeem)
+            );
+        }
+        OVER_LIMIT_ACCOUNTS.set(exposures.over_limit_accounts() as i64);
+    }
+    Ok(())
+}
+
+/// Track the outstanding issue and redeem amounts of each account with this vault, and
+/// alert when a new request takes an account over the limit. The parachain does not let
+/// a vault refuse requests, and requests that were made are still executed and paid, since
+/// the requester has paid or locked funds for them.
+///
+/// # Arguments
+///
+/// * `parachain_rpc` - the parachain RPC handle
+/// * `limit` - maximum outstanding amount (in satoshis) of any single account
+pub async fn track_exposure(parachain_rpc: InterBtcParachain, limit: Option<u128>) -> Result<(), ServiceError> {
+    set_limit(limit);
+    initialize_exposures(&parachain_rpc).await?;
+
+    let parachain_rpc = &parachain_rpc;
+    let vault_id = parachain_rpc.get_account_id();
+    let on_error = |error: SubxtError| tracing::error!("Error reading request event: {}", error.to_string());
+    try_join!(
+        parachain_rpc.on_event::<RequestIssueEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if &event.vault_id == vault_id {
+                    let amount = event.amount_btc.saturating_add(event.fee);
+                    open(event.issue_id, event.requester, ExposureKind::Issue, amount);
+                }
+            },
+            on_error
+        ),
+        parachain_rpc.on_event::<RequestRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if &event.vault_id == vault_id {
+                    open(event.redeem_id, event.redeemer, ExposureKind::Redeem, event.amount);
+                }
+            },
+            on_error
+        ),
+        // requests of other vaults are not tracked, closing them does nothing
+        parachain_rpc.on_event::<ExecuteIssueEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { close(&event.issue_id) },
+            on_error
+        ),
+        parachain_rpc.on_event::<CancelIssueEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { close(&event.issue_id) },
+            on_error
+        ),
+        parachain_rpc.on_event::<ExecuteRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { close(&event.redeem_id) },
+            on_error
+        ),
+        parachain_rpc.on_event::<CancelRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { close(&event.redeem_id) },
+            on_error
+        ),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_keyring::AccountKeyring;
+
+    #[test]
+    fn test_concentration() {
+        let (alice, bob) = (
+            AccountKeyring::Alice.to_account_id(),
+            AccountKeyring::Bob.to_account_id(),
+        );
+        let mut exposures = Exposures {
+            limit: Some(1000),
+            ..Default::default()
+        };
+        let mut open = |id: u64, account: &AccountId, kind, amount| {
+            exposures.requests.insert(
+                H256::from_low_u64_be(id),
+                OpenRequest {
+                    account: account.clone(),
+                    kind,
+                    amount,
+                },
+            );
+        };
+        open(1, &alice, ExposureKind::Issue, 600);
+        open(2, &alice, ExposureKind::Redeem, 600);
+        open(3, &bob, ExposureKind::Issue, 300);
+        open(4, &bob, ExposureKind::Issue, 100);
+
+        assert!(exposures.is_over_limit(&alice));
+        assert!(!exposures.is_over_limit(&bob));
+        assert_eq!(
+            exposures.concentration(),
+            vec![
+                AccountExposure {
+                    account: alice.to_ss58check(),
+                    issue: 600,
+                    redeem: 600,
+                    share: 0.75,
+                    over_limit: true,
+                },
+                AccountExposure {
+                    account: bob.to_ss58check(),
+                    issue: 400,
+                    redeem: 0,
+                    share: 0.25,
+                    over_limit: false,
+                },
+            ]
+        );
+
+        exposures.requests.remove(&H256::from_low_u64_be(2));
+        assert!(!exposures.is_over_limit(&alice));
+        assert_eq!(exposures.over_limit_accounts(), 0);
+    }
+}
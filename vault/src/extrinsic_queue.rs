@@ -0,0 +1,150 @@
+use crate::Error;
+use runtime::{CollateralAmount, InterBtcParachain, UtilFuncs, VaultRegistryPallet};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Extrinsic that is not time-sensitive, so it can still be submitted after an outage of
+/// the parachain. Relay headers are not queued, the relayer resumes from the best block
+/// of the relay after reconnecting.
+///
+/// Entries record the intended end state rather than the call, since the interrupted
+/// submission may have been included after all: on replay, only what is still missing
+/// on chain is submitted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub enum QueuedExtrinsic {
+    /// Deposit collateral until the backing collateral of the vault reaches `target` (in planck).
+    DepositCollateral { target: u128 },
+}
+
+impl QueuedExtrinsic {
+    async fn submit(&self, parachain_rpc: &InterBtcParachain) -> Result<(), runtime::Error> {
+        match self {
+            QueuedExtrinsic::DepositCollateral { target } => {
+                let vault = parachain_rpc.get_vault(parachain_rpc.get_account_id().clone()).await?;
+                match target.checked_sub(vault.backing_collateral) {
+                    Some(amount) if amount > 0 => {
+                        parachain_rpc
+                            .deposit_collateral(CollateralAmount::from_planck(amount))
+                            .await
+                    }
+                    _ => {
+                        tracing::info!(
+                            "Backing collateral {} already reaches the target of the queued deposit",
+                            vault.backing_collateral
+                        );
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// True if the error indicates that the parachain could not be reached, as opposed to the
+/// extrinsic being rejected.
+pub fn is_unreachable(err: &runtime::Error) -> bool {
+    err.is_rpc_error() || matches!(err, runtime::Error::ChannelClosed)
+}
+
+/// Queue of extrinsics that could not be submitted because the parachain was unreachable,
+/// persisted to a file so that it survives the restart of the service on reconnection.
+#[derive(Clone)]
+pub struct ExtrinsicQueue {
+    path: PathBuf,
+    entries: Arc<Mutex<VecDeque<QueuedExtrinsic>>>,
+}
+
+impl ExtrinsicQueue {
+    /// Load the queue from the given file, it is created on the first push.
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// Write the entries to a temporary file that replaces the queue file, so that a crash
+    /// does not leave a partially written queue behind.
+    fn persist(&self, entries: &VecDeque<QueuedExtrinsic>) -> Result<(), Error> {
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string(entries)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    pub fn push(&self, extrinsic: QueuedExtrinsic) -> Result<(), Error> {
+        let mut entries = self.entries.lock().expect("poisoned");
+        entries.push_back(extrinsic);
+        self.persist(&entries)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().expect("poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn front(&self) -> Option<QueuedExtrinsic> {
+        self.entries.lock().expect("poisoned").front().cloned()
+    }
+
+    fn pop_front(&self) -> Result<(), Error> {
+        let mut entries = self.entries.lock().expect("poisoned");
+        entries.pop_front();
+        self.persist(&entries)
+    }
+
+    /// Submit the queued extrinsics in order. Stops if the parachain is unreachable again,
+    /// extrinsics that are rejected are dropped.
+    pub async fn drain(&self, parachain_rpc: &InterBtcParachain) -> Result<(), Error> {
+        if !self.is_empty() {
+            tracing::info!("Submitting {} queued extrinsics", self.len());
+        }
+        while let Some(extrinsic) = self.front() {
+            match extrinsic.submit(parachain_rpc).await {
+                Ok(()) => tracing::info!("Submitted queued extrinsic {:?}", extrinsic),
+                Err(err) if is_unreachable(&err) => return Err(err.into()),
+                Err(err) => tracing::error!("Dropping queued extrinsic {:?}: {}", extrinsic, err),
+            }
+            self.pop_front()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_is_persisted() {
+        let path = std::env::temp_dir().join(format!("extrinsic-queue-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let queue = ExtrinsicQueue::open(path.clone()).unwrap();
+        queue.push(QueuedExtrinsic::DepositCollateral { target: 100 }).unwrap();
+        queue.push(QueuedExtrinsic::DepositCollateral { target: 200 }).unwrap();
+        queue.pop_front().unwrap();
+
+        let reopened = ExtrinsicQueue::open(path.clone()).unwrap();
+        assert_eq!(reopened.len(), 1);
+        assert_eq!(
+            reopened.front(),
+            Some(QueuedExtrinsic::DepositCollateral { target: 200 })
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -4,8 +4,25 @@ use hex::FromHex;
 use jsonrpc_core::Value;
 use jsonrpc_core_client::{transports::http as jsonrpc_http, TypedClient};
 use parity_scale_codec::{Decode, Encode};
-use runtime::{AccountId, InterBtcParachain, VaultRegistryPallet, PLANCK_PER_DOT, TX_FEES};
+use runtime::{
+    AccountId, BalanceSubscription, CollateralAmount, Dot, InterBtcParachain, UtilFuncs, VaultRegistryPallet,
+    FEE_CURRENCY, TX_FEES,
+};
 use serde::{Deserialize, Deserializer};
+use service::Error as ServiceError;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::time::delay_for;
+
+/// Number of attempts to request funds from the faucet before giving up until the cooldown passed.
+const TOP_UP_MAX_ATTEMPTS: u32 = 5;
+/// Delay before the second attempt, doubled after each failed attempt.
+const TOP_UP_INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+/// Minimum time between top-ups: the funds only show up once finalized, and the faucet limits
+/// how often an account is funded.
+const TOP_UP_COOLDOWN: Duration = Duration::from_secs(60 * 60);
 
 #[derive(Debug, Clone, Deserialize)]
 struct RawBytes(#[serde(deserialize_with = "hex_to_buffer")] Vec<u8>);
@@ -55,13 +72,10 @@ pub async fn fund_and_register<B: BitcoinCoreApi + Clone>(
     }
 
     let user_allowance_in_dot: u128 = get_faucet_allowance(connection.clone(), "user_allowance").await?;
-    let registration_collateral = user_allowance_in_dot
-        .checked_mul(PLANCK_PER_DOT)
-        .ok_or(Error::ArithmeticOverflow)?
-        .checked_sub(TX_FEES)
-        .ok_or(Error::ArithmeticUnderflow)?;
+    let registration_collateral = CollateralAmount::<Dot>::from_units(user_allowance_in_dot)?
+        .checked_sub(CollateralAmount::from_planck(TX_FEES))?;
 
-    tracing::info!("Registering the vault");
+    tracing::info!("Registering the vault with {}", registration_collateral);
     let public_key = bitcoin_core.get_new_public_key().await?;
     parachain_rpc
         .register_vault(registration_collateral, public_key)
@@ -72,16 +86,75 @@ pub async fn fund_and_register<B: BitcoinCoreApi + Clone>(
 
     // TODO: faucet allowance should return planck
     let vault_allowance_in_dot: u128 = get_faucet_allowance(connection.clone(), "vault_allowance").await?;
-    let vault_allowance_in_planck = vault_allowance_in_dot
-        .checked_mul(PLANCK_PER_DOT)
-        .ok_or(Error::ArithmeticOverflow)?;
-    let operational_collateral = vault_allowance_in_planck
-        .checked_div(3)
-        .unwrap_or_default()
-        .checked_mul(2)
-        .unwrap_or_default();
+    let operational_collateral = CollateralAmount::<Dot>::from_units(vault_allowance_in_dot)?
+        .checked_div(3)?
+        .checked_mul(2)?;
 
     deposit_collateral(&parachain_rpc, operational_collateral).await?;
 
     Ok(())
 }
+
+/// Request funds from the faucet, retrying with exponential backoff.
+async fn request_top_up(faucet_url: &str, vault_id: AccountId) -> Result<(), Error> {
+    let mut backoff = TOP_UP_INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let result = async {
+            let connection = jsonrpc_http::connect::<TypedClient>(faucet_url).await?;
+            get_funding(connection, vault_id.clone()).await
+        }
+        .await;
+        match result {
+            Err(err) if attempt < TOP_UP_MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "Failed to get funding from faucet (attempt {} of {}): {}",
+                    attempt,
+                    TOP_UP_MAX_ATTEMPTS,
+                    err
+                );
+                delay_for(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Keep the vault able to pay for its extrinsics on test networks: when the free balance of the
+/// fee currency drops below `threshold`, funds are requested from the faucet.
+pub async fn maintain_fee_balance(
+    parachain_rpc: InterBtcParachain,
+    faucet_url: String,
+    threshold: u128,
+) -> Result<(), ServiceError> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+    let subscription = BalanceSubscription::new(vec![vault_id.clone()]).with_fee_currency();
+    let last_top_up: Mutex<Option<Instant>> = Mutex::new(None);
+    let (vault_id, faucet_url, last_top_up) = (&vault_id, &faucet_url, &last_top_up);
+    parachain_rpc
+        .on_balance_change(subscription, |update| async move {
+            let free = match update.get(vault_id, FEE_CURRENCY) {
+                Some(change) if change.current.free < threshold => change.current.free,
+                _ => return,
+            };
+            {
+                let mut last_top_up = last_top_up.lock().expect("poisoned");
+                if last_top_up.map_or(false, |at| at.elapsed() < TOP_UP_COOLDOWN) {
+                    return;
+                }
+                *last_top_up = Some(Instant::now());
+            }
+            tracing::info!(
+                "Fee balance of {} is below {}, requesting funds from the faucet",
+                free,
+                threshold
+            );
+            if let Err(err) = request_top_up(faucet_url, vault_id.clone()).await {
+                tracing::error!("Failed to top up the fee balance: {}", err);
+            }
+        })
+        .await?;
+    Ok(())
+}
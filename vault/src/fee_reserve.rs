@@ -0,0 +1,67 @@
+use bitcoin::{fee_for_vsize, signed_difference, Amount, AmountExt, Error, SignedAmount};
+
+/// Virtual size assumed for a payment: two inputs, the payment, the change and the
+/// OP_RETURN output.
+pub const ESTIMATED_PAYMENT_VSIZE: u64 = 250;
+
+/// Bitcoin needed to pay the network fees of all outstanding payments (pending redeems,
+/// replaces and refunds), which is not available to the vault even though it is part of
+/// the float.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeReserve {
+    pub pending_payments: u64,
+    /// Fee rate (sat/vbyte) currently needed for inclusion in the next block.
+    pub fee_rate: u64,
+}
+
+impl FeeReserve {
+    /// Fees of the outstanding payments at the current fee rate.
+    pub fn required(&self) -> Result<Amount, Error> {
+        fee_for_vsize(self.fee_rate, ESTIMATED_PAYMENT_VSIZE)?.try_mul(self.pending_payments)
+    }
+
+    /// Fees of the outstanding payments if the fee rate rises by the given factor.
+    pub fn required_under_spike(&self, spike_multiplier: u64) -> Result<Amount, Error> {
+        self.required()?.try_mul(spike_multiplier)
+    }
+
+    /// The float minus the reserve, negative if the float does not cover the fees.
+    pub fn free_balance(&self, float: Amount) -> Result<SignedAmount, Error> {
+        signed_difference(float, self.required()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_reserve() {
+        let reserve = FeeReserve {
+            pending_payments: 4,
+            fee_rate: 10,
+        };
+        assert_eq!(
+            reserve.required().unwrap(),
+            Amount::from_sat(4 * ESTIMATED_PAYMENT_VSIZE * 10)
+        );
+        assert_eq!(
+            reserve.required_under_spike(3).unwrap(),
+            Amount::from_sat(3 * 4 * ESTIMATED_PAYMENT_VSIZE * 10)
+        );
+        assert_eq!(
+            reserve.free_balance(Amount::from_sat(20_000)).unwrap(),
+            SignedAmount::from_sat(10_000)
+        );
+        assert_eq!(
+            reserve.free_balance(Amount::from_sat(0)).unwrap(),
+            SignedAmount::from_sat(-10_000)
+        );
+        assert!(FeeReserve {
+            pending_payments: u64::MAX,
+            fee_rate: 10,
+        }
+        .required()
+        .is_err());
+    }
+}
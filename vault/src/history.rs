@@ -0,0 +1,64 @@
+use crate::Error;
+use bitcoin::{BitcoinCore, Hash, PartialAddress, TransactionExt, Txid};
+use runtime::{
+    link_payments, BlockNumber, BtcPayment, H256Le, HistoricalRequestKind, HistoricalStatus, InterBtcParachain,
+    RequestHistory, RequestRole, UtilFuncs,
+};
+use serde::Serialize;
+use sp_core::H256;
+
+/// Request involving the vault, with the bitcoin transactions of its wallet paying for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub request_id: H256,
+    pub kind: HistoricalRequestKind,
+    pub role: RequestRole,
+    /// Amount of bitcoin to be transferred, in satoshis.
+    pub amount: u128,
+    pub btc_address: Option<String>,
+    pub opentime: BlockNumber,
+    pub status: HistoricalStatus,
+    pub btc_txids: Vec<Txid>,
+}
+
+/// Collect the issue, redeem and replace requests of the vault from the parachain, and link
+/// them to the transactions of the wallet that pay for them.
+pub async fn collect_request_history(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+) -> Result<Vec<HistoryEntry>, Error> {
+    let mut history = parachain_rpc
+        .get_request_history(parachain_rpc.get_account_id().clone())
+        .await?;
+
+    let payments: Vec<_> = bitcoin_core
+        .get_wallet_transactions()
+        .await?
+        .into_iter()
+        .map(|transaction| BtcPayment {
+            txid: H256Le::from_bytes_le(&transaction.txid().to_vec()),
+            op_return: transaction.get_op_return(),
+            output_addresses: transaction.extract_output_addresses(),
+        })
+        .collect();
+    link_payments(&mut history, &payments);
+
+    history.sort_by_key(|request| request.opentime);
+    Ok(history
+        .into_iter()
+        .map(|request| HistoryEntry {
+            request_id: request.id,
+            kind: request.kind,
+            role: request.role,
+            amount: request.amount,
+            btc_address: request.btc_address.encode_str(bitcoin_core.network()).ok(),
+            opentime: request.opentime,
+            status: request.status,
+            btc_txids: request
+                .btc_txids
+                .iter()
+                .filter_map(|txid| Txid::from_slice(&txid.to_bytes_le()).ok())
+                .collect(),
+        })
+        .collect())
+}
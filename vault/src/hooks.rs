@@ -0,0 +1,150 @@
+use crate::{request_state::RequestKind, Error};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use runtime::{
+    pallets::{issue::RequestIssueEvent, redeem::RequestRedeemEvent},
+    BtcAddress, InterBtcRuntime,
+};
+use sp_core::H256;
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+lazy_static! {
+    static ref INSTALLED: RwLock<Hooks> = RwLock::new(Hooks::default());
+}
+
+/// Bitcoin payment the vault is about to make for a redeem, replace or refund.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payment {
+    pub request_id: H256,
+    pub kind: RequestKind,
+    /// Amount in satoshi.
+    pub amount: u128,
+    pub btc_address: BtcAddress,
+}
+
+/// Extension points for custom business logic, e.g. compliance checks or treasury rules,
+/// registered with `VaultServiceConfig::with_hooks`. All methods have no-op defaults. They
+/// are awaited by the handlers of the requests, so they should return quickly.
+#[async_trait]
+pub trait VaultHooks: Send + Sync {
+    /// An issue request directed at this vault was received.
+    async fn on_issue_request(&self, _event: &RequestIssueEvent<InterBtcRuntime>) {}
+
+    /// A redeem request directed at this vault was received, before it is paid.
+    async fn on_redeem_request(&self, _event: &RequestRedeemEvent<InterBtcRuntime>) {}
+
+    /// Called before each payment, after the deadline check and before the operator approval.
+    /// Returning an error refuses the payment, the request is left to expire.
+    async fn before_payment(&self, _payment: &Payment) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// The execution of the request by this vault was included in a parachain block.
+    async fn after_execution(&self, _request_id: H256, _kind: RequestKind) {}
+}
+
+/// The registered hooks, if any.
+#[derive(Clone, Default)]
+pub struct Hooks(Option<Arc<dyn VaultHooks>>);
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Hooks(registered)"),
+            None => write!(f, "Hooks(none)"),
+        }
+    }
+}
+
+impl Hooks {
+    pub fn new(hooks: impl VaultHooks + 'static) -> Self {
+        Self(Some(Arc::new(hooks)))
+    }
+
+    async fn before_payment(&self, payment: &Payment) -> Result<(), Error> {
+        match &self.0 {
+            Some(hooks) => hooks.before_payment(payment).await.map_err(|reason| {
+                tracing::warn!(
+                    "Payment for request #{} refused by hook: {}",
+                    payment.request_id,
+                    reason
+                );
+                Error::PaymentRefused(reason)
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Make the hooks available to the request handlers, replacing those installed before.
+pub(crate) fn install(hooks: Hooks) {
+    if let Ok(mut installed) = INSTALLED.write() {
+        *installed = hooks;
+    }
+}
+
+fn installed() -> Option<Arc<dyn VaultHooks>> {
+    INSTALLED.read().ok()?.0.clone()
+}
+
+pub(crate) async fn on_issue_request(event: &RequestIssueEvent<InterBtcRuntime>) {
+    if let Some(hooks) = installed() {
+        hooks.on_issue_request(event).await;
+    }
+}
+
+pub(crate) async fn on_redeem_request(event: &RequestRedeemEvent<InterBtcRuntime>) {
+    if let Some(hooks) = installed() {
+        hooks.on_redeem_request(event).await;
+    }
+}
+
+pub(crate) async fn before_payment(payment: &Payment) -> Result<(), Error> {
+    Hooks(installed()).before_payment(payment).await
+}
+
+pub(crate) async fn after_execution(request_id: H256, kind: RequestKind) {
+    if let Some(hooks) = installed() {
+        hooks.after_execution(request_id, kind).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H160;
+
+    struct TreasuryLimit(u128);
+
+    #[async_trait]
+    impl VaultHooks for TreasuryLimit {
+        async fn before_payment(&self, payment: &Payment) -> Result<(), String> {
+            if payment.amount > self.0 {
+                Err(format!("{} exceeds the limit of {}", payment.amount, self.0))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_before_payment() {
+        let payment = |amount| Payment {
+            request_id: H256::zero(),
+            kind: RequestKind::Redeem,
+            amount,
+            btc_address: BtcAddress::P2SH(H160::zero()),
+        };
+        assert!(Hooks::default().before_payment(&payment(u128::MAX)).await.is_ok());
+
+        let hooks = Hooks::new(TreasuryLimit(100));
+        assert!(hooks.before_payment(&payment(100)).await.is_ok());
+        assert!(matches!(
+            hooks.before_payment(&payment(101)).await,
+            Err(Error::PaymentRefused(_))
+        ));
+    }
+}
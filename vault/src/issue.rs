@@ -1,6 +1,16 @@
-use crate::{Error, Event, IssueRequests};
-use bitcoin::{BitcoinCoreApi, BlockHash, Transaction, TransactionExt};
+use crate::{
+    analytics::{self, AnalyticsEvent},
+    concurrency::TaskLimiter,
+    degradation, deposit_uri, hooks,
+    latency::{self, Stage},
+    metrics::{DEPOSIT_ADDRESS_MISMATCHES, ISSUE_PAYMENT_DISCREPANCIES},
+    replay::{self, ScenarioStep},
+    request_state::{self, RequestKind, RequestState},
+    Error, Event, IssueRequests,
+};
+use bitcoin::{BitcoinCoreApi, BlockHash, Error as BitcoinError, PartialAddress, Transaction, TransactionExt};
 use futures::{channel::mpsc::Sender, future, SinkExt, StreamExt};
+use lazy_static::lazy_static;
 use runtime::{
     pallets::issue::{CancelIssueEvent, ExecuteIssueEvent, RequestIssueEvent},
     BtcAddress, BtcPublicKey, BtcRelayPallet, H256Le, InterBtcParachain, InterBtcRuntime, IssuePallet, UtilFuncs,
@@ -8,7 +18,16 @@ use runtime::{
 use service::Error as ServiceError;
 use sha2::{Digest, Sha256};
 use sp_core::H256;
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+lazy_static! {
+    /// Issues whose deposit address could not be verified against the vault's own key. They
+    /// are watched, but the operator has to check and execute them.
+    static ref UNVERIFIED_DEPOSITS: Mutex<HashSet<H256>> = Mutex::new(HashSet::new());
+}
 
 // initialize `issue_set` with currently open issues, and return the block height
 // from which to start watching the bitcoin chain
@@ -27,6 +46,14 @@ pub(crate) async fn initialize_issue_set<B: BitcoinCoreApi + Clone + Send + Sync
     };
 
     for (issue_id, request) in requests.into_iter() {
+        if &request.vault == btc_parachain.get_account_id() {
+            check_deposit_address(bitcoin_core, issue_id, &request.btc_public_key, &request.btc_address).await;
+            deposit_uri::insert(
+                issue_id,
+                request.btc_address,
+                request.amount.saturating_add(request.fee),
+            );
+        }
         issue_set.insert(issue_id, request.btc_address);
     }
 
@@ -41,39 +68,99 @@ pub async fn process_issue_requests<B: BitcoinCoreApi + Clone + Send + Sync + 's
     issue_set: Arc<IssueRequests>,
     btc_start_height: u32,
     num_confirmations: u32,
+    proof_safety: ProofSafety,
+    task_limiter: TaskLimiter,
 ) -> Result<(), ServiceError> {
+    // proofs are submitted in the tasks of the limiter, so that a slow execution does
+    // not hold up the payments to other issues
+    let execute = |block_hash: BlockHash, transaction: Transaction| {
+        let bitcoin_core = bitcoin_core.clone();
+        let btc_parachain = btc_parachain.clone();
+        let issue_set = issue_set.clone();
+        let proof_safety = proof_safety.clone();
+        task_limiter.spawn(async move {
+            if let Err(e) = process_transaction_and_execute_issue(
+                &bitcoin_core,
+                &btc_parachain,
+                &issue_set,
+                num_confirmations,
+                &proof_safety,
+                block_hash,
+                transaction,
+            )
+            .await
+            {
+                tracing::warn!("Failed to execute issue request: {}", e.to_string());
+            }
+        });
+    };
+
+    let btc_start_height =
+        catch_up_issue_requests(&bitcoin_core, &issue_set, btc_start_height, num_confirmations, &execute).await?;
+
     let mut stream =
         bitcoin::stream_in_chain_transactions(bitcoin_core.clone(), btc_start_height, num_confirmations).await;
 
     while let Some(Ok((block_hash, transaction))) = stream.next().await {
-        if let Err(e) = process_transaction_and_execute_issue(
-            &bitcoin_core,
-            &btc_parachain,
-            &issue_set,
-            num_confirmations,
-            block_hash,
-            transaction,
-        )
-        .await
-        {
-            tracing::warn!("Failed to execute issue request: {}", e.to_string());
-        }
+        execute(block_hash, transaction);
     }
 
     // stream closed, restart client
     Err(ServiceError::ClientShutdown)
 }
 
+/// Process the blocks from `btc_start_height` which already have enough confirmations, only
+/// deserializing the transactions paying to an open issue. Returns the height of the first
+/// block that is not confirmed yet.
+async fn catch_up_issue_requests<B: BitcoinCoreApi, F: Fn(BlockHash, Transaction)>(
+    bitcoin_core: &B,
+    issue_set: &Arc<IssueRequests>,
+    btc_start_height: u32,
+    num_confirmations: u32,
+    execute: F,
+) -> Result<u32, BitcoinError> {
+    let block_count = bitcoin_core.get_block_count().await? as u32;
+    // a block has `block_count - height + 1` confirmations
+    let confirmed_height = (block_count + 1).saturating_sub(num_confirmations.max(1));
+
+    let mut height = btc_start_height;
+    while height <= confirmed_height {
+        let scripts = issue_set
+            .lock()
+            .await
+            .values()
+            .filter_map(|address| address.to_script().ok())
+            .collect::<Vec<_>>();
+        if scripts.is_empty() {
+            // nothing to look for, the blocks are skipped
+            return Ok(confirmed_height + 1);
+        }
+
+        let block_hash = bitcoin_core.get_block_hash(height).await?;
+        for transaction in bitcoin_core
+            .get_block_transactions_paying_to(&block_hash, &scripts)
+            .await?
+        {
+            execute(block_hash, transaction);
+        }
+        height += 1;
+    }
+
+    Ok(height)
+}
+
 pub async fn add_keys_from_past_issue_request<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     bitcoin_core: &B,
     btc_parachain: &InterBtcParachain,
+    rescan_start_height: Option<u32>,
 ) -> Result<(), Error> {
     let issue_requests = btc_parachain
         .get_vault_issue_requests(btc_parachain.get_account_id().clone())
         .await?;
 
     let btc_start_height = match issue_requests.iter().map(|(_, request)| request.btc_height).min() {
-        Some(x) => x as usize,
+        // blocks below the configured height have already been scanned, e.g. by a restored wallet
+        Some(x) => x.max(rescan_start_height.unwrap_or_default()) as usize,
         None => return Ok(()), // the iterator is empty so we have nothing to do
     };
 
@@ -89,21 +176,48 @@ pub async fn add_keys_from_past_issue_request<B: BitcoinCoreApi + Clone + Send +
     Ok(())
 }
 
+/// Comparison of the amount transferred for an issue request with the requested amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IssuePayment {
+    Exact,
+    /// Transferred more than requested by the given amount
+    Overpaid(u128),
+    /// Transferred less than requested by the given amount
+    Underpaid(u128),
+}
+
+impl IssuePayment {
+    fn reconcile(expected: u128, transferred: u128) -> Self {
+        if transferred > expected {
+            IssuePayment::Overpaid(transferred - expected)
+        } else if transferred < expected {
+            IssuePayment::Underpaid(expected - transferred)
+        } else {
+            IssuePayment::Exact
+        }
+    }
+}
+
 /// execute issue requests with a matching Bitcoin payment
 async fn process_transaction_and_execute_issue<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     bitcoin_core: &B,
     btc_parachain: &InterBtcParachain,
     issue_set: &Arc<IssueRequests>,
     num_confirmations: u32,
+    proof_safety: &ProofSafety,
     block_hash: BlockHash,
     transaction: Transaction,
 ) -> Result<(), Error> {
     let addresses = transaction.extract_output_addresses::<BtcAddress>();
-    let mut issue_requests = issue_set.lock().await;
-    if let Some((issue_id, address)) = addresses.iter().find_map(|address| {
-        let issue_id = issue_requests.get_key_for_value(address)?;
-        Some((*issue_id, *address))
-    }) {
+    // the lock is not held while executing, so that other issues are processed concurrently
+    let payment = {
+        let mut issue_requests = issue_set.lock().await;
+        addresses.iter().find_map(|address| {
+            let issue_id = issue_requests.get_key_for_value(address)?;
+            Some((*issue_id, *address))
+        })
+    };
+    if let Some((issue_id, address)) = payment {
         let issue = btc_parachain.get_issue_request(issue_id).await?;
         // tx has output to address
         match transaction.get_payment_amount_to(address) {
@@ -117,30 +231,75 @@ async fn process_transaction_and_execute_issue<B: BitcoinCoreApi + Clone + Send
             }
             Some(transferred) => {
                 let transferred = transferred as u128;
-                if transferred == issue.amount + issue.fee {
-                    tracing::info!("Found tx for issue with id {:?}", issue_id);
-                } else {
-                    tracing::info!(
-                        "Found tx for issue with id {}. Expected amount = {}, got {}",
-                        issue_id,
-                        issue.amount,
-                        transferred
-                    );
+                let expected = issue.amount + issue.fee;
+                match IssuePayment::reconcile(expected, transferred) {
+                    IssuePayment::Exact => {
+                        tracing::info!("Found tx for issue with id {:?}", issue_id);
+                    }
+                    IssuePayment::Overpaid(excess) => {
+                        // the parachain requests a refund of the excess on execution
+                        tracing::warn!(
+                            "Issue #{} overpaid by {} in tx {}, expecting refund request",
+                            issue_id,
+                            excess,
+                            transaction.txid()
+                        );
+                        ISSUE_PAYMENT_DISCREPANCIES.with_label_values(&["overpaid"]).inc();
+                    }
+                    IssuePayment::Underpaid(missing) => {
+                        // only the requester may execute an underpaid issue, for the transferred amount
+                        tracing::warn!(
+                            "Issue #{} underpaid by {} in tx {}, awaiting execution by requester",
+                            issue_id,
+                            missing,
+                            transaction.txid()
+                        );
+                        ISSUE_PAYMENT_DISCREPANCIES.with_label_values(&["underpaid"]).inc();
+                        return Ok(());
+                    }
                 }
 
-                if transferred < issue.amount + issue.fee {
-                    // insufficient amount, don't execute
+                issue_set.lock().await.remove_value(&address);
+                latency::mark(issue_id, Stage::PaymentConfirmed);
+                request_state::transition(
+                    issue_id,
+                    RequestState::Confirmed {
+                        txid: transaction.txid().to_string(),
+                        block_hash: block_hash.to_string(),
+                    },
+                );
+
+                if UNVERIFIED_DEPOSITS.lock().expect("poisoned").contains(&issue_id) {
+                    tracing::error!(
+                        "Not executing issue #{} paid in tx {}, its deposit address could not be verified",
+                        issue_id,
+                        transaction.txid()
+                    );
                     return Ok(());
                 }
 
-                issue_requests.remove_value(&address);
-
                 // at this point we know that the transaction has `num_confirmations` on the bitcoin chain,
                 // but the relay can introduce a delay, so wait until the relay also confirms the transaction.
                 btc_parachain
                     .wait_for_block_in_relay(H256Le::from_bytes_le(&block_hash.to_vec()), Some(num_confirmations))
                     .await?;
 
+                // the payment may additionally have to be buried deeper to be safe against shallow forks
+                let block_height = bitcoin_core.get_block_info(&block_hash).await?.height as u32;
+                if let Err(err) = proof_safety
+                    .wait_until_safe(btc_parachain, bitcoin_core, block_height, &block_hash, transferred)
+                    .await
+                {
+                    if matches!(err, Error::PaymentReorganized) {
+                        // watch the deposit address again, the payment may be included in another block
+                        issue_set.insert(issue_id, address).await;
+                    }
+                    return Err(err);
+                }
+
+                // executing issues is not deadline-critical for the vault, the requester can execute them
+                degradation::yield_to_critical().await;
+
                 // found tx, submit proof
                 let txid = transaction.txid();
 
@@ -148,13 +307,39 @@ async fn process_transaction_and_execute_issue<B: BitcoinCoreApi + Clone + Send
                 let raw_tx = bitcoin_core.get_raw_tx(&txid, &block_hash).await?;
                 let proof = bitcoin_core.get_proof(txid, &block_hash).await?;
 
+                // the relay may have reorganized while waiting for the proof to be safe
+                btc_parachain
+                    .verify_block_header_inclusion(H256Le::from_bytes_le(&block_hash.to_vec()))
+                    .await?;
+
                 tracing::info!("Executing issue #{:?}", issue_id);
-                match btc_parachain.execute_issue(issue_id, &proof, &raw_tx).await {
-                    Ok(_) => (),
+                latency::mark(issue_id, Stage::ProofSubmitted);
+                match request_state::correlate(issue_id, btc_parachain.execute_issue(issue_id, &proof, &raw_tx)).await {
+                    Ok(receipt) => {
+                        latency::mark(issue_id, Stage::Executed);
+                        analytics::record(|| AnalyticsEvent::Proof {
+                            request_id: issue_id,
+                            kind: RequestKind::Issue,
+                            txid: txid.to_string(),
+                            block_hash: block_hash.to_string(),
+                            block_height: None,
+                            parachain_fee: receipt.as_ref().and_then(analytics::parachain_fee),
+                        });
+                        request_state::transition(issue_id, RequestState::Executed);
+                        hooks::after_execution(issue_id, RequestKind::Issue).await;
+                    }
                     Err(err) if err.is_issue_completed() => {
                         tracing::info!("Issue #{} has already been completed", issue_id);
                     }
-                    Err(err) => return Err(err.into()),
+                    Err(err) => {
+                        request_state::transition(
+                            issue_id,
+                            RequestState::Failed {
+                                reason: err.to_string(),
+                            },
+                        );
+                        return Err(err.into());
+                    }
                 };
             }
         }
@@ -164,19 +349,72 @@ async fn process_transaction_and_execute_issue<B: BitcoinCoreApi + Clone + Send
     Ok(())
 }
 
+/// Compute the public secret of a deposit using the on-chain key derivation scheme
+pub(crate) fn deposit_secret(secure_id: H256, public_key: &BtcPublicKey) -> Vec<u8> {
+    let mut hasher = Sha256::default();
+    // input compressed public key
+    hasher.input(public_key.0.to_vec());
+    // input issue id
+    hasher.input(secure_id.as_bytes());
+    hasher.result().as_slice().to_vec()
+}
+
+/// Check that the deposit address stored by the parachain is the one derived from the
+/// vault's public key and the secure id, i.e. the address of the deposit key imported into
+/// the wallet. Funds sent to any other address would not be watched by the wallet.
+pub(crate) fn verify_deposit_address(
+    secure_id: H256,
+    public_key: &BtcPublicKey,
+    btc_address: &BtcAddress,
+) -> Result<(), Error> {
+    let payload = bitcoin::calculate_deposit_address(public_key.0, &deposit_secret(secure_id, public_key))?;
+    let expected = BtcAddress::from_payload(payload).map_err(BitcoinError::from)?;
+    if &expected != btc_address {
+        return Err(Error::DepositAddressMismatch {
+            expected,
+            found: *btc_address,
+        });
+    }
+    Ok(())
+}
+
+/// Verify that the deposit address of the issue was derived from the vault's own key. If not,
+/// the operator is alerted and the issue is still watched, but not executed automatically.
+async fn check_deposit_address<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
+    bitcoin_core: &B,
+    issue_id: H256,
+    public_key: &BtcPublicKey,
+    btc_address: &BtcAddress,
+) {
+    let result = match bitcoin_core.wallet_has_public_key(public_key.0).await {
+        Ok(true) => verify_deposit_address(issue_id, public_key, btc_address),
+        Ok(false) => Err(Error::ForeignDepositKey),
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = result {
+        tracing::error!(
+            "Deposit address of issue #{} could not be verified, it will not be executed automatically: {}",
+            issue_id,
+            e.to_string()
+        );
+        DEPOSIT_ADDRESS_MISMATCHES.inc();
+        UNVERIFIED_DEPOSITS.lock().expect("poisoned").insert(issue_id);
+    }
+}
+
+/// Stop tracking the verification of a closed issue.
+fn forget_deposit_address(issue_id: &H256) {
+    UNVERIFIED_DEPOSITS.lock().expect("poisoned").remove(issue_id);
+}
+
 /// Import the deposit key using the on-chain key derivation scheme
 async fn add_new_deposit_key<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     bitcoin_core: &B,
     secure_id: H256,
     public_key: BtcPublicKey,
 ) -> Result<(), Error> {
-    let mut hasher = Sha256::default();
-    // input compressed public key
-    hasher.input(public_key.0.to_vec());
-    // input issue id
-    hasher.input(secure_id.as_bytes());
     bitcoin_core
-        .add_new_deposit_key(public_key.0, hasher.result().as_slice().to_vec())
+        .add_new_deposit_key(public_key.0, deposit_secret(secure_id, &public_key))
         .await?;
     Ok(())
 }
@@ -190,36 +428,74 @@ async fn add_new_deposit_key<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
 /// * `btc_parachain` - the parachain RPC handle
 /// * `event_channel` - the channel over which to signal events
 /// * `issue_set` - all issue ids observed since vault started
+/// * `task_limiter` - bounds the number of events handled concurrently
 pub async fn listen_for_issue_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     bitcoin_core: B,
     btc_parachain: InterBtcParachain,
     event_channel: Sender<Event>,
     issue_set: Arc<IssueRequests>,
+    task_limiter: TaskLimiter,
 ) -> Result<(), ServiceError> {
     let bitcoin_core = &bitcoin_core;
     let btc_parachain = &btc_parachain;
     let event_channel = &event_channel;
     let issue_set = &issue_set;
+    let task_limiter = &task_limiter;
     btc_parachain
         .on_event::<RequestIssueEvent<InterBtcRuntime>, _, _, _>(
             |event| async move {
-                if &event.vault_id == btc_parachain.get_account_id() {
-                    tracing::info!("Received request issue event: {:?}", event);
-                    // try to send the event, but ignore the returned result since
-                    // the only way it can fail is if the channel is closed
-                    let _ = event_channel.clone().send(Event::Opened).await;
+                // e.g. importing the deposit key of one issue must not hold up the next event
+                let bitcoin_core = bitcoin_core.clone();
+                let btc_parachain = btc_parachain.clone();
+                let mut event_channel = event_channel.clone();
+                let issue_set = issue_set.clone();
+                task_limiter.spawn(async move {
+                    if &event.vault_id == btc_parachain.get_account_id() {
+                        check_deposit_address(
+                            &bitcoin_core,
+                            event.issue_id,
+                            &event.vault_public_key,
+                            &event.vault_btc_address,
+                        )
+                        .await;
+                        latency::observe(event.issue_id, "issue");
+                        request_state::seen(event.issue_id, RequestKind::Issue);
+                        tracing::info!("Received request issue event: {:?}", event);
+                        replay::record(|_| {
+                            Ok(ScenarioStep::IssueRequested {
+                                issue_id: event.issue_id,
+                            })
+                        });
+                        analytics::record(|| AnalyticsEvent::Request {
+                            request_id: event.issue_id,
+                            kind: RequestKind::Issue,
+                            amount: event.amount_btc,
+                            fee: Some(event.fee),
+                            btc_address: event.vault_btc_address,
+                        });
+                        hooks::on_issue_request(&event).await;
+                        deposit_uri::insert(
+                            event.issue_id,
+                            event.vault_btc_address,
+                            event.amount_btc.saturating_add(event.fee),
+                        );
+                        // try to send the event, but ignore the returned result since
+                        // the only way it can fail is if the channel is closed
+                        let _ = event_channel.send(Event::Opened).await;
 
-                    if let Err(e) = add_new_deposit_key(bitcoin_core, event.issue_id, event.vault_public_key).await {
-                        tracing::error!("Failed to add new deposit key #{}: {}", event.issue_id, e.to_string());
+                        if let Err(e) = add_new_deposit_key(&bitcoin_core, event.issue_id, event.vault_public_key).await
+                        {
+                            tracing::error!("Failed to add new deposit key #{}: {}", event.issue_id, e.to_string());
+                        }
                     }
-                }
 
-                tracing::trace!(
-                    "watching issue #{} for payment to {:?}",
-                    event.issue_id,
-                    event.vault_btc_address
-                );
-                issue_set.insert(event.issue_id, event.vault_btc_address).await;
+                    tracing::trace!(
+                        "watching issue #{} for payment to {:?}",
+                        event.issue_id,
+                        event.vault_btc_address
+                    );
+                    issue_set.insert(event.issue_id, event.vault_btc_address).await;
+                });
             },
             |error| tracing::error!("Error reading request issue event: {}", error.to_string()),
         )
@@ -248,6 +524,11 @@ pub async fn listen_for_issue_executes(
             |event| async move {
                 if &event.vault_id == btc_parachain.get_account_id() {
                     tracing::info!("Received execute issue event: {:?}", event);
+                    replay::record(|_| {
+                        Ok(ScenarioStep::IssueExecuted {
+                            issue_id: event.issue_id,
+                        })
+                    });
                     // try to send the event, but ignore the returned result since
                     // the only way it can fail is if the channel is closed
                     let _ = event_channel.clone().send(Event::Executed(event.issue_id)).await;
@@ -255,6 +536,8 @@ pub async fn listen_for_issue_executes(
 
                 tracing::trace!("issue #{} executed, no longer watching", event.issue_id);
                 issue_set.remove(&event.issue_id).await;
+                deposit_uri::remove(&event.issue_id);
+                forget_deposit_address(&event.issue_id);
             },
             |error| tracing::error!("Error reading execute issue event: {}", error.to_string()),
         )
@@ -278,9 +561,42 @@ pub async fn listen_for_issue_cancels(
             |event| async move {
                 tracing::trace!("issue #{} cancelled, no longer watching", event.issue_id);
                 issue_set.remove(&event.issue_id).await;
+                deposit_uri::remove(&event.issue_id);
+                forget_deposit_address(&event.issue_id);
             },
             |error| tracing::error!("Error reading cancel issue event: {}", error.to_string()),
         )
         .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconcile_issue_payment() {
+        assert_eq!(IssuePayment::reconcile(100, 100), IssuePayment::Exact);
+        assert_eq!(IssuePayment::reconcile(100, 150), IssuePayment::Overpaid(50));
+        assert_eq!(IssuePayment::reconcile(100, 40), IssuePayment::Underpaid(60));
+    }
+
+    #[test]
+    fn test_verify_deposit_address() {
+        use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+        let vault_secret_key = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let public_key = BtcPublicKey(PublicKey::from_secret_key(&Secp256k1::new(), &vault_secret_key).serialize());
+        let secure_id = H256::repeat_byte(1);
+        let payload =
+            bitcoin::calculate_deposit_address(public_key.0, &deposit_secret(secure_id, &public_key)).unwrap();
+        let btc_address = BtcAddress::from_payload(payload).unwrap();
+
+        assert!(verify_deposit_address(secure_id, &public_key, &btc_address).is_ok());
+        // derived for another request
+        assert!(matches!(
+            verify_deposit_address(H256::repeat_byte(2), &public_key, &btc_address),
+            Err(Error::DepositAddressMismatch { found, .. }) if found == btc_address
+        ));
+    }
+}
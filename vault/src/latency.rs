@@ -0,0 +1,182 @@
+use lazy_static::lazy_static;
+use prometheus::{HistogramOpts, HistogramVec};
+use sp_core::H256;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Maximum number of requests for which the timeline is kept, the oldest are evicted first.
+const MAX_TIMELINES: usize = 1000;
+
+lazy_static! {
+    pub static ref REQUEST_LATENCY: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "request_latency_seconds",
+            "Time taken to reach each stage of a request since the previous stage, or in total"
+        )
+        .buckets(vec![6.0, 30.0, 60.0, 300.0, 600.0, 1800.0, 3600.0, 7200.0, 14400.0]),
+        &["request_type", "stage"]
+    )
+    .expect("Failed to create prometheus metric");
+    static ref TIMELINES: Mutex<Timelines> = Mutex::new(Timelines::default());
+}
+
+/// Stages of an issue, redeem, replace or refund request as processed by the vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The bitcoin payment was placed in the mempool (not applicable to issues).
+    PaymentBroadcast,
+    /// The payment has the required number of bitcoin confirmations.
+    PaymentConfirmed,
+    /// The block is confirmed by the relay, the proof is submitted for execution.
+    ProofSubmitted,
+    /// The execution was finalized on the parachain.
+    Executed,
+}
+
+impl Stage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Stage::PaymentBroadcast => "payment_broadcast",
+            Stage::PaymentConfirmed => "payment_confirmed",
+            Stage::ProofSubmitted => "proof_submitted",
+            Stage::Executed => "executed",
+        }
+    }
+}
+
+/// Time at which each stage of a single request was reached.
+#[derive(Debug, Clone)]
+struct Timeline {
+    request_type: &'static str,
+    observed: Instant,
+    stages: Vec<(Stage, Duration)>,
+}
+
+impl Timeline {
+    /// Record the stage, returns the time since the previous stage if it was not reached before.
+    fn mark(&mut self, stage: Stage, now: Instant) -> Option<Duration> {
+        if self.stages.iter().any(|(reached, _)| *reached == stage) {
+            return None;
+        }
+        let elapsed = now.saturating_duration_since(self.observed);
+        let previous = self.stages.last().map(|(_, at)| *at).unwrap_or_default();
+        self.stages.push((stage, elapsed));
+        Some(elapsed.checked_sub(previous).unwrap_or_default())
+    }
+}
+
+#[derive(Default)]
+struct Timelines {
+    timelines: HashMap<H256, Timeline>,
+    order: VecDeque<H256>,
+}
+
+impl Timelines {
+    fn observe(&mut self, request_id: H256, request_type: &'static str, now: Instant) {
+        if self.timelines.contains_key(&request_id) {
+            return;
+        }
+        self.timelines.insert(
+            request_id,
+            Timeline {
+                request_type,
+                observed: now,
+                stages: Vec::new(),
+            },
+        );
+        self.order.push_back(request_id);
+        while self.order.len() > MAX_TIMELINES {
+            if let Some(evicted) = self.order.pop_front() {
+                self.timelines.remove(&evicted);
+            }
+        }
+    }
+}
+
+/// Start the timeline of a request when its event is observed. Does nothing if the
+/// request is already tracked, e.g. when it is picked up again after being queued.
+pub fn observe(request_id: H256, request_type: &'static str) {
+    if let Ok(mut timelines) = TIMELINES.lock() {
+        timelines.observe(request_id, request_type, Instant::now());
+    }
+}
+
+/// Record that a request reached the given stage. Requests observed before the vault
+/// (re)started have no timeline and are ignored.
+pub fn mark(request_id: H256, stage: Stage) {
+    let mut timelines = match TIMELINES.lock() {
+        Ok(timelines) => timelines,
+        Err(_) => return,
+    };
+    let timeline = match timelines.timelines.get_mut(&request_id) {
+        Some(timeline) => timeline,
+        None => return,
+    };
+    let elapsed = match timeline.mark(stage, Instant::now()) {
+        Some(elapsed) => elapsed,
+        None => return,
+    };
+    REQUEST_LATENCY
+        .with_label_values(&[timeline.request_type, stage.as_str()])
+        .observe(elapsed.as_secs_f64());
+
+    if stage == Stage::Executed {
+        let total = timeline.stages.last().map(|(_, at)| *at).unwrap_or_default();
+        REQUEST_LATENCY
+            .with_label_values(&[timeline.request_type, "total"])
+            .observe(total.as_secs_f64());
+        tracing::info!(
+            "Timeline of {} #{}: {}",
+            timeline.request_type,
+            request_id,
+            timeline
+                .stages
+                .iter()
+                .map(|(stage, at)| format!("{} after {:.1}s", stage.as_str(), at.as_secs_f64()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeline_stage_durations() {
+        let observed = Instant::now();
+        let mut timeline = Timeline {
+            request_type: "redeem",
+            observed,
+            stages: Vec::new(),
+        };
+        assert_eq!(
+            timeline.mark(Stage::PaymentBroadcast, observed + Duration::from_secs(5)),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            timeline.mark(Stage::PaymentConfirmed, observed + Duration::from_secs(65)),
+            Some(Duration::from_secs(60))
+        );
+        // stages are only recorded once, e.g. when the payment is re-fetched after a fork
+        assert_eq!(
+            timeline.mark(Stage::PaymentConfirmed, observed + Duration::from_secs(75)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_timelines_are_bounded() {
+        let mut timelines = Timelines::default();
+        let now = Instant::now();
+        for i in 0..=MAX_TIMELINES as u64 {
+            timelines.observe(H256::from_low_u64_be(i), "issue", now);
+        }
+        assert_eq!(timelines.timelines.len(), MAX_TIMELINES);
+        assert!(!timelines.timelines.contains_key(&H256::from_low_u64_be(0)));
+    }
+}
@@ -0,0 +1,363 @@
+use crate::{metrics::IS_LEADER, Error};
+use bitcoin::BitcoinCore;
+use futures::future::{AbortHandle, Abortable};
+use service::Error as ServiceError;
+use std::{
+    ffi::OsString,
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    task::JoinHandle,
+    time::{delay_for, timeout},
+};
+
+/// State of the lease for one epoch, as stored in its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LeaseState {
+    holder: String,
+    /// Unix timestamp (in seconds) at which the lease expires.
+    expiry: u64,
+}
+
+/// Epoch of the lease held by this instance, and the local time until which it may act on it.
+#[derive(Debug, Clone, Copy)]
+pub struct Tenure {
+    pub epoch: u64,
+    valid_until: Instant,
+}
+
+/// Lease shared between a primary and standby vault instances (e.g. on a network file system),
+/// so that only the holder signs and broadcasts bitcoin payments.
+///
+/// Every epoch of the lease has its own file `<path>.<epoch>`, the highest one is current.
+/// An instance takes over an expired lease by linking the file of the next epoch into place,
+/// which fails if another instance did so first, so that at most one instance wins an epoch.
+/// Only the holder rewrites the file of its epoch to renew it. The clocks of the instances
+/// must agree to within a third of the lease duration.
+#[derive(Debug, Clone)]
+pub struct LeaderLease {
+    path: PathBuf,
+    holder: String,
+    duration: Duration,
+}
+
+/// Stops the task renewing the lease when dropped. Dropping the join handle does not stop a
+/// task, so a restarted service would otherwise leave it renewing the lease under the old
+/// holder id, and stand by forever.
+pub struct LeaseKeeper(AbortHandle);
+
+impl Drop for LeaseKeeper {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "vault".to_string())
+}
+
+/// Identifier of this instance in the lease: the instance name (or the hostname) and a random
+/// suffix, so that instances with the same name or process id never mistake each other's lease
+/// for their own.
+pub fn holder_id(instance_name: Option<String>) -> String {
+    let name = instance_name.unwrap_or_else(hostname);
+    format!(
+        "{}-{:016x}",
+        name.replace(char::is_whitespace, "_"),
+        rand::random::<u64>()
+    )
+}
+
+impl LeaderLease {
+    pub fn new(path: PathBuf, holder: String, duration: Duration) -> Self {
+        Self { path, holder, duration }
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.file_name().map(OsString::from).unwrap_or_default();
+        name.push(format!(".{}", suffix));
+        self.path.with_file_name(name)
+    }
+
+    fn epoch_path(&self, epoch: u64) -> PathBuf {
+        self.sibling(&epoch.to_string())
+    }
+
+    /// Epochs for which a lease file exists, in no particular order.
+    fn epochs(&self) -> Result<Vec<u64>, Error> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let prefix = format!("{}.", self.path.file_name().unwrap_or_default().to_string_lossy());
+        let mut epochs = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            if let Some(Ok(epoch)) = name.strip_prefix(&prefix).map(str::parse::<u64>) {
+                epochs.push(epoch);
+            }
+        }
+        Ok(epochs)
+    }
+
+    /// The current epoch and its state. An unreadable lease is treated as expired.
+    fn current(&self) -> Result<Option<(u64, Option<LeaseState>)>, Error> {
+        let epoch = match self.epochs()?.into_iter().max() {
+            Some(epoch) => epoch,
+            None => return Ok(None),
+        };
+        let contents = match fs::read_to_string(self.epoch_path(epoch)) {
+            Ok(contents) => contents,
+            // removed by a new holder in the meantime, it has a higher epoch
+            Err(err) if err.kind() == ErrorKind::NotFound => return self.current(),
+            Err(err) => return Err(err.into()),
+        };
+        let state = match contents.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [holder, expiry] => expiry.parse().ok().map(|expiry| LeaseState {
+                holder: holder.to_string(),
+                expiry,
+            }),
+            _ => None,
+        };
+        Ok(Some((epoch, state)))
+    }
+
+    /// Write the state to a temporary file, so that the lease file is only ever seen complete.
+    fn write_tmp(&self, state: &LeaseState) -> Result<PathBuf, Error> {
+        let tmp_path = self.sibling(&format!("{}.tmp", self.holder));
+        fs::write(&tmp_path, format!("{} {}", state.holder, state.expiry))?;
+        Ok(tmp_path)
+    }
+
+    fn state(&self, now: u64) -> LeaseState {
+        LeaseState {
+            holder: self.holder.clone(),
+            expiry: now + self.duration.as_secs(),
+        }
+    }
+
+    /// Take over the lease if it is free or expired, returns the epoch if we won it.
+    fn try_acquire(&self, now: u64) -> Result<Option<u64>, Error> {
+        let next_epoch = match self.current()? {
+            Some((_, Some(state))) if state.holder != self.holder && state.expiry > now => return Ok(None),
+            Some((epoch, _)) => epoch + 1,
+            None => 1,
+        };
+        let tmp_path = self.write_tmp(&self.state(now))?;
+        // unlike a rename, linking fails if another instance created the epoch first
+        let linked = fs::hard_link(&tmp_path, self.epoch_path(next_epoch));
+        let _ = fs::remove_file(&tmp_path);
+        match linked {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        for epoch in self.epochs()?.into_iter().filter(|epoch| *epoch < next_epoch) {
+            let _ = fs::remove_file(self.epoch_path(epoch));
+        }
+        Ok(Some(next_epoch))
+    }
+
+    /// Extend the lease of the given epoch, returns false if it changed hands in the meantime.
+    fn try_renew(&self, now: u64, epoch: u64) -> Result<bool, Error> {
+        match self.current()? {
+            Some((current, Some(state))) if current == epoch && state.holder == self.holder => {}
+            _ => return Ok(false),
+        }
+        let tmp_path = self.write_tmp(&self.state(now))?;
+        fs::rename(&tmp_path, self.epoch_path(epoch))?;
+        // a standby may have taken over just before the renewal
+        Ok(matches!(self.current()?, Some((current, _)) if current == epoch))
+    }
+
+    fn renew_interval(&self) -> Duration {
+        // renew well before expiry so that a slow write does not let a standby take over
+        self.duration / 3
+    }
+
+    /// Local time until which the lease may be acted upon if renewed at `renewed_at`. This
+    /// leaves a third of the lease for clock differences, so we stop before a standby starts.
+    fn valid_until(&self, renewed_at: Instant) -> Instant {
+        renewed_at + self.duration - self.renew_interval()
+    }
+
+    /// Wait until this instance becomes the leader, bitcoin spends stay halted until then.
+    pub async fn acquire(&self, bitcoin_core: &BitcoinCore) -> Result<Tenure, Error> {
+        bitcoin_core.fence_spends();
+        IS_LEADER.set(0);
+        let mut logged = false;
+        loop {
+            let started = Instant::now();
+            let lease = self.clone();
+            match tokio::task::spawn_blocking(move || lease.try_acquire(unix_now())).await? {
+                Ok(Some(epoch)) => {
+                    tracing::info!("Acquired leader lease (epoch {}), resuming bitcoin spends", epoch);
+                    bitcoin_core.unfence_spends();
+                    IS_LEADER.set(1);
+                    return Ok(Tenure {
+                        epoch,
+                        valid_until: self.valid_until(started),
+                    });
+                }
+                Ok(None) if !logged => {
+                    tracing::info!("Leader lease is held by another instance, standing by");
+                    logged = true;
+                }
+                Ok(None) => {}
+                Err(err) => tracing::error!("Failed to acquire leader lease: {}", err),
+            }
+            delay_for(self.renew_interval()).await;
+        }
+    }
+
+    /// Keep renewing the lease. If it is not renewed before it may expire for a standby, bitcoin
+    /// spends are halted and an error is returned so that the service restarts as a standby.
+    pub async fn maintain(self, bitcoin_core: BitcoinCore, tenure: Tenure) -> Result<(), ServiceError> {
+        let mut valid_until = tenure.valid_until;
+        loop {
+            delay_for(self.renew_interval()).await;
+            let started = Instant::now();
+            let remaining = valid_until.saturating_duration_since(started);
+            let lease = self.clone();
+            let renewal = tokio::task::spawn_blocking(move || lease.try_renew(unix_now(), tenure.epoch));
+            let reason = match timeout(remaining, renewal).await {
+                Ok(Ok(Ok(true))) => {
+                    valid_until = self.valid_until(started);
+                    continue;
+                }
+                Ok(Ok(Ok(false))) => "taken over by another instance".to_string(),
+                Ok(Ok(Err(err))) => err.to_string(),
+                Ok(Err(err)) => err.to_string(),
+                Err(_) => "not renewed in time".to_string(),
+            };
+            bitcoin_core.fence_spends();
+            IS_LEADER.set(0);
+            tracing::error!("Lost leader lease ({}), halted bitcoin spends", reason);
+            return Err(ServiceError::Other(format!("Lost leader lease: {}", reason)));
+        }
+    }
+
+    /// Run `maintain` in a task, which stops once the returned keeper is dropped.
+    pub fn spawn_keeper(
+        self,
+        bitcoin_core: BitcoinCore,
+        tenure: Tenure,
+    ) -> (JoinHandle<Result<(), ServiceError>>, LeaseKeeper) {
+        let (abort_handle, registration) = AbortHandle::new_pair();
+        let maintain = Abortable::new(self.maintain(bitcoin_core, tenure), registration);
+        let handle = tokio::spawn(async move { maintain.await.unwrap_or(Ok(())) });
+        (handle, LeaseKeeper(abort_handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Auth, Network};
+
+    fn lease(path: &PathBuf, holder: &str) -> LeaderLease {
+        LeaderLease::new(path.clone(), holder.to_string(), Duration::from_secs(30))
+    }
+
+    #[test]
+    fn test_leader_lease_fencing() {
+        let dir = std::env::temp_dir().join(format!("vault-leader-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("leader.lease");
+        let primary = lease(&path, "primary");
+        let standby = lease(&path, "standby");
+
+        assert_eq!(primary.try_acquire(100).unwrap(), Some(1));
+        assert_eq!(standby.try_acquire(110).unwrap(), None);
+        assert!(primary.try_renew(120, 1).unwrap());
+
+        // the primary misses its heartbeats, the standby takes over after expiry
+        assert_eq!(standby.try_acquire(151).unwrap(), Some(2));
+        // the old primary is fenced even though it could otherwise overwrite the lease
+        assert!(!primary.try_renew(152, 1).unwrap());
+        assert_eq!(primary.try_acquire(152).unwrap(), None);
+        assert!(standby.try_renew(160, 2).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_leader_lease_exclusive_takeover() {
+        let dir = std::env::temp_dir().join(format!("vault-leader-takeover-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("leader.lease");
+        let first = lease(&path, "first");
+        let second = lease(&path, "second");
+
+        // both standbys saw the same expired epoch, only one can create the next one
+        fs::write(first.epoch_path(1), "primary 100").unwrap();
+        let tmp_path = second.write_tmp(&second.state(200)).unwrap();
+        assert_eq!(first.try_acquire(200).unwrap(), Some(2));
+        assert_eq!(
+            fs::hard_link(&tmp_path, second.epoch_path(2)).unwrap_err().kind(),
+            ErrorKind::AlreadyExists
+        );
+        assert!(!second.try_renew(200, 2).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reacquire_after_restart() {
+        let dir = std::env::temp_dir().join(format!("vault-leader-restart-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("leader.lease");
+        let bitcoin_core = BitcoinCore::new(
+            "http://127.0.0.1:1".to_string(),
+            Auth::None,
+            None,
+            Network::Regtest,
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        let duration = Duration::from_secs(3);
+
+        let first = LeaderLease::new(path.clone(), holder_id(None), duration);
+        let tenure = first.acquire(&bitcoin_core).await.unwrap();
+        let (_, keeper) = first.spawn_keeper(bitcoin_core.clone(), tenure);
+
+        // the service restarts with a new holder id, the old one must stop renewing the lease
+        drop(keeper);
+        let second = LeaderLease::new(path.clone(), holder_id(None), duration);
+        let tenure = timeout(Duration::from_secs(10), second.acquire(&bitcoin_core))
+            .await
+            .expect("lease is still renewed by the old keeper")
+            .unwrap();
+        assert_eq!(tenure.epoch, 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_holder_id() {
+        assert_ne!(
+            holder_id(Some("vault".to_string())),
+            holder_id(Some("vault".to_string()))
+        );
+        assert!(holder_id(Some("my vault".to_string())).starts_with("my_vault-"));
+    }
+}
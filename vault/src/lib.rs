@@ -1,41 +1,93 @@
 #![recursion_limit = "256"]
 
+mod analytics;
+mod appeal;
+mod approval;
+mod ban;
 mod cancellation;
 mod collateral;
+mod concurrency;
+mod degradation;
+mod deposit_uri;
 mod error;
 mod execution;
+mod exposure;
+mod extrinsic_queue;
 mod faucet;
+mod fee_reserve;
+mod history;
+mod hooks;
 mod issue;
+mod latency;
+mod leader;
+mod liquidation;
+mod maintenance;
+mod metrics;
+mod proof_safety;
+mod reconciliation;
+mod recovery;
 mod redeem;
 mod refund;
 mod relay;
 mod replace;
+mod replay;
+mod request_state;
+mod retire;
+mod snapshot;
 mod system;
 mod types;
 mod vaults;
 
-use runtime::{InterBtcParachain, VaultRegistryPallet};
+use runtime::{CollateralAmount, InterBtcParachain, VaultRegistryPallet};
 use std::time::Duration;
 
 pub mod service {
     pub use crate::{
-        cancellation::{CancellationScheduler, IssueCanceller, ReplaceCanceller},
+        approval::PaymentApproval,
+        ban::BanStatus,
+        cancellation::{CancellationScheduler, IssueCanceller, RedeemCancelPolicy, RedeemCanceller, ReplaceCanceller},
         collateral::maintain_collateralization_rate,
+        concurrency::TaskLimiter,
         execution::execute_open_requests,
+        extrinsic_queue::{ExtrinsicQueue, QueuedExtrinsic},
         issue::{
             listen_for_issue_cancels, listen_for_issue_executes, listen_for_issue_requests, process_issue_requests,
         },
-        redeem::listen_for_redeem_requests,
+        proof_safety::{DepthOverride, ProofSafety},
+        reconciliation::Reconciliation,
+        redeem::{listen_for_own_redeems, listen_for_redeem_requests},
         refund::listen_for_refund_requests,
         relay::{Config, Runner},
         replace::{listen_for_accept_replace, listen_for_execute_replace, listen_for_replace_requests},
-        vaults::{listen_for_vaults_registered, listen_for_wallet_updates, report_vault_thefts},
+        request_state::listen_for_finalized_requests,
+        vaults::{
+            listen_for_vaults_registered, listen_for_wallet_updates, refresh_vault_cache, report_vault_thefts,
+            watch_vault_addresses,
+        },
     };
 }
-pub use crate::{cancellation::Event, error::Error, system::*, types::IssueRequests};
+pub use crate::{
+    appeal::{collect_appeal_info, AppealInfo},
+    cancellation::Event,
+    error::Error,
+    exposure::{concentration, AccountExposure},
+    history::{collect_request_history, HistoryEntry},
+    hooks::{Hooks, Payment, VaultHooks},
+    maintenance::{MaintenancePhase, MaintenanceSchedule, MaintenanceWindow},
+    metrics::start_metrics_server,
+    recovery::{plan_recovery, Heartbeat, RecoveryAction, RecoveryPlan, RequestSummary},
+    replay::{record_to, ScenarioStep},
+    request_state::{
+        correlation_id, get_record, subscribe_transitions, RequestKind, RequestRecord, RequestState, Transition,
+    },
+    retire::{retire_vault, RetirementDeadline, RetirementPlan, RetirementReport},
+    snapshot::{export_snapshot, import_snapshot, Snapshot},
+    system::*,
+    types::IssueRequests,
+};
 pub use vaults::Vaults;
 
-pub(crate) async fn deposit_collateral(api: &InterBtcParachain, amount: u128) -> Result<(), Error> {
+pub(crate) async fn deposit_collateral(api: &InterBtcParachain, amount: CollateralAmount) -> Result<(), Error> {
     let result = api.deposit_collateral(amount).await;
     tracing::info!("Locking additional collateral; amount {}: {:?}", amount, result);
     Ok(result?)
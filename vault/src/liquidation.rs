@@ -0,0 +1,122 @@
+use crate::degradation;
+use lazy_static::lazy_static;
+use prometheus::{Gauge, IntGauge};
+use runtime::{Error as RuntimeError, ExchangeRateOraclePallet, InterBtcParachain, LiquidationPallet, WrappedAmount};
+use service::Error as ServiceError;
+use std::time::Duration;
+use tokio::time::delay_for;
+
+/// Interval at which the liquidation vault is checked for redeem opportunities.
+const LIQUIDATION_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    pub static ref LIQUIDATION_VAULT_TOKENS: IntGauge = IntGauge::new(
+        "liquidation_vault_tokens",
+        "Tokens that can be burned against the liquidation vault for its collateral"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref LIQUIDATION_REDEEM_PREMIUM: Gauge = Gauge::new(
+        "liquidation_redeem_premium_percent",
+        "Collateral received by a liquidation redeem relative to the market value of the burned tokens, minus 100%"
+    )
+    .expect("Failed to create prometheus metric");
+}
+
+/// Limits within which tokens are burned against the liquidation vault automatically.
+#[derive(Debug, Clone, Copy)]
+pub struct LiquidationRedeemLimits {
+    /// Minimum premium in percent of the received collateral over the market value of the tokens.
+    pub min_premium_percent: u64,
+    /// Maximum amount of tokens to burn in a single redeem.
+    pub max_amount: u128,
+}
+
+/// Premium in percent of `collateral` over `market_value`, negative if it is worth less.
+fn premium_percent(collateral: u128, market_value: u128) -> f64 {
+    if market_value == 0 {
+        return 0.0;
+    }
+    (collateral as f64 / market_value as f64 - 1.0) * 100.0
+}
+
+/// Report the tokens backed by the liquidation vault and its premium, and burn tokens against
+/// it if the premium is within the limits. Returns the reported amount of tokens.
+async fn check_liquidation_vault(
+    parachain_rpc: &InterBtcParachain,
+    limits: Option<LiquidationRedeemLimits>,
+    reported_tokens: u128,
+) -> Result<u128, RuntimeError> {
+    let liquidation_vault = parachain_rpc.get_liquidation_vault().await?;
+    let tokens = liquidation_vault.redeemable_tokens();
+    LIQUIDATION_VAULT_TOKENS.set(tokens as i64);
+
+    if tokens == 0 {
+        LIQUIDATION_REDEEM_PREMIUM.set(0.0);
+        return Ok(tokens);
+    }
+
+    // the collateral is shared proportionally, so the premium does not depend on the amount
+    let market_value = parachain_rpc
+        .wrapped_to_collateral(WrappedAmount::from_planck(tokens))
+        .await?
+        .planck();
+    let premium = premium_percent(liquidation_vault.collateral_for(tokens), market_value);
+    LIQUIDATION_REDEEM_PREMIUM.set(premium);
+    if tokens != reported_tokens {
+        tracing::info!(
+            "Liquidation vault backs {} tokens, redeeming them pays a premium of {:.2}%",
+            tokens,
+            premium
+        );
+    }
+
+    if let Some(limits) = limits.filter(|limits| premium >= limits.min_premium_percent as f64) {
+        let amount = parachain_rpc
+            .get_free_wrapped_balance()
+            .await?
+            .min(tokens)
+            .min(limits.max_amount);
+        if amount > 0 {
+            match parachain_rpc.liquidation_redeem(amount).await {
+                Ok(()) => tracing::info!(
+                    "Burned {} tokens against the liquidation vault at a premium of {:.2}%",
+                    amount,
+                    premium
+                ),
+                Err(err) => tracing::warn!("Failed to redeem against the liquidation vault: {}", err),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Periodically report the tokens backed by the liquidation vault and the premium of burning
+/// them for its collateral. If limits are given, the free tokens of this vault's account are
+/// burned whenever the premium is high enough. Failed checks are retried in the next interval,
+/// only a lost connection ends the task so that the service restarts.
+pub async fn watch_liquidation_vault(
+    parachain_rpc: InterBtcParachain,
+    limits: Option<LiquidationRedeemLimits>,
+) -> Result<(), ServiceError> {
+    let mut reported_tokens = 0;
+    loop {
+        match check_liquidation_vault(&parachain_rpc, limits, reported_tokens).await {
+            Ok(tokens) => reported_tokens = tokens,
+            Err(err) if err.is_rpc_disconnect_error() => return Err(err.into()),
+            Err(err) => tracing::warn!("Failed to check the liquidation vault, retrying: {}", err),
+        }
+        delay_for(degradation::stretch(LIQUIDATION_POLL_INTERVAL)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_premium_percent() {
+        assert!((premium_percent(110, 100) - 10.0).abs() < 1e-9);
+        assert!(premium_percent(90, 100) < 0.0);
+        assert_eq!(premium_percent(100, 0), 0.0);
+    }
+}
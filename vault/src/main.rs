@@ -1,8 +1,17 @@
 use clap::Clap;
-use runtime::{substrate_subxt::PairSigner, InterBtcRuntime};
+use runtime::{
+    substrate_subxt::{PairSigner, Signer},
+    InterBtcRuntime,
+};
 use service::{ConnectionManager, ServiceConfig};
 
-use vault::{Error, VaultService, VaultServiceConfig, ABOUT, AUTHORS, NAME, VERSION};
+use serde::Serialize;
+use std::{path::PathBuf, str::FromStr};
+use vault::{
+    collect_appeal_info, collect_request_history, export_snapshot, import_snapshot, plan_recovery,
+    start_metrics_server, Error, Heartbeat, RetirementDeadline, RetirementPlan, Snapshot, VaultService,
+    VaultServiceConfig, ABOUT, AUTHORS, NAME, VERSION,
+};
 
 #[derive(Clap, Debug, Clone)]
 #[clap(name = NAME, version = VERSION, author = AUTHORS, about = ABOUT)]
@@ -26,15 +35,289 @@ pub struct Opts {
     /// General service settings.
     #[clap(flatten)]
     pub service: ServiceConfig,
+
+    /// Output format of the subcommands, `text` or `json`. With `json`, the result (or the error,
+    /// as `{"error": <message>}`) is written to stdout as a single JSON document and the logs are
+    /// written to stderr.
+    #[clap(long, default_value = "text")]
+    pub output: OutputFormat,
+
+    #[clap(subcommand)]
+    pub subcmd: Option<SubCommand>,
 }
 
-async fn start() -> Result<(), Error> {
-    let opts: Opts = Opts::parse();
-    opts.service.logging_format.init_subscriber();
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("expected text or json, got {}", src)),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Print the result of a subcommand: pretty-printed for humans, on a single line otherwise.
+    fn print<T: Serialize>(&self, result: &T) -> Result<(), Error> {
+        match self {
+            OutputFormat::Text => println!("{}", serde_json::to_string_pretty(result)?),
+            OutputFormat::Json => println!("{}", serde_json::to_string(result)?),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorResult {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct SnapshotExportResult {
+    output: PathBuf,
+}
+
+#[derive(Serialize)]
+struct SnapshotImportResult {
+    /// Height from which the wallet was rescanned.
+    rescan_start_height: u32,
+}
+
+#[derive(Clap, Debug, Clone)]
+pub enum SubCommand {
+    /// Export or import the operational state of the vault.
+    Snapshot(SnapshotOpts),
+    /// Collect the evidence needed to appeal a theft report against this vault.
+    AppealInfo(AppealInfoOpts),
+    /// Run the vault and replace all issued tokens before the deadline, then withdraw the
+    /// collateral. Stop any other instance of the vault first.
+    Retire(RetireOpts),
+    /// Rescan the bitcoin chain since the last heartbeat and show what happened while the
+    /// vault was offline, and the actions taken when it resumes. Requires `--heartbeat-file`.
+    RecoveryPlan,
+    /// List the issue, redeem and replace requests of the vault, with the bitcoin transactions
+    /// of the wallet that pay for them.
+    RequestHistory,
+}
+
+#[derive(Clap, Debug, Clone)]
+pub struct AppealInfoOpts {
+    /// Id of a transaction reported as theft, may be given multiple times.
+    #[clap(long)]
+    pub txid: Vec<bitcoin::Txid>,
+}
+
+#[derive(Clap, Debug, Clone)]
+pub struct RetireOpts {
+    /// Date (YYYY-MM-DD) or RFC 3339 timestamp by which all tokens should be replaced.
+    #[clap(long)]
+    pub deadline: RetirementDeadline,
+
+    /// Amount of the first replace request, all issued tokens if not set.
+    #[clap(long)]
+    pub replace_chunk: Option<u128>,
+
+    /// Griefing collateral offered with each replace request, in percent of the
+    /// collateral required for the replaced amount.
+    #[clap(long, default_value = "10")]
+    pub griefing_collateral_percent: u128,
+
+    /// Send the remaining bitcoin to this address once all tokens are replaced.
+    #[clap(long)]
+    pub sweep_address: Option<String>,
+}
+
+#[derive(Clap, Debug, Clone)]
+pub struct SnapshotOpts {
+    #[clap(subcommand)]
+    pub action: SnapshotAction,
+}
+
+#[derive(Clap, Debug, Clone)]
+pub enum SnapshotAction {
+    /// Write a snapshot of the vault state to a new file.
+    Export {
+        #[clap(long)]
+        output: PathBuf,
+    },
+    /// Restore the vault state from a snapshot file.
+    Import {
+        #[clap(long)]
+        input: PathBuf,
+    },
+}
+
+async fn run_snapshot(
+    opts: Opts,
+    signer: runtime::InterBtcSigner,
+    wallet_name: String,
+    action: SnapshotAction,
+) -> Result<(), Error> {
+    let bitcoin_core = opts.bitcoin.new_client(Some(wallet_name))?;
+    bitcoin_core.connect().await?;
+    let parachain_rpc = opts.parachain.try_connect(signer).await?;
+
+    match action {
+        SnapshotAction::Export { output } => {
+            let snapshot = export_snapshot(&parachain_rpc, &bitcoin_core, &opts.vault).await?;
+            snapshot.write(&output)?;
+            match opts.output {
+                OutputFormat::Text => tracing::info!("Exported snapshot to {}", output.display()),
+                OutputFormat::Json => opts.output.print(&SnapshotExportResult { output })?,
+            }
+        }
+        SnapshotAction::Import { input } => {
+            let snapshot = Snapshot::read(&input)?;
+            let height = import_snapshot(&parachain_rpc, &bitcoin_core, &opts.vault, snapshot).await?;
+            match opts.output {
+                OutputFormat::Text => tracing::info!(
+                    "Imported snapshot, start the vault with --bitcoin-rescan-start-height {} to skip rescanning",
+                    height
+                ),
+                OutputFormat::Json => opts.output.print(&SnapshotImportResult {
+                    rescan_start_height: height,
+                })?,
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_appeal_info(
+    opts: Opts,
+    signer: runtime::InterBtcSigner,
+    wallet_name: String,
+    appeal_opts: AppealInfoOpts,
+) -> Result<(), Error> {
+    let bitcoin_core = opts.bitcoin.new_client(Some(wallet_name))?;
+    bitcoin_core.connect().await?;
+    let parachain_rpc = opts.parachain.try_connect(signer).await?;
+
+    let appeal_info = collect_appeal_info(&parachain_rpc, &bitcoin_core, &appeal_opts.txid).await?;
+    opts.output.print(&appeal_info)
+}
+
+async fn run_recovery_plan(opts: Opts, signer: runtime::InterBtcSigner, wallet_name: String) -> Result<(), Error> {
+    let heartbeat = match &opts.vault.heartbeat_file {
+        Some(path) => Heartbeat::read(path)?.ok_or(Error::MissingHeartbeat)?,
+        None => return Err(Error::MissingHeartbeat),
+    };
+    let bitcoin_core = opts.bitcoin.new_client(Some(wallet_name))?;
+    bitcoin_core.connect().await?;
+    let parachain_rpc = opts.parachain.try_connect(signer).await?;
+
+    let plan = plan_recovery(&parachain_rpc, &bitcoin_core, heartbeat).await?;
+    opts.output.print(&plan)
+}
+
+async fn run_request_history(opts: Opts, signer: runtime::InterBtcSigner, wallet_name: String) -> Result<(), Error> {
+    let bitcoin_core = opts.bitcoin.new_client(Some(wallet_name))?;
+    bitcoin_core.connect().await?;
+    let parachain_rpc = opts.parachain.try_connect(signer).await?;
+
+    let history = collect_request_history(&parachain_rpc, &bitcoin_core).await?;
+    opts.output.print(&history)
+}
+
+async fn start(mut opts: Opts) -> Result<(), Error> {
+    if opts.subcmd.is_some() && opts.output == OutputFormat::Json {
+        opts.service.logging_format.init_stderr_subscriber();
+    } else {
+        opts.service.logging_format.init_subscriber();
+    }
+
+    // `--network` used to set the bitcoin network, which it still does for the bitcoin network names
+    if let Some(network) = opts.parachain.deprecated_bitcoin_network() {
+        tracing::warn!(
+            "`--network {}` is deprecated, use `--bitcoin-network {}` instead, `--network` selects the network profile",
+            network,
+            network
+        );
+        if opts.bitcoin.bitcoin_network.is_none() {
+            opts.bitcoin.bitcoin_network = Some(network.parse()?);
+        }
+    }
+
+    // settings not given explicitly default to those of the network profile
+    if let Some(profile) = opts.parachain.network_profile() {
+        if profile.name == "testnet" && opts.parachain.btc_parachain_url.is_none() {
+            // the bitcoin network `testnet` is now the profile, which connects to another parachain
+            tracing::warn!(
+                "`--network testnet` selects the testnet profile and connects to {}, use `--bitcoin-network testnet` \
+                 to only set the bitcoin network",
+                profile.parachain_url
+            );
+        }
+        if opts.bitcoin.bitcoin_network.is_none() {
+            opts.bitcoin.bitcoin_network = Some(profile.bitcoin_network.parse()?);
+        }
+        opts.vault
+            .bitcoin_relay_esplora_url
+            .get_or_insert_with(|| profile.electrs_url.to_string());
+    }
+
+    // the tunnel hides the .onion address, so decide on the Tor timeouts beforehand
+    opts.bitcoin.bitcoin_tor |= bitcoin::is_onion_url(&opts.bitcoin.bitcoin_rpc_url);
+
+    // connect through the local end of the proxy tunnels, if any
+    let (parachain_url, bitcoin_url) = opts
+        .service
+        .route_through_proxies(&opts.parachain.parachain_url(), &opts.bitcoin.bitcoin_rpc_url)
+        .await?;
+    opts.parachain.btc_parachain_url = Some(parachain_url);
+    opts.bitcoin.bitcoin_rpc_url = bitcoin_url;
+    if let Some(esplora_url) = &opts.vault.bitcoin_relay_esplora_url {
+        opts.vault.bitcoin_relay_esplora_url =
+            Some(service::route_through_proxy(opts.service.bitcoin_proxy(), esplora_url).await?);
+    }
 
     let (pair, wallet_name) = opts.account_info.get_key_pair()?;
     let signer = PairSigner::<InterBtcRuntime, _>::new(pair);
 
+    match opts.subcmd.clone() {
+        Some(SubCommand::Snapshot(snapshot_opts)) => {
+            return run_snapshot(opts, signer, wallet_name.to_string(), snapshot_opts.action).await;
+        }
+        Some(SubCommand::AppealInfo(appeal_opts)) => {
+            return run_appeal_info(opts, signer, wallet_name.to_string(), appeal_opts).await;
+        }
+        Some(SubCommand::Retire(retire_opts)) => {
+            // retire within the service, so that it uses its signer and pays for the replaces
+            opts.vault.retirement = Some(RetirementPlan {
+                deadline: retire_opts.deadline,
+                initial_chunk: retire_opts.replace_chunk,
+                griefing_collateral_percent: retire_opts.griefing_collateral_percent,
+                sweep_address: retire_opts.sweep_address,
+            });
+        }
+        Some(SubCommand::RecoveryPlan) => {
+            return run_recovery_plan(opts, signer, wallet_name.to_string()).await;
+        }
+        Some(SubCommand::RequestHistory) => {
+            return run_request_history(opts, signer, wallet_name.to_string()).await;
+        }
+        None => {}
+    }
+
+    if let Some(addr) = opts.vault.prometheus_addr {
+        // metrics outlive service restarts, so serve them independently
+        let vault_id = signer.account_id().clone();
+        let network = opts.bitcoin.network();
+        tokio::spawn(async move {
+            if let Err(err) = start_metrics_server(addr, vault_id, network).await {
+                tracing::error!("Metrics server stopped: {}", err);
+            }
+        });
+    }
+
     ConnectionManager::<_, VaultService>::new(
         signer.clone(),
         Some(wallet_name.to_string()),
@@ -51,8 +334,14 @@ async fn start() -> Result<(), Error> {
 
 #[tokio::main]
 async fn main() {
-    let exit_code = if let Err(err) = start().await {
-        eprintln!("Error: {}", err);
+    let opts: Opts = Opts::parse();
+    // only the subcommands produce json output, the service logs its errors
+    let json_output = opts.subcmd.is_some() && opts.output == OutputFormat::Json;
+    let exit_code = if let Err(err) = start(opts).await {
+        let result = ErrorResult { error: err.to_string() };
+        if !json_output || OutputFormat::Json.print(&result).is_err() {
+            eprintln!("Error: {}", err);
+        }
         1
     } else {
         0
@@ -0,0 +1,319 @@
+use crate::{deposit_collateral, retire::stop_new_issues, Error};
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use lazy_static::lazy_static;
+use prometheus::IntGauge;
+use runtime::{CollateralAmount, InterBtcParachain, ReplacePallet, UtilFuncs, VaultRegistryPallet};
+use service::Error as ServiceError;
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tokio::time::delay_for;
+
+/// Interval at which the maintenance schedule is checked.
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    pub static ref DRAIN_MODE: IntGauge = IntGauge::new(
+        "drain_mode",
+        "Set to 1 while the vault drains ahead of, or during, a maintenance window"
+    )
+    .expect("Failed to create prometheus metric");
+}
+
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+/// True while the vault drains for maintenance, no new requests are taken on.
+pub fn is_draining() -> bool {
+    DRAINING.load(Ordering::Relaxed)
+}
+
+fn set_draining(draining: bool) {
+    DRAINING.store(draining, Ordering::Relaxed);
+    DRAIN_MODE.set(draining as i64);
+}
+
+/// Recurring maintenance window in UTC, daily (`02:00-04:00`) or on one day of the week
+/// (`sun 02:00-04:00`). The window may cross midnight, e.g. `23:30-00:30`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaintenanceWindow {
+    pub weekday: Option<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected [DAY ]HH:MM-HH:MM, got {}", src);
+        let (weekday, times) = match src.trim().splitn(2, ' ').collect::<Vec<_>>().as_slice() {
+            [times] => (None, *times),
+            [weekday, times] => (Some(weekday.parse::<Weekday>().map_err(|_| invalid())?), *times),
+            _ => return Err(invalid()),
+        };
+        let (start, end) = match times.splitn(2, '-').collect::<Vec<_>>().as_slice() {
+            [start, end] => (
+                NaiveTime::parse_from_str(start.trim(), "%H:%M").map_err(|_| invalid())?,
+                NaiveTime::parse_from_str(end.trim(), "%H:%M").map_err(|_| invalid())?,
+            ),
+            _ => return Err(invalid()),
+        };
+        if start == end {
+            return Err(format!("maintenance window {} is empty", src));
+        }
+        Ok(Self { weekday, start, end })
+    }
+}
+
+impl MaintenanceWindow {
+    /// The start and end of the occurrence that is in progress at `now`, or else of the next one.
+    fn next_occurrence(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let length = if self.end > self.start {
+            self.end - self.start
+        } else {
+            self.end - self.start + chrono::Duration::days(1)
+        };
+        // starting from the day before, which may still be in progress if it crosses midnight
+        (-1..=7)
+            .map(|days| now.date() + chrono::Duration::days(days))
+            .filter(|date| self.weekday.map_or(true, |weekday| date.weekday() == weekday))
+            .filter_map(|date| date.and_time(self.start))
+            .map(|start| (start, start + length))
+            .find(|(_, end)| *end > now)
+            .expect("every window occurs within a week")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaintenancePhase {
+    Normal,
+    /// Within the lead time before the window: the vault takes on no new requests, but
+    /// keeps processing its open ones.
+    Draining {
+        starts: DateTime<Utc>,
+    },
+    InWindow {
+        ends: DateTime<Utc>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MaintenanceSchedule {
+    pub windows: Vec<MaintenanceWindow>,
+    /// Time before each window at which the vault starts draining.
+    pub lead_time: Duration,
+    /// File in which the collateral withdrawn for the maintenance is kept, so that it is
+    /// deposited again if the vault is restarted during the window.
+    pub state_file: PathBuf,
+}
+
+impl MaintenanceSchedule {
+    pub fn phase(&self, now: DateTime<Utc>) -> MaintenancePhase {
+        let lead_time = chrono::Duration::from_std(self.lead_time).unwrap_or_else(|_| chrono::Duration::zero());
+        let mut phase = MaintenancePhase::Normal;
+        for (start, end) in self.windows.iter().map(|window| window.next_occurrence(now)) {
+            if start <= now {
+                return MaintenancePhase::InWindow { ends: end };
+            }
+            match phase {
+                MaintenancePhase::Draining { starts } if starts <= start => {}
+                _ if start - lead_time <= now => phase = MaintenancePhase::Draining { starts: start },
+                _ => {}
+            }
+        }
+        phase
+    }
+
+    /// The collateral withdrawn for the maintenance, zero if the file does not exist. Failing
+    /// to read it is an error, since the collateral would otherwise not be deposited again.
+    fn read_withdrawn(&self) -> Result<CollateralAmount, Error> {
+        match std::fs::read_to_string(&self.state_file) {
+            Ok(contents) => contents.trim().parse().map(CollateralAmount::from_planck).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid withdrawn collateral in {}", self.state_file.display()),
+                )
+                .into()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(CollateralAmount::from_planck(0)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write_withdrawn(&self, amount: CollateralAmount) {
+        let tmp_path = self.state_file.with_extension("tmp");
+        // the amount is kept in planck
+        if let Err(err) = std::fs::write(&tmp_path, amount.planck().to_string())
+            .and_then(|_| std::fs::rename(&tmp_path, &self.state_file))
+        {
+            tracing::error!(
+                "Failed to record the withdrawn collateral of {} in {}: {}",
+                amount,
+                self.state_file.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Wind down the obligations of the vault ahead of the window: its replace requests that no
+/// vault accepted yet are withdrawn, since accepting them during the window would require
+/// the vault to pay. Returns the tokens still in open requests, which complete as usual.
+async fn finish_obligations(parachain_rpc: &InterBtcParachain) -> Result<u128, Error> {
+    let vault = parachain_rpc.get_vault(parachain_rpc.get_account_id().clone()).await?;
+    if vault.to_be_replaced_tokens > 0 {
+        tracing::info!(
+            "Withdrawing the replace request for {} tokens ahead of the maintenance",
+            vault.to_be_replaced_tokens
+        );
+        parachain_rpc.withdraw_replace(vault.to_be_replaced_tokens).await?;
+    }
+    Ok(vault.to_be_issued_tokens.saturating_add(vault.to_be_redeemed_tokens))
+}
+
+/// Drain the vault ahead of each maintenance window and resume after it. When draining
+/// starts, the collateral not backing issued tokens is withdrawn once, keeping a margin,
+/// so that few new issues can be requested, and replace requests are not accepted. While
+/// draining, the unaccepted replace requests of the vault are withdrawn and its open
+/// requests complete. After the window, the withdrawn collateral is deposited again.
+pub async fn run_maintenance_schedule(
+    parachain_rpc: InterBtcParachain,
+    schedule: MaintenanceSchedule,
+) -> Result<(), ServiceError> {
+    let mut withdrawn = schedule
+        .read_withdrawn()
+        .map_err(|err| ServiceError::Other(err.to_string()))?;
+    // whether the excess collateral was withdrawn for the current window
+    let mut drained = false;
+    let mut last_phase = None;
+    let mut reported_obligations = None;
+    loop {
+        let phase = schedule.phase(Utc::now());
+        if last_phase != Some(phase) {
+            match phase {
+                MaintenancePhase::Normal if is_draining() => tracing::info!("Maintenance ended, resuming"),
+                MaintenancePhase::Normal => {}
+                MaintenancePhase::Draining { starts } => {
+                    tracing::info!("Entering drain mode ahead of the maintenance at {}", starts)
+                }
+                MaintenancePhase::InWindow { ends } => tracing::info!("In maintenance window until {}", ends),
+            }
+            last_phase = Some(phase);
+        }
+
+        match phase {
+            MaintenancePhase::Normal => {
+                set_draining(false);
+                drained = false;
+                reported_obligations = None;
+                if !withdrawn.is_zero() {
+                    match deposit_collateral(&parachain_rpc, withdrawn).await {
+                        Ok(()) => {
+                            withdrawn = CollateralAmount::from_planck(0);
+                            schedule.write_withdrawn(withdrawn);
+                        }
+                        Err(err) => {
+                            tracing::error!("Failed to deposit the collateral withdrawn for maintenance: {}", err)
+                        }
+                    }
+                }
+            }
+            MaintenancePhase::Draining { .. } | MaintenancePhase::InWindow { .. } => {
+                set_draining(true);
+                if !drained {
+                    match stop_new_issues(&parachain_rpc).await {
+                        Ok(excess) => {
+                            if !excess.is_zero() {
+                                withdrawn = withdrawn.saturating_add(excess);
+                                schedule.write_withdrawn(withdrawn);
+                            }
+                            drained = true;
+                        }
+                        Err(err) => tracing::error!("Failed to withdraw excess collateral for maintenance: {}", err),
+                    }
+                }
+                match finish_obligations(&parachain_rpc).await {
+                    Ok(open) if reported_obligations != Some(open) => {
+                        if open == 0 {
+                            tracing::info!("All open requests are completed, the vault is ready for maintenance");
+                        } else if matches!(phase, MaintenancePhase::InWindow { .. }) {
+                            tracing::warn!("Maintenance started with {} tokens in open requests", open);
+                        } else {
+                            tracing::info!("Waiting for {} tokens in open requests to complete", open);
+                        }
+                        reported_obligations = Some(open);
+                    }
+                    Ok(_) => {}
+                    Err(err) => tracing::error!("Failed to check the open requests: {}", err),
+                }
+            }
+        }
+
+        delay_for(SCHEDULE_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2021-06-06 is a sunday
+        Utc.ymd(2021, 6, day).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(
+            "sun 02:00-04:00".parse::<MaintenanceWindow>().unwrap(),
+            MaintenanceWindow {
+                weekday: Some(Weekday::Sun),
+                start: NaiveTime::from_hms(2, 0, 0),
+                end: NaiveTime::from_hms(4, 0, 0),
+            }
+        );
+        assert_eq!("23:30-00:30".parse::<MaintenanceWindow>().unwrap().weekday, None);
+        assert!("02:00-02:00".parse::<MaintenanceWindow>().is_err());
+        assert!("someday 02:00-04:00".parse::<MaintenanceWindow>().is_err());
+        assert!("02:00".parse::<MaintenanceWindow>().is_err());
+    }
+
+    #[test]
+    fn test_phase() {
+        let schedule = MaintenanceSchedule {
+            windows: vec!["sun 02:00-04:00".parse().unwrap()],
+            lead_time: Duration::from_secs(2 * 60 * 60),
+            state_file: PathBuf::new(),
+        };
+        assert_eq!(schedule.phase(at(5, 23, 0)), MaintenancePhase::Normal);
+        assert_eq!(
+            schedule.phase(at(6, 0, 30)),
+            MaintenancePhase::Draining { starts: at(6, 2, 0) }
+        );
+        assert_eq!(
+            schedule.phase(at(6, 3, 0)),
+            MaintenancePhase::InWindow { ends: at(6, 4, 0) }
+        );
+        assert_eq!(schedule.phase(at(6, 4, 0)), MaintenancePhase::Normal);
+
+        // daily window crossing midnight
+        let schedule = MaintenanceSchedule {
+            windows: vec!["23:30-00:30".parse().unwrap()],
+            lead_time: Duration::from_secs(30 * 60),
+            state_file: PathBuf::new(),
+        };
+        assert_eq!(
+            schedule.phase(at(7, 0, 15)),
+            MaintenancePhase::InWindow { ends: at(7, 0, 30) }
+        );
+        assert_eq!(
+            schedule.phase(at(7, 23, 0)),
+            MaintenancePhase::Draining { starts: at(7, 23, 30) }
+        );
+        assert_eq!(schedule.phase(at(7, 12, 0)), MaintenancePhase::Normal);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{
+    analytics::ANALYTICS_EVENTS,
+    degradation::{DEGRADED_MODE, RPC_LATENCY},
+    deposit_uri::{self, deposit_uri},
+    error::Error,
+    exposure::{self, OVER_LIMIT_ACCOUNTS},
+    latency::REQUEST_LATENCY,
+    liquidation::{LIQUIDATION_REDEEM_PREMIUM, LIQUIDATION_VAULT_TOKENS},
+    maintenance::DRAIN_MODE,
+};
+use bitcoin::Network;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use lazy_static::lazy_static;
+use prometheus::{Encoder, Gauge, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use runtime::{AccountId, COLLATERAL_CURRENCY, WRAPPED_CURRENCY};
+use sp_core::crypto::Ss58Codec;
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr};
+
+lazy_static! {
+    pub static ref QUEUED_TASKS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("queued_tasks", "Number of tasks waiting for a free execution slot"),
+        &["task"]
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref RUNNING_TASKS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("running_tasks", "Number of tasks currently executing"),
+        &["task"]
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref ISSUE_PAYMENT_DISCREPANCIES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "issue_payment_discrepancies",
+            "Number of issue payments whose amount differs from the requested amount"
+        ),
+        &["kind"]
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref ORACLE_STALE: IntGauge = IntGauge::new(
+        "oracle_stale",
+        "Set to 1 if the exchange rate has not been updated within the staleness threshold"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref TIMESTAMP_DRIFTING: IntGauge = IntGauge::new(
+        "timestamp_drifting",
+        "Set to 1 if the parachain time drifts from the local time by more than the threshold"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref WALLET_BALANCE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "wallet_balance",
+            "Balance of the bitcoin wallet in satoshis, split by status and purpose"
+        ),
+        &["kind"]
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref WALLET_RESCAN_PROGRESS: Gauge = Gauge::new(
+        "wallet_rescan_progress",
+        "Fraction of the chain scanned by the running wallet rescan, 0 if no rescan is running"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref FEE_RESERVE_SHORTFALL: IntGauge = IntGauge::new(
+        "fee_reserve_shortfall",
+        "Set to 1 if the float would not cover the fees of all outstanding payments in a fee spike"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref THEFT_FLAGGED: IntGauge = IntGauge::new(
+        "theft_flagged",
+        "Set to 1 if this vault has been flagged for theft by the parachain"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref PENDING_APPROVALS: IntGauge = IntGauge::new(
+        "pending_payment_approvals",
+        "Number of payments waiting for approval by the operator"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref APPROVAL_TIMEOUTS: IntCounter = IntCounter::new(
+        "payment_approval_timeouts",
+        "Number of payments aborted because they were not approved in time"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref EXTERNAL_SPENDS: IntCounter = IntCounter::new(
+        "external_spends",
+        "Number of wallet outputs spent in the mempool by transactions not created by this vault"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref DEPOSIT_ADDRESS_MISMATCHES: IntCounter = IntCounter::new(
+        "deposit_address_mismatches",
+        "Number of issue requests not executed automatically because the deposit address was not derived from the vault's key"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref IS_LEADER: IntGauge = IntGauge::new(
+        "is_leader",
+        "Set to 1 if this instance holds the leader lease and may spend bitcoin"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref BAN_BLOCKS_REMAINING: IntGauge = IntGauge::new(
+        "ban_blocks_remaining",
+        "Number of active blocks until the ban of this vault lapses, 0 if not banned"
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref VAULT_TOKENS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("vault_tokens", "Wrapped tokens of this vault in satoshis, by state"),
+        &["kind"]
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref ACCOUNT_BALANCE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "account_balance",
+            "Balance of the vault account on the parachain, by currency and status"
+        ),
+        &["currency", "status"]
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref VAULT_COLLATERAL: IntGauge = IntGauge::new("vault_collateral", "Collateral locked by this vault")
+        .expect("Failed to create prometheus metric");
+    pub static ref TOTAL_TOKENS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "total_tokens",
+            "Wrapped tokens of all vaults in satoshis, by state. Issued tokens equal the total BTC locked"
+        ),
+        &["kind"]
+    )
+    .expect("Failed to create prometheus metric");
+    pub static ref TOTAL_COLLATERAL: IntGauge = IntGauge::new("total_collateral", "Collateral locked by all vaults")
+        .expect("Failed to create prometheus metric");
+}
+
+pub(crate) fn currency_label(currency: runtime::CurrencyId) -> String {
+    format!("{:?}", currency).to_lowercase()
+}
+
+/// Labels attached to every metric, so that operators running multiple vaults
+/// can break down and aggregate them per vault and market.
+fn const_labels(vault_id: &AccountId) -> HashMap<String, String> {
+    let mut labels = HashMap::new();
+    labels.insert("vault_id".to_string(), vault_id.to_ss58check());
+    labels.insert("collateral_currency".to_string(), currency_label(COLLATERAL_CURRENCY));
+    labels.insert("wrapped_currency".to_string(), currency_label(WRAPPED_CURRENCY));
+    labels
+}
+
+fn register_custom_metrics(registry: &Registry) -> Result<(), prometheus::Error> {
+    registry.register(Box::new(QUEUED_TASKS.clone()))?;
+    registry.register(Box::new(RUNNING_TASKS.clone()))?;
+    registry.register(Box::new(ISSUE_PAYMENT_DISCREPANCIES.clone()))?;
+    registry.register(Box::new(ORACLE_STALE.clone()))?;
+    registry.register(Box::new(TIMESTAMP_DRIFTING.clone()))?;
+    registry.register(Box::new(WALLET_BALANCE.clone()))?;
+    registry.register(Box::new(WALLET_RESCAN_PROGRESS.clone()))?;
+    registry.register(Box::new(FEE_RESERVE_SHORTFALL.clone()))?;
+    registry.register(Box::new(THEFT_FLAGGED.clone()))?;
+    registry.register(Box::new(EXTERNAL_SPENDS.clone()))?;
+    registry.register(Box::new(DEPOSIT_ADDRESS_MISMATCHES.clone()))?;
+    registry.register(Box::new(PENDING_APPROVALS.clone()))?;
+    registry.register(Box::new(APPROVAL_TIMEOUTS.clone()))?;
+    registry.register(Box::new(IS_LEADER.clone()))?;
+    registry.register(Box::new(BAN_BLOCKS_REMAINING.clone()))?;
+    registry.register(Box::new(VAULT_TOKENS.clone()))?;
+    registry.register(Box::new(ACCOUNT_BALANCE.clone()))?;
+    registry.register(Box::new(VAULT_COLLATERAL.clone()))?;
+    registry.register(Box::new(TOTAL_TOKENS.clone()))?;
+    registry.register(Box::new(TOTAL_COLLATERAL.clone()))?;
+    registry.register(Box::new(REQUEST_LATENCY.clone()))?;
+    registry.register(Box::new(RPC_LATENCY.clone()))?;
+    registry.register(Box::new(DEGRADED_MODE.clone()))?;
+    registry.register(Box::new(DRAIN_MODE.clone()))?;
+    registry.register(Box::new(OVER_LIMIT_ACCOUNTS.clone()))?;
+    registry.register(Box::new(ANALYTICS_EVENTS.clone()))?;
+    registry.register(Box::new(LIQUIDATION_VAULT_TOKENS.clone()))?;
+    registry.register(Box::new(LIQUIDATION_REDEEM_PREMIUM.clone()))?;
+    registry.register(Box::new(runtime::TIPS_SPENT.clone()))?;
+    registry.register(Box::new(runtime::DRY_RUN_FAILURES.clone()))?;
+    registry.register(Box::new(runtime::EXTRINSIC_FEES.clone()))?;
+    registry.register(Box::new(runtime::BLOCK_LATENCY.clone()))?;
+    registry.register(Box::new(runtime::TIMESTAMP_DRIFT.clone()))?;
+    registry.register(Box::new(runtime::MISSED_BLOCKS.clone()))?;
+    registry.register(Box::new(runtime::CHAIN_LAG.clone()))?;
+    registry.register(Box::new(runtime::CALL_RETRIES.clone()))?;
+    registry.register(Box::new(runtime::POOL_CONFLICTS.clone()))?;
+    registry.register(Box::new(runtime::CALL_LATENCY.clone()))?;
+    registry.register(Box::new(bitcoin::REJECTED_PAYMENTS.clone()))?;
+    registry.register(Box::new(bitcoin::HEADER_STORE_LOOKUPS.clone()))?;
+    registry.register(Box::new(bitcoin::MALLEATED_TRANSACTIONS.clone()))?;
+    Ok(())
+}
+
+/// Serves the BIP21 URI of an open issue request at `/issues/<id>/payment-uri`, e.g. for
+/// integrators to show a QR code, the outstanding amounts by account at `/exposure`, and
+/// the metrics on any other path.
+async fn metrics_handler(
+    registry: Registry,
+    network: Network,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if let Some(issue_id) = deposit_uri::parse_path(req.uri().path()) {
+        let response = match deposit_uri(&issue_id, network) {
+            Some(uri) => Response::builder()
+                .header(CONTENT_TYPE, "text/plain")
+                .body(Body::from(uri)),
+            None => Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()),
+        };
+        return Ok(response.unwrap_or_default());
+    }
+    if req.uri().path() == "/exposure" {
+        let response = match serde_json::to_vec(&exposure::concentration()) {
+            Ok(body) => Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body)),
+            Err(err) => {
+                tracing::error!("Failed to encode exposure: {}", err);
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::empty())
+            }
+        };
+        return Ok(response.unwrap_or_default());
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(err) = encoder.encode(&registry.gather(), &mut buffer) {
+        tracing::error!("Failed to encode metrics: {}", err);
+    }
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, encoder.format_type())
+        .body(Body::from(buffer))
+        .unwrap_or_default())
+}
+
+/// Serve the prometheus metrics of this vault on the given address.
+pub async fn start_metrics_server(addr: SocketAddr, vault_id: AccountId, network: Network) -> Result<(), Error> {
+    let registry = Registry::new_custom(None, Some(const_labels(&vault_id)))?;
+    register_custom_metrics(&registry)?;
+    tracing::info!("Serving metrics on {}", addr);
+    let make_svc = make_service_fn(move |_conn| {
+        let registry = registry.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| metrics_handler(registry.clone(), network, req))) }
+    });
+    Server::bind(&addr).serve(make_svc).await?;
+    Ok(())
+}
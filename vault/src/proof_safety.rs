@@ -0,0 +1,125 @@
+use crate::Error;
+use bitcoin::{BitcoinCoreApi, BlockHash};
+use runtime::BtcRelayPallet;
+use std::{str::FromStr, time::Duration};
+use tokio::time::{delay_for, timeout};
+
+const SAFETY_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time after which `wait_until_safe` gives up on the payment reaching the required depth.
+const SAFETY_TIMEOUT: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Minimum depth required of payments of at least the given amount (in satoshi), parsed
+/// from `<amount>=<depth>`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthOverride {
+    pub min_amount: u128,
+    pub depth: u32,
+}
+
+impl FromStr for DepthOverride {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut parts = src.splitn(2, '=');
+        let (min_amount, depth) = match (parts.next(), parts.next()) {
+            (Some(min_amount), Some(depth)) => (min_amount, depth),
+            _ => return Err(format!("expected <amount>=<depth>, got {}", src)),
+        };
+        Ok(Self {
+            min_amount: min_amount.trim().parse().map_err(|err| format!("{}", err))?,
+            depth: depth.trim().parse().map_err(|err| format!("{}", err))?,
+        })
+    }
+}
+
+/// Policy guarding the submission of execution proofs against shallow forks: the block
+/// containing the payment must be buried below bitcoind's tip by the required depth and
+/// must not be above the best block of the relay. Disabled if the required depth is zero.
+#[derive(Debug, Clone, Default)]
+pub struct ProofSafety {
+    depth: u32,
+    overrides: Vec<DepthOverride>,
+}
+
+impl ProofSafety {
+    pub fn new(depth: u32, mut overrides: Vec<DepthOverride>) -> Self {
+        overrides.sort_by_key(|depth_override| depth_override.min_amount);
+        Self { depth, overrides }
+    }
+
+    /// Depth required of a payment of the given amount, the override with the highest
+    /// applicable amount takes precedence over the default depth.
+    pub fn required_depth(&self, amount: u128) -> u32 {
+        self.overrides
+            .iter()
+            .rev()
+            .find(|depth_override| amount >= depth_override.min_amount)
+            .map_or(self.depth, |depth_override| depth_override.depth)
+    }
+
+    /// Waits until the block containing the payment satisfies the policy. Fails with
+    /// `PaymentReorganized` if the block is no longer in bitcoind's main chain, in which
+    /// case the transaction metadata needs to be refetched, and with `ProofSafetyTimeout`
+    /// if the policy is not satisfied within `SAFETY_TIMEOUT`.
+    pub async fn wait_until_safe<B: BitcoinCoreApi, P: BtcRelayPallet>(
+        &self,
+        parachain_rpc: &P,
+        btc_rpc: &B,
+        block_height: u32,
+        block_hash: &BlockHash,
+        amount: u128,
+    ) -> Result<(), Error> {
+        let depth = self.required_depth(amount);
+        if depth == 0 {
+            return Ok(());
+        }
+        timeout(SAFETY_TIMEOUT, async {
+            loop {
+                if btc_rpc.get_block_hash(block_height).await? != *block_hash {
+                    return Err(Error::PaymentReorganized);
+                }
+                let btc_tip = btc_rpc.get_block_count().await? as u32;
+                let relay_best = parachain_rpc.get_best_block_height().await?;
+                if is_safe(block_height, depth, btc_tip, relay_best) {
+                    return Ok(());
+                }
+                tracing::info!(
+                    "Payment in block {} is not yet {} blocks below the tip {} and at or below the relay's best block {}",
+                    block_height,
+                    depth,
+                    btc_tip,
+                    relay_best
+                );
+                delay_for(SAFETY_POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .map_err(|_| Error::ProofSafetyTimeout)?
+    }
+}
+
+fn is_safe(block_height: u32, depth: u32, btc_tip: u32, relay_best: u32) -> bool {
+    btc_tip.saturating_sub(block_height) >= depth && block_height <= relay_best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_depth() {
+        let policy = ProofSafety::new(2, vec!["100000000=6".parse().unwrap(), "1000000=3".parse().unwrap()]);
+        assert_eq!(policy.required_depth(999_999), 2);
+        assert_eq!(policy.required_depth(1_000_000), 3);
+        assert_eq!(policy.required_depth(200_000_000), 6);
+        assert!("6".parse::<DepthOverride>().is_err());
+    }
+
+    #[test]
+    fn test_is_safe() {
+        assert!(is_safe(100, 2, 102, 100));
+        assert!(!is_safe(100, 2, 101, 100));
+        assert!(!is_safe(100, 2, 110, 99));
+    }
+}
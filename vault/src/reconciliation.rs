@@ -0,0 +1,160 @@
+use crate::request_state::{self, RequestState};
+use sp_core::H256;
+use std::fmt;
+
+/// Result of cross-checking the payments in the wallet and the recorded request states
+/// against the open requests on the parachain at startup, grouped by corrective action.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Reconciliation {
+    /// Open requests with a payment in the wallet, the proof is submitted once the payment
+    /// is confirmed.
+    pub paid_unproven: Vec<H256>,
+    /// Open requests without a payment, they are paid and executed.
+    pub unpaid: Vec<H256>,
+    /// Requests recorded as executed that are still open on the parachain, i.e. the
+    /// execution was not finalized. The proof is submitted again.
+    pub executed_still_open: Vec<H256>,
+    /// Requests recorded as executed that were completed on the parachain, the execution was
+    /// finalized while the vault was not running.
+    pub executed_finalized: Vec<H256>,
+    /// Requests recorded as executed that were cancelled on the parachain instead, e.g. a
+    /// redeem that was reimbursed, for which the vault was slashed.
+    pub executed_cancelled: Vec<H256>,
+}
+
+/// Status of a request on the parachain, as far as reconciliation is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnChainStatus {
+    Open,
+    Completed,
+    /// Cancelled, reimbursed or retried, so the execution will never be accepted.
+    Cancelled,
+}
+
+impl Reconciliation {
+    /// Check the requests recorded as executed but not finalized against their status on the
+    /// parachain. Completed requests are marked as finalized, cancelled ones as expired. Requests
+    /// that are not found keep their state.
+    pub(crate) fn check_executed(&mut self, status: impl Fn(&H256) -> Option<OnChainStatus>) {
+        for request_id in request_state::find(|record| record.state == RequestState::Executed) {
+            match status(&request_id) {
+                Some(OnChainStatus::Open) => self.executed_still_open.push(request_id),
+                Some(OnChainStatus::Completed) => {
+                    request_state::transition(request_id, RequestState::Finalized);
+                    self.executed_finalized.push(request_id);
+                }
+                Some(OnChainStatus::Cancelled) => {
+                    request_state::transition(request_id, RequestState::Expired);
+                    self.executed_cancelled.push(request_id);
+                }
+                None => tracing::warn!("Request #{} recorded as executed was not found on chain", request_id),
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paid_unproven.is_empty()
+            && self.unpaid.is_empty()
+            && self.executed_still_open.is_empty()
+            && self.executed_finalized.is_empty()
+            && self.executed_cancelled.is_empty()
+    }
+
+    /// Log the summary, and the affected requests at debug level.
+    pub fn log(&self) {
+        tracing::info!("Reconciliation: {}", self);
+        for (action, request_ids) in &[
+            ("paid, submitting proof", &self.paid_unproven),
+            ("unpaid, paying", &self.unpaid),
+            ("executed but still open, resubmitting proof", &self.executed_still_open),
+            ("executed, marked as finalized", &self.executed_finalized),
+            (
+                "executed but cancelled on chain, marked as expired",
+                &self.executed_cancelled,
+            ),
+        ] {
+            for request_id in request_ids.iter() {
+                tracing::debug!("Request #{} {}", request_id, action);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no open requests");
+        }
+        write!(
+            f,
+            "{} paid but not proven, {} unpaid, {} executed but not finalized ({} still open, {} since finalized, {} cancelled)",
+            self.paid_unproven.len(),
+            self.unpaid.len(),
+            self.executed_still_open.len() + self.executed_finalized.len() + self.executed_cancelled.len(),
+            self.executed_still_open.len(),
+            self.executed_finalized.len(),
+            self.executed_cancelled.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_state::RequestKind;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_check_executed() {
+        let still_open = H256::from_low_u64_be(0x7290_0001);
+        let completed = H256::from_low_u64_be(0x7290_0002);
+        let cancelled = H256::from_low_u64_be(0x7290_0003);
+        let statuses: HashMap<_, _> = vec![
+            (still_open, OnChainStatus::Open),
+            (completed, OnChainStatus::Completed),
+            (cancelled, OnChainStatus::Cancelled),
+        ]
+        .into_iter()
+        .collect();
+        for request_id in statuses.keys() {
+            request_state::seen(*request_id, RequestKind::Redeem);
+            request_state::transition(*request_id, RequestState::Executed);
+        }
+
+        let mut reconciliation = Reconciliation::default();
+        reconciliation.check_executed(|request_id| statuses.get(request_id).copied());
+        assert_eq!(reconciliation.executed_still_open, vec![still_open]);
+        assert_eq!(reconciliation.executed_finalized, vec![completed]);
+        assert_eq!(reconciliation.executed_cancelled, vec![cancelled]);
+
+        let state = |request_id| request_state::get_record(request_id).map(|record| record.state);
+        assert_eq!(state(still_open), Some(RequestState::Executed));
+        assert_eq!(state(completed), Some(RequestState::Finalized));
+        assert_eq!(state(cancelled), Some(RequestState::Expired));
+
+        // not reported again at the next restart
+        let mut reconciliation = Reconciliation::default();
+        reconciliation.check_executed(|request_id| statuses.get(request_id).copied());
+        assert!(reconciliation.executed_cancelled.is_empty());
+
+        for request_id in statuses.keys() {
+            request_state::forget(*request_id);
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(Reconciliation::default().to_string(), "no open requests");
+        let reconciliation = Reconciliation {
+            paid_unproven: vec![H256::from_low_u64_be(1)],
+            unpaid: vec![H256::from_low_u64_be(2), H256::from_low_u64_be(3)],
+            executed_still_open: vec![H256::from_low_u64_be(1)],
+            executed_finalized: vec![],
+            executed_cancelled: vec![H256::from_low_u64_be(4)],
+        };
+        assert_eq!(
+            reconciliation.to_string(),
+            "1 paid but not proven, 2 unpaid, 2 executed but not finalized (1 still open, 0 since finalized, 1 cancelled)"
+        );
+    }
+}
@@ -0,0 +1,482 @@
+use crate::{request_state::RequestKind, Error};
+use bitcoin::{BitcoinCore, BitcoinCoreApi, Txid};
+use futures::try_join;
+use runtime::{
+    InterBtcParachain, IssuePallet, IssueRequestStatus, RedeemPallet, RedeemRequestStatus, RefundPallet, ReplacePallet,
+    ReplaceRequestStatus, SecurityPallet, UtilFuncs, VaultRegistryPallet, VaultStatus,
+};
+use serde::{Deserialize, Serialize};
+use service::Error as ServiceError;
+use sp_core::H256;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::delay_for;
+
+/// Interval at which the heartbeat file is updated.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Point up to which the vault was known to be running, written periodically so that a
+/// prolonged downtime can be detected at the next start.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub bitcoin_height: u32,
+    /// Active block number of the parachain, in which the deadlines of requests are given.
+    pub active_block: u32,
+    /// Unix timestamp in seconds.
+    pub timestamp: u64,
+}
+
+fn replace_file(path: &Path, contents: &str) -> Result<(), Error> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+impl Heartbeat {
+    async fn current(parachain_rpc: &InterBtcParachain, bitcoin_core: &BitcoinCore) -> Result<Self, Error> {
+        Ok(Self {
+            bitcoin_height: bitcoin_core.get_block_count().await? as u32,
+            active_block: parachain_rpc.get_current_active_block_number().await?,
+            timestamp: unix_now(),
+        })
+    }
+
+    /// Read the last heartbeat, `None` if the vault has not written one yet.
+    pub fn read(path: &Path) -> Result<Option<Self>, Error> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Replace the heartbeat file through a temporary file, so that a crash does not leave
+    /// a partially written heartbeat behind.
+    async fn write(self, path: PathBuf) -> Result<(), Error> {
+        let contents = serde_json::to_string(&self)?;
+        tokio::task::spawn_blocking(move || replace_file(&path, &contents)).await?
+    }
+
+    pub fn downtime(&self) -> Duration {
+        Duration::from_secs(unix_now().saturating_sub(self.timestamp))
+    }
+}
+
+/// Periodically record that the vault is running.
+pub async fn write_heartbeats(
+    parachain_rpc: InterBtcParachain,
+    bitcoin_core: BitcoinCore,
+    path: PathBuf,
+) -> Result<(), ServiceError> {
+    loop {
+        let written = match Heartbeat::current(&parachain_rpc, &bitcoin_core).await {
+            Ok(heartbeat) => heartbeat.write(path.clone()).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = written {
+            tracing::warn!("Failed to write heartbeat: {}", err);
+        }
+        delay_for(HEARTBEAT_INTERVAL).await;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Pending,
+    Completed,
+    /// Cancelled, reimbursed or retried, i.e. the vault did not fulfil the request.
+    Failed,
+}
+
+/// Request of the vault as found in the parachain storage.
+#[derive(Debug, Clone)]
+struct Obligation {
+    request_id: H256,
+    kind: RequestKind,
+    outcome: Outcome,
+    /// Active block at which the request was opened, `None` for refunds.
+    opentime: Option<u32>,
+    /// Active block after which the request can be cancelled, `None` if there is no deadline.
+    deadline: Option<u32>,
+}
+
+/// Number of requests of one kind opened during the downtime, by their current status.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RequestSummary {
+    pub opened: usize,
+    pub completed: usize,
+    pub pending: usize,
+    pub failed: usize,
+}
+
+/// Step of the recovery, in the order in which they should be reviewed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RecoveryAction {
+    /// The vault is no longer active, e.g. it was liquidated or reported for theft.
+    ReviewVaultStatus { status: String },
+    /// The request was cancelled while the vault was offline, the collateral was slashed.
+    ReviewSlashed { request_id: H256, kind: RequestKind },
+    /// The payment was made, the proof is submitted once it is confirmed.
+    SubmitProof {
+        request_id: H256,
+        kind: RequestKind,
+        txid: Txid,
+    },
+    /// Unpaid past its deadline, the requester can cancel it and slash the collateral at
+    /// any time. It is paid on resumption.
+    PayOverdue { request_id: H256, kind: RequestKind },
+    /// Unpaid, it is paid on resumption.
+    Pay {
+        request_id: H256,
+        kind: RequestKind,
+        /// Active blocks left until the deadline, `None` if there is no deadline.
+        blocks_left: Option<u32>,
+    },
+}
+
+impl fmt::Display for RecoveryAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecoveryAction::ReviewVaultStatus { status } => write!(f, "review the vault status: {}", status),
+            RecoveryAction::ReviewSlashed { request_id, kind } => {
+                write!(
+                    f,
+                    "review {:?} request #{}: failed while offline, collateral slashed",
+                    kind, request_id
+                )
+            }
+            RecoveryAction::SubmitProof { request_id, kind, txid } => {
+                write!(
+                    f,
+                    "submit proof for {:?} request #{}: paid by {}",
+                    kind, request_id, txid
+                )
+            }
+            RecoveryAction::PayOverdue { request_id, kind } => {
+                write!(
+                    f,
+                    "pay {:?} request #{}: overdue, can be cancelled at any time",
+                    kind, request_id
+                )
+            }
+            RecoveryAction::Pay {
+                request_id,
+                kind,
+                blocks_left: Some(blocks_left),
+            } => write!(f, "pay {:?} request #{}: {} blocks left", kind, request_id, blocks_left),
+            RecoveryAction::Pay {
+                request_id,
+                kind,
+                blocks_left: None,
+            } => write!(f, "pay {:?} request #{}", kind, request_id),
+        }
+    }
+}
+
+/// Summary of what happened while the vault was offline and what is done on resumption,
+/// built from the current parachain storage rather than by replaying every event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecoveryPlan {
+    pub since: Heartbeat,
+    pub downtime_secs: u64,
+    pub active_blocks_missed: u32,
+    pub bitcoin_blocks_missed: u32,
+    pub issues: RequestSummary,
+    pub redeems: RequestSummary,
+    pub replaces: RequestSummary,
+    pub actions: Vec<RecoveryAction>,
+}
+
+impl RecoveryPlan {
+    /// True if a request failed while the vault was offline or the vault is no longer active.
+    pub fn has_missed_obligations(&self) -> bool {
+        self.actions.iter().any(|action| {
+            matches!(
+                action,
+                RecoveryAction::ReviewVaultStatus { .. } | RecoveryAction::ReviewSlashed { .. }
+            )
+        })
+    }
+
+    pub fn log(&self) {
+        if self.has_missed_obligations() {
+            tracing::error!("Recovery after blackout: {}", self);
+        } else {
+            tracing::warn!("Recovery after blackout: {}", self);
+        }
+        for action in self.actions.iter() {
+            tracing::info!("Recovery action: {}", action);
+        }
+    }
+
+    /// Present the plan to the operator before the vault resumes: print it to stdout, as the
+    /// `recovery-plan` command does, and keep it in `path` for review after the fact.
+    pub async fn present(&self, path: PathBuf) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self)?;
+        println!("{}", contents);
+        tokio::task::spawn_blocking(move || replace_file(&path, &contents)).await?
+    }
+}
+
+impl fmt::Display for RecoveryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "offline for {} h ({} parachain and {} bitcoin blocks), {} issues, {} redeems and {} replaces opened \
+             meanwhile, {} actions",
+            self.downtime_secs / 3600,
+            self.active_blocks_missed,
+            self.bitcoin_blocks_missed,
+            self.issues.opened,
+            self.redeems.opened,
+            self.replaces.opened,
+            self.actions.len()
+        )
+    }
+}
+
+fn summarize(obligations: &[Obligation], kind: RequestKind, since: u32) -> RequestSummary {
+    obligations
+        .iter()
+        .filter(|obligation| obligation.kind == kind && obligation.opentime.map_or(false, |opentime| opentime >= since))
+        .fold(RequestSummary::default(), |mut summary, obligation| {
+            summary.opened += 1;
+            match obligation.outcome {
+                Outcome::Pending => summary.pending += 1,
+                Outcome::Completed => summary.completed += 1,
+                Outcome::Failed => summary.failed += 1,
+            }
+            summary
+        })
+}
+
+/// Decide on the actions for the requests of the vault, given the payments in the wallet.
+fn plan_actions(
+    obligations: &[Obligation],
+    payments: &HashMap<H256, Txid>,
+    since: u32,
+    active_block: u32,
+) -> Vec<RecoveryAction> {
+    let mut actions = Vec::new();
+    for obligation in obligations
+        .iter()
+        .filter(|obligation| obligation.kind != RequestKind::Issue)
+    {
+        let (request_id, kind) = (obligation.request_id, obligation.kind);
+        match obligation.outcome {
+            // a request can only be cancelled after its deadline, so it failed while the vault
+            // was offline if the deadline passed after the last heartbeat
+            Outcome::Failed if obligation.deadline.map_or(false, |deadline| deadline > since) => {
+                actions.push(RecoveryAction::ReviewSlashed { request_id, kind })
+            }
+            Outcome::Pending => actions.push(match (payments.get(&request_id), obligation.deadline) {
+                (Some(txid), _) => RecoveryAction::SubmitProof {
+                    request_id,
+                    kind,
+                    txid: *txid,
+                },
+                (None, Some(deadline)) if deadline <= active_block => RecoveryAction::PayOverdue { request_id, kind },
+                (None, deadline) => RecoveryAction::Pay {
+                    request_id,
+                    kind,
+                    blocks_left: deadline.map(|deadline| deadline - active_block),
+                },
+            }),
+            _ => {}
+        }
+    }
+    actions
+}
+
+async fn get_obligations(parachain_rpc: &InterBtcParachain) -> Result<Vec<Obligation>, Error> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+    let (issue_requests, redeem_requests, replace_requests, refund_requests) = try_join!(
+        parachain_rpc.get_vault_issue_requests(vault_id.clone()),
+        parachain_rpc.get_vault_redeem_requests(vault_id.clone()),
+        parachain_rpc.get_old_vault_replace_requests(vault_id.clone()),
+        parachain_rpc.get_vault_refund_requests(vault_id),
+    )?;
+
+    let issues = issue_requests.into_iter().map(|(request_id, request)| Obligation {
+        request_id,
+        kind: RequestKind::Issue,
+        outcome: match request.status {
+            IssueRequestStatus::Pending => Outcome::Pending,
+            IssueRequestStatus::Completed(_) => Outcome::Completed,
+            _ => Outcome::Failed,
+        },
+        opentime: Some(request.opentime),
+        deadline: Some(request.opentime.saturating_add(request.period)),
+    });
+    let redeems = redeem_requests.into_iter().map(|(request_id, request)| Obligation {
+        request_id,
+        kind: RequestKind::Redeem,
+        outcome: match request.status {
+            RedeemRequestStatus::Pending => Outcome::Pending,
+            RedeemRequestStatus::Completed => Outcome::Completed,
+            _ => Outcome::Failed,
+        },
+        opentime: Some(request.opentime),
+        deadline: Some(request.opentime.saturating_add(request.period)),
+    });
+    let replaces = replace_requests.into_iter().map(|(request_id, request)| Obligation {
+        request_id,
+        kind: RequestKind::Replace,
+        outcome: match request.status {
+            ReplaceRequestStatus::Pending => Outcome::Pending,
+            ReplaceRequestStatus::Completed => Outcome::Completed,
+            _ => Outcome::Failed,
+        },
+        opentime: Some(request.accept_time),
+        deadline: Some(request.accept_time.saturating_add(request.period)),
+    });
+    let refunds = refund_requests.into_iter().map(|(request_id, request)| Obligation {
+        request_id,
+        kind: RequestKind::Refund,
+        outcome: if request.completed {
+            Outcome::Completed
+        } else {
+            Outcome::Pending
+        },
+        opentime: None,
+        deadline: None,
+    });
+    Ok(issues.chain(redeems).chain(replaces).chain(refunds).collect())
+}
+
+/// Recover from a prolonged downtime: rescan the bitcoin chain for payments since the
+/// heartbeat, check that no request failed meanwhile and plan the actions to take on
+/// resumption. The open requests themselves are processed as on every start.
+pub async fn plan_recovery(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    since: Heartbeat,
+) -> Result<RecoveryPlan, Error> {
+    tracing::info!("Rescanning the bitcoin chain from height {}...", since.bitcoin_height);
+    bitcoin_core.rescan_blockchain(since.bitcoin_height as usize).await?;
+    let payments = bitcoin_core
+        .get_outgoing_payments()
+        .await?
+        .into_iter()
+        .map(|(txid, request_id)| (request_id, txid))
+        .collect();
+
+    let current = Heartbeat::current(parachain_rpc, bitcoin_core).await?;
+    let obligations = get_obligations(parachain_rpc).await?;
+
+    let mut actions = Vec::new();
+    let vault = parachain_rpc
+        .get_vault_unchecked(parachain_rpc.get_account_id().clone())
+        .await?;
+    if !matches!(vault.status, VaultStatus::Active(..)) {
+        actions.push(RecoveryAction::ReviewVaultStatus {
+            status: format!("{:?}", vault.status),
+        });
+    }
+    actions.extend(plan_actions(
+        &obligations,
+        &payments,
+        since.active_block,
+        current.active_block,
+    ));
+
+    Ok(RecoveryPlan {
+        since,
+        downtime_secs: since.downtime().as_secs(),
+        active_blocks_missed: current.active_block.saturating_sub(since.active_block),
+        bitcoin_blocks_missed: current.bitcoin_height.saturating_sub(since.bitcoin_height),
+        issues: summarize(&obligations, RequestKind::Issue, since.active_block),
+        redeems: summarize(&obligations, RequestKind::Redeem, since.active_block),
+        replaces: summarize(&obligations, RequestKind::Replace, since.active_block),
+        actions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::Hash;
+
+    fn obligation(id: u64, kind: RequestKind, outcome: Outcome, opentime: u32) -> Obligation {
+        Obligation {
+            request_id: H256::from_low_u64_be(id),
+            kind,
+            outcome,
+            opentime: Some(opentime),
+            deadline: Some(opentime + 100),
+        }
+    }
+
+    #[test]
+    fn test_plan_actions() {
+        let obligations = vec![
+            // opened before the downtime and completed, nothing to do
+            obligation(1, RequestKind::Redeem, Outcome::Completed, 10),
+            // failed before the downtime, already handled
+            Obligation {
+                deadline: Some(40),
+                ..obligation(8, RequestKind::Redeem, Outcome::Failed, 0)
+            },
+            // opened before the downtime but its deadline passed meanwhile
+            obligation(2, RequestKind::Redeem, Outcome::Failed, 10),
+            obligation(3, RequestKind::Replace, Outcome::Failed, 60),
+            obligation(4, RequestKind::Redeem, Outcome::Pending, 60),
+            obligation(5, RequestKind::Redeem, Outcome::Pending, 60),
+            obligation(6, RequestKind::Redeem, Outcome::Pending, 150),
+            obligation(7, RequestKind::Issue, Outcome::Pending, 150),
+        ];
+        let txid = Txid::from_slice(&[1; 32]).unwrap();
+        let payments = vec![(H256::from_low_u64_be(4), txid)].into_iter().collect();
+
+        assert_eq!(
+            plan_actions(&obligations, &payments, 50, 200),
+            vec![
+                RecoveryAction::ReviewSlashed {
+                    request_id: H256::from_low_u64_be(2),
+                    kind: RequestKind::Redeem
+                },
+                RecoveryAction::ReviewSlashed {
+                    request_id: H256::from_low_u64_be(3),
+                    kind: RequestKind::Replace
+                },
+                RecoveryAction::SubmitProof {
+                    request_id: H256::from_low_u64_be(4),
+                    kind: RequestKind::Redeem,
+                    txid
+                },
+                RecoveryAction::PayOverdue {
+                    request_id: H256::from_low_u64_be(5),
+                    kind: RequestKind::Redeem
+                },
+                RecoveryAction::Pay {
+                    request_id: H256::from_low_u64_be(6),
+                    kind: RequestKind::Redeem,
+                    blocks_left: Some(50)
+                },
+            ]
+        );
+
+        assert_eq!(
+            summarize(&obligations, RequestKind::Redeem, 50),
+            RequestSummary {
+                opened: 3,
+                completed: 0,
+                pending: 3,
+                failed: 0
+            }
+        );
+    }
+}
@@ -1,8 +1,20 @@
-use crate::execution::*;
-use bitcoin::BitcoinCoreApi;
-use runtime::{pallets::redeem::RequestRedeemEvent, InterBtcParachain, InterBtcRuntime, RedeemPallet, UtilFuncs};
+use crate::{
+    approval::PaymentApproval,
+    cancellation::Event,
+    concurrency::TaskLimiter,
+    execution::*,
+    hooks, latency,
+    proof_safety::ProofSafety,
+    replay::{self, ScenarioStep},
+};
+use bitcoin::{BitcoinCoreApi, PartialAddress};
+use futures::{channel::mpsc::Sender, future::try_join3, SinkExt};
+use runtime::{
+    pallets::redeem::{CancelRedeemEvent, ExecuteRedeemEvent, RequestRedeemEvent},
+    InterBtcParachain, InterBtcRuntime, RedeemPallet, UtilFuncs,
+};
 use service::Error as ServiceError;
-use std::time::Duration;
+use std::{convert::TryInto, time::Duration};
 
 /// Listen for RequestRedeemEvent directed at this vault; upon reception, transfer
 /// bitcoin and call execute_redeem
@@ -13,11 +25,18 @@ use std::time::Duration;
 /// * `btc_rpc` - the bitcoin RPC handle
 /// * `network` - network the bitcoin network used (i.e. regtest/testnet/mainnet)
 /// * `num_confirmations` - the number of bitcoin confirmation to await
+/// * `proof_safety` - the depth required of the payment before submitting the proof
+/// * `approval` - the gate holding back large payments until the operator approves them
+/// * `payment_margin` - minimum time to the the redeem execution deadline to make the bitcoin payment
+/// * `task_limiter` - bounds the number of redeem requests processed concurrently
 pub async fn listen_for_redeem_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     parachain_rpc: InterBtcParachain,
     btc_rpc: B,
     num_confirmations: u32,
     payment_margin: Duration,
+    proof_safety: ProofSafety,
+    approval: PaymentApproval,
+    task_limiter: TaskLimiter,
 ) -> Result<(), ServiceError> {
     parachain_rpc
         .on_event::<RequestRedeemEvent<InterBtcRuntime>, _, _, _>(
@@ -26,22 +45,38 @@ pub async fn listen_for_redeem_requests<B: BitcoinCoreApi + Clone + Send + Sync
                     return;
                 }
                 tracing::info!("Received redeem request: {:?}", event);
+                latency::observe(event.redeem_id, RequestType::Redeem.as_str());
+                hooks::on_redeem_request(&event).await;
 
                 // within this event callback, we captured the arguments of listen_for_redeem_requests
                 // by reference. Since spawn requires static lifetimes, we will need to capture the
                 // arguments by value rather than by reference, so clone these:
                 let parachain_rpc = parachain_rpc.clone();
                 let btc_rpc = btc_rpc.clone();
+                let proof_safety = proof_safety.clone();
+                let approval = approval.clone().releasing_slot_of(task_limiter.clone());
                 // Spawn a new task so that we handle these events concurrently
-                tokio::spawn(async move {
+                task_limiter.spawn(async move {
                     tracing::info!("Executing redeem #{:?}", event.redeem_id);
                     let result = async {
-                        let request = Request::from_redeem_request(
-                            event.redeem_id,
-                            parachain_rpc.get_redeem_request(event.redeem_id).await?,
-                            payment_margin,
-                        )?;
-                        request.pay_and_execute(parachain_rpc, btc_rpc, num_confirmations).await
+                        let redeem_request = parachain_rpc.get_redeem_request(event.redeem_id).await?;
+                        replay::record(|network| {
+                            Ok(ScenarioStep::RedeemRequested {
+                                redeem_id: event.redeem_id,
+                                btc_address: redeem_request
+                                    .btc_address
+                                    .encode_str(network)
+                                    .map_err(bitcoin::Error::from)?,
+                                amount: redeem_request.amount_btc.try_into()?,
+                                parachain_height: redeem_request.opentime,
+                                bitcoin_height: redeem_request.btc_height,
+                                period: redeem_request.period,
+                            })
+                        });
+                        let request = Request::from_redeem_request(event.redeem_id, redeem_request, payment_margin)?;
+                        request
+                            .pay_and_execute(parachain_rpc, btc_rpc, num_confirmations, proof_safety, approval)
+                            .await
                     }
                     .await;
 
@@ -64,3 +99,49 @@ pub async fn listen_for_redeem_requests<B: BitcoinCoreApi + Clone + Send + Sync
         .await?;
     Ok(())
 }
+
+/// Listen for redeems requested by this vault's account from other vaults (e.g. when
+/// rebalancing) and signal their opening and completion to the cancellation scheduler.
+///
+/// # Arguments
+///
+/// * `parachain_rpc` - the parachain RPC handle
+/// * `event_channel` - the channel over which to signal events
+pub async fn listen_for_own_redeems(
+    parachain_rpc: InterBtcParachain,
+    event_channel: Sender<Event>,
+) -> Result<(), ServiceError> {
+    let parachain_rpc = &parachain_rpc;
+    let event_channel = &event_channel;
+    let redeemer = parachain_rpc.get_account_id();
+    try_join3(
+        parachain_rpc.on_event::<RequestRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if &event.redeemer == redeemer {
+                    tracing::info!("Requested redeem #{:?} from vault {}", event.redeem_id, event.vault_id);
+                    let _ = event_channel.clone().send(Event::Opened).await;
+                }
+            },
+            |error| tracing::error!("Error reading redeem event: {}", error.to_string()),
+        ),
+        parachain_rpc.on_event::<ExecuteRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if &event.redeemer == redeemer {
+                    let _ = event_channel.clone().send(Event::Executed(event.redeem_id)).await;
+                }
+            },
+            |error| tracing::error!("Error reading execute redeem event: {}", error.to_string()),
+        ),
+        // also cancelled by the scheduler, or manually
+        parachain_rpc.on_event::<CancelRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if &event.redeemer == redeemer {
+                    let _ = event_channel.clone().send(Event::Executed(event.redeem_id)).await;
+                }
+            },
+            |error| tracing::error!("Error reading cancel redeem event: {}", error.to_string()),
+        ),
+    )
+    .await?;
+    Ok(())
+}
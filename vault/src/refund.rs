@@ -1,4 +1,4 @@
-use crate::execution::*;
+use crate::{approval::PaymentApproval, concurrency::TaskLimiter, execution::*, latency, proof_safety::ProofSafety};
 use bitcoin::BitcoinCoreApi;
 use runtime::{pallets::refund::RequestRefundEvent, InterBtcParachain, InterBtcRuntime, UtilFuncs};
 use service::Error as ServiceError;
@@ -12,10 +12,16 @@ use service::Error as ServiceError;
 /// * `btc_rpc` - the bitcoin RPC handle
 /// * `network` - network the bitcoin network used (i.e. regtest/testnet/mainnet)
 /// * `num_confirmations` - the number of bitcoin confirmation to await
+/// * `proof_safety` - the depth required of the payment before submitting the proof
+/// * `approval` - the gate holding back large payments until the operator approves them
+/// * `task_limiter` - bounds the number of refund requests processed concurrently
 pub async fn listen_for_refund_requests<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     parachain_rpc: InterBtcParachain,
     btc_rpc: B,
     num_confirmations: u32,
+    proof_safety: ProofSafety,
+    approval: PaymentApproval,
+    task_limiter: TaskLimiter,
 ) -> Result<(), ServiceError> {
     parachain_rpc
         .on_event::<RequestRefundEvent<InterBtcRuntime>, _, _, _>(
@@ -24,18 +30,23 @@ pub async fn listen_for_refund_requests<B: BitcoinCoreApi + Clone + Send + Sync
                     return;
                 }
                 tracing::info!("Received refund request: {:?}", event);
+                latency::observe(event.refund_id, RequestType::Refund.as_str());
 
                 // within this event callback, we captured the arguments of listen_for_refund_requests
                 // by reference. Since spawn requires static lifetimes, we will need to capture the
                 // arguments by value rather than by reference, so clone these:
                 let parachain_rpc = parachain_rpc.clone();
                 let btc_rpc = btc_rpc.clone();
+                let proof_safety = proof_safety.clone();
+                let approval = approval.clone().releasing_slot_of(task_limiter.clone());
                 // Spawn a new task so that we handle these events concurrently
-                tokio::spawn(async move {
+                task_limiter.spawn(async move {
                     tracing::info!("Executing refund #{:?}", event.refund_id);
                     // prepare the action that will be executed after the bitcoin transfer
                     let request = Request::from_refund_request_event(&event);
-                    let result = request.pay_and_execute(parachain_rpc, btc_rpc, num_confirmations).await;
+                    let result = request
+                        .pay_and_execute(parachain_rpc, btc_rpc, num_confirmations, proof_safety, approval)
+                        .await;
 
                     match result {
                         Ok(_) => tracing::info!(
@@ -1,6 +1,6 @@
 use super::Error;
 use async_trait::async_trait;
-use bitcoin::{serialize, BitcoinCore, BitcoinCoreApi, Error as BitcoinError};
+use bitcoin::{serialize, BitcoinCore, BitcoinCoreApi, Error as BitcoinError, EsploraClient};
 
 #[async_trait]
 pub trait Backing {
@@ -48,3 +48,66 @@ impl Backing for BitcoinCore {
         Ok(block_hash)
     }
 }
+
+/// Bitcoind with an Esplora API (e.g. electrs) to fall back to while bitcoind is unreachable,
+/// so that block headers are still relayed during an outage of the full node.
+#[derive(Clone)]
+pub struct FallbackBacking {
+    bitcoin_core: BitcoinCore,
+    esplora: Option<EsploraClient>,
+}
+
+impl FallbackBacking {
+    pub fn new(bitcoin_core: BitcoinCore, esplora: Option<EsploraClient>) -> Self {
+        Self { bitcoin_core, esplora }
+    }
+
+    /// Get the fallback if the error means that bitcoind is unreachable, else return the error.
+    fn fallback(&self, err: Error) -> Result<&EsploraClient, Error> {
+        match (&err, &self.esplora) {
+            (Error::BitcoinError(inner), Some(esplora))
+                if inner.is_connection_refused() || inner.is_connection_aborted() =>
+            {
+                tracing::debug!("Bitcoind is unreachable, falling back to esplora: {}", inner);
+                Ok(esplora)
+            }
+            _ => Err(err),
+        }
+    }
+}
+
+#[async_trait]
+impl Backing for FallbackBacking {
+    async fn get_block_count(&self) -> Result<u32, Error> {
+        match Backing::get_block_count(&self.bitcoin_core).await {
+            Err(err) => Ok(self.fallback(err)?.get_block_count().await? as u32),
+            result => result,
+        }
+    }
+
+    async fn get_block_header(&self, height: u32) -> Result<Option<Vec<u8>>, Error> {
+        match Backing::get_block_header(&self.bitcoin_core, height).await {
+            Err(err) => {
+                let esplora = self.fallback(err)?;
+                let block_hash = match esplora.get_block_hash(height).await {
+                    Ok(h) => h,
+                    Err(BitcoinError::InvalidBitcoinHeight) => {
+                        return Ok(None);
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                // the header is checked against the hash and its proof of work
+                let block_header = esplora.get_block_header(&block_hash).await?;
+                Ok(Some(serialize(&block_header)))
+            }
+            result => result,
+        }
+    }
+
+    async fn get_block_hash(&self, height: u32) -> Result<Vec<u8>, Error> {
+        match Backing::get_block_hash(&self.bitcoin_core, height).await {
+            Err(err) => Ok(serialize(&self.fallback(err)?.get_block_hash(height).await?)),
+            result => result,
+        }
+    }
+}
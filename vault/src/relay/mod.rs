@@ -1,4 +1,6 @@
-use bitcoin::BitcoinCore;
+use crate::{concurrency::TaskLimiter, degradation};
+use futures::future;
+use rand::Rng;
 use runtime::InterBtcParachain;
 use service::Error as ServiceError;
 use std::time::Duration;
@@ -8,20 +10,25 @@ mod backing;
 mod error;
 mod issuing;
 
-pub use backing::Backing;
+pub use backing::{Backing, FallbackBacking};
 pub use error::Error;
 pub use issuing::Issuing;
 
 // 10 minutes = 600 seconds
 const SLEEP_TIME: Duration = Duration::from_secs(600);
 
-/// Retrieves `batch` blocks starting at block `height` from the backing blockchain
-async fn collect_headers(height: u32, batch: u32, cli: &impl Backing) -> Result<Vec<Vec<u8>>, Error> {
-    let mut headers = Vec::new();
-    for h in height..height + batch {
-        headers.push(cli.get_block_header(h).await.map(|header| header.unwrap())?);
-    }
-    Ok(headers)
+/// Retrieves `batch` blocks starting at block `height` from the backing blockchain,
+/// fetching as many concurrently as the limiter allows
+async fn collect_headers(
+    height: u32,
+    batch: u32,
+    cli: &impl Backing,
+    task_limiter: &TaskLimiter,
+) -> Result<Vec<Vec<u8>>, Error> {
+    future::try_join_all((height..height + batch).map(|h| {
+        task_limiter.run(async move { Ok::<_, Error>(cli.get_block_header(h).await.map(|header| header.unwrap())?) })
+    }))
+    .await
 }
 
 /// Computes the height at which the relayer should start to submit blocks.
@@ -61,10 +68,17 @@ pub struct Config {
     pub start_height: Option<u32>,
     /// Maximum number of headers to collect on catchup
     pub max_batch_size: u32,
+    /// Maximum number of headers to fetch concurrently on catchup
+    pub max_concurrent_headers: usize,
     /// Thread sleep duration
     pub interval: Option<Duration>,
     /// Number of confirmations a block needs to have before it is submitted.
     pub btc_confirmations: u32,
+    /// Submit headers immediately, without waiting for other relayers.
+    pub always_relay: bool,
+    /// Upper bound of the random delay before submitting, so that relayers
+    /// don't all race to submit the same header.
+    pub max_holdoff: Duration,
 }
 
 /// Runner implements the main loop for the relayer
@@ -73,8 +87,11 @@ pub struct Runner<B: Backing, I: Issuing> {
     issuing: I,
     start_height: Option<u32>,
     max_batch_size: u32,
+    task_limiter: TaskLimiter,
     interval: Duration,
     btc_confirmations: u32,
+    always_relay: bool,
+    max_holdoff: Duration,
 }
 
 impl<B: Backing, I: Issuing> Runner<B, I> {
@@ -84,9 +101,34 @@ impl<B: Backing, I: Issuing> Runner<B, I> {
             issuing,
             start_height: conf.start_height,
             max_batch_size: conf.max_batch_size,
+            task_limiter: TaskLimiter::new("relayer", conf.max_concurrent_headers),
             interval: conf.interval.unwrap_or_else(|| SLEEP_TIME),
             btc_confirmations: conf.btc_confirmations,
+            always_relay: conf.always_relay,
+            max_holdoff: conf.max_holdoff,
+        }
+    }
+
+    /// Wait for a random duration and check whether another relayer has submitted the
+    /// block at `height` in the meantime. Returns true if we should still submit it.
+    async fn hold_off(&self, height: u32) -> Result<bool, Error> {
+        if self.always_relay || self.max_holdoff == Duration::from_secs(0) {
+            return Ok(true);
+        }
+        let holdoff = rand::thread_rng().gen_range(Duration::from_secs(0), self.max_holdoff);
+        tracing::trace!(
+            "Holding off for {:?} before submitting block at height {}",
+            holdoff,
+            height
+        );
+        delay_for(holdoff).await;
+
+        let new_height = compute_start_height(&self.backing, &self.issuing).await?;
+        if new_height > height {
+            tracing::info!("Block at height {} has already been relayed", height);
+            return Ok(false);
         }
+        Ok(true)
     }
 
     /// Returns the block header at `height`
@@ -129,17 +171,25 @@ impl<B: Backing, I: Issuing> Runner<B, I> {
         let current_height = compute_start_height(&self.backing, &self.issuing).await?;
         tracing::trace!("Issuing height: {}", current_height);
 
-        let batch_size = if current_height.saturating_add(self.max_batch_size) > max_height {
+        // fewer, larger batches while the parachain rpc is slow
+        let max_batch_size = degradation::batch_size(self.max_batch_size);
+        let batch_size = if current_height.saturating_add(max_batch_size) > max_height {
             max_height.saturating_add(1).saturating_sub(current_height)
         } else {
-            self.max_batch_size
+            max_batch_size
         };
 
+        // only the latest block is contested by other relayers, blocks behind it are
+        // caught up on immediately
+        if batch_size > 0 && current_height == max_height && !self.hold_off(current_height).await? {
+            return Ok(());
+        }
+
         match batch_size {
             0 => {
                 // nothing to submit right now. Wait a little while
                 tracing::trace!("Waiting for the next Bitcoin block...");
-                delay_for(self.interval).await;
+                delay_for(degradation::stretch(self.interval)).await;
             }
             1 => {
                 // submit a single block header
@@ -156,7 +206,7 @@ impl<B: Backing, I: Issuing> Runner<B, I> {
                     current_height + batch_size,
                     batch_size
                 );
-                let headers = collect_headers(current_height, batch_size, &self.backing).await?;
+                let headers = collect_headers(current_height, batch_size, &self.backing, &self.task_limiter).await?;
                 self.issuing.submit_block_header_batch(headers).await?;
                 tracing::info!(
                     "Submitted blocks {} -> {} [{}]",
@@ -171,7 +221,7 @@ impl<B: Backing, I: Issuing> Runner<B, I> {
     }
 }
 
-pub async fn run_relayer(runner: Runner<BitcoinCore, InterBtcParachain>) -> Result<(), ServiceError> {
+pub async fn run_relayer(runner: Runner<FallbackBacking, InterBtcParachain>) -> Result<(), ServiceError> {
     loop {
         match runner.submit_next().await {
             Ok(_) => (),
@@ -373,8 +423,11 @@ mod tests {
             Config {
                 start_height: None,
                 max_batch_size: 1,
+                max_concurrent_headers: 1,
                 interval: None,
                 btc_confirmations: 0,
+                always_relay: false,
+                max_holdoff: Duration::from_secs(0),
             },
         );
 
@@ -394,8 +447,11 @@ mod tests {
             Config {
                 start_height: Some(0),
                 max_batch_size: 16,
+                max_concurrent_headers: 1,
                 interval: None,
                 btc_confirmations: 0,
+                always_relay: false,
+                max_holdoff: Duration::from_secs(0),
             },
         );
 
@@ -427,8 +483,11 @@ mod tests {
             Config {
                 start_height: None,
                 max_batch_size: 1,
+                max_concurrent_headers: 1,
                 interval: None,
                 btc_confirmations: 0,
+                always_relay: false,
+                max_holdoff: Duration::from_secs(0),
             },
         );
 
@@ -457,7 +516,10 @@ mod tests {
                 start_height: None,
                 interval: Some(Duration::from_secs(0)),
                 max_batch_size: 16,
+                max_concurrent_headers: 1,
                 btc_confirmations: 1,
+                always_relay: false,
+                max_holdoff: Duration::from_secs(0),
             },
         );
 
@@ -488,8 +550,11 @@ mod tests {
             Config {
                 start_height: None,
                 max_batch_size: 1,
+                max_concurrent_headers: 1,
                 interval: Some(Duration::from_secs(0)),
                 btc_confirmations: 1,
+                always_relay: false,
+                max_holdoff: Duration::from_secs(0),
             },
         );
 
@@ -521,8 +586,11 @@ mod tests {
             Config {
                 start_height: None,
                 max_batch_size: 1,
+                max_concurrent_headers: 1,
                 interval: Some(Duration::from_secs(0)),
                 btc_confirmations: 2,
+                always_relay: false,
+                max_holdoff: Duration::from_secs(0),
             },
         );
 
@@ -543,4 +611,56 @@ mod tests {
         assert!(!runner.issuing.is_block_stored(make_hash("d")).await?);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn submit_next_after_holdoff_succeeds() -> Result<(), Error> {
+        let backing_hashes = make_hashes(vec![(2, "a"), (3, "b"), (4, "c")]);
+        let issuing_hashes = make_hashes(vec![(2, "a"), (3, "b")]);
+        let backing = DummyBacking::new(backing_hashes);
+        let issuing = DummyIssuing::new(issuing_hashes);
+        let runner = Runner::new(
+            backing,
+            issuing,
+            Config {
+                start_height: None,
+                max_batch_size: 1,
+                max_concurrent_headers: 1,
+                interval: Some(Duration::from_secs(0)),
+                btc_confirmations: 0,
+                always_relay: false,
+                max_holdoff: Duration::from_millis(10),
+            },
+        );
+
+        runner.submit_next().await?;
+        assert!(runner.issuing.is_block_stored(make_hash("c")).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn submit_next_catching_up_does_not_hold_off() -> Result<(), Error> {
+        let backing_hashes = make_hashes(vec![(2, "a"), (3, "b"), (4, "c"), (5, "d")]);
+        let issuing_hashes = make_hashes(vec![(2, "a"), (3, "b")]);
+        let backing = DummyBacking::new(backing_hashes);
+        let issuing = DummyIssuing::new(issuing_hashes);
+        let runner = Runner::new(
+            backing,
+            issuing,
+            Config {
+                start_height: None,
+                max_batch_size: 1,
+                max_concurrent_headers: 1,
+                interval: Some(Duration::from_secs(0)),
+                btc_confirmations: 0,
+                always_relay: false,
+                max_holdoff: Duration::from_secs(3600),
+            },
+        );
+
+        tokio::time::timeout(Duration::from_secs(1), runner.submit_next())
+            .await
+            .expect("held off while catching up")?;
+        assert!(runner.issuing.is_block_stored(make_hash("c")).await?);
+        Ok(())
+    }
 }
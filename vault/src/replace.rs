@@ -1,9 +1,19 @@
-use crate::{cancellation::Event, error::Error, execution::Request};
+use crate::{
+    approval::PaymentApproval,
+    ban::BanStatus,
+    cancellation::Event,
+    concurrency::TaskLimiter,
+    error::Error,
+    execution::{Request, RequestType},
+    latency, maintenance,
+    proof_safety::ProofSafety,
+};
 use bitcoin::BitcoinCoreApi;
 use futures::{channel::mpsc::Sender, future::try_join3, SinkExt};
 use runtime::{
     pallets::replace::{AcceptReplaceEvent, ExecuteReplaceEvent, RequestReplaceEvent},
-    CollateralBalancesPallet, InterBtcParachain, InterBtcRuntime, ReplacePallet, UtilFuncs, VaultRegistryPallet,
+    CollateralAmount, CollateralBalancesPallet, InterBtcParachain, InterBtcRuntime, ReplacePallet, UtilFuncs,
+    VaultRegistryPallet, WrappedAmount,
 };
 use service::Error as ServiceError;
 use std::time::Duration;
@@ -16,14 +26,23 @@ use std::time::Duration;
 /// * `parachain_rpc` - the parachain RPC handle
 /// * `btc_rpc` - the bitcoin RPC handle
 /// * `num_confirmations` - the number of bitcoin confirmation to await
+/// * `proof_safety` - the depth required of the payment before submitting the proof
+/// * `approval` - the gate holding back large payments until the operator approves them
+/// * `task_limiter` - bounds the number of replace requests processed concurrently
 pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Clone + Send + Sync + 'static>(
     parachain_rpc: InterBtcParachain,
     btc_rpc: B,
     num_confirmations: u32,
     payment_margin: Duration,
+    proof_safety: ProofSafety,
+    approval: PaymentApproval,
+    task_limiter: TaskLimiter,
 ) -> Result<(), ServiceError> {
     let parachain_rpc = &parachain_rpc;
     let btc_rpc = &btc_rpc;
+    let proof_safety = &proof_safety;
+    let approval = &approval;
+    let task_limiter = &task_limiter;
     parachain_rpc
         .on_event::<AcceptReplaceEvent<InterBtcRuntime>, _, _, _>(
             |event| async move {
@@ -31,14 +50,17 @@ pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Clone + Send + Sync +
                     return;
                 }
                 tracing::info!("Received accept replace event: {:?}", event);
+                latency::observe(event.replace_id, RequestType::Replace.as_str());
 
                 // within this event callback, we captured the arguments of listen_for_redeem_requests
                 // by reference. Since spawn requires static lifetimes, we will need to capture the
                 // arguments by value rather than by reference, so clone these:
                 let parachain_rpc = parachain_rpc.clone();
                 let btc_rpc = btc_rpc.clone();
+                let proof_safety = proof_safety.clone();
+                let approval = approval.clone().releasing_slot_of(task_limiter.clone());
                 // Spawn a new task so that we handle these events concurrently
-                tokio::spawn(async move {
+                task_limiter.spawn(async move {
                     tracing::info!("Executing accept replace #{:?}", event.replace_id);
 
                     let result = async {
@@ -47,7 +69,9 @@ pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Clone + Send + Sync +
                             parachain_rpc.get_replace_request(event.replace_id).await?,
                             payment_margin,
                         )?;
-                        request.pay_and_execute(parachain_rpc, btc_rpc, num_confirmations).await
+                        request
+                            .pay_and_execute(parachain_rpc, btc_rpc, num_confirmations, proof_safety, approval)
+                            .await
                     }
                     .await;
 
@@ -78,12 +102,15 @@ pub async fn listen_for_accept_replace<B: BitcoinCoreApi + Clone + Send + Sync +
 /// * `parachain_rpc` - the parachain RPC handle
 /// * `event_channel` - the channel over which to signal events
 /// * `accept_replace_requests` - if true, we attempt to accept replace requests
+/// * `ban_status` - requests are not accepted while the vault is banned
 pub async fn listen_for_replace_requests<B: BitcoinCoreApi + Clone>(
     parachain_rpc: InterBtcParachain,
     btc_rpc: B,
     event_channel: Sender<Event>,
     accept_replace_requests: bool,
+    ban_status: BanStatus,
 ) -> Result<(), ServiceError> {
+    let ban_status = &ban_status;
     let parachain_rpc = &parachain_rpc;
     let btc_rpc = &btc_rpc;
     let event_channel = &event_channel;
@@ -101,7 +128,14 @@ pub async fn listen_for_replace_requests<B: BitcoinCoreApi + Clone>(
                     event.amount_btc
                 );
 
-                if accept_replace_requests {
+                if accept_replace_requests && ban_status.is_banned() {
+                    tracing::info!(
+                        "Not accepting replace request while banned ({} blocks remaining)",
+                        ban_status.blocks_remaining()
+                    );
+                } else if accept_replace_requests && maintenance::is_draining() {
+                    tracing::info!("Not accepting replace request while draining for maintenance");
+                } else if accept_replace_requests {
                     match handle_replace_request(parachain_rpc.clone(), btc_rpc.clone(), &event).await {
                         Ok(_) => {
                             tracing::info!("Accepted replace request from {}", event.old_vault_id);
@@ -134,22 +168,22 @@ pub async fn handle_replace_request<
     event: &RequestReplaceEvent<InterBtcRuntime>,
 ) -> Result<(), Error> {
     let (required_collateral, free_balance, minimum_replace) = try_join3(
-        parachain_rpc.get_required_collateral_for_wrapped(event.amount_btc),
+        parachain_rpc.get_required_collateral_for_wrapped(WrappedAmount::from_planck(event.amount_btc)),
         parachain_rpc.get_free_balance(),
         parachain_rpc.get_replace_dust_amount(),
     )
     .await?;
 
-    if required_collateral <= minimum_replace {
+    if required_collateral.planck() <= minimum_replace {
         Err(Error::BelowDustAmount)
-    } else if free_balance < required_collateral {
+    } else if CollateralAmount::from_planck(free_balance) < required_collateral {
         Err(Error::InsufficientFunds)
     } else {
         Ok(parachain_rpc
             .accept_replace(
                 &event.old_vault_id,
                 event.amount_btc,
-                required_collateral,
+                required_collateral.planck(),
                 btc_rpc.get_new_address().await?,
             )
             .await?)
@@ -189,12 +223,12 @@ mod tests {
     use super::*;
     use async_trait::async_trait;
     use bitcoin::{
-        Block, BlockHash, BlockHeader, Error as BitcoinError, GetBlockResult, LockedTransaction, PartialAddress,
-        PrivateKey, Transaction, TransactionMetadata, Txid, PUBLIC_KEY_SIZE,
+        Block, BlockHash, BlockHeader, Error as BitcoinError, FeeEstimation, GetBlockResult, LockedTransaction,
+        PartialAddress, PrivateKey, Transaction, TransactionMetadata, Txid, PUBLIC_KEY_SIZE,
     };
     use runtime::{
         pallets::Core, AccountId, BtcAddress, BtcPublicKey, Error as RuntimeError, InterBtcReplaceRequest,
-        InterBtcRuntime, InterBtcVault,
+        InterBtcRuntime, InterBtcVault, SubmissionReceipt,
     };
     use sp_core::H256;
 
@@ -238,11 +272,12 @@ mod tests {
                 txid: Txid,
                 num_confirmations: u32,
             ) -> Result<TransactionMetadata, BitcoinError>;
-            async fn create_transaction<A: PartialAddress + Send + Sync + 'static>(
+            async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
                 &self,
                 address: A,
                 sat: u64,
                 request_id: Option<H256>,
+                fee_estimation: FeeEstimation,
             ) -> Result<LockedTransaction, BitcoinError>;
             async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError>;
             async fn create_and_send_transaction<A: PartialAddress + Send + Sync + 'static>(
@@ -281,13 +316,13 @@ mod tests {
         pub trait VaultRegistryPallet {
             async fn get_vault(&self, vault_id: AccountId) -> Result<InterBtcVault, RuntimeError>;
             async fn get_all_vaults(&self) -> Result<Vec<InterBtcVault>, RuntimeError>;
-            async fn register_vault(&self, collateral: u128, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
-            async fn deposit_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
-            async fn withdraw_collateral(&self, amount: u128) -> Result<(), RuntimeError>;
+            async fn register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
+            async fn deposit_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
+            async fn withdraw_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
             async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
             async fn register_address(&self, btc_address: BtcAddress) -> Result<(), RuntimeError>;
-            async fn get_required_collateral_for_wrapped(&self, amount_btc: u128) -> Result<u128, RuntimeError>;
-            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<u128, RuntimeError>;
+            async fn get_required_collateral_for_wrapped(&self, amount_btc: WrappedAmount) -> Result<CollateralAmount, RuntimeError>;
+            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<CollateralAmount, RuntimeError>;
         }
 
         #[async_trait]
@@ -306,7 +341,7 @@ mod tests {
                 replace_id: H256,
                 merkle_proof: &[u8],
                 raw_tx: &[u8],
-            ) -> Result<(), RuntimeError>;
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
             async fn cancel_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
             async fn get_new_vault_replace_requests(
                 &self,
@@ -347,7 +382,7 @@ mod tests {
         let mut parachain_rpc = MockProvider::default();
         parachain_rpc
             .expect_get_required_collateral_for_wrapped()
-            .returning(|_| Ok(100));
+            .returning(|_| Ok(CollateralAmount::from_planck(100)));
         parachain_rpc.expect_get_free_balance().returning(|| Ok(50));
         parachain_rpc
             .expect_get_replace_dust_amount()
@@ -0,0 +1,804 @@
+use crate::Error;
+use bitcoin::{serialize, Block, Network, TransactionExt};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sp_core::H256;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+lazy_static! {
+    static ref RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+}
+
+/// Event observed by the vault, in the order it was observed. A recording is a file with
+/// one step per line, which can be replayed against the state machines of the vault to
+/// reproduce its decisions, e.g. in regression tests for past incidents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScenarioStep {
+    /// Number of active parachain blocks after which an issue request can be cancelled.
+    IssuePeriod {
+        period: u32,
+    },
+    ParachainBlock {
+        height: u32,
+    },
+    /// Only the transactions with an OP_RETURN output are kept, the others cannot pay
+    /// for a redeem or replace request.
+    BitcoinBlock {
+        height: u32,
+        hash: String,
+        /// Hex-encoded transactions.
+        transactions: Vec<String>,
+    },
+    /// An issue request directed at this vault, opened at the current heights.
+    IssueRequested {
+        issue_id: H256,
+    },
+    IssueExecuted {
+        issue_id: H256,
+    },
+    /// A redeem request directed at this vault.
+    RedeemRequested {
+        redeem_id: H256,
+        btc_address: String,
+        /// Amount in satoshis.
+        amount: u64,
+        parachain_height: u32,
+        bitcoin_height: u32,
+        period: u32,
+    },
+    RedeemExecuted {
+        redeem_id: H256,
+    },
+}
+
+impl ScenarioStep {
+    pub(crate) fn bitcoin_block(height: u32, block: &Block) -> Self {
+        ScenarioStep::BitcoinBlock {
+            height,
+            hash: block.block_hash().to_string(),
+            transactions: block
+                .txdata
+                .iter()
+                .filter(|transaction| transaction.get_op_return().is_some())
+                .map(|transaction| hex::encode(serialize(transaction)))
+                .collect(),
+        }
+    }
+}
+
+struct Recorder {
+    file: File,
+    network: Network,
+    last_parachain_height: u32,
+}
+
+impl Recorder {
+    fn write(&mut self, step: &ScenarioStep) -> Result<(), Error> {
+        if let ScenarioStep::ParachainBlock { height } = step {
+            // the active block is observed by the listener of each cancellation scheduler
+            if *height <= self.last_parachain_height {
+                return Ok(());
+            }
+            self.last_parachain_height = *height;
+        }
+        let mut line = serde_json::to_string(step)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Append the events observed from now on to the given file.
+pub fn record_to(path: PathBuf, network: Network) -> Result<(), Error> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *RECORDER.lock().expect("poisoned") = Some(Recorder {
+        file,
+        network,
+        last_parachain_height: 0,
+    });
+    Ok(())
+}
+
+/// Record a step if recording is enabled, the step is only built in that case.
+pub(crate) fn record(step: impl FnOnce(Network) -> Result<ScenarioStep, Error>) {
+    let mut recorder = match RECORDER.lock() {
+        Ok(recorder) => recorder,
+        Err(_) => return,
+    };
+    let recorder = match recorder.as_mut() {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    if let Err(e) = step(recorder.network).and_then(|step| recorder.write(&step)) {
+        tracing::error!("Failed to record event: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        approval::PaymentApproval,
+        cancellation::{ActiveRequest, CancellationScheduler, Event, IssueCanceller, ListState},
+        execution::Request,
+        proof_safety::ProofSafety,
+        request_state::{self, RequestKind, RequestState},
+    };
+    use async_trait::async_trait;
+    use bitcoin::{
+        deserialize, opcodes, Address, BitcoinCoreApi, BlockHash, BlockHeader, Builder, Error as BitcoinError,
+        FeeEstimation, GetBlockResult, LockedTransaction, OutPoint, PartialAddress, PrivateKey, Script, Transaction,
+        TransactionMetadata, TxIn, TxOut, Txid, PUBLIC_KEY_SIZE,
+    };
+    use futures::{future::LocalBoxFuture, stream::FuturesUnordered, FutureExt, StreamExt};
+    use runtime::{
+        AccountId, BlockNumber, BtcAddress, BtcPublicKey, BtcRelayPallet, CollateralAmount, Error as RuntimeError,
+        ErrorCode, H256Le, InterBtcIssueRequest, InterBtcRedeemRequest, InterBtcRefundRequest, InterBtcReplaceRequest,
+        InterBtcRequestIssueEvent, InterBtcRichBlockHeader, InterBtcVault, IssuePallet, RedeemPallet, RefundPallet,
+        ReplacePallet, SecurityPallet, StatusCode, SubmissionReceipt, UtilFuncs, VaultRegistryPallet, WrappedAmount,
+    };
+    use std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        fs,
+        path::Path,
+        str::FromStr,
+        sync::Arc,
+        time::Duration,
+    };
+    use tokio::sync::watch;
+
+    /// Extrinsic submitted or bitcoin payment made by the vault.
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(tag = "call", rename_all = "snake_case")]
+    enum Action {
+        Pay {
+            redeem_id: H256,
+            btc_address: String,
+            amount: u64,
+        },
+        ExecuteRedeem {
+            redeem_id: H256,
+            txid: String,
+        },
+        CancelIssue {
+            issue_id: H256,
+        },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct StepAction {
+        /// Index of the step that caused the action.
+        step: usize,
+        #[serde(flatten)]
+        action: Action,
+    }
+
+    /// Everything the vault did in a scenario, compared against the golden file.
+    #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+    struct Outcome {
+        actions: Vec<StepAction>,
+        states: BTreeMap<H256, RequestState>,
+    }
+
+    /// Payment created by the vault.
+    #[derive(Clone)]
+    struct CreatedPayment {
+        redeem_id: H256,
+        btc_address: String,
+        transaction: Transaction,
+    }
+
+    /// The parachain and the bitcoin chain as far as the scenario got, and what the vault did.
+    #[derive(Default)]
+    struct Chain {
+        /// Index of the step being replayed, actions are attributed to it.
+        step: usize,
+        issue_period: u32,
+        active_block: u32,
+        bitcoin_height: u32,
+        issues: HashMap<H256, InterBtcIssueRequest>,
+        /// The recorded transactions, with the hash and height of their block.
+        transactions: Vec<(Transaction, BlockHash, u32)>,
+        payments: HashMap<Txid, CreatedPayment>,
+        actions: Vec<StepAction>,
+    }
+
+    impl Chain {
+        fn act(&mut self, action: Action) {
+            let step = self.step;
+            self.actions.push(StepAction { step, action });
+        }
+
+        /// The recorded transaction with the outputs of the payment, if it was included yet.
+        fn confirmed(&self, txid: &Txid) -> Option<TransactionMetadata> {
+            let payment = self.payments.get(txid)?;
+            self.transactions
+                .iter()
+                .find(|(transaction, ..)| transaction.output == payment.transaction.output)
+                .map(|(transaction, block_hash, block_height)| TransactionMetadata {
+                    txid: transaction.txid(),
+                    proof: vec![],
+                    raw_tx: serialize(transaction),
+                    block_height: *block_height,
+                    block_hash: *block_hash,
+                    outputs: Default::default(),
+                })
+        }
+    }
+
+    type SharedChain = Arc<Mutex<Chain>>;
+
+    mockall::mock! {
+        Parachain {}
+
+        #[async_trait]
+        pub trait UtilFuncs {
+            async fn get_current_chain_height(&self) -> Result<u32, RuntimeError>;
+            fn get_account_id(&self) -> &AccountId;
+        }
+
+        #[async_trait]
+        pub trait VaultRegistryPallet {
+            async fn get_vault(&self, vault_id: AccountId) -> Result<InterBtcVault, RuntimeError>;
+            async fn get_all_vaults(&self) -> Result<Vec<InterBtcVault>, RuntimeError>;
+            async fn register_vault(&self, collateral: CollateralAmount, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
+            async fn deposit_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
+            async fn withdraw_collateral(&self, amount: CollateralAmount) -> Result<(), RuntimeError>;
+            async fn update_public_key(&self, public_key: BtcPublicKey) -> Result<(), RuntimeError>;
+            async fn register_address(&self, btc_address: BtcAddress) -> Result<(), RuntimeError>;
+            async fn get_required_collateral_for_wrapped(&self, amount_btc: WrappedAmount) -> Result<CollateralAmount, RuntimeError>;
+            async fn get_required_collateral_for_vault(&self, vault_id: AccountId) -> Result<CollateralAmount, RuntimeError>;
+        }
+
+        #[async_trait]
+        pub trait IssuePallet {
+            async fn request_issue(
+                &self,
+                amount: WrappedAmount,
+                vault_id: &AccountId,
+                griefing_collateral: CollateralAmount,
+            ) -> Result<InterBtcRequestIssueEvent, RuntimeError>;
+            async fn execute_issue(&self, issue_id: H256, merkle_proof: &[u8], raw_tx: &[u8]) -> Result<Option<SubmissionReceipt>, RuntimeError>;
+            async fn cancel_issue(&self, issue_id: H256) -> Result<(), RuntimeError>;
+            async fn get_issue_request(&self, issue_id: H256) -> Result<InterBtcIssueRequest, RuntimeError>;
+            async fn get_vault_issue_requests(
+                &self,
+                account_id: AccountId,
+            ) -> Result<Vec<(H256, InterBtcIssueRequest)>, RuntimeError>;
+            async fn get_issue_period(&self) -> Result<u32, RuntimeError>;
+            async fn set_issue_period(&self, period: u32) -> Result<(), RuntimeError>;
+            async fn get_all_active_issues(&self) -> Result<Vec<(H256, InterBtcIssueRequest)>, RuntimeError>;
+        }
+
+        #[async_trait]
+        pub trait RedeemPallet {
+            async fn request_redeem(
+                &self,
+                amount: u128,
+                btc_address: BtcAddress,
+                vault_id: &AccountId,
+            ) -> Result<H256, RuntimeError>;
+            async fn execute_redeem(
+                &self,
+                redeem_id: H256,
+                merkle_proof: &[u8],
+                raw_tx: &[u8],
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
+            async fn cancel_redeem(&self, redeem_id: H256, reimburse: bool) -> Result<(), RuntimeError>;
+            async fn get_redeem_request(&self, redeem_id: H256) -> Result<InterBtcRedeemRequest, RuntimeError>;
+            async fn get_vault_redeem_requests(
+                &self,
+                account_id: AccountId,
+            ) -> Result<Vec<(H256, InterBtcRedeemRequest)>, RuntimeError>;
+            async fn get_all_open_redeem_requests(&self) -> Result<Vec<(H256, InterBtcRedeemRequest)>, RuntimeError>;
+            async fn get_redeem_period(&self) -> Result<BlockNumber, RuntimeError>;
+            async fn set_redeem_period(&self, period: u32) -> Result<(), RuntimeError>;
+        }
+
+        #[async_trait]
+        pub trait ReplacePallet {
+            async fn request_replace(&self, amount: u128, griefing_collateral: u128) -> Result<(), RuntimeError>;
+            async fn withdraw_replace(&self, amount: u128) -> Result<(), RuntimeError>;
+            async fn accept_replace(
+                &self,
+                old_vault: &AccountId,
+                amount_btc: u128,
+                collateral: u128,
+                btc_address: BtcAddress,
+            ) -> Result<(), RuntimeError>;
+            async fn execute_replace(
+                &self,
+                replace_id: H256,
+                merkle_proof: &[u8],
+                raw_tx: &[u8],
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
+            async fn cancel_replace(&self, replace_id: H256) -> Result<(), RuntimeError>;
+            async fn get_new_vault_replace_requests(
+                &self,
+                account_id: AccountId,
+            ) -> Result<Vec<(H256, InterBtcReplaceRequest)>, RuntimeError>;
+            async fn get_old_vault_replace_requests(
+                &self,
+                account_id: AccountId,
+            ) -> Result<Vec<(H256, InterBtcReplaceRequest)>, RuntimeError>;
+            async fn get_replace_period(&self) -> Result<u32, RuntimeError>;
+            async fn set_replace_period(&self, period: u32) -> Result<(), RuntimeError>;
+            async fn get_replace_request(&self, replace_id: H256) -> Result<InterBtcReplaceRequest, RuntimeError>;
+            async fn get_replace_dust_amount(&self) -> Result<u128, RuntimeError>;
+        }
+
+        #[async_trait]
+        pub trait RefundPallet {
+            async fn execute_refund(
+                &self,
+                refund_id: H256,
+                merkle_proof: &[u8],
+                raw_tx: &[u8],
+            ) -> Result<Option<SubmissionReceipt>, RuntimeError>;
+            async fn get_vault_refund_requests(
+                &self,
+                account_id: AccountId,
+            ) -> Result<Vec<(H256, InterBtcRefundRequest)>, RuntimeError>;
+        }
+
+        #[async_trait]
+        pub trait BtcRelayPallet {
+            async fn get_best_block(&self) -> Result<H256Le, RuntimeError>;
+            async fn get_best_block_height(&self) -> Result<u32, RuntimeError>;
+            async fn get_block_hash(&self, height: u32) -> Result<H256Le, RuntimeError>;
+            async fn get_block_header(&self, hash: H256Le) -> Result<InterBtcRichBlockHeader, RuntimeError>;
+            async fn get_bitcoin_confirmations(&self) -> Result<u32, RuntimeError>;
+            async fn set_bitcoin_confirmations(&self, value: u32) -> Result<(), RuntimeError>;
+            async fn get_parachain_confirmations(&self) -> Result<BlockNumber, RuntimeError>;
+            async fn set_parachain_confirmations(&self, value: BlockNumber) -> Result<(), RuntimeError>;
+            async fn wait_for_block_in_relay(
+                &self,
+                block_hash: H256Le,
+                btc_confirmations: Option<BlockNumber>,
+            ) -> Result<(), RuntimeError>;
+            async fn verify_block_header_inclusion(&self, block_hash: H256Le) -> Result<(), RuntimeError>;
+        }
+
+        #[async_trait]
+        pub trait SecurityPallet {
+            async fn get_parachain_status(&self) -> Result<StatusCode, RuntimeError>;
+            async fn get_error_codes(&self) -> Result<BTreeSet<ErrorCode>, RuntimeError>;
+            async fn get_current_active_block_number(&self) -> Result<u32, RuntimeError>;
+        }
+    }
+
+    impl Clone for MockParachain {
+        fn clone(&self) -> Self {
+            // NOTE: expectations dropped
+            Self::default()
+        }
+    }
+
+    /// Parachain answering from the scenario, calls the vault is not expected to make panic.
+    fn parachain(chain: &SharedChain) -> MockParachain {
+        let mut parachain = MockParachain::default();
+        let scenario = chain.clone();
+        parachain
+            .expect_get_current_active_block_number()
+            .returning(move || Ok(scenario.lock().expect("poisoned").active_block));
+        let scenario = chain.clone();
+        parachain.expect_get_vault_issue_requests().returning(move |_| {
+            Ok(scenario
+                .lock()
+                .expect("poisoned")
+                .issues
+                .iter()
+                .map(|(issue_id, issue)| (*issue_id, issue.clone()))
+                .collect())
+        });
+        let scenario = chain.clone();
+        parachain
+            .expect_get_issue_period()
+            .returning(move || Ok(scenario.lock().expect("poisoned").issue_period));
+        let scenario = chain.clone();
+        parachain.expect_cancel_issue().returning(move |issue_id| {
+            let mut chain = scenario.lock().expect("poisoned");
+            chain.issues.remove(&issue_id);
+            chain.act(Action::CancelIssue { issue_id });
+            // as observed by the listener for cancelled issues
+            request_state::transition(issue_id, RequestState::Expired);
+            Ok(())
+        });
+        let scenario = chain.clone();
+        parachain
+            .expect_execute_redeem()
+            .returning(move |redeem_id, _, raw_tx| {
+                let transaction: Transaction = deserialize(raw_tx).expect("recorded transaction");
+                scenario.lock().expect("poisoned").act(Action::ExecuteRedeem {
+                    redeem_id,
+                    txid: transaction.txid().to_string(),
+                });
+                Ok(None)
+            });
+        parachain.expect_wait_for_block_in_relay().returning(|_, _| Ok(()));
+        parachain.expect_verify_block_header_inclusion().returning(|_| Ok(()));
+        parachain
+    }
+
+    /// Wallet answering from the scenario. A payment of the vault is confirmed once a recorded
+    /// block contains a transaction with the same outputs, i.e. the payment the vault made.
+    #[derive(Clone)]
+    struct ScenarioBitcoin {
+        chain: SharedChain,
+        blocks: watch::Receiver<u32>,
+    }
+
+    #[async_trait]
+    impl BitcoinCoreApi for ScenarioBitcoin {
+        async fn wait_for_block(&self, _height: u32, _num_confirmations: u32) -> Result<Block, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_block_count(&self) -> Result<u64, BitcoinError> {
+            Ok(self.chain.lock().expect("poisoned").bitcoin_height as u64)
+        }
+
+        async fn get_raw_tx(&self, _txid: &Txid, _block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_proof(&self, _txid: Txid, _block_hash: &BlockHash) -> Result<Vec<u8>, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_block_hash(&self, _height: u32) -> Result<BlockHash, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn is_block_known(&self, _block_hash: BlockHash) -> Result<bool, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_new_address<A: PartialAddress + Send + 'static>(&self) -> Result<A, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_new_public_key<P: From<[u8; PUBLIC_KEY_SIZE]> + 'static>(&self) -> Result<P, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn add_new_deposit_key<P: Into<[u8; PUBLIC_KEY_SIZE]> + Send + Sync + 'static>(
+            &self,
+            _public_key: P,
+            _secret_key: Vec<u8>,
+        ) -> Result<(), BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_best_block_hash(&self) -> Result<BlockHash, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_block(&self, _hash: &BlockHash) -> Result<Block, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_block_header(&self, _hash: &BlockHash) -> Result<BlockHeader, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_block_info(&self, _hash: &BlockHash) -> Result<GetBlockResult, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn get_mempool_transactions<'a>(
+            &'a self,
+        ) -> Result<Box<dyn Iterator<Item = Result<Transaction, BitcoinError>> + Send + 'a>, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn wait_for_transaction_metadata(
+            &self,
+            txid: Txid,
+            _num_confirmations: u32,
+        ) -> Result<TransactionMetadata, BitcoinError> {
+            let mut blocks = self.blocks.clone();
+            loop {
+                let confirmed = self.chain.lock().expect("poisoned").confirmed(&txid);
+                if let Some(tx_metadata) = confirmed {
+                    return Ok(tx_metadata);
+                }
+                blocks.recv().await;
+            }
+        }
+
+        async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
+            &self,
+            address: A,
+            sat: u64,
+            request_id: Option<H256>,
+            // no fees are paid on the simulated chain
+            _fee_estimation: FeeEstimation,
+        ) -> Result<LockedTransaction, BitcoinError> {
+            let btc_address = address.encode_str(Network::Regtest)?;
+            let redeem_id = request_id.expect("payments are made for requests");
+            let transaction = Transaction {
+                version: 2,
+                lock_time: 0,
+                input: vec![TxIn {
+                    previous_output: OutPoint {
+                        txid: Txid::default(),
+                        vout: 0,
+                    },
+                    script_sig: Script::new(),
+                    sequence: u32::MAX,
+                    witness: vec![],
+                }],
+                output: vec![
+                    TxOut {
+                        value: sat,
+                        script_pubkey: Address::from_str(&btc_address).expect("valid address").script_pubkey(),
+                    },
+                    TxOut {
+                        value: 0,
+                        script_pubkey: Builder::new()
+                            .push_opcode(opcodes::OP_RETURN)
+                            .push_slice(redeem_id.as_bytes())
+                            .into_script(),
+                    },
+                ],
+            };
+            self.chain.lock().expect("poisoned").payments.insert(
+                transaction.txid(),
+                CreatedPayment {
+                    redeem_id,
+                    btc_address: btc_address.clone(),
+                    transaction: transaction.clone(),
+                },
+            );
+            Ok(LockedTransaction::new(transaction, btc_address, None))
+        }
+
+        async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError> {
+            let txid = transaction.transaction.txid();
+            let mut chain = self.chain.lock().expect("poisoned");
+            let payment = chain.payments.get(&txid).cloned().expect("created by the vault");
+            chain.act(Action::Pay {
+                redeem_id: payment.redeem_id,
+                btc_address: payment.btc_address,
+                amount: payment.transaction.output[0].value,
+            });
+            Ok(txid)
+        }
+
+        async fn create_and_send_transaction<A: PartialAddress + Send + Sync + 'static>(
+            &self,
+            _address: A,
+            _sat: u64,
+            _request_id: Option<H256>,
+        ) -> Result<Txid, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn send_to_address<A: PartialAddress + Send + Sync + 'static>(
+            &self,
+            _address: A,
+            _sat: u64,
+            _request_id: Option<H256>,
+            _num_confirmations: u32,
+        ) -> Result<TransactionMetadata, BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn create_or_load_wallet(&self) -> Result<(), BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn wallet_has_public_key<P>(&self, _public_key: P) -> Result<bool, BitcoinError>
+        where
+            P: Into<[u8; PUBLIC_KEY_SIZE]> + From<[u8; PUBLIC_KEY_SIZE]> + Clone + PartialEq + Send + Sync + 'static,
+        {
+            unimplemented!()
+        }
+
+        async fn import_private_key(&self, _privkey: PrivateKey) -> Result<(), BitcoinError> {
+            unimplemented!()
+        }
+
+        async fn rescan_blockchain(&self, _start_height: usize) -> Result<(), BitcoinError> {
+            unimplemented!()
+        }
+    }
+
+    /// Feeds the steps of a recording to the cancellation scheduler and the redeem payments of
+    /// the vault, against a parachain and wallet answering from the recording.
+    struct Replay {
+        chain: SharedChain,
+        blocks: watch::Sender<u32>,
+        bitcoin: ScenarioBitcoin,
+        scheduler: CancellationScheduler<MockParachain>,
+        active_issues: Vec<ActiveRequest>,
+        list_state: ListState,
+        /// Payments and executions in progress, they run until they wait for a later step.
+        pending: FuturesUnordered<LocalBoxFuture<'static, ()>>,
+        request_ids: BTreeSet<H256>,
+    }
+
+    impl Replay {
+        fn new() -> Self {
+            let chain = SharedChain::default();
+            let (blocks, receiver) = watch::channel(0);
+            Self {
+                bitcoin: ScenarioBitcoin {
+                    chain: chain.clone(),
+                    blocks: receiver,
+                },
+                scheduler: CancellationScheduler::new(parachain(&chain), 0, 0, AccountId::default()),
+                chain,
+                blocks,
+                active_issues: vec![],
+                list_state: ListState::Invalid,
+                pending: FuturesUnordered::new(),
+                request_ids: BTreeSet::new(),
+            }
+        }
+
+        /// Start tracking the request, forgetting the state of other scenarios with the same id.
+        fn seen(&mut self, request_id: H256, kind: RequestKind) {
+            request_state::forget(request_id);
+            request_state::seen(request_id, kind);
+            self.request_ids.insert(request_id);
+        }
+
+        async fn notify_scheduler(&mut self, event: Event) {
+            let list_state = std::mem::replace(&mut self.list_state, ListState::Invalid);
+            self.list_state = self
+                .scheduler
+                .process_event::<IssueCanceller>(event, &mut self.active_issues, list_state)
+                .await
+                .unwrap();
+        }
+
+        async fn apply(&mut self, step: usize, scenario_step: ScenarioStep) {
+            self.chain.lock().expect("poisoned").step = step;
+            match scenario_step {
+                ScenarioStep::IssuePeriod { period } => self.chain.lock().expect("poisoned").issue_period = period,
+                ScenarioStep::ParachainBlock { height } => {
+                    self.chain.lock().expect("poisoned").active_block = height;
+                    self.notify_scheduler(Event::ParachainBlock(height)).await;
+                }
+                ScenarioStep::BitcoinBlock {
+                    height,
+                    hash,
+                    transactions,
+                } => {
+                    {
+                        let mut chain = self.chain.lock().expect("poisoned");
+                        chain.bitcoin_height = height;
+                        let block_hash = BlockHash::from_str(&hash).unwrap();
+                        for transaction in transactions {
+                            let transaction = deserialize(&hex::decode(transaction).unwrap()).unwrap();
+                            chain.transactions.push((transaction, block_hash, height));
+                        }
+                    }
+                    self.blocks.broadcast(height).unwrap();
+                    self.notify_scheduler(Event::BitcoinBlock(height)).await;
+                }
+                ScenarioStep::IssueRequested { issue_id } => {
+                    self.seen(issue_id, RequestKind::Issue);
+                    {
+                        let mut chain = self.chain.lock().expect("poisoned");
+                        let issue = InterBtcIssueRequest {
+                            opentime: chain.active_block,
+                            btc_height: chain.bitcoin_height,
+                            ..Default::default()
+                        };
+                        chain.issues.insert(issue_id, issue);
+                    }
+                    self.notify_scheduler(Event::Opened).await;
+                }
+                ScenarioStep::IssueExecuted { issue_id } => {
+                    self.chain.lock().expect("poisoned").issues.remove(&issue_id);
+                    self.notify_scheduler(Event::Executed(issue_id)).await;
+                    // as observed by the listener for finalized requests
+                    request_state::transition(issue_id, RequestState::Finalized);
+                }
+                ScenarioStep::RedeemRequested {
+                    redeem_id,
+                    btc_address,
+                    amount,
+                    parachain_height,
+                    bitcoin_height,
+                    period,
+                } => {
+                    self.seen(redeem_id, RequestKind::Redeem);
+                    let request = Request::redeem(
+                        redeem_id,
+                        amount.into(),
+                        BtcAddress::decode_str(&btc_address).unwrap(),
+                        parachain_height,
+                        bitcoin_height,
+                        period,
+                        Duration::from_secs(0),
+                    )
+                    .unwrap();
+                    let parachain_rpc = parachain(&self.chain);
+                    let btc_rpc = self.bitcoin.clone();
+                    self.pending.push(
+                        async move {
+                            let _ = request
+                                .pay_and_execute(
+                                    parachain_rpc,
+                                    btc_rpc,
+                                    1,
+                                    ProofSafety::default(),
+                                    PaymentApproval::default(),
+                                )
+                                .await;
+                        }
+                        .boxed_local(),
+                    );
+                }
+                ScenarioStep::RedeemExecuted { redeem_id } => {
+                    // as observed by the listener for finalized requests
+                    request_state::transition(redeem_id, RequestState::Finalized);
+                }
+            }
+            // let the vault act on the step before the next one
+            while let Some(Some(())) = self.pending.next().now_or_never() {}
+        }
+
+        fn outcome(self) -> Outcome {
+            let states = self
+                .request_ids
+                .iter()
+                .filter_map(|request_id| Some((*request_id, request_state::get_record(*request_id)?.state)))
+                .collect();
+            let actions = std::mem::take(&mut self.chain.lock().expect("poisoned").actions);
+            Outcome { actions, states }
+        }
+    }
+
+    async fn replay(path: &Path) -> Outcome {
+        let mut replay = Replay::new();
+        for (step, line) in fs::read_to_string(path).unwrap().lines().enumerate() {
+            replay.apply(step, serde_json::from_str(line).unwrap()).await;
+        }
+        replay.outcome()
+    }
+
+    /// Replays every recording in `fixtures/replay` and compares the outcome with the golden
+    /// file next to it. Run with `UPDATE_GOLDEN=1` to write the golden files instead.
+    #[tokio::test]
+    async fn test_replay_scenarios() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/replay");
+        let mut scenarios = 0;
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("jsonl") {
+                continue;
+            }
+            scenarios += 1;
+            let outcome = replay(&path).await;
+            let golden_path = path.with_extension("golden.json");
+            if std::env::var("UPDATE_GOLDEN").is_ok() {
+                fs::write(&golden_path, serde_json::to_string_pretty(&outcome).unwrap() + "\n").unwrap();
+                continue;
+            }
+            let golden: Outcome = serde_json::from_str(&fs::read_to_string(&golden_path).unwrap()).unwrap();
+            assert_eq!(outcome, golden, "unexpected outcome of {}", path.display());
+        }
+        assert!(scenarios > 0);
+    }
+
+    #[test]
+    fn test_step_encoding() {
+        let step = ScenarioStep::IssueRequested {
+            issue_id: H256::from_low_u64_be(1),
+        };
+        let line = serde_json::to_string(&step).unwrap();
+        assert!(line.starts_with(r#"{"type":"issue_requested","issue_id":"0x"#));
+        assert_eq!(serde_json::from_str::<ScenarioStep>(&line).unwrap(), step);
+    }
+}
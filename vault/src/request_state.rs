@@ -0,0 +1,641 @@
+use crate::{
+    execution::RequestType,
+    replay::{self, ScenarioStep},
+    Error,
+};
+use bitcoin::TransactionMetadata;
+use futures::try_join;
+use lazy_static::lazy_static;
+use runtime::{
+    pallets::{
+        issue::{CancelIssueEvent, ExecuteIssueEvent},
+        redeem::{CancelRedeemEvent, ExecuteRedeemEvent},
+        refund::ExecuteRefundEvent,
+        replace::{CancelReplaceEvent, ExecuteReplaceEvent},
+    },
+    substrate_subxt::Error as SubxtError,
+    InterBtcParachain, InterBtcRuntime,
+};
+use serde::{Deserialize, Serialize};
+use service::Error as ServiceError;
+use sp_core::H256;
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast;
+use tracing::Instrument;
+
+/// Maximum number of finalized or expired requests that are kept, the oldest are evicted first.
+const MAX_FINISHED_REQUESTS: usize = 1000;
+
+/// Number of transitions buffered for each subscriber before it starts lagging.
+const TRANSITION_CHANNEL_CAPACITY: usize = 256;
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+    static ref TRANSITIONS: broadcast::Sender<Transition> = broadcast::channel(TRANSITION_CHANNEL_CAPACITY).0;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKind {
+    Issue,
+    Redeem,
+    Replace,
+    Refund,
+}
+
+impl From<&RequestType> for RequestKind {
+    fn from(request_type: &RequestType) -> Self {
+        match request_type {
+            RequestType::Redeem => RequestKind::Redeem,
+            RequestType::Replace => RequestKind::Replace,
+            RequestType::Refund => RequestKind::Refund,
+        }
+    }
+}
+
+/// State of a request as processed by the vault. Requests move forward through
+/// `Seen → Paid → Confirmed → Executed → Finalized`, or end up `Failed` (from which they
+/// can be retried) or `Expired`. For issues the payment is made by the user, so they
+/// move from `Seen` to `Confirmed` directly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RequestState {
+    /// The request was observed, no payment has been made yet.
+    Seen,
+    /// The bitcoin payment was broadcast.
+    Paid { txid: String },
+    /// The payment is confirmed on bitcoin and by the relay, the proof can be submitted.
+    Confirmed { txid: String, block_hash: String },
+    /// The execution was included in a parachain block.
+    Executed,
+    /// The execution was observed in a finalized parachain block.
+    Finalized,
+    /// Processing stopped with an error, the request may be retried.
+    Failed { reason: String },
+    /// The request can no longer be executed, or was cancelled.
+    Expired,
+}
+
+impl RequestState {
+    /// Position on the happy path, `None` for the error and expiry branches.
+    fn rank(&self) -> Option<u8> {
+        match self {
+            RequestState::Seen => Some(0),
+            RequestState::Paid { .. } => Some(1),
+            RequestState::Confirmed { .. } => Some(2),
+            RequestState::Executed => Some(3),
+            RequestState::Finalized => Some(4),
+            RequestState::Failed { .. } | RequestState::Expired => None,
+        }
+    }
+
+    pub fn confirmed(tx_metadata: &TransactionMetadata) -> Self {
+        RequestState::Confirmed {
+            txid: tx_metadata.txid.to_string(),
+            block_hash: tx_metadata.block_hash.to_string(),
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, RequestState::Finalized | RequestState::Expired)
+    }
+
+    /// Txid of the payment, if one was made.
+    pub fn payment(&self) -> Option<&str> {
+        match self {
+            RequestState::Paid { txid } | RequestState::Confirmed { txid, .. } => Some(txid),
+            _ => None,
+        }
+    }
+
+    pub fn can_transition_to(&self, next: &RequestState) -> bool {
+        match (self, next) {
+            (from, _) if from.is_terminal() => false,
+            // an executed request can no longer fail, but it expires if it was cancelled on
+            // chain before the execution was finalized
+            (RequestState::Executed, next) => matches!(next, RequestState::Finalized | RequestState::Expired),
+            (_, RequestState::Failed { .. }) | (_, RequestState::Expired) => true,
+            (RequestState::Failed { .. }, _) => true,
+            // the block containing the payment was reorganized out of the main chain, or the
+            // payment was dropped and made again
+            (RequestState::Confirmed { .. }, RequestState::Paid { .. })
+            | (RequestState::Confirmed { .. }, RequestState::Confirmed { .. })
+            | (RequestState::Paid { .. }, RequestState::Paid { .. }) => true,
+            (from, to) => to.rank() > from.rank(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestRecord {
+    pub request_id: H256,
+    pub kind: RequestKind,
+    pub state: RequestState,
+    /// Txid of the last payment, kept when the request fails so that it is not paid twice.
+    #[serde(default)]
+    pub payment: Option<String>,
+    /// Unix timestamp (seconds) of the last transition.
+    pub updated_at: u64,
+    /// Identifier generated when the request is first seen, attached to the log lines of
+    /// everything the vault does for the request so that it can be traced with one query.
+    #[serde(default)]
+    pub correlation_id: String,
+}
+
+/// A change of the state of a request, published to the subscribers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition {
+    pub request_id: H256,
+    pub kind: RequestKind,
+    /// `None` when the request is first seen.
+    pub from: Option<RequestState>,
+    pub to: RequestState,
+}
+
+#[derive(Default)]
+pub(crate) struct Registry {
+    records: HashMap<H256, RequestRecord>,
+    path: Option<PathBuf>,
+    /// Incremented with every snapshot, so that an older one never overwrites a newer one.
+    generation: u64,
+    /// Generation of the snapshot last written to the file.
+    written: Arc<Mutex<u64>>,
+}
+
+/// The records of the registry as written to the state file.
+struct Snapshot {
+    path: PathBuf,
+    contents: String,
+    generation: u64,
+    written: Arc<Mutex<u64>>,
+}
+
+impl Snapshot {
+    /// Write the records to a temporary file that replaces the state file, so that a crash
+    /// does not leave a partially written file behind. Does nothing if a newer snapshot was
+    /// written in the meantime.
+    fn write(self) -> Result<(), Error> {
+        let mut written = self.written.lock().expect("poisoned");
+        if *written >= self.generation {
+            return Ok(());
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, self.contents)?;
+        fs::rename(&tmp_path, &self.path)?;
+        *written = self.generation;
+        Ok(())
+    }
+}
+
+impl Registry {
+    /// Start tracking the request, does nothing if it is already tracked.
+    pub(crate) fn seen(&mut self, request_id: H256, kind: RequestKind, now: u64) -> Option<Transition> {
+        if self.records.contains_key(&request_id) {
+            return None;
+        }
+        self.records.insert(
+            request_id,
+            RequestRecord {
+                request_id,
+                kind,
+                state: RequestState::Seen,
+                payment: None,
+                updated_at: now,
+                correlation_id: new_correlation_id(),
+            },
+        );
+        Some(Transition {
+            request_id,
+            kind,
+            from: None,
+            to: RequestState::Seen,
+        })
+    }
+
+    /// Move a tracked request to the given state. Returns `None` if the request is not
+    /// tracked, already in that state, or the transition is not allowed.
+    pub(crate) fn transition(&mut self, request_id: H256, to: RequestState, now: u64) -> Option<Transition> {
+        let record = self.records.get_mut(&request_id)?;
+        if record.state == to {
+            return None;
+        }
+        if !record.state.can_transition_to(&to) {
+            tracing::warn!(
+                "Ignoring transition of {:?} #{} from {:?} to {:?}",
+                record.kind,
+                request_id,
+                record.state,
+                to
+            );
+            return None;
+        }
+        if let Some(txid) = to.payment() {
+            record.payment = Some(txid.to_string());
+        }
+        let from = std::mem::replace(&mut record.state, to.clone());
+        record.updated_at = now;
+        let transition = Transition {
+            request_id,
+            kind: record.kind,
+            from: Some(from),
+            to,
+        };
+        if transition.to.is_terminal() {
+            self.evict_finished();
+        }
+        Some(transition)
+    }
+
+    fn evict_finished(&mut self) {
+        let mut finished = self
+            .records
+            .values()
+            .filter(|record| record.state.is_terminal())
+            .map(|record| (record.updated_at, record.request_id))
+            .collect::<Vec<_>>();
+        if finished.len() <= MAX_FINISHED_REQUESTS {
+            return;
+        }
+        finished.sort();
+        for (_, request_id) in finished.iter().take(finished.len() - MAX_FINISHED_REQUESTS) {
+            self.records.remove(request_id);
+        }
+    }
+
+    /// Merge the records of the file into the registry, records already tracked take precedence.
+    fn load(&mut self, path: PathBuf) -> Result<(), Error> {
+        let records: Vec<RequestRecord> = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        for mut record in records {
+            // recorded before correlation ids were introduced
+            if record.correlation_id.is_empty() {
+                record.correlation_id = new_correlation_id();
+            }
+            self.records.entry(record.request_id).or_insert(record);
+        }
+        self.path = Some(path);
+        Ok(())
+    }
+
+    /// Serialize the records to be written to the state file, `None` if they are not persisted.
+    fn snapshot(&mut self) -> Result<Option<Snapshot>, Error> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return Ok(None),
+        };
+        let mut records = self.records.values().collect::<Vec<_>>();
+        records.sort_by_key(|record| (record.updated_at, record.request_id));
+        let contents = serde_json::to_string(&records)?;
+        self.generation += 1;
+        Ok(Some(Snapshot {
+            path,
+            contents,
+            generation: self.generation,
+            written: self.written.clone(),
+        }))
+    }
+}
+
+fn new_correlation_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Write the snapshot off the async runtime, if called from within it.
+fn write_snapshot(snapshot: Snapshot) {
+    let write = move || {
+        if let Err(e) = snapshot.write() {
+            tracing::error!("Failed to persist request states: {}", e);
+        }
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(write);
+        }
+        Err(_) => write(),
+    }
+}
+
+fn apply(update: impl FnOnce(&mut Registry, u64) -> Option<Transition>) {
+    let (transition, snapshot, correlation_id) = {
+        let mut registry = match REGISTRY.lock() {
+            Ok(registry) => registry,
+            Err(_) => return,
+        };
+        let transition = match update(&mut registry, unix_now()) {
+            Some(transition) => transition,
+            None => return,
+        };
+        let snapshot = registry.snapshot().unwrap_or_else(|e| {
+            tracing::error!("Failed to persist request states: {}", e);
+            None
+        });
+        let correlation_id = registry
+            .records
+            .get(&transition.request_id)
+            .map(|record| record.correlation_id.clone())
+            .unwrap_or_default();
+        (transition, snapshot, correlation_id)
+    };
+    // the file is written after releasing the lock, so that the registry is not blocked on IO
+    if let Some(snapshot) = snapshot {
+        write_snapshot(snapshot);
+    }
+    tracing::info!(
+        correlation_id = %correlation_id,
+        "{:?} #{} transitioned from {:?} to {:?}",
+        transition.kind,
+        transition.request_id,
+        transition.from,
+        transition.to
+    );
+    // there may be no subscribers
+    let _ = TRANSITIONS.send(transition);
+}
+
+/// Keep the request states in the given file, loading the states recorded before a restart.
+pub fn persist_to(path: PathBuf) -> Result<(), Error> {
+    let snapshot = {
+        let mut registry = REGISTRY.lock().expect("poisoned");
+        registry.load(path)?;
+        registry.snapshot()?
+    };
+    match snapshot {
+        Some(snapshot) => snapshot.write(),
+        None => Ok(()),
+    }
+}
+
+/// Receive all subsequent transitions.
+pub fn subscribe_transitions() -> broadcast::Receiver<Transition> {
+    TRANSITIONS.subscribe()
+}
+
+/// Current state of the request, `None` if it is not tracked.
+pub fn get_record(request_id: H256) -> Option<RequestRecord> {
+    REGISTRY.lock().ok()?.records.get(&request_id).cloned()
+}
+
+/// Ids of the tracked requests matching the predicate.
+pub(crate) fn find(predicate: impl Fn(&RequestRecord) -> bool) -> Vec<H256> {
+    match REGISTRY.lock() {
+        Ok(registry) => registry
+            .records
+            .values()
+            .filter(|record| predicate(record))
+            .map(|record| record.request_id)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Correlation id of the request, `None` if it is not tracked.
+pub fn correlation_id(request_id: H256) -> Option<String> {
+    Some(REGISTRY.lock().ok()?.records.get(&request_id)?.correlation_id.clone())
+}
+
+/// Span carrying the correlation id of the request, under which everything done for the
+/// request is run so that all its log lines (including those of the submitted extrinsics
+/// and bitcoin payments) carry the id. The request should be seen before the span is created.
+pub fn span(request_id: H256) -> tracing::Span {
+    let correlation_id = correlation_id(request_id).unwrap_or_default();
+    tracing::info_span!("request", %correlation_id, request_id = ?request_id)
+}
+
+/// Run the future under the span of the request, with the execute and cancel extrinsics it
+/// submits batched with a remark of the correlation id, so that they can be found on chain.
+pub async fn correlate<F: Future>(request_id: H256, future: F) -> F::Output {
+    let future = future.instrument(span(request_id));
+    match correlation_id(request_id) {
+        Some(correlation_id) => runtime::with_remark(correlation_id.into_bytes(), future).await,
+        None => future.await,
+    }
+}
+
+/// Txid of the payment recorded for the request, if the states are persisted. Used to
+/// avoid paying again for a request whose payment is not found after a restart.
+pub fn recorded_payment(request_id: H256) -> Option<String> {
+    let registry = REGISTRY.lock().ok()?;
+    registry.path.as_ref()?;
+    registry.records.get(&request_id)?.payment.clone()
+}
+
+/// Stop tracking the request, so that a replayed scenario starts from a clean state.
+#[cfg(test)]
+pub(crate) fn forget(request_id: H256) {
+    if let Ok(mut registry) = REGISTRY.lock() {
+        registry.records.remove(&request_id);
+    }
+}
+
+/// Start tracking a request when it is observed. Does nothing if it is already tracked,
+/// e.g. when it is picked up again after a restart.
+pub fn seen(request_id: H256, kind: RequestKind) {
+    apply(|registry, now| registry.seen(request_id, kind, now))
+}
+
+/// Move a tracked request to the given state, invalid transitions are logged and ignored.
+pub fn transition(request_id: H256, to: RequestState) {
+    apply(|registry, now| registry.transition(request_id, to, now))
+}
+
+/// Listen for the execution and cancellation of tracked requests in finalized blocks.
+/// Events of requests that are not tracked, e.g. those of other vaults, are ignored.
+///
+/// # Arguments
+///
+/// * `parachain_rpc` - the parachain RPC handle
+pub async fn listen_for_finalized_requests(parachain_rpc: InterBtcParachain) -> Result<(), ServiceError> {
+    let parachain_rpc = &parachain_rpc;
+    let on_error = |error: SubxtError| tracing::error!("Error reading request event: {}", error.to_string());
+    try_join!(
+        parachain_rpc.on_event::<ExecuteIssueEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { transition(event.issue_id, RequestState::Finalized) },
+            on_error
+        ),
+        parachain_rpc.on_event::<ExecuteRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move {
+                if get_record(event.redeem_id).is_some() {
+                    replay::record(|_| {
+                        Ok(ScenarioStep::RedeemExecuted {
+                            redeem_id: event.redeem_id,
+                        })
+                    });
+                }
+                transition(event.redeem_id, RequestState::Finalized)
+            },
+            on_error
+        ),
+        parachain_rpc.on_event::<ExecuteReplaceEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { transition(event.replace_id, RequestState::Finalized) },
+            on_error
+        ),
+        parachain_rpc.on_event::<ExecuteRefundEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { transition(event.refund_id, RequestState::Finalized) },
+            on_error
+        ),
+        parachain_rpc.on_event::<CancelIssueEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { transition(event.issue_id, RequestState::Expired) },
+            on_error
+        ),
+        parachain_rpc.on_event::<CancelRedeemEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { transition(event.redeem_id, RequestState::Expired) },
+            on_error
+        ),
+        parachain_rpc.on_event::<CancelReplaceEvent<InterBtcRuntime>, _, _, _>(
+            |event| async move { transition(event.replace_id, RequestState::Expired) },
+            on_error
+        ),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paid() -> RequestState {
+        RequestState::Paid { txid: "ab".to_string() }
+    }
+
+    fn confirmed() -> RequestState {
+        RequestState::Confirmed {
+            txid: "ab".to_string(),
+            block_hash: "cd".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_allowed_transitions() {
+        assert!(RequestState::Seen.can_transition_to(&paid()));
+        assert!(paid().can_transition_to(&confirmed()));
+        assert!(confirmed().can_transition_to(&RequestState::Executed));
+        assert!(RequestState::Executed.can_transition_to(&RequestState::Finalized));
+        // cancelled on chain before the execution was finalized
+        assert!(RequestState::Executed.can_transition_to(&RequestState::Expired));
+        // issues are paid by the user
+        assert!(RequestState::Seen.can_transition_to(&confirmed()));
+        // reorgs
+        assert!(confirmed().can_transition_to(&paid()));
+        // paid again after the payment was dropped
+        assert!(paid().can_transition_to(&RequestState::Paid { txid: "ef".to_string() }));
+        // retries
+        let failed = RequestState::Failed {
+            reason: "timeout".to_string(),
+        };
+        assert!(paid().can_transition_to(&failed));
+        assert!(failed.can_transition_to(&RequestState::Seen));
+
+        assert!(!paid().can_transition_to(&RequestState::Seen));
+        assert!(!RequestState::Executed.can_transition_to(&failed));
+        assert!(!RequestState::Executed.can_transition_to(&paid()));
+        assert!(!RequestState::Expired.can_transition_to(&RequestState::Seen));
+        assert!(!RequestState::Finalized.can_transition_to(&failed));
+    }
+
+    #[test]
+    fn test_registry_transitions() {
+        let mut registry = Registry::default();
+        let request_id = H256::from_low_u64_be(1);
+
+        // untracked requests are ignored
+        assert_eq!(registry.transition(request_id, RequestState::Expired, 0), None);
+
+        assert!(registry.seen(request_id, RequestKind::Redeem, 0).is_some());
+        assert_eq!(registry.seen(request_id, RequestKind::Redeem, 1), None);
+        assert_eq!(
+            registry.transition(request_id, paid(), 2),
+            Some(Transition {
+                request_id,
+                kind: RequestKind::Redeem,
+                from: Some(RequestState::Seen),
+                to: paid(),
+            })
+        );
+        assert_eq!(registry.transition(request_id, paid(), 3), None);
+        assert_eq!(registry.transition(request_id, RequestState::Seen, 3), None);
+        assert_eq!(registry.records[&request_id].state, paid());
+        assert_eq!(registry.records[&request_id].updated_at, 2);
+
+        // the payment is remembered when the request fails
+        let failed = RequestState::Failed {
+            reason: "timeout".to_string(),
+        };
+        assert!(registry.transition(request_id, failed, 4).is_some());
+        assert_eq!(registry.records[&request_id].payment, Some("ab".to_string()));
+    }
+
+    #[test]
+    fn test_finished_requests_are_bounded() {
+        let mut registry = Registry::default();
+        // open requests are never evicted
+        registry.seen(H256::zero(), RequestKind::Issue, 0);
+        for i in 1..=MAX_FINISHED_REQUESTS as u64 + 1 {
+            let request_id = H256::from_low_u64_be(i);
+            registry.seen(request_id, RequestKind::Issue, i);
+            registry.transition(request_id, RequestState::Expired, i);
+        }
+        assert_eq!(registry.records.len(), MAX_FINISHED_REQUESTS + 1);
+        assert!(registry.records.contains_key(&H256::zero()));
+        assert!(!registry.records.contains_key(&H256::from_low_u64_be(1)));
+    }
+
+    #[test]
+    fn test_correlation_ids() {
+        let mut registry = Registry::default();
+        registry.seen(H256::from_low_u64_be(1), RequestKind::Issue, 0);
+        registry.seen(H256::from_low_u64_be(2), RequestKind::Issue, 0);
+        let first = registry.records[&H256::from_low_u64_be(1)].correlation_id.clone();
+        assert_eq!(first.len(), 16);
+        assert_ne!(first, registry.records[&H256::from_low_u64_be(2)].correlation_id);
+
+        // records written before correlation ids were introduced
+        let record: RequestRecord =
+            serde_json::from_str(r#"{"request_id":"0x0000000000000000000000000000000000000000000000000000000000000001","kind":"issue","state":{"state":"seen"},"updated_at":0}"#)
+                .unwrap();
+        assert!(record.correlation_id.is_empty());
+    }
+
+    #[test]
+    fn test_states_are_persisted() {
+        let path = std::env::temp_dir().join(format!("request-state-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let request_id = H256::from_low_u64_be(1);
+
+        let mut registry = Registry::default();
+        registry.load(path.clone()).unwrap();
+        registry.seen(request_id, RequestKind::Replace, 0);
+        registry.transition(request_id, confirmed(), 1);
+        let stale = registry.snapshot().unwrap().unwrap();
+        registry.transition(request_id, RequestState::Executed, 2);
+        registry.snapshot().unwrap().unwrap().write().unwrap();
+        // an older snapshot written late does not overwrite the newer one
+        stale.write().unwrap();
+
+        let mut reopened = Registry::default();
+        reopened.load(path.clone()).unwrap();
+        assert_eq!(reopened.records[&request_id].state, RequestState::Executed);
+        assert_eq!(reopened.records[&request_id].payment, Some("ab".to_string()));
+        assert_eq!(
+            reopened.records[&request_id].correlation_id,
+            registry.records[&request_id].correlation_id
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
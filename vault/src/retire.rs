@@ -0,0 +1,271 @@
+use crate::Error;
+use bitcoin::{BitcoinCore, FeeEstimation};
+use chrono::{DateTime, NaiveDate, Utc};
+use runtime::{CollateralAmount, InterBtcParachain, ReplacePallet, UtilFuncs, VaultRegistryPallet, WrappedAmount};
+use serde::Serialize;
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::time::delay_for;
+
+const RETIREMENT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time after which an unaccepted replace request is withdrawn and requested again
+/// with half the amount.
+const REPLACE_ACCEPTANCE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Point in time by which the vault should be retired, parsed from an RFC 3339
+/// timestamp or a date (midnight UTC).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetirementDeadline(pub DateTime<Utc>);
+
+impl FromStr for RetirementDeadline {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        if let Ok(date_time) = DateTime::parse_from_rfc3339(src) {
+            return Ok(Self(date_time.with_timezone(&Utc)));
+        }
+        NaiveDate::parse_from_str(src, "%Y-%m-%d")
+            .map(|date| Self(DateTime::from_utc(date.and_hms(0, 0, 0), Utc)))
+            .map_err(|_| format!("expected YYYY-MM-DD or an RFC 3339 timestamp, got {}", src))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetirementPlan {
+    pub deadline: RetirementDeadline,
+    /// Amount of the first replace request, adjusted to what other vaults accept.
+    pub initial_chunk: Option<u128>,
+    /// Griefing collateral offered with each replace request, as a percentage of the
+    /// collateral required for the requested amount.
+    pub griefing_collateral_percent: u128,
+    /// Address to which the remaining bitcoin is sent once all tokens are replaced.
+    pub sweep_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetirementReport {
+    /// True if all tokens were replaced before the deadline.
+    pub completed: bool,
+    pub replace_requests: u32,
+    pub withdrawn_replace_requests: u32,
+    pub replaced_tokens: u128,
+    pub remaining_tokens: u128,
+    pub withdrawn_collateral: u128,
+    /// The sweep is split over several transactions if a single one would be too large.
+    pub sweep_txids: Vec<String>,
+    /// Amount sent to the sweep address, including the fees.
+    pub swept_amount: u64,
+}
+
+/// Sizes replace requests to what the market accepts: the amount grows after an accepted
+/// request and shrinks after one that was not accepted in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChunkSize {
+    amount: u128,
+}
+
+impl ChunkSize {
+    fn accepted(&mut self) {
+        self.amount = self.amount.saturating_mul(2);
+    }
+
+    fn rejected(&mut self) {
+        self.amount = (self.amount / 2).max(1);
+    }
+
+    fn next(&self, remaining: u128) -> u128 {
+        self.amount.min(remaining)
+    }
+}
+
+/// Collateral kept on top of the required collateral when withdrawing the excess, in percent
+/// of the required collateral, so that a rise of the exchange rate does not immediately bring
+/// the vault close to liquidation.
+const COLLATERAL_MARGIN_PERCENT: u128 = 10;
+
+/// Withdraw the collateral not needed for the issued tokens, apart from a margin, so that
+/// hardly any new issues can be requested from this vault. Returns the withdrawn amount.
+pub(crate) async fn stop_new_issues(parachain_rpc: &InterBtcParachain) -> Result<CollateralAmount, Error> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+    let vault = parachain_rpc.get_vault(vault_id.clone()).await?;
+    let required = parachain_rpc.get_required_collateral_for_vault(vault_id).await?;
+    let kept = required
+        .checked_mul(100 + COLLATERAL_MARGIN_PERCENT)?
+        .checked_div(100)?;
+    let excess = CollateralAmount::from_planck(vault.backing_collateral).saturating_sub(kept);
+    if !excess.is_zero() {
+        tracing::info!("Withdrawing {} excess collateral to stop new issues", excess);
+        parachain_rpc.withdraw_collateral(excess).await?;
+    }
+    Ok(excess)
+}
+
+/// Virtual sizes of a P2WPKH input, of an output and of the rest of a transaction.
+const INPUT_VSIZE: u64 = 68;
+const OUTPUT_VSIZE: u64 = 31;
+const TRANSACTION_OVERHEAD_VSIZE: u64 = 11;
+
+/// Virtual size of a sweep spending the given number of outputs, with room for a change output.
+fn sweep_vsize(inputs: usize) -> u64 {
+    TRANSACTION_OVERHEAD_VSIZE + (inputs as u64).saturating_mul(INPUT_VSIZE) + 2 * OUTPUT_VSIZE
+}
+
+/// Send all remaining bitcoin to the sweep address, the fees are deducted from the swept
+/// amount. Nothing is sent if the balance would not cover the fee of spending all outputs
+/// of the wallet at the estimated fee rate.
+async fn sweep(bitcoin_core: &BitcoinCore, address: &str, report: &mut RetirementReport) -> Result<(), Error> {
+    let address = bitcoin::validate_address(address, bitcoin_core.network())?.payload;
+    let fee_rate = match bitcoin_core.estimate_fee_rate(FeeEstimation::economical()).await? {
+        Some(fee_rate) => fee_rate,
+        // no estimate yet, e.g. shortly after bitcoind started
+        None => bitcoin_core.mempool_fee_histogram().await?.fee_rate_for_blocks(1),
+    };
+    let inputs = bitcoin_core.list_unspent_outpoints().await?.len();
+    let fee = bitcoin::fee_for_vsize(fee_rate, sweep_vsize(inputs))?;
+    let amount = bitcoin_core.get_balances().await?.trusted;
+    if amount <= fee.as_sat() {
+        tracing::info!(
+            "Not sweeping {} sat, less than the estimated fee of {} sat",
+            amount,
+            fee.as_sat()
+        );
+        return Ok(());
+    }
+    let txids = bitcoin_core.send_split(address, amount).await?;
+    tracing::info!("Swept {} sat in {} transaction(s)", amount, txids.len());
+    report.sweep_txids = txids.iter().map(ToString::to_string).collect();
+    report.swept_amount = amount;
+    Ok(())
+}
+
+/// Retire the vault by the deadline: stop new issues, replace all issued tokens in chunks
+/// sized to what other vaults accept, then withdraw the collateral and sweep the remaining
+/// bitcoin. This runs within the vault service, which pays for the accepted replaces, so
+/// that all extrinsics are submitted by its signer and all payments made from its wallet.
+pub async fn retire_vault(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    plan: RetirementPlan,
+) -> Result<RetirementReport, Error> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+    let mut report = RetirementReport::default();
+
+    let initial_tokens = parachain_rpc.get_vault(vault_id.clone()).await?.issued_tokens;
+    let mut chunk = ChunkSize {
+        amount: plan.initial_chunk.unwrap_or(initial_tokens).max(1),
+    };
+    let mut requested_at: Option<Instant> = None;
+    // the collateral freed by every replace could back new issues, so it is withdrawn as well
+    let mut issued_at_withdrawal: Option<u128> = None;
+
+    loop {
+        let vault = parachain_rpc.get_vault(vault_id.clone()).await?;
+        if issued_at_withdrawal.map_or(true, |issued| vault.issued_tokens < issued) {
+            report.withdrawn_collateral = report
+                .withdrawn_collateral
+                .saturating_add(stop_new_issues(parachain_rpc).await?.planck());
+            issued_at_withdrawal = Some(vault.issued_tokens);
+        }
+        report.replaced_tokens = initial_tokens.saturating_sub(vault.issued_tokens);
+        report.remaining_tokens = vault.issued_tokens;
+        if vault.issued_tokens == 0 && vault.to_be_redeemed_tokens == 0 {
+            report.completed = true;
+            break;
+        }
+        if Utc::now() > plan.deadline.0 {
+            tracing::error!(
+                "Retirement deadline passed with {} tokens remaining",
+                vault.issued_tokens
+            );
+            break;
+        }
+
+        if vault.to_be_replaced_tokens == 0 {
+            if requested_at.take().is_some() {
+                chunk.accepted();
+            }
+            // tokens being redeemed or replaced can not be replaced again
+            let replaceable = vault
+                .issued_tokens
+                .saturating_sub(vault.to_be_redeemed_tokens)
+                .saturating_sub(vault.to_be_replaced_tokens);
+            let amount = chunk.next(replaceable);
+            if amount > 0 {
+                let griefing_collateral = parachain_rpc
+                    .get_required_collateral_for_wrapped(WrappedAmount::from_planck(amount))
+                    .await?
+                    .checked_mul(plan.griefing_collateral_percent)?
+                    .checked_div(100)?;
+                tracing::info!(
+                    "Requesting replace of {} tokens ({} remaining)",
+                    amount,
+                    vault.issued_tokens
+                );
+                parachain_rpc
+                    .request_replace(amount, griefing_collateral.planck())
+                    .await?;
+                report.replace_requests += 1;
+                requested_at = Some(Instant::now());
+            }
+        } else if requested_at.map_or(false, |at| at.elapsed() > REPLACE_ACCEPTANCE_TIMEOUT) {
+            tracing::info!(
+                "Replace of {} tokens was not accepted in time, retrying with a smaller amount",
+                vault.to_be_replaced_tokens
+            );
+            parachain_rpc.withdraw_replace(vault.to_be_replaced_tokens).await?;
+            report.withdrawn_replace_requests += 1;
+            chunk.rejected();
+            requested_at = None;
+        }
+
+        delay_for(RETIREMENT_POLL_INTERVAL).await;
+    }
+
+    if report.completed {
+        // without issued tokens, all collateral is free
+        let vault = parachain_rpc.get_vault(vault_id).await?;
+        if vault.backing_collateral > 0 {
+            parachain_rpc
+                .withdraw_collateral(CollateralAmount::from_planck(vault.backing_collateral))
+                .await?;
+            report.withdrawn_collateral = report.withdrawn_collateral.saturating_add(vault.backing_collateral);
+        }
+        if let Some(address) = &plan.sweep_address {
+            sweep(bitcoin_core, address, &mut report).await?;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_deadline() {
+        let date: RetirementDeadline = "2021-07-01".parse().unwrap();
+        let timestamp: RetirementDeadline = "2021-07-01T00:00:00Z".parse().unwrap();
+        assert_eq!(date, timestamp);
+        assert!("01.07.2021".parse::<RetirementDeadline>().is_err());
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        let mut chunk = ChunkSize { amount: 100 };
+        assert_eq!(chunk.next(50), 50);
+        chunk.rejected();
+        assert_eq!(chunk.next(1000), 50);
+        chunk.accepted();
+        chunk.accepted();
+        assert_eq!(chunk.next(1000), 200);
+    }
+
+    #[test]
+    fn test_sweep_vsize() {
+        assert_eq!(sweep_vsize(1), 141);
+        assert_eq!(sweep_vsize(10), 753);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{issue, Error, VaultServiceConfig};
+use bitcoin::{BitcoinCore, BitcoinCoreApi, PrivateKey};
+use runtime::{
+    AccountId, InterBtcParachain, IssuePallet, IssueRequestStatus, RedeemPallet, RedeemRequestStatus, UtilFuncs,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fs, io::Write, path::Path};
+
+/// Version of the snapshot format, bumped on incompatible changes.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Operational state of a vault, used to migrate it to new infrastructure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub vault_id: AccountId,
+    /// Hash of the vault configuration, to detect changes during migration.
+    pub config_hash: String,
+    /// Height from which the wallet is rescanned on import: the lowest bitcoin height of
+    /// the open issue and redeem requests, or the tip at the time of export if there are none.
+    /// Payments for these requests, both incoming and our own, are found again by the rescan.
+    pub rescan_height: u32,
+    /// Derived deposit keys in WIF format.
+    pub deposit_keys: Vec<String>,
+}
+
+/// The settings that determine how the vault treats requests. Host-specific settings such as
+/// file paths and urls are left out, since they are expected to change during a migration.
+#[derive(Serialize)]
+struct RequestSettings {
+    btc_confirmations: Option<u32>,
+    payment_margin_secs: u64,
+    proof_min_depth: u32,
+    no_auto_replace: bool,
+    no_issue_execution: bool,
+    max_collateral: Option<u128>,
+    approval_threshold: Option<u128>,
+    max_account_exposure: Option<u128>,
+    bitcoin_relay_confirmations: u32,
+}
+
+/// Hash of the JSON encoding of the request settings, which unlike the `Debug` output of the
+/// config does not change with the formatting of unrelated fields.
+pub fn config_hash(config: &VaultServiceConfig) -> Result<String, Error> {
+    let settings = RequestSettings {
+        btc_confirmations: config.btc_confirmations,
+        payment_margin_secs: config.payment_margin_minutes.as_secs(),
+        proof_min_depth: config.proof_min_depth,
+        no_auto_replace: config.no_auto_replace,
+        no_issue_execution: config.no_issue_execution,
+        max_collateral: config.max_collateral,
+        approval_threshold: config.approval_threshold,
+        max_account_exposure: config.max_account_exposure,
+        bitcoin_relay_confirmations: config.bitcoin_relay_confirmations,
+    };
+    Ok(hex::encode(Sha256::digest(&serde_json::to_vec(&settings)?)))
+}
+
+impl Snapshot {
+    /// Write the snapshot to the given path. The file contains private keys, so it is
+    /// only readable by the current user.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let snapshot: Snapshot = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedSnapshotVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Capture the operational state of the vault.
+pub async fn export_snapshot(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    config: &VaultServiceConfig,
+) -> Result<Snapshot, Error> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+
+    let bitcoin_height = bitcoin_core.get_block_count().await? as u32;
+    let issue_requests = parachain_rpc.get_vault_issue_requests(vault_id.clone()).await?;
+    let redeem_requests = parachain_rpc.get_vault_redeem_requests(vault_id.clone()).await?;
+
+    let rescan_height = issue_requests
+        .iter()
+        .filter(|(_, request)| request.status == IssueRequestStatus::Pending)
+        .map(|(_, request)| request.btc_height)
+        .chain(
+            redeem_requests
+                .iter()
+                .filter(|(_, request)| request.status == RedeemRequestStatus::Pending)
+                .map(|(_, request)| request.btc_height),
+        )
+        .min()
+        .unwrap_or(bitcoin_height)
+        .min(bitcoin_height);
+
+    let mut deposit_keys = Vec::with_capacity(issue_requests.len());
+    for (issue_id, request) in issue_requests {
+        let secret = issue::deposit_secret(issue_id, &request.btc_public_key);
+        let deposit_key = bitcoin_core
+            .derive_deposit_key(request.btc_public_key.0, secret)
+            .await?;
+        deposit_keys.push(deposit_key.to_wif());
+    }
+
+    Ok(Snapshot {
+        version: SNAPSHOT_VERSION,
+        vault_id,
+        config_hash: config_hash(config)?,
+        rescan_height,
+        deposit_keys,
+    })
+}
+
+/// Restore the deposit keys of the snapshot into the wallet and rescan the bitcoin chain
+/// from the lowest height of the open requests. Returns the height from which the vault can start scanning.
+pub async fn import_snapshot(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    config: &VaultServiceConfig,
+    snapshot: Snapshot,
+) -> Result<u32, Error> {
+    if &snapshot.vault_id != parachain_rpc.get_account_id() {
+        return Err(Error::SnapshotVaultMismatch);
+    }
+    if snapshot.config_hash != config_hash(config)? {
+        tracing::warn!("Vault configuration differs from the exported configuration");
+    }
+
+    bitcoin_core.create_or_load_wallet().await?;
+
+    let deposit_keys = snapshot
+        .deposit_keys
+        .iter()
+        .map(|key| PrivateKey::from_wif(key).map_err(bitcoin::Error::from))
+        .collect::<Result<Vec<_>, _>>()?;
+    tracing::info!(
+        "Importing {} deposit keys, rescanning from height {}...",
+        deposit_keys.len(),
+        snapshot.rescan_height
+    );
+    bitcoin_core
+        .import_private_keys(&deposit_keys, snapshot.rescan_height as usize)
+        .await?;
+
+    Ok(snapshot.rescan_height)
+}
@@ -1,9 +1,34 @@
+#[cfg(feature = "postgres")]
+use crate::analytics::run_analytics_sink;
 use crate::{
-    collateral::lock_required_collateral, faucet, issue, relay::run_relayer, service::*, Error, Event, IssueRequests,
-    Vaults, CHAIN_HEIGHT_POLLING_INTERVAL,
+    appeal::{listen_for_own_theft, monitor_external_spends},
+    ban::{monitor_ban_status, BanStatus},
+    collateral::{lock_collateral_on_deposit, lock_required_collateral},
+    concurrency::TaskLimiter,
+    degradation::{self, DegradationThresholds},
+    exposure::track_exposure,
+    extrinsic_queue::ExtrinsicQueue,
+    faucet,
+    fee_reserve::FeeReserve,
+    hooks::{self, Hooks, VaultHooks},
+    issue,
+    leader::{self, LeaderLease},
+    liquidation::{watch_liquidation_vault, LiquidationRedeemLimits},
+    maintenance::{run_maintenance_schedule, MaintenanceSchedule, MaintenanceWindow},
+    metrics::{
+        currency_label, ACCOUNT_BALANCE, FEE_RESERVE_SHORTFALL, ORACLE_STALE, TIMESTAMP_DRIFTING, TOTAL_COLLATERAL,
+        TOTAL_TOKENS, VAULT_COLLATERAL, VAULT_TOKENS, WALLET_BALANCE, WALLET_RESCAN_PROGRESS,
+    },
+    recovery::{plan_recovery, write_heartbeats, Heartbeat},
+    relay::{run_relayer, FallbackBacking},
+    replay::{self, ScenarioStep},
+    request_state,
+    retire::{retire_vault, RetirementPlan},
+    service::*,
+    Error, Event, IssueRequests, Vaults, CHAIN_HEIGHT_POLLING_INTERVAL,
 };
 use async_trait::async_trait;
-use bitcoin::{stream_blocks, BitcoinCore, BitcoinCoreApi};
+use bitcoin::{stream_blocks, Amount, AmountExt, BitcoinCore, BitcoinCoreApi, EsploraClient, FeeEstimation};
 use clap::Clap;
 use futures::{
     channel::{mpsc, mpsc::Sender},
@@ -14,11 +39,18 @@ use git_version::git_version;
 use runtime::{
     cli::{parse_duration_minutes, parse_duration_ms},
     pallets::{security::UpdateActiveBlockEvent, sla::UpdateVaultSLAEvent},
-    AccountId, BtcRelayPallet, Error as RuntimeError, InterBtcParachain, InterBtcRuntime, UtilFuncs,
-    VaultRegistryPallet,
+    AccountId, BalanceSubscription, BtcAddress, BtcRelayPallet, CollateralAmount, Error as RuntimeError,
+    InterBtcParachain, InterBtcRuntime, IssuePallet, RedeemPallet, RedeemRequestStatus, RefundPallet, ReplacePallet,
+    ReplaceRequestStatus, UtilFuncs, VaultRegistryPallet,
 };
 use service::{wait_or_shutdown, Error as ServiceError, Service, ShutdownSender};
-use std::{sync::Arc, time::Duration};
+use std::{
+    convert::TryInto,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::time::delay_for;
 
 pub const VERSION: &str = git_version!(args = ["--tags"]);
@@ -26,6 +58,12 @@ pub const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const ABOUT: &str = env!("CARGO_PKG_DESCRIPTION");
 
+const CHANGE_ADDRESS_POOL_INTERVAL: Duration = Duration::from_secs(60);
+const WALLET_BALANCE_INTERVAL: Duration = Duration::from_secs(60);
+const FEE_RESERVE_INTERVAL: Duration = Duration::from_secs(600);
+/// Minimum fee rate (sat/vbyte) relayed by bitcoind with the default settings.
+const MIN_RELAY_FEE_RATE: u64 = 1;
+
 #[derive(Clap, Clone, Debug)]
 pub struct VaultServiceConfig {
     /// Automatically register the vault with the given amount of collateral and a newly generated address.
@@ -37,10 +75,25 @@ pub struct VaultServiceConfig {
     #[clap(long, conflicts_with("auto-register-with-collateral"))]
     pub auto_register_with_faucet_url: Option<String>,
 
+    /// On test networks, request funds from the faucet at this URL whenever the free balance
+    /// of the fee currency drops below `--fee-top-up-threshold`. Ignored on mainnet.
+    #[clap(long)]
+    pub fee_top_up_faucet_url: Option<String>,
+
+    /// Free balance of the fee currency (in planck) below which the faucet is asked for funds.
+    #[clap(long, default_value = "20000000000")]
+    pub fee_top_up_threshold: u128,
+
     /// Opt out of participation in replace requests.
     #[clap(long)]
     pub no_auto_replace: bool,
 
+    /// Cancel redeems requested by this vault's account from other vaults once the
+    /// redeeming vault misses the deadline, either `reimburse` (burn the tokens for
+    /// collateral) or `retry` (keep the tokens to redeem them elsewhere). Disabled if not set.
+    #[clap(long)]
+    pub cancel_own_redeems: Option<RedeemCancelPolicy>,
+
     /// Don't check the collateralization rate at startup.
     #[clap(long)]
     pub no_startup_collateral_increase: bool,
@@ -71,6 +124,37 @@ pub struct VaultServiceConfig {
     #[clap(long, parse(try_from_str = parse_duration_minutes), default_value = "120")]
     pub payment_margin_minutes: Duration,
 
+    /// Number of blocks the bitcoin block containing a payment must be below bitcoind's
+    /// tip before its proof is submitted. The block must also not be above the best block
+    /// of the relay. Disabled if 0.
+    #[clap(long, default_value = "0")]
+    pub proof_min_depth: u32,
+
+    /// Depth required of payments of at least the given amount (in satoshi) instead of
+    /// `--proof-min-depth`, e.g. 100000000=6. Can be specified multiple times.
+    #[clap(long)]
+    pub proof_min_depth_override: Vec<DepthOverride>,
+
+    /// Amount (in satoshi) above which redeem, replace and refund payments are held back
+    /// until the operator approves them. Disabled if not set.
+    #[clap(long)]
+    pub approval_threshold: Option<u128>,
+
+    /// Directory in which payments awaiting approval are written to `<id>.pending`. The
+    /// operator approves a payment by creating `<id>.approved`, or rejects it by creating
+    /// `<id>.rejected`.
+    #[clap(long, default_value = "approvals")]
+    pub approval_dir: PathBuf,
+
+    /// Time to wait for the approval of a payment before aborting it.
+    #[clap(long, parse(try_from_str = parse_duration_minutes), default_value = "60")]
+    pub approval_timeout_minutes: Duration,
+
+    /// Factor by which the bitcoin fee rate is assumed to rise in a fee spike. An alert is
+    /// raised if the float would not cover the fees of all outstanding payments at that rate.
+    #[clap(long, default_value = "5")]
+    pub fee_spike_multiplier: u64,
+
     /// Starting height for vault theft checks, if not defined
     /// automatically start from the chain tip.
     #[clap(long)]
@@ -85,6 +169,11 @@ pub struct VaultServiceConfig {
     #[clap(long)]
     pub bitcoin_relay_start_height: Option<u32>,
 
+    /// Skip rescanning the bitcoin chain below this height at startup, e.g. after
+    /// importing a snapshot into a wallet restored from backup.
+    #[clap(long)]
+    pub bitcoin_rescan_start_height: Option<u32>,
+
     /// Max batch size for combined block header submission.
     #[clap(long, default_value = "16")]
     pub max_batch_size: u32,
@@ -97,9 +186,451 @@ pub struct VaultServiceConfig {
     #[clap(long)]
     pub no_bitcoin_block_relay: bool,
 
+    /// Relay block headers immediately, without waiting to see if another relayer submits them.
+    #[clap(long)]
+    pub always_relay: bool,
+
+    /// Maximum random delay in milliseconds before relaying block headers.
+    #[clap(long, parse(try_from_str = parse_duration_ms), default_value = "30000")]
+    pub bitcoin_relay_max_holdoff_ms: Duration,
+
+    /// Esplora API (e.g. of electrs) to fetch block headers from while bitcoind is
+    /// unreachable, so that headers are still relayed. Headers are checked locally
+    /// against their proof of work.
+    #[clap(long)]
+    pub bitcoin_relay_esplora_url: Option<String>,
+
     /// Don't monitor vault thefts.
     #[clap(long)]
     pub no_vault_theft_report: bool,
+
+    /// Maximum number of redeem, replace and refund payments to process concurrently.
+    #[clap(long, default_value = "32")]
+    pub max_concurrent_payments: usize,
+
+    /// Maximum number of block headers the relayer fetches concurrently when catching up.
+    #[clap(long, default_value = "8")]
+    pub max_concurrent_header_fetches: usize,
+
+    /// Maximum number of issue request events to process concurrently, e.g. importing
+    /// their deposit keys.
+    #[clap(long, default_value = "32")]
+    pub max_concurrent_issue_events: usize,
+
+    /// Maximum number of issue proofs to submit concurrently.
+    #[clap(long, default_value = "8")]
+    pub max_concurrent_issue_executions: usize,
+
+    /// Maximum number of open requests to process concurrently at startup.
+    #[clap(long, default_value = "32")]
+    pub max_concurrent_open_requests: usize,
+
+    /// Capacity of the queues buffering issue and replace events for the cancellation schedulers.
+    #[clap(long, default_value = "32")]
+    pub event_queue_capacity: usize,
+
+    /// Number of pre-registered change addresses to keep available for outgoing payments.
+    /// If zero, change addresses are registered when a payment is made.
+    #[clap(long, default_value = "3")]
+    pub change_address_pool_size: usize,
+
+    /// Warn when the exchange rate has not been updated by the oracles for this long.
+    #[clap(long, parse(try_from_str = parse_duration_ms), default_value = "1800000")]
+    pub oracle_staleness_threshold_ms: Duration,
+
+    /// Warn when the timestamps of parachain blocks drift from the local time by more than
+    /// this, since the deadlines of redeem and replace requests can then not be relied upon.
+    #[clap(long, parse(try_from_str = parse_duration_ms), default_value = "120000")]
+    pub timestamp_drift_threshold_ms: Duration,
+
+    /// Lease file shared with standby instances of this vault, only the instance holding
+    /// the lease makes bitcoin payments. A standby takes over when the lease expires.
+    /// Each epoch of the lease is kept in a file `<path>.<epoch>` next to it.
+    #[clap(long)]
+    pub leader_lease_file: Option<PathBuf>,
+
+    /// Duration in milliseconds of the leader lease, renewed every third of it. The leader
+    /// halts bitcoin spends if the lease is not renewed within two thirds of it.
+    #[clap(long, parse(try_from_str = parse_duration_ms), default_value = "30000")]
+    pub leader_lease_ms: Duration,
+
+    /// Name identifying this instance in the lease file, followed by a random suffix.
+    /// Defaults to the hostname.
+    #[clap(long)]
+    pub instance_name: Option<String>,
+
+    /// File in which to keep extrinsics that could not be submitted because the parachain
+    /// was unreachable, e.g. collateral deposits. After reconnecting, what is still missing
+    /// on chain is submitted. If unset, such extrinsics are dropped.
+    #[clap(long)]
+    pub extrinsic_queue_file: Option<PathBuf>,
+
+    /// File in which to keep the state of each request processed by this vault, so that
+    /// requests recorded as paid are not paid again after a restart, unless the payment was
+    /// dropped from the mempool. If unset, the states are only kept in memory.
+    #[clap(long)]
+    pub request_state_file: Option<PathBuf>,
+
+    /// File in which the vault records every minute that it is running. If the vault was
+    /// offline for longer than `--blackout-threshold-minutes`, the bitcoin chain is rescanned
+    /// from the last record and a recovery plan is printed before resuming. The plan is also
+    /// written next to this file, with the extension `.recovery.json`. If unset, no
+    /// downtime is detected.
+    #[clap(long)]
+    pub heartbeat_file: Option<PathBuf>,
+
+    /// Downtime in minutes after which the recovery is run at startup.
+    #[clap(long, parse(try_from_str = parse_duration_minutes), default_value = "1440")]
+    pub blackout_threshold_minutes: Duration,
+
+    /// Recurring maintenance window in UTC, daily (e.g. 02:00-04:00) or weekly (e.g.
+    /// sun 02:00-04:00). Ahead of each window the vault drains: it withdraws the collateral
+    /// not backing issued tokens (keeping a margin of 10%) so that hardly any new issues can
+    /// be requested, stops accepting replace requests, withdraws its unaccepted replace
+    /// requests and completes its open requests. The withdrawn collateral is deposited again
+    /// after the window. Can be specified multiple times, requires `--maintenance-state-file`.
+    #[clap(long, requires = "maintenance-state-file")]
+    pub maintenance_window: Vec<MaintenanceWindow>,
+
+    /// Time in minutes before each maintenance window at which the vault starts draining.
+    #[clap(long, parse(try_from_str = parse_duration_minutes), default_value = "120")]
+    pub maintenance_lead_time_minutes: Duration,
+
+    /// File in which the collateral withdrawn for maintenance is recorded, so that it is
+    /// deposited again if the vault is restarted during the window.
+    #[clap(long)]
+    pub maintenance_state_file: Option<PathBuf>,
+
+    /// Maximum amount (in satoshis) of open issue and redeem requests of any single account
+    /// with this vault. A warning is logged when a new request takes an account over the
+    /// limit, its requests are still executed and paid. Not limited if unset.
+    #[clap(long)]
+    pub max_account_exposure: Option<u128>,
+
+    /// Append the parachain events and bitcoin blocks observed by this vault to this file,
+    /// so that they can be replayed in regression tests. If unset, nothing is recorded.
+    #[clap(long)]
+    pub record_scenario: Option<PathBuf>,
+
+    /// Write every request, payment, proof and state transition of this vault to this
+    /// Postgres database, e.g. `postgres://vault@localhost/analytics`. If unset, nothing
+    /// is written.
+    #[cfg(feature = "postgres")]
+    #[clap(long)]
+    pub analytics_database_url: Option<String>,
+
+    /// PEM certificate of the CA of the analytics database, if it is not trusted by the
+    /// system. By default TLS is used if the database supports it, set `sslmode` in the
+    /// database url to change this.
+    #[cfg(feature = "postgres")]
+    #[clap(long)]
+    pub analytics_database_ca_file: Option<PathBuf>,
+
+    /// Enter degradation mode when the smoothed latency of the parachain RPC exceeds this:
+    /// non-critical polling is slowed down, relay batches grow and issue executions are
+    /// deferred in favour of the executions of payments.
+    #[clap(long, parse(try_from_str = parse_duration_ms), default_value = "2000")]
+    pub rpc_degrade_latency_ms: Duration,
+
+    /// Leave degradation mode when the smoothed latency drops below this.
+    #[clap(long, parse(try_from_str = parse_duration_ms), default_value = "500")]
+    pub rpc_recover_latency_ms: Duration,
+
+    /// Factor by which polling intervals and batch sizes are increased in degradation mode.
+    #[clap(long, default_value = "4")]
+    pub degraded_interval_multiplier: u32,
+
+    /// Burn the free tokens of the vault account against the liquidation vault whenever that
+    /// pays at least this premium (in percent) over the exchange rate. If unset, redeem
+    /// opportunities are only reported.
+    #[clap(long)]
+    pub liquidation_redeem_min_premium_percent: Option<u64>,
+
+    /// Maximum amount of tokens (in satoshis) to burn against the liquidation vault at once.
+    #[clap(long, default_value = "100000")]
+    pub liquidation_redeem_max_amount: u128,
+
+    /// Address on which to serve prometheus metrics, e.g. 127.0.0.1:9615. The BIP21 payment
+    /// URIs of open issue requests are served at `/issues/<id>/payment-uri`.
+    /// If unset, no metrics are exposed.
+    #[clap(long)]
+    pub prometheus_addr: Option<SocketAddr>,
+
+    /// Custom logic invoked by the request handlers, registered with `with_hooks`.
+    #[clap(skip)]
+    pub hooks: Hooks,
+
+    /// Retirement to run within the service, set by the `retire` subcommand.
+    #[clap(skip)]
+    pub retirement: Option<RetirementPlan>,
+}
+
+impl VaultServiceConfig {
+    /// Register hooks implementing custom business logic, e.g. when embedding the vault
+    /// in another binary.
+    pub fn with_hooks(mut self, hooks: impl VaultHooks + 'static) -> Self {
+        self.hooks = Hooks::new(hooks);
+        self
+    }
+}
+
+async fn refill_change_address_pool(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    pool_size: usize,
+) -> Result<(), Error> {
+    if !is_registered(parachain_rpc, parachain_rpc.get_account_id().clone()).await? {
+        return Ok(());
+    }
+    while bitcoin_core.change_address_pool_size().await < pool_size {
+        let address = bitcoin_core.get_new_address::<BtcAddress>().await?;
+        tracing::info!("Registering change address {:?}", address);
+        parachain_rpc.register_address(address).await?;
+        bitcoin_core.add_change_address(address).await?;
+    }
+    Ok(())
+}
+
+/// Reuse the registered change addresses that have not received any funds, e.g. because
+/// they were still in the pool when the vault was restarted.
+async fn restore_change_address_pool(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+) -> Result<(), Error> {
+    let vault = match parachain_rpc.get_vault(parachain_rpc.get_account_id().clone()).await {
+        Ok(vault) => vault,
+        Err(RuntimeError::VaultNotFound) => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let restored = bitcoin_core
+        .restore_change_addresses(vault.wallet.addresses.into_iter().collect::<Vec<_>>())
+        .await?;
+    if restored > 0 {
+        tracing::info!("Reusing {} unused change addresses", restored);
+    }
+    Ok(())
+}
+
+/// Keeps the bitcoin change address pool filled with addresses that are registered on
+/// the parachain, so that change outputs are never mistaken for theft.
+async fn maintain_change_address_pool(
+    parachain_rpc: InterBtcParachain,
+    bitcoin_core: BitcoinCore,
+    pool_size: usize,
+) -> Result<(), ServiceError> {
+    if let Err(err) = restore_change_address_pool(&parachain_rpc, &bitcoin_core).await {
+        tracing::error!("Failed to restore change address pool: {}", err);
+    }
+    loop {
+        if let Err(err) = refill_change_address_pool(&parachain_rpc, &bitcoin_core, pool_size).await {
+            tracing::error!("Failed to refill change address pool: {}", err);
+        }
+        delay_for(degradation::stretch(CHANGE_ADDRESS_POOL_INTERVAL)).await;
+    }
+}
+
+/// Number of payments this vault still has to make: pending redeems, replaces and refunds.
+async fn count_pending_payments(parachain_rpc: &InterBtcParachain) -> Result<u64, Error> {
+    let vault_id = parachain_rpc.get_account_id().clone();
+    let (redeem_requests, replace_requests, refund_requests) = futures::future::try_join3(
+        parachain_rpc.get_vault_redeem_requests(vault_id.clone()),
+        parachain_rpc.get_old_vault_replace_requests(vault_id.clone()),
+        parachain_rpc.get_vault_refund_requests(vault_id),
+    )
+    .await?;
+    let pending = redeem_requests
+        .iter()
+        .filter(|(_, request)| request.status == RedeemRequestStatus::Pending)
+        .count()
+        + replace_requests
+            .iter()
+            .filter(|(_, request)| request.status == ReplaceRequestStatus::Pending)
+            .count()
+        + refund_requests.iter().filter(|(_, request)| !request.completed).count();
+    Ok(pending as u64)
+}
+
+/// Fee reserve at the fee rate bitcoind estimates for confirmation in the next block, or at the
+/// minimum relay fee rate if it has no estimate yet.
+async fn estimate_fee_reserve(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+) -> Result<FeeReserve, Error> {
+    let next_block = FeeEstimation {
+        mode: None,
+        conf_target: Some(1),
+    };
+    Ok(FeeReserve {
+        pending_payments: count_pending_payments(parachain_rpc).await?,
+        fee_rate: bitcoin_core
+            .estimate_fee_rate(next_block)
+            .await?
+            .unwrap_or(MIN_RELAY_FEE_RATE),
+    })
+}
+
+async fn update_wallet_balances(
+    parachain_rpc: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    fee_reserve: &FeeReserve,
+    fee_spike_multiplier: u64,
+) -> Result<(), Error> {
+    let balances = bitcoin_core.get_balances().await?;
+    WALLET_BALANCE
+        .with_label_values(&["trusted"])
+        .set(balances.trusted as i64);
+    WALLET_BALANCE
+        .with_label_values(&["untrusted_pending"])
+        .set(balances.untrusted_pending as i64);
+    WALLET_BALANCE
+        .with_label_values(&["immature"])
+        .set(balances.immature as i64);
+
+    // split the funds by purpose: issued tokens must remain backed by the wallet, of which the
+    // tokens to be redeemed are due to be paid out, anything else is operational float (e.g. for fees)
+    let vault = parachain_rpc.get_vault(parachain_rpc.get_account_id().clone()).await?;
+    let backing = vault.issued_tokens.saturating_sub(vault.to_be_redeemed_tokens);
+    let pending_outgoing = vault.to_be_redeemed_tokens;
+    let float = Amount::from_sat(balances.total())
+        .try_sub(Amount::from_sat(vault.issued_tokens.try_into()?))
+        .unwrap_or_else(|_| Amount::from_sat(0));
+    WALLET_BALANCE.with_label_values(&["backing"]).set(backing as i64);
+    WALLET_BALANCE
+        .with_label_values(&["pending_outgoing"])
+        .set(pending_outgoing as i64);
+    WALLET_BALANCE.with_label_values(&["float"]).set(float.as_sat() as i64);
+
+    // part of the float is needed to pay the fees of the outstanding payments
+    let required = fee_reserve.required()?;
+    let free = fee_reserve.free_balance(float)?;
+    WALLET_BALANCE
+        .with_label_values(&["fee_reserve"])
+        .set(required.as_sat() as i64);
+    WALLET_BALANCE.with_label_values(&["free"]).set(free.as_sat());
+    let spike_reserve = fee_reserve.required_under_spike(fee_spike_multiplier)?;
+    if float < spike_reserve {
+        tracing::warn!(
+            "Float of {} sat does not cover the fees of {} pending payments if the fee rate rises from {} to {} sat/vbyte ({} sat)",
+            float.as_sat(),
+            fee_reserve.pending_payments,
+            fee_reserve.fee_rate,
+            fee_reserve.fee_rate.saturating_mul(fee_spike_multiplier),
+            spike_reserve.as_sat()
+        );
+        FEE_RESERVE_SHORTFALL.set(1);
+    } else {
+        FEE_RESERVE_SHORTFALL.set(0);
+    }
+
+    tracing::debug!(
+        "Wallet balances: {:?}, backing = {}, pending outgoing = {}, float = {}, fee reserve = {}, free = {}",
+        balances,
+        backing,
+        pending_outgoing,
+        float.as_sat(),
+        required.as_sat(),
+        free.as_sat()
+    );
+    Ok(())
+}
+
+/// Periodically exports the wallet balances as metrics.
+async fn monitor_wallet_balances(
+    parachain_rpc: InterBtcParachain,
+    bitcoin_core: BitcoinCore,
+    fee_spike_multiplier: u64,
+) -> Result<(), ServiceError> {
+    // the fee reserve requires all requests of the vault, so it is refreshed less often
+    let mut fee_reserve: Option<(Instant, FeeReserve)> = None;
+    loop {
+        let stale = match fee_reserve {
+            Some((updated, _)) => updated.elapsed() >= FEE_RESERVE_INTERVAL,
+            None => true,
+        };
+        if stale {
+            match estimate_fee_reserve(&parachain_rpc, &bitcoin_core).await {
+                Ok(reserve) => fee_reserve = Some((Instant::now(), reserve)),
+                Err(err) => tracing::warn!("Failed to estimate fee reserve: {}", err),
+            }
+        }
+        if let Some((_, reserve)) = &fee_reserve {
+            if let Err(err) = update_wallet_balances(&parachain_rpc, &bitcoin_core, reserve, fee_spike_multiplier).await
+            {
+                tracing::warn!("Failed to update wallet balances: {}", err);
+            }
+        }
+        delay_for(degradation::stretch(WALLET_BALANCE_INTERVAL)).await;
+    }
+}
+
+async fn update_vault_totals(parachain_rpc: &InterBtcParachain) -> Result<(), Error> {
+    let vault_id = parachain_rpc.get_account_id();
+    let (mut issued, mut to_be_issued, mut to_be_redeemed, mut collateral) = (0u128, 0u128, 0u128, 0u128);
+    for vault in parachain_rpc.get_all_vaults().await? {
+        if &vault.id == vault_id {
+            VAULT_TOKENS
+                .with_label_values(&["issued"])
+                .set(vault.issued_tokens as i64);
+            VAULT_TOKENS
+                .with_label_values(&["to_be_issued"])
+                .set(vault.to_be_issued_tokens as i64);
+            VAULT_TOKENS
+                .with_label_values(&["to_be_redeemed"])
+                .set(vault.to_be_redeemed_tokens as i64);
+            VAULT_COLLATERAL.set(vault.backing_collateral as i64);
+        }
+        issued = issued.saturating_add(vault.issued_tokens);
+        to_be_issued = to_be_issued.saturating_add(vault.to_be_issued_tokens);
+        to_be_redeemed = to_be_redeemed.saturating_add(vault.to_be_redeemed_tokens);
+        collateral = collateral.saturating_add(vault.backing_collateral);
+    }
+    TOTAL_TOKENS.with_label_values(&["issued"]).set(issued as i64);
+    TOTAL_TOKENS
+        .with_label_values(&["to_be_issued"])
+        .set(to_be_issued as i64);
+    TOTAL_TOKENS
+        .with_label_values(&["to_be_redeemed"])
+        .set(to_be_redeemed as i64);
+    TOTAL_COLLATERAL.set(collateral as i64);
+    Ok(())
+}
+
+/// Periodically exports the tokens and collateral of this vault and of all vaults as metrics.
+async fn monitor_vault_totals(parachain_rpc: InterBtcParachain) -> Result<(), ServiceError> {
+    loop {
+        if let Err(err) = update_vault_totals(&parachain_rpc).await {
+            tracing::warn!("Failed to update vault totals: {}", err);
+        }
+        delay_for(degradation::stretch(WALLET_BALANCE_INTERVAL)).await;
+    }
+}
+
+/// Follows the balances of the vault account: exports them as metrics, and locks the required
+/// collateral when collateral is deposited. The cached fee balance is used by the balance guard.
+async fn monitor_account_balances(
+    parachain_rpc: InterBtcParachain,
+    maximum_collateral: Option<CollateralAmount>,
+) -> Result<(), ServiceError> {
+    let subscription = BalanceSubscription::new(vec![parachain_rpc.get_account_id().clone()])
+        .with_fee_currency()
+        .with_collateral_currency()
+        .with_wrapped_currency();
+    let parachain_rpc = &parachain_rpc;
+    parachain_rpc
+        .on_balance_change(subscription, |update| async move {
+            for change in update.changes.iter() {
+                let currency = currency_label(change.currency_id);
+                ACCOUNT_BALANCE
+                    .with_label_values(&[&currency, "free"])
+                    .set(change.current.free as i64);
+                ACCOUNT_BALANCE
+                    .with_label_values(&[&currency, "reserved"])
+                    .set(change.current.reserved as i64);
+            }
+            lock_collateral_on_deposit(parachain_rpc, maximum_collateral, &update).await;
+        })
+        .await?;
+    Ok(())
 }
 
 async fn active_block_listener(parachain_rpc: InterBtcParachain, block_tx: Sender<Event>) -> Result<(), ServiceError> {
@@ -107,6 +638,7 @@ async fn active_block_listener(parachain_rpc: InterBtcParachain, block_tx: Sende
     parachain_rpc
         .on_event::<UpdateActiveBlockEvent<InterBtcRuntime>, _, _, _>(
             |event| async move {
+                replay::record(|_| Ok(ScenarioStep::ParachainBlock { height: event.height }));
                 let _ = block_tx.clone().send(Event::ParachainBlock(event.height)).await;
             },
             |err| tracing::error!("Error (UpdateActiveBlockEvent): {}", err.to_string()),
@@ -170,6 +702,25 @@ impl VaultService {
     async fn run_service(&self) -> Result<(), Error> {
         let bitcoin_core = self.bitcoin_core.clone();
 
+        // stand by until no other instance is the leader, to guarantee at most one active payer
+        let leader_lease = self.config.leader_lease_file.clone().map(|path| {
+            LeaderLease::new(
+                path,
+                leader::holder_id(self.config.instance_name.clone()),
+                self.config.leader_lease_ms,
+            )
+        });
+        // renew the lease from the moment it is acquired, since starting up may take longer than it lasts,
+        // until the service returns (e.g. to restart after a lost connection) and drops the keeper
+        let (leader, _leader_keeper) = match leader_lease {
+            Some(lease) => {
+                let tenure = lease.acquire(&bitcoin_core).await?;
+                let (handle, keeper) = lease.spawn_keeper(bitcoin_core.clone(), tenure);
+                (Some(handle), Some(keeper))
+            }
+            None => (None, None),
+        };
+
         // load wallet. Exit on failure, since without wallet we can't do a lot
         bitcoin_core
             .create_or_load_wallet()
@@ -183,13 +734,25 @@ impl VaultService {
             None => self.btc_parachain.get_bitcoin_confirmations().await?,
         };
         tracing::info!("Using {} bitcoin confirmations", num_confirmations);
+        let proof_safety = ProofSafety::new(
+            self.config.proof_min_depth,
+            self.config.proof_min_depth_override.clone(),
+        );
+        hooks::install(self.config.hooks.clone());
+        let approval = PaymentApproval::new(
+            self.config.approval_threshold,
+            self.config.approval_dir.clone(),
+            self.config.approval_timeout_minutes,
+        );
 
         if let Some(collateral) = self.config.auto_register_with_collateral {
             if !is_registered(&self.btc_parachain, vault_id.clone()).await? {
                 tracing::info!("Automatically registering vault");
                 // bitcoin core is currently blocking, no need to try_join
                 let public_key = bitcoin_core.get_new_public_key().await?;
-                self.btc_parachain.register_vault(collateral, public_key).await?;
+                self.btc_parachain
+                    .register_vault(CollateralAmount::from_planck(collateral), public_key)
+                    .await?;
             } else {
                 tracing::info!("Not registering vault -- already registered");
             }
@@ -207,26 +770,99 @@ impl VaultService {
             }
         }
 
-        issue::add_keys_from_past_issue_request(&bitcoin_core, &self.btc_parachain).await?;
+        // the rescan of past issue requests can take hours when recovering a wallet
+        let mut scan_progress = bitcoin_core.subscribe_scan_progress();
+        tokio::spawn(async move {
+            while let Some(progress) = scan_progress.recv().await {
+                WALLET_RESCAN_PROGRESS.set(progress.map_or(0.0, |progress| progress.progress));
+            }
+        });
+
+        issue::add_keys_from_past_issue_request(
+            &bitcoin_core,
+            &self.btc_parachain,
+            self.config.bitcoin_rescan_start_height,
+        )
+        .await?;
+
+        // load the request states before the open requests are resumed
+        if let Some(path) = &self.config.request_state_file {
+            request_state::persist_to(path.clone())?;
+        }
+
+        // after a prolonged downtime, rescan for the payments made meanwhile and check for
+        // missed obligations before the open requests are resumed
+        if let Some(path) = &self.config.heartbeat_file {
+            match Heartbeat::read(path) {
+                Ok(Some(heartbeat)) if heartbeat.downtime() >= self.config.blackout_threshold_minutes => {
+                    tracing::warn!(
+                        "Vault was offline for {} minutes, recovering...",
+                        heartbeat.downtime().as_secs() / 60
+                    );
+                    match plan_recovery(&self.btc_parachain, &bitcoin_core, heartbeat).await {
+                        Ok(plan) => {
+                            plan.log();
+                            if let Err(e) = plan.present(path.with_extension("recovery.json")).await {
+                                tracing::error!("Failed to present the recovery plan: {}", e);
+                            }
+                        }
+                        // the open requests are still processed below
+                        Err(e) => tracing::error!("Failed to plan the recovery: {}", e),
+                    }
+                }
+                Ok(_) => {}
+                // a corrupt heartbeat must not keep the vault from starting
+                Err(e) => tracing::warn!("Failed to read the heartbeat, skipping the recovery: {}", e),
+            }
+        }
+
+        if let Some(path) = &self.config.record_scenario {
+            replay::record_to(path.clone(), bitcoin_core.network())?;
+            let period = self.btc_parachain.get_issue_period().await?;
+            replay::record(|_| Ok(ScenarioStep::IssuePeriod { period }));
+        }
 
         let open_request_executor = execute_open_requests(
             self.btc_parachain.clone(),
             bitcoin_core.clone(),
             num_confirmations,
             self.config.payment_margin_minutes,
+            proof_safety.clone(),
+            approval.clone(),
+            TaskLimiter::new("open_request", self.config.max_concurrent_open_requests),
         );
         tokio::spawn(async move {
             tracing::info!("Checking for open requests...");
             match open_request_executor.await {
-                Ok(_) => tracing::info!("Done processing open requests"),
+                Ok(reconciliation) => {
+                    reconciliation.log();
+                    tracing::info!("Done processing open requests");
+                }
                 Err(e) => tracing::error!("Failed to process open requests: {}", e),
             }
         });
 
+        // submit the extrinsics queued during the last outage before checking the collateral
+        let extrinsic_queue = self
+            .config
+            .extrinsic_queue_file
+            .clone()
+            .map(ExtrinsicQueue::open)
+            .transpose()?;
+        if let Some(extrinsic_queue) = &extrinsic_queue {
+            if let Err(e) = extrinsic_queue.drain(&self.btc_parachain).await {
+                tracing::error!("Failed to submit queued extrinsics: {}", e);
+            }
+        }
+
         if !self.config.no_startup_collateral_increase {
             // check if the vault is registered
-            match lock_required_collateral(self.btc_parachain.clone(), vault_id.clone(), self.config.max_collateral)
-                .await
+            match lock_required_collateral(
+                self.btc_parachain.clone(),
+                vault_id.clone(),
+                self.config.max_collateral.map(CollateralAmount::from_planck),
+            )
+            .await
             {
                 Err(Error::RuntimeError(runtime::Error::VaultNotFound)) => {} // not registered
                 Err(e) => tracing::error!("Failed to lock required additional collateral: {}", e),
@@ -236,7 +872,11 @@ impl VaultService {
 
         let collateral_maintainer = wait_or_shutdown(
             self.shutdown.clone(),
-            maintain_collateralization_rate(self.btc_parachain.clone(), self.config.max_collateral),
+            maintain_collateralization_rate(
+                self.btc_parachain.clone(),
+                self.config.max_collateral.map(CollateralAmount::from_planck),
+                extrinsic_queue.clone(),
+            ),
         );
 
         // wait for a new block to arrive, to prevent processing an event that potentially
@@ -254,7 +894,7 @@ impl VaultService {
             issue::initialize_issue_set(&bitcoin_core, &self.btc_parachain, &issue_set).await?;
         let initial_btc_height = bitcoin_core.get_block_count().await? as u32;
 
-        let (issue_event_tx, issue_event_rx) = mpsc::channel::<Event>(32);
+        let (issue_event_tx, issue_event_rx) = mpsc::channel::<Event>(self.config.event_queue_capacity);
 
         let issue_request_listener = wait_or_shutdown(
             self.shutdown.clone(),
@@ -263,6 +903,7 @@ impl VaultService {
                 self.btc_parachain.clone(),
                 issue_event_tx.clone(),
                 issue_set.clone(),
+                TaskLimiter::new("issue_event", self.config.max_concurrent_issue_events),
             ),
         );
 
@@ -305,12 +946,24 @@ impl VaultService {
                     issue_set.clone(),
                     oldest_issue_btc_height,
                     num_confirmations,
+                    proof_safety.clone(),
+                    TaskLimiter::new("issue_execution", self.config.max_concurrent_issue_executions),
                 ),
             ),
         );
 
+        // redeem, replace and refund payments are bounded by the same limit
+        let payment_limiter = TaskLimiter::new("payment", self.config.max_concurrent_payments);
+
+        // pause actions that would fail while the vault is banned
+        let ban_status = BanStatus::default();
+        let ban_monitor = wait_or_shutdown(
+            self.shutdown.clone(),
+            monitor_ban_status(self.btc_parachain.clone(), ban_status.clone()),
+        );
+
         // replace handling
-        let (replace_event_tx, replace_event_rx) = mpsc::channel::<Event>(16);
+        let (replace_event_tx, replace_event_rx) = mpsc::channel::<Event>(self.config.event_queue_capacity);
 
         let request_replace_listener = wait_or_shutdown(
             self.shutdown.clone(),
@@ -319,6 +972,7 @@ impl VaultService {
                 bitcoin_core.clone(),
                 replace_event_tx.clone(),
                 !self.config.no_auto_replace,
+                ban_status.clone(),
             ),
         );
 
@@ -329,6 +983,9 @@ impl VaultService {
                 bitcoin_core.clone(),
                 num_confirmations,
                 self.config.payment_margin_minutes,
+                proof_safety.clone(),
+                approval.clone(),
+                payment_limiter.clone(),
             ),
         );
 
@@ -356,15 +1013,71 @@ impl VaultService {
             Ok(())
         });
 
+        // cancellation of redeems requested by this vault's account
+        let (own_redeem_event_tx, own_redeem_event_rx) = mpsc::channel::<Event>(self.config.event_queue_capacity);
+        // nothing consumes the events if disabled, don't block the senders on the full queue
+        let own_redeem_block_tx = self.config.cancel_own_redeems.map(|_| own_redeem_event_tx.clone());
+
+        let own_redeem_listener = maybe_run_task(
+            self.config.cancel_own_redeems.is_some(),
+            wait_or_shutdown(
+                self.shutdown.clone(),
+                listen_for_own_redeems(self.btc_parachain.clone(), own_redeem_event_tx.clone()),
+            ),
+        );
+
+        let own_redeem_block_listener = maybe_run_task(
+            self.config.cancel_own_redeems.is_some(),
+            wait_or_shutdown(
+                self.shutdown.clone(),
+                active_block_listener(self.btc_parachain.clone(), own_redeem_event_tx),
+            ),
+        );
+
+        let mut own_redeem_cancellation_scheduler = CancellationScheduler::new(
+            self.btc_parachain.clone(),
+            startup_height,
+            initial_btc_height,
+            vault_id.clone(),
+        );
+        let cancel_own_redeems = self.config.cancel_own_redeems;
+        let own_redeem_cancel_scheduler = maybe_run_task(
+            cancel_own_redeems.is_some(),
+            wait_or_shutdown(self.shutdown.clone(), async move {
+                match cancel_own_redeems {
+                    Some(RedeemCancelPolicy::Reimburse) => {
+                        own_redeem_cancellation_scheduler
+                            .handle_cancellation::<RedeemCanceller<true>>(own_redeem_event_rx)
+                            .await?
+                    }
+                    Some(RedeemCancelPolicy::Retry) => {
+                        own_redeem_cancellation_scheduler
+                            .handle_cancellation::<RedeemCanceller<false>>(own_redeem_event_rx)
+                            .await?
+                    }
+                    None => (),
+                }
+                Ok(())
+            }),
+        );
+
         // listen for bitcoin blocks, used for cancellation
         let bitcoin_block_listener_btc_rpc = bitcoin_core.clone();
         let bitcoin_block_listener = wait_or_shutdown(self.shutdown.clone(), async move {
-            stream_blocks(bitcoin_block_listener_btc_rpc.clone(), initial_btc_height, 1)
+            let btc_rpc = &bitcoin_block_listener_btc_rpc;
+            let replace_event_tx = &replace_event_tx;
+            let issue_event_tx = &issue_event_tx;
+            let own_redeem_block_tx = &own_redeem_block_tx;
+            stream_blocks(btc_rpc.clone(), initial_btc_height, 1)
                 .await
-                .try_for_each(|_| async {
-                    let height = bitcoin_block_listener_btc_rpc.get_block_count().await? as u32;
+                .try_for_each(|block| async move {
+                    let height = btc_rpc.get_block_count().await? as u32;
+                    replay::record(|_| Ok(ScenarioStep::bitcoin_block(height, &block)));
                     let _ = replace_event_tx.clone().send(Event::BitcoinBlock(height)).await;
                     let _ = issue_event_tx.clone().send(Event::BitcoinBlock(height)).await;
+                    if let Some(own_redeem_block_tx) = &own_redeem_block_tx {
+                        let _ = own_redeem_block_tx.clone().send(Event::BitcoinBlock(height)).await;
+                    }
                     Ok(())
                 })
                 .await
@@ -379,13 +1092,28 @@ impl VaultService {
                 bitcoin_core.clone(),
                 num_confirmations,
                 self.config.payment_margin_minutes,
+                proof_safety.clone(),
+                approval.clone(),
+                payment_limiter.clone(),
             ),
         );
 
         // refund handling
         let refund_listener = wait_or_shutdown(
             self.shutdown.clone(),
-            listen_for_refund_requests(self.btc_parachain.clone(), bitcoin_core.clone(), num_confirmations),
+            listen_for_refund_requests(
+                self.btc_parachain.clone(),
+                bitcoin_core.clone(),
+                num_confirmations,
+                proof_safety,
+                approval,
+                payment_limiter,
+            ),
+        );
+
+        let finalized_request_listener = wait_or_shutdown(
+            self.shutdown.clone(),
+            listen_for_finalized_requests(self.btc_parachain.clone()),
         );
 
         let sla_provider = self.btc_parachain.clone();
@@ -404,6 +1132,236 @@ impl VaultService {
             Ok(())
         });
 
+        let change_address_pool = maybe_run_task(
+            self.config.change_address_pool_size > 0,
+            wait_or_shutdown(
+                self.shutdown.clone(),
+                maintain_change_address_pool(
+                    self.btc_parachain.clone(),
+                    bitcoin_core.clone(),
+                    self.config.change_address_pool_size,
+                ),
+            ),
+        );
+
+        // halt bitcoin spends if this vault is flagged for theft
+        let own_theft_listener = wait_or_shutdown(
+            self.shutdown.clone(),
+            listen_for_own_theft(self.btc_parachain.clone(), bitcoin_core.clone()),
+        );
+
+        // warn about spends of wallet outputs that this vault did not make
+        let external_spend_monitor =
+            wait_or_shutdown(self.shutdown.clone(), monitor_external_spends(bitcoin_core.clone()));
+
+        // re-reads the bitcoind credentials on SIGHUP
+        #[cfg(unix)]
+        {
+            let bitcoin_core = bitcoin_core.clone();
+            tokio::spawn(wait_or_shutdown(self.shutdown.clone(), async move {
+                bitcoin_core.reload_auth_on_hangup().await?;
+                Ok(())
+            }));
+        }
+
+        let wallet_balances = wait_or_shutdown(
+            self.shutdown.clone(),
+            monitor_wallet_balances(
+                self.btc_parachain.clone(),
+                bitcoin_core.clone(),
+                self.config.fee_spike_multiplier,
+            ),
+        );
+
+        let vault_totals = wait_or_shutdown(self.shutdown.clone(), monitor_vault_totals(self.btc_parachain.clone()));
+
+        let rpc_latency_monitor = wait_or_shutdown(
+            self.shutdown.clone(),
+            degradation::monitor_rpc_latency(
+                self.btc_parachain.clone(),
+                DegradationThresholds {
+                    degrade_above: self.config.rpc_degrade_latency_ms,
+                    recover_below: self.config.rpc_recover_latency_ms,
+                    multiplier: self.config.degraded_interval_multiplier,
+                },
+            ),
+        );
+
+        // drains the vault ahead of maintenance windows and resumes after them
+        let maintenance_schedule = maybe_run_task(
+            !self.config.maintenance_window.is_empty(),
+            wait_or_shutdown(
+                self.shutdown.clone(),
+                run_maintenance_schedule(
+                    self.btc_parachain.clone(),
+                    MaintenanceSchedule {
+                        windows: self.config.maintenance_window.clone(),
+                        lead_time: self.config.maintenance_lead_time_minutes,
+                        state_file: self.config.maintenance_state_file.clone().unwrap_or_default(),
+                    },
+                ),
+            ),
+        );
+
+        // tracks the open requests by account, served at `/exposure` by the metrics server
+        let exposure_tracker = wait_or_shutdown(
+            self.shutdown.clone(),
+            track_exposure(self.btc_parachain.clone(), self.config.max_account_exposure),
+        );
+
+        // writes the requests of the vault to the analytics database
+        #[cfg(feature = "postgres")]
+        let analytics_sink = maybe_run_task(
+            self.config.analytics_database_url.is_some(),
+            wait_or_shutdown(
+                self.shutdown.clone(),
+                run_analytics_sink(
+                    self.config.analytics_database_url.clone().unwrap_or_default(),
+                    self.config.analytics_database_ca_file.clone(),
+                    bitcoin_core.network(),
+                ),
+            ),
+        );
+        #[cfg(not(feature = "postgres"))]
+        let analytics_sink = async {};
+
+        // reports, and optionally takes, redeem opportunities against the liquidation vault
+        let liquidation_watcher = wait_or_shutdown(
+            self.shutdown.clone(),
+            watch_liquidation_vault(
+                self.btc_parachain.clone(),
+                self.config
+                    .liquidation_redeem_min_premium_percent
+                    .map(|min_premium_percent| LiquidationRedeemLimits {
+                        min_premium_percent,
+                        max_amount: self.config.liquidation_redeem_max_amount,
+                    }),
+            ),
+        );
+
+        // only test networks have a faucet
+        let fee_top_up_url = match self.config.fee_top_up_faucet_url.clone() {
+            Some(_) if bitcoin_core.network() == bitcoin::Network::Bitcoin => {
+                tracing::warn!("Ignoring --fee-top-up-faucet-url on mainnet");
+                None
+            }
+            url => url,
+        };
+        let fee_top_up_provider = self.btc_parachain.clone();
+        let fee_top_up_threshold = self.config.fee_top_up_threshold;
+        let fee_top_up = maybe_run_task(
+            fee_top_up_url.is_some(),
+            wait_or_shutdown(self.shutdown.clone(), async move {
+                match fee_top_up_url {
+                    Some(url) => faucet::maintain_fee_balance(fee_top_up_provider, url, fee_top_up_threshold).await,
+                    None => Ok(()),
+                }
+            }),
+        );
+
+        let account_balances = wait_or_shutdown(
+            self.shutdown.clone(),
+            monitor_account_balances(
+                self.btc_parachain.clone(),
+                self.config.max_collateral.map(CollateralAmount::from_planck),
+            ),
+        );
+
+        // pause extrinsic submission while the parachain is shut down, bitcoin
+        // monitoring continues and pending actions resume once it is running again
+        let status_provider = self.btc_parachain.clone();
+        let parachain_status_listener = wait_or_shutdown(self.shutdown.clone(), async move {
+            status_provider.listen_for_parachain_status().await?;
+            Ok(())
+        });
+
+        // alert operators when the price feed is stale
+        let oracle_provider = self.btc_parachain.clone();
+        let oracle_staleness_threshold = self.config.oracle_staleness_threshold_ms;
+        let oracle_staleness_listener = wait_or_shutdown(self.shutdown.clone(), async move {
+            oracle_provider
+                .on_oracle_staleness_change(oracle_staleness_threshold, |is_stale, staleness| async move {
+                    ORACLE_STALE.set(is_stale as i64);
+                    if is_stale {
+                        tracing::warn!("Exchange rate has not been updated for {:?}", staleness.age());
+                    } else {
+                        tracing::info!("Exchange rate is up to date");
+                    }
+                })
+                .await?;
+            Ok(())
+        });
+
+        // stops the vault once the parachain upgraded to a runtime this build does not support
+        let runtime_provider = self.btc_parachain.clone();
+        let runtime_version_watcher = wait_or_shutdown(self.shutdown.clone(), async move {
+            runtime_provider.watch_runtime_version().await?;
+            Ok(())
+        });
+
+        // alert operators when the parachain or the local clock is off
+        let drift_provider = self.btc_parachain.clone();
+        let timestamp_drift_threshold = self.config.timestamp_drift_threshold_ms;
+        let timestamp_drift_listener = wait_or_shutdown(self.shutdown.clone(), async move {
+            drift_provider
+                .on_timestamp_drift_change(timestamp_drift_threshold, |is_drifting, drift| async move {
+                    TIMESTAMP_DRIFTING.set(is_drifting as i64);
+                    if is_drifting {
+                        tracing::warn!(
+                            "Parachain time drifts by {} ms from the local time, deadlines may be off",
+                            drift.drift_ms()
+                        );
+                    } else {
+                        tracing::info!("Parachain time is in sync with the local time");
+                    }
+                })
+                .await?;
+            Ok(())
+        });
+
+        // halts bitcoin spends and restarts as standby if the leader lease is lost
+        let leader_lease_keeper = maybe_run_task(
+            leader.is_some(),
+            wait_or_shutdown(self.shutdown.clone(), async move {
+                match leader {
+                    Some(keeper) => keeper
+                        .await
+                        .unwrap_or_else(|err| Err(ServiceError::Other(err.to_string()))),
+                    None => Ok(()),
+                }
+            }),
+        );
+
+        let heartbeat_writer = maybe_run_task(
+            self.config.heartbeat_file.is_some(),
+            wait_or_shutdown(
+                self.shutdown.clone(),
+                write_heartbeats(
+                    self.btc_parachain.clone(),
+                    bitcoin_core.clone(),
+                    self.config.heartbeat_file.clone().unwrap_or_default(),
+                ),
+            ),
+        );
+
+        // not wrapped in `wait_or_shutdown`, the vault keeps paying for replaces after the retirement ends
+        let retirement_plan = self.config.retirement.clone();
+        let retirement_parachain = self.btc_parachain.clone();
+        let retirement_bitcoin_core = bitcoin_core.clone();
+        let retirement = maybe_run_task(retirement_plan.is_some(), async move {
+            let plan = match retirement_plan {
+                Some(plan) => plan,
+                None => return,
+            };
+            match retire_vault(&retirement_parachain, &retirement_bitcoin_core, plan).await {
+                Ok(report) => match serde_json::to_string_pretty(&report) {
+                    Ok(report) => println!("{}", report),
+                    Err(e) => tracing::error!("Failed to serialize the retirement report: {}", e),
+                },
+                Err(e) => tracing::error!("Failed to retire the vault: {}", e),
+            }
+        });
+
         let err_provider = self.btc_parachain.clone();
         let err_listener = wait_or_shutdown(self.shutdown.clone(), async move {
             err_provider
@@ -421,13 +1379,19 @@ impl VaultService {
             wait_or_shutdown(
                 self.shutdown.clone(),
                 run_relayer(Runner::new(
-                    bitcoin_core.clone(),
+                    FallbackBacking::new(
+                        bitcoin_core.clone(),
+                        self.config.bitcoin_relay_esplora_url.as_deref().map(EsploraClient::new),
+                    ),
                     self.btc_parachain.clone(),
                     Config {
                         start_height: self.config.bitcoin_relay_start_height,
                         max_batch_size: self.config.max_batch_size,
+                        max_concurrent_headers: self.config.max_concurrent_header_fetches,
                         interval: Some(self.config.bitcoin_poll_interval_ms),
                         btc_confirmations: self.config.bitcoin_relay_confirmations,
+                        always_relay: self.config.always_relay,
+                        max_holdoff: self.config.bitcoin_relay_max_holdoff_ms,
                     },
                 )),
             ),
@@ -440,6 +1404,45 @@ impl VaultService {
             tokio::spawn(async move { err_listener.await }),
             // runs sla listener to log events
             tokio::spawn(async move { sla_listener.await }),
+            // tracks the parachain status to pause and resume submissions
+            tokio::spawn(async move { parachain_status_listener.await }),
+            // monitors the age of the exchange rate
+            tokio::spawn(async move { oracle_staleness_listener.await }),
+            tokio::spawn(async move { timestamp_drift_listener.await }),
+            // stops on an upgrade to an unsupported runtime
+            tokio::spawn(async move { runtime_version_watcher.await }),
+            // keeps registered change addresses available for payments
+            tokio::spawn(async move { change_address_pool.await }),
+            // tracks whether the vault is banned
+            tokio::spawn(async move { ban_monitor.await }),
+            // renews the leader lease
+            tokio::spawn(async move { leader_lease_keeper.await }),
+            // records that the vault is running, to detect downtimes
+            tokio::spawn(async move { heartbeat_writer.await }),
+            // replaces all issued tokens and withdraws the collateral, if requested
+            tokio::spawn(async move { retirement.await }),
+            // stops payments if the vault is flagged for theft
+            tokio::spawn(async move { own_theft_listener.await }),
+            // detects external spends of wallet outputs
+            tokio::spawn(async move { external_spend_monitor.await }),
+            // exports the wallet balances by purpose
+            tokio::spawn(async move { wallet_balances.await }),
+            // exports the tokens and collateral per vault and in total
+            tokio::spawn(async move { vault_totals.await }),
+            // switches to degradation mode while the parachain rpc is slow
+            tokio::spawn(async move { rpc_latency_monitor.await }),
+            // drains the vault for scheduled maintenance
+            tokio::spawn(async move { maintenance_schedule.await }),
+            // alerts on accounts over the exposure limit
+            tokio::spawn(async move { exposure_tracker.await }),
+            // records the requests for analytics
+            tokio::spawn(async move { analytics_sink.await }),
+            // tracks the liquidation vault for redeem opportunities
+            tokio::spawn(async move { liquidation_watcher.await }),
+            // requests funds from the faucet when the fee balance is low
+            tokio::spawn(async move { fee_top_up.await }),
+            // exports the account balances and locks deposited collateral
+            tokio::spawn(async move { account_balances.await }),
             // maintain collateralization rate
             tokio::spawn(async move {
                 collateral_maintainer.await;
@@ -461,8 +1464,13 @@ impl VaultService {
             tokio::spawn(async move { replace_cancel_scheduler.await }),
             // redeem handling
             tokio::spawn(async move { redeem_listener.await }),
+            tokio::spawn(async move { own_redeem_listener.await }),
+            tokio::spawn(async move { own_redeem_block_listener.await }),
+            tokio::spawn(async move { own_redeem_cancel_scheduler.await }),
             // refund handling
             tokio::spawn(async move { refund_listener.await }),
+            // finalizes or expires the tracked request states
+            tokio::spawn(async move { finalized_request_listener.await }),
             // runs vault theft checks
             tokio::spawn(async move { vaults_listener.await }),
             // relayer process
@@ -475,6 +1483,8 @@ impl VaultService {
     pub(crate) async fn start_theft_reporting(&self) -> Result<impl Future, Error> {
         // TODO: don't fetch vaults if reporting is disabled
         tracing::info!("Fetching all active vaults...");
+        // the events of later blocks are replayed by the cache refresher
+        let vault_cache_height = self.btc_parachain.get_current_chain_height().await? + 1;
         let vaults = self
             .btc_parachain
             .get_all_vaults()
@@ -492,6 +1502,11 @@ impl VaultService {
 
         // store vaults in Arc<RwLock>
         let vaults = Arc::new(Vaults::from(vaults));
+        tracing::info!(
+            "Matching bitcoin transactions against {} vault addresses",
+            vaults.len().await
+        );
+        watch_vault_addresses(&self.bitcoin_core, vaults.addresses().await).await;
 
         // scan from custom height or the current tip
         let bitcoin_theft_start_height = self
@@ -512,19 +1527,31 @@ impl VaultService {
         // keep track of all registered vaults (i.e. keep the `vaults` map up-to-date)
         let vaults_registration_listener = wait_or_shutdown(
             self.shutdown.clone(),
-            listen_for_vaults_registered(self.btc_parachain.clone(), vaults.clone()),
+            listen_for_vaults_registered(self.btc_parachain.clone(), self.bitcoin_core.clone(), vaults.clone()),
         );
 
         // keep vault wallets up-to-date
         let wallet_update_listener = wait_or_shutdown(
             self.shutdown.clone(),
-            listen_for_wallet_updates(self.btc_parachain.clone(), vaults.clone()),
+            listen_for_wallet_updates(self.btc_parachain.clone(), self.bitcoin_core.clone(), vaults.clone()),
+        );
+
+        // catch up on events missed while the subscriptions were reconnecting
+        let vault_cache_refresher = wait_or_shutdown(
+            self.shutdown.clone(),
+            refresh_vault_cache(
+                self.btc_parachain.clone(),
+                self.bitcoin_core.clone(),
+                vaults.clone(),
+                vault_cache_height,
+            ),
         );
 
-        Ok(futures::future::join3(
+        Ok(futures::future::join4(
             vaults_listener,
             vaults_registration_listener,
             wallet_update_listener,
+            vault_cache_refresher,
         ))
     }
 }
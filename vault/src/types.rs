@@ -60,6 +60,11 @@ where
     {
         self.0 .1.get(v)
     }
+
+    /// Iterate over the values of the map.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.0 .0.values()
+    }
 }
 
 pub struct IssueRequests(Mutex<ReversibleHashMap<H256, BtcAddress>>);
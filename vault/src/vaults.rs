@@ -1,15 +1,21 @@
 use crate::error::Error;
-use bitcoin::{BitcoinCoreApi, BlockHash, Transaction, TransactionExt as _};
+use bitcoin::{BitcoinCore, BitcoinCoreApi, BlockHash, Transaction, TransactionExt as _};
 use futures::stream::{iter, StreamExt};
 use runtime::{
     pallets::vault_registry::{RegisterAddressEvent, RegisterVaultEvent},
-    AccountId, BtcAddress, BtcRelayPallet, Error as RuntimeError, H256Le, InterBtcParachain, InterBtcRuntime,
-    InterBtcVault, StakedRelayerPallet, VaultRegistryPallet,
+    AccountId, BlockNumber, BtcAddress, BtcRelayPallet, Error as RuntimeError, H256Le, InterBtcParachain,
+    InterBtcRuntime, InterBtcVault, StakedRelayerPallet, UtilFuncs, VaultRegistryPallet,
 };
 use service::Error as ServiceError;
 use sp_core::crypto::Ss58Codec;
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, time::delay_for};
+
+/// Interval at which the vault registry events of the new blocks are replayed into the cache.
+const VAULT_CACHE_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Label of the vault addresses in the watch-only wallet.
+const VAULTS_LABEL: &str = "vaults";
 
 #[derive(Default)]
 pub struct Vaults(RwLock<HashMap<BtcAddress, AccountId>>);
@@ -19,21 +25,105 @@ impl Vaults {
         Self(RwLock::new(vaults))
     }
 
-    pub async fn write(&self, key: BtcAddress, value: AccountId) {
-        self.0.write().await.insert(key, value);
+    /// Returns true if the address was not cached before.
+    pub async fn write(&self, key: BtcAddress, value: AccountId) -> bool {
+        self.0.write().await.insert(key, value).is_none()
     }
 
-    pub async fn add_vault(&self, vault: InterBtcVault) {
+    /// Returns the addresses of the vault that were not cached before.
+    pub async fn add_vault(&self, vault: InterBtcVault) -> Vec<BtcAddress> {
         let mut vaults = self.0.write().await;
-        for address in vault.wallet.addresses {
-            vaults.insert(address, vault.id.clone());
-        }
+        vault
+            .wallet
+            .addresses
+            .into_iter()
+            .filter(|address| vaults.insert(*address, vault.id.clone()).is_none())
+            .collect()
+    }
+
+    /// All cached vault addresses.
+    pub async fn addresses(&self) -> Vec<BtcAddress> {
+        self.0.read().await.keys().cloned().collect()
     }
 
     pub async fn contains_key(&self, key: BtcAddress) -> Option<AccountId> {
         let vaults = self.0.read().await;
         vaults.get(&key).cloned()
     }
+
+    /// Number of cached vault addresses.
+    pub async fn len(&self) -> usize {
+        self.0.read().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+}
+
+/// Import the vault addresses into the watch-only wallet, so that bitcoind tracks their
+/// transactions. Theft reports do not depend on it, so failures are only logged.
+pub async fn watch_vault_addresses(bitcoin_core: &BitcoinCore, addresses: Vec<BtcAddress>) {
+    if let Err(err) = bitcoin_core.import_watch_only_addresses(addresses, VAULTS_LABEL).await {
+        tracing::error!("Failed to import vault addresses as watch-only: {}", err);
+    }
+}
+
+/// Periodically replays the registrations and addresses of the finalized blocks after
+/// `from_height`, so that events missed while the subscriptions were reconnecting are still
+/// matched. The events are decoded with the metadata of the runtime that emitted them, so
+/// the gap may span runtime upgrades. Entries are only added, an address is never reassigned
+/// to a different vault.
+pub async fn refresh_vault_cache(
+    btc_parachain: InterBtcParachain,
+    bitcoin_core: BitcoinCore,
+    vaults: Arc<Vaults>,
+    from_height: BlockNumber,
+) -> Result<(), ServiceError> {
+    let mut next_height = from_height;
+    loop {
+        delay_for(VAULT_CACHE_REFRESH_INTERVAL).await;
+        match replay_vault_events(&btc_parachain, &bitcoin_core, &vaults, next_height).await {
+            Ok(height) => {
+                next_height = height;
+                tracing::debug!("Refreshed vault cache, {} addresses", vaults.len().await);
+            }
+            Err(err) => tracing::error!("Failed to refresh vault cache: {}", err),
+        }
+    }
+}
+
+/// Add the vaults and addresses registered in the finalized blocks from `from_height` to the
+/// cache. Returns the height of the first block that has not been replayed.
+async fn replay_vault_events(
+    btc_parachain: &InterBtcParachain,
+    bitcoin_core: &BitcoinCore,
+    vaults: &Vaults,
+    from_height: BlockNumber,
+) -> Result<BlockNumber, RuntimeError> {
+    let to_height = btc_parachain.get_current_chain_height().await?;
+    if to_height < from_height {
+        return Ok(from_height);
+    }
+
+    let mut new_addresses = Vec::new();
+    for (_, event) in btc_parachain
+        .get_past_events::<RegisterVaultEvent<InterBtcRuntime>>(from_height, to_height)
+        .await?
+    {
+        new_addresses.extend(vaults.add_vault(btc_parachain.get_vault(event.account_id).await?).await);
+    }
+    for (_, event) in btc_parachain
+        .get_past_events::<RegisterAddressEvent<InterBtcRuntime>>(from_height, to_height)
+        .await?
+    {
+        if vaults.write(event.btc_address, event.vault_id).await {
+            new_addresses.push(event.btc_address);
+        }
+    }
+    watch_vault_addresses(bitcoin_core, new_addresses).await;
+
+    Ok(to_height + 1)
 }
 
 pub async fn report_vault_thefts<P: StakedRelayerPallet + BtcRelayPallet, B: BitcoinCoreApi + Clone>(
@@ -127,9 +217,11 @@ impl<P: StakedRelayerPallet + BtcRelayPallet, B: BitcoinCoreApi + Clone> VaultTh
 
 pub async fn listen_for_wallet_updates(
     btc_parachain: InterBtcParachain,
+    bitcoin_core: BitcoinCore,
     vaults: Arc<Vaults>,
 ) -> Result<(), ServiceError> {
     let vaults = &vaults;
+    let bitcoin_core = &bitcoin_core;
     btc_parachain
         .on_event::<RegisterAddressEvent<InterBtcRuntime>, _, _, _>(
             |event| async move {
@@ -138,7 +230,9 @@ pub async fn listen_for_wallet_updates(
                     event.btc_address,
                     event.vault_id.to_ss58check()
                 );
-                vaults.write(event.btc_address, event.vault_id).await;
+                if vaults.write(event.btc_address, event.vault_id).await {
+                    watch_vault_addresses(bitcoin_core, vec![event.btc_address]).await;
+                }
             },
             |err| tracing::error!("Error (RegisterAddressEvent): {}", err.to_string()),
         )
@@ -148,6 +242,7 @@ pub async fn listen_for_wallet_updates(
 
 pub async fn listen_for_vaults_registered(
     btc_parachain: InterBtcParachain,
+    bitcoin_core: BitcoinCore,
     vaults: Arc<Vaults>,
 ) -> Result<(), ServiceError> {
     btc_parachain
@@ -156,7 +251,8 @@ pub async fn listen_for_vaults_registered(
                 match btc_parachain.get_vault(event.account_id).await {
                     Ok(vault) => {
                         tracing::info!("Vault registered: {}", vault.id.to_ss58check());
-                        vaults.add_vault(vault).await;
+                        let new_addresses = vaults.add_vault(vault).await;
+                        watch_vault_addresses(&bitcoin_core, new_addresses).await;
                     }
                     Err(err) => tracing::error!("Error getting vault: {}", err.to_string()),
                 };
@@ -179,8 +275,8 @@ mod tests {
     use super::*;
     use async_trait::async_trait;
     use bitcoin::{
-        Block, BlockHeader, Error as BitcoinError, GetBlockResult, LockedTransaction, PartialAddress, PrivateKey,
-        Transaction, TransactionMetadata, Txid, PUBLIC_KEY_SIZE,
+        Block, BlockHeader, Error as BitcoinError, FeeEstimation, GetBlockResult, LockedTransaction, PartialAddress,
+        PrivateKey, Transaction, TransactionMetadata, Txid, PUBLIC_KEY_SIZE,
     };
     use runtime::{
         AccountId, BitcoinBlockHeight, BlockNumber, Error as RuntimeError, H256Le, InterBtcRichBlockHeader,
@@ -262,11 +358,12 @@ mod tests {
                 txid: Txid,
                 num_confirmations: u32,
             ) -> Result<TransactionMetadata, BitcoinError>;
-            async fn create_transaction<A: PartialAddress + Send + Sync + 'static>(
+            async fn create_transaction_with_fee_estimation<A: PartialAddress + Send + Sync + 'static>(
                 &self,
                 address: A,
                 sat: u64,
                 request_id: Option<H256>,
+                fee_estimation: FeeEstimation,
             ) -> Result<LockedTransaction, BitcoinError>;
             async fn send_transaction(&self, transaction: LockedTransaction) -> Result<Txid, BitcoinError>;
             async fn create_and_send_transaction<A: PartialAddress + Send + Sync + 'static>(
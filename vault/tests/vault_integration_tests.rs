@@ -11,13 +11,18 @@ use runtime::{
     pallets::{
         issue::*, redeem::*, refund::*, replace::*, security::UpdateActiveBlockEvent, tokens::*, vault_registry::*,
     },
-    BtcAddress, ExchangeRateOraclePallet, FixedPointNumber, FixedU128, InterBtcParachain, InterBtcRedeemRequest,
-    InterBtcRuntime, IssuePallet, RedeemPallet, ReplacePallet, UtilFuncs, VaultRegistryPallet,
+    BtcAddress, CollateralAmount, ExchangeRateOraclePallet, FixedPointNumber, FixedU128, InterBtcParachain,
+    InterBtcRedeemRequest, InterBtcRuntime, IssuePallet, RedeemPallet, ReplacePallet, UtilFuncs, VaultRegistryPallet,
+    WrappedAmount,
 };
 use sp_core::{H160, H256};
 use sp_keyring::AccountKeyring;
 use std::{sync::Arc, time::Duration};
-use vault::{self, Event as CancellationEvent, IssueRequests};
+use vault::{
+    self,
+    service::{BanStatus, PaymentApproval, ProofSafety, TaskLimiter},
+    Event as CancellationEvent, IssueRequests,
+};
 
 const TIMEOUT: Duration = Duration::from_secs(60);
 
@@ -48,7 +53,15 @@ async fn test_redeem_succeeds() {
     assert_issue(&user_provider, &btc_rpc, vault_provider.get_account_id(), issue_amount).await;
 
     test_service(
-        vault::service::listen_for_redeem_requests(vault_provider.clone(), btc_rpc, 0, Duration::from_secs(0)),
+        vault::service::listen_for_redeem_requests(
+            vault_provider.clone(),
+            btc_rpc,
+            0,
+            Duration::from_secs(0),
+            ProofSafety::default(),
+            PaymentApproval::default(),
+            TaskLimiter::new("test", 32),
+        ),
         async {
             let address = BtcAddress::P2PKH(H160::from_slice(&[2; 20]));
             let vault_id = vault_provider.clone().get_account_id().clone();
@@ -108,12 +121,16 @@ async fn test_replace_succeeds() {
                 btc_rpc.clone(),
                 replace_event_tx.clone(),
                 true,
+                BanStatus::default(),
             ),
             vault::service::listen_for_accept_replace(
                 old_vault_provider.clone(),
                 btc_rpc.clone(),
                 0,
                 Duration::from_secs(0),
+                ProofSafety::default(),
+                PaymentApproval::default(),
+                TaskLimiter::new("test", 32),
             ),
         ),
         async {
@@ -163,7 +180,7 @@ async fn test_maintain_collateral_succeeds() {
     assert_issue(&user_provider, &btc_rpc, vault_provider.get_account_id(), issue_amount).await;
 
     test_service(
-        vault::service::maintain_collateralization_rate(vault_provider.clone(), Some(1000000000)),
+        vault::service::maintain_collateralization_rate(vault_provider.clone(), Some(1000000000), None),
         async {
             // dot per btc increases by 10%
             relayer_provider
@@ -171,7 +188,7 @@ async fn test_maintain_collateral_succeeds() {
                 .await
                 .unwrap();
             assert_event::<DepositCollateralEvent<InterBtcRuntime>, _>(TIMEOUT, vault_provider.clone(), |e| {
-                assert_eq!(e.new_collateral, vault_collateral / 10);
+                assert_eq!(e.new_collateral, vault_collateral.planck() / 10);
                 true
             })
             .await;
@@ -238,7 +255,7 @@ async fn test_withdraw_replace_succeeds() {
 
     let address = BtcAddress::P2PKH(H160::from_slice(&[2; 20]));
     assert!(new_vault_provider
-        .accept_replace(&old_vault_id, 1u32.into(), vault_collateral, address)
+        .accept_replace(&old_vault_id, 1u32.into(), vault_collateral.planck(), address)
         .await
         .is_err());
 }
@@ -300,6 +317,7 @@ async fn test_cancellation_succeeds() {
         new_vault_provider.clone(),
         issue_cancellation_event_tx.clone(),
         issue_set.clone(),
+        TaskLimiter::new("test", 32),
     );
 
     let mut issue_cancellation_scheduler = vault::service::CancellationScheduler::new(
@@ -381,7 +399,11 @@ async fn test_cancellation_succeeds() {
 
                     // setup the to-be-cancelled issue
                     user_provider
-                        .request_issue(issue_amount, new_vault_provider.get_account_id(), 10000)
+                        .request_issue(
+                            WrappedAmount::from_planck(issue_amount),
+                            new_vault_provider.get_account_id(),
+                            CollateralAmount::from_planck(10000),
+                        )
                         .await
                         .unwrap();
                 },
@@ -422,10 +444,20 @@ async fn test_refund_succeeds() {
         .await
         .unwrap();
 
-    let refund_service = vault::service::listen_for_refund_requests(vault_provider.clone(), btc_rpc.clone(), 0);
+    let refund_service = vault::service::listen_for_refund_requests(
+        vault_provider.clone(),
+        btc_rpc.clone(),
+        0,
+        ProofSafety::default(),
+        PaymentApproval::default(),
+        TaskLimiter::new("test", 32),
+    );
 
     let issue_amount = 100000;
-    let vault_collateral = 2 * get_required_vault_collateral_for_issue(&vault_provider, issue_amount).await;
+    let vault_collateral = get_required_vault_collateral_for_issue(&vault_provider, issue_amount)
+        .await
+        .checked_mul(2)
+        .unwrap();
     vault_provider
         .register_vault(vault_collateral, btc_rpc.get_new_public_key().await.unwrap())
         .await
@@ -436,7 +468,11 @@ async fn test_refund_succeeds() {
         let over_payment = 100500;
 
         let issue = user_provider
-            .request_issue(issue_amount, vault_provider.get_account_id(), 10000)
+            .request_issue(
+                WrappedAmount::from_planck(issue_amount),
+                vault_provider.get_account_id(),
+                CollateralAmount::from_planck(10000),
+            )
             .await
             .unwrap();
 
@@ -483,7 +519,14 @@ async fn test_issue_overpayment_succeeds() {
         .await
         .unwrap();
 
-    let refund_service = vault::service::listen_for_refund_requests(vault_provider.clone(), btc_rpc.clone(), 0);
+    let refund_service = vault::service::listen_for_refund_requests(
+        vault_provider.clone(),
+        btc_rpc.clone(),
+        0,
+        ProofSafety::default(),
+        PaymentApproval::default(),
+        TaskLimiter::new("test", 32),
+    );
 
     let issue_amount = 100000;
     let over_payment_factor = 3;
@@ -497,7 +540,11 @@ async fn test_issue_overpayment_succeeds() {
     let _vault_id = vault_provider.get_account_id().clone();
     let fut_user = async {
         let issue = user_provider
-            .request_issue(issue_amount, vault_provider.get_account_id(), 10000)
+            .request_issue(
+                WrappedAmount::from_planck(issue_amount),
+                vault_provider.get_account_id(),
+                CollateralAmount::from_planck(10000),
+            )
             .await
             .unwrap();
 
@@ -561,7 +608,11 @@ async fn test_automatic_issue_execution_succeeds() {
 
     let fut_user = async {
         let issue = user_provider
-            .request_issue(issue_amount, vault1_provider.get_account_id(), 10000)
+            .request_issue(
+                WrappedAmount::from_planck(issue_amount),
+                vault1_provider.get_account_id(),
+                CollateralAmount::from_planck(10000),
+            )
             .await
             .unwrap();
 
@@ -586,8 +637,17 @@ async fn test_automatic_issue_execution_succeeds() {
             vault2_provider.clone(),
             issue_event_tx.clone(),
             issue_set.clone(),
+            TaskLimiter::new("test", 32),
+        ),
+        vault::service::process_issue_requests(
+            btc_rpc.clone(),
+            vault2_provider.clone(),
+            issue_set.clone(),
+            1,
+            0,
+            ProofSafety::default(),
+            TaskLimiter::new("test", 32),
         ),
-        vault::service::process_issue_requests(btc_rpc.clone(), vault2_provider.clone(), issue_set.clone(), 1, 0),
     );
 
     test_service(service, fut_user).await;
@@ -650,8 +710,16 @@ async fn test_execute_open_requests_succeeds() {
     btc_rpc.send_to_mempool(transaction).await;
 
     join3(
-        vault::service::execute_open_requests(vault_provider, btc_rpc.clone(), 0, Duration::from_secs(0))
-            .map(Result::unwrap),
+        vault::service::execute_open_requests(
+            vault_provider,
+            btc_rpc.clone(),
+            0,
+            Duration::from_secs(0),
+            ProofSafety::default(),
+            PaymentApproval::default(),
+            TaskLimiter::new("test", 32),
+        )
+        .map(Result::unwrap),
         assert_redeem_event(TIMEOUT, user_provider.clone(), redeem_ids[0]),
         assert_redeem_event(TIMEOUT, user_provider.clone(), redeem_ids[2]),
     )